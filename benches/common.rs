@@ -186,12 +186,12 @@ pub trait BenchReader {
 }
 
 pub struct TonboS3BenchDataBase {
-    db: tonbo::DB<Customer, TokioExecutor>,
+    db: tonbo::DB<Customer>,
 }
 
 impl TonboS3BenchDataBase {
     #[allow(dead_code)]
-    pub fn new(db: tonbo::DB<Customer, TokioExecutor>) -> Self {
+    pub fn new(db: tonbo::DB<Customer>) -> Self {
         TonboS3BenchDataBase { db }
     }
 }
@@ -277,12 +277,12 @@ impl BenchDatabase for TonboS3BenchDataBase {
 }
 
 pub struct TonboBenchDataBase {
-    db: tonbo::DB<Customer, TokioExecutor>,
+    db: tonbo::DB<Customer>,
 }
 
 impl TonboBenchDataBase {
     #[allow(dead_code)]
-    pub fn new(db: tonbo::DB<Customer, TokioExecutor>) -> Self {
+    pub fn new(db: tonbo::DB<Customer>) -> Self {
         TonboBenchDataBase { db }
     }
 }
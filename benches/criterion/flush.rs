@@ -0,0 +1,82 @@
+use std::{
+    iter::repeat_with,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use mimalloc::MiMalloc;
+use tonbo::{executor::tokio::TokioExecutor, DbOption, Record, DB};
+
+#[global_allocator]
+static GLOBAL: MiMalloc = MiMalloc;
+
+#[derive(Record, Debug)]
+pub struct KV {
+    #[record(primary_key)]
+    key: String,
+    value: String,
+}
+
+fn kvs(count: usize) -> Vec<KV> {
+    repeat_with(|| KV {
+        key: repeat_with(fastrand::alphanumeric).take(32).collect(),
+        value: repeat_with(fastrand::alphanumeric).take(256).collect(),
+    })
+    .take(count)
+    .collect()
+}
+
+/// the time to write the memtable holding `count` records into a level 0 parquet, the frozen
+/// memtable holds arrow arrays written as they are
+fn flush(c: &mut Criterion) {
+    let runtime = Arc::new(
+        tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(8)
+            .enable_all()
+            .build()
+            .unwrap(),
+    );
+
+    let mut group = c.benchmark_group("flush");
+
+    for count in [1_000, 10_000, 100_000] {
+        let _ = std::fs::remove_dir_all("/tmp/tonbo_flush");
+        let _ = std::fs::create_dir_all("/tmp/tonbo_flush");
+
+        // every flush writes the memtable
+        let option =
+            DbOption::from(fusio::path::Path::from_filesystem_path("/tmp/tonbo_flush").unwrap())
+                .disable_wal()
+                .immutable_chunk_num(1)
+                .immutable_chunk_max_num(0)
+                .major_threshold_with_sst_size(usize::MAX);
+        let db = runtime
+            .block_on(async { DB::<KV>::new(option, TokioExecutor::default()).await })
+            .unwrap();
+
+        group.throughput(Throughput::Elements(count as u64));
+        group.bench_with_input(BenchmarkId::new("Tonbo", count), &count, |b, count| {
+            b.to_async(&*runtime).iter_custom(|iters| {
+                let db = &db;
+                async move {
+                    let mut elapsed = Duration::ZERO;
+                    for _ in 0..iters {
+                        db.insert_batch(kvs(*count).into_iter()).await.unwrap();
+
+                        let start = Instant::now();
+                        db.flush().await.unwrap();
+                        elapsed += start.elapsed();
+                    }
+                    elapsed
+                }
+            });
+        });
+    }
+    let _ = std::fs::remove_dir_all("/tmp/tonbo_flush");
+
+    group.finish();
+}
+
+criterion_group!(benches, flush);
+criterion_main!(benches);
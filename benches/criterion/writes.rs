@@ -15,7 +15,7 @@ pub struct KV {
 }
 
 #[inline(never)]
-async fn tonbo_write(db: &DB<KV, TokioExecutor>, batch_size: usize) {
+async fn tonbo_write(db: &DB<KV>, batch_size: usize) {
     let mut kvs = Vec::with_capacity(128);
     for _ in 0..batch_size {
         let key = repeat_with(fastrand::alphanumeric).take(256).collect();
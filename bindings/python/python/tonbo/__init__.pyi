@@ -1,3 +1,4 @@
+import pyarrow
 from typing import Any, AsyncIterable, final
 from enum import Enum, auto
 from tonbo import error as error
@@ -118,6 +119,25 @@ class Transaction:
             projection: fields to projection
         """
         ...
+    async def scan_arrow(
+        self,
+        lower: Bound | None,
+        high: Bound | None,
+        limit: int | None = None,
+        projection: list[str] = ["*"],
+        batch_size: int = 8192,
+    ) -> AsyncIterable[pyarrow.RecordBatch]:
+        """Create an async stream of :py:class:`pyarrow.RecordBatch` for scanning.
+        Requires `pyarrow`.
+
+        Args:
+            lower: Lower bound of range. Use None represent unbounded.
+            high: High bound of range. Use None represent unbounded.
+            limit: max number records to scan
+            projection: fields to projection
+            batch_size: max number of records in a batch
+        """
+        ...
     async def commit(self) -> None:
         """Commit :py:class:`Transaction`."""
         ...
@@ -22,13 +22,11 @@ use crate::{
     utils::{to_col, to_dict},
 };
 
-type PyExecutor = TokioExecutor;
-
 #[pyclass]
 pub struct TonboDB {
     desc: Arc<Vec<Column>>,
     primary_key_index: usize,
-    db: Arc<DB<DynRecord, PyExecutor>>,
+    db: Arc<DB<DynRecord>>,
 }
 
 #[pymethods]
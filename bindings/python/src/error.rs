@@ -51,6 +51,15 @@ impl From<DbError> for PyErr {
             tonbo::DbError::Recover(err) => RecoverError::new_err(err.to_string()),
             tonbo::DbError::WalWrite(err) => PyIOError::new_err(err.to_string()),
             tonbo::DbError::ExceedsMaxLevel => ExceedsMaxLevelError::new_err("Exceeds max level"),
+            err @ tonbo::DbError::InvalidOption(_) => PyValueError::new_err(err.to_string()),
+            tonbo::DbError::Ingest(err) => InnerError::new_err(err),
+            tonbo::DbError::Export(err) => InnerError::new_err(err.to_string()),
+            tonbo::DbError::Arrow(err) => InnerError::new_err(err.to_string()),
+            tonbo::DbError::ReadOnly => PyIOError::new_err("the database is read-only"),
+            err @ tonbo::DbError::Locked(_) => PyIOError::new_err(err.to_string()),
+            err @ tonbo::DbError::TimestampNotRetained { .. } => {
+                PyValueError::new_err(err.to_string())
+            }
         }
     }
 }
@@ -64,6 +73,20 @@ impl From<CommitError> for PyErr {
             tonbo::transaction::CommitError::WriteConflict(key) => {
                 WriteConflictError::new_err(key.name)
             }
+            tonbo::transaction::CommitError::ConditionFailed { key, .. } => {
+                WriteConflictError::new_err(key.name)
+            }
+            err @ tonbo::transaction::CommitError::StaleTimestamp { .. } => {
+                PyValueError::new_err(err.to_string())
+            }
+            err @ tonbo::transaction::CommitError::ReadConflict(..) => {
+                WriteConflictError::new_err(err.to_string())
+            }
+            err @ (tonbo::transaction::CommitError::Deadlock(_)
+            | tonbo::transaction::CommitError::LockTimeout(_)) => {
+                WriteConflictError::new_err(err.to_string())
+            }
+            tonbo::transaction::CommitError::Serde(err) => DecodeError::new_err(err.to_string()),
             tonbo::transaction::CommitError::SendCompactTaskError(err) => {
                 InnerError::new_err(err.to_string())
             }
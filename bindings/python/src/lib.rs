@@ -55,6 +55,11 @@ use crate::error::{DecodeError, WriteConflictError};
 ///     async for record in scan:
 ///         print(record)
 ///
+///     # or scan into `pyarrow.RecordBatch`
+///     batches = await txn.scan_arrow(None, None, projection=["id", "name"])
+///     async for batch in batches:
+///         print(batch)
+///
 /// asyncio.run(main())
 /// ````
 #[pymodule]
@@ -66,6 +71,7 @@ fn _tonbo(py: Python, m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<Record>()?;
     m.add_class::<Transaction>()?;
     m.add_class::<ScanStream>()?;
+    m.add_class::<ArrowStream>()?;
     m.add_class::<range::Bound>()?;
     m.add_class::<RecordBatch>()?;
 
@@ -2,14 +2,20 @@ use std::{pin::Pin, sync::Arc};
 
 use futures::{Stream, TryStreamExt};
 use pyo3::{
-    exceptions::PyStopAsyncIteration, prelude::*, pyclass, pymethods, IntoPy, PyRef, PyRefMut,
-    PyResult, Python,
+    exceptions::PyStopAsyncIteration, prelude::*, pyclass, pymethods, types::PyBytes, IntoPy,
+    PyRef, PyRefMut, PyResult, Python,
 };
 use pyo3_asyncio::tokio::future_into_py;
 use tokio::sync::Mutex;
-use tonbo::{parquet::errors::ParquetError, record::DynRecord, stream};
+use tonbo::{
+    arrow::{array::RecordBatch, ipc::writer::StreamWriter},
+    inmem::immutable::ArrowArrays,
+    parquet::errors::ParquetError,
+    record::{DynRecord, Record},
+    stream,
+};
 
-use crate::utils::to_dict;
+use crate::{error::InnerError, utils::to_dict};
 
 type AsyncStream =
     Pin<Box<dyn Stream<Item = Result<stream::Entry<'static, DynRecord>, ParquetError>> + Send>>;
@@ -50,3 +56,78 @@ impl ScanStream {
         Ok(Some(fut.into()))
     }
 }
+
+type AsyncBatchStream =
+    Pin<Box<dyn Stream<Item = Result<<DynRecord as Record>::Columns, ParquetError>> + Send>>;
+
+/// Async stream of `pyarrow.RecordBatch` holding the projected columns of the scanned records.
+#[pyclass]
+pub struct ArrowStream {
+    stream: Arc<Mutex<AsyncBatchStream>>,
+    /// indices of the projected columns in the scanned batches
+    indices: Arc<Vec<usize>>,
+}
+
+impl ArrowStream {
+    pub fn new(
+        stream: impl Stream<Item = Result<<DynRecord as Record>::Columns, ParquetError>>
+            + 'static
+            + Sized
+            + Send,
+        projection: Vec<usize>,
+    ) -> Self {
+        Self {
+            stream: Arc::new(Mutex::new(Box::pin(stream))),
+            // skip two columns: _null and _ts
+            indices: Arc::new(projection.into_iter().map(|i| i + 2).collect()),
+        }
+    }
+}
+
+#[pymethods]
+impl ArrowStream {
+    fn __aiter__(slf: PyRef<Self>) -> PyRef<Self> {
+        slf
+    }
+
+    fn __anext__(slf: PyRefMut<Self>, py: Python<'_>) -> PyResult<Option<PyObject>> {
+        let stream: Arc<Mutex<AsyncBatchStream>> = Arc::clone(&slf.stream);
+        let indices = Arc::clone(&slf.indices);
+        let fut = future_into_py(py, async move {
+            let mut locked_stream = stream.lock().await;
+            let columns = locked_stream
+                .try_next()
+                .await
+                .map_err(|err| InnerError::new_err(err.to_string()))?;
+            match columns {
+                Some(columns) => {
+                    let batch = columns
+                        .as_record_batch()
+                        .project(&indices)
+                        .map_err(|err| InnerError::new_err(err.to_string()))?;
+                    Python::with_gil(|py| to_pyarrow(py, &batch))
+                }
+                None => Err(PyStopAsyncIteration::new_err("stream exhausted")),
+            }
+        })?;
+        Ok(Some(fut.into()))
+    }
+}
+
+/// hand the `batch` to `pyarrow` through the Arrow IPC stream format
+fn to_pyarrow(py: Python<'_>, batch: &RecordBatch) -> PyResult<PyObject> {
+    let mut bytes = Vec::new();
+    {
+        let mut writer = StreamWriter::try_new(&mut bytes, batch.schema_ref())
+            .map_err(|err| InnerError::new_err(err.to_string()))?;
+        writer
+            .write(batch)
+            .and_then(|_| writer.finish())
+            .map_err(|err| InnerError::new_err(err.to_string()))?;
+    }
+    let reader = py
+        .import_bound("pyarrow.ipc")?
+        .call_method1("open_stream", (PyBytes::new_bound(py, &bytes),))?;
+
+    Ok(reader.call_method0("read_next_batch")?.unbind())
+}
@@ -12,7 +12,7 @@ use crate::{
     column::Column,
     error::{repeated_commit_err, CommitError, DbError},
     range,
-    stream::ScanStream,
+    stream::{ArrowStream, ScanStream},
     utils::{to_bound, to_col, to_dict},
 };
 
@@ -206,6 +206,66 @@ impl Transaction {
         })
     }
 
+    /// Create an async stream of `pyarrow.RecordBatch` for scanning.
+    ///
+    /// * `lower`: - Lower bound of range. Use None represent unbounded.
+    /// * `high`: - High bound of range. Use None represent unbounded.
+    /// * `limit`: - Max number records to scan.
+    /// * `projection`: - Fields to projection in the batches. Projection all by default.
+    /// * `batch_size`: - Max number of records in a batch.
+    #[pyo3(signature= (lower, high, limit=None, projection=vec!["*".to_string()], batch_size=8192))]
+    fn scan_arrow<'py>(
+        &'py mut self,
+        py: Python<'py>,
+        lower: Option<Py<range::Bound>>,
+        high: Option<Py<range::Bound>>,
+        limit: Option<usize>,
+        projection: Vec<String>,
+        batch_size: usize,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        if self.txn.is_none() {
+            return Err(repeated_commit_err());
+        }
+        let txn = self.txn.as_ref().unwrap();
+        let txn = unsafe {
+            transmute::<
+                &transaction::Transaction<'_, DynRecord>,
+                &'static transaction::Transaction<'_, DynRecord>,
+            >(txn)
+        };
+        let col_desc = self.desc.get(self.primary_key_index).unwrap();
+        let projection = self.projection(projection);
+
+        let (lower, high) = to_bound(py, col_desc, lower, high);
+
+        future_into_py(py, async move {
+            let mut scan = txn.scan((
+                unsafe {
+                    transmute::<
+                        std::ops::Bound<&tonbo::record::Column>,
+                        std::ops::Bound<&'static tonbo::record::Column>,
+                    >(lower.as_ref())
+                },
+                unsafe {
+                    transmute::<
+                        std::ops::Bound<&tonbo::record::Column>,
+                        std::ops::Bound<&'static tonbo::record::Column>,
+                    >(high.as_ref())
+                },
+            ));
+
+            if let Some(limit) = limit {
+                scan = scan.limit(limit);
+            }
+            scan = scan.projection(projection.clone());
+            let stream = scan.package(batch_size).await.map_err(DbError::from)?;
+
+            let stream = ArrowStream::new(stream, projection);
+
+            Ok(Python::with_gil(|py| stream.into_py(py)))
+        })
+    }
+
     /// Commit `Transaction`
     fn commit<'py>(&'py mut self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
         if self.txn.is_none() {
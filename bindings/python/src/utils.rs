@@ -136,7 +136,7 @@ pub(crate) fn to_dict(
                     dict.set_item(col.name.clone(), value).unwrap();
                 }
             }
-            Datatype::Bytes => {
+            Datatype::Bytes | Datatype::LargeBytes | Datatype::FixedSizeBytes(_) => {
                 if idx == primary_key_index {
                     let value = col.value.as_ref().downcast_ref::<Vec<u8>>().unwrap();
                     let v = PyBytes::new_bound(py, value);
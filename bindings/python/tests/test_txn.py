@@ -101,6 +101,28 @@ async def test_txn_limit():
     assert i == 10
 
 
+@pytest.mark.asyncio
+async def test_txn_scan_arrow():
+    db = build_db()
+    txn = await db.transaction()
+    for i in range(0, 100):
+        txn.insert(User(age=i, height=i * 10, weight=i * 20))
+    await txn.commit()
+
+    txn = await db.transaction()
+    batches = await txn.scan_arrow(
+        Bound.Included(10), Bound.Excluded(75), projection=["age", "weight"], batch_size=16
+    )
+    ages, weights = [], []
+    async for batch in batches:
+        assert batch.schema.names == ["age", "weight"]
+        assert batch.num_rows <= 16
+        ages.extend(batch.column("age").to_pylist())
+        weights.extend(batch.column("weight").to_pylist())
+    assert ages == list(range(10, 75))
+    assert weights == [i * 20 for i in range(10, 75)]
+
+
 @pytest.mark.asyncio
 async def test_repeated_commit():
     db = build_db()
@@ -36,6 +36,8 @@ impl AsyncFileReader for BoxedFileReader {
 
 pub trait DynLruCache<K> {
     fn get_reader(&self, key: K, reader: BoxedFileReader) -> BoxFuture<'_, BoxedFileReader>;
+
+    fn memory_usage(&self) -> usize;
 }
 
 impl<K, C> DynLruCache<K> for C
@@ -46,4 +48,8 @@ where
     fn get_reader(&self, key: K, reader: BoxedFileReader) -> BoxFuture<'_, BoxedFileReader> {
         Box::pin(async move { BoxedFileReader::new(self.get_reader(key, reader).await) })
     }
+
+    fn memory_usage(&self) -> usize {
+        LruCache::memory_usage(self)
+    }
 }
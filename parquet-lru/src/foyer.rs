@@ -1,6 +1,7 @@
-use std::{hash::Hash, ops::Range, sync::Arc};
+use std::{hash::Hash, ops::Range, path::Path, sync::Arc};
 
 use bytes::Bytes;
+use foyer::{CacheBuilder, DirectFsDeviceOptions, Engine, HybridCacheBuilder};
 use futures_core::future::BoxFuture;
 use futures_util::FutureExt;
 use parquet::{
@@ -28,6 +29,43 @@ where
     data: foyer::HybridCache<(K, Range<usize>), Bytes>,
 }
 
+impl<K> FoyerCache<K>
+where
+    for<'a> K: Send + Sync + Hash + Eq + Serialize + Deserialize<'a> + Clone + 'static,
+{
+    /// cache the metadata of files in `meta`, and the byte ranges read from them in `data`,
+    /// whose disk tier keeps the ranges of remote files evicted from memory on local disk
+    pub fn new(
+        meta: foyer::Cache<K, Arc<ParquetMetaData>>,
+        data: foyer::HybridCache<(K, Range<usize>), Bytes>,
+    ) -> Self {
+        Self {
+            inner: Arc::new(FoyerCacheInner { meta, data }),
+        }
+    }
+
+    /// a cache of the metadata of `meta_capacity` files, and of the byte ranges read in
+    /// `memory_capacity` bytes of memory, spilling to `disk_capacity` bytes of files in `dir`
+    pub async fn open(
+        meta_capacity: usize,
+        memory_capacity: usize,
+        dir: impl AsRef<Path>,
+        disk_capacity: usize,
+    ) -> Result<Self> {
+        let meta = CacheBuilder::new(meta_capacity).build();
+        let data = HybridCacheBuilder::new()
+            .memory(memory_capacity)
+            .with_weighter(|_, data: &Bytes| data.len())
+            .storage(Engine::Large)
+            .with_device_options(DirectFsDeviceOptions::new(dir).with_capacity(disk_capacity))
+            .build()
+            .await
+            .map_err(|e| ParquetError::External(e.into()))?;
+
+        Ok(Self::new(meta, data))
+    }
+}
+
 impl<K> LruCache<K> for FoyerCache<K>
 where
     for<'a> K: Send + Sync + Hash + Eq + Serialize + Deserialize<'a> + Clone + 'static,
@@ -43,6 +81,10 @@ where
     {
         FoyerReader::new(self.clone(), key, reader)
     }
+
+    fn memory_usage(&self) -> usize {
+        self.inner.meta.usage() + self.inner.data.memory().usage()
+    }
 }
 
 pub struct FoyerReader<K, R>
@@ -19,6 +19,11 @@ where
     fn get_reader<R>(&self, key: K, reader: R) -> impl Future<Output = Self::LruReader<R>> + Send
     where
         R: AsyncFileReader + 'static;
+
+    /// memory held by the cached metadata and pages, as weighed by the cache
+    fn memory_usage(&self) -> usize {
+        0
+    }
 }
 
 #[derive(Default)]
@@ -0,0 +1,601 @@
+use std::{
+    ops::{Bound, RangeBounds},
+    sync::Arc,
+};
+
+use arrow::{
+    array::{Array, ArrayRef, AsArray},
+    compute::{concat, sort_to_indices, SortOptions},
+    datatypes::UInt64Type,
+    error::ArrowError,
+};
+use futures_util::StreamExt;
+use parquet::{
+    arrow::{arrow_reader::statistics::StatisticsConverter, ProjectionMask},
+    errors::Result as ParquetResult,
+    file::metadata::ParquetMetaData,
+};
+
+use crate::{
+    compaction::HISTORY_KEY,
+    inmem::immutable::ArrowArrays,
+    range_tombstone::RangeTombstone,
+    record::{KeyRef, Record},
+    scope::Scope,
+    timestamp::Timestamp,
+    DbError, Projection, Scan, DB,
+};
+
+/// an SST whose rows are exactly the records the scan sees in its scope, so its footer answers
+/// for them
+struct Summarized<'scan, K> {
+    scope: &'scan Scope<K>,
+    metadata: Arc<ParquetMetaData>,
+}
+
+impl<'scan, 'range, R> Scan<'scan, 'range, R>
+where
+    R: Record + Send,
+{
+    /// count the live records of the scan. The SSTs answered by their footers are counted from
+    /// them and the rest of the range by a scan of the primary keys only.
+    ///
+    /// An SST is answered by its footer when it is below level 0, lies within the range, overlaps
+    /// no memtable, other SST or range tombstone, and its statistics show neither tombstones nor
+    /// versions newer than the scan. Scans of a transaction or with a limit are always evaluated
+    /// in full.
+    pub async fn count(self) -> Result<usize, DbError<R>> {
+        let tables = self.summarize(None).await?;
+        if tables.is_empty() {
+            return count_live(self).await;
+        }
+
+        let mut count = tables
+            .iter()
+            .map(|table| table.metadata.file_metadata().num_rows() as usize)
+            .sum::<usize>();
+        for range in gaps((self.lower, self.upper), &tables) {
+            count += count_live(self.sub_scan(range)).await?;
+        }
+        Ok(count)
+    }
+
+    /// the smallest non-null value of the field `column` in the live records of the scan, as an
+    /// array of one element. The SSTs answered by their footers, as in [`Scan::count`], give
+    /// theirs from the statistics of their row groups and the rest of the range is read with
+    /// only the primary key and the field.
+    pub async fn min(self, column: usize) -> Result<Option<ArrayRef>, DbError<R>> {
+        self.extreme(column, false).await
+    }
+
+    /// the largest non-null value of the field `column` in the live records of the scan, see
+    /// [`Scan::min`]
+    pub async fn max(self, column: usize) -> Result<Option<ArrayRef>, DbError<R>> {
+        self.extreme(column, true).await
+    }
+
+    async fn extreme(self, column: usize, max: bool) -> Result<Option<ArrayRef>, DbError<R>> {
+        let arrow_schema = self.schema.record_instance.arrow_schema::<R>();
+        // skip two columns: _null and _ts
+        let name = arrow_schema.field(column + 2).name().clone();
+
+        let tables = self.summarize(Some(&name)).await?;
+        if tables.is_empty() {
+            return extreme_live(self, column, &name, max).await;
+        }
+
+        let mut candidates = Vec::new();
+        for table in tables.iter() {
+            let converter = StatisticsConverter::try_new(
+                &name,
+                &arrow_schema,
+                table.metadata.file_metadata().schema_descr(),
+            )?;
+            let row_groups = table.metadata.row_groups();
+            let values = if max {
+                converter.row_group_maxes(row_groups)?
+            } else {
+                converter.row_group_mins(row_groups)?
+            };
+            candidates.extend(extreme_of(&values, max)?);
+        }
+        for range in gaps((self.lower, self.upper), &tables) {
+            candidates.extend(extreme_live(self.sub_scan(range), column, &name, max).await?);
+        }
+        extreme_of_all(candidates, max)
+    }
+
+    /// the SSTs answered by their footers, when `column` is given only the ones with the
+    /// statistics of all its row groups
+    async fn summarize(
+        &self,
+        column: Option<&str>,
+    ) -> Result<Vec<Summarized<'scan, R::Key>>, DbError<R>> {
+        let mut tables = Vec::new();
+        if self.fn_pre_stream.is_some() || self.limit.is_some() {
+            return Ok(tables);
+        }
+        let range_tombstones = self.schema.range_tombstones(self.version);
+
+        // level 0 SSTs are flushed memtables, which may hold several versions of a key
+        for (level, scopes) in self.version.level_slice.iter().enumerate().skip(1) {
+            for scope in scopes {
+                let range = (self.lower, self.upper);
+                if !range.contains(&scope.min)
+                    || !range.contains(&scope.max)
+                    || self.overlaps(scope, &range_tombstones)
+                {
+                    continue;
+                }
+                let metadata = self
+                    .version
+                    .table_metadata(self.manager, level, scope.gen, self.parquet_lru.clone())
+                    .await?;
+                if self.is_exact(&metadata, column)? {
+                    tables.push(Summarized { scope, metadata });
+                }
+            }
+        }
+        tables.sort_by(|a, b| a.scope.min.cmp(&b.scope.min));
+        Ok(tables)
+    }
+
+    /// whether anything other than the SST of `scope` may hold records in it
+    fn overlaps(&self, scope: &Scope<R::Key>, range_tombstones: &[RangeTombstone<R::Key>]) -> bool {
+        let overlaps = |min: &R::Key, max: &R::Key| min <= &scope.max && &scope.min <= max;
+
+        self.version
+            .level_slice
+            .iter()
+            .flatten()
+            .any(|other| other.gen != scope.gen && overlaps(&other.min, &other.max))
+            || self.schema.immutables.iter().any(|(_, immutable)| {
+                matches!(immutable.scope(), (Some(min), Some(max)) if overlaps(min, max))
+            })
+            || self
+                .schema
+                .mutable
+                .scan(
+                    (Bound::Included(&scope.min), Bound::Included(&scope.max)),
+                    u64::MAX.into(),
+                )
+                .next()
+                .is_some()
+            || range_tombstones.iter().any(|range_tombstone| {
+                scope.meets_range((
+                    range_tombstone.lower.as_ref(),
+                    range_tombstone.upper.as_ref(),
+                ))
+            })
+    }
+
+    /// whether every row of the SST is a live record visible to the scan, and with `column`
+    /// whether every row group has its statistics
+    fn is_exact(&self, metadata: &ParquetMetaData, column: Option<&str>) -> ParquetResult<bool> {
+        // the superseded versions kept for the GC watermark are rows as well
+        if metadata
+            .file_metadata()
+            .key_value_metadata()
+            .is_some_and(|metadata| metadata.iter().any(|kv| kv.key == HISTORY_KEY))
+        {
+            return Ok(false);
+        }
+        let arrow_schema = self.schema.record_instance.arrow_schema::<R>();
+        let parquet_schema = metadata.file_metadata().schema_descr();
+        let row_groups = metadata.row_groups();
+
+        let nulls = StatisticsConverter::try_new("_null", &arrow_schema, parquet_schema)?
+            .row_group_maxes(row_groups)?;
+        if nulls.null_count() > 0 || nulls.as_boolean().true_count() > 0 {
+            return Ok(false);
+        }
+        let ts = StatisticsConverter::try_new("_ts", &arrow_schema, parquet_schema)?
+            .row_group_maxes(row_groups)?;
+        if ts.null_count() > 0
+            || ts
+                .as_primitive::<UInt64Type>()
+                .values()
+                .iter()
+                .any(|ts| Timestamp::from(*ts) > self.ts)
+        {
+            return Ok(false);
+        }
+
+        if let Some(column) = column {
+            let converter = StatisticsConverter::try_new(column, &arrow_schema, parquet_schema)?;
+            let mins = converter.row_group_mins(row_groups)?;
+            let maxes = converter.row_group_maxes(row_groups)?;
+            let null_counts = converter.row_group_null_counts(row_groups)?;
+            for (i, row_group) in row_groups.iter().enumerate() {
+                // the statistics of a row group of nulls only have no bounds
+                let all_null =
+                    null_counts.is_valid(i) && null_counts.value(i) == row_group.num_rows() as u64;
+                if (mins.is_null(i) || maxes.is_null(i)) && !all_null {
+                    return Ok(false);
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    fn sub_scan(
+        &self,
+        range: (Bound<&'scan R::Key>, Bound<&'scan R::Key>),
+    ) -> Scan<'scan, 'scan, R> {
+        Scan::new(
+            self.schema,
+            self.manager,
+            range,
+            self.ts,
+            self.version,
+            None,
+            self.parquet_lru.clone(),
+        )
+    }
+}
+
+/// the parts of the range between the summarized SSTs, sorted by their smallest keys
+fn gaps<'scan, K>(
+    (lower, upper): (Bound<&'scan K>, Bound<&'scan K>),
+    tables: &[Summarized<'scan, K>],
+) -> Vec<(Bound<&'scan K>, Bound<&'scan K>)> {
+    let mut gaps = Vec::with_capacity(tables.len() + 1);
+    let mut start = lower;
+    for table in tables {
+        gaps.push((start, Bound::Excluded(&table.scope.min)));
+        start = Bound::Excluded(&table.scope.max);
+    }
+    gaps.push((start, upper));
+    gaps
+}
+
+async fn count_live<R>(scan: Scan<'_, '_, R>) -> Result<usize, DbError<R>>
+where
+    R: Record + Send,
+{
+    let mut stream = scan.projection(vec![]).take().await?;
+    let mut count = 0;
+    while let Some(entry) = stream.next().await.transpose()? {
+        if entry.value().is_some() {
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+async fn extreme_live<R>(
+    scan: Scan<'_, '_, R>,
+    column: usize,
+    name: &str,
+    max: bool,
+) -> Result<Option<ArrayRef>, DbError<R>>
+where
+    R: Record + Send,
+{
+    let mut batches = scan.projection(vec![column]).package(8192).await?;
+    let mut candidates = Vec::new();
+    while let Some(columns) = batches.next().await.transpose()? {
+        if let Some(values) = columns.as_record_batch().column_by_name(name) {
+            candidates.extend(extreme_of(values, max)?);
+        }
+    }
+    extreme_of_all(candidates, max)
+}
+
+fn extreme_of_all<R>(candidates: Vec<ArrayRef>, max: bool) -> Result<Option<ArrayRef>, DbError<R>>
+where
+    R: Record,
+{
+    if candidates.is_empty() {
+        return Ok(None);
+    }
+    let candidates = candidates.iter().map(AsRef::as_ref).collect::<Vec<_>>();
+    Ok(extreme_of(&concat(&candidates)?, max)?)
+}
+
+/// the smallest or largest non-null value of the array
+fn extreme_of(values: &dyn Array, max: bool) -> Result<Option<ArrayRef>, ArrowError> {
+    let indices = sort_to_indices(
+        values,
+        Some(SortOptions {
+            descending: max,
+            nulls_first: false,
+        }),
+        Some(1),
+    )?;
+    Ok(indices
+        .values()
+        .first()
+        .map(|index| *index as usize)
+        .filter(|index| values.is_valid(*index))
+        .map(|index| values.slice(index, 1)))
+}
+
+impl<R> DB<R>
+where
+    R: Record + Send + Sync,
+    R::Columns: Send + Sync,
+{
+    /// estimate the number of records, see [`DB::approximate_count`]
+    pub async fn approximate_len(&self) -> Result<usize, DbError<R>> {
+        self.approximate_count((Bound::Unbounded, Bound::Unbounded))
+            .await
+    }
+
+    /// estimate the number of records with primary keys in the `range` from the row counts of
+    /// the SSTs overlapping it recorded in the version and the entries of the memtables, without
+    /// reading any record. Overwritten versions and tombstones are counted and SSTs only partly
+    /// in the range are counted whole, so it is an upper bound of [`DB::count`].
+    pub async fn approximate_count(
+        &self,
+        range: (Bound<&R::Key>, Bound<&R::Key>),
+    ) -> Result<usize, DbError<R>> {
+        let version = self.version_set.current().await;
+        let mut count = version
+            .approximate_rows(&self.manager, range, self.parquet_lru.clone())
+            .await?;
+
+        let schema = self.schema.read().await;
+        count += schema.mutable.scan(range, u64::MAX.into()).count();
+        for (_, immutable) in schema.immutables.iter() {
+            count += immutable
+                .scan(range, u64::MAX.into(), ProjectionMask::all())
+                .count();
+        }
+        Ok(count)
+    }
+
+    /// count the records with primary keys in the `range` exactly, with a merged scan reading
+    /// only the primary keys
+    pub async fn count(
+        &self,
+        range: (Bound<&R::Key>, Bound<&R::Key>),
+    ) -> Result<usize, DbError<R>> {
+        let snapshot = self.snapshot().await;
+        let mut scan = snapshot.scan(range).projection(vec![]).take().await?;
+        let mut count = 0;
+        while let Some(entry) = scan.next().await.transpose()? {
+            if entry.value().is_some() {
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    /// the smallest primary key of the live records. The scans of every component are merged
+    /// lazily, so only their first blocks are read unless the smallest keys were removed.
+    pub async fn first(&self) -> Result<Option<R::Key>, DbError<R>> {
+        let snapshot = self.snapshot().await;
+        let mut scan = snapshot
+            .scan((Bound::Unbounded, Bound::Unbounded))
+            .projection(vec![])
+            .take()
+            .await?;
+        while let Some(entry) = scan.next().await.transpose()? {
+            if entry.value().is_some() {
+                return Ok(Some(entry.key().value.to_key()));
+            }
+        }
+        Ok(None)
+    }
+
+    /// the largest primary key of the live records. The largest key of every memtable and SST is
+    /// known without reading it, so only the largest of them is looked up, falling back to a scan
+    /// of the keys below it if that record was removed.
+    pub async fn last(&self) -> Result<Option<R::Key>, DbError<R>> {
+        let snapshot = self.snapshot().await;
+        let schema = snapshot.schema();
+        let version = snapshot.version();
+
+        let mut candidates = Vec::new();
+        candidates.extend(
+            schema
+                .mutable
+                .data
+                .back()
+                .map(|entry| entry.key().value.clone()),
+        );
+        for (_, immutable) in schema.immutables.iter() {
+            candidates.extend(immutable.scope().1.cloned());
+        }
+        candidates.extend(version.level_slice[0].iter().map(|scope| scope.max.clone()));
+        for scopes in version.level_slice[1..].iter() {
+            candidates.extend(scopes.last().map(|scope| scope.max.clone()));
+        }
+        let Some(candidate) = candidates.into_iter().max() else {
+            return Ok(None);
+        };
+        if snapshot
+            .get(&candidate, Projection::Parts(vec![]))
+            .await?
+            .is_some()
+        {
+            return Ok(Some(candidate));
+        }
+
+        let mut scan = snapshot
+            .scan((Bound::Unbounded, Bound::Excluded(&candidate)))
+            .projection(vec![])
+            .take()
+            .await?;
+        let mut last = None;
+        while let Some(entry) = scan.next().await.transpose()? {
+            if entry.value().is_some() {
+                last = Some(entry.key().value.to_key());
+            }
+        }
+        Ok(last)
+    }
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod tests {
+    use std::ops::{Bound, RangeBounds};
+
+    use arrow::{
+        array::AsArray,
+        datatypes::{DataType, UInt32Type},
+    };
+    use fusio::path::Path;
+    use tempfile::TempDir;
+
+    use crate::{
+        executor::tokio::TokioExecutor,
+        tests::{test_items, Test},
+        DbOption, DB,
+    };
+
+    async fn aggregate(
+        db: &DB<Test>,
+        range: (Bound<&String>, Bound<&String>),
+    ) -> (usize, u32, u32) {
+        let snapshot = db.snapshot().await;
+        let count = snapshot.scan(range).count().await.unwrap();
+        let min = snapshot.scan(range).min(1).await.unwrap().unwrap();
+        let max = snapshot.scan(range).max(1).await.unwrap().unwrap();
+        assert_eq!(min.data_type(), &DataType::UInt32);
+        (
+            count,
+            min.as_primitive::<UInt32Type>().value(0),
+            max.as_primitive::<UInt32Type>().value(0),
+        )
+    }
+
+    fn expected(items: &[Test], range: (Bound<&String>, Bound<&String>)) -> (usize, u32, u32) {
+        let values = items
+            .iter()
+            .filter(|item| range.contains(&item.vstring))
+            .map(|item| item.vu32)
+            .collect::<Vec<_>>();
+        (
+            values.len(),
+            *values.iter().min().unwrap(),
+            *values.iter().max().unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn count_min_max() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap());
+        option.immutable_chunk_num = 1;
+        option.immutable_chunk_max_num = 0;
+        option.major_threshold_with_sst_size = 1;
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+
+        let mut items = test_items();
+        for item in items.iter().cloned() {
+            db.insert(item).await.unwrap();
+        }
+        db.flush().await.unwrap();
+        let item = Test {
+            vstring: "zz".to_string(),
+            vu32: 100,
+            vbool: None,
+        };
+        db.insert(item.clone()).await.unwrap();
+        items.push(item);
+        db.flush().await.unwrap();
+
+        let all = (Bound::Unbounded, Bound::Unbounded);
+        let (lower, upper) = ("1".to_string(), "3".to_string());
+        let part = (Bound::Included(&lower), Bound::Excluded(&upper));
+        // the records were compacted into level 1, where the footers answer for them
+        {
+            let snapshot = db.snapshot().await;
+            assert!(!snapshot
+                .scan(all)
+                .summarize(Some("vu32"))
+                .await
+                .unwrap()
+                .is_empty());
+            assert!(snapshot
+                .scan(part)
+                .summarize(None)
+                .await
+                .unwrap()
+                .is_empty());
+        }
+        assert_eq!(aggregate(&db, all).await, expected(&items, all));
+        assert_eq!(aggregate(&db, part).await, expected(&items, part));
+
+        // tombstones and overwrites in the memtable are merged with the records of the SSTs
+        db.remove(items[0].vstring.clone()).await.unwrap();
+        items.remove(0);
+        items[0].vu32 = 1000;
+        db.insert(items[0].clone()).await.unwrap();
+        assert_eq!(aggregate(&db, all).await, expected(&items, all));
+        assert_eq!(aggregate(&db, part).await, expected(&items, part));
+
+        // the local writes of a transaction are counted too
+        let mut txn = db.transaction().await;
+        txn.remove(items[1].vstring.clone());
+        assert_eq!(txn.scan(all).count().await.unwrap(), items.len() - 1);
+    }
+
+    #[tokio::test]
+    async fn count() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap());
+        option.immutable_chunk_num = 1;
+        option.immutable_chunk_max_num = 0;
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+
+        for item in test_items() {
+            db.insert(item).await.unwrap();
+        }
+        db.flush().await.unwrap();
+        for item in &test_items()[0..10] {
+            db.insert(item.clone()).await.unwrap();
+        }
+        db.remove("25".to_string()).await.unwrap();
+
+        // 40 rows in the SST, 10 overwrites and a tombstone in the memtable
+        assert_eq!(db.approximate_len().await.unwrap(), 51);
+        assert_eq!(
+            db.count((Bound::Unbounded, Bound::Unbounded))
+                .await
+                .unwrap(),
+            39
+        );
+        // "1" and "10" to "19"
+        let (lower, upper) = ("1".to_string(), "2".to_string());
+        let range = (Bound::Included(&lower), Bound::Excluded(&upper));
+        assert_eq!(db.count(range).await.unwrap(), 11);
+        // the SST overlapping the range is counted whole
+        assert_eq!(db.approximate_count(range).await.unwrap(), 40 + 1);
+    }
+
+    #[tokio::test]
+    async fn first_last() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap());
+        option.immutable_chunk_num = 1;
+        option.immutable_chunk_max_num = 0;
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+        assert_eq!(db.first().await.unwrap(), None);
+        assert_eq!(db.last().await.unwrap(), None);
+
+        for item in test_items() {
+            db.insert(item).await.unwrap();
+        }
+        db.flush().await.unwrap();
+        assert_eq!(db.first().await.unwrap(), Some("0".to_string()));
+        assert_eq!(db.last().await.unwrap(), Some("9".to_string()));
+
+        // the extremes removed in the memtable fall back to the keys next to them
+        db.remove("0".to_string()).await.unwrap();
+        db.remove("9".to_string()).await.unwrap();
+        assert_eq!(db.first().await.unwrap(), Some("1".to_string()));
+        assert_eq!(db.last().await.unwrap(), Some("8".to_string()));
+
+        db.insert(Test {
+            vstring: "a".to_string(),
+            vu32: 0,
+            vbool: None,
+        })
+        .await
+        .unwrap();
+        assert_eq!(db.last().await.unwrap(), Some("a".to_string()));
+    }
+}
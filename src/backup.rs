@@ -0,0 +1,843 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    io::Cursor,
+    marker::PhantomData,
+    pin::pin,
+    str::FromStr,
+    sync::Arc,
+};
+
+use fusio::{fs::OpenOptions, path::Path, DynFs, SeqRead, Write};
+use fusio_dispatch::FsOptions;
+use futures_util::StreamExt;
+use thiserror::Error;
+
+use crate::{
+    fs::{self, manager::StoreManager, parse_file_id, FileId, FileType},
+    record::{Key, Record},
+    serdes::{Decode, Encode},
+    timestamp::{Timestamp, Timestamped},
+    version::{edit::VersionEdit, TransactionTs},
+    wal::{WalEntry, WalFile},
+    DbError, DbOption, DB,
+};
+
+pub type BackupId = u32;
+
+/// summary of a backup kept by [`BackupEngine`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackupInfo {
+    pub id: BackupId,
+    /// the latest timestamp of the database when the backup was taken
+    pub ts: Timestamp,
+    /// total size of the files, including the SSTs shared with other backups
+    pub size: u64,
+    pub file_count: usize,
+}
+
+/// Numbered backups of a [`DB`] kept in a separate location, local or on object storage.
+///
+/// Backups are incremental: the content of every file is stored as blobs in `shared/`, which are
+/// referenced by every backup containing them. SSTs are immutable, so they are uploaded once and
+/// deduplicated by [`FileId`] and by content hash, while only the tail of a WAL appended since the
+/// previous backup is uploaded. The version edits and the blobs of a backup are recorded in
+/// `meta/<id>`, which is written last and renamed into place, so an interrupted backup is never
+/// visible.
+pub struct BackupEngine<R> {
+    fs: Arc<dyn DynFs>,
+    path: Path,
+    is_local: bool,
+    _p: PhantomData<R>,
+}
+
+impl<R> BackupEngine<R>
+where
+    R: Record + Send + Sync,
+    R::Columns: Send + Sync,
+{
+    /// open the backups stored in `path` of the file system described by `fs_options`
+    pub async fn open(
+        path: impl Into<Path>,
+        fs_options: FsOptions,
+    ) -> Result<Self, BackupError<R>> {
+        let is_local = matches!(fs_options, FsOptions::Local);
+        let fs = fs_options.parse()?;
+        let path = path.into();
+
+        fs.create_dir_all(&path.child("shared")).await?;
+        fs.create_dir_all(&path.child("meta")).await?;
+
+        Ok(BackupEngine {
+            fs,
+            path,
+            is_local,
+            _p: PhantomData,
+        })
+    }
+
+    /// back up the current state of `db`, including the data not yet flushed to SSTs. Only the
+    /// data missing from the existing backups is uploaded. Writes of `db` are only blocked while
+    /// its WALs are read, the SSTs are copied afterwards from the version taken then, which keeps
+    /// them from being removed by compactions.
+    pub async fn create_backup(&self, db: &DB<R>) -> Result<BackupId, BackupError<R>> {
+        let metas = self.metas().await?;
+        let id = metas.keys().max().map_or(1, |id| id + 1);
+        let mut backed_up_tables = HashMap::new();
+        let mut backed_up_contents = HashMap::new();
+        for file in metas.values().flat_map(|meta| meta.files.iter()) {
+            if file.is_table() {
+                backed_up_tables.insert(file.name.clone(), file);
+                backed_up_contents.insert((file.size, file.checksum), &file.blobs);
+            }
+        }
+        let previous_wals = metas
+            .values()
+            .next_back()
+            .into_iter()
+            .flat_map(|meta| meta.files.iter())
+            .filter(|file| !file.is_table())
+            .map(|file| (file.name.as_str(), file))
+            .collect::<HashMap<_, _>>();
+
+        let (version, ts, edits, wals) = {
+            let schema = db.schema.write().await;
+            schema.flush_wal().await?;
+            let version = db.version_set.current().await;
+            let option = version.option();
+            // read now, as a flush removes them
+            let wal_fs = db.manager.get_fs(&option.wal_dir_path());
+            let mut wals = Vec::new();
+            for src in fs::list_files(wal_fs, &option.wal_dir_path(), FileType::Wal).await? {
+                let name = src.filename().unwrap().to_string();
+                wals.push((name, fs::read_file(wal_fs, &src).await?));
+            }
+            let edits = version.to_edits();
+            (version, db.version_set.load_ts(), edits, wals)
+        };
+        let option = version.option();
+        let base_fs = db.manager.base_fs();
+        let mut files = Vec::new();
+
+        for (level, scopes) in version.level_slice.iter().enumerate() {
+            let level_fs = option
+                .level_fs_path(level)
+                .map(|path| db.manager.get_fs(path))
+                .unwrap_or(base_fs);
+
+            for scope in scopes {
+                let name = format!("{}.{}", scope.gen, FileType::Parquet);
+                if let Some(file) = backed_up_tables.get(&name) {
+                    files.push(BackupFile::clone(file));
+                    continue;
+                }
+                let buf = fs::read_file(level_fs, &option.table_path(scope.gen, level)).await?;
+                let (size, checksum) = (buf.len() as u64, crc32fast::hash(&buf));
+                let blobs = match backed_up_contents.get(&(size, checksum)) {
+                    Some(blobs) => Vec::clone(blobs),
+                    None => {
+                        self.write_blob(&name, &buf).await?;
+                        vec![name.clone()]
+                    }
+                };
+                files.push(BackupFile {
+                    name,
+                    size,
+                    checksum,
+                    blobs,
+                });
+            }
+        }
+        for (name, buf) in wals {
+            let mut blobs = Vec::new();
+            let mut offset = 0;
+
+            // WALs are append only, so the part in the previous backup can be reused
+            if let Some(previous) = previous_wals.get(name.as_str()) {
+                let previous_size = previous.size as usize;
+
+                if previous_size <= buf.len()
+                    && crc32fast::hash(&buf[..previous_size]) == previous.checksum
+                {
+                    blobs.clone_from(&previous.blobs);
+                    offset = previous_size;
+                }
+            }
+            if offset < buf.len() {
+                let blob = format!("{}.{}-{}", name, offset, buf.len());
+                self.write_blob(&blob, &buf[offset..]).await?;
+                blobs.push(blob);
+            }
+            files.push(BackupFile {
+                name,
+                size: buf.len() as u64,
+                checksum: crc32fast::hash(&buf),
+                blobs,
+            });
+        }
+        let meta = BackupMeta { ts, edits, files };
+
+        // not listed as a backup until renamed
+        let temp_path = self.path.child("meta").child(format!("{}.tmp", id));
+        let _ = self.fs.remove(&temp_path).await;
+        let mut meta_file = self
+            .fs
+            .open_options(&temp_path, OpenOptions::default().create(true).write(true))
+            .await?;
+        meta.encode(&mut meta_file)
+            .await
+            .map_err(BackupError::Encode)?;
+        meta_file.close().await?;
+        fs::rename_file(&self.fs, &temp_path, &self.meta_path(id), self.is_local).await?;
+
+        Ok(id)
+    }
+
+    /// list the backups ordered by id
+    pub async fn backup_infos(&self) -> Result<Vec<BackupInfo>, BackupError<R>> {
+        Ok(self
+            .metas()
+            .await?
+            .into_iter()
+            .map(|(id, meta)| BackupInfo {
+                id,
+                ts: meta.ts,
+                size: meta.files.iter().map(|file| file.size).sum(),
+                file_count: meta.files.len(),
+            })
+            .collect())
+    }
+
+    /// check that every file of the backup exists and matches its recorded size and checksum
+    pub async fn verify_backup(&self, id: BackupId) -> Result<(), BackupError<R>> {
+        let meta = self.meta(id).await?;
+
+        for file in meta.files.iter() {
+            self.read_file(file).await?;
+        }
+        Ok(())
+    }
+
+    /// restore the backup into the database configured by `option`, which must not exist yet
+    pub async fn restore(&self, id: BackupId, option: &DbOption<R>) -> Result<(), BackupError<R>> {
+        let manager = StoreManager::new(option.base_fs.clone(), option.fs_paths())?;
+        let base_fs = manager.base_fs();
+        let wal_fs = manager.get_fs(&option.wal_dir_path());
+
+        Self::check_absent(base_fs, option).await?;
+        let meta = self.meta(id).await?;
+        let levels = meta
+            .edits
+            .iter()
+            .filter_map(|edit| match edit {
+                VersionEdit::Add { level, scope } => Some((scope.gen, *level as usize)),
+                _ => None,
+            })
+            .collect::<HashMap<_, _>>();
+
+        wal_fs.create_dir_all(&option.wal_dir_path()).await?;
+        base_fs
+            .create_dir_all(&option.version_log_dir_path())
+            .await?;
+
+        for file in meta.files.iter() {
+            let (dst_fs, dst) = if file.is_table() {
+                let gen = FileId::from_str(
+                    file.name
+                        .trim_end_matches(&format!(".{}", FileType::Parquet)),
+                )?;
+                let level = levels[&gen];
+                let dst_fs = option
+                    .level_fs_path(level)
+                    .map(|path| manager.get_fs(path))
+                    .unwrap_or(base_fs);
+                (dst_fs, option.table_path(gen, level))
+            } else {
+                (wal_fs, option.wal_dir_path().child(file.name.as_str()))
+            };
+
+            fs::write_file(dst_fs, &dst, &self.read_file(file).await?).await?;
+        }
+        // the version log makes the database visible, so it goes last
+        let mut log = base_fs
+            .open_options(
+                &option.version_log_path(FileId::new()),
+                FileType::Log.open_options(false),
+            )
+            .await?;
+        VersionEdit::encode_batch(&meta.edits, &mut log, option.format_version)
+            .await
+            .map_err(BackupError::Encode)?;
+        log.close().await?;
+
+        Ok(())
+    }
+
+    /// restore the database configured by `option` to its state at `ts`. The latest backup taken
+    /// at or before `ts` is restored, then the records written after it up to `ts` are replayed
+    /// from the WALs of the database configured by `source`, the archived ones included.
+    pub async fn restore_to(
+        &self,
+        ts: Timestamp,
+        source: &DbOption<R>,
+        option: &DbOption<R>,
+    ) -> Result<(), BackupError<R>> {
+        let backup = self
+            .backup_infos()
+            .await?
+            .into_iter()
+            .rev()
+            .find(|info| info.ts <= ts)
+            .ok_or(BackupError::NoBackupBefore(ts))?;
+        let source_manager = StoreManager::new(source.base_fs.clone(), source.fs_paths())?;
+        let manager = StoreManager::new(option.base_fs.clone(), option.fs_paths())?;
+        Self::check_absent(manager.base_fs(), option).await?;
+
+        let mut wal_paths = BTreeMap::new();
+        let mut wal_dirs = Vec::new();
+        if let Some(archive_path) = source.wal_archive_path() {
+            wal_dirs.push((source_manager.get_fs(archive_path), archive_path.clone()));
+        }
+        let source_wal_dir_path = source.wal_dir_path();
+        wal_dirs.push((
+            source_manager.get_fs(&source_wal_dir_path),
+            source_wal_dir_path,
+        ));
+        for (wal_fs, dir) in wal_dirs {
+            for path in fs::list_files(wal_fs, &dir, FileType::Wal).await? {
+                wal_paths.insert(path.filename().unwrap().to_string(), (wal_fs, path));
+            }
+        }
+
+        // a new id is ordered after the WALs of the backup, so the replayed records are recovered
+        // last
+        let replay_id = FileId::new();
+        let wal_fs = manager.get_fs(&option.wal_dir_path());
+        wal_fs.create_dir_all(&option.wal_dir_path()).await?;
+        let mut replay = WalFile::<_, R>::new(
+            wal_fs
+                .open_options(
+                    &option.wal_path(replay_id),
+                    FileType::Wal.open_options(false),
+                )
+                .await?,
+            replay_id,
+        )
+        .with_format_version(option.format_version);
+        for (wal_fs, path) in wal_paths.into_values() {
+            let file = wal_fs
+                .open_options(&path, FileType::Wal.open_options(false))
+                .await?;
+            let wal_id = parse_file_id(&path, FileType::Wal)?.unwrap();
+            let mut wal = WalFile::<_, R>::new(Cursor::new(file), wal_id);
+
+            let mut recover_stream = pin!(wal.recover());
+            while let Some(entry) = recover_stream.next().await {
+                match entry.map_err(DbError::from)? {
+                    WalEntry::Record {
+                        log_type,
+                        key,
+                        value,
+                    } => {
+                        if key.ts() <= backup.ts || key.ts() > ts {
+                            continue;
+                        }
+                        replay
+                            .write(
+                                log_type,
+                                Timestamped::new(key.value().as_key_ref(), key.ts()),
+                                value.as_ref().map(R::as_record_ref),
+                            )
+                            .await
+                            .map_err(|err| DbError::WalWrite(Box::new(err)))?;
+                    }
+                    WalEntry::RangeTombstone(range_tombstone) => {
+                        if range_tombstone.ts <= backup.ts || range_tombstone.ts > ts {
+                            continue;
+                        }
+                        replay
+                            .write_range_tombstone(&range_tombstone)
+                            .await
+                            .map_err(BackupError::Encode)?;
+                    }
+                }
+            }
+        }
+        replay.flush().await?;
+
+        self.restore(backup.id, option).await
+    }
+
+    /// delete the backup and the blobs no longer referenced by other backups
+    pub async fn delete_backup(&self, id: BackupId) -> Result<(), BackupError<R>> {
+        let mut metas = self.metas().await?;
+        let meta = metas.remove(&id).ok_or(BackupError::NotFound(id))?;
+        let referenced = metas
+            .values()
+            .flat_map(|meta| meta.files.iter())
+            .flat_map(|file| file.blobs.iter())
+            .collect::<HashSet<_>>();
+        let unreferenced = meta
+            .files
+            .iter()
+            .flat_map(|file| file.blobs.iter())
+            .filter(|blob| !referenced.contains(blob))
+            .collect::<HashSet<_>>();
+
+        // the meta goes first, so that an interrupted deletion never leaves a broken backup
+        self.fs.remove(&self.meta_path(id)).await?;
+        for blob in unreferenced {
+            self.fs.remove(&self.blob_path(blob)).await?;
+        }
+        Ok(())
+    }
+
+    async fn check_absent(fs: &Arc<dyn DynFs>, option: &DbOption<R>) -> Result<(), BackupError<R>> {
+        if !fs::list_files(fs, &option.version_log_dir_path(), FileType::Log)
+            .await?
+            .is_empty()
+        {
+            return Err(BackupError::DbExists);
+        }
+        Ok(())
+    }
+
+    async fn metas(&self) -> Result<BTreeMap<BackupId, BackupMeta<R::Key>>, BackupError<R>> {
+        let mut metas = BTreeMap::new();
+        let mut file_stream = self.fs.list(&self.path.child("meta")).await?;
+
+        while let Some(file_meta) = file_stream.next().await {
+            let path = file_meta?.path;
+
+            if let Some(id) = path.filename().and_then(|name| name.parse().ok()) {
+                metas.insert(id, self.meta(id).await?);
+            }
+        }
+        Ok(metas)
+    }
+
+    async fn meta(&self, id: BackupId) -> Result<BackupMeta<R::Key>, BackupError<R>> {
+        let mut meta_file = self
+            .fs
+            .open_options(&self.meta_path(id), OpenOptions::default().read(true))
+            .await
+            .map_err(|_| BackupError::NotFound(id))?;
+
+        BackupMeta::decode(&mut Cursor::new(&mut meta_file))
+            .await
+            .map_err(BackupError::Decode)
+    }
+
+    /// read the content of `file` from its blobs and check it against the recorded checksum
+    async fn read_file(&self, file: &BackupFile) -> Result<Vec<u8>, BackupError<R>> {
+        let mut buf = Vec::with_capacity(file.size as usize);
+
+        for blob in file.blobs.iter() {
+            buf.extend(fs::read_file(&self.fs, &self.blob_path(blob)).await?);
+        }
+        if buf.len() as u64 != file.size || crc32fast::hash(&buf) != file.checksum {
+            return Err(BackupError::Corrupted {
+                name: file.name.clone(),
+            });
+        }
+        Ok(buf)
+    }
+
+    async fn write_blob(&self, blob: &str, buf: &[u8]) -> Result<(), BackupError<R>> {
+        let path = self.blob_path(blob);
+
+        // may be left behind by an interrupted backup
+        let _ = self.fs.remove(&path).await;
+        fs::write_file(&self.fs, &path, buf).await?;
+        Ok(())
+    }
+
+    fn meta_path(&self, id: BackupId) -> Path {
+        self.path.child("meta").child(id.to_string())
+    }
+
+    fn blob_path(&self, blob: &str) -> Path {
+        self.path.child("shared").child(blob)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct BackupFile {
+    /// the file name in the database
+    name: String,
+    size: u64,
+    checksum: u32,
+    /// names of the blobs whose concatenation is the content of the file
+    blobs: Vec<String>,
+}
+
+impl BackupFile {
+    fn is_table(&self) -> bool {
+        self.name.ends_with(&format!(".{}", FileType::Parquet))
+    }
+}
+
+impl Encode for BackupFile {
+    type Error = fusio::Error;
+
+    async fn encode<W>(&self, writer: &mut W) -> Result<(), Self::Error>
+    where
+        W: Write,
+    {
+        self.name.encode(writer).await?;
+        self.size.encode(writer).await?;
+        self.checksum.encode(writer).await?;
+        (self.blobs.len() as u32).encode(writer).await?;
+        for blob in self.blobs.iter() {
+            blob.encode(writer).await?;
+        }
+        Ok(())
+    }
+
+    fn size(&self) -> usize {
+        self.name.size()
+            + self.size.size()
+            + self.checksum.size()
+            + 0u32.size()
+            + self.blobs.iter().map(Encode::size).sum::<usize>()
+    }
+}
+
+impl Decode for BackupFile {
+    type Error = fusio::Error;
+
+    async fn decode<R: SeqRead>(reader: &mut R) -> Result<Self, Self::Error> {
+        let name = String::decode(reader).await?;
+        let size = u64::decode(reader).await?;
+        let checksum = u32::decode(reader).await?;
+        let blob_len = u32::decode(reader).await?;
+        let mut blobs = Vec::with_capacity(blob_len as usize);
+        for _ in 0..blob_len {
+            blobs.push(String::decode(reader).await?);
+        }
+        Ok(BackupFile {
+            name,
+            size,
+            checksum,
+            blobs,
+        })
+    }
+}
+
+struct BackupMeta<K> {
+    ts: Timestamp,
+    edits: Vec<VersionEdit<K>>,
+    files: Vec<BackupFile>,
+}
+
+impl<K> Encode for BackupMeta<K>
+where
+    K: Encode + Sync,
+{
+    type Error = <K as Encode>::Error;
+
+    async fn encode<W>(&self, writer: &mut W) -> Result<(), Self::Error>
+    where
+        W: Write,
+    {
+        self.ts.encode(writer).await?;
+        (self.edits.len() as u32).encode(writer).await?;
+        for edit in self.edits.iter() {
+            edit.encode(writer).await?;
+        }
+        (self.files.len() as u32).encode(writer).await?;
+        for file in self.files.iter() {
+            file.encode(writer).await?;
+        }
+        Ok(())
+    }
+
+    fn size(&self) -> usize {
+        self.ts.size()
+            + 0u32.size()
+            + self.edits.iter().map(Encode::size).sum::<usize>()
+            + 0u32.size()
+            + self.files.iter().map(Encode::size).sum::<usize>()
+    }
+}
+
+impl<K> Decode for BackupMeta<K>
+where
+    K: Decode,
+{
+    type Error = <K as Decode>::Error;
+
+    async fn decode<R: SeqRead>(reader: &mut R) -> Result<Self, Self::Error> {
+        let ts = Timestamp::decode(reader).await?;
+        let edit_len = u32::decode(reader).await?;
+        let mut edits = Vec::with_capacity(edit_len as usize);
+        for _ in 0..edit_len {
+            edits.push(VersionEdit::decode(reader).await?);
+        }
+        let file_len = u32::decode(reader).await?;
+        let mut files = Vec::with_capacity(file_len as usize);
+        for _ in 0..file_len {
+            files.push(BackupFile::decode(reader).await?);
+        }
+        Ok(BackupMeta { ts, edits, files })
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum BackupError<R>
+where
+    R: Record,
+{
+    #[error("backup fusio error: {0}")]
+    Fusio(#[from] fusio::Error),
+    #[error("backup db error: {0}")]
+    Db(#[from] DbError<R>),
+    #[error("backup ulid decode error: {0}")]
+    UlidDecode(#[from] ulid::DecodeError),
+    #[error("backup encode error: {0}")]
+    Encode(#[source] <R::Key as Encode>::Error),
+    #[error("backup decode error: {0}")]
+    Decode(#[source] <R::Key as Decode>::Error),
+    #[error("backup {0} not found")]
+    NotFound(BackupId),
+    #[error("no backup taken at or before {0:?}")]
+    NoBackupBefore(Timestamp),
+    #[error("backup file {name} is corrupted")]
+    Corrupted { name: String },
+    #[error("restore into an existing database")]
+    DbExists,
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod tests {
+    use fusio::path::Path;
+    use fusio_dispatch::FsOptions;
+    use tempfile::TempDir;
+
+    use super::{BackupEngine, BackupError};
+    use crate::{
+        executor::tokio::TokioExecutor,
+        tests::{test_items, Test},
+        trigger::TriggerType,
+        version::TransactionTs,
+        DbOption, DB,
+    };
+
+    #[tokio::test]
+    async fn backup_and_restore() {
+        let temp_dir = TempDir::new().unwrap();
+        let backup_dir = TempDir::new().unwrap();
+        let restore_dir = TempDir::new().unwrap();
+
+        let mut option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap());
+        option.immutable_chunk_num = 1;
+        option.immutable_chunk_max_num = 1;
+        option.trigger_type = TriggerType::Length(5);
+
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+        let engine = BackupEngine::<Test>::open(
+            Path::from_filesystem_path(backup_dir.path()).unwrap(),
+            FsOptions::Local,
+        )
+        .await
+        .unwrap();
+
+        let items = test_items();
+        for item in items[..20].iter().cloned() {
+            db.insert(item).await.unwrap();
+        }
+        db.flush().await.unwrap();
+        let id_1 = engine.create_backup(&db).await.unwrap();
+
+        for item in items[20..].iter().cloned() {
+            db.insert(item).await.unwrap();
+        }
+        db.flush().await.unwrap();
+        let id_2 = engine.create_backup(&db).await.unwrap();
+
+        let infos = engine.backup_infos().await.unwrap();
+        assert_eq!(
+            infos.iter().map(|info| info.id).collect::<Vec<_>>(),
+            vec![id_1, id_2]
+        );
+        assert!(infos[0].ts < infos[1].ts);
+        // the meta of an interrupted backup is not listed
+        std::fs::write(backup_dir.path().join("meta").join("3.tmp"), b"interrupted").unwrap();
+        assert_eq!(engine.backup_infos().await.unwrap(), infos);
+        engine.verify_backup(id_1).await.unwrap();
+        engine.verify_backup(id_2).await.unwrap();
+
+        // the files shared with backup 2 must survive
+        engine.delete_backup(id_1).await.unwrap();
+        assert!(matches!(
+            engine.verify_backup(id_1).await,
+            Err(BackupError::NotFound(_))
+        ));
+        engine.verify_backup(id_2).await.unwrap();
+
+        let restore_option =
+            DbOption::from(Path::from_filesystem_path(restore_dir.path()).unwrap());
+        engine.restore(id_2, &restore_option).await.unwrap();
+        assert!(matches!(
+            engine.restore(id_2, &restore_option).await,
+            Err(BackupError::DbExists)
+        ));
+
+        let restored: DB<Test> = DB::new(restore_option, TokioExecutor::new()).await.unwrap();
+        for item in items {
+            let vu32 = restored
+                .get(&item.vstring, |entry| Some(entry.get().vu32.unwrap()))
+                .await
+                .unwrap();
+            assert_eq!(vu32, Some(item.vu32));
+        }
+    }
+
+    #[tokio::test]
+    async fn verify_corrupted_backup() {
+        let temp_dir = TempDir::new().unwrap();
+        let backup_dir = TempDir::new().unwrap();
+
+        let db: DB<Test> = DB::new(
+            DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap()),
+            TokioExecutor::new(),
+        )
+        .await
+        .unwrap();
+        let engine = BackupEngine::<Test>::open(
+            Path::from_filesystem_path(backup_dir.path()).unwrap(),
+            FsOptions::Local,
+        )
+        .await
+        .unwrap();
+
+        for item in test_items() {
+            db.insert(item).await.unwrap();
+        }
+        let id = engine.create_backup(&db).await.unwrap();
+        engine.verify_backup(id).await.unwrap();
+
+        for entry in std::fs::read_dir(backup_dir.path().join("shared")).unwrap() {
+            std::fs::write(entry.unwrap().path(), b"corrupted").unwrap();
+        }
+        assert!(matches!(
+            engine.verify_backup(id).await,
+            Err(BackupError::Corrupted { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn incremental_backup() {
+        let temp_dir = TempDir::new().unwrap();
+        let backup_dir = TempDir::new().unwrap();
+
+        let mut option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap());
+        option.immutable_chunk_num = 1;
+        option.immutable_chunk_max_num = 1;
+        option.trigger_type = TriggerType::Length(5);
+
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+        let engine = BackupEngine::<Test>::open(
+            Path::from_filesystem_path(backup_dir.path()).unwrap(),
+            FsOptions::Local,
+        )
+        .await
+        .unwrap();
+        let blobs = || {
+            std::fs::read_dir(backup_dir.path().join("shared"))
+                .unwrap()
+                .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        let items = test_items();
+        for item in items[..20].iter().cloned() {
+            db.insert(item).await.unwrap();
+        }
+        db.flush().await.unwrap();
+        db.insert(items[20].clone()).await.unwrap();
+        engine.create_backup(&db).await.unwrap();
+        let table_count = blobs()
+            .iter()
+            .filter(|blob| blob.ends_with(".parquet"))
+            .count();
+        assert!(table_count > 0);
+
+        // only appended to the WAL
+        db.insert(items[21].clone()).await.unwrap();
+        let id = engine.create_backup(&db).await.unwrap();
+
+        let blobs = blobs();
+        assert_eq!(
+            blobs
+                .iter()
+                .filter(|blob| blob.ends_with(".parquet"))
+                .count(),
+            table_count
+        );
+        assert!(blobs
+            .iter()
+            .any(|blob| blob.contains(".wal.") && !blob.contains(".wal.0-")));
+        engine.verify_backup(id).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn restore_to_timestamp() {
+        let temp_dir = TempDir::new().unwrap();
+        let backup_dir = TempDir::new().unwrap();
+        let restore_dir = TempDir::new().unwrap();
+
+        let mut option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap())
+            .wal_archive(
+                Path::from_filesystem_path(temp_dir.path().join("archive")).unwrap(),
+                FsOptions::Local,
+            );
+        option.immutable_chunk_num = 1;
+        option.immutable_chunk_max_num = 1;
+        option.trigger_type = TriggerType::Length(5);
+
+        let db: DB<Test> = DB::new(option.clone(), TokioExecutor::new()).await.unwrap();
+        let engine = BackupEngine::<Test>::open(
+            Path::from_filesystem_path(backup_dir.path()).unwrap(),
+            FsOptions::Local,
+        )
+        .await
+        .unwrap();
+
+        let items = test_items();
+        for item in items[..10].iter().cloned() {
+            db.insert(item).await.unwrap();
+        }
+        db.flush().await.unwrap();
+        engine.create_backup(&db).await.unwrap();
+
+        for item in items[10..20].iter().cloned() {
+            db.insert(item).await.unwrap();
+        }
+        let ts = db.version_set.load_ts();
+        for item in items[20..].iter().cloned() {
+            db.insert(item).await.unwrap();
+        }
+        db.flush().await.unwrap();
+        db.flush_wal().await.unwrap();
+        assert!(std::fs::read_dir(temp_dir.path().join("archive"))
+            .unwrap()
+            .next()
+            .is_some());
+
+        let restore_option =
+            DbOption::from(Path::from_filesystem_path(restore_dir.path()).unwrap());
+        engine
+            .restore_to(ts, &option, &restore_option)
+            .await
+            .unwrap();
+
+        let restored: DB<Test> = DB::new(restore_option, TokioExecutor::new()).await.unwrap();
+        for (i, item) in items.into_iter().enumerate() {
+            let vu32 = restored
+                .get(&item.vstring, |entry| Some(entry.get().vu32.unwrap()))
+                .await
+                .unwrap();
+            assert_eq!(vu32.is_some(), i < 20);
+        }
+    }
+}
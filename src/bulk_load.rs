@@ -0,0 +1,258 @@
+use std::{ops::Bound, pin::pin, sync::Arc};
+
+use arrow::datatypes::Schema;
+use futures_core::Stream;
+use futures_util::StreamExt;
+
+use crate::{
+    inmem::immutable::{ArrowArrays, Builder},
+    option::DbOption,
+    record::{Key, KeyRef, Record},
+    scope::Scope,
+    timestamp::{Timestamp, Timestamped},
+    version::{edit::VersionEdit, TransactionTs, MAX_LEVEL},
+    write_table, DbError, DB,
+};
+
+/// Loads a sorted stream of records directly into SSTs of the last level, bypassing the WAL and
+/// the memtables.
+///
+/// Records are written into tables capped by [`DbOption::max_sst_file_size`], and all tables are
+/// committed in a single version edit by [`BulkLoader::finish`], so none of the records is visible
+/// before the load completes. Every record shares the timestamp taken when the loader is created.
+/// The loaded range must not overlap any existing data, which makes the loader suitable for
+/// initial loads.
+pub struct BulkLoader<'db, R>
+where
+    R: Record,
+{
+    db: &'db DB<R>,
+    option: Arc<DbOption<R>>,
+    arrow_schema: Arc<Schema>,
+    ts: Timestamp,
+    builder: <R::Columns as ArrowArrays>::Builder,
+    min: Option<R::Key>,
+    max: Option<R::Key>,
+    scopes: Vec<Scope<R::Key>>,
+}
+
+impl<'db, R> BulkLoader<'db, R>
+where
+    R: Record + Send + Sync,
+    R::Columns: Send + Sync,
+{
+    pub(crate) async fn new(db: &'db DB<R>) -> Self {
+        let arrow_schema = db.schema.read().await.record_instance.arrow_schema::<R>();
+        let option = db.version_set.current().await.option().clone();
+
+        BulkLoader {
+            db,
+            option,
+            builder: R::Columns::builder(&arrow_schema, 8192),
+            arrow_schema,
+            ts: db.version_set.increase_ts(),
+            min: None,
+            max: None,
+            scopes: Vec::new(),
+        }
+    }
+
+    /// append a record, its key must be greater than the key of the previous record
+    pub async fn add(&mut self, record: R) -> Result<(), DbError<R>> {
+        let key = record.key().to_key();
+
+        if self.max.as_ref().is_some_and(|max| *max >= key) {
+            return Err(DbError::Ingest(
+                "bulk loaded keys are not sorted or unique".to_string(),
+            ));
+        }
+        self.builder.push(
+            Timestamped::new(record.key(), self.ts),
+            Some(record.as_record_ref()),
+        );
+        if self.min.is_none() {
+            self.min = Some(key.clone());
+        }
+        self.max = Some(key);
+
+        if self.builder.written_size() >= self.option.max_sst_file_size {
+            self.write().await?;
+        }
+        Ok(())
+    }
+
+    /// write the remaining records and commit all tables in one version edit. Fails without
+    /// changing the database if the loaded range overlaps existing data.
+    pub async fn finish(mut self) -> Result<(), DbError<R>> {
+        self.write().await?;
+
+        let (Some(min), Some(max)) = (
+            self.scopes.first().map(|scope| &scope.min),
+            self.scopes.last().map(|scope| &scope.max),
+        ) else {
+            return Ok(());
+        };
+        let range = (Bound::Included(min), Bound::Included(max));
+
+        // block writes and compaction until the version edit is applied
+        let schema = self.db.schema.write().await;
+        let version = self.db.version_set.current().await;
+        let is_overlapped = schema.is_unflushed(range)
+            || version
+                .level_slice
+                .iter()
+                .flatten()
+                .any(|scope| scope.meets_range(range));
+        if is_overlapped {
+            drop(schema);
+            self.clean().await?;
+
+            return Err(DbError::Ingest(
+                "bulk loaded records overlap existing data".to_string(),
+            ));
+        }
+
+        let mut version_edits = Vec::with_capacity(self.scopes.len() + 1);
+        for scope in self.scopes {
+            version_edits.push(VersionEdit::Add {
+                level: (MAX_LEVEL - 1) as u8,
+                scope,
+            });
+        }
+        version_edits.push(VersionEdit::LatestTimeStamp {
+            ts: self.db.version_set.increase_ts(),
+        });
+        self.db
+            .version_set
+            .apply_edits(version_edits, None, false)
+            .await?;
+        drop(schema);
+
+        Ok(())
+    }
+
+    /// remove the tables written so far without committing them
+    pub async fn abort(mut self) -> Result<(), DbError<R>> {
+        self.clean().await
+    }
+
+    async fn write(&mut self) -> Result<(), DbError<R>> {
+        let (Some(min), Some(max)) = (self.min.take(), self.max.clone()) else {
+            return Ok(());
+        };
+        self.db.schema.read().await.check_writable()?;
+        let columns = self.builder.finish(None);
+        let (gen, stats) = write_table(
+            &self.option,
+            &self.db.manager,
+            MAX_LEVEL - 1,
+            &self.arrow_schema,
+            &columns,
+        )
+        .await?;
+
+        self.scopes.push(Scope {
+            min,
+            max,
+            gen,
+            wal_ids: None,
+            stats: Some(stats),
+        });
+        Ok(())
+    }
+
+    async fn clean(&mut self) -> Result<(), DbError<R>> {
+        let level_fs = self.db.manager.get_fs(
+            self.option
+                .level_fs_path(MAX_LEVEL - 1)
+                .unwrap_or(&self.option.base_path),
+        );
+        for scope in self.scopes.drain(..) {
+            level_fs
+                .remove(&self.option.table_path(scope.gen, MAX_LEVEL - 1))
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+impl<R> DB<R>
+where
+    R: Record + Send + Sync,
+    R::Columns: Send + Sync,
+{
+    /// create a [`BulkLoader`] writing sorted records directly into SSTs
+    pub async fn bulk_loader(&self) -> BulkLoader<'_, R> {
+        BulkLoader::new(self).await
+    }
+
+    /// load a stream of records sorted by key, see [`BulkLoader`]. The tables written are removed
+    /// if the load fails.
+    pub async fn bulk_load(&self, records: impl Stream<Item = R>) -> Result<(), DbError<R>> {
+        let mut records = pin!(records);
+        let mut loader = self.bulk_loader().await;
+
+        while let Some(record) = records.next().await {
+            if let Err(err) = loader.add(record).await {
+                loader.abort().await?;
+                return Err(err);
+            }
+        }
+        loader.finish().await
+    }
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod tests {
+    use fusio::path::Path;
+    use futures_util::stream;
+    use tempfile::TempDir;
+
+    use crate::{
+        executor::tokio::TokioExecutor,
+        tests::{test_items, Test},
+        version::MAX_LEVEL,
+        DbError, DbOption, DB,
+    };
+
+    #[tokio::test]
+    async fn bulk_load() {
+        let temp_dir = TempDir::new().unwrap();
+        let option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap())
+            .max_sst_file_size(256);
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+
+        let mut items = test_items();
+        items.sort_by(|a, b| a.vstring.cmp(&b.vstring));
+        db.bulk_load(stream::iter(items.clone())).await.unwrap();
+
+        let version = db.version_set.current().await;
+        assert!(version.level_slice[MAX_LEVEL - 1].len() > 1);
+        assert!(version.level_slice[..MAX_LEVEL - 1]
+            .iter()
+            .all(|scopes| scopes.is_empty()));
+        for item in items.iter() {
+            let key = item.vstring.clone();
+            let vu32 = db.get(&key, |entry| entry.get().vu32).await.unwrap();
+            assert_eq!(vu32, Some(item.vu32));
+        }
+
+        // overlapping the loaded range
+        let mut loader = db.bulk_loader().await;
+        loader.add(items[0].clone()).await.unwrap();
+        assert!(matches!(loader.finish().await, Err(DbError::Ingest(_))));
+        assert_eq!(
+            db.version_set.current().await.level_slice[MAX_LEVEL - 1].len(),
+            version.level_slice[MAX_LEVEL - 1].len()
+        );
+
+        // unsorted
+        let mut loader = db.bulk_loader().await;
+        loader.add(items[1].clone()).await.unwrap();
+        assert!(matches!(
+            loader.add(items[0].clone()).await,
+            Err(DbError::Ingest(_))
+        ));
+        loader.abort().await.unwrap();
+    }
+}
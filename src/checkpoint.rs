@@ -0,0 +1,280 @@
+use std::{io, sync::Arc};
+
+#[cfg(feature = "tokio")]
+use fusio::path::path_to_local;
+use fusio::{path::Path, DynFs};
+use fusio_dispatch::FsOptions;
+use futures_util::StreamExt;
+
+use crate::{
+    executor::Executor,
+    fs::{self, FileId, FileType},
+    record::{Record, RecordInstance},
+    version::{edit::VersionEdit, VersionError, MAX_LEVEL},
+    DbError, DbOption, DB,
+};
+
+impl<R> DB<R>
+where
+    R: Record + Send + Sync,
+    R::Columns: Send + Sync,
+{
+    /// write a consistent copy of the database into the empty directory `path` of the base file
+    /// system, which can be opened as an independent database, e.g. for backups. SSTs are
+    /// hard-linked on local file systems and copied otherwise, WALs are always copied.
+    pub async fn checkpoint(&self, path: impl Into<Path>) -> Result<(), DbError<R>> {
+        self.write_checkpoint(path).await.map(|_| ())
+    }
+
+    /// write a checkpoint of the database into the empty directory `path` of the base file system
+    /// as by [`DB::checkpoint`], and open it as a new database written independently of this one,
+    /// e.g. to try a migration on production data. SSTs are never modified, so on local file
+    /// systems the fork shares them with this database through hard links, and only the files
+    /// written by either afterwards take up space. SSTs on other file systems are copied. The
+    /// fork has the options of this database, without the WAL archive.
+    pub async fn fork<E>(&self, path: impl Into<Path>, executor: E) -> Result<DB<R>, DbError<R>>
+    where
+        E: Executor + Send + Sync + 'static,
+    {
+        let option = DbOption {
+            wal_archive: None,
+            ..self.write_checkpoint(path).await?
+        };
+        let instance = RecordInstance::clone(&self.schema.read().await.record_instance);
+        let lru_cache = option.lru_cache();
+
+        Self::build(Arc::new(option), executor, instance, lru_cache).await
+    }
+
+    /// write the checkpoint of [`DB::checkpoint`], returning the options of the database written
+    async fn write_checkpoint(&self, path: impl Into<Path>) -> Result<DbOption<R>, DbError<R>> {
+        // block writes and compaction, so that WALs, SSTs and the version stay consistent
+        let schema = self.schema.write().await;
+        schema.flush_wal().await?;
+
+        let version = self.version_set.current().await;
+        let option = version.option();
+        let target = self.create_copy(option, path.into()).await?;
+        let base_fs = self.manager.base_fs();
+
+        for (level, scopes) in version.level_slice.iter().enumerate() {
+            for scope in scopes {
+                self.share_table(option, &target, level, scope.gen).await?;
+            }
+        }
+        // WALs are still appended to, so they can not be shared with the checkpoint
+        let wal_fs = self.manager.get_fs(&option.wal_dir_path());
+        for src in fs::list_files(wal_fs, &option.wal_dir_path(), FileType::Wal).await? {
+            let dst = target.wal_dir_path().child(src.filename().unwrap());
+            fs::copy_file(wal_fs, &src, base_fs, &dst).await?;
+        }
+        // the live version log may be rewritten at any time, so write a snapshot of it instead
+        Self::write_copy_log(&target, base_fs, &version.to_edits()).await?;
+
+        Ok(target)
+    }
+
+    /// the options of a copy of the database in the empty directory `path` of the base file
+    /// system, with the directories of its WALs and version log created
+    pub(crate) async fn create_copy(
+        &self,
+        option: &DbOption<R>,
+        path: Path,
+    ) -> Result<DbOption<R>, DbError<R>> {
+        let target = DbOption {
+            base_path: path,
+            level_paths: vec![None; MAX_LEVEL],
+            wal_dir: None,
+            ..DbOption::clone(option)
+        };
+        let base_fs = self.manager.base_fs();
+
+        if let Ok(mut file_stream) = base_fs.list(&target.base_path).await {
+            if file_stream.next().await.is_some() {
+                return Err(DbError::Io(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    "checkpoint directory is not empty",
+                )));
+            }
+        }
+        base_fs.create_dir_all(&target.wal_dir_path()).await?;
+        base_fs
+            .create_dir_all(&target.version_log_dir_path())
+            .await?;
+
+        Ok(target)
+    }
+
+    /// hard-link the SST `gen` of the `level` into the copy `target` on local file systems, or
+    /// copy it otherwise
+    pub(crate) async fn share_table(
+        &self,
+        option: &DbOption<R>,
+        target: &DbOption<R>,
+        level: usize,
+        gen: FileId,
+    ) -> Result<(), DbError<R>> {
+        let base_fs = self.manager.base_fs();
+        let (level_fs, is_local) = match &option.level_paths[level] {
+            Some((level_path, fs_options)) => (
+                self.manager.get_fs(level_path),
+                matches!(fs_options, FsOptions::Local),
+            ),
+            None => (base_fs, true),
+        };
+        let is_local = is_local && matches!(option.base_fs, FsOptions::Local);
+        let src = option.table_path(gen, level);
+        let dst = target.table_path(gen, level);
+
+        if !(is_local && Self::hard_link(&src, &dst).await) {
+            fs::copy_file(level_fs, &src, base_fs, &dst).await?;
+        }
+        Ok(())
+    }
+
+    /// write the version log of the copy `target` holding the `version_edits`
+    pub(crate) async fn write_copy_log(
+        target: &DbOption<R>,
+        base_fs: &Arc<dyn DynFs>,
+        version_edits: &[VersionEdit<R::Key>],
+    ) -> Result<(), DbError<R>> {
+        let mut log = base_fs
+            .open_options(
+                &target.version_log_path(FileId::new()),
+                FileType::Log.open_options(false),
+            )
+            .await?;
+        VersionEdit::encode_batch(version_edits, &mut log, target.format_version)
+            .await
+            .map_err(VersionError::Encode)?;
+        log.close().await?;
+
+        Ok(())
+    }
+
+    #[cfg(feature = "tokio")]
+    async fn hard_link(src: &Path, dst: &Path) -> bool {
+        match (path_to_local(src), path_to_local(dst)) {
+            (Ok(src), Ok(dst)) => tokio::fs::hard_link(src, dst).await.is_ok(),
+            _ => false,
+        }
+    }
+
+    #[cfg(not(feature = "tokio"))]
+    async fn hard_link(_: &Path, _: &Path) -> bool {
+        false
+    }
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod tests {
+    use fusio::path::Path;
+    use tempfile::TempDir;
+
+    use crate::{
+        executor::tokio::TokioExecutor,
+        tests::{test_items, Test},
+        trigger::TriggerType,
+        DbOption, DB,
+    };
+
+    #[tokio::test]
+    async fn checkpoint() {
+        let temp_dir = TempDir::new().unwrap();
+        let checkpoint_dir = TempDir::new().unwrap();
+        let checkpoint_path = Path::from_filesystem_path(checkpoint_dir.path())
+            .unwrap()
+            .child("checkpoint");
+
+        let mut option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap());
+        option.immutable_chunk_num = 1;
+        option.immutable_chunk_max_num = 1;
+        option.major_threshold_with_sst_size = 3;
+        option.major_default_oldest_table_num = 1;
+        option.trigger_type = TriggerType::Length(5);
+
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+        for item in test_items() {
+            db.insert(item).await.unwrap();
+        }
+        db.flush().await.unwrap();
+        // only in the WAL
+        db.insert(Test {
+            vstring: "40".to_string(),
+            vu32: 40,
+            vbool: Some(true),
+        })
+        .await
+        .unwrap();
+
+        db.checkpoint(checkpoint_path.clone()).await.unwrap();
+        assert!(db.checkpoint(checkpoint_path.clone()).await.is_err());
+        db.insert(Test {
+            vstring: "41".to_string(),
+            vu32: 41,
+            vbool: Some(true),
+        })
+        .await
+        .unwrap();
+
+        let checkpoint: DB<Test> = DB::new(DbOption::from(checkpoint_path), TokioExecutor::new())
+            .await
+            .unwrap();
+        for i in 0..41 {
+            let vu32 = checkpoint
+                .get(&i.to_string(), |entry| Some(entry.get().vu32.unwrap()))
+                .await
+                .unwrap();
+            assert_eq!(vu32, Some(i));
+        }
+        assert!(checkpoint
+            .get(&"41".to_string(), |_| Some(()))
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn fork() {
+        let temp_dir = TempDir::new().unwrap();
+        let fork_dir = TempDir::new().unwrap();
+        let fork_path = Path::from_filesystem_path(fork_dir.path())
+            .unwrap()
+            .child("fork");
+
+        let mut option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap());
+        option.immutable_chunk_num = 1;
+        option.immutable_chunk_max_num = 1;
+        option.trigger_type = TriggerType::Length(5);
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+        for item in test_items() {
+            db.insert(item).await.unwrap();
+        }
+        db.flush().await.unwrap();
+
+        let fork = db.fork(fork_path, TokioExecutor::new()).await.unwrap();
+        async fn get(db: &DB<Test>, key: &str) -> Option<u32> {
+            db.get(&key.to_string(), |entry| Some(entry.get().vu32.unwrap()))
+                .await
+                .unwrap()
+        }
+        for i in 0..40 {
+            assert_eq!(get(&fork, &i.to_string()).await, Some(i));
+        }
+
+        // written independently
+        db.remove("0".to_string()).await.unwrap();
+        fork.insert(Test {
+            vstring: "1".to_string(),
+            vu32: 100,
+            vbool: None,
+        })
+        .await
+        .unwrap();
+        fork.flush().await.unwrap();
+        assert_eq!(get(&db, "0").await, None);
+        assert_eq!(get(&db, "1").await, Some(1));
+        assert_eq!(get(&fork, "0").await, Some(0));
+        assert_eq!(get(&fork, "1").await, Some(100));
+    }
+}
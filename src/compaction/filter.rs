@@ -0,0 +1,32 @@
+use crate::record::{Key, Record};
+
+/// What a [`CompactionFilter`] wants to happen to a record being compacted.
+pub enum FilterDecision<R>
+where
+    R: Record,
+{
+    /// write the record to the output table unchanged
+    Keep,
+    /// drop the record, a tombstone is written in its place so that older versions in lower
+    /// levels stay hidden
+    Remove,
+    /// replace the record with a new value, the primary key must not be changed
+    Change(R),
+}
+
+/// Callback invoked for the newest version of every key rewritten by major compaction.
+///
+/// This allows application level garbage collection, data scrubbing and lazy migrations
+/// without rewriting the whole table.
+pub trait CompactionFilter<R>: Send + Sync
+where
+    R: Record,
+{
+    /// `level` is the level the output table is written to. `value` is `None` for tombstones.
+    fn filter<'r>(
+        &self,
+        level: usize,
+        key: <R::Key as Key>::Ref<'r>,
+        value: Option<R::Ref<'r>>,
+    ) -> FilterDecision<R>;
+}
@@ -0,0 +1,194 @@
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use web_time::Instant;
+
+use crate::{fs::FileId, properties::CompactionJob, scope::Scope};
+
+/// The major compactions in flight, shared by the compactors and the [`DB`](crate::DB) through
+/// the [`VersionSet`](crate::version::set::VersionSet).
+#[derive(Debug, Default)]
+pub(crate) struct Jobs {
+    next_id: AtomicU64,
+    running: Mutex<BTreeMap<u64, Arc<JobState>>>,
+}
+
+#[derive(Debug)]
+struct JobState {
+    started: Instant,
+    inputs: Mutex<Vec<(usize, FileId)>>,
+    outputs: Mutex<Vec<(usize, FileId)>>,
+    input_bytes: AtomicU64,
+    bytes_read: Arc<AtomicU64>,
+    cancelled: AtomicBool,
+}
+
+impl Jobs {
+    pub(crate) fn start(self: &Arc<Self>) -> Job {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let state = Arc::new(JobState {
+            started: Instant::now(),
+            inputs: Default::default(),
+            outputs: Default::default(),
+            input_bytes: Default::default(),
+            bytes_read: Default::default(),
+            cancelled: Default::default(),
+        });
+        self.running.lock().unwrap().insert(id, state.clone());
+
+        Job {
+            id,
+            state,
+            jobs: self.clone(),
+        }
+    }
+
+    /// the jobs which picked their inputs already, the oldest one first
+    pub(crate) fn list(&self) -> Vec<CompactionJob> {
+        self.running
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|(id, state)| {
+                let inputs = state.inputs.lock().unwrap().clone();
+                if inputs.is_empty() {
+                    return None;
+                }
+                let input_bytes = state.input_bytes.load(Ordering::Relaxed);
+                let bytes_read = state.bytes_read.load(Ordering::Relaxed);
+                let elapsed = state.started.elapsed();
+                let eta = (bytes_read > 0 && input_bytes > 0).then(|| {
+                    elapsed
+                        .mul_f64(input_bytes.saturating_sub(bytes_read) as f64 / bytes_read as f64)
+                });
+
+                Some(CompactionJob {
+                    id: *id,
+                    inputs,
+                    input_bytes,
+                    bytes_read,
+                    elapsed,
+                    eta,
+                })
+            })
+            .collect()
+    }
+
+    /// cancel the job, which stops at the next batch of records it writes and removes the
+    /// tables it wrote. `false` if it is not running
+    pub(crate) fn cancel(&self, id: u64) -> bool {
+        match self.running.lock().unwrap().get(&id) {
+            Some(state) => {
+                state.cancelled.store(true, Ordering::Relaxed);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// A major compaction registered in [`Jobs`] until dropped.
+pub(crate) struct Job {
+    id: u64,
+    state: Arc<JobState>,
+    jobs: Arc<Jobs>,
+}
+
+impl Job {
+    pub(crate) fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// the tables of `level` merged by the job, sized by their statistics
+    pub(crate) fn add_inputs<'a, K: 'a>(
+        &self,
+        level: usize,
+        scopes: impl IntoIterator<Item = &'a Scope<K>>,
+    ) {
+        let mut inputs = self.state.inputs.lock().unwrap();
+        for scope in scopes {
+            inputs.push((level, scope.gen));
+            let bytes = scope.stats.as_ref().map_or(0, |stats| stats.bytes);
+            self.state.input_bytes.fetch_add(bytes, Ordering::Relaxed);
+        }
+    }
+
+    /// the bytes of the inputs added so far, by their statistics
+    pub(crate) fn input_bytes(&self) -> u64 {
+        self.state.input_bytes.load(Ordering::Relaxed)
+    }
+
+    /// the counter of the bytes read from the inputs, see
+    /// [`SsTable::count_reads`](crate::ondisk::sstable::SsTable::count_reads)
+    pub(crate) fn bytes_read(&self) -> Arc<AtomicU64> {
+        self.state.bytes_read.clone()
+    }
+
+    pub(crate) fn add_output(&self, level: usize, gen: FileId) {
+        self.state.outputs.lock().unwrap().push((level, gen));
+    }
+
+    /// the tables written by the job, which are not in any version until it completes
+    pub(crate) fn outputs(&self) -> Vec<(usize, FileId)> {
+        self.state.outputs.lock().unwrap().clone()
+    }
+
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.state.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for Job {
+    fn drop(&mut self) {
+        self.jobs.running.lock().unwrap().remove(&self.id);
+    }
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod tests {
+    use std::sync::{atomic::Ordering, Arc};
+
+    use super::Jobs;
+    use crate::{
+        fs::FileId,
+        scope::{Scope, TableStats},
+    };
+
+    #[test]
+    fn list_and_cancel() {
+        let jobs = Arc::new(Jobs::default());
+        let job = jobs.start();
+        // nothing to report before the inputs are picked
+        assert!(jobs.list().is_empty());
+
+        let scope = Scope {
+            min: 1.to_string(),
+            max: 2.to_string(),
+            gen: FileId::new(),
+            wal_ids: None,
+            stats: Some(TableStats {
+                bytes: 100,
+                ..Default::default()
+            }),
+        };
+        job.add_inputs(0, [&scope]);
+        assert_eq!(jobs.list()[0].eta, None);
+        job.bytes_read().fetch_add(25, Ordering::Relaxed);
+        let listed = jobs.list();
+        assert_eq!(listed[0].inputs, vec![(0, scope.gen)]);
+        assert_eq!((listed[0].input_bytes, listed[0].bytes_read), (100, 25));
+        assert!(listed[0].eta.is_some());
+
+        assert!(!job.is_cancelled());
+        assert!(jobs.cancel(job.id()));
+        assert!(job.is_cancelled());
+        drop(job);
+        assert!(jobs.list().is_empty());
+        assert!(!jobs.cancel(0));
+    }
+}
@@ -1,34 +1,174 @@
-use std::{cmp, collections::Bound, mem, pin::Pin, sync::Arc};
+pub(crate) mod filter;
+pub(crate) mod job;
+pub(crate) mod tiered;
+
+use std::{
+    cmp,
+    collections::Bound,
+    mem,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+};
 
 use async_lock::{RwLock, RwLockUpgradableReadGuard};
+use flume::Sender;
 use fusio::DynFs;
 use fusio_parquet::writer::AsyncWriter;
-use futures_util::StreamExt;
-use parquet::arrow::{AsyncArrowWriter, ProjectionMask};
+use futures_util::{future::try_join_all, StreamExt};
+use parquet::{
+    arrow::{AsyncArrowWriter, ProjectionMask},
+    format::KeyValue,
+};
 use thiserror::Error;
 use tokio::sync::oneshot;
+use tracing::{debug, instrument};
+use web_time::{SystemTime, UNIX_EPOCH};
 
 use crate::{
-    fs::{manager::StoreManager, FileId, FileType},
-    inmem::{
-        immutable::{ArrowArrays, Builder, Immutable},
-        mutable::Mutable,
+    compaction::{
+        filter::FilterDecision,
+        job::Job,
+        tiered::{self, Run},
     },
+    fs::{self, manager::StoreManager, FileId},
+    inmem::immutable::{ArrowArrays, Builder, Immutable},
+    metrics::{self, Timer},
     ondisk::sstable::SsTable,
+    option::CompactionStyle,
+    range_tombstone::RangeTombstone,
     record::{KeyRef, Record, RecordInstance},
-    scope::Scope,
+    scope::{Scope, TableStats},
+    statistics::{InFlight, LevelStatistics},
     stream::{level::LevelStream, merge::MergeStream, ScanStream},
+    timestamp::Timestamp,
     transaction::CommitError,
     version::{
         edit::VersionEdit, set::VersionSet, TransactionTs, Version, VersionError, MAX_LEVEL,
     },
-    DbOption, ParquetLru, Schema,
+    CompactionScore, DbOption, ParquetLru, Schema,
 };
 
+/// the key of the parquet metadata marking SSTs below level 0 which kept superseded versions
+/// of their keys for the GC watermark
+pub(crate) const HISTORY_KEY: &str = "tonbo.history";
+
+/// the [`CompactionScore`] of each level a major compaction merges into the next one, in the
+/// order the background compactions pick them
+pub(crate) fn compaction_scores<R>(
+    option: &DbOption<R>,
+    version: &Version<R>,
+) -> Vec<CompactionScore>
+where
+    R: Record,
+{
+    let mut scores = (0..MAX_LEVEL - 2)
+        .map(|level| CompactionScore {
+            level,
+            score: option.compaction_score(version, level),
+            oldest: version.level_slice[level].iter().map(Scope::gen).min(),
+        })
+        .collect::<Vec<_>>();
+    scores.sort_by(|score, other| {
+        let rank = |score: &CompactionScore| (score.score < 1.0, score.level != 0);
+        rank(score)
+            .cmp(&rank(other))
+            .then(other.score.total_cmp(&score.score))
+            .then(score.oldest.cmp(&other.oldest))
+    });
+    scores
+}
+
+/// the level and the table below level 0 holding the largest share of tombstones among the
+/// ones over [`DbOption::tombstone_compaction_ratio`], the oldest one first among equals. The
+/// tables of level 0 may overlap older ones left behind, so they wait for their level to be
+/// compacted
+pub(crate) fn tombstone_candidate<'a, R>(
+    option: &DbOption<R>,
+    version: &'a Version<R>,
+) -> Option<(usize, &'a Scope<R::Key>)>
+where
+    R: Record,
+{
+    let ratio = |scope: &Scope<R::Key>| {
+        scope
+            .stats
+            .as_ref()
+            .map_or(0.0, |stats| stats.tombstones as f64 / stats.rows as f64)
+    };
+    (1..MAX_LEVEL - 1)
+        .flat_map(|level| {
+            version.level_slice[level]
+                .iter()
+                .map(move |scope| (level, scope))
+        })
+        .filter(|(_, scope)| option.is_tombstone_heavy(scope))
+        .min_by(|(_, scope), (_, other)| {
+            ratio(other)
+                .total_cmp(&ratio(scope))
+                .then(scope.gen.cmp(&other.gen))
+        })
+}
+
+/// the level and the oldest table written over [`DbOption::periodic_compaction_interval`]
+/// before `now`, in milliseconds since the Unix epoch
+pub(crate) fn periodic_candidate<'a, R>(
+    option: &DbOption<R>,
+    version: &'a Version<R>,
+    now: u64,
+) -> Option<(usize, &'a Scope<R::Key>)>
+where
+    R: Record,
+{
+    version
+        .level_slice
+        .iter()
+        .enumerate()
+        .flat_map(|(level, scopes)| scopes.iter().map(move |scope| (level, scope)))
+        .filter(|(_, scope)| option.is_periodic_due(scope.gen, now))
+        .min_by_key(|(_, scope)| scope.gen)
+}
+
+/// the level and the oldest table written before `written_before`, in milliseconds since the Unix
+/// epoch, which may hold the versions dropped by
+/// [`DB::purge_versions_older_than`](crate::DB::purge_versions_older_than)
+pub(crate) fn purge_candidate<R>(
+    version: &Version<R>,
+    written_before: u64,
+) -> Option<(usize, &Scope<R::Key>)>
+where
+    R: Record,
+{
+    version
+        .level_slice
+        .iter()
+        .enumerate()
+        .flat_map(|(level, scopes)| scopes.iter().map(move |scope| (level, scope)))
+        .filter(|(_, scope)| scope.gen.timestamp_ms() < written_before)
+        .min_by_key(|(_, scope)| scope.gen)
+}
+
+/// the wall clock in milliseconds
+pub(crate) fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_millis() as u64)
+}
+
 #[derive(Debug)]
 pub enum CompactTask {
     Freeze,
     Flush(Option<oneshot::Sender<()>>),
+    /// freeze the mutable memtable and flush every immutable whatever their thresholds, to
+    /// bring the memtables back within the budget of a
+    /// [`WriteBufferManager`](crate::WriteBufferManager) or past
+    /// [`DbOption::memtable_flush_interval`](crate::DbOption::memtable_flush_interval)
+    Release,
+    /// rewrite the tables holding the versions dropped by
+    /// [`DB::purge_versions_older_than`](crate::DB::purge_versions_older_than)
+    Purge,
 }
 
 pub(crate) struct Compactor<R>
@@ -39,6 +179,24 @@ where
     pub(crate) schema: Arc<RwLock<Schema<R>>>,
     pub(crate) version_set: VersionSet<R>,
     pub(crate) manager: Arc<StoreManager>,
+    /// wakes up the background compactions after a flush, if
+    /// [`DbOption::max_background_compactions`] is above 1
+    pub(crate) background: Option<Sender<()>>,
+}
+
+impl<R> Clone for Compactor<R>
+where
+    R: Record,
+{
+    fn clone(&self) -> Self {
+        Compactor {
+            option: self.option.clone(),
+            schema: self.schema.clone(),
+            version_set: self.version_set.clone(),
+            manager: self.manager.clone(),
+            background: self.background.clone(),
+        }
+    }
 }
 
 impl<R> Compactor<R>
@@ -56,37 +214,46 @@ where
             schema,
             version_set,
             manager,
+            background: None,
         }
     }
 
     pub(crate) async fn check_then_compaction(
         &mut self,
         parquet_lru: ParquetLru,
+        release: bool,
     ) -> Result<(), CompactionError<R>> {
+        // picks up the options set since the last run
+        self.option = self.version_set.option();
         let mut guard = self.schema.write().await;
 
         guard.trigger.reset();
 
-        if guard.mutable.is_empty() {
-            return Ok(());
+        // immutables may also be pushed by inserting record batches
+        if !guard.mutable.is_empty() {
+            let wal_fs = self.manager.get_fs(&self.option.wal_dir_path());
+            guard.freeze(&self.option, wal_fs).await?;
         }
-
-        let trigger_clone = guard.trigger.clone();
-        let mutable = mem::replace(
-            &mut guard.mutable,
-            Mutable::new(&self.option, trigger_clone, self.manager.base_fs()).await?,
-        );
-        let (file_id, immutable) = mutable.into_immutable(&guard.record_instance).await?;
-
-        guard.immutables.push((file_id, immutable));
-        if guard.immutables.len() > self.option.immutable_chunk_max_num {
+        // the WALs are only removed with their memtables flushed
+        let release = release || guard.is_wal_exceeded();
+        if guard.immutables.len() > self.option.immutable_chunk_max_num
+            || (release && !guard.immutables.is_empty())
+        {
             let recover_wal_ids = guard.recover_wal_ids.take();
             drop(guard);
 
             let guard = self.schema.upgradable_read().await;
-            let chunk_num = self.option.immutable_chunk_num;
+            let chunk_num = if release {
+                guard.immutables.len()
+            } else {
+                self.option.immutable_chunk_num
+            };
             let excess = &guard.immutables[0..chunk_num];
+            let listener = self.option.event_listener.as_ref();
 
+            if let Some(listener) = listener {
+                listener.on_flush_begin(excess.len());
+            }
             if let Some(scope) = Self::minor_compaction(
                 &self.option,
                 recover_wal_ids,
@@ -99,45 +266,533 @@ where
                 let version_ref = self.version_set.current().await;
                 let mut version_edits = vec![];
                 let mut delete_gens = vec![];
-
-                if self.option.is_threshold_exceeded_major(&version_ref, 0) {
-                    Self::major_compaction(
-                        &version_ref,
-                        &self.option,
-                        &scope.min,
-                        &scope.max,
-                        &mut version_edits,
-                        &mut delete_gens,
-                        &guard.record_instance,
-                        &self.manager,
-                        parquet_lru,
-                    )
-                    .await?;
+                // taken under the upgradable lock, which `DB::hold_history` waits for
+                let watermark = self.version_set.gc().get();
+                let job = self.version_set.jobs().start();
+
+                let compacted = match self.option.compaction_style {
+                    // left to the background compactions
+                    CompactionStyle::Leveled if self.background.is_some() => Ok(()),
+                    CompactionStyle::Leveled => {
+                        if self.option.is_threshold_exceeded_major(&version_ref, 0) {
+                            Self::major_compaction(
+                                &version_ref,
+                                &self.option,
+                                &scope.min,
+                                &scope.max,
+                                &mut version_edits,
+                                &mut delete_gens,
+                                &guard.record_instance,
+                                &self.manager,
+                                parquet_lru,
+                                watermark,
+                                &job,
+                            )
+                            .await
+                        } else if let Some((level, scope)) =
+                            tombstone_candidate(&self.option, &version_ref)
+                                .or_else(|| version_ref.seeks().hot(&version_ref))
+                                .or_else(|| {
+                                    periodic_candidate(&self.option, &version_ref, unix_millis())
+                                })
+                                .or_else(|| {
+                                    let purge = self.version_set.gc().purged()?;
+                                    purge_candidate(&version_ref, purge.written_before)
+                                })
+                        {
+                            Self::compact_table(
+                                &version_ref,
+                                &self.option,
+                                level,
+                                scope,
+                                &mut version_edits,
+                                &mut delete_gens,
+                                &guard.record_instance,
+                                &self.manager,
+                                parquet_lru,
+                                watermark,
+                                &job,
+                            )
+                            .await
+                        } else {
+                            Ok(())
+                        }
+                    }
+                    CompactionStyle::Tiered {
+                        max_runs,
+                        size_ratio,
+                        min_merge_width,
+                    } => {
+                        Self::tiered_compaction(
+                            &version_ref,
+                            &self.option,
+                            (max_runs, size_ratio, min_merge_width),
+                            &mut version_edits,
+                            &mut delete_gens,
+                            &guard.record_instance,
+                            &self.manager,
+                            parquet_lru,
+                            watermark,
+                            &job,
+                        )
+                        .await
+                    }
+                };
+                if let Err(err) = compacted {
+                    Self::discard(&self.option, &self.manager, &job).await?;
+                    if !matches!(err, CompactionError::Cancelled) {
+                        return Err(err);
+                    }
+                    // the flush is applied all the same
+                    version_edits.clear();
+                    delete_gens.clear();
                 }
+                let (inputs, outputs) = self.count_compaction(&version_edits).await?;
+                let flushed = scope.gen;
+                self.count_tables(&[(0, flushed)], |level| &level.compaction_bytes_written)
+                    .await?;
                 version_edits.insert(0, VersionEdit::Add { level: 0, scope });
+                for (_, immutable) in excess {
+                    for range_tombstone in immutable.range_tombstones.iter() {
+                        version_edits.push(VersionEdit::RangeTombstone {
+                            range_tombstone: range_tombstone.clone(),
+                        });
+                    }
+                }
                 version_edits.push(VersionEdit::LatestTimeStamp {
                     ts: version_ref.increase_ts(),
                 });
 
                 self.version_set
-                    .apply_edits(version_edits, Some(delete_gens), false)
+                    .apply_compaction_edits(version_edits, Some(delete_gens), false, watermark)
                     .await?;
+                if let Some(listener) = listener {
+                    listener.on_flush_complete(flushed);
+                    if !inputs.is_empty() {
+                        listener.on_compaction_complete(&inputs, &outputs);
+                    }
+                }
+                if let Some(background) = &self.background {
+                    // the ones running already pick up the level 0 they find over its threshold
+                    let _ = background.try_send(());
+                }
             }
             let mut guard = RwLockUpgradableReadGuard::upgrade(guard).await;
             let sources = guard.immutables.split_off(chunk_num);
             let _ = mem::replace(&mut guard.immutables, sources);
+            guard.account_memory();
+        } else {
+            guard.account_memory();
+        }
+        Ok(())
+    }
+
+    /// run the major compactions of the levels over their threshold, one level into the next at
+    /// a time, until none is left apart from the ones the other background compactions work on
+    pub(crate) async fn background_compaction(
+        &self,
+        parquet_lru: ParquetLru,
+    ) -> Result<(), CompactionError<R>> {
+        let instance = self.schema.read().await.record_instance.clone();
+        let option = self.version_set.option();
+        let listener = option.event_listener.as_ref();
+
+        loop {
+            let version = self.version_set.current().await;
+            let tombstones = tombstone_candidate(&option, &version);
+            let periodic = periodic_candidate(&option, &version, unix_millis());
+            let purge = self
+                .version_set
+                .gc()
+                .purged()
+                .and_then(|purge| purge_candidate(&version, purge.written_before));
+            let Some((level, scope, _reservation)) = compaction_scores(&option, &version)
+                .into_iter()
+                .filter(|score| score.score >= 1.0 && score.oldest.is_some())
+                .map(|score| (score.level, &version.level_slice[score.level][0]))
+                .chain(tombstones)
+                .chain(version.seeks().hot(&version))
+                .chain(periodic)
+                .chain(purge)
+                .find_map(|(level, scope)| {
+                    // a table of the last level is merged with the ones above overlapping it
+                    let levels = if level < MAX_LEVEL - 1 {
+                        level..level + 2
+                    } else {
+                        0..MAX_LEVEL
+                    };
+                    self.version_set
+                        .reserve(&version, levels)
+                        .map(|reservation| (level, scope, reservation))
+                })
+            else {
+                return Ok(());
+            };
+            debug!(level, gen = %scope.gen, "picked for a major compaction");
+            let (watermark, _compacting) = {
+                // taken under the same lock as by `DB::hold_history`, which then sees the
+                // history dropped by this compaction
+                let _schema = self.schema.upgradable_read().await;
+                let watermark = self.version_set.gc().get();
+                let floor = watermark.unwrap_or_else(|| self.version_set.load_ts());
+                (watermark, self.version_set.gc().compacting(floor))
+            };
+            let timer = Timer::start();
+            let mut version_edits = vec![];
+            let mut delete_gens = vec![];
+            let job = self.version_set.jobs().start();
+
+            let compacted = Self::compact_table(
+                &version,
+                &option,
+                level,
+                scope,
+                &mut version_edits,
+                &mut delete_gens,
+                &instance,
+                &self.manager,
+                parquet_lru.clone(),
+                watermark,
+                &job,
+            )
+            .await;
+            if let Err(err) = compacted {
+                Self::discard(&option, &self.manager, &job).await?;
+                return match err {
+                    // picked again by the next compaction woken up
+                    CompactionError::Cancelled => Ok(()),
+                    err => Err(err),
+                };
+            }
+            timer.record(metrics::COMPACTION_SECONDS);
+
+            let (inputs, outputs) = self.count_compaction(&version_edits).await?;
+            version_edits.push(VersionEdit::LatestTimeStamp {
+                ts: version.increase_ts(),
+            });
+            self.version_set
+                .apply_compaction_edits(version_edits, Some(delete_gens), false, watermark)
+                .await?;
+            if let Some(listener) = listener {
+                listener.on_compaction_complete(&inputs, &outputs);
+            }
+        }
+    }
+
+    /// rewrite the tables written before the last purge, by waking up the background compactions
+    /// if any. The tiered compactions drop the purged versions as they merge the runs
+    pub(crate) async fn purge(&self, parquet_lru: ParquetLru) -> Result<(), CompactionError<R>> {
+        match (
+            &self.version_set.option().compaction_style,
+            &self.background,
+        ) {
+            (CompactionStyle::Tiered { .. }, _) => Ok(()),
+            (CompactionStyle::Leveled, Some(background)) => {
+                let _ = background.try_send(());
+                Ok(())
+            }
+            (CompactionStyle::Leveled, None) => self.background_compaction(parquet_lru).await,
+        }
+    }
+
+    /// merge the tables overlapping `range` in the levels down to `target_level` into it, see
+    /// [`DB::compact_range`](crate::DB::compact_range)
+    pub(crate) async fn compact_range(
+        &self,
+        range: (Bound<&R::Key>, Bound<&R::Key>),
+        target_level: usize,
+        parquet_lru: ParquetLru,
+    ) -> Result<(), CompactionError<R>> {
+        if target_level >= MAX_LEVEL {
+            return Err(CompactionError::ExceedsMaxLevel);
+        }
+        // held until the edits are applied, so that neither the compaction of a flush nor
+        // `DB::hold_history` runs in between
+        let schema = self.schema.upgradable_read().await;
+        schema.check_writable().map_err(CommitError::from)?;
+        let version = self.version_set.current().await;
+        let scopes = Self::range_scopes(&version, range, target_level);
+        if scopes.is_empty() {
+            return Ok(());
+        }
+        let _reservation = self
+            .version_set
+            .reserve_tables(scopes.iter().map(|(_, scope)| scope.gen).collect())
+            .ok_or(CompactionError::Reserved)?;
+        let watermark = self.version_set.gc().get();
+        let timer = Timer::start();
+
+        let mut version_edits = vec![];
+        let mut delete_gens = vec![];
+        let job = self.version_set.jobs().start();
+        let merged = Self::merge_range(
+            &version,
+            &self.option,
+            scopes,
+            target_level,
+            &mut version_edits,
+            &mut delete_gens,
+            &schema.record_instance,
+            &self.manager,
+            parquet_lru,
+            watermark,
+            &job,
+        )
+        .await;
+        if let Err(err) = merged {
+            Self::discard(&self.option, &self.manager, &job).await?;
+            return Err(err);
+        }
+        timer.record(metrics::COMPACTION_SECONDS);
+
+        let (inputs, outputs) = self.count_compaction(&version_edits).await?;
+        version_edits.push(VersionEdit::LatestTimeStamp {
+            ts: version.increase_ts(),
+        });
+        self.version_set
+            .apply_compaction_edits(version_edits, Some(delete_gens), false, watermark)
+            .await?;
+        if let Some(listener) = self.option.event_listener.as_ref() {
+            listener.on_compaction_complete(&inputs, &outputs);
+        }
+        Ok(())
+    }
+
+    /// remove the tables written by a major compaction which did not complete, which no version
+    /// refers to
+    async fn discard(
+        option: &DbOption<R>,
+        manager: &StoreManager,
+        job: &Job,
+    ) -> Result<(), CompactionError<R>> {
+        for (level, gen) in job.outputs() {
+            let level_path = option.level_fs_path(level).unwrap_or(&option.base_path);
+            manager
+                .get_fs(level_path)
+                .remove(&option.table_path(gen, level))
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// merge the tables `scopes` of the levels down to `target_level` into it, which must hold
+    /// every table there overlapping them as picked by [`Compactor::range_scopes`]
+    #[allow(clippy::too_many_arguments)]
+    async fn merge_range(
+        version: &Version<R>,
+        option: &DbOption<R>,
+        scopes: Vec<(usize, &Scope<R::Key>)>,
+        target_level: usize,
+        version_edits: &mut Vec<VersionEdit<R::Key>>,
+        delete_gens: &mut Vec<(FileId, usize)>,
+        instance: &RecordInstance,
+        manager: &StoreManager,
+        parquet_lru: ParquetLru,
+        watermark: Option<Timestamp>,
+        job: &Job,
+    ) -> Result<(), CompactionError<R>> {
+        for (level, scope) in &scopes {
+            job.add_inputs(*level, [*scope]);
+        }
+        let mut streams = Vec::with_capacity(scopes.len());
+        for (level, tables) in version.level_slice[..=target_level].iter().enumerate() {
+            let level_path = option.level_fs_path(level).unwrap_or(&option.base_path);
+            let level_fs = manager.get_fs(level_path);
+            let picked = tables
+                .iter()
+                .enumerate()
+                .filter(|(_, table)| {
+                    scopes.iter().any(|(picked_level, scope)| {
+                        *picked_level == level && scope.gen == table.gen
+                    })
+                })
+                .map(|(index, _)| index)
+                .collect::<Vec<_>>();
+            let (Some(&start), Some(&end)) = (picked.first(), picked.last()) else {
+                continue;
+            };
+            if level == 0 {
+                for index in picked {
+                    let gen = tables[index].gen;
+                    let file = fs::open_table(
+                        level_fs,
+                        &option.table_path(gen, 0),
+                        true,
+                        option.level_direct_io(0),
+                    )
+                    .await?;
+
+                    streams.push(ScanStream::SsTable {
+                        inner: SsTable::open(parquet_lru.clone(), gen, file)
+                            .await?
+                            .count_reads(job.bytes_read())
+                            .readahead(option.scan_readahead_bytes)
+                            .scan(
+                                (Bound::Unbounded, Bound::Unbounded),
+                                u64::MAX.into(),
+                                None,
+                                ProjectionMask::all(),
+                            )
+                            .await?
+                            .prefetch(option.scan_prefetch_batches),
+                    });
+                }
+            } else {
+                // the tables of a level overlapping a range follow each other
+                let level_scan = LevelStream::new(
+                    version,
+                    level,
+                    start,
+                    end,
+                    (Bound::Unbounded, Bound::Unbounded),
+                    u64::MAX.into(),
+                    None,
+                    ProjectionMask::all(),
+                    level_fs.clone(),
+                    parquet_lru.clone(),
+                )
+                .ok_or(CompactionError::EmptyLevel)?
+                .count_reads(job.bytes_read());
+
+                streams.push(ScanStream::Level { inner: level_scan });
+            }
+        }
+        let target_path = option
+            .level_fs_path(target_level)
+            .unwrap_or(&option.base_path);
+        let older = version.level_slice[target_level + 1..]
+            .iter()
+            .flatten()
+            .collect::<Vec<_>>();
+        Self::build_tables(
+            option,
+            version_edits,
+            target_level,
+            streams,
+            &older,
+            version.range_tombstones.to_vec(),
+            instance,
+            manager.get_fs(target_path),
+            &version.statistics().compaction_memory,
+            watermark,
+            job,
+        )
+        .await?;
+        let inputs = scopes
+            .iter()
+            .map(|(_, scope)| scope.gen)
+            .collect::<Vec<_>>();
+        Self::drop_range_tombstones(version, &inputs, watermark, version_edits);
+        for (level, scope) in scopes {
+            version_edits.push(VersionEdit::Remove {
+                level: level as u8,
+                gen: scope.gen,
+            });
+            delete_gens.push((scope.gen, level));
+        }
+
+        Ok(())
+    }
+
+    /// the tables of the levels down to `target_level` overlapping `range`, widened to the ones
+    /// overlapping their keys until none left behind there overlaps the merged table
+    fn range_scopes<'a>(
+        version: &'a Version<R>,
+        range: (Bound<&R::Key>, Bound<&R::Key>),
+        target_level: usize,
+    ) -> Vec<(usize, &'a Scope<R::Key>)> {
+        let tables = || {
+            version.level_slice[..=target_level]
+                .iter()
+                .enumerate()
+                .flat_map(|(level, scopes)| scopes.iter().map(move |scope| (level, scope)))
+        };
+        let mut picked = tables()
+            .filter(|(_, scope)| scope.meets_range(range))
+            .collect::<Vec<_>>();
+        loop {
+            let (Some(min), Some(max)) = (
+                picked.iter().map(|(_, scope)| &scope.min).min(),
+                picked.iter().map(|(_, scope)| &scope.max).max(),
+            ) else {
+                return picked;
+            };
+            let widened = tables()
+                .filter(|(_, scope)| &scope.min <= max && min <= &scope.max)
+                .collect::<Vec<_>>();
+            if widened.len() == picked.len() {
+                return picked;
+            }
+            picked = widened;
+        }
+    }
+
+    /// the tables removed and added by the edits of a major compaction, counting the bytes
+    /// read and written by it
+    async fn count_compaction(
+        &self,
+        version_edits: &[VersionEdit<R::Key>],
+    ) -> Result<(Vec<(usize, FileId)>, Vec<(usize, FileId)>), CompactionError<R>> {
+        let (mut inputs, mut outputs) = (Vec::new(), Vec::new());
+        for edit in version_edits.iter() {
+            match edit {
+                VersionEdit::Remove { level, gen } => inputs.push((*level as usize, *gen)),
+                VersionEdit::Add { level, scope } => outputs.push((*level as usize, scope.gen)),
+                _ => (),
+            }
+        }
+        // the tables moved to the next level are neither read nor written
+        let rewritten = |tables: &[(usize, FileId)], others: &[(usize, FileId)]| {
+            tables
+                .iter()
+                .filter(|(_, gen)| others.iter().all(|(_, other)| other != gen))
+                .copied()
+                .collect::<Vec<_>>()
+        };
+        self.count_tables(&rewritten(&inputs, &outputs), |level| {
+            &level.compaction_bytes_read
+        })
+        .await?;
+        self.count_tables(&rewritten(&outputs, &inputs), |level| {
+            &level.compaction_bytes_written
+        })
+        .await?;
+
+        Ok((inputs, outputs))
+    }
+
+    /// add the sizes of the SSTs to the counters of their levels
+    async fn count_tables(
+        &self,
+        tables: &[(usize, FileId)],
+        counter: impl Fn(&LevelStatistics) -> &AtomicU64,
+    ) -> Result<(), CompactionError<R>> {
+        let statistics = self.version_set.statistics();
+        for (level, gen) in tables {
+            let level_path = self
+                .option
+                .level_fs_path(*level)
+                .unwrap_or(&self.option.base_path);
+            let bytes = fs::file_size(
+                self.manager.get_fs(level_path),
+                &self.option.table_path(*gen, *level),
+            )
+            .await?;
+            counter(&statistics.levels[*level]).fetch_add(bytes, Ordering::Relaxed);
         }
         Ok(())
     }
 
+    #[instrument(name = "flush", level = "debug", skip_all, fields(batches = batches.len()))]
     pub(crate) async fn minor_compaction(
         option: &DbOption<R>,
         recover_wal_ids: Option<Vec<FileId>>,
-        batches: &[(Option<FileId>, Immutable<R::Columns>)],
+        batches: &[(Vec<FileId>, Immutable<R::Columns>)],
         instance: &RecordInstance,
         manager: &StoreManager,
     ) -> Result<Option<Scope<R::Key>>, CompactionError<R>> {
         if !batches.is_empty() {
+            let timer = Timer::start();
             let level_0_path = option.level_fs_path(0).unwrap_or(&option.base_path);
             let level_0_fs = manager.get_fs(level_0_path);
 
@@ -145,25 +800,30 @@ where
             let mut max = None;
 
             let gen = FileId::new();
+            let path = option.table_path(gen, 0);
             let mut wal_ids = Vec::with_capacity(batches.len());
+            let mut tombstones = 0;
 
+            let preallocation = option.level_preallocation(
+                0,
+                batches
+                    .iter()
+                    .map(|(_, batch)| batch.as_record_batch().get_array_memory_size() as u64)
+                    .sum(),
+            );
             let mut writer = AsyncArrowWriter::try_new(
                 AsyncWriter::new(
-                    level_0_fs
-                        .open_options(
-                            &option.table_path(gen, 0),
-                            FileType::Parquet.open_options(false),
-                        )
+                    fs::create_table(level_0_fs, &path, option.level_direct_io(0), preallocation)
                         .await?,
                 ),
                 instance.arrow_schema::<R>().clone(),
-                Some(option.write_parquet_properties.clone()),
+                Some(option.sst_properties(0)),
             )?;
 
             if let Some(mut recover_wal_ids) = recover_wal_ids {
                 wal_ids.append(&mut recover_wal_ids);
             }
-            for (file_id, batch) in batches {
+            for (batch_wal_ids, batch) in batches {
                 if let (Some(batch_min), Some(batch_max)) = batch.scope() {
                     if matches!(min.as_ref().map(|min| min > batch_min), Some(true) | None) {
                         min = Some(batch_min.clone())
@@ -173,22 +833,32 @@ where
                     }
                 }
                 writer.write(batch.as_record_batch()).await?;
-                if let Some(file_id) = file_id {
-                    wal_ids.push(*file_id);
-                }
+                tombstones += TableStats::tombstones(batch.as_record_batch());
+                wal_ids.extend(batch_wal_ids);
+            }
+            metrics::sst_written(0, writer.bytes_written() + writer.in_progress_size());
+            let metadata = writer.close().await?;
+            if preallocation.is_some() {
+                fs::truncate_table(&path)?;
             }
-            writer.close().await?;
+            timer.record(metrics::FLUSH_SECONDS);
             return Ok(Some(Scope {
                 min: min.ok_or(CompactionError::EmptyLevel)?,
                 max: max.ok_or(CompactionError::EmptyLevel)?,
                 gen,
                 wal_ids: Some(wal_ids),
+                stats: Some(TableStats::new(
+                    &instance.arrow_schema::<R>(),
+                    &metadata,
+                    tombstones,
+                )?),
             }));
         }
         Ok(None)
     }
 
     #[allow(clippy::too_many_arguments)]
+    #[instrument(level = "debug", skip_all, fields(?min, ?max))]
     pub(crate) async fn major_compaction(
         version: &Version<R>,
         option: &DbOption<R>,
@@ -199,57 +869,189 @@ where
         instance: &RecordInstance,
         manager: &StoreManager,
         parquet_lru: ParquetLru,
+        watermark: Option<Timestamp>,
+        job: &Job,
     ) -> Result<(), CompactionError<R>> {
+        let timer = Timer::start();
         let mut level = 0;
 
         while level < MAX_LEVEL - 2 {
             if !option.is_threshold_exceeded_major(version, level) {
                 break;
             }
-            let (meet_scopes_l, start_l, end_l) = Self::this_level_scopes(version, min, max, level);
-            let (meet_scopes_ll, start_ll, end_ll) =
-                Self::next_level_scopes(version, &mut min, &mut max, level, &meet_scopes_l)?;
+            Self::compact_level(
+                version,
+                option,
+                level,
+                &mut min,
+                &mut max,
+                version_edits,
+                delete_gens,
+                instance,
+                manager,
+                parquet_lru.clone(),
+                watermark,
+                job,
+            )
+            .await?;
+            level += 1;
+        }
+        timer.record(metrics::COMPACTION_SECONDS);
 
-            let level_path = option.level_fs_path(level).unwrap_or(&option.base_path);
-            let level_fs = manager.get_fs(level_path);
+        Ok(())
+    }
+
+    /// merge `scope` of `level` into the next level along with the tables overlapping it, or
+    /// back into the last level if it is there
+    #[allow(clippy::too_many_arguments)]
+    async fn compact_table(
+        version: &Version<R>,
+        option: &DbOption<R>,
+        level: usize,
+        scope: &Scope<R::Key>,
+        version_edits: &mut Vec<VersionEdit<R::Key>>,
+        delete_gens: &mut Vec<(FileId, usize)>,
+        instance: &RecordInstance,
+        manager: &StoreManager,
+        parquet_lru: ParquetLru,
+        watermark: Option<Timestamp>,
+        job: &Job,
+    ) -> Result<(), CompactionError<R>> {
+        if level < MAX_LEVEL - 1 {
+            let (mut min, mut max) = (&scope.min, &scope.max);
+            return Self::compact_level(
+                version,
+                option,
+                level,
+                &mut min,
+                &mut max,
+                version_edits,
+                delete_gens,
+                instance,
+                manager,
+                parquet_lru,
+                watermark,
+                job,
+            )
+            .await;
+        }
+        let range = (Bound::Included(&scope.min), Bound::Included(&scope.max));
+        Self::merge_range(
+            version,
+            option,
+            Self::range_scopes(version, range, level),
+            level,
+            version_edits,
+            delete_gens,
+            instance,
+            manager,
+            parquet_lru,
+            watermark,
+            job,
+        )
+        .await
+    }
+
+    /// merge the tables of `level` overlapping `min` and `max` into the next level, widening
+    /// them to the tables of the next level merged along
+    #[allow(clippy::too_many_arguments)]
+    async fn compact_level<'a>(
+        version: &'a Version<R>,
+        option: &DbOption<R>,
+        level: usize,
+        min: &mut &'a R::Key,
+        max: &mut &'a R::Key,
+        version_edits: &mut Vec<VersionEdit<R::Key>>,
+        delete_gens: &mut Vec<(FileId, usize)>,
+        instance: &RecordInstance,
+        manager: &StoreManager,
+        parquet_lru: ParquetLru,
+        watermark: Option<Timestamp>,
+        job: &Job,
+    ) -> Result<(), CompactionError<R>> {
+        let (meet_scopes_l, start_l, end_l) = Self::this_level_scopes(version, *min, *max, level);
+        if Self::is_trivial_move(version, option, level, &meet_scopes_l) {
+            // added to the next level before being removed from this one, so that the
+            // recovery does not clean up their files
+            for scope in meet_scopes_l {
+                version_edits.push(VersionEdit::Add {
+                    level: (level + 1) as u8,
+                    scope: Scope {
+                        wal_ids: None,
+                        ..scope.clone()
+                    },
+                });
+                version_edits.push(VersionEdit::Remove {
+                    level: level as u8,
+                    gen: scope.gen,
+                });
+            }
+            return Ok(());
+        }
+        let (meet_scopes_ll, start_ll, end_ll) =
+            Self::next_level_scopes(version, min, max, level, &meet_scopes_l)?;
+        job.add_inputs(level, meet_scopes_l.iter().copied());
+        job.add_inputs(level + 1, meet_scopes_ll.iter().copied());
+
+        let level_path = option.level_fs_path(level).unwrap_or(&option.base_path);
+        let level_fs = manager.get_fs(level_path);
+        let boundaries = Self::subcompaction_boundaries(option, &meet_scopes_l, &meet_scopes_ll);
+        let older = version.level_slice[level + 2..]
+            .iter()
+            .flatten()
+            .chain(version.level_slice[0].iter().filter(|scope| {
+                level == 0 && meet_scopes_l.iter().all(|meet| meet.gen != scope.gen)
+            }))
+            .collect::<Vec<_>>();
+        let older = &older;
+        let mut subcompactions = Vec::with_capacity(boundaries.len() + 1);
+        // the key space is split into disjoint ranges merged concurrently into their own
+        // tables, the versions of a key all fall into one of them
+        for i in 0..=boundaries.len() {
+            let range = (
+                i.checked_sub(1)
+                    .map_or(Bound::Unbounded, |i| Bound::Included(boundaries[i])),
+                boundaries
+                    .get(i)
+                    .map_or(Bound::Unbounded, |boundary| Bound::Excluded(*boundary)),
+            );
             let mut streams = Vec::with_capacity(meet_scopes_l.len() + meet_scopes_ll.len());
             // This Level
             if level == 0 {
                 for scope in meet_scopes_l.iter() {
-                    let file = level_fs
-                        .open_options(
-                            &option.table_path(scope.gen, level),
-                            FileType::Parquet.open_options(true),
-                        )
-                        .await?;
+                    let file = fs::open_table(
+                        level_fs,
+                        &option.table_path(scope.gen, level),
+                        true,
+                        option.level_direct_io(level),
+                    )
+                    .await?;
 
                     streams.push(ScanStream::SsTable {
                         inner: SsTable::open(parquet_lru.clone(), scope.gen, file)
                             .await?
-                            .scan(
-                                (Bound::Unbounded, Bound::Unbounded),
-                                u32::MAX.into(),
-                                None,
-                                ProjectionMask::all(),
-                            )
-                            .await?,
+                            .count_reads(job.bytes_read())
+                            .readahead(option.scan_readahead_bytes)
+                            .scan(range, u64::MAX.into(), None, ProjectionMask::all())
+                            .await?
+                            .prefetch(option.scan_prefetch_batches),
                     });
                 }
             } else {
-                let (lower, upper) = Self::full_scope(&meet_scopes_l)?;
                 let level_scan_l = LevelStream::new(
                     version,
                     level,
                     start_l,
                     end_l,
-                    (Bound::Included(lower), Bound::Included(upper)),
-                    u32::MAX.into(),
+                    Self::narrow(&meet_scopes_l, range)?,
+                    u64::MAX.into(),
                     None,
                     ProjectionMask::all(),
                     level_fs.clone(),
                     parquet_lru.clone(),
                 )
-                .ok_or(CompactionError::EmptyLevel)?;
+                .ok_or(CompactionError::EmptyLevel)?
+                .count_reads(job.bytes_read());
 
                 streams.push(ScanStream::Level {
                     inner: level_scan_l,
@@ -257,55 +1059,274 @@ where
             }
             if !meet_scopes_ll.is_empty() {
                 // Next Level
-                let (lower, upper) = Self::full_scope(&meet_scopes_ll)?;
                 let level_scan_ll = LevelStream::new(
                     version,
                     level + 1,
                     start_ll,
                     end_ll,
-                    (Bound::Included(lower), Bound::Included(upper)),
-                    u32::MAX.into(),
+                    Self::narrow(&meet_scopes_ll, range)?,
+                    u64::MAX.into(),
                     None,
                     ProjectionMask::all(),
                     level_fs.clone(),
                     parquet_lru.clone(),
                 )
-                .ok_or(CompactionError::EmptyLevel)?;
+                .ok_or(CompactionError::EmptyLevel)?
+                .count_reads(job.bytes_read());
 
                 streams.push(ScanStream::Level {
                     inner: level_scan_ll,
                 });
             }
-            Self::build_tables(
-                option,
-                version_edits,
-                level + 1,
-                streams,
-                instance,
-                level_fs,
-            )
-            .await?;
+            subcompactions.push(async move {
+                let mut version_edits = Vec::new();
+                Self::build_tables(
+                    option,
+                    &mut version_edits,
+                    level + 1,
+                    streams,
+                    older,
+                    version.range_tombstones.to_vec(),
+                    instance,
+                    level_fs,
+                    &version.statistics().compaction_memory,
+                    watermark,
+                    job,
+                )
+                .await
+                .map(|_| version_edits)
+            });
+        }
+        for edits in try_join_all(subcompactions).await? {
+            version_edits.extend(edits);
+        }
+        let inputs = meet_scopes_l
+            .iter()
+            .chain(meet_scopes_ll.iter())
+            .map(|scope| scope.gen)
+            .collect::<Vec<_>>();
+        Self::drop_range_tombstones(version, &inputs, watermark, version_edits);
+
+        for scope in meet_scopes_l {
+            version_edits.push(VersionEdit::Remove {
+                level: level as u8,
+                gen: scope.gen,
+            });
+            delete_gens.push((scope.gen, level));
+        }
+        for scope in meet_scopes_ll {
+            version_edits.push(VersionEdit::Remove {
+                level: (level + 1) as u8,
+                gen: scope.gen,
+            });
+            delete_gens.push((scope.gen, level));
+        }
 
-            for scope in meet_scopes_l {
-                version_edits.push(VersionEdit::Remove {
-                    level: level as u8,
-                    gen: scope.gen,
-                });
-                delete_gens.push((scope.gen, level));
+        Ok(())
+    }
+
+    /// merge the sorted runs picked by [`tiered::pick`] into the deepest level among them
+    #[allow(clippy::too_many_arguments)]
+    #[instrument(level = "debug", skip_all)]
+    pub(crate) async fn tiered_compaction(
+        version: &Version<R>,
+        option: &DbOption<R>,
+        (max_runs, size_ratio, min_merge_width): (usize, usize, usize),
+        version_edits: &mut Vec<VersionEdit<R::Key>>,
+        delete_gens: &mut Vec<(FileId, usize)>,
+        instance: &RecordInstance,
+        manager: &StoreManager,
+        parquet_lru: ParquetLru,
+        watermark: Option<Timestamp>,
+        job: &Job,
+    ) -> Result<(), CompactionError<R>> {
+        let timer = Timer::start();
+        let mut runs = Vec::new();
+        for (index, scope) in version.level_slice[0].iter().enumerate().rev() {
+            let bytes = Self::run_bytes(option, manager, 0, std::slice::from_ref(scope)).await?;
+            runs.push((Run::Table(index), bytes));
+        }
+        for (level, scopes) in version.level_slice.iter().enumerate().skip(1) {
+            if !scopes.is_empty() {
+                let bytes = Self::run_bytes(option, manager, level, scopes).await?;
+                runs.push((Run::Level(level), bytes));
             }
-            for scope in meet_scopes_ll {
-                version_edits.push(VersionEdit::Remove {
-                    level: (level + 1) as u8,
-                    gen: scope.gen,
-                });
-                delete_gens.push((scope.gen, level));
+        }
+        let Some((window, output)) = tiered::pick(&runs, max_runs, size_ratio, min_merge_width)
+        else {
+            return Ok(());
+        };
+
+        let mut streams = Vec::with_capacity(window.len());
+        let mut inputs = Vec::new();
+        for (run, _) in &runs[window] {
+            match *run {
+                Run::Table(index) => {
+                    let scope = &version.level_slice[0][index];
+                    let level_path = option.level_fs_path(0).unwrap_or(&option.base_path);
+                    let file = fs::open_table(
+                        manager.get_fs(level_path),
+                        &option.table_path(scope.gen, 0),
+                        true,
+                        option.level_direct_io(0),
+                    )
+                    .await?;
+
+                    streams.push(ScanStream::SsTable {
+                        inner: SsTable::open(parquet_lru.clone(), scope.gen, file)
+                            .await?
+                            .count_reads(job.bytes_read())
+                            .readahead(option.scan_readahead_bytes)
+                            .scan(
+                                (Bound::Unbounded, Bound::Unbounded),
+                                u64::MAX.into(),
+                                None,
+                                ProjectionMask::all(),
+                            )
+                            .await?
+                            .prefetch(option.scan_prefetch_batches),
+                    });
+                    inputs.push((0, scope.gen));
+                    job.add_inputs(0, [scope]);
+                }
+                Run::Level(level) => {
+                    let scopes = &version.level_slice[level];
+                    let level_path = option.level_fs_path(level).unwrap_or(&option.base_path);
+                    let level_scan = LevelStream::new(
+                        version,
+                        level,
+                        0,
+                        scopes.len() - 1,
+                        (Bound::Unbounded, Bound::Unbounded),
+                        u64::MAX.into(),
+                        None,
+                        ProjectionMask::all(),
+                        manager.get_fs(level_path).clone(),
+                        parquet_lru.clone(),
+                    )
+                    .ok_or(CompactionError::EmptyLevel)?
+                    .count_reads(job.bytes_read());
+
+                    streams.push(ScanStream::Level { inner: level_scan });
+                    inputs.extend(scopes.iter().map(|scope| (level, scope.gen)));
+                    job.add_inputs(level, scopes);
+                }
             }
-            level += 1;
         }
+        let output_path = option.level_fs_path(output).unwrap_or(&option.base_path);
+        // the runs left out are the newer tables of level 0 and the levels below the output
+        let older = version.level_slice[output + 1..]
+            .iter()
+            .flatten()
+            .collect::<Vec<_>>();
+        Self::build_tables(
+            option,
+            version_edits,
+            output,
+            streams,
+            &older,
+            version.range_tombstones.to_vec(),
+            instance,
+            manager.get_fs(output_path),
+            &version.statistics().compaction_memory,
+            watermark,
+            job,
+        )
+        .await?;
+        let gens = inputs.iter().map(|(_, gen)| *gen).collect::<Vec<_>>();
+        Self::drop_range_tombstones(version, &gens, watermark, version_edits);
+
+        for (level, gen) in inputs {
+            version_edits.push(VersionEdit::Remove {
+                level: level as u8,
+                gen,
+            });
+            delete_gens.push((gen, level));
+        }
+        timer.record(metrics::COMPACTION_SECONDS);
 
         Ok(())
     }
 
+    /// the bytes of the SSTs of a sorted run
+    async fn run_bytes(
+        option: &DbOption<R>,
+        manager: &StoreManager,
+        level: usize,
+        scopes: &[Scope<R::Key>],
+    ) -> Result<u64, CompactionError<R>> {
+        let level_path = option.level_fs_path(level).unwrap_or(&option.base_path);
+        let mut bytes = 0;
+        for scope in scopes {
+            bytes += fs::file_size(
+                manager.get_fs(level_path),
+                &option.table_path(scope.gen, level),
+            )
+            .await?;
+        }
+        Ok(bytes)
+    }
+
+    /// the keys splitting the tables merged by a major compaction into at most
+    /// [`DbOption::max_subcompactions`] ranges, taken from the smallest keys of the tables
+    fn subcompaction_boundaries<'a>(
+        option: &DbOption<R>,
+        meet_scopes_l: &[&'a Scope<R::Key>],
+        meet_scopes_ll: &[&'a Scope<R::Key>],
+    ) -> Vec<&'a R::Key> {
+        let mut keys = meet_scopes_l
+            .iter()
+            .chain(meet_scopes_ll)
+            .copied()
+            .map(|scope| &scope.min)
+            .collect::<Vec<_>>();
+        keys.sort();
+        keys.dedup();
+        // the smallest key would leave the first range empty
+        let candidates = keys.get(1..).unwrap_or_default();
+        let splits = option
+            .max_subcompactions
+            .saturating_sub(1)
+            .min(candidates.len());
+
+        (0..splits)
+            .map(|i| candidates[(i + 1) * candidates.len() / (splits + 1)])
+            .collect()
+    }
+
+    /// whether the tables of `level` can be moved to the next level by a version edit alone,
+    /// which holds if they are stored at the same path on both levels and overlap neither each
+    /// other, nor a table of the next level, nor a table of level 0 left behind. Tables heavy
+    /// with tombstones or due for a periodic compaction are rewritten instead
+    fn is_trivial_move(
+        version: &Version<R>,
+        option: &DbOption<R>,
+        level: usize,
+        scopes: &[&Scope<R::Key>],
+    ) -> bool {
+        let overlaps = |scope: &Scope<R::Key>, other: &Scope<R::Key>| {
+            scope.min <= other.max && other.min <= scope.max
+        };
+        let moved = |other: &Scope<R::Key>| scopes.iter().any(|scope| scope.gen == other.gen);
+        let now = unix_millis();
+
+        !scopes.is_empty()
+            && scopes.iter().enumerate().all(|(i, scope)| {
+                !option.is_tombstone_heavy(scope)
+                    && !option.is_periodic_due(scope.gen, now)
+                    && option.table_path(scope.gen, level)
+                        == option.table_path(scope.gen, level + 1)
+                    && scopes[i + 1..].iter().all(|other| !overlaps(scope, other))
+                    && version.level_slice[level + 1]
+                        .iter()
+                        .all(|other| !overlaps(scope, other))
+                    && (level > 0
+                        || version.level_slice[0]
+                            .iter()
+                            .all(|other| moved(other) || !overlaps(scope, other)))
+            })
+    }
+
     fn next_level_scopes<'a>(
         version: &'a Version<R>,
         min: &mut &'a <R as Record>::Key,
@@ -381,58 +1402,150 @@ where
                 meet_scopes_l.push(scope);
             }
         }
-        (meet_scopes_l, start_l, end_l - 1)
+        (meet_scopes_l, start_l, end_l - 1)
+    }
+
+    /// drop the range tombstones applied by [`Self::build_tables`] to the tables `inputs` once no
+    /// other table holds a key they delete, so that only the tombstones still hiding records are
+    /// kept in the version
+    fn drop_range_tombstones(
+        version: &Version<R>,
+        inputs: &[FileId],
+        watermark: Option<Timestamp>,
+        version_edits: &mut Vec<VersionEdit<R::Key>>,
+    ) {
+        for range_tombstone in version.range_tombstones.iter() {
+            if watermark.is_some_and(|watermark| range_tombstone.ts > watermark) {
+                continue;
+            }
+            let range = (
+                range_tombstone.lower.as_ref(),
+                range_tombstone.upper.as_ref(),
+            );
+            let is_left = version
+                .level_slice
+                .iter()
+                .flatten()
+                .any(|scope| !inputs.contains(&scope.gen) && scope.meets_range(range));
+            if !is_left {
+                version_edits.push(VersionEdit::RemoveRangeTombstone {
+                    ts: range_tombstone.ts,
+                });
+            }
+        }
     }
 
+    #[allow(clippy::too_many_arguments)]
     async fn build_tables<'scan>(
         option: &DbOption<R>,
         version_edits: &mut Vec<VersionEdit<<R as Record>::Key>>,
         level: usize,
         streams: Vec<ScanStream<'scan, R>>,
+        older: &[&Scope<R::Key>],
+        range_tombstones: Vec<RangeTombstone<R::Key>>,
         instance: &RecordInstance,
         fs: &Arc<dyn DynFs>,
+        memory: &AtomicUsize,
+        watermark: Option<Timestamp>,
+        job: &Job,
     ) -> Result<(), CompactionError<R>> {
-        let mut stream = MergeStream::<R>::from_vec(streams, u32::MAX.into()).await?;
+        let mut range_tombstones = range_tombstones;
+        if let Some(watermark) = watermark {
+            // newer ones still let the reads before them see the records they cover
+            range_tombstones.retain(|range_tombstone| range_tombstone.ts <= watermark);
+        }
+        // records hidden by range tombstones are dropped instead of being rewritten
+        let mut stream =
+            MergeStream::<R>::with_range_tombstones(streams, u64::MAX.into(), range_tombstones)
+                .await?
+                .paranoid(option.paranoid_checks);
+        if let Some(watermark) = watermark {
+            stream = stream.keep_versions_after(watermark);
+        }
 
         // Kould: is the capacity parameter necessary?
         let mut builder = R::Columns::builder(&instance.arrow_schema::<R>(), 8192);
+        let mut table = None;
         let mut min = None;
         let mut max = None;
+        let mut history = false;
+        let mut in_flight = InFlight::new(memory);
 
         while let Some(result) = Pin::new(&mut stream).next().await {
             let entry = result?;
             let key = entry.key();
+            let owned_key = key.value.clone().to_key();
 
-            if min.is_none() {
-                min = Some(key.value.clone().to_key())
+            if entry.value().is_none()
+                && watermark.is_none()
+                && older.iter().all(|scope| !scope.contains(&owned_key))
+            {
+                // neither a read nor an older table is left for the tombstone to hide a record
+                // from
+                continue;
             }
-            max = Some(key.value.clone().to_key());
-            builder.push(key, entry.value());
-
-            if builder.written_size() >= option.max_sst_file_size {
+            if max.as_ref() == Some(&owned_key) {
+                history = true;
+            } else if table
+                .as_ref()
+                .map_or(0, |table: &OutputTable| table.written)
+                + builder.written_size()
+                >= option.max_sst_file_size
+            {
+                // the versions of a key stay in one table, so the tables of a level never overlap
                 Self::build_table(
                     option,
                     version_edits,
                     level,
                     &mut builder,
+                    &mut table,
                     &mut min,
                     &mut max,
+                    mem::take(&mut history),
                     instance,
                     fs,
+                    job,
                 )
                 .await?;
             }
+            if min.is_none() {
+                min = Some(owned_key.clone())
+            }
+            max = Some(owned_key);
+            match &option.compaction_filter {
+                Some(filter) => match filter.filter(level, key.value.clone(), entry.value()) {
+                    FilterDecision::Keep => builder.push(key, entry.value()),
+                    FilterDecision::Remove => builder.push(key, None),
+                    FilterDecision::Change(record) => {
+                        builder.push(key, Some(record.as_record_ref()))
+                    }
+                },
+                None => builder.push(key, entry.value()),
+            }
+            if builder.written_size() >= option.compaction_memory_budget {
+                Self::write_batch(option, level, &mut builder, &mut table, instance, fs, job)
+                    .await?;
+            }
+            in_flight.set(
+                builder.written_size()
+                    + table
+                        .as_ref()
+                        .map_or(0, |table| table.writer.in_progress_size()),
+            );
         }
-        if builder.written_size() > 0 {
+        if table.is_some() || builder.written_size() > 0 {
             Self::build_table(
                 option,
                 version_edits,
                 level,
                 &mut builder,
+                &mut table,
                 &mut min,
                 &mut max,
+                history,
                 instance,
                 fs,
+                job,
             )
             .await?;
         }
@@ -447,35 +1560,121 @@ where
         Ok((lower, upper))
     }
 
+    /// the `range` of a subcompaction narrowed to the keys of `meet_scopes`
+    fn narrow<'a>(
+        meet_scopes: &[&'a Scope<<R as Record>::Key>],
+        range: (Bound<&'a R::Key>, Bound<&'a R::Key>),
+    ) -> Result<(Bound<&'a R::Key>, Bound<&'a R::Key>), CompactionError<R>> {
+        let (lower, upper) = Self::full_scope(meet_scopes)?;
+        Ok((
+            match range.0 {
+                Bound::Included(key) if key > lower => Bound::Included(key),
+                _ => Bound::Included(lower),
+            },
+            match range.1 {
+                Bound::Excluded(key) if key <= upper => Bound::Excluded(key),
+                _ => Bound::Included(upper),
+            },
+        ))
+    }
+
+    /// write the records of `builder` to the output table, opened by the first batch, and end
+    /// its row group once it takes up [`DbOption::compaction_memory_budget`] in the writer. A
+    /// cancelled job stops here, leaving the tables it wrote to [`Compactor::discard`]
+    async fn write_batch(
+        option: &DbOption<R>,
+        level: usize,
+        builder: &mut <R::Columns as ArrowArrays>::Builder,
+        table: &mut Option<OutputTable>,
+        instance: &RecordInstance,
+        fs: &Arc<dyn DynFs>,
+        job: &Job,
+    ) -> Result<(), CompactionError<R>> {
+        if builder.written_size() == 0 {
+            return Ok(());
+        }
+        let table = match table {
+            Some(table) => table,
+            None => {
+                let gen = FileId::new();
+                // each output is as large as the inputs at most
+                let preallocation = option.level_preallocation(
+                    level,
+                    job.input_bytes().min(option.max_sst_file_size as u64),
+                );
+                let writer = AsyncArrowWriter::try_new(
+                    AsyncWriter::new(
+                        fs::create_table(
+                            fs,
+                            &option.table_path(gen, level),
+                            option.level_direct_io(level),
+                            preallocation,
+                        )
+                        .await?,
+                    ),
+                    instance.arrow_schema::<R>().clone(),
+                    Some(option.sst_properties(level)),
+                )?;
+                job.add_output(level, gen);
+                table.insert(OutputTable {
+                    gen,
+                    writer,
+                    written: 0,
+                    tombstones: 0,
+                    preallocated: preallocation.is_some(),
+                })
+            }
+        };
+        table.written += builder.written_size();
+        let columns = builder.finish(None);
+        table.tombstones += TableStats::tombstones(columns.as_record_batch());
+        table.writer.write(columns.as_record_batch()).await?;
+        if table.writer.in_progress_size() >= option.compaction_memory_budget {
+            table.writer.flush().await?;
+        }
+        if job.is_cancelled() {
+            return Err(CompactionError::Cancelled);
+        }
+        Ok(())
+    }
+
     #[allow(clippy::too_many_arguments)]
     async fn build_table(
         option: &DbOption<R>,
         version_edits: &mut Vec<VersionEdit<R::Key>>,
         level: usize,
         builder: &mut <R::Columns as ArrowArrays>::Builder,
+        table: &mut Option<OutputTable>,
         min: &mut Option<R::Key>,
         max: &mut Option<R::Key>,
+        history: bool,
         instance: &RecordInstance,
         fs: &Arc<dyn DynFs>,
+        job: &Job,
     ) -> Result<(), CompactionError<R>> {
         debug_assert!(min.is_some());
         debug_assert!(max.is_some());
 
-        let gen = FileId::new();
-        let columns = builder.finish(None);
-        let mut writer = AsyncArrowWriter::try_new(
-            AsyncWriter::new(
-                fs.open_options(
-                    &option.table_path(gen, level),
-                    FileType::Parquet.open_options(false),
-                )
-                .await?,
-            ),
-            instance.arrow_schema::<R>().clone(),
-            Some(option.write_parquet_properties.clone()),
-        )?;
-        writer.write(columns.as_record_batch()).await?;
-        writer.close().await?;
+        Self::write_batch(option, level, builder, table, instance, fs, job).await?;
+        let OutputTable {
+            gen,
+            mut writer,
+            tombstones,
+            preallocated,
+            ..
+        } = table.take().ok_or(CompactionError::EmptyLevel)?;
+        if history {
+            writer.append_key_value_metadata(KeyValue::new(
+                HISTORY_KEY.to_string(),
+                Some("true".to_string()),
+            ));
+        }
+        metrics::sst_written(level, writer.bytes_written() + writer.in_progress_size());
+        let metadata = writer.close().await?;
+        if preallocated {
+            fs::truncate_table(&option.table_path(gen, level))?;
+        }
+        let stats = TableStats::new(&instance.arrow_schema::<R>(), &metadata, tombstones)?;
         version_edits.push(VersionEdit::Add {
             level: level as u8,
             scope: Scope {
@@ -483,12 +1682,25 @@ where
                 max: max.take().ok_or(CompactionError::EmptyLevel)?,
                 gen,
                 wal_ids: None,
+                stats: Some(stats),
             },
         });
         Ok(())
     }
 }
 
+/// An SST being written by a major compaction, which the merged records are written to batch
+/// by batch instead of all at once when it is full.
+struct OutputTable {
+    gen: FileId,
+    writer: AsyncArrowWriter<AsyncWriter>,
+    /// the in-memory size of the records written, which the tables are split by
+    written: usize,
+    tombstones: u64,
+    /// whether its disk was reserved, see [`DbOption::preallocate_tables`]
+    preallocated: bool,
+}
+
 #[derive(Debug, Error)]
 pub enum CompactionError<R>
 where
@@ -508,28 +1720,45 @@ where
     Commit(#[from] CommitError<R>),
     #[error("the level being compacted does not have a table")]
     EmptyLevel,
+    #[error("exceeds the maximum level(0-6)")]
+    ExceedsMaxLevel,
+    #[error("the tables are merged by a background compaction already")]
+    Reserved,
+    #[error("the compaction is cancelled")]
+    Cancelled,
 }
 
 #[cfg(all(test, feature = "tokio"))]
 pub(crate) mod tests {
-    use std::sync::{atomic::AtomicU32, Arc};
+    use std::{
+        ops::Bound,
+        sync::{atomic::AtomicU64, Arc},
+        time::Duration,
+    };
 
     use flume::bounded;
     use fusio::{path::Path, DynFs};
     use fusio_dispatch::FsOptions;
     use fusio_parquet::writer::AsyncWriter;
-    use parquet::arrow::AsyncArrowWriter;
+    use futures_util::StreamExt;
+    use parquet::arrow::{AsyncArrowWriter, ProjectionMask};
     use parquet_lru::NoCache;
     use tempfile::TempDir;
 
     use crate::{
-        compaction::Compactor,
+        compaction::{
+            compaction_scores,
+            filter::{CompactionFilter, FilterDecision},
+            job::Jobs,
+            periodic_candidate, tombstone_candidate, CompactionError, Compactor,
+        },
         executor::tokio::TokioExecutor,
         fs::{manager::StoreManager, FileId, FileType},
         inmem::{immutable::Immutable, mutable::Mutable},
+        ondisk::sstable::SsTable,
         record::{Column, ColumnDesc, Datatype, DynRecord, Record, RecordInstance},
-        scope::Scope,
-        tests::Test,
+        scope::{Scope, TableStats},
+        tests::{Test, TestRef},
         timestamp::Timestamp,
         trigger::{TriggerFactory, TriggerType},
         version::{edit::VersionEdit, Version, MAX_LEVEL},
@@ -683,8 +1912,8 @@ pub(crate) mod tests {
             &option,
             None,
             &vec![
-                (Some(FileId::new()), batch_1),
-                (Some(FileId::new()), batch_2),
+                (vec![FileId::new()], batch_1),
+                (vec![FileId::new()], batch_2),
             ],
             &RecordInstance::Normal,
             &manager,
@@ -747,8 +1976,8 @@ pub(crate) mod tests {
             &option,
             None,
             &vec![
-                (Some(FileId::new()), batch_1),
-                (Some(FileId::new()), batch_2),
+                (vec![FileId::new()], batch_1),
+                (vec![FileId::new()], batch_2),
             ],
             &instance,
             &manager,
@@ -818,6 +2047,8 @@ pub(crate) mod tests {
             &RecordInstance::Normal,
             &manager,
             Arc::new(NoCache::default()),
+            None,
+            &Arc::<Jobs>::default().start(),
         )
         .await
         .unwrap();
@@ -850,6 +2081,110 @@ pub(crate) mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn major_compaction_filter() {
+        struct TestFilter;
+
+        impl CompactionFilter<Test> for TestFilter {
+            fn filter<'r>(
+                &self,
+                level: usize,
+                key: &'r str,
+                value: Option<TestRef<'r>>,
+            ) -> FilterDecision<Test> {
+                assert_eq!(level, 1);
+                match key {
+                    "2" => FilterDecision::Remove,
+                    "5" => FilterDecision::Change(Test {
+                        vstring: key.to_string(),
+                        vu32: 55,
+                        vbool: value.and_then(|value| value.vbool),
+                    }),
+                    _ => FilterDecision::Keep,
+                }
+            }
+        }
+
+        let temp_dir = TempDir::new().unwrap();
+
+        let mut option =
+            DbOption::<Test>::from(Path::from_filesystem_path(temp_dir.path()).unwrap())
+                .compaction_filter(TestFilter);
+        option.major_threshold_with_sst_size = 2;
+        let option = Arc::new(option);
+        let manager =
+            StoreManager::new(option.base_fs.clone(), option.level_paths.clone()).unwrap();
+
+        manager
+            .base_fs()
+            .create_dir_all(&option.version_log_dir_path())
+            .await
+            .unwrap();
+        manager
+            .base_fs()
+            .create_dir_all(&option.wal_dir_path())
+            .await
+            .unwrap();
+
+        let (_, version) = build_version(&option, &manager).await;
+
+        let min = 2.to_string();
+        let max = 5.to_string();
+        let mut version_edits = Vec::new();
+
+        Compactor::<Test>::major_compaction(
+            &version,
+            &option,
+            &min,
+            &max,
+            &mut version_edits,
+            &mut vec![],
+            &RecordInstance::Normal,
+            &manager,
+            Arc::new(NoCache::default()),
+            None,
+            &Arc::<Jobs>::default().start(),
+        )
+        .await
+        .unwrap();
+
+        let VersionEdit::Add { level, scope } = &version_edits[0] else {
+            unreachable!()
+        };
+        assert_eq!(*level, 1);
+
+        let file = manager
+            .base_fs()
+            .open_options(
+                &option.table_path(scope.gen, 1),
+                FileType::Parquet.open_options(true),
+            )
+            .await
+            .unwrap();
+        let mut scan = SsTable::<Test>::open(Arc::new(NoCache::default()), scope.gen, file)
+            .await
+            .unwrap()
+            .scan(
+                (Bound::Unbounded, Bound::Unbounded),
+                u64::MAX.into(),
+                None,
+                ProjectionMask::all(),
+            )
+            .await
+            .unwrap();
+
+        let mut count = 0;
+        while let Some(entry) = scan.next().await.transpose().unwrap() {
+            match entry.key() {
+                "2" => assert!(entry.get().is_none()),
+                "5" => assert_eq!(entry.get().unwrap().vu32, Some(55)),
+                _ => assert_eq!(entry.get().unwrap().vu32, Some(0)),
+            }
+            count += 1;
+        }
+        assert_eq!(count, 6);
+    }
+
     pub(crate) async fn build_version(
         option: &Arc<DbOption<Test>>,
         manager: &StoreManager,
@@ -1063,37 +2398,46 @@ pub(crate) mod tests {
         .unwrap();
 
         let (sender, _) = bounded(1);
-        let mut version =
-            Version::<Test>::new(option.clone(), sender, Arc::new(AtomicU32::default()));
+        let mut version = Version::<Test>::new(
+            option.clone(),
+            sender,
+            Arc::new(AtomicU64::default()),
+            Default::default(),
+        );
         version.level_slice[0].push(Scope {
             min: 1.to_string(),
             max: 3.to_string(),
             gen: table_gen_1,
             wal_ids: None,
+            stats: None,
         });
         version.level_slice[0].push(Scope {
             min: 4.to_string(),
             max: 6.to_string(),
             gen: table_gen_2,
             wal_ids: None,
+            stats: None,
         });
         version.level_slice[1].push(Scope {
             min: 1.to_string(),
             max: 3.to_string(),
             gen: table_gen_3,
             wal_ids: None,
+            stats: None,
         });
         version.level_slice[1].push(Scope {
             min: 4.to_string(),
             max: 6.to_string(),
             gen: table_gen_4,
             wal_ids: None,
+            stats: None,
         });
         version.level_slice[1].push(Scope {
             min: 7.to_string(),
             max: 9.to_string(),
             gen: table_gen_5,
             wal_ids: None,
+            stats: None,
         });
         (
             (
@@ -1107,6 +2451,306 @@ pub(crate) mod tests {
         )
     }
 
+    #[test]
+    fn compaction_priority() {
+        let option = DbOption::<Test>::from(Path::from("compaction_priority"))
+            .major_threshold_with_sst_size(2)
+            .level_sst_magnification(2);
+        let (sender, _) = bounded(1);
+        let mut version = Version::<Test>::new(
+            Arc::new(option.clone()),
+            sender,
+            Arc::new(AtomicU64::default()),
+            Default::default(),
+        );
+        let mut push = |level: usize, tables: u64, written: u64| {
+            for i in 0..tables {
+                version.level_slice[level].push(Scope {
+                    min: i.to_string(),
+                    max: i.to_string(),
+                    gen: FileId::from_parts(written + i, 0),
+                    wal_ids: None,
+                    stats: None,
+                });
+            }
+        };
+        push(0, 2, 300);
+        push(1, 5, 200);
+        push(2, 10, 100);
+        push(3, 1, 0);
+
+        let scores = compaction_scores(&option, &version);
+        // level 0 first, then the most overfull levels with the oldest tables first
+        assert_eq!(
+            scores.iter().map(|score| score.level).collect::<Vec<_>>()[..4],
+            [0, 2, 1, 3]
+        );
+        assert_eq!(scores[0].score, 1.0);
+        assert_eq!(scores[1].score, 1.25);
+        assert_eq!(scores[1].oldest, Some(FileId::from_parts(100, 0)));
+        assert_eq!(scores[3].score, 1.0 / 16.0);
+    }
+
+    #[test]
+    fn periodic_priority() {
+        let option = DbOption::<Test>::from(Path::from("periodic_priority"));
+        let (sender, _) = bounded(1);
+        let mut version = Version::<Test>::new(
+            Arc::new(option.clone()),
+            sender,
+            Arc::new(AtomicU64::default()),
+            Default::default(),
+        );
+        for (level, written) in [(0, 5_000), (1, 3_000), (MAX_LEVEL - 1, 2_000)] {
+            version.level_slice[level].push(Scope {
+                min: written.to_string(),
+                max: written.to_string(),
+                gen: FileId::from_parts(written, 0),
+                wal_ids: None,
+                stats: None,
+            });
+        }
+
+        assert!(periodic_candidate(&option, &version, 10_000).is_none());
+        let option = option.periodic_compaction_interval(Duration::from_secs(6));
+        assert!(periodic_candidate(&option, &version, 7_000).is_none());
+        // the oldest one first, even in the last level
+        let (level, scope) = periodic_candidate(&option, &version, 8_000).unwrap();
+        assert_eq!(
+            (level, scope.gen),
+            (MAX_LEVEL - 1, FileId::from_parts(2_000, 0))
+        );
+        assert!(option.is_periodic_due(FileId::from_parts(5_000, 0), 11_000));
+    }
+
+    #[test]
+    fn tombstone_priority() {
+        let option = DbOption::<Test>::from(Path::from("tombstone_priority"));
+        let (sender, _) = bounded(1);
+        let mut version = Version::<Test>::new(
+            Arc::new(option.clone()),
+            sender,
+            Arc::new(AtomicU64::default()),
+            Default::default(),
+        );
+        let mut push = |level: usize, written: u64, tombstones: u64| {
+            version.level_slice[level].push(Scope {
+                min: written.to_string(),
+                max: written.to_string(),
+                gen: FileId::from_parts(written, 0),
+                wal_ids: None,
+                stats: Some(TableStats {
+                    rows: 10,
+                    tombstones,
+                    ..Default::default()
+                }),
+            });
+        };
+        push(0, 400, 10);
+        push(1, 300, 4);
+        push(1, 301, 8);
+        push(2, 200, 8);
+        push(3, 100, 1);
+
+        assert!(tombstone_candidate(&option, &version).is_none());
+        let option = option.tombstone_compaction_ratio(0.5);
+        // level 0 is left to its own compactions, the oldest one goes first among equals
+        let (level, scope) = tombstone_candidate(&option, &version).unwrap();
+        assert_eq!((level, scope.gen), (2, FileId::from_parts(200, 0)));
+        assert!(!option.is_tombstone_heavy(&version.level_slice[1][0]));
+    }
+
+    #[test]
+    fn level_bytes_threshold() {
+        let option = DbOption::<Test>::from(Path::from("level_bytes"))
+            .major_threshold_with_sst_size(2)
+            .level_base_bytes(1000)
+            .level_bytes_magnification(10)
+            .max_sst_file_size(5000);
+        let (sender, _) = bounded(1);
+        let mut version = Version::<Test>::new(
+            Arc::new(option.clone()),
+            sender,
+            Arc::new(AtomicU64::default()),
+            Default::default(),
+        );
+        let scope = |bytes: Option<u64>| Scope {
+            min: 1.to_string(),
+            max: 2.to_string(),
+            gen: FileId::new(),
+            wal_ids: None,
+            stats: bytes.map(|bytes| TableStats {
+                bytes,
+                ..Default::default()
+            }),
+        };
+
+        // level 0 is still compacted by its number of tables
+        version.level_slice[0].push(scope(Some(100_000)));
+        assert!(!option.is_threshold_exceeded_major(&version, 0));
+        version.level_slice[0].push(scope(Some(1)));
+        assert!(option.is_threshold_exceeded_major(&version, 0));
+
+        version.level_slice[1].push(scope(Some(600)));
+        assert!(!option.is_threshold_exceeded_major(&version, 1));
+        version.level_slice[1].push(scope(Some(600)));
+        assert!(option.is_threshold_exceeded_major(&version, 1));
+
+        version.level_slice[2].push(scope(Some(9000)));
+        assert!(!option.is_threshold_exceeded_major(&version, 2));
+        // a table without statistics counts as a full one
+        version.level_slice[2].push(scope(None));
+        assert!(option.is_threshold_exceeded_major(&version, 2));
+    }
+
+    #[tokio::test]
+    async fn major_subcompactions() {
+        let temp_dir = TempDir::new().unwrap();
+        let temp_dir_l0 = TempDir::new().unwrap();
+        let temp_dir_l1 = TempDir::new().unwrap();
+
+        let mut option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap())
+            .level_path(
+                0,
+                Path::from_filesystem_path(temp_dir_l0.path()).unwrap(),
+                FsOptions::Local,
+            )
+            .unwrap()
+            .level_path(
+                1,
+                Path::from_filesystem_path(temp_dir_l1.path()).unwrap(),
+                FsOptions::Local,
+            )
+            .unwrap()
+            .max_subcompactions(4);
+        option.major_threshold_with_sst_size = 2;
+        let option = Arc::new(option);
+        let manager =
+            StoreManager::new(option.base_fs.clone(), option.level_paths.clone()).unwrap();
+
+        manager
+            .base_fs()
+            .create_dir_all(&option.version_log_dir_path())
+            .await
+            .unwrap();
+        manager
+            .base_fs()
+            .create_dir_all(&option.wal_dir_path())
+            .await
+            .unwrap();
+
+        let (_, version) = build_version(&option, &manager).await;
+
+        let min = 2.to_string();
+        let max = 5.to_string();
+        let mut version_edits = Vec::new();
+
+        Compactor::<Test>::major_compaction(
+            &version,
+            &option,
+            &min,
+            &max,
+            &mut version_edits,
+            &mut vec![],
+            &RecordInstance::Normal,
+            &manager,
+            Arc::new(NoCache::default()),
+            None,
+            &Arc::<Jobs>::default().start(),
+        )
+        .await
+        .unwrap();
+
+        // split at the smallest key of the second table of each level
+        let added = version_edits
+            .iter()
+            .filter_map(|edit| match edit {
+                VersionEdit::Add { level, scope } => {
+                    Some((*level, scope.min.clone(), scope.max.clone()))
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            added,
+            vec![
+                (1, 1.to_string(), 3.to_string()),
+                (1, 4.to_string(), 6.to_string()),
+            ]
+        );
+        assert_eq!(
+            version_edits
+                .iter()
+                .filter(|edit| matches!(edit, VersionEdit::Remove { .. }))
+                .count(),
+            4
+        );
+    }
+
+    #[tokio::test]
+    async fn major_cancelled() {
+        let temp_dir = TempDir::new().unwrap();
+
+        let mut option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap())
+            .compaction_memory_budget(1);
+        option.major_threshold_with_sst_size = 2;
+        let option = Arc::new(option);
+        let manager =
+            StoreManager::new(option.base_fs.clone(), option.level_paths.clone()).unwrap();
+
+        manager
+            .base_fs()
+            .create_dir_all(&option.version_log_dir_path())
+            .await
+            .unwrap();
+        manager
+            .base_fs()
+            .create_dir_all(&option.wal_dir_path())
+            .await
+            .unwrap();
+
+        let (_, version) = build_version(&option, &manager).await;
+
+        let jobs = Arc::<Jobs>::default();
+        let job = jobs.start();
+        assert!(jobs.cancel(job.id()));
+        let min = 2.to_string();
+        let max = 5.to_string();
+        let result = Compactor::<Test>::major_compaction(
+            &version,
+            &option,
+            &min,
+            &max,
+            &mut vec![],
+            &mut vec![],
+            &RecordInstance::Normal,
+            &manager,
+            Arc::new(NoCache::default()),
+            None,
+            &job,
+        )
+        .await;
+        assert!(matches!(result, Err(CompactionError::Cancelled)));
+        assert_eq!(jobs.list()[0].inputs.len(), 4);
+
+        // stopped once the first batch is written
+        let outputs = job.outputs();
+        assert!(!outputs.is_empty());
+        Compactor::<Test>::discard(&option, &manager, &job)
+            .await
+            .unwrap();
+        for (level, gen) in outputs {
+            assert!(manager
+                .base_fs()
+                .open_options(
+                    &option.table_path(gen, level),
+                    FileType::Parquet.open_options(true)
+                )
+                .await
+                .is_err());
+        }
+    }
+
     // https://github.com/tonbo-io/tonbo/pull/139
     #[tokio::test]
     pub(crate) async fn major_panic() {
@@ -1181,19 +2825,25 @@ pub(crate) mod tests {
 
         let option = Arc::new(option);
         let (sender, _) = bounded(1);
-        let mut version =
-            Version::<Test>::new(option.clone(), sender, Arc::new(AtomicU32::default()));
+        let mut version = Version::<Test>::new(
+            option.clone(),
+            sender,
+            Arc::new(AtomicU64::default()),
+            Default::default(),
+        );
         version.level_slice[0].push(Scope {
             min: 0.to_string(),
             max: 4.to_string(),
             gen: table_gen0,
             wal_ids: None,
+            stats: None,
         });
         version.level_slice[1].push(Scope {
             min: 5.to_string(),
             max: 9.to_string(),
             gen: table_gen1,
             wal_ids: None,
+            stats: None,
         });
 
         let mut version_edits = Vec::new();
@@ -1210,6 +2860,8 @@ pub(crate) mod tests {
             &RecordInstance::Normal,
             &manager,
             Arc::new(NoCache::default()),
+            None,
+            &Arc::<Jobs>::default().start(),
         )
         .await
         .unwrap();
@@ -1230,7 +2882,7 @@ pub(crate) mod tests {
         option.major_default_oldest_table_num = 1;
         option.trigger_type = TriggerType::Length(5);
 
-        let db: DB<Test, TokioExecutor> = DB::new(option, TokioExecutor::new()).await.unwrap();
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
 
         for i in 5..9 {
             let item = Test {
@@ -0,0 +1,124 @@
+use std::ops::Range;
+
+use crate::version::MAX_LEVEL;
+
+/// A sorted run merged by a tiered compaction: a table of level 0, by its index in the level,
+/// or a whole level below it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Run {
+    Table(usize),
+    Level(usize),
+}
+
+/// The runs a tiered compaction merges, as a range of `runs`, and the level the merged run is
+/// written to.
+///
+/// `runs` are ordered from the newest to the oldest with their sizes: the tables of level 0
+/// from the last one added, then the levels below it from the top. The merged run must stay
+/// older than the runs before it and newer than the ones after it, so a window holding a
+/// table of level 0 also holds the oldest one, and the merged run replaces the deepest level
+/// of the window or, if it only holds tables of level 0, fills the empty level above the
+/// first non-empty one.
+pub(crate) fn pick(
+    runs: &[(Run, u64)],
+    max_runs: usize,
+    size_ratio: usize,
+    min_merge_width: usize,
+) -> Option<(Range<usize>, usize)> {
+    if runs.len() <= max_runs {
+        return None;
+    }
+    let tables = runs
+        .iter()
+        .take_while(|(run, _)| matches!(run, Run::Table(_)))
+        .count();
+    let first_level = runs[tables..].iter().find_map(|(run, _)| match run {
+        Run::Level(level) => Some(*level),
+        Run::Table(_) => None,
+    });
+    let output = |window: &Range<usize>| -> Option<usize> {
+        if window.start < tables && window.end < tables {
+            return None;
+        }
+        match runs[window.end - 1].0 {
+            Run::Level(level) => Some(level),
+            Run::Table(_) => match first_level {
+                Some(level) => (level > 1).then_some(level - 1),
+                None => Some(MAX_LEVEL - 1),
+            },
+        }
+    };
+    let bytes = |window: &Range<usize>| runs[window.clone()].iter().map(|(_, bytes)| *bytes);
+    let windows = |width: usize| {
+        (0..=runs.len() - width)
+            .map(move |start| start..start + width)
+            .filter_map(move |window| output(&window).map(|level| (window, level)))
+    };
+
+    // the widest window of runs of similar sizes
+    for width in (min_merge_width.max(2)..=runs.len()).rev() {
+        let similar = windows(width)
+            .filter(|(window, _)| {
+                let min = bytes(window).min().unwrap_or(0);
+                let max = bytes(window).max().unwrap_or(0);
+                max as u128 * 100 <= min as u128 * (100 + size_ratio) as u128
+            })
+            .min_by_key(|(window, _)| bytes(window).sum::<u64>());
+        if similar.is_some() {
+            return similar;
+        }
+    }
+    // otherwise the cheapest window bringing the runs back to `max_runs`, the window of every
+    // run is always valid
+    let width = (runs.len() - max_runs + 1).max(min_merge_width.max(2));
+    (width.min(runs.len())..=runs.len())
+        .find_map(|width| windows(width).min_by_key(|(window, _)| bytes(window).sum::<u64>()))
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod tests {
+    use super::{pick, Run};
+    use crate::version::MAX_LEVEL;
+
+    #[test]
+    fn similar_runs() {
+        let runs = [
+            (Run::Table(2), 10),
+            (Run::Table(1), 11),
+            (Run::Table(0), 12),
+            (Run::Level(3), 1000),
+        ];
+        assert_eq!(pick(&runs, 4, 20, 2), None);
+        // the tables of level 0 fill the level above the first non-empty one
+        assert_eq!(pick(&runs, 3, 20, 2), Some((0..3, 2)));
+        // the newest tables alone would leave an older table of level 0 above them
+        assert_eq!(pick(&runs, 3, 10, 2), Some((1..3, 2)));
+    }
+
+    #[test]
+    fn levels() {
+        let runs = [
+            (Run::Table(0), 10),
+            (Run::Level(1), 100),
+            (Run::Level(2), 110),
+            (Run::Level(3), 5000),
+        ];
+        assert_eq!(pick(&runs, 3, 20, 2), Some((1..3, 2)));
+
+        let runs = [(Run::Table(1), 10), (Run::Table(0), 10)];
+        assert_eq!(pick(&runs, 1, 20, 2), Some((0..2, MAX_LEVEL - 1)));
+    }
+
+    #[test]
+    fn dissimilar_runs() {
+        let runs = [
+            (Run::Table(0), 1),
+            (Run::Level(1), 10),
+            (Run::Level(2), 100),
+            (Run::Level(3), 1000),
+        ];
+        // the cheapest merge of as many runs as the excess
+        assert_eq!(pick(&runs, 3, 20, 2), Some((0..2, 1)));
+        assert_eq!(pick(&runs, 2, 20, 2), Some((0..3, 2)));
+    }
+}
@@ -0,0 +1,413 @@
+//! Tuning of a [`DbOption`] kept in a TOML or JSON file, with the `config` feature, so that
+//! deployments change it without recompiling.
+
+use std::{
+    collections::BTreeMap,
+    io,
+    path::{Path as StdPath, PathBuf},
+    time::Duration,
+};
+
+use fusio::path::Path;
+use fusio_dispatch::FsOptions;
+use serde::{de::IgnoredAny, Deserialize, Serialize};
+use thiserror::Error;
+use tracing::warn;
+
+use crate::{
+    record::Record, trigger::TriggerType, CompactionStyle, DbOption, FormatVersion, OptionError,
+    SstCompression, VersionLogRecoveryMode, WalCompression, WalRecoveryMode, WalSyncPolicy,
+};
+
+/// The options of a [`DbOption`] that can be kept in a file, each left to its default when
+/// missing. The durations are in milliseconds. Keys unknown to this version, e.g. written for a
+/// newer one, are kept in [`DbConfig::unknown`] and logged instead of failing.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DbConfig {
+    /// the directory of the database on the base file system, required by
+    /// [`DbOption::from_file`]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// the size of the mutable memtable at which it is frozen
+    pub max_mem_table_size: Option<usize>,
+    pub immutable_chunk_num: Option<usize>,
+    pub immutable_chunk_max_num: Option<usize>,
+    pub major_threshold_with_sst_size: Option<usize>,
+    pub level_sst_magnification: Option<usize>,
+    pub level_base_bytes: Option<usize>,
+    pub level_bytes_magnification: Option<usize>,
+    pub compaction_style: Option<CompactionStyle>,
+    pub max_subcompactions: Option<usize>,
+    pub max_background_compactions: Option<usize>,
+    pub tombstone_compaction_ratio: Option<f64>,
+    pub bytes_per_seek: Option<u64>,
+    pub compaction_memory_budget: Option<usize>,
+    pub periodic_compaction_interval_ms: Option<u64>,
+    pub immutable_slowdown_writes_trigger: Option<usize>,
+    pub immutable_stop_writes_trigger: Option<usize>,
+    pub level0_slowdown_writes_trigger: Option<usize>,
+    pub level0_stop_writes_trigger: Option<usize>,
+    pub write_slowdown_delay_ms: Option<u64>,
+    pub memtable_flush_interval_ms: Option<u64>,
+    pub max_total_wal_size: Option<usize>,
+    pub scan_prefetch_batches: Option<usize>,
+    pub scan_readahead_bytes: Option<usize>,
+    pub max_writes_per_sec: Option<u64>,
+    pub max_write_bytes_per_sec: Option<u64>,
+    pub obsolete_file_retention_ms: Option<u64>,
+    pub max_sst_file_size: Option<usize>,
+    pub file_deletion_batch_size: Option<usize>,
+    pub max_file_deletes_per_sec: Option<u64>,
+    pub file_deletion_retries: Option<usize>,
+    pub sst_compression: Option<SstCompression>,
+    pub sst_max_row_group_size: Option<usize>,
+    pub sst_data_page_size: Option<usize>,
+    pub sst_write_batch_size: Option<usize>,
+    pub use_wal: Option<bool>,
+    pub wal_sync_policy: Option<WalSyncPolicy>,
+    pub wal_recovery_mode: Option<WalRecoveryMode>,
+    pub version_log_recovery_mode: Option<VersionLogRecoveryMode>,
+    pub wal_buffer_size: Option<usize>,
+    pub wal_segment_size: Option<usize>,
+    pub wal_compression: Option<WalCompression>,
+    pub format_version: Option<FormatVersion>,
+    pub version_log_snapshot_threshold: Option<u32>,
+    pub slow_operation_threshold_ms: Option<u64>,
+    pub lock_timeout_ms: Option<u64>,
+    pub lock_shards: Option<usize>,
+    pub max_transaction_size: Option<usize>,
+    pub max_key_size: Option<usize>,
+    pub max_record_size: Option<usize>,
+    pub unchecked_writes: Option<bool>,
+    pub paranoid_checks: Option<bool>,
+    pub fast_open: Option<bool>,
+    pub direct_io: Option<bool>,
+    pub preallocate_tables: Option<bool>,
+    pub preallocate_wal: Option<bool>,
+    /// the keys of the file not known to this version
+    #[serde(flatten, skip_serializing)]
+    pub unknown: BTreeMap<String, IgnoredAny>,
+}
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("config io error: {0}")]
+    Io(#[from] io::Error),
+    #[error("config toml error: {0}")]
+    Toml(#[from] toml::de::Error),
+    #[error("config json error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("config file {0} is neither a .toml nor a .json file")]
+    Format(PathBuf),
+    #[error("config file lacks the path of the database")]
+    MissingPath,
+    #[error("invalid option: {0}")]
+    InvalidOption(#[from] OptionError),
+}
+
+impl DbConfig {
+    /// read the TOML or JSON file at `path`, told apart by its extension
+    pub fn from_file(path: impl AsRef<StdPath>) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)?;
+        let config: DbConfig = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("toml") => toml::from_str(&text)?,
+            Some("json") => serde_json::from_str(&text)?,
+            _ => return Err(ConfigError::Format(path.to_path_buf())),
+        };
+        for key in config.unknown.keys() {
+            warn!(key, file = %path.display(), "ignored an unknown option of the config file");
+        }
+        Ok(config)
+    }
+
+    /// set the options of `option` that the config holds
+    pub fn apply<R>(&self, mut option: DbOption<R>) -> DbOption<R>
+    where
+        R: Record,
+    {
+        let millis = Duration::from_millis;
+        macro_rules! set {
+            ($($field:ident),* $(,)?) => {
+                $(
+                    if let Some(value) = self.$field {
+                        option = option.$field(value);
+                    }
+                )*
+            };
+        }
+        macro_rules! set_millis {
+            ($($field:ident => $builder:ident),* $(,)?) => {
+                $(
+                    if let Some(value) = self.$field {
+                        option = option.$builder(millis(value));
+                    }
+                )*
+            };
+        }
+
+        if let Some(path) = &self.path {
+            option = option.path(base_path(&option.base_fs, path));
+        }
+        if let Some(size) = self.max_mem_table_size {
+            option.trigger_type = TriggerType::SizeOfMem(size);
+        }
+        set!(
+            immutable_chunk_num,
+            immutable_chunk_max_num,
+            major_threshold_with_sst_size,
+            level_sst_magnification,
+            level_base_bytes,
+            level_bytes_magnification,
+            compaction_style,
+            max_subcompactions,
+            max_background_compactions,
+            tombstone_compaction_ratio,
+            bytes_per_seek,
+            compaction_memory_budget,
+            immutable_slowdown_writes_trigger,
+            immutable_stop_writes_trigger,
+            level0_slowdown_writes_trigger,
+            level0_stop_writes_trigger,
+            max_total_wal_size,
+            scan_prefetch_batches,
+            scan_readahead_bytes,
+            max_writes_per_sec,
+            max_write_bytes_per_sec,
+            max_sst_file_size,
+            file_deletion_batch_size,
+            max_file_deletes_per_sec,
+            file_deletion_retries,
+            sst_compression,
+            sst_max_row_group_size,
+            sst_data_page_size,
+            sst_write_batch_size,
+            wal_sync_policy,
+            wal_recovery_mode,
+            version_log_recovery_mode,
+            wal_buffer_size,
+            wal_segment_size,
+            wal_compression,
+            format_version,
+            version_log_snapshot_threshold,
+            lock_shards,
+            max_transaction_size,
+            max_key_size,
+            max_record_size,
+            unchecked_writes,
+            paranoid_checks,
+            fast_open,
+            direct_io,
+            preallocate_tables,
+            preallocate_wal,
+        );
+        set_millis!(
+            periodic_compaction_interval_ms => periodic_compaction_interval,
+            write_slowdown_delay_ms => write_slowdown_delay,
+            memtable_flush_interval_ms => memtable_flush_interval,
+            obsolete_file_retention_ms => obsolete_file_retention,
+            slow_operation_threshold_ms => slow_operation_threshold,
+            lock_timeout_ms => lock_timeout,
+        );
+        if self.use_wal == Some(false) {
+            option = option.disable_wal();
+        }
+        option
+    }
+}
+
+impl<R> From<&DbOption<R>> for DbConfig
+where
+    R: Record,
+{
+    /// the options of `option` that can be kept in a file, the path aside
+    fn from(option: &DbOption<R>) -> Self {
+        let millis = |duration: Duration| duration.as_millis() as u64;
+        DbConfig {
+            path: None,
+            max_mem_table_size: match option.trigger_type {
+                TriggerType::SizeOfMem(size) => Some(size),
+                TriggerType::Length(_) => None,
+            },
+            immutable_chunk_num: Some(option.immutable_chunk_num),
+            immutable_chunk_max_num: Some(option.immutable_chunk_max_num),
+            major_threshold_with_sst_size: Some(option.major_threshold_with_sst_size),
+            level_sst_magnification: Some(option.level_sst_magnification),
+            level_base_bytes: option.level_base_bytes,
+            level_bytes_magnification: Some(option.level_bytes_magnification),
+            compaction_style: Some(option.compaction_style),
+            max_subcompactions: Some(option.max_subcompactions),
+            max_background_compactions: Some(option.max_background_compactions),
+            tombstone_compaction_ratio: option.tombstone_compaction_ratio,
+            bytes_per_seek: option.bytes_per_seek,
+            compaction_memory_budget: Some(option.compaction_memory_budget),
+            periodic_compaction_interval_ms: option.periodic_compaction_interval.map(millis),
+            immutable_slowdown_writes_trigger: option.immutable_slowdown_writes_trigger,
+            immutable_stop_writes_trigger: option.immutable_stop_writes_trigger,
+            level0_slowdown_writes_trigger: option.level0_slowdown_writes_trigger,
+            level0_stop_writes_trigger: option.level0_stop_writes_trigger,
+            write_slowdown_delay_ms: Some(millis(option.write_slowdown_delay)),
+            memtable_flush_interval_ms: option.memtable_flush_interval.map(millis),
+            max_total_wal_size: option.max_total_wal_size,
+            scan_prefetch_batches: Some(option.scan_prefetch_batches),
+            scan_readahead_bytes: Some(option.scan_readahead_bytes),
+            max_writes_per_sec: option.max_writes_per_sec,
+            max_write_bytes_per_sec: option.max_write_bytes_per_sec,
+            obsolete_file_retention_ms: option.obsolete_file_retention.map(millis),
+            max_sst_file_size: Some(option.max_sst_file_size),
+            file_deletion_batch_size: Some(option.file_deletion_batch_size),
+            max_file_deletes_per_sec: option.max_file_deletes_per_sec,
+            file_deletion_retries: Some(option.file_deletion_retries),
+            sst_compression: Some(option.sst_compression),
+            sst_max_row_group_size: option.sst_max_row_group_size,
+            sst_data_page_size: option.sst_data_page_size,
+            sst_write_batch_size: option.sst_write_batch_size,
+            use_wal: Some(option.use_wal),
+            wal_sync_policy: Some(option.wal_sync_policy),
+            wal_recovery_mode: Some(option.wal_recovery_mode),
+            version_log_recovery_mode: Some(option.version_log_recovery_mode),
+            wal_buffer_size: Some(option.wal_buffer_size),
+            wal_segment_size: option.wal_segment_size,
+            wal_compression: Some(option.wal_compression),
+            format_version: Some(option.format_version),
+            version_log_snapshot_threshold: Some(option.version_log_snapshot_threshold),
+            slow_operation_threshold_ms: option.slow_operation_threshold.map(millis),
+            lock_timeout_ms: option.lock_timeout.map(millis),
+            lock_shards: Some(option.lock_shards),
+            max_transaction_size: option.max_transaction_size,
+            max_key_size: option.max_key_size,
+            max_record_size: option.max_record_size,
+            unchecked_writes: Some(option.unchecked_writes),
+            paranoid_checks: Some(option.paranoid_checks),
+            fast_open: Some(option.fast_open),
+            direct_io: Some(option.direct_io),
+            preallocate_tables: Some(option.preallocate_tables),
+            preallocate_wal: Some(option.preallocate_wal),
+            unknown: BTreeMap::new(),
+        }
+    }
+}
+
+impl<R> DbOption<R>
+where
+    R: Record,
+{
+    /// the default [`DbOption`] of a record with a static schema, tuned by the TOML or JSON file
+    /// at `path` which holds the path of the database, see [`DbConfig`]. Records of a schema
+    /// known at runtime apply a [`DbConfig`] to [`DbOption::with_path`] instead
+    pub fn from_file(path: impl AsRef<StdPath>) -> Result<Self, ConfigError> {
+        let config = DbConfig::from_file(path)?;
+        let base_path = config.path.clone().ok_or(ConfigError::MissingPath)?;
+        let option = config.apply(DbOption::from(Path::from(base_path)));
+        option.validate()?;
+        Ok(option)
+    }
+}
+
+/// the path of the database on `base_fs`, a local directory is named as its file system does,
+/// e.g. `C:\tonbo` on Windows, see [`local_path`](crate::fs::local_path)
+#[cfg(not(target_arch = "wasm32"))]
+fn base_path(base_fs: &FsOptions, path: &str) -> Path {
+    match (base_fs, crate::fs::local_path(path)) {
+        (FsOptions::Local, Ok(local)) => local,
+        _ => Path::from(path.to_string()),
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn base_path(_: &FsOptions, path: &str) -> Path {
+    Path::from(path.to_string())
+}
+
+/// (de)serialize a duration as milliseconds
+pub(crate) mod millis {
+    use std::time::Duration;
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub(crate) fn serialize<S>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_u64(duration.as_millis() as u64)
+    }
+
+    pub(crate) fn deserialize<'de, D>(deserializer: D) -> Result<Duration, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        u64::deserialize(deserializer).map(Duration::from_millis)
+    }
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod tests {
+    use std::time::Duration;
+
+    use fusio::path::Path;
+    use tempfile::TempDir;
+
+    use super::{ConfigError, DbConfig};
+    use crate::{CompactionStyle, DbOption, SstCompression, WalSyncPolicy};
+
+    #[test]
+    fn from_toml_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("tonbo.toml");
+        std::fs::write(
+            &file,
+            r#"
+            path = "/data/tonbo"
+            max_mem_table_size = 1048576
+            level_sst_magnification = 4
+            sst_compression = "snappy"
+            wal_sync_policy = { interval = 500 }
+            lock_timeout_ms = 200
+            compaction_style = { tiered = { max_runs = 4, size_ratio = 20, min_merge_width = 2 } }
+            from_a_newer_version = true
+            "#,
+        )
+        .unwrap();
+
+        let config = DbConfig::from_file(&file).unwrap();
+        assert!(config.unknown.contains_key("from_a_newer_version"));
+
+        let option = DbOption::<String>::from_file(&file).unwrap();
+        assert_eq!(option.base_path, Path::from("/data/tonbo".to_string()));
+        assert_eq!(option.level_sst_magnification, 4);
+        assert_eq!(option.sst_compression, SstCompression::Snappy);
+        assert_eq!(
+            option.wal_sync_policy,
+            WalSyncPolicy::Interval(Duration::from_millis(500))
+        );
+        assert_eq!(option.lock_timeout, Some(Duration::from_millis(200)));
+        assert_eq!(
+            option.compaction_style,
+            CompactionStyle::Tiered {
+                max_runs: 4,
+                size_ratio: 20,
+                min_merge_width: 2,
+            }
+        );
+
+        // round trips through JSON
+        let file = temp_dir.path().join("tonbo.json");
+        let mut config = DbConfig::from(&option);
+        config.path = Some("/data/tonbo".to_string());
+        std::fs::write(&file, serde_json::to_string(&config).unwrap()).unwrap();
+        let reopened = DbOption::<String>::from_file(&file).unwrap();
+        assert_eq!(format!("{:?}", reopened), format!("{:?}", option));
+
+        std::fs::write(&file, "{}").unwrap();
+        assert!(matches!(
+            DbOption::<String>::from_file(&file),
+            Err(ConfigError::MissingPath)
+        ));
+        std::fs::write(&file, r#"{ "path": "/data", "lock_shards": 0 }"#).unwrap();
+        assert!(matches!(
+            DbOption::<String>::from_file(&file),
+            Err(ConfigError::InvalidOption(_))
+        ));
+    }
+}
@@ -0,0 +1,448 @@
+use std::{
+    any::Any,
+    fmt::{Debug, Formatter},
+    ops::Bound,
+    sync::Arc,
+};
+
+use ::datafusion::{
+    catalog::Session,
+    common::{internal_err, ScalarValue},
+    datasource::{TableProvider, TableType},
+    error::{DataFusionError, Result},
+    execution::{SendableRecordBatchStream, TaskContext},
+    logical_expr::{Between, BinaryExpr, Operator, TableProviderFilterPushDown},
+    physical_expr::EquivalenceProperties,
+    physical_plan::{
+        stream::RecordBatchStreamAdapter, DisplayAs, DisplayFormatType, ExecutionMode,
+        ExecutionPlan, Partitioning, PlanProperties,
+    },
+    prelude::Expr,
+};
+use arrow::datatypes::{Field, SchemaRef};
+use async_stream::stream;
+use async_trait::async_trait;
+use futures_util::StreamExt;
+
+use crate::{
+    inmem::immutable::ArrowArrays,
+    record::{Column, Datatype, Record},
+    DB,
+};
+
+/// Primary keys which filters on the primary key column can be pushed down to as a scan range.
+pub trait FromScalar: Sized {
+    /// the key equal to `value`, which has the data type of the primary key column
+    fn from_scalar(value: &ScalarValue) -> Option<Self>;
+}
+
+macro_rules! implement_from_scalar {
+    ($({$Type:ty, $Variant:ident, $Datatype:ident}),*) => {
+        $(
+            impl FromScalar for $Type {
+                fn from_scalar(value: &ScalarValue) -> Option<Self> {
+                    match value {
+                        ScalarValue::$Variant(value) => *value,
+                        _ => None,
+                    }
+                }
+            }
+        )*
+
+        impl FromScalar for Column {
+            fn from_scalar(value: &ScalarValue) -> Option<Self> {
+                let (datatype, value): (_, Arc<dyn Any + Send + Sync>) = match value {
+                    $(
+                        ScalarValue::$Variant(Some(value)) => {
+                            (Datatype::$Datatype, Arc::new(*value) as _)
+                        }
+                    )*
+                    ScalarValue::Utf8(Some(value)) | ScalarValue::LargeUtf8(Some(value)) => {
+                        (Datatype::String, Arc::new(value.clone()) as _)
+                    }
+                    _ => return None,
+                };
+                Some(Column::new(datatype, String::new(), value, false))
+            }
+        }
+    };
+}
+
+implement_from_scalar!(
+    {i8, Int8, Int8},
+    {i16, Int16, Int16},
+    {i32, Int32, Int32},
+    {i64, Int64, Int64},
+    {u8, UInt8, UInt8},
+    {u16, UInt16, UInt16},
+    {u32, UInt32, UInt32},
+    {u64, UInt64, UInt64}
+);
+
+impl FromScalar for String {
+    fn from_scalar(value: &ScalarValue) -> Option<Self> {
+        match value {
+            ScalarValue::Utf8(value) | ScalarValue::LargeUtf8(value) => value.clone(),
+            _ => None,
+        }
+    }
+}
+
+/// A [`DB`] exposed to DataFusion as a table, e.g. to run SQL over a live database.
+///
+/// The table has the columns of the record, without the internal `_null` and `_ts` columns.
+/// Every query reads a snapshot taken when it starts. Projections are pushed down to the scan,
+/// and so are comparisons of the primary key with literals, which narrow the range scanned.
+pub struct TonboTable<R>
+where
+    R: Record,
+{
+    db: Arc<DB<R>>,
+    schema: SchemaRef,
+    primary_key: Field,
+}
+
+impl<R> TonboTable<R>
+where
+    R: Record + Send + Sync,
+    R::Columns: Send + Sync,
+    R::Key: FromScalar,
+{
+    pub async fn new(db: Arc<DB<R>>) -> Self {
+        let (arrow_schema, primary_key_index) = {
+            let schema = db.schema.read().await;
+            (
+                schema.record_instance.arrow_schema::<R>(),
+                schema.record_instance.primary_key_index::<R>(),
+            )
+        };
+        // skip two columns: _null and _ts
+        let indices = (2..arrow_schema.fields().len()).collect::<Vec<_>>();
+
+        TonboTable {
+            db,
+            schema: Arc::new(arrow_schema.project(&indices).unwrap()),
+            primary_key: arrow_schema.field(primary_key_index).clone(),
+        }
+    }
+
+    /// the bound of the primary key set by `filter`, `None` if it does not compare the primary
+    /// key with a literal
+    fn key_bounds(&self, filter: &Expr) -> Option<(Bound<R::Key>, Bound<R::Key>)> {
+        match filter {
+            Expr::BinaryExpr(BinaryExpr { left, op, right }) => {
+                let (value, op) = match (left.as_ref(), right.as_ref()) {
+                    (Expr::Column(column), Expr::Literal(value)) if self.is_key(column) => {
+                        (value, *op)
+                    }
+                    (Expr::Literal(value), Expr::Column(column)) if self.is_key(column) => {
+                        (value, op.swap()?)
+                    }
+                    _ => return None,
+                };
+                let key = self.to_key(value)?;
+                match op {
+                    Operator::Eq => Some((Bound::Included(key.clone()), Bound::Included(key))),
+                    Operator::Lt => Some((Bound::Unbounded, Bound::Excluded(key))),
+                    Operator::LtEq => Some((Bound::Unbounded, Bound::Included(key))),
+                    Operator::Gt => Some((Bound::Excluded(key), Bound::Unbounded)),
+                    Operator::GtEq => Some((Bound::Included(key), Bound::Unbounded)),
+                    _ => None,
+                }
+            }
+            Expr::Between(Between {
+                expr,
+                negated: false,
+                low,
+                high,
+            }) => match (expr.as_ref(), low.as_ref(), high.as_ref()) {
+                (Expr::Column(column), Expr::Literal(low), Expr::Literal(high))
+                    if self.is_key(column) =>
+                {
+                    Some((
+                        Bound::Included(self.to_key(low)?),
+                        Bound::Included(self.to_key(high)?),
+                    ))
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn is_key(&self, column: &::datafusion::common::Column) -> bool {
+        column.name == *self.primary_key.name()
+    }
+
+    fn to_key(&self, value: &ScalarValue) -> Option<R::Key> {
+        if value.data_type() != *self.primary_key.data_type() {
+            return None;
+        }
+        R::Key::from_scalar(value)
+    }
+}
+
+#[async_trait]
+impl<R> TableProvider for TonboTable<R>
+where
+    R: Record + Send + Sync,
+    R::Columns: Send + Sync,
+    R::Key: FromScalar,
+{
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::Base
+    }
+
+    async fn scan(
+        &self,
+        _: &dyn Session,
+        projection: Option<&Vec<usize>>,
+        filters: &[Expr],
+        limit: Option<usize>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let mut range = (Bound::Unbounded, Bound::Unbounded);
+        for (lower, upper) in filters.iter().filter_map(|filter| self.key_bounds(filter)) {
+            range = (max_lower(range.0, lower), min_upper(range.1, upper));
+        }
+        let schema = match projection {
+            Some(projection) => Arc::new(self.schema.project(projection)?),
+            None => self.schema.clone(),
+        };
+
+        Ok(Arc::new(TonboExec {
+            cache: PlanProperties::new(
+                EquivalenceProperties::new(schema),
+                Partitioning::UnknownPartitioning(1),
+                ExecutionMode::Bounded,
+            ),
+            db: self.db.clone(),
+            projection: projection.cloned(),
+            limit,
+            range,
+        }))
+    }
+
+    fn supports_filters_pushdown(
+        &self,
+        filters: &[&Expr],
+    ) -> Result<Vec<TableProviderFilterPushDown>> {
+        // the range is scanned as a whole, so the filters are applied again
+        Ok(filters
+            .iter()
+            .map(|filter| match self.key_bounds(filter) {
+                Some(_) => TableProviderFilterPushDown::Inexact,
+                None => TableProviderFilterPushDown::Unsupported,
+            })
+            .collect())
+    }
+}
+
+fn max_lower<K: Ord>(a: Bound<K>, b: Bound<K>) -> Bound<K> {
+    match (&a, &b) {
+        (Bound::Unbounded, _) => b,
+        (_, Bound::Unbounded) => a,
+        (Bound::Included(x) | Bound::Excluded(x), Bound::Included(y) | Bound::Excluded(y)) => {
+            if x > y || (x == y && matches!(a, Bound::Excluded(_))) {
+                a
+            } else {
+                b
+            }
+        }
+    }
+}
+
+fn min_upper<K: Ord>(a: Bound<K>, b: Bound<K>) -> Bound<K> {
+    match (&a, &b) {
+        (Bound::Unbounded, _) => b,
+        (_, Bound::Unbounded) => a,
+        (Bound::Included(x) | Bound::Excluded(x), Bound::Included(y) | Bound::Excluded(y)) => {
+            if x < y || (x == y && matches!(a, Bound::Excluded(_))) {
+                a
+            } else {
+                b
+            }
+        }
+    }
+}
+
+struct TonboExec<R>
+where
+    R: Record,
+{
+    cache: PlanProperties,
+    db: Arc<DB<R>>,
+    projection: Option<Vec<usize>>,
+    limit: Option<usize>,
+    range: (Bound<R::Key>, Bound<R::Key>),
+}
+
+impl<R> DisplayAs for TonboExec<R>
+where
+    R: Record,
+{
+    fn fmt_as(&self, _: DisplayFormatType, f: &mut Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "TonboExec: range: {:?}, projection: {:?}, limit: {:?}",
+            self.range, self.projection, self.limit
+        )
+    }
+}
+
+impl<R> Debug for TonboExec<R>
+where
+    R: Record,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TonboExec")
+            .field("cache", &self.cache)
+            .field("projection", &self.projection)
+            .field("limit", &self.limit)
+            .field("range", &self.range)
+            .finish()
+    }
+}
+
+impl<R> ExecutionPlan for TonboExec<R>
+where
+    R: Record + Send + Sync,
+    R::Columns: Send + Sync,
+{
+    fn name(&self) -> &str {
+        "TonboExec"
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn properties(&self) -> &PlanProperties {
+        &self.cache
+    }
+
+    fn children(&self) -> Vec<&Arc<dyn ExecutionPlan>> {
+        vec![]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        if children.is_empty() {
+            Ok(self)
+        } else {
+            internal_err!("Children cannot be replaced in {self:?}")
+        }
+    }
+
+    fn execute(&self, _: usize, _: Arc<TaskContext>) -> Result<SendableRecordBatchStream> {
+        let db = self.db.clone();
+        let (lower, upper) = self.range.clone();
+        let limit = self.limit;
+        let projection = self.projection.clone();
+        // skip two columns: _null and _ts
+        let indices = match &projection {
+            Some(projection) => projection.iter().map(|i| i + 2).collect(),
+            None => (2..self.schema().fields().len() + 2).collect::<Vec<_>>(),
+        };
+
+        Ok(Box::pin(RecordBatchStreamAdapter::new(
+            self.schema(),
+            stream! {
+                let snapshot = db.snapshot().await;
+                let mut scan = snapshot.scan((lower.as_ref(), upper.as_ref()));
+                if let Some(limit) = limit {
+                    scan = scan.limit(limit);
+                }
+                if let Some(projection) = projection {
+                    scan = scan.projection(projection);
+                }
+                let mut batches = scan
+                    .package(8192)
+                    .await
+                    .map_err(|err| DataFusionError::Execution(err.to_string()))?;
+
+                while let Some(columns) = batches.next().await {
+                    yield Ok(columns?.as_record_batch().project(&indices)?);
+                }
+            },
+        )))
+    }
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod tests {
+    use std::sync::Arc;
+
+    use ::datafusion::{physical_plan::displayable, prelude::SessionContext};
+    use arrow::{
+        array::{AsArray, StringArray},
+        datatypes::UInt32Type,
+    };
+    use fusio::path::Path;
+    use tempfile::TempDir;
+
+    use super::TonboTable;
+    use crate::{
+        executor::tokio::TokioExecutor,
+        tests::{test_items, Test},
+        DbOption, DB,
+    };
+
+    #[tokio::test]
+    async fn sql() {
+        let temp_dir = TempDir::new().unwrap();
+        let option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap());
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+        for item in test_items() {
+            db.insert(item).await.unwrap();
+        }
+        let db = Arc::new(db);
+
+        let ctx = SessionContext::new();
+        ctx.register_table("test", Arc::new(TonboTable::new(db.clone()).await))
+            .unwrap();
+
+        let df = ctx
+            .sql("select vu32 from test where vstring >= '2' and vstring < '3' and vu32 > 2")
+            .await
+            .unwrap();
+        let plan = df.clone().create_physical_plan().await.unwrap();
+        let plan = displayable(plan.as_ref()).indent(true).to_string();
+        assert!(plan.contains("TonboExec: range: (Included(\"2\"), Excluded(\"3\"))"));
+
+        let mut vu32s = Vec::new();
+        for batch in df.collect().await.unwrap() {
+            assert_eq!(batch.num_columns(), 1);
+            vu32s.extend(batch.column(0).as_primitive::<UInt32Type>().values().iter());
+        }
+        vu32s.sort();
+        assert_eq!(vu32s, (20..30).collect::<Vec<u32>>());
+
+        // the table follows the writes
+        db.remove("25".to_string()).await.unwrap();
+        let batches = ctx
+            .sql("select vstring from test where vstring = '25' or vstring = '26'")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        let keys = batches
+            .iter()
+            .flat_map(|batch| {
+                let column = batch.column(0).as_any().downcast_ref::<StringArray>();
+                column.unwrap().iter().flatten().map(str::to_string)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(keys, vec!["26".to_string()]);
+    }
+}
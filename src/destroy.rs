@@ -0,0 +1,97 @@
+use crate::{
+    fs::{self, manager::StoreManager, FileType},
+    record::Record,
+    DbError, DbOption, DB,
+};
+
+impl<R> DB<R>
+where
+    R: Record + Send + Sync,
+    R::Columns: Send + Sync,
+{
+    /// check whether a database configured by `option` exists, that is, its version log is
+    /// present
+    pub async fn exists(option: &DbOption<R>) -> Result<bool, DbError<R>> {
+        let manager = StoreManager::new(option.base_fs.clone(), vec![])?;
+
+        Ok(!fs::list_files(
+            manager.base_fs(),
+            &option.version_log_dir_path(),
+            FileType::Log,
+        )
+        .await?
+        .is_empty())
+    }
+
+    /// remove the SSTs, WALs and version logs of the database configured by `option`, files not
+    /// created by tonbo are left untouched. The database must not be opened while destroying.
+    pub async fn destroy(option: &DbOption<R>) -> Result<(), DbError<R>> {
+        let manager = StoreManager::new(option.base_fs.clone(), option.fs_paths())?;
+        let base_fs = manager.base_fs();
+
+        let mut table_dirs = vec![(base_fs, &option.base_path)];
+        for (path, _) in option.level_paths.iter().flatten() {
+            if table_dirs.iter().all(|(_, dir)| *dir != path) {
+                table_dirs.push((manager.get_fs(path), path));
+            }
+        }
+        for (table_fs, dir) in table_dirs {
+            for path in fs::list_files(table_fs, dir, FileType::Parquet).await? {
+                table_fs.remove(&path).await?;
+            }
+        }
+        let wal_fs = manager.get_fs(&option.wal_dir_path());
+        for path in fs::list_files(wal_fs, &option.wal_dir_path(), FileType::Wal).await? {
+            wal_fs.remove(&path).await?;
+        }
+        // the version log goes last, so that an interrupted destroy can be retried
+        for path in fs::list_files(base_fs, &option.version_log_dir_path(), FileType::Log).await? {
+            base_fs.remove(&path).await?;
+        }
+        // absent if the database has never been opened for writing on a local file system
+        let _ = base_fs.remove(&option.lock_path()).await;
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod tests {
+    use fusio::path::Path;
+    use tempfile::TempDir;
+
+    use crate::{
+        executor::tokio::TokioExecutor,
+        tests::{test_items, Test},
+        DbOption, DB,
+    };
+
+    #[tokio::test]
+    async fn exists_and_destroy() {
+        let temp_dir = TempDir::new().unwrap();
+        let option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap());
+
+        assert!(!DB::<Test>::exists(&option).await.unwrap());
+        {
+            let db: DB<Test> = DB::new(option.clone(), TokioExecutor::new()).await.unwrap();
+            for item in test_items() {
+                db.insert(item).await.unwrap();
+            }
+            db.flush().await.unwrap();
+            db.flush_wal().await.unwrap();
+        }
+        let unrelated = temp_dir.path().join("unrelated.parquet");
+        std::fs::write(&unrelated, b"not a table").unwrap();
+
+        assert!(DB::<Test>::exists(&option).await.unwrap());
+        DB::<Test>::destroy(&option).await.unwrap();
+        assert!(!DB::<Test>::exists(&option).await.unwrap());
+
+        assert!(unrelated.exists());
+        for dir in [temp_dir.path().to_path_buf(), temp_dir.path().join("wal")] {
+            for entry in std::fs::read_dir(dir).unwrap() {
+                let path = entry.unwrap().path();
+                assert!(path.is_dir() || path == unrelated);
+            }
+        }
+    }
+}
@@ -0,0 +1,193 @@
+use std::time::Duration;
+
+use crate::fs::FileId;
+
+/// Callbacks for the background activity of a [`DB`](crate::DB), registered with
+/// [`DbOption::event_listener`](crate::DbOption::event_listener).
+///
+/// They are invoked inline by the task doing the work, so they should return quickly and hand
+/// anything heavy to another task. Each method does nothing by default.
+pub trait EventListener: Send + Sync {
+    /// `immutables` memtables are about to be flushed into a level 0 SST
+    fn on_flush_begin(&self, immutables: usize) {
+        let _ = immutables;
+    }
+
+    /// the flushed memtables were written to the level 0 SST `gen`, which is now visible
+    fn on_flush_complete(&self, gen: FileId) {
+        let _ = gen;
+    }
+
+    /// major compaction merged the `inputs` SSTs into the `outputs`, both given with their level
+    fn on_compaction_complete(&self, inputs: &[(usize, FileId)], outputs: &[(usize, FileId)]) {
+        let _ = (inputs, outputs);
+    }
+
+    /// the WAL buffer was flushed to the file system
+    fn on_wal_sync(&self) {}
+
+    /// a write has to wait for the memtables, held by a flush or a compaction, or is delayed or
+    /// blocked by the write stall triggers, see [`WriteStall`](crate::WriteStall)
+    fn on_stall(&self) {}
+
+    /// [`DB::new`](crate::DB::new) loaded the version log, or replayed one more WAL
+    fn on_recovery_progress(&self, progress: &RecoveryProgress) {
+        let _ = progress;
+    }
+}
+
+/// How far [`DB::new`](crate::DB::new) is in recovering a database, reported to
+/// [`EventListener::on_recovery_progress`] once the version log is loaded, then after each WAL
+/// replayed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecoveryProgress {
+    pub wals_replayed: usize,
+    pub wals_total: usize,
+    pub bytes_replayed: u64,
+    pub bytes_total: u64,
+    /// since opening the database
+    pub elapsed: Duration,
+}
+
+impl RecoveryProgress {
+    /// the time left to replay the rest of the WALs at the pace of the recovery so far, `None`
+    /// until a WAL is replayed
+    pub fn eta(&self) -> Option<Duration> {
+        if self.bytes_replayed == 0 {
+            return None;
+        }
+        let left = self.bytes_total.saturating_sub(self.bytes_replayed);
+        Some(
+            self.elapsed
+                .mul_f64(left as f64 / self.bytes_replayed as f64),
+        )
+    }
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod tests {
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use fusio::path::Path;
+    use tempfile::TempDir;
+
+    use super::{EventListener, RecoveryProgress};
+    use crate::{
+        executor::tokio::TokioExecutor,
+        fs::FileId,
+        tests::{test_items, Test},
+        DbOption, DB,
+    };
+
+    #[derive(Default)]
+    struct Events {
+        flushes_begun: usize,
+        flushed: Vec<FileId>,
+        compactions: Vec<(Vec<(usize, FileId)>, Vec<(usize, FileId)>)>,
+        wal_syncs: usize,
+    }
+
+    struct Recorder(Arc<Mutex<Events>>);
+
+    impl EventListener for Recorder {
+        fn on_flush_begin(&self, immutables: usize) {
+            assert_eq!(immutables, 1);
+            self.0.lock().unwrap().flushes_begun += 1;
+        }
+
+        fn on_flush_complete(&self, gen: FileId) {
+            self.0.lock().unwrap().flushed.push(gen);
+        }
+
+        fn on_compaction_complete(&self, inputs: &[(usize, FileId)], outputs: &[(usize, FileId)]) {
+            self.0
+                .lock()
+                .unwrap()
+                .compactions
+                .push((inputs.to_vec(), outputs.to_vec()));
+        }
+
+        fn on_wal_sync(&self) {
+            self.0.lock().unwrap().wal_syncs += 1;
+        }
+    }
+
+    #[tokio::test]
+    async fn listen() {
+        let temp_dir = TempDir::new().unwrap();
+        let events = Arc::new(Mutex::new(Events::default()));
+        let mut option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap())
+            .event_listener(Recorder(events.clone()));
+        option.immutable_chunk_num = 1;
+        option.immutable_chunk_max_num = 0;
+        option.major_threshold_with_sst_size = 1;
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+
+        for _ in 0..2 {
+            for item in test_items() {
+                db.insert(item).await.unwrap();
+            }
+            db.flush_wal().await.unwrap();
+            db.flush().await.unwrap();
+        }
+
+        let events = events.lock().unwrap();
+        assert_eq!(events.flushes_begun, 2);
+        assert_eq!(events.flushed.len(), 2);
+        assert!(events.wal_syncs >= 2);
+        // the second flush compacts the first level 0 SST into level 1
+        assert_eq!(events.compactions.len(), 1);
+        let (inputs, outputs) = &events.compactions[0];
+        assert_eq!(inputs, &vec![(0, events.flushed[0])]);
+        assert!(!outputs.is_empty());
+        assert!(outputs.iter().all(|(level, _)| *level == 1));
+    }
+
+    struct Progress(Arc<Mutex<Vec<RecoveryProgress>>>);
+
+    impl EventListener for Progress {
+        fn on_recovery_progress(&self, progress: &RecoveryProgress) {
+            self.0.lock().unwrap().push(progress.clone());
+        }
+    }
+
+    #[tokio::test]
+    async fn recovery_progress() {
+        let temp_dir = TempDir::new().unwrap();
+        let option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap());
+        {
+            let db: DB<Test> = DB::new(option.clone(), TokioExecutor::new()).await.unwrap();
+            for item in test_items() {
+                db.insert(item).await.unwrap();
+            }
+            db.flush_wal().await.unwrap();
+        }
+
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let db: DB<Test> = DB::new(
+            option.event_listener(Progress(reports.clone())),
+            TokioExecutor::new(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            db.get(&"0".to_string(), |entry| entry.get().vu32)
+                .await
+                .unwrap(),
+            Some(0)
+        );
+
+        let reports = reports.lock().unwrap();
+        let (first, last) = (reports.first().unwrap(), reports.last().unwrap());
+        assert_eq!(reports.len(), first.wals_total + 1);
+        assert_eq!((first.wals_replayed, first.bytes_replayed), (0, 0));
+        assert_eq!(first.eta(), None);
+        assert_eq!(last.wals_replayed, last.wals_total);
+        assert!(last.bytes_total > 0);
+        assert_eq!(last.bytes_replayed, last.bytes_total);
+        assert_eq!(last.eta(), Some(Duration::ZERO));
+    }
+}
@@ -1,16 +1,95 @@
-use std::future::Future;
+use std::{
+    fmt,
+    future::{self, Future},
+    pin::{pin, Pin},
+    sync::Arc,
+    task::Poll,
+    time::Duration,
+};
 
-use fusio::MaybeSend;
+use fusio::{dynamic::MaybeSendFuture, MaybeSend};
 
 pub trait Executor {
     fn spawn<F>(&self, future: F)
     where
         F: Future<Output = ()> + MaybeSend + 'static;
+
+    /// wait out `duration` on the timer of the runtime, for the write throttling and slowdowns,
+    /// the lock timeouts, the scan deadlines and the background tasks of the database
+    fn sleep(&self, duration: Duration) -> impl Future<Output = ()> + MaybeSend + 'static;
+
+    /// the output of `future`, or none if it is not ready within `duration`
+    fn timeout<F>(
+        &self,
+        duration: Duration,
+        future: F,
+    ) -> impl Future<Output = Option<F::Output>> + MaybeSend
+    where
+        F: Future + MaybeSend,
+    {
+        let sleep = self.sleep(duration);
+        async move { race(future, sleep).await }
+    }
+}
+
+/// the output of `future`, or none if `sleep` is done first
+async fn race<F, S>(future: F, sleep: S) -> Option<F::Output>
+where
+    F: Future,
+    S: Future<Output = ()>,
+{
+    let (mut future, mut sleep) = (pin!(future), pin!(sleep));
+    future::poll_fn(|cx| {
+        if let Poll::Ready(output) = future.as_mut().poll(cx) {
+            return Poll::Ready(Some(output));
+        }
+        sleep.as_mut().poll(cx).map(|_| None)
+    })
+    .await
+}
+
+pub(crate) type Sleep = Pin<Box<dyn MaybeSendFuture<Output = ()>>>;
+
+/// The timer of the [`Executor`] a database is opened with, for the parts of it not generic over
+/// the executor. The instants and wall clock read along it come from `web_time`, as the ones of
+/// `std::time` panic on wasm.
+#[derive(Clone)]
+pub(crate) struct Clock {
+    sleep: Arc<dyn Fn(Duration) -> Sleep + Send + Sync>,
+}
+
+impl Clock {
+    pub(crate) fn new<E>(executor: Arc<E>) -> Self
+    where
+        E: Executor + Send + Sync + 'static,
+    {
+        Clock {
+            sleep: Arc::new(move |duration| -> Sleep { Box::pin(executor.sleep(duration)) }),
+        }
+    }
+
+    pub(crate) fn sleep(&self, duration: Duration) -> Sleep {
+        (self.sleep)(duration)
+    }
+
+    /// see [`Executor::timeout`]
+    pub(crate) async fn timeout<F>(&self, duration: Duration, future: F) -> Option<F::Output>
+    where
+        F: Future,
+    {
+        race(future, self.sleep(duration)).await
+    }
+}
+
+impl fmt::Debug for Clock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Clock").finish_non_exhaustive()
+    }
 }
 
 #[cfg(feature = "tokio")]
 pub mod tokio {
-    use std::future::Future;
+    use std::{future::Future, time::Duration};
 
     use fusio::MaybeSend;
     use tokio::runtime::Handle;
@@ -43,18 +122,164 @@ pub mod tokio {
         {
             self.handle.spawn(future);
         }
+
+        fn sleep(&self, duration: Duration) -> impl Future<Output = ()> + MaybeSend + 'static {
+            tokio::time::sleep(duration)
+        }
+    }
+}
+
+/// [`Executor`] spawning on the global executor of `async-std`, whose timer wakes the database.
+///
+/// The files are accessed through the file system of `fusio` picked by the other features, that
+/// is tokio's with the default ones. `async-std` is built with its `tokio1` feature so that
+/// tokio's runtime is available to its tasks then.
+#[cfg(feature = "async-std")]
+pub mod async_std {
+    use std::{future::Future, time::Duration};
+
+    use fusio::MaybeSend;
+
+    use super::Executor;
+
+    #[derive(Debug, Default)]
+    pub struct AsyncStdExecutor;
+
+    impl AsyncStdExecutor {
+        pub fn new() -> Self {
+            Self
+        }
+    }
+
+    impl Executor for AsyncStdExecutor {
+        fn spawn<F>(&self, future: F)
+        where
+            F: Future<Output = ()> + MaybeSend + 'static,
+        {
+            async_std::task::spawn(future);
+        }
+
+        fn sleep(&self, duration: Duration) -> impl Future<Output = ()> + MaybeSend + 'static {
+            async_std::task::sleep(duration)
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use std::{future, time::Duration};
+
+        use fusio::path::Path;
+        use tempfile::TempDir;
+        use web_time::Instant;
+
+        use super::AsyncStdExecutor;
+        use crate::{
+            executor::Executor,
+            tests::{test_items, Test},
+            DbOption, DB,
+        };
+
+        #[test]
+        fn timers() {
+            async_std::task::block_on(async {
+                let executor = AsyncStdExecutor::new();
+                let start = Instant::now();
+                executor.sleep(Duration::from_millis(10)).await;
+                assert!(start.elapsed() >= Duration::from_millis(10));
+
+                let pending = future::pending::<()>();
+                assert_eq!(
+                    executor.timeout(Duration::from_millis(10), pending).await,
+                    None
+                );
+                let ready = async { 1 };
+                assert_eq!(
+                    executor.timeout(Duration::from_secs(1), ready).await,
+                    Some(1)
+                );
+            })
+        }
+
+        #[test]
+        fn read_write() {
+            async_std::task::block_on(async {
+                let temp_dir = TempDir::new().unwrap();
+                let option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap());
+                let db: DB<Test> = DB::new(option, AsyncStdExecutor::new()).await.unwrap();
+
+                for item in test_items() {
+                    db.insert(item).await.unwrap();
+                }
+                // runs on the compaction task spawned by the executor
+                db.flush().await.unwrap();
+
+                let vu32 = db
+                    .get(&"7".to_string(), |entry| Some(entry.get().vu32.unwrap()))
+                    .await
+                    .unwrap();
+                assert_eq!(vu32, Some(7));
+            })
+        }
+    }
+}
+
+/// [`Executor`] spawning on the `monoio` runtime of the current thread, for local disks driven by
+/// io_uring.
+///
+/// Build with `--no-default-features --features monoio` so that the local file system of `fusio`
+/// is its io_uring backed one, and run the database inside a `monoio` runtime with a timer and
+/// io_uring driver. WAL appends and SST writes go through the buffers of `fusio`, which are not
+/// registered with the ring.
+#[cfg(feature = "monoio")]
+pub mod monoio {
+    use std::{future::Future, time::Duration};
+
+    use fusio::MaybeSend;
+
+    use super::Executor;
+
+    #[derive(Debug, Default)]
+    pub struct MonoioExecutor;
+
+    impl MonoioExecutor {
+        pub fn new() -> Self {
+            Self
+        }
+    }
+
+    impl Executor for MonoioExecutor {
+        fn spawn<F>(&self, future: F)
+        where
+            F: Future<Output = ()> + MaybeSend + 'static,
+        {
+            monoio::spawn(future);
+        }
+
+        fn sleep(&self, duration: Duration) -> impl Future<Output = ()> + MaybeSend + 'static {
+            monoio::time::sleep(duration)
+        }
     }
 }
 
 #[cfg(all(feature = "opfs", target_arch = "wasm32"))]
 pub mod opfs {
-    use std::future::Future;
+    use std::{future::Future, time::Duration};
 
     use fusio::MaybeSend;
     use wasm_bindgen::prelude::*;
+    use wasm_bindgen_futures::{
+        js_sys::{Function, Promise},
+        JsFuture,
+    };
 
     use super::Executor;
 
+    #[wasm_bindgen]
+    extern "C" {
+        #[wasm_bindgen(js_name = setTimeout)]
+        fn set_timeout(handler: &Function, timeout: i32) -> JsValue;
+    }
+
     #[wasm_bindgen]
     pub struct OpfsExecutor();
 
@@ -77,5 +302,15 @@ pub mod opfs {
         {
             wasm_bindgen_futures::spawn_local(future);
         }
+
+        fn sleep(&self, duration: Duration) -> impl Future<Output = ()> + MaybeSend + 'static {
+            let timeout = duration.as_millis().min(i32::MAX as u128) as i32;
+            let promise = Promise::new(&mut |resolve, _| {
+                set_timeout(&resolve, timeout);
+            });
+            async move {
+                let _ = JsFuture::from(promise).await;
+            }
+        }
     }
 }
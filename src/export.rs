@@ -0,0 +1,341 @@
+use std::{future::Future, io::Write, ops::Bound, pin::pin, sync::Arc};
+
+use arrow::{
+    array::RecordBatch, datatypes::SchemaRef, error::ArrowError, ipc::writer::StreamWriter,
+};
+use fusio::{path::Path, DynFs, MaybeSend};
+use fusio_dispatch::FsOptions;
+use fusio_parquet::writer::AsyncWriter;
+use futures_util::StreamExt;
+use parquet::{arrow::AsyncArrowWriter, errors::ParquetError, file::properties::WriterProperties};
+use thiserror::Error;
+
+use crate::{fs::FileType, inmem::immutable::ArrowArrays, record::Record, DbError, DB};
+
+/// Destination of [`DB::export`](crate::DB::export).
+///
+/// The exported batches only hold the columns of the record, without the internal `_null` and
+/// `_ts` columns, and are written in the order of the primary key.
+pub trait ExportWriter {
+    /// called once with the schema of the exported batches, before any batch is written
+    fn begin(&mut self, schema: SchemaRef) -> impl Future<Output = Result<(), ExportError>>;
+
+    fn write(&mut self, batch: RecordBatch) -> impl Future<Output = Result<(), ExportError>>;
+
+    /// called once after the last batch is written
+    fn finish(&mut self) -> impl Future<Output = Result<(), ExportError>>;
+}
+
+/// write the export as a single Arrow IPC stream
+pub struct IpcExportWriter<W>
+where
+    W: Write,
+{
+    inner: Option<W>,
+    writer: Option<StreamWriter<W>>,
+}
+
+impl<W> IpcExportWriter<W>
+where
+    W: Write,
+{
+    pub fn new(inner: W) -> Self {
+        IpcExportWriter {
+            inner: Some(inner),
+            writer: None,
+        }
+    }
+
+    /// the underlying writer, the stream is finished if it was not
+    pub fn into_inner(self) -> Result<W, ExportError> {
+        match self.writer {
+            Some(writer) => Ok(writer.into_inner()?),
+            None => Ok(self.inner.unwrap()),
+        }
+    }
+}
+
+impl<W> ExportWriter for IpcExportWriter<W>
+where
+    W: Write + MaybeSend,
+{
+    async fn begin(&mut self, schema: SchemaRef) -> Result<(), ExportError> {
+        let inner = self.inner.take().expect("export has begun");
+        self.writer = Some(StreamWriter::try_new(inner, &schema)?);
+        Ok(())
+    }
+
+    async fn write(&mut self, batch: RecordBatch) -> Result<(), ExportError> {
+        self.writer
+            .as_mut()
+            .expect("export has not begun")
+            .write(&batch)?;
+        Ok(())
+    }
+
+    async fn finish(&mut self) -> Result<(), ExportError> {
+        self.writer
+            .as_mut()
+            .expect("export has not begun")
+            .finish()?;
+        Ok(())
+    }
+}
+
+/// write the export as a dataset of parquet files `part-<n>.parquet` in a directory, each holding
+/// at most `max_rows_per_file` rows
+pub struct ParquetDatasetWriter {
+    fs: Arc<dyn DynFs>,
+    dir: Path,
+    max_rows_per_file: usize,
+    properties: WriterProperties,
+    schema: Option<SchemaRef>,
+    writer: Option<AsyncArrowWriter<AsyncWriter>>,
+    row_count: usize,
+    file_count: usize,
+}
+
+impl ParquetDatasetWriter {
+    /// create the dataset in `dir` of the file system described by `fs_options`
+    pub async fn new(
+        dir: impl Into<Path>,
+        fs_options: FsOptions,
+        max_rows_per_file: usize,
+    ) -> Result<Self, ExportError> {
+        assert!(max_rows_per_file > 0, "max_rows_per_file must be positive");
+
+        let fs = fs_options.parse()?;
+        let dir = dir.into();
+        fs.create_dir_all(&dir).await?;
+
+        Ok(ParquetDatasetWriter {
+            fs,
+            dir,
+            max_rows_per_file,
+            properties: WriterProperties::default(),
+            schema: None,
+            writer: None,
+            row_count: 0,
+            file_count: 0,
+        })
+    }
+
+    /// properties of the parquet files written
+    pub fn properties(self, properties: WriterProperties) -> Self {
+        ParquetDatasetWriter { properties, ..self }
+    }
+
+    /// number of files written
+    pub fn file_count(&self) -> usize {
+        self.file_count
+    }
+
+    async fn close(&mut self) -> Result<(), ExportError> {
+        if let Some(writer) = self.writer.take() {
+            writer.close().await?;
+        }
+        self.row_count = 0;
+        Ok(())
+    }
+}
+
+impl ExportWriter for ParquetDatasetWriter {
+    async fn begin(&mut self, schema: SchemaRef) -> Result<(), ExportError> {
+        self.schema = Some(schema);
+        Ok(())
+    }
+
+    async fn write(&mut self, batch: RecordBatch) -> Result<(), ExportError> {
+        let mut offset = 0;
+
+        while offset < batch.num_rows() {
+            if self.writer.is_none() {
+                let path = self
+                    .dir
+                    .child(format!("part-{:05}.parquet", self.file_count));
+                let file = self
+                    .fs
+                    .open_options(&path, FileType::Parquet.open_options(false))
+                    .await?;
+                self.file_count += 1;
+                self.writer = Some(AsyncArrowWriter::try_new(
+                    AsyncWriter::new(file),
+                    self.schema.clone().expect("export has not begun"),
+                    Some(self.properties.clone()),
+                )?);
+            }
+            let writer = self.writer.as_mut().unwrap();
+            let len = (self.max_rows_per_file - self.row_count).min(batch.num_rows() - offset);
+            writer.write(&batch.slice(offset, len)).await?;
+            offset += len;
+            self.row_count += len;
+
+            if self.row_count >= self.max_rows_per_file {
+                self.close().await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn finish(&mut self) -> Result<(), ExportError> {
+        self.close().await
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum ExportError {
+    #[error("export arrow error: {0}")]
+    Arrow(#[from] ArrowError),
+    #[error("export parquet error: {0}")]
+    Parquet(#[from] ParquetError),
+    #[error("export fusio error: {0}")]
+    Fusio(#[from] fusio::Error),
+}
+
+impl<R> DB<R>
+where
+    R: Record + Send + Sync,
+    R::Columns: Send + Sync,
+{
+    /// write the latest version of every record in the `range` to the `writer`, as seen by a
+    /// snapshot taken when the export starts, and return the number of records exported
+    pub async fn export(
+        &self,
+        range: (Bound<&R::Key>, Bound<&R::Key>),
+        writer: &mut impl ExportWriter,
+    ) -> Result<usize, DbError<R>> {
+        let arrow_schema = self.schema.read().await.record_instance.arrow_schema::<R>();
+        // skip two columns: _null and _ts
+        let indices = (2..arrow_schema.fields().len()).collect::<Vec<_>>();
+        writer
+            .begin(Arc::new(
+                arrow_schema.project(&indices).map_err(ExportError::from)?,
+            ))
+            .await?;
+
+        let snapshot = self.snapshot().await;
+        let mut batches = pin!(snapshot.scan(range).package(8192).await?);
+        let mut row_count = 0;
+        while let Some(columns) = batches.next().await.transpose()? {
+            let batch = columns
+                .as_record_batch()
+                .project(&indices)
+                .map_err(ExportError::from)?;
+            row_count += batch.num_rows();
+            writer.write(batch).await?;
+        }
+        writer.finish().await?;
+
+        Ok(row_count)
+    }
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod tests {
+    use std::{fs::File, ops::Bound};
+
+    use arrow::ipc::reader::StreamReader;
+    use fusio::path::Path;
+    use fusio_dispatch::FsOptions;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use tempfile::TempDir;
+
+    use super::{IpcExportWriter, ParquetDatasetWriter};
+    use crate::{
+        executor::tokio::TokioExecutor,
+        tests::{test_items, Test},
+        DbOption, DB,
+    };
+
+    #[tokio::test]
+    async fn export() {
+        let temp_dir = TempDir::new().unwrap();
+        let option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap());
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+
+        for item in test_items() {
+            db.insert(item).await.unwrap();
+        }
+        db.remove("1".to_string()).await.unwrap();
+        // a newer version of an exported key
+        let mut item = test_items().remove(2);
+        item.vu32 = 100;
+        db.insert(item).await.unwrap();
+
+        let mut writer = IpcExportWriter::new(Vec::new());
+        let rows = db
+            .export((Bound::Unbounded, Bound::Unbounded), &mut writer)
+            .await
+            .unwrap();
+        assert_eq!(rows, 39);
+
+        let bytes = writer.into_inner().unwrap();
+        let reader = StreamReader::try_new(bytes.as_slice(), None).unwrap();
+        assert_eq!(
+            reader
+                .schema()
+                .fields()
+                .iter()
+                .map(|field| field.name().as_str())
+                .collect::<Vec<_>>(),
+            vec!["vstring", "vu32", "vbool"]
+        );
+        let mut keys = Vec::new();
+        for batch in reader {
+            let batch = batch.unwrap();
+            let vstring = batch
+                .column(0)
+                .as_any()
+                .downcast_ref::<arrow::array::StringArray>()
+                .unwrap();
+            let vu32 = batch
+                .column(1)
+                .as_any()
+                .downcast_ref::<arrow::array::UInt32Array>()
+                .unwrap();
+            for i in 0..batch.num_rows() {
+                if vstring.value(i) == "2" {
+                    assert_eq!(vu32.value(i), 100);
+                }
+                keys.push(vstring.value(i).to_string());
+            }
+        }
+        let mut expected = test_items()
+            .into_iter()
+            .map(|item| item.vstring)
+            .filter(|key| key != "1")
+            .collect::<Vec<_>>();
+        expected.sort();
+        assert_eq!(keys, expected);
+
+        let dataset_dir = TempDir::new().unwrap();
+        let mut writer = ParquetDatasetWriter::new(
+            Path::from_filesystem_path(dataset_dir.path()).unwrap(),
+            FsOptions::Local,
+            10,
+        )
+        .await
+        .unwrap();
+        let lower = "2".to_string();
+        let rows = db
+            .export((Bound::Included(&lower), Bound::Unbounded), &mut writer)
+            .await
+            .unwrap();
+        assert_eq!(rows, 28);
+        assert_eq!(writer.file_count(), 3);
+
+        let mut row_count = 0;
+        for i in 0..3 {
+            let file =
+                File::open(dataset_dir.path().join(format!("part-{:05}.parquet", i))).unwrap();
+            for batch in ParquetRecordBatchReaderBuilder::try_new(file)
+                .unwrap()
+                .build()
+                .unwrap()
+            {
+                row_count += batch.unwrap().num_rows();
+            }
+        }
+        assert_eq!(row_count, 28);
+    }
+}
@@ -0,0 +1,632 @@
+//! C ABI of tonbo, to embed a database with a runtime schema in applications not written in
+//! Rust.
+//!
+//! Records are exchanged through the [Arrow C Data Interface](https://arrow.apache.org/docs/format/CDataInterface.html):
+//! records are written and read as struct arrays with the columns of the schema given to
+//! [`tonbo_open`], keys as arrays of the type of the primary key, and scans are returned as
+//! [Arrow C Streams](https://arrow.apache.org/docs/format/CStreamInterface.html).
+//!
+//! Every function returns a [`TonboStatus`], the message of the last error of the calling thread
+//! is returned by [`tonbo_last_error`].
+use std::{
+    any::Any,
+    cell::RefCell,
+    ffi::{c_char, CStr, CString},
+    ops::Bound,
+    pin::Pin,
+    ptr,
+    sync::Arc,
+};
+
+use arrow::{
+    array::{make_array, Array, ArrayRef, AsArray, RecordBatch, RecordBatchReader, StructArray},
+    compute::concat_batches,
+    datatypes::{
+        DataType, Int16Type, Int32Type, Int64Type, Int8Type, Schema, SchemaRef, UInt16Type,
+        UInt32Type, UInt64Type, UInt8Type,
+    },
+    error::ArrowError,
+    ffi::{from_ffi, to_ffi, FFI_ArrowArray, FFI_ArrowSchema},
+    ffi_stream::FFI_ArrowArrayStream,
+};
+use async_stream::stream;
+use futures_core::Stream;
+use futures_util::StreamExt;
+use tokio::runtime::{Builder, Runtime};
+
+use crate::{
+    executor::tokio::TokioExecutor,
+    fs,
+    inmem::immutable::ArrowArrays,
+    record::{Column, ColumnDesc, Datatype, DynRecord},
+    DbOption, DB,
+};
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TonboStatus {
+    Ok = 0,
+    /// an argument is null, or does not match the schema of the database
+    InvalidArgument = 1,
+    Error = 2,
+}
+
+/// A bound of a scan, unbounded when `array` is null. `array` holds the key as its only element
+/// and is released by [`tonbo_scan`].
+#[repr(C)]
+pub struct TonboBound {
+    pub array: *mut FFI_ArrowArray,
+    pub schema: *const FFI_ArrowSchema,
+    pub inclusive: bool,
+}
+
+/// An open database, created by [`tonbo_open`] and released by [`tonbo_close`].
+pub struct TonboDb {
+    // shared with the scans, which may outlive the database
+    runtime: Arc<Runtime>,
+    db: Arc<DB<DynRecord>>,
+    schema: SchemaRef,
+    descs: Vec<ColumnDesc>,
+    primary_key_index: usize,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn fail(status: TonboStatus, err: impl ToString) -> TonboStatus {
+    let message = CString::new(err.to_string().replace('\0', " ")).unwrap();
+    LAST_ERROR.with(|last_error| *last_error.borrow_mut() = Some(message));
+    status
+}
+
+macro_rules! try_status {
+    ($status:expr, $result:expr) => {
+        match $result {
+            Ok(value) => value,
+            Err(err) => return fail($status, err),
+        }
+    };
+}
+
+/// The message of the last error returned to the calling thread, or null. The message is valid
+/// until the next call failing on the thread.
+#[no_mangle]
+pub extern "C" fn tonbo_last_error() -> *const c_char {
+    LAST_ERROR.with(|last_error| {
+        last_error
+            .borrow()
+            .as_ref()
+            .map_or(ptr::null(), |message| message.as_ptr())
+    })
+}
+
+/// Open the database at the local `path`, created if absent, with the columns of the struct
+/// `schema`, the column at `primary_key_index` being the primary key. The `schema` is not
+/// released.
+///
+/// # Safety
+///
+/// `path` must be a null-terminated string, `schema` a valid Arrow schema and `db` a valid
+/// pointer.
+#[no_mangle]
+pub unsafe extern "C" fn tonbo_open(
+    path: *const c_char,
+    schema: *const FFI_ArrowSchema,
+    primary_key_index: usize,
+    db: *mut *mut TonboDb,
+) -> TonboStatus {
+    if path.is_null() || schema.is_null() || db.is_null() {
+        return fail(TonboStatus::InvalidArgument, "null argument");
+    }
+    let path = try_status!(TonboStatus::InvalidArgument, CStr::from_ptr(path).to_str());
+    let schema = try_status!(TonboStatus::InvalidArgument, Schema::try_from(&*schema));
+    let mut descs = Vec::with_capacity(schema.fields().len());
+    for field in schema.fields() {
+        let Some(datatype) = datatype(field.data_type()) else {
+            return fail(
+                TonboStatus::InvalidArgument,
+                format!("unsupported type of column {}", field.name()),
+            );
+        };
+        descs.push(ColumnDesc::new(
+            field.name().clone(),
+            datatype,
+            field.is_nullable(),
+        ));
+    }
+    let Some(primary_key) = descs.get(primary_key_index) else {
+        return fail(
+            TonboStatus::InvalidArgument,
+            "primary key index out of range",
+        );
+    };
+    if primary_key.is_nullable {
+        return fail(
+            TonboStatus::InvalidArgument,
+            "primary key must not be nullable",
+        );
+    }
+    let option = DbOption::with_path(
+        try_status!(TonboStatus::InvalidArgument, fs::local_path(path)),
+        primary_key.name.clone(),
+        primary_key_index,
+    );
+    let runtime = try_status!(
+        TonboStatus::Error,
+        Builder::new_multi_thread().enable_all().build()
+    );
+    let tonbo = try_status!(
+        TonboStatus::Error,
+        runtime.block_on(DB::with_schema(
+            option,
+            TokioExecutor::new(),
+            descs.clone(),
+            primary_key_index,
+        ))
+    );
+    let schema = DynRecord::empty_record(descs.clone(), primary_key_index).arrow_schema();
+    // skip two columns: _null and _ts
+    let indices = (2..schema.fields().len()).collect::<Vec<_>>();
+
+    *db = Box::into_raw(Box::new(TonboDb {
+        runtime: Arc::new(runtime),
+        db: Arc::new(tonbo),
+        schema: Arc::new(schema.project(&indices).unwrap()),
+        descs,
+        primary_key_index,
+    }));
+    TonboStatus::Ok
+}
+
+/// Flush the WAL and release the `db`.
+///
+/// # Safety
+///
+/// `db` must have been returned by [`tonbo_open`] and not be used afterward.
+#[no_mangle]
+pub unsafe extern "C" fn tonbo_close(db: *mut TonboDb) -> TonboStatus {
+    if db.is_null() {
+        return fail(TonboStatus::InvalidArgument, "null argument");
+    }
+    let db = Box::from_raw(db);
+    try_status!(TonboStatus::Error, db.runtime.block_on(db.db.flush_wal()));
+    TonboStatus::Ok
+}
+
+/// Write the records of the struct `array` as a single batch. The `array` is released, the
+/// `schema` is not.
+///
+/// # Safety
+///
+/// `db` must have been returned by [`tonbo_open`], `array` and `schema` must be a valid Arrow
+/// array and its schema.
+#[no_mangle]
+pub unsafe extern "C" fn tonbo_put(
+    db: *const TonboDb,
+    array: *mut FFI_ArrowArray,
+    schema: *const FFI_ArrowSchema,
+) -> TonboStatus {
+    let (db, array) = try_status!(TonboStatus::InvalidArgument, import(db, array, schema));
+    let Some(array) = array.as_struct_opt() else {
+        return fail(
+            TonboStatus::InvalidArgument,
+            "records must be a struct array",
+        );
+    };
+    if array.num_columns() != db.descs.len()
+        || array
+            .columns()
+            .iter()
+            .zip(&db.descs)
+            .any(|(column, desc)| datatype(column.data_type()) != Some(desc.datatype))
+    {
+        return fail(
+            TonboStatus::InvalidArgument,
+            "records do not match the schema of the database",
+        );
+    }
+    if array.column(db.primary_key_index).null_count() > 0 {
+        return fail(TonboStatus::InvalidArgument, "primary key must not be null");
+    }
+    let records = (0..array.len())
+        .map(|row| {
+            let columns = array
+                .columns()
+                .iter()
+                .zip(&db.descs)
+                .enumerate()
+                .map(|(i, (column, desc))| to_column(column, row, desc, i == db.primary_key_index))
+                .collect();
+            DynRecord::new(columns, db.primary_key_index)
+        })
+        .collect::<Vec<_>>();
+
+    try_status!(
+        TonboStatus::Error,
+        db.runtime.block_on(db.db.insert_batch(records.into_iter()))
+    );
+    TonboStatus::Ok
+}
+
+/// Delete the records with the keys of `array`. The `array` is released, the `schema` is not.
+///
+/// # Safety
+///
+/// `db` must have been returned by [`tonbo_open`], `array` and `schema` must be a valid Arrow
+/// array and its schema.
+#[no_mangle]
+pub unsafe extern "C" fn tonbo_delete(
+    db: *const TonboDb,
+    array: *mut FFI_ArrowArray,
+    schema: *const FFI_ArrowSchema,
+) -> TonboStatus {
+    let (db, array) = try_status!(TonboStatus::InvalidArgument, import(db, array, schema));
+    let keys = try_status!(TonboStatus::InvalidArgument, db.keys(&array));
+
+    try_status!(
+        TonboStatus::Error,
+        db.runtime.block_on(async {
+            for key in keys {
+                db.db.remove(key).await?;
+            }
+            Ok::<_, crate::transaction::CommitError<DynRecord>>(())
+        })
+    );
+    TonboStatus::Ok
+}
+
+/// Get the record with the key held by the one element `array` into `out_array` and
+/// `out_schema`, as a struct array with no row if the record is absent. The `array` is
+/// released, the `schema` is not.
+///
+/// # Safety
+///
+/// `db` must have been returned by [`tonbo_open`], `array` and `schema` must be a valid Arrow
+/// array and its schema, `out_array` and `out_schema` valid pointers.
+#[no_mangle]
+pub unsafe extern "C" fn tonbo_get(
+    db: *const TonboDb,
+    array: *mut FFI_ArrowArray,
+    schema: *const FFI_ArrowSchema,
+    out_array: *mut FFI_ArrowArray,
+    out_schema: *mut FFI_ArrowSchema,
+) -> TonboStatus {
+    if out_array.is_null() || out_schema.is_null() {
+        return fail(TonboStatus::InvalidArgument, "null argument");
+    }
+    let (db, array) = try_status!(TonboStatus::InvalidArgument, import(db, array, schema));
+    let mut keys = try_status!(TonboStatus::InvalidArgument, db.keys(&array));
+    if keys.len() != 1 {
+        return fail(TonboStatus::InvalidArgument, "a single key is expected");
+    }
+    let key = keys.pop().unwrap();
+    let scan = db.scan((Bound::Included(key.clone()), Bound::Included(key)), None);
+    let batches = try_status!(
+        TonboStatus::Error,
+        db.runtime
+            .block_on(scan.collect::<Vec<_>>())
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()
+    );
+    let batch = try_status!(TonboStatus::Error, concat_batches(&db.schema, &batches));
+    let (array, schema) = try_status!(
+        TonboStatus::Error,
+        to_ffi(&StructArray::from(batch).into_data())
+    );
+
+    ptr::write(out_array, array);
+    ptr::write(out_schema, schema);
+    TonboStatus::Ok
+}
+
+/// Scan the records with keys between `lower` and `upper` in the order of the keys, as struct
+/// arrays of the columns at the `projection_len` indices of `projection`, or all the columns if
+/// `projection` is null. The scan reads a snapshot of the database taken on the first read of
+/// the stream written to `out`.
+///
+/// # Safety
+///
+/// `db` must have been returned by [`tonbo_open`], the bounds must be valid as described by
+/// [`TonboBound`], `projection` must be null or hold `projection_len` indices and `out` be a valid
+/// pointer.
+#[no_mangle]
+pub unsafe extern "C" fn tonbo_scan(
+    db: *const TonboDb,
+    lower: TonboBound,
+    upper: TonboBound,
+    projection: *const usize,
+    projection_len: usize,
+    out: *mut FFI_ArrowArrayStream,
+) -> TonboStatus {
+    if db.is_null() || out.is_null() {
+        return fail(TonboStatus::InvalidArgument, "null argument");
+    }
+    let db = &*db;
+    let lower = try_status!(TonboStatus::InvalidArgument, db.bound(lower));
+    let upper = try_status!(TonboStatus::InvalidArgument, db.bound(upper));
+    let projection = (!projection.is_null())
+        .then(|| std::slice::from_raw_parts(projection, projection_len).to_vec());
+    if projection
+        .iter()
+        .flatten()
+        .any(|index| *index >= db.descs.len())
+    {
+        return fail(TonboStatus::InvalidArgument, "projection out of range");
+    }
+    let schema = match &projection {
+        Some(projection) => Arc::new(db.schema.project(projection).unwrap()),
+        None => db.schema.clone(),
+    };
+    let reader = ScanReader {
+        runtime: db.runtime.clone(),
+        stream: Box::pin(db.scan((lower, upper), projection)),
+        schema,
+    };
+
+    ptr::write(out, FFI_ArrowArrayStream::new(Box::new(reader)));
+    TonboStatus::Ok
+}
+
+type BatchStream = Pin<Box<dyn Stream<Item = Result<RecordBatch, ArrowError>> + Send>>;
+
+struct ScanReader {
+    runtime: Arc<Runtime>,
+    stream: BatchStream,
+    schema: SchemaRef,
+}
+
+impl Iterator for ScanReader {
+    type Item = Result<RecordBatch, ArrowError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.runtime.block_on(self.stream.next())
+    }
+}
+
+impl RecordBatchReader for ScanReader {
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+}
+
+impl TonboDb {
+    fn scan(
+        &self,
+        (lower, upper): (Bound<Column>, Bound<Column>),
+        projection: Option<Vec<usize>>,
+    ) -> impl Stream<Item = Result<RecordBatch, ArrowError>> + Send + 'static {
+        let db = self.db.clone();
+        // skip two columns: _null and _ts
+        let indices = match &projection {
+            Some(projection) => projection.iter().map(|i| i + 2).collect(),
+            None => (2..self.descs.len() + 2).collect::<Vec<_>>(),
+        };
+
+        stream! {
+            let snapshot = db.snapshot().await;
+            let mut scan = snapshot.scan((lower.as_ref(), upper.as_ref()));
+            if let Some(projection) = projection {
+                scan = scan.projection(projection);
+            }
+            let mut batches = scan
+                .package(8192)
+                .await
+                .map_err(|err| ArrowError::ExternalError(err.to_string().into()))?;
+
+            while let Some(columns) = batches.next().await {
+                let columns = columns.map_err(|err| ArrowError::ExternalError(Box::new(err)))?;
+                yield columns.as_record_batch().project(&indices);
+            }
+        }
+    }
+
+    /// the keys held by `array`
+    fn keys(&self, array: &ArrayRef) -> Result<Vec<Column>, &'static str> {
+        let desc = &self.descs[self.primary_key_index];
+        if datatype(array.data_type()) != Some(desc.datatype) {
+            return Err("keys do not match the type of the primary key");
+        }
+        if array.null_count() > 0 {
+            return Err("keys must not be null");
+        }
+        Ok((0..array.len())
+            .map(|row| to_column(array, row, desc, true))
+            .collect())
+    }
+
+    unsafe fn bound(&self, bound: TonboBound) -> Result<Bound<Column>, String> {
+        if bound.array.is_null() {
+            return Ok(Bound::Unbounded);
+        }
+        let (_, array) = import(self, bound.array, bound.schema)?;
+        let mut keys = self.keys(&array)?;
+        if keys.len() != 1 {
+            return Err("a bound holds a single key".to_string());
+        }
+        let key = keys.pop().unwrap();
+
+        Ok(match bound.inclusive {
+            true => Bound::Included(key),
+            false => Bound::Excluded(key),
+        })
+    }
+}
+
+/// take the ownership of the `array`
+unsafe fn import<'a>(
+    db: *const TonboDb,
+    array: *mut FFI_ArrowArray,
+    schema: *const FFI_ArrowSchema,
+) -> Result<(&'a TonboDb, ArrayRef), String> {
+    if db.is_null() || array.is_null() || schema.is_null() {
+        return Err("null argument".to_string());
+    }
+    let data =
+        from_ffi(FFI_ArrowArray::from_raw(array), &*schema).map_err(|err| err.to_string())?;
+
+    Ok((&*db, make_array(data)))
+}
+
+fn datatype(data_type: &DataType) -> Option<Datatype> {
+    Some(match data_type {
+        DataType::UInt8 => Datatype::UInt8,
+        DataType::UInt16 => Datatype::UInt16,
+        DataType::UInt32 => Datatype::UInt32,
+        DataType::UInt64 => Datatype::UInt64,
+        DataType::Int8 => Datatype::Int8,
+        DataType::Int16 => Datatype::Int16,
+        DataType::Int32 => Datatype::Int32,
+        DataType::Int64 => Datatype::Int64,
+        DataType::Utf8 => Datatype::String,
+        DataType::Boolean => Datatype::Boolean,
+        DataType::Binary => Datatype::Bytes,
+        DataType::LargeBinary => Datatype::LargeBytes,
+        DataType::FixedSizeBinary(size) => Datatype::FixedSizeBytes(*size),
+        _ => return None,
+    })
+}
+
+/// the value at `row` of the `array` of the type of `desc`, not null if `is_primary_key`
+fn to_column(array: &ArrayRef, row: usize, desc: &ColumnDesc, is_primary_key: bool) -> Column {
+    macro_rules! value {
+        ($value:expr) => {{
+            let value = array.is_valid(row).then(|| $value);
+            match is_primary_key {
+                true => Arc::new(value.unwrap()) as Arc<dyn Any + Send + Sync>,
+                false => Arc::new(value) as _,
+            }
+        }};
+    }
+    let value = match desc.datatype {
+        Datatype::UInt8 => value!(array.as_primitive::<UInt8Type>().value(row)),
+        Datatype::UInt16 => value!(array.as_primitive::<UInt16Type>().value(row)),
+        Datatype::UInt32 => value!(array.as_primitive::<UInt32Type>().value(row)),
+        Datatype::UInt64 => value!(array.as_primitive::<UInt64Type>().value(row)),
+        Datatype::Int8 => value!(array.as_primitive::<Int8Type>().value(row)),
+        Datatype::Int16 => value!(array.as_primitive::<Int16Type>().value(row)),
+        Datatype::Int32 => value!(array.as_primitive::<Int32Type>().value(row)),
+        Datatype::Int64 => value!(array.as_primitive::<Int64Type>().value(row)),
+        Datatype::String => value!(array.as_string::<i32>().value(row).to_string()),
+        Datatype::Boolean => value!(array.as_boolean().value(row)),
+        Datatype::Bytes => value!(array.as_binary::<i32>().value(row).to_vec()),
+        Datatype::LargeBytes => value!(array.as_binary::<i64>().value(row).to_vec()),
+        Datatype::FixedSizeBytes(_) => value!(array.as_fixed_size_binary().value(row).to_vec()),
+    };
+
+    Column::new(desc.datatype, desc.name.clone(), value, desc.is_nullable)
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod tests {
+    use std::{ffi::CString, ptr, sync::Arc};
+
+    use arrow::{
+        array::{Array, AsArray, Int64Array, RecordBatch, StringArray, StructArray},
+        datatypes::{DataType, Field, Int64Type, Schema},
+        ffi::{from_ffi, to_ffi, FFI_ArrowArray, FFI_ArrowSchema},
+        ffi_stream::{ArrowArrayStreamReader, FFI_ArrowArrayStream},
+    };
+    use tempfile::TempDir;
+
+    use super::{
+        tonbo_close, tonbo_delete, tonbo_get, tonbo_open, tonbo_put, tonbo_scan, TonboBound,
+        TonboStatus,
+    };
+
+    fn key(key: i64) -> (FFI_ArrowArray, FFI_ArrowSchema) {
+        to_ffi(&Int64Array::from(vec![key]).into_data()).unwrap()
+    }
+
+    #[test]
+    fn ffi() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = CString::new(temp_dir.path().to_str().unwrap()).unwrap();
+        let schema = Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("name", DataType::Utf8, true),
+        ]);
+        let ffi_schema = FFI_ArrowSchema::try_from(&schema).unwrap();
+
+        unsafe {
+            let mut db = ptr::null_mut();
+            assert_eq!(
+                tonbo_open(path.as_ptr(), &ffi_schema, 0, &mut db),
+                TonboStatus::Ok
+            );
+
+            let batch = RecordBatch::try_new(
+                Arc::new(schema.clone()),
+                vec![
+                    Arc::new(Int64Array::from_iter_values(0..10)),
+                    Arc::new(StringArray::from_iter(
+                        (0..10).map(|i| (i % 2 == 0).then(|| i.to_string())),
+                    )),
+                ],
+            )
+            .unwrap();
+            let (mut array, array_schema) = to_ffi(&StructArray::from(batch).into_data()).unwrap();
+            assert_eq!(tonbo_put(db, &mut array, &array_schema), TonboStatus::Ok);
+
+            let (mut array, array_schema) = key(4);
+            let mut out_array = FFI_ArrowArray::empty();
+            let mut out_schema = FFI_ArrowSchema::empty();
+            assert_eq!(
+                tonbo_get(
+                    db,
+                    &mut array,
+                    &array_schema,
+                    &mut out_array,
+                    &mut out_schema
+                ),
+                TonboStatus::Ok
+            );
+            let record = StructArray::from(from_ffi(out_array, &out_schema).unwrap());
+            assert_eq!(record.len(), 1);
+            assert_eq!(record.column(1).as_string::<i32>().value(0), "4");
+
+            let (mut array, array_schema) = key(4);
+            assert_eq!(tonbo_delete(db, &mut array, &array_schema), TonboStatus::Ok);
+
+            let (mut array, array_schema) = key(2);
+            let (mut upper, upper_schema) = key(7);
+            let projection = [0];
+            let mut stream = FFI_ArrowArrayStream::empty();
+            assert_eq!(
+                tonbo_scan(
+                    db,
+                    TonboBound {
+                        array: &mut array,
+                        schema: &array_schema,
+                        inclusive: true,
+                    },
+                    TonboBound {
+                        array: &mut upper,
+                        schema: &upper_schema,
+                        inclusive: false,
+                    },
+                    projection.as_ptr(),
+                    projection.len(),
+                    &mut stream,
+                ),
+                TonboStatus::Ok
+            );
+            let reader = ArrowArrayStreamReader::try_new(stream).unwrap();
+            let mut ids = Vec::new();
+            for batch in reader {
+                let batch = batch.unwrap();
+                assert_eq!(batch.num_columns(), 1);
+                ids.extend(batch.column(0).as_primitive::<Int64Type>().values().iter());
+            }
+            assert_eq!(ids, vec![2, 3, 5, 6]);
+
+            let (mut array, array_schema) =
+                to_ffi(&StringArray::from(vec!["0"]).into_data()).unwrap();
+            assert_eq!(
+                tonbo_delete(db, &mut array, &array_schema),
+                TonboStatus::InvalidArgument
+            );
+            assert_eq!(tonbo_close(db), TonboStatus::Ok);
+        }
+    }
+}
@@ -0,0 +1,495 @@
+use std::{io::Cursor, ops::Bound, pin::Pin, sync::Arc};
+
+use arrow::{datatypes::SchemaRef, error::ArrowError, ipc::writer::IpcWriteOptions};
+use arrow_flight::{
+    encode::FlightDataEncoderBuilder, error::FlightError, flight_service_server,
+    flight_service_server::FlightServiceServer, Action, ActionType, Criteria, Empty, FlightData,
+    FlightDescriptor, FlightEndpoint, FlightInfo, HandshakeRequest, HandshakeResponse, PollInfo,
+    PutResult, SchemaAsIpc, SchemaResult, Ticket,
+};
+use async_stream::stream;
+use fusio::{SeqRead, Write};
+use futures_core::Stream;
+use futures_util::{StreamExt, TryStreamExt};
+use tonic::{Request, Response, Status, Streaming};
+
+use crate::{
+    inmem::immutable::ArrowArrays,
+    record::{Key, Record},
+    serdes::{Decode, Encode},
+    DB,
+};
+
+/// The scan requested by the ticket of a `DoGet`, built by clients with
+/// [`ScanTicket::to_ticket`].
+///
+/// `projection` holds the indices of the columns of the record, the internal `_null` and `_ts`
+/// columns excluded.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScanTicket<K> {
+    pub lower: Bound<K>,
+    pub upper: Bound<K>,
+    pub projection: Option<Vec<usize>>,
+    pub limit: Option<usize>,
+}
+
+impl<K> ScanTicket<K>
+where
+    K: Key,
+{
+    /// scan the records with primary keys in the `range`
+    pub fn new(range: (Bound<K>, Bound<K>)) -> Self {
+        ScanTicket {
+            lower: range.0,
+            upper: range.1,
+            projection: None,
+            limit: None,
+        }
+    }
+
+    /// get the record with `key` as the primary key
+    pub fn get(key: K) -> Self {
+        Self::new((Bound::Included(key.clone()), Bound::Included(key)))
+    }
+
+    pub fn projection(self, projection: Vec<usize>) -> Self {
+        ScanTicket {
+            projection: Some(projection),
+            ..self
+        }
+    }
+
+    pub fn limit(self, limit: usize) -> Self {
+        ScanTicket {
+            limit: Some(limit),
+            ..self
+        }
+    }
+
+    pub async fn to_ticket(&self) -> Result<Ticket, <K as Encode>::Error> {
+        let mut bytes = Vec::new();
+        self.encode(&mut Cursor::new(&mut bytes)).await?;
+        Ok(Ticket::new(bytes))
+    }
+
+    pub async fn from_ticket(ticket: &Ticket) -> Result<Self, <K as Decode>::Error> {
+        let mut bytes = ticket.ticket.to_vec();
+        Self::decode(&mut Cursor::new(&mut bytes)).await
+    }
+}
+
+impl<K> Encode for ScanTicket<K>
+where
+    K: Encode + Sync,
+{
+    type Error = <K as Encode>::Error;
+
+    async fn encode<W>(&self, writer: &mut W) -> Result<(), Self::Error>
+    where
+        W: Write,
+    {
+        for bound in [&self.lower, &self.upper] {
+            match bound {
+                Bound::Included(key) => {
+                    0u8.encode(writer).await?;
+                    key.encode(writer).await?;
+                }
+                Bound::Excluded(key) => {
+                    1u8.encode(writer).await?;
+                    key.encode(writer).await?;
+                }
+                Bound::Unbounded => 2u8.encode(writer).await?,
+            }
+        }
+        match &self.projection {
+            Some(projection) => {
+                1u8.encode(writer).await?;
+                (projection.len() as u32).encode(writer).await?;
+                for index in projection {
+                    (*index as u32).encode(writer).await?;
+                }
+            }
+            None => 0u8.encode(writer).await?,
+        }
+        match self.limit {
+            Some(limit) => {
+                1u8.encode(writer).await?;
+                (limit as u64).encode(writer).await?;
+            }
+            None => 0u8.encode(writer).await?,
+        }
+
+        Ok(())
+    }
+
+    fn size(&self) -> usize {
+        let bound_size = |bound: &Bound<K>| match bound {
+            Bound::Included(key) | Bound::Excluded(key) => 1 + key.size(),
+            Bound::Unbounded => 1,
+        };
+        bound_size(&self.lower)
+            + bound_size(&self.upper)
+            + self
+                .projection
+                .as_ref()
+                .map_or(1, |projection| 5 + 4 * projection.len())
+            + self.limit.map_or(1, |_| 9)
+    }
+}
+
+impl<K> Decode for ScanTicket<K>
+where
+    K: Decode,
+{
+    type Error = <K as Decode>::Error;
+
+    async fn decode<R: SeqRead>(reader: &mut R) -> Result<Self, Self::Error> {
+        // tickets come from the network, so invalid tags are errors instead of panics
+        let invalid = |what: &str| fusio::Error::Other(format!("invalid {} tag", what).into());
+        let mut bounds = Vec::with_capacity(2);
+
+        for _ in 0..2 {
+            bounds.push(match u8::decode(reader).await? {
+                0 => Bound::Included(K::decode(reader).await?),
+                1 => Bound::Excluded(K::decode(reader).await?),
+                2 => Bound::Unbounded,
+                _ => return Err(invalid("bound").into()),
+            });
+        }
+        let projection = match u8::decode(reader).await? {
+            0 => None,
+            1 => {
+                let len = u32::decode(reader).await?;
+                let mut projection = Vec::with_capacity(len.min(1024) as usize);
+                for _ in 0..len {
+                    projection.push(u32::decode(reader).await? as usize);
+                }
+                Some(projection)
+            }
+            _ => return Err(invalid("projection").into()),
+        };
+        let limit = match u8::decode(reader).await? {
+            0 => None,
+            1 => Some(u64::decode(reader).await? as usize),
+            _ => return Err(invalid("limit").into()),
+        };
+        let upper = bounds.pop().unwrap();
+        let lower = bounds.pop().unwrap();
+
+        Ok(ScanTicket {
+            lower,
+            upper,
+            projection,
+            limit,
+        })
+    }
+}
+
+type BoxStream<T> = Pin<Box<dyn Stream<Item = Result<T, Status>> + Send + 'static>>;
+
+/// Serves the gets and scans of a [`DB`] over Arrow Flight, so that an embedded database can be
+/// read through the network without a separate server.
+///
+/// `DoGet` takes a ticket encoded by [`ScanTicket::to_ticket`] and streams the records, without
+/// the internal `_null` and `_ts` columns, in the order of the primary key as seen by a snapshot
+/// taken when the request is received. `GetFlightInfo` answers a descriptor whose command is
+/// such a ticket with a single endpoint for it, and `GetSchema` returns the schema of the
+/// records. Other calls are unimplemented.
+pub struct TonboFlightService<R>
+where
+    R: Record,
+{
+    db: Arc<DB<R>>,
+    schema: SchemaRef,
+}
+
+impl<R> TonboFlightService<R>
+where
+    R: Record + Send + Sync,
+    R::Columns: Send + Sync,
+{
+    pub async fn new(db: Arc<DB<R>>) -> Self {
+        let arrow_schema = db.schema.read().await.record_instance.arrow_schema::<R>();
+        // skip two columns: _null and _ts
+        let indices = (2..arrow_schema.fields().len()).collect::<Vec<_>>();
+
+        TonboFlightService {
+            db,
+            schema: Arc::new(arrow_schema.project(&indices).unwrap()),
+        }
+    }
+
+    /// the gRPC service to be added to a `tonic` server
+    pub fn into_server(self) -> FlightServiceServer<Self> {
+        FlightServiceServer::new(self)
+    }
+
+    fn schema_result(&self, schema: &SchemaRef) -> Result<SchemaResult, Status> {
+        SchemaAsIpc::new(schema, &IpcWriteOptions::default())
+            .try_into()
+            .map_err(|err: ArrowError| Status::internal(err.to_string()))
+    }
+}
+
+#[tonic::async_trait]
+impl<R> flight_service_server::FlightService for TonboFlightService<R>
+where
+    R: Record + Send + Sync,
+    R::Columns: Send + Sync,
+{
+    type HandshakeStream = BoxStream<HandshakeResponse>;
+    type ListFlightsStream = BoxStream<FlightInfo>;
+    type DoGetStream = BoxStream<FlightData>;
+    type DoPutStream = BoxStream<PutResult>;
+    type DoExchangeStream = BoxStream<FlightData>;
+    type DoActionStream = BoxStream<arrow_flight::Result>;
+    type ListActionsStream = BoxStream<ActionType>;
+
+    async fn handshake(
+        &self,
+        _: Request<Streaming<HandshakeRequest>>,
+    ) -> Result<Response<Self::HandshakeStream>, Status> {
+        Err(Status::unimplemented("handshake"))
+    }
+
+    async fn list_flights(
+        &self,
+        _: Request<Criteria>,
+    ) -> Result<Response<Self::ListFlightsStream>, Status> {
+        Err(Status::unimplemented("list_flights"))
+    }
+
+    async fn get_flight_info(
+        &self,
+        request: Request<FlightDescriptor>,
+    ) -> Result<Response<FlightInfo>, Status> {
+        let descriptor = request.into_inner();
+        let ticket = Ticket::new(descriptor.cmd.clone());
+        ScanTicket::<R::Key>::from_ticket(&ticket)
+            .await
+            .map_err(|err| Status::invalid_argument(err.to_string()))?;
+
+        let info = FlightInfo::new()
+            .try_with_schema(&self.schema)
+            .map_err(|err| Status::internal(err.to_string()))?
+            .with_endpoint(FlightEndpoint::new().with_ticket(ticket))
+            .with_descriptor(descriptor);
+        Ok(Response::new(info))
+    }
+
+    async fn poll_flight_info(
+        &self,
+        _: Request<FlightDescriptor>,
+    ) -> Result<Response<PollInfo>, Status> {
+        Err(Status::unimplemented("poll_flight_info"))
+    }
+
+    async fn get_schema(
+        &self,
+        _: Request<FlightDescriptor>,
+    ) -> Result<Response<SchemaResult>, Status> {
+        Ok(Response::new(self.schema_result(&self.schema)?))
+    }
+
+    async fn do_get(
+        &self,
+        request: Request<Ticket>,
+    ) -> Result<Response<Self::DoGetStream>, Status> {
+        let ScanTicket {
+            lower,
+            upper,
+            projection,
+            limit,
+        } = ScanTicket::<R::Key>::from_ticket(request.get_ref())
+            .await
+            .map_err(|err| Status::invalid_argument(err.to_string()))?;
+        let column_count = self.schema.fields().len();
+        if projection
+            .iter()
+            .flatten()
+            .any(|index| *index >= column_count)
+        {
+            return Err(Status::invalid_argument("projection out of range"));
+        }
+        let schema = match &projection {
+            Some(projection) => Arc::new(
+                self.schema
+                    .project(projection)
+                    .map_err(|err| Status::invalid_argument(err.to_string()))?,
+            ),
+            None => self.schema.clone(),
+        };
+        // skip two columns: _null and _ts
+        let indices = match &projection {
+            Some(projection) => projection.iter().map(|i| i + 2).collect(),
+            None => (2..column_count + 2).collect::<Vec<_>>(),
+        };
+
+        let db = self.db.clone();
+        let batches = stream! {
+            let snapshot = db.snapshot().await;
+            let mut scan = snapshot.scan((lower.as_ref(), upper.as_ref()));
+            if let Some(limit) = limit {
+                scan = scan.limit(limit);
+            }
+            if let Some(projection) = projection {
+                scan = scan.projection(projection);
+            }
+            let mut batches = scan
+                .package(8192)
+                .await
+                .map_err(|err| FlightError::ExternalError(err.to_string().into()))?;
+
+            while let Some(columns) = batches.next().await {
+                let columns =
+                    columns.map_err(|err| FlightError::ExternalError(Box::new(err)))?;
+                yield Ok(columns.as_record_batch().project(&indices)?);
+            }
+        };
+        let flight_data = FlightDataEncoderBuilder::new()
+            .with_schema(schema)
+            .build(batches)
+            .map_err(Status::from);
+
+        Ok(Response::new(Box::pin(flight_data)))
+    }
+
+    async fn do_put(
+        &self,
+        _: Request<Streaming<FlightData>>,
+    ) -> Result<Response<Self::DoPutStream>, Status> {
+        Err(Status::unimplemented("do_put"))
+    }
+
+    async fn do_exchange(
+        &self,
+        _: Request<Streaming<FlightData>>,
+    ) -> Result<Response<Self::DoExchangeStream>, Status> {
+        Err(Status::unimplemented("do_exchange"))
+    }
+
+    async fn do_action(
+        &self,
+        _: Request<Action>,
+    ) -> Result<Response<Self::DoActionStream>, Status> {
+        Err(Status::unimplemented("do_action"))
+    }
+
+    async fn list_actions(
+        &self,
+        _: Request<Empty>,
+    ) -> Result<Response<Self::ListActionsStream>, Status> {
+        Err(Status::unimplemented("list_actions"))
+    }
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod tests {
+    use std::{ops::Bound, sync::Arc};
+
+    use arrow::{
+        array::{AsArray, StringArray},
+        datatypes::UInt32Type,
+    };
+    use arrow_flight::{
+        decode::FlightRecordBatchStream, error::FlightError, flight_service_server::FlightService,
+        FlightDescriptor,
+    };
+    use fusio::path::Path;
+    use futures_util::{StreamExt, TryStreamExt};
+    use tempfile::TempDir;
+    use tonic::{Code, Request};
+
+    use super::{ScanTicket, TonboFlightService};
+    use crate::{
+        executor::tokio::TokioExecutor,
+        tests::{test_items, Test},
+        DbOption, DB,
+    };
+
+    #[tokio::test]
+    async fn ticket_encode_and_decode() {
+        let ticket = ScanTicket::new((Bound::Excluded("a".to_string()), Bound::Unbounded))
+            .projection(vec![1, 2])
+            .limit(10);
+        let decoded = ScanTicket::from_ticket(&ticket.to_ticket().await.unwrap())
+            .await
+            .unwrap();
+        assert_eq!(ticket, decoded);
+
+        let ticket = ScanTicket::get(1u64);
+        let decoded = ScanTicket::from_ticket(&ticket.to_ticket().await.unwrap())
+            .await
+            .unwrap();
+        assert_eq!(ticket, decoded);
+    }
+
+    #[tokio::test]
+    async fn do_get() {
+        let temp_dir = TempDir::new().unwrap();
+        let option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap());
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+        for item in test_items() {
+            db.insert(item).await.unwrap();
+        }
+        let service = TonboFlightService::new(Arc::new(db)).await;
+
+        let ticket = ScanTicket::new((
+            Bound::Included("2".to_string()),
+            Bound::Excluded("3".to_string()),
+        ))
+        .projection(vec![0, 1])
+        .to_ticket()
+        .await
+        .unwrap();
+        let flight_data = service
+            .do_get(Request::new(ticket.clone()))
+            .await
+            .unwrap()
+            .into_inner();
+        let batches =
+            FlightRecordBatchStream::new_from_flight_data(flight_data.map_err(FlightError::from))
+                .try_collect::<Vec<_>>()
+                .await
+                .unwrap();
+
+        let mut keys = Vec::new();
+        let mut vu32s = Vec::new();
+        for batch in batches {
+            assert_eq!(batch.num_columns(), 2);
+            let vstring = batch.column(0).as_any().downcast_ref::<StringArray>();
+            keys.extend(vstring.unwrap().iter().flatten().map(str::to_string));
+            vu32s.extend(batch.column(1).as_primitive::<UInt32Type>().values().iter());
+        }
+        let mut expected = (20..30).map(|i| i.to_string()).collect::<Vec<_>>();
+        expected.insert(0, "2".to_string());
+        assert_eq!(keys, expected);
+        assert_eq!(vu32s[0], 2);
+
+        let info = service
+            .get_flight_info(Request::new(FlightDescriptor::new_cmd(ticket.ticket)))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(info.endpoint.len(), 1);
+
+        let invalid = ScanTicket::new((Bound::<String>::Unbounded, Bound::Unbounded))
+            .projection(vec![3])
+            .to_ticket()
+            .await
+            .unwrap();
+        let err = match service.do_get(Request::new(invalid)).await {
+            Ok(_) => panic!("projection out of range"),
+            Err(err) => err,
+        };
+        assert_eq!(err.code(), Code::InvalidArgument);
+        let mut flight_data = service
+            .do_get(Request::new(
+                ScanTicket::get("0".to_string()).to_ticket().await.unwrap(),
+            ))
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(flight_data.next().await.is_some());
+    }
+}
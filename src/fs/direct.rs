@@ -0,0 +1,305 @@
+use std::{
+    alloc::{self, Layout},
+    fs::{File, OpenOptions},
+    io,
+    os::unix::fs::{FileExt, OpenOptionsExt},
+    ptr::NonNull,
+    slice,
+};
+
+use fusio::{
+    path::{path_to_local, Path},
+    Error, IoBuf, IoBufMut, Read, Write,
+};
+
+/// the alignment of the offsets, lengths and memory of the reads and writes with `O_DIRECT`, a
+/// multiple of the logical block size of the devices
+pub(crate) const ALIGNMENT: usize = 4096;
+/// the bytes a [`DirectFile`] buffers before writing them
+const WRITE_BUFFER_SIZE: usize = 256 * ALIGNMENT;
+
+fn align_down(pos: u64) -> u64 {
+    pos / ALIGNMENT as u64 * ALIGNMENT as u64
+}
+
+fn align_up(len: usize) -> usize {
+    len.div_ceil(ALIGNMENT) * ALIGNMENT
+}
+
+/// A buffer whose memory and capacity are aligned to [`ALIGNMENT`]
+struct AlignedBuf {
+    ptr: NonNull<u8>,
+    capacity: usize,
+    len: usize,
+}
+
+// SAFETY: the buffer owns its memory as a `Vec<u8>` does
+unsafe impl Send for AlignedBuf {}
+unsafe impl Sync for AlignedBuf {}
+
+impl AlignedBuf {
+    /// an empty buffer holding at least `capacity` bytes
+    fn new(capacity: usize) -> Self {
+        let capacity = align_up(capacity.max(1));
+        let layout = Self::layout(capacity);
+        // SAFETY: the size of the layout is not zero
+        let ptr = unsafe { alloc::alloc_zeroed(layout) };
+        let ptr = NonNull::new(ptr).unwrap_or_else(|| alloc::handle_alloc_error(layout));
+
+        AlignedBuf {
+            ptr,
+            capacity,
+            len: 0,
+        }
+    }
+
+    fn layout(capacity: usize) -> Layout {
+        Layout::from_size_align(capacity, ALIGNMENT).expect("invalid aligned buffer size")
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        &self.blocks()[..self.len]
+    }
+
+    /// the whole capacity, past the length
+    fn blocks(&self) -> &[u8] {
+        // SAFETY: the memory is allocated and zeroed up to the capacity
+        unsafe { slice::from_raw_parts(self.ptr.as_ptr(), self.capacity) }
+    }
+
+    fn blocks_mut(&mut self) -> &mut [u8] {
+        // SAFETY: the memory is allocated and zeroed up to the capacity
+        unsafe { slice::from_raw_parts_mut(self.ptr.as_ptr(), self.capacity) }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn is_full(&self) -> bool {
+        self.len == self.capacity
+    }
+
+    /// append as much of `bytes` as fits, returning how many
+    fn extend(&mut self, bytes: &[u8]) -> usize {
+        let len = bytes.len().min(self.capacity - self.len);
+        let start = self.len;
+        self.blocks_mut()[start..start + len].copy_from_slice(&bytes[..len]);
+        self.len += len;
+        len
+    }
+
+    /// the bytes followed by zeros up to the end of their last block
+    fn padded(&mut self) -> &[u8] {
+        let (len, end) = (self.len, align_up(self.len));
+        self.blocks_mut()[len..end].fill(0);
+        &self.blocks()[..end]
+    }
+
+    /// drop the first `len` bytes
+    fn consume(&mut self, len: usize) {
+        let end = self.len;
+        self.blocks_mut().copy_within(len..end, 0);
+        self.len -= len;
+    }
+}
+
+impl Drop for AlignedBuf {
+    fn drop(&mut self) {
+        // SAFETY: the memory was allocated with the same layout
+        unsafe { alloc::dealloc(self.ptr.as_ptr(), Self::layout(self.capacity)) }
+    }
+}
+
+/// A local file opened with `O_DIRECT`, see [`DbOption::direct_io`](crate::DbOption::direct_io).
+/// Reads are widened to whole blocks read into an aligned buffer, and writes are buffered until
+/// a whole [`WRITE_BUFFER_SIZE`] is written at once. A flush writes the last partial block
+/// padded with zeros, truncates the padding away and writes the block again once it grows. The
+/// reads and writes block the thread of the task issuing them.
+pub(crate) struct DirectFile {
+    file: File,
+    /// the bytes from `offset` not written yet, starting on a block boundary
+    buf: Option<AlignedBuf>,
+    offset: u64,
+    size: u64,
+}
+
+impl DirectFile {
+    /// open the file at `path` for reading, or create or truncate it for writing unless
+    /// `only_read`
+    pub(crate) fn open(path: &Path, only_read: bool) -> Result<Self, Error> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(!only_read)
+            .create(!only_read)
+            .truncate(!only_read)
+            .custom_flags(libc::O_DIRECT)
+            .open(path_to_local(path)?)?;
+        let size = file.metadata()?.len();
+
+        Ok(DirectFile {
+            file,
+            buf: None,
+            offset: 0,
+            size,
+        })
+    }
+
+    /// read the `len` bytes at `pos` along with the blocks around them, returning the blocks and
+    /// where the bytes start in them
+    fn read_blocks(&self, pos: u64, len: usize) -> Result<(AlignedBuf, usize), Error> {
+        let start = align_down(pos);
+        let skip = (pos - start) as usize;
+        let mut blocks = AlignedBuf::new(skip + len);
+
+        while blocks.len < skip + len {
+            let read = blocks.len;
+            match self
+                .file
+                .read_at(&mut blocks.blocks_mut()[read..], start + read as u64)
+            {
+                // only the last block of the file is read short
+                Ok(0) => break,
+                Ok(n) => blocks.len += n,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err.into()),
+            }
+        }
+        if blocks.len < skip + len {
+            return Err(Error::Io(io::ErrorKind::UnexpectedEof.into()));
+        }
+        Ok((blocks, skip))
+    }
+
+    fn append(&mut self, mut bytes: &[u8]) -> Result<(), Error> {
+        let buf = self
+            .buf
+            .get_or_insert_with(|| AlignedBuf::new(WRITE_BUFFER_SIZE));
+
+        while !bytes.is_empty() {
+            bytes = &bytes[buf.extend(bytes)..];
+            if buf.is_full() {
+                self.file.write_all_at(buf.as_slice(), self.offset)?;
+                self.offset += buf.len as u64;
+                buf.len = 0;
+            }
+        }
+        self.size = self.offset + buf.len as u64;
+        Ok(())
+    }
+}
+
+impl Read for DirectFile {
+    async fn read_exact_at<B: IoBufMut>(&mut self, mut buf: B, pos: u64) -> (Result<(), Error>, B) {
+        let len = buf.bytes_init();
+        let result = self.read_blocks(pos, len).map(|(blocks, skip)| {
+            buf.as_slice_mut()
+                .copy_from_slice(&blocks.as_slice()[skip..skip + len])
+        });
+
+        (result, buf)
+    }
+
+    async fn read_to_end_at(&mut self, mut buf: Vec<u8>, pos: u64) -> (Result<(), Error>, Vec<u8>) {
+        let len = self.size.saturating_sub(pos) as usize;
+        let result = self
+            .read_blocks(pos, len)
+            .map(|(blocks, skip)| buf.extend_from_slice(&blocks.as_slice()[skip..skip + len]));
+
+        (result, buf)
+    }
+
+    async fn size(&self) -> Result<u64, Error> {
+        Ok(self.size)
+    }
+}
+
+impl Write for DirectFile {
+    async fn write_all<B: IoBuf>(&mut self, buf: B) -> (Result<(), Error>, B) {
+        let result = self.append(buf.as_slice());
+
+        (result, buf)
+    }
+
+    async fn flush(&mut self) -> Result<(), Error> {
+        if let Some(buf) = self.buf.as_mut().filter(|buf| !buf.is_empty()) {
+            self.file.write_all_at(buf.padded(), self.offset)?;
+            self.file.set_len(self.size)?;
+
+            let written = align_down(buf.len as u64);
+            buf.consume(written as usize);
+            self.offset += written;
+        }
+        self.file.sync_data()?;
+        Ok(())
+    }
+
+    async fn close(&mut self) -> Result<(), Error> {
+        self.flush().await
+    }
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod tests {
+    use fusio::{path::Path, Read, Write};
+    use tempfile::TempDir;
+
+    use super::{DirectFile, ALIGNMENT, WRITE_BUFFER_SIZE};
+
+    /// a file in `dir` opened with `O_DIRECT`, or none if the file system of the temporary
+    /// directory refuses it, as tmpfs does
+    fn open(dir: &TempDir, only_read: bool) -> Option<DirectFile> {
+        let path = Path::from_filesystem_path(dir.path())
+            .unwrap()
+            .child("table");
+        match DirectFile::open(&path, only_read) {
+            Ok(file) => Some(file),
+            Err(fusio::Error::Io(err)) if err.raw_os_error() == Some(libc::EINVAL) => None,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    #[tokio::test]
+    async fn unaligned_reads_and_writes() {
+        let dir = TempDir::new().unwrap();
+        let Some(mut file) = open(&dir, false) else {
+            return;
+        };
+        let bytes: Vec<u8> = (0..WRITE_BUFFER_SIZE + 3 * ALIGNMENT + 77)
+            .map(|i| (i % 251) as u8)
+            .collect();
+
+        let (head, tail) = bytes.split_at(ALIGNMENT + 5);
+        let (result, _) = file.write_all(head.to_vec()).await;
+        result.unwrap();
+        // the partial block flushed is written again by the writes following it
+        file.flush().await.unwrap();
+        assert_eq!(file.file.metadata().unwrap().len(), head.len() as u64);
+        let (result, _) = file.write_all(tail.to_vec()).await;
+        result.unwrap();
+        assert_eq!(file.size().await.unwrap(), bytes.len() as u64);
+        file.close().await.unwrap();
+        drop(file);
+
+        let mut file = open(&dir, true).unwrap();
+        assert_eq!(file.size().await.unwrap(), bytes.len() as u64);
+
+        for (pos, len) in [
+            (0, 10),
+            (3, ALIGNMENT),
+            (ALIGNMENT - 1, 2),
+            (WRITE_BUFFER_SIZE, 200),
+        ] {
+            let (result, buf) = file.read_exact_at(vec![0; len], pos as u64).await;
+            result.unwrap();
+            assert_eq!(buf, &bytes[pos..pos + len]);
+        }
+        let (result, buf) = file.read_to_end_at(vec![1], 1001).await;
+        result.unwrap();
+        assert_eq!(buf[0], 1);
+        assert_eq!(&buf[1..], &bytes[1001..]);
+
+        let (result, _) = file.read_exact_at(vec![0; 2], bytes.len() as u64 - 1).await;
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,259 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use fusio::{
+    fs::{FileMeta, FileSystemTag, Fs, OpenOptions},
+    path::{path_to_local, Path},
+    Error, IoBuf, IoBufMut, Read, SeqRead, Write,
+};
+use futures_core::Stream;
+
+/// How often each fault is injected into a [`FaultyFile`], drawn from a generator seeded by the
+/// test so that a failing seed reproduces.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Faults {
+    /// chance of a write failing after writing a part of its buffer
+    pub(crate) partial_write: f64,
+    /// chance of a flush returning without the writes before it becoming durable, as a lying or
+    /// delayed fsync does
+    pub(crate) delayed_flush: f64,
+    pub(crate) read_error: f64,
+    /// every read and write fails once this many went through, as a failed disk does
+    pub(crate) budget: Option<usize>,
+}
+
+/// the bytes written to a file since it was opened, and how many of them were flushed
+#[derive(Debug, Default)]
+struct Written {
+    len: usize,
+    durable: usize,
+}
+
+/// A file injecting [`Faults`] into the reads and writes of `file`, which tracks the bytes a
+/// crash would leave behind: the ones flushed, followed by any part of the ones written since.
+pub(crate) struct FaultyFile<F> {
+    file: F,
+    faults: Faults,
+    rng: fastrand::Rng,
+    written: Arc<Mutex<Written>>,
+}
+
+impl<F> FaultyFile<F> {
+    pub(crate) fn new(file: F, faults: Faults, seed: u64) -> Self {
+        FaultyFile {
+            file,
+            faults,
+            rng: fastrand::Rng::with_seed(seed),
+            written: Default::default(),
+        }
+    }
+
+    /// the length of the file after a crash at this point
+    pub(crate) fn crash(&mut self) -> usize {
+        let written = self.written.lock().unwrap();
+        written.durable + self.rng.usize(0..=written.len - written.durable)
+    }
+
+    /// whether a crash cannot lose any byte written so far
+    pub(crate) fn is_durable(&self) -> bool {
+        let written = self.written.lock().unwrap();
+        written.durable == written.len
+    }
+
+    fn inject(&mut self, chance: f64) -> bool {
+        self.rng.f64() < chance
+    }
+
+    /// whether the disk failed, once [`Faults::budget`] reads and writes went through
+    fn is_failed(&mut self) -> bool {
+        match &mut self.faults.budget {
+            Some(0) => true,
+            Some(budget) => {
+                *budget -= 1;
+                false
+            }
+            None => false,
+        }
+    }
+
+    /// the error of a read, if failed
+    fn read_error(&mut self) -> Option<Error> {
+        if self.is_failed() {
+            return Some(Error::Io(std::io::Error::other("disk failure")));
+        }
+        self.inject(self.faults.read_error)
+            .then(|| Error::Io(std::io::Error::other("injected read error")))
+    }
+
+    fn sync(&mut self) {
+        if !self.inject(self.faults.delayed_flush) {
+            let mut written = self.written.lock().unwrap();
+            written.durable = written.len;
+        }
+    }
+}
+
+impl<F: Write> Write for FaultyFile<F> {
+    async fn write_all<B: IoBuf>(&mut self, buf: B) -> (Result<(), Error>, B) {
+        if self.is_failed() {
+            let err = Error::Io(std::io::Error::other("disk failure"));
+            return (Err(err), buf);
+        }
+        if self.inject(self.faults.partial_write) {
+            let len = self.rng.usize(0..buf.as_slice().len().max(1));
+            let (result, _) = self.file.write_all(buf.as_slice()[..len].to_vec()).await;
+            if result.is_ok() {
+                self.written.lock().unwrap().len += len;
+            }
+            let err = Error::Io(std::io::Error::other("injected partial write"));
+            return (Err(err), buf);
+        }
+        let (result, buf) = self.file.write_all(buf).await;
+        if result.is_ok() {
+            self.written.lock().unwrap().len += buf.as_slice().len();
+        }
+        (result, buf)
+    }
+
+    async fn flush(&mut self) -> Result<(), Error> {
+        self.file.flush().await?;
+        self.sync();
+        Ok(())
+    }
+
+    async fn close(&mut self) -> Result<(), Error> {
+        self.file.close().await?;
+        self.sync();
+        Ok(())
+    }
+}
+
+impl<F: SeqRead> SeqRead for FaultyFile<F> {
+    async fn read_exact<B: IoBufMut>(&mut self, buf: B) -> (Result<(), Error>, B) {
+        if let Some(err) = self.read_error() {
+            return (Err(err), buf);
+        }
+        self.file.read_exact(buf).await
+    }
+}
+
+impl<F: Read> Read for FaultyFile<F> {
+    async fn read_exact_at<B: IoBufMut>(&mut self, buf: B, pos: u64) -> (Result<(), Error>, B) {
+        if let Some(err) = self.read_error() {
+            return (Err(err), buf);
+        }
+        self.file.read_exact_at(buf, pos).await
+    }
+
+    async fn read_to_end_at(&mut self, buf: Vec<u8>, pos: u64) -> (Result<(), Error>, Vec<u8>) {
+        if let Some(err) = self.read_error() {
+            return (Err(err), buf);
+        }
+        self.file.read_to_end_at(buf, pos).await
+    }
+
+    async fn size(&self) -> Result<u64, Error> {
+        self.file.size().await
+    }
+}
+
+/// A file system opening every file of `fs` as a [`FaultyFile`] injecting the same [`Faults`],
+/// each seeded from `seed`, so that the files of a database are failed as a whole. It tracks the
+/// bytes of the local files a crash would leave behind, which [`FaultyFs::crash`] cuts them to.
+pub(crate) struct FaultyFs<F> {
+    fs: F,
+    faults: Faults,
+    rng: Mutex<fastrand::Rng>,
+    /// the files opened, with their size when first opened
+    files: Mutex<HashMap<Path, (u64, Arc<Mutex<Written>>)>>,
+}
+
+impl<F> FaultyFs<F> {
+    pub(crate) fn new(fs: F, faults: Faults, seed: u64) -> Self {
+        FaultyFs {
+            fs,
+            faults,
+            rng: Mutex::new(fastrand::Rng::with_seed(seed)),
+            files: Default::default(),
+        }
+    }
+
+    /// whether a crash cannot lose any byte written to the files so far
+    pub(crate) fn is_durable(&self) -> bool {
+        self.files.lock().unwrap().values().all(|(_, written)| {
+            let written = written.lock().unwrap();
+            written.durable == written.len
+        })
+    }
+
+    /// cut the local files left by a crash at this point to the bytes it would leave of them,
+    /// the files removed since they were opened are gone
+    pub(crate) fn crash(&self) -> Result<(), Error> {
+        let mut rng = self.rng.lock().unwrap();
+        for (path, (size, written)) in self.files.lock().unwrap().drain() {
+            let written = written.lock().unwrap();
+            let len = written.durable + rng.usize(0..=written.len - written.durable);
+            match std::fs::OpenOptions::new()
+                .write(true)
+                .open(path_to_local(&path)?)
+            {
+                Ok(file) => file.set_len(size + len as u64)?,
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<F: Fs> Fs for FaultyFs<F> {
+    type File = FaultyFile<F::File>;
+
+    fn file_system(&self) -> FileSystemTag {
+        self.fs.file_system()
+    }
+
+    async fn open_options(&self, path: &Path, options: OpenOptions) -> Result<Self::File, Error> {
+        let file = self.fs.open_options(path, options).await?;
+        let size = file.size().await?;
+        let seed = self.rng.lock().unwrap().u64(..);
+        let mut file = FaultyFile::new(file, self.faults, seed);
+        // the handles of a file share what was written through them
+        file.written = self
+            .files
+            .lock()
+            .unwrap()
+            .entry(path.clone())
+            .or_insert_with(|| (size, Default::default()))
+            .1
+            .clone();
+        Ok(file)
+    }
+
+    async fn create_dir_all(path: &Path) -> Result<(), Error> {
+        F::create_dir_all(path).await
+    }
+
+    async fn list(
+        &self,
+        path: &Path,
+    ) -> Result<impl Stream<Item = Result<FileMeta, Error>>, Error> {
+        self.fs.list(path).await
+    }
+
+    async fn remove(&self, path: &Path) -> Result<(), Error> {
+        self.fs.remove(path).await?;
+        self.files.lock().unwrap().remove(path);
+        Ok(())
+    }
+
+    async fn copy(&self, from: &Path, to: &Path) -> Result<(), Error> {
+        self.fs.copy(from, to).await
+    }
+
+    async fn link(&self, from: &Path, to: &Path) -> Result<(), Error> {
+        self.fs.link(from, to).await
+    }
+}
@@ -23,6 +23,15 @@ impl StoreManager {
         Ok(StoreManager { base_fs, fs_map })
     }
 
+    /// a manager storing every level in `base_fs`
+    #[cfg(test)]
+    pub(crate) fn with_base_fs(base_fs: Arc<dyn DynFs>) -> Self {
+        StoreManager {
+            base_fs,
+            fs_map: HashMap::new(),
+        }
+    }
+
     pub fn base_fs(&self) -> &Arc<dyn DynFs> {
         &self.base_fs
     }
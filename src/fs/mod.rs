@@ -1,11 +1,21 @@
+#[cfg(target_os = "linux")]
+pub(crate) mod direct;
+#[cfg(test)]
+pub(crate) mod fault;
 pub mod manager;
 
 use std::{
     fmt::{Display, Formatter},
+    io::Cursor,
     str::FromStr,
+    sync::Arc,
 };
 
-use fusio::{fs::OpenOptions, path::Path};
+#[cfg(not(target_arch = "wasm32"))]
+use fusio::path::path_to_local;
+use fusio::{dynamic::DynFile, fs::OpenOptions, path::Path, DynFs, SeqRead, Write};
+use fusio_dispatch::FsOptions;
+use futures_util::StreamExt;
 use ulid::{DecodeError, Ulid};
 
 pub type FileId = Ulid;
@@ -51,3 +61,332 @@ pub(crate) fn parse_file_id(path: &Path, suffix: FileType) -> Result<Option<File
         })
         .transpose()
 }
+
+/// open the SST at `path` of `fs` for reading, or create it for writing unless `only_read`,
+/// with `O_DIRECT` if `direct_io` on Linux, see [`DbOption::direct_io`](crate::DbOption::direct_io)
+pub(crate) async fn open_table(
+    fs: &Arc<dyn DynFs>,
+    path: &Path,
+    only_read: bool,
+    direct_io: bool,
+) -> Result<Box<dyn DynFile>, fusio::Error> {
+    #[cfg(target_os = "linux")]
+    if direct_io {
+        match direct::DirectFile::open(path, only_read) {
+            Ok(file) => return Ok(Box::new(file)),
+            // file systems such as tmpfs refuse `O_DIRECT`, their files are read and written
+            // through the page cache
+            Err(fusio::Error::Io(err)) if err.raw_os_error() == Some(libc::EINVAL) => {}
+            Err(err) => return Err(err),
+        }
+    }
+    #[cfg(not(target_os = "linux"))]
+    let _ = direct_io;
+
+    fs.open_options(path, FileType::Parquet.open_options(only_read))
+        .await
+}
+
+/// create the SST at `path` of `fs` for writing as [`open_table`] does, reserving `preallocate`
+/// bytes of disk for it, see [`DbOption::preallocate_tables`](crate::DbOption::preallocate_tables)
+pub(crate) async fn create_table(
+    fs: &Arc<dyn DynFs>,
+    path: &Path,
+    direct_io: bool,
+    preallocate: Option<u64>,
+) -> Result<Box<dyn DynFile>, fusio::Error> {
+    let file = open_table(fs, path, false, direct_io).await?;
+    if let Some(len) = preallocate {
+        fallocate(path, len)?;
+    }
+    Ok(file)
+}
+
+/// reserve `len` bytes of disk for the local file at `path` without changing its size, a file
+/// system unable to is left to allocate as the file is written
+#[cfg(target_os = "linux")]
+pub(crate) fn fallocate(path: &Path, len: u64) -> Result<(), fusio::Error> {
+    use std::os::fd::AsRawFd;
+
+    let file = std::fs::OpenOptions::new()
+        .write(true)
+        .open(path_to_local(path)?)?;
+    // SAFETY: the descriptor is owned by `file`, open until it is dropped
+    let result = unsafe {
+        libc::fallocate(
+            file.as_raw_fd(),
+            libc::FALLOC_FL_KEEP_SIZE,
+            0,
+            len as libc::off_t,
+        )
+    };
+    if result == -1 {
+        let err = std::io::Error::last_os_error();
+        if err.raw_os_error() != Some(libc::EOPNOTSUPP) {
+            return Err(err.into());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn fallocate(_: &Path, _: u64) -> Result<(), fusio::Error> {
+    Ok(())
+}
+
+/// release the disk reserved by [`create_table`] or [`fallocate`] past the end of the closed SST
+/// or WAL segment at `path`, by truncating it to its own size
+#[cfg(target_os = "linux")]
+pub(crate) fn truncate_table(path: &Path) -> Result<(), fusio::Error> {
+    let file = std::fs::OpenOptions::new()
+        .write(true)
+        .open(path_to_local(path)?)?;
+    file.set_len(file.metadata()?.len())?;
+
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn truncate_table(_: &Path) -> Result<(), fusio::Error> {
+    Ok(())
+}
+
+/// the [`Path`] of `path` on the local file system, relative to the current directory unless
+/// absolute, understanding the `\` separators and the drive and UNC prefixes of Windows.
+/// Unlike [`Path::from_filesystem_path`], `path` does not have to exist yet, and unlike
+/// [`Path::from`], a relative one is not taken as relative to the root
+#[cfg(not(target_arch = "wasm32"))]
+pub fn local_path(path: impl AsRef<std::path::Path>) -> Result<Path, fusio::Error> {
+    let path = std::path::absolute(path)?;
+
+    Path::from_absolute_path(path).map_err(|err| fusio::Error::Other(Box::new(err)))
+}
+
+/// list the files named `<ulid>.<file_type>` in `dir`, a missing directory has no files
+pub(crate) async fn list_files(
+    fs: &Arc<dyn DynFs>,
+    dir: &Path,
+    file_type: FileType,
+) -> Result<Vec<Path>, fusio::Error> {
+    let mut paths = Vec::new();
+    let suffix = format!(".{}", file_type);
+
+    // the directory has not been created
+    let Ok(mut file_stream) = fs.list(dir).await else {
+        return Ok(paths);
+    };
+    while let Some(file_meta) = file_stream.next().await {
+        let path = file_meta?.path;
+        let is_tonbo_file = path
+            .filename()
+            .and_then(|file_name| file_name.strip_suffix(&suffix))
+            .is_some_and(|file_id| FileId::from_str(file_id).is_ok());
+
+        if is_tonbo_file {
+            paths.push(path);
+        }
+    }
+    Ok(paths)
+}
+
+pub(crate) async fn read_file(fs: &Arc<dyn DynFs>, path: &Path) -> Result<Vec<u8>, fusio::Error> {
+    let mut file = fs
+        .open_options(path, OpenOptions::default().read(true))
+        .await?;
+    let mut buf = vec![0; file.size().await? as usize];
+    let (result, _) = Cursor::new(&mut file).read_exact(buf.as_mut_slice()).await;
+    result?;
+
+    Ok(buf)
+}
+
+pub(crate) async fn file_size(fs: &Arc<dyn DynFs>, path: &Path) -> Result<u64, fusio::Error> {
+    fs.open_options(path, OpenOptions::default().read(true))
+        .await?
+        .size()
+        .await
+}
+
+pub(crate) async fn write_file(
+    fs: &Arc<dyn DynFs>,
+    path: &Path,
+    buf: &[u8],
+) -> Result<(), fusio::Error> {
+    let mut file = fs
+        .open_options(path, OpenOptions::default().create(true).write(true))
+        .await?;
+    let (result, _) = file.write_all(buf).await;
+    result?;
+    file.close().await
+}
+
+/// cut the file at `path` of `fs` back to its first `len` bytes, in place if `is_local` and by
+/// writing the bytes kept again otherwise. A file no longer than `len` is left as it is
+pub(crate) async fn truncate_file(
+    fs: &Arc<dyn DynFs>,
+    path: &Path,
+    len: u64,
+    is_local: bool,
+) -> Result<(), fusio::Error> {
+    #[cfg(not(target_arch = "wasm32"))]
+    if is_local {
+        let file = std::fs::OpenOptions::new()
+            .write(true)
+            .open(path_to_local(path)?)?;
+        if file.metadata()?.len() > len {
+            file.set_len(len)?;
+            file.sync_all()?;
+        }
+        return Ok(());
+    }
+    #[cfg(target_arch = "wasm32")]
+    let _ = is_local;
+
+    let mut buf = read_file(fs, path).await?;
+    if buf.len() as u64 <= len {
+        return Ok(());
+    }
+    buf.truncate(len as usize);
+    let mut file = fs
+        .open_options(path, OpenOptions::default().write(true).truncate(true))
+        .await?;
+    let (result, _) = file.write_all(buf).await;
+    result?;
+    file.close().await
+}
+
+/// An exclusive lock of a file taken by [`lock_file`], released once dropped, even if the process
+/// crashes
+#[derive(Debug)]
+pub(crate) enum FileLock {
+    /// held until dropped, not read
+    Local { _file: std::fs::File },
+    /// the file system has no locks, as object storage
+    Unsupported,
+}
+
+/// take the exclusive lock of the file at `path` of the file system described by `fs_options`,
+/// creating it if missing. Fails with [`std::io::ErrorKind::WouldBlock`] if another process
+/// holds it
+pub(crate) fn lock_file(fs_options: &FsOptions, path: &Path) -> Result<FileLock, fusio::Error> {
+    #[cfg(not(target_arch = "wasm32"))]
+    if matches!(fs_options, FsOptions::Local) {
+        use fs4::fs_std::FileExt;
+
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(path_to_local(path)?)?;
+        return match file.try_lock_exclusive() {
+            Ok(()) => Ok(FileLock::Local { _file: file }),
+            Err(err) if err.raw_os_error() == fs4::lock_contended_error().raw_os_error() => {
+                Err(std::io::Error::from(std::io::ErrorKind::WouldBlock).into())
+            }
+            Err(err) => Err(err.into()),
+        };
+    }
+    #[cfg(target_arch = "wasm32")]
+    let _ = (fs_options, path);
+
+    Ok(FileLock::Unsupported)
+}
+
+/// move `src` to `dst` of `fs`, replacing it. Renamed if `is_local`, so `dst` is never seen
+/// partially written, and copied otherwise, as object storage only shows an object once it is
+/// written whole
+pub(crate) async fn rename_file(
+    fs: &Arc<dyn DynFs>,
+    src: &Path,
+    dst: &Path,
+    is_local: bool,
+) -> Result<(), fusio::Error> {
+    #[cfg(not(target_arch = "wasm32"))]
+    if is_local {
+        std::fs::rename(path_to_local(src)?, path_to_local(dst)?)?;
+        return Ok(());
+    }
+    #[cfg(target_arch = "wasm32")]
+    let _ = is_local;
+
+    copy_file(fs, src, fs, dst).await?;
+    fs.remove(src).await
+}
+
+/// copy `src` to `dst`, which may be on another file system, and return the size and the crc32
+/// checksum of the content
+pub(crate) async fn copy_file(
+    src_fs: &Arc<dyn DynFs>,
+    src: &Path,
+    dst_fs: &Arc<dyn DynFs>,
+    dst: &Path,
+) -> Result<(u64, u32), fusio::Error> {
+    let buf = read_file(src_fs, src).await?;
+    write_file(dst_fs, dst, &buf).await?;
+
+    Ok((buf.len() as u64, crc32fast::hash(&buf)))
+}
+
+#[cfg(test)]
+mod tests {
+    use fusio::path::path_to_local;
+    use tempfile::TempDir;
+
+    use super::local_path;
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn preallocate() {
+        use std::os::unix::fs::MetadataExt;
+
+        use super::{fallocate, truncate_table};
+
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("table");
+        std::fs::write(&file_path, b"parquet").unwrap();
+        let path = local_path(&file_path).unwrap();
+
+        fallocate(&path, 1 << 20).unwrap();
+        let metadata = std::fs::metadata(&file_path).unwrap();
+        // the size is kept, only the disk is reserved where the file system supports it
+        assert_eq!(metadata.len(), 7);
+        let reserved = metadata.blocks() * 512 >= 1 << 20;
+
+        truncate_table(&path).unwrap();
+        let metadata = std::fs::metadata(&file_path).unwrap();
+        assert_eq!(metadata.len(), 7);
+        if reserved {
+            assert!(metadata.blocks() * 512 < 1 << 20);
+        }
+        assert_eq!(std::fs::read(&file_path).unwrap(), b"parquet");
+    }
+
+    #[test]
+    fn local_paths() {
+        let temp_dir = TempDir::new().unwrap();
+        // does not have to exist yet
+        let dir = temp_dir.path().join("db").join("users");
+        assert_eq!(path_to_local(&local_path(&dir).unwrap()).unwrap(), dir);
+
+        let relative = local_path("db").unwrap();
+        assert_eq!(
+            path_to_local(&relative).unwrap(),
+            std::env::current_dir().unwrap().join("db")
+        );
+
+        #[cfg(unix)]
+        assert_eq!(
+            local_path("/data/tonbo").unwrap(),
+            fusio::path::Path::from("/data/tonbo".to_string())
+        );
+        #[cfg(windows)]
+        {
+            let path = local_path(r"C:\data\tonbo").unwrap();
+            assert_eq!(path.filename(), Some("tonbo"));
+            assert_eq!(
+                path_to_local(&path).unwrap(),
+                std::path::Path::new(r"C:\data\tonbo")
+            );
+        }
+    }
+}
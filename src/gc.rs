@@ -0,0 +1,318 @@
+use std::sync::{Arc, Mutex};
+
+use crate::{
+    compaction::{unix_millis, CompactTask},
+    record::Record,
+    snapshot::ActiveSnapshots,
+    timestamp::Timestamp,
+    transaction::CommitError,
+    version::TransactionTs,
+    DbError, DB,
+};
+
+/// How much history major compactions keep, set by
+/// [`DB::set_gc_watermark`](crate::DB::set_gc_watermark), held back by
+/// [`DB::hold_history`](crate::DB::hold_history) and cut by
+/// [`DB::purge_versions_older_than`](crate::DB::purge_versions_older_than).
+#[derive(Default)]
+pub(crate) struct GcWatermark {
+    watermark: Mutex<Option<Timestamp>>,
+    purge: Mutex<Option<Purge>>,
+    holds: ActiveSnapshots,
+    /// the history dropped by the background compactions running, see
+    /// [`GcWatermark::compacting`]
+    compactions: ActiveSnapshots,
+}
+
+impl GcWatermark {
+    pub(crate) fn set(&self, watermark: Option<Timestamp>) {
+        *self.watermark.lock().unwrap() = watermark;
+    }
+
+    /// drop the versions superseded before `ts` whatever the holds, from the tables written
+    /// before `now`, in milliseconds since the Unix epoch, on
+    pub(crate) fn purge(&self, ts: Timestamp, now: u64) {
+        let mut purge = self.purge.lock().unwrap();
+        let ts = purge.map_or(ts, |purge| purge.ts.max(ts));
+        *purge = Some(Purge {
+            ts,
+            written_before: now,
+        });
+    }
+
+    /// the timestamp of the last purge and when it was made
+    pub(crate) fn purged(&self) -> Option<Purge> {
+        *self.purge.lock().unwrap()
+    }
+
+    pub(crate) fn hold(self: &Arc<Self>, ts: Timestamp) -> HistoryHold {
+        self.holds.register(ts);
+
+        HistoryHold {
+            ts,
+            gc: self.clone(),
+        }
+    }
+
+    /// keep the history before `floor`, which a compaction running outside of the schema lock
+    /// drops, from being held until the returned guard is dropped
+    pub(crate) fn compacting(self: &Arc<Self>, floor: Timestamp) -> Compacting {
+        self.compactions.register(floor);
+
+        Compacting {
+            floor,
+            gc: self.clone(),
+        }
+    }
+
+    /// the newest history dropped by a running background compaction
+    pub(crate) fn compacted(&self) -> Option<Timestamp> {
+        self.compactions.newest()
+    }
+
+    /// the timestamp compactions keep every newer version after, along with the newest one at
+    /// or before it. `None` keeps only the newest version of each key
+    pub(crate) fn get(&self) -> Option<Timestamp> {
+        let watermark = *self.watermark.lock().unwrap();
+        let kept = match (watermark, self.holds.oldest()) {
+            (Some(watermark), Some(hold)) => Some(watermark.min(hold)),
+            (watermark, hold) => watermark.or(hold),
+        };
+        match (kept, self.purged()) {
+            (Some(kept), Some(purge)) => Some(kept.max(purge.ts)),
+            (kept, _) => kept,
+        }
+    }
+}
+
+/// see [`GcWatermark::purge`]
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Purge {
+    pub(crate) ts: Timestamp,
+    pub(crate) written_before: u64,
+}
+
+/// Keeps the records visible at a timestamp through major compactions until it is dropped, so a
+/// long lived reader can open [`DB::snapshot_at`](crate::DB::snapshot_at) it later on.
+pub struct HistoryHold {
+    ts: Timestamp,
+    gc: Arc<GcWatermark>,
+}
+
+impl HistoryHold {
+    pub fn ts(&self) -> Timestamp {
+        self.ts
+    }
+}
+
+impl Drop for HistoryHold {
+    fn drop(&mut self) {
+        self.gc.holds.release(self.ts);
+    }
+}
+
+/// Registered by a background compaction for as long as it runs, see
+/// [`GcWatermark::compacting`].
+pub(crate) struct Compacting {
+    floor: Timestamp,
+    gc: Arc<GcWatermark>,
+}
+
+impl Drop for Compacting {
+    fn drop(&mut self) {
+        self.gc.compactions.release(self.floor);
+    }
+}
+
+impl<R> DB<R>
+where
+    R: Record + Send + Sync,
+    R::Columns: Send + Sync,
+{
+    /// let major compactions drop only the versions superseded at or before `watermark`, so the
+    /// records as they were at any later timestamp stay readable by [`DB::snapshot_at`]. `None`,
+    /// the default, keeps only the newest version of each key, and [`DB::hold_history`] holds
+    /// the watermark back
+    pub fn set_gc_watermark(&self, watermark: Option<Timestamp>) {
+        self.version_set.gc().set(watermark);
+    }
+
+    /// keep the records as they were at `ts` through major compactions until the returned hold
+    /// is dropped, for a long lived reader of [`DB::snapshot_at`]. It fails with
+    /// [`DbError::TimestampNotRetained`] if they are already gone
+    pub async fn hold_history(&self, ts: Timestamp) -> Result<HistoryHold, DbError<R>> {
+        // compactions take the watermark under the same lock
+        let _schema = self.schema.upgradable_read().await;
+        let version = self.version_set.current().await;
+        let gc = self.version_set.gc();
+        // or the history a background compaction is dropping
+        let oldest = gc.compacted().map_or(version.retained_ts(), |floor| {
+            floor.max(version.retained_ts())
+        });
+        // or the history purged
+        let oldest = gc.purged().map_or(oldest, |purge| purge.ts.max(oldest));
+        let latest = version.load_ts();
+        if ts < oldest || ts > latest {
+            return Err(DbError::TimestampNotRetained { ts, oldest, latest });
+        }
+        Ok(gc.hold(ts))
+    }
+
+    /// drop the versions superseded before `ts` whatever the watermark and the holds, and
+    /// schedule the compactions rewriting the SSTs holding them, to bound the history kept apart
+    /// from the lifetime of the snapshots. [`DB::snapshot_at`] an earlier timestamp fails once
+    /// they are rewritten, and so does [`DB::hold_history`] it from now on. The purge lasts until
+    /// the database is closed, a later one only ever moves it forward
+    pub async fn purge_versions_older_than(&self, ts: Timestamp) -> Result<(), CommitError<R>> {
+        let compaction_tx = {
+            // compactions take the watermark under the same lock
+            let schema = self.schema.upgradable_read().await;
+            schema.check_writable()?;
+            let ts = ts.min(self.version_set.load_ts());
+            self.version_set.gc().purge(ts, unix_millis());
+            schema.compaction_tx.clone()
+        };
+        compaction_tx.send_async(CompactTask::Purge).await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod tests {
+    use std::{ops::Bound, time::Duration};
+
+    use fusio::path::Path;
+    use futures_util::StreamExt;
+    use tempfile::TempDir;
+
+    use crate::{
+        executor::tokio::TokioExecutor,
+        snapshot::Snapshot,
+        tests::{test_items, Test},
+        DbError, DbOption, Projection, DB,
+    };
+
+    #[tokio::test]
+    async fn gc_watermark() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap());
+        option.immutable_chunk_num = 1;
+        option.immutable_chunk_max_num = 0;
+        option.major_threshold_with_sst_size = 1;
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+
+        let items = test_items();
+        for item in items.iter().cloned() {
+            db.insert(item).await.unwrap();
+        }
+        let ts = db.snapshot().await.ts();
+        let mut item = items[0].clone();
+        item.vu32 = 100;
+        db.insert(item).await.unwrap();
+        db.remove(items[1].vstring.clone()).await.unwrap();
+        db.flush().await.unwrap();
+
+        let hold = db.hold_history(ts).await.unwrap();
+        db.set_gc_watermark(Some(db.snapshot().await.ts()));
+        db.insert(items[2].clone()).await.unwrap();
+        db.flush().await.unwrap();
+        assert!(!db.version_set.current().await.level_slice[1].is_empty());
+
+        async fn get(snapshot: &Snapshot<'_, Test>, key: &str) -> Option<u32> {
+            snapshot
+                .get(&key.to_string(), Projection::All)
+                .await
+                .unwrap()
+                .map(|entry| entry.value().unwrap().vu32.unwrap())
+        }
+        // the hold keeps the versions superseded after it through the major compaction
+        let snapshot = db.snapshot_at(ts).await.unwrap();
+        assert_eq!(get(&snapshot, "0").await, Some(0));
+        assert_eq!(get(&snapshot, "1").await, Some(1));
+        let count = snapshot
+            .scan((Bound::Unbounded, Bound::Unbounded))
+            .take()
+            .await
+            .unwrap()
+            .count()
+            .await;
+        assert_eq!(count, items.len());
+        drop(snapshot);
+        let snapshot = db.snapshot().await;
+        assert_eq!(get(&snapshot, "0").await, Some(100));
+        assert_eq!(get(&snapshot, "1").await, None);
+        drop(snapshot);
+
+        drop(hold);
+        db.set_gc_watermark(None);
+        let mut item = items[0].clone();
+        item.vu32 = 200;
+        db.insert(item).await.unwrap();
+        db.flush().await.unwrap();
+        assert!(matches!(
+            db.snapshot_at(ts).await,
+            Err(DbError::TimestampNotRetained { .. })
+        ));
+        assert!(matches!(
+            db.hold_history(ts).await,
+            Err(DbError::TimestampNotRetained { .. })
+        ));
+        assert_eq!(get(&db.snapshot().await, "0").await, Some(200));
+    }
+
+    #[tokio::test]
+    async fn purge_versions() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap());
+        option.immutable_chunk_num = 1;
+        option.immutable_chunk_max_num = 0;
+        option.major_threshold_with_sst_size = 1;
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+        db.set_gc_watermark(Some(db.snapshot().await.ts()));
+
+        let items = test_items();
+        let mut timestamps = Vec::new();
+        for vu32 in 0..3 {
+            let mut item = items[0].clone();
+            item.vu32 = vu32;
+            db.insert(item).await.unwrap();
+            db.flush().await.unwrap();
+            timestamps.push(db.snapshot().await.ts());
+        }
+        let _hold = db.hold_history(timestamps[0]).await.unwrap();
+
+        async fn get(snapshot: &Snapshot<'_, Test>) -> Option<u32> {
+            snapshot
+                .get(&"0".to_string(), Projection::All)
+                .await
+                .unwrap()
+                .map(|entry| entry.value().unwrap().vu32.unwrap())
+        }
+        assert_eq!(
+            get(&db.snapshot_at(timestamps[0]).await.unwrap()).await,
+            Some(0)
+        );
+
+        // the tables written in the same millisecond as the purge are left as they are
+        tokio::time::sleep(Duration::from_millis(2)).await;
+        db.purge_versions_older_than(timestamps[1]).await.unwrap();
+        // queued after the compactions of the purge
+        db.flush().await.unwrap();
+
+        // the hold does not keep the purged versions
+        assert!(matches!(
+            db.snapshot_at(timestamps[0]).await,
+            Err(DbError::TimestampNotRetained { .. })
+        ));
+        assert!(matches!(
+            db.hold_history(timestamps[0]).await,
+            Err(DbError::TimestampNotRetained { .. })
+        ));
+        assert_eq!(
+            get(&db.snapshot_at(timestamps[1]).await.unwrap()).await,
+            Some(1)
+        );
+        assert_eq!(get(&db.snapshot().await).await, Some(2));
+    }
+}
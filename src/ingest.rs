@@ -0,0 +1,260 @@
+use std::{ops::Bound, sync::Arc};
+
+use fusio::path::Path;
+use fusio_parquet::reader::AsyncReader;
+use futures_util::StreamExt;
+use parquet::arrow::{ParquetRecordBatchStreamBuilder, ProjectionMask};
+use parquet_lru::NoCache;
+
+use crate::{
+    fs::{FileId, FileType},
+    inmem::immutable::{ArrowArrays, Builder},
+    is_fields_matched,
+    ondisk::sstable::SsTable,
+    record::{KeyRef, Record},
+    scope::Scope,
+    timestamp::{Timestamp, Timestamped},
+    version::{edit::VersionEdit, TransactionTs, MAX_LEVEL},
+    write_table, DbError, DB,
+};
+
+impl<R> DB<R>
+where
+    R: Record + Send + Sync,
+    R::Columns: Send + Sync,
+{
+    /// install pre-sorted parquet files with the schema of `R` as SSTs, skipping the WAL and the
+    /// memtables. The records are copied with a new timestamp, so they are newer than every record
+    /// written before, and placed in the deepest level not overlapping their keys. The files must
+    /// not overlap each other or the data not yet flushed.
+    pub async fn ingest(&self, paths: &[Path]) -> Result<(), DbError<R>> {
+        let ts = self.version_set.increase_ts();
+        let arrow_schema = self.schema.read().await.record_instance.arrow_schema::<R>();
+        let mut tables = Vec::with_capacity(paths.len());
+
+        for path in paths {
+            tables.push(self.read_ingested(path, &arrow_schema, ts).await?);
+        }
+        tables.sort_by(|(min_a, ..), (min_b, ..)| min_a.cmp(min_b));
+        for pair in tables.windows(2) {
+            if pair[0].1 >= pair[1].0 {
+                return Err(DbError::Ingest("ingested files overlap".to_string()));
+            }
+        }
+        let (Some((min, ..)), Some((_, max, _))) = (tables.first(), tables.last()) else {
+            return Ok(());
+        };
+        let range = (Bound::Included(min), Bound::Included(max));
+
+        // block writes and compaction until the version edit is applied
+        let schema = self.schema.write().await;
+        schema.check_writable()?;
+        if schema.is_unflushed(range) {
+            return Err(DbError::Ingest(
+                "ingested files overlap unflushed data".to_string(),
+            ));
+        }
+        let version = self.version_set.current().await;
+        // level 0 is searched from the newest table, other levels must not overlap
+        let level = match version
+            .level_slice
+            .iter()
+            .position(|scopes| scopes.iter().any(|scope| scope.meets_range(range)))
+        {
+            Some(level) => level.saturating_sub(1),
+            None => MAX_LEVEL - 1,
+        };
+
+        let mut version_edits = Vec::with_capacity(tables.len() + 1);
+        for (min, max, columns) in tables {
+            let (gen, stats) = write_table(
+                version.option(),
+                &self.manager,
+                level,
+                &arrow_schema,
+                &columns,
+            )
+            .await?;
+
+            version_edits.push(VersionEdit::Add {
+                level: level as u8,
+                scope: Scope {
+                    min,
+                    max,
+                    gen,
+                    wal_ids: None,
+                    stats: Some(stats),
+                },
+            });
+        }
+        version_edits.push(VersionEdit::LatestTimeStamp {
+            ts: self.version_set.increase_ts(),
+        });
+        self.version_set
+            .apply_edits(version_edits, None, false)
+            .await?;
+        drop(schema);
+
+        Ok(())
+    }
+
+    /// read the records of an ingested file with their timestamps replaced by `ts`
+    async fn read_ingested(
+        &self,
+        path: &Path,
+        arrow_schema: &Arc<arrow::datatypes::Schema>,
+        ts: Timestamp,
+    ) -> Result<(R::Key, R::Key, R::Columns), DbError<R>> {
+        let base_fs = self.manager.base_fs();
+        let open = || base_fs.open_options(path, FileType::Parquet.open_options(true));
+
+        let file = open().await?;
+        let size = file.size().await?;
+        let builder =
+            ParquetRecordBatchStreamBuilder::new(AsyncReader::new(file, size).await?).await?;
+        let is_matched = is_fields_matched(builder.schema().fields(), arrow_schema.fields());
+        if !is_matched {
+            return Err(DbError::Ingest(format!(
+                "schema of {} does not match the record",
+                path
+            )));
+        }
+
+        let mut scan =
+            SsTable::<R>::open(Arc::new(NoCache::default()), FileId::new(), open().await?)
+                .await?
+                .scan(
+                    (Bound::Unbounded, Bound::Unbounded),
+                    u64::MAX.into(),
+                    None,
+                    ProjectionMask::all(),
+                )
+                .await?;
+        let mut builder = R::Columns::builder(arrow_schema, 8192);
+        let mut min: Option<R::Key> = None;
+        let mut max: Option<R::Key> = None;
+
+        while let Some(entry) = scan.next().await.transpose()? {
+            let key = entry.key();
+            let owned_key = key.clone().to_key();
+
+            if max.as_ref().is_some_and(|max| *max >= owned_key) {
+                return Err(DbError::Ingest(format!(
+                    "keys of {} are not sorted or unique",
+                    path
+                )));
+            }
+            if min.is_none() {
+                min = Some(owned_key.clone());
+            }
+            max = Some(owned_key);
+            builder.push(Timestamped::new(key, ts), entry.get());
+        }
+        match (min, max) {
+            (Some(min), Some(max)) => Ok((min, max, builder.finish(None))),
+            _ => Err(DbError::Ingest(format!("{} is empty", path))),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod tests {
+    use std::sync::Arc;
+
+    use fusio::{disk::TokioFs, path::Path, DynFs};
+    use tempfile::TempDir;
+
+    use crate::{
+        compaction::tests::build_parquet_table,
+        executor::tokio::TokioExecutor,
+        fs::FileId,
+        record::RecordInstance,
+        tests::{test_items, Test},
+        trigger::TriggerType,
+        wal::log::LogType,
+        DbError, DbOption, DB,
+    };
+
+    #[tokio::test]
+    async fn ingest() {
+        let temp_dir = TempDir::new().unwrap();
+        let external_dir = TempDir::new().unwrap();
+
+        let mut option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap());
+        option.immutable_chunk_num = 1;
+        option.immutable_chunk_max_num = 1;
+        option.trigger_type = TriggerType::Length(5);
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+        for item in test_items() {
+            db.insert(item).await.unwrap();
+        }
+
+        let external_option =
+            DbOption::from(Path::from_filesystem_path(external_dir.path()).unwrap());
+        let fs = Arc::new(TokioFs) as Arc<dyn DynFs>;
+        fs.create_dir_all(&external_option.wal_dir_path())
+            .await
+            .unwrap();
+        let build_file = |keys: Vec<&'static str>| {
+            let gen = FileId::new();
+            let records = keys
+                .into_iter()
+                .map(|key| {
+                    (
+                        LogType::Full,
+                        Test {
+                            vstring: key.to_string(),
+                            vu32: 100,
+                            vbool: None,
+                        },
+                        0.into(),
+                    )
+                })
+                .collect();
+            let external_option = &external_option;
+            let fs = &fs;
+            async move {
+                build_parquet_table::<Test>(
+                    external_option,
+                    gen,
+                    records,
+                    &RecordInstance::Normal,
+                    0,
+                    fs,
+                )
+                .await
+                .unwrap();
+                external_option.table_path(gen, 0)
+            }
+        };
+        let file_1 = build_file(vec!["a", "b"]).await;
+        let file_2 = build_file(vec!["c", "d"]).await;
+        let file_3 = build_file(vec!["b", "e"]).await;
+        let file_4 = build_file(vec!["0", "z"]).await;
+
+        assert!(matches!(
+            db.ingest(&[file_1.clone(), file_3]).await,
+            Err(DbError::Ingest(_))
+        ));
+        // the latest records are still in the memtables
+        assert!(matches!(
+            db.ingest(&[file_4]).await,
+            Err(DbError::Ingest(_))
+        ));
+        db.ingest(&[file_1, file_2]).await.unwrap();
+
+        for key in ["a", "b", "c", "d"] {
+            let vu32 = db
+                .get(&key.to_string(), |entry| Some(entry.get().vu32.unwrap()))
+                .await
+                .unwrap();
+            assert_eq!(vu32, Some(100));
+        }
+        assert_eq!(
+            db.get(&"39".to_string(), |entry| Some(entry.get().vu32.unwrap()))
+                .await
+                .unwrap(),
+            Some(39)
+        );
+    }
+}
@@ -10,9 +10,10 @@ use crossbeam_skiplist::SkipMap;
 use parquet::arrow::ProjectionMask;
 
 use crate::{
-    record::{internal::InternalRecordRef, Key, Record, RecordInstance, RecordRef},
+    range_tombstone::RangeTombstone,
+    record::{internal::InternalRecordRef, Key, KeyRef, Record, RecordInstance, RecordRef},
     stream::record_batch::RecordBatchEntry,
-    timestamp::{Timestamp, Timestamped, TimestampedRef, EPOCH},
+    timestamp::{Timestamp, Timestamped, TimestampedKey, TimestampedRange, TimestampedRef, EPOCH},
 };
 
 pub trait ArrowArrays: Sized + Sync {
@@ -52,6 +53,9 @@ where
 {
     data: A,
     index: BTreeMap<Timestamped<<A::Record as Record>::Key>, u32>,
+    pub(crate) range_tombstones: Vec<RangeTombstone<<A::Record as Record>::Key>>,
+    /// bytes of the WAL written for the memtable, removed once it is flushed
+    pub(crate) wal_size: usize,
 }
 
 impl<A>
@@ -82,7 +86,12 @@ where
 
         let data = builder.finish(None);
 
-        Self { data, index }
+        Self {
+            data,
+            index,
+            range_tombstones: Vec::new(),
+            wal_size: 0,
+        }
     }
 }
 
@@ -90,6 +99,35 @@ impl<A> Immutable<A>
 where
     A: ArrowArrays,
 {
+    /// build from a `batch` holding the internal columns, a later row of the same key replaces
+    /// the earlier one
+    pub(crate) fn from_record_batch(batch: &RecordBatch) -> Self {
+        let schema = batch.schema();
+        let projection_mask = ProjectionMask::all();
+        let mut index = BTreeMap::new();
+        let mut builder = A::builder(&schema, batch.num_rows());
+
+        for offset in 0..batch.num_rows() {
+            let record = <A::Record as Record>::Ref::from_record_batch(
+                batch,
+                offset,
+                &projection_mask,
+                &schema,
+            );
+            let key = record.value();
+
+            index.insert(key.map(|key| key.clone().to_key()), offset as u32);
+            builder.push(key, record.get());
+        }
+
+        Self {
+            data: builder.finish(None),
+            index,
+            range_tombstones: Vec::new(),
+            wal_size: 0,
+        }
+    }
+
     pub(crate) fn scope(
         &self,
     ) -> (
@@ -128,7 +166,9 @@ where
 
         let range = self
             .index
-            .range::<TimestampedRef<<A::Record as Record>::Key>, _>((lower, upper));
+            .range::<dyn TimestampedKey<<A::Record as Record>::Key>, _>(TimestampedRange::new(
+                lower, upper,
+            ));
 
         ImmutableScan::<A::Record>::new(range, self.data.as_record_batch(), projection_mask)
     }
@@ -149,13 +189,40 @@ where
 
     pub(crate) fn check_conflict(&self, key: &<A::Record as Record>::Key, ts: Timestamp) -> bool {
         self.index
-            .range::<TimestampedRef<<A::Record as Record>::Key>, _>((
-                Bound::Excluded(TimestampedRef::new(key, u32::MAX.into())),
+            .range::<dyn TimestampedKey<<A::Record as Record>::Key>, _>(TimestampedRange::new(
+                Bound::Excluded(TimestampedRef::new(key, u64::MAX.into())),
                 Bound::Excluded(TimestampedRef::new(key, ts)),
             ))
             .next()
             .is_some()
     }
+
+    /// whether a record with its key in `range` was written after `ts`
+    pub(crate) fn check_range_conflict(
+        &self,
+        range: (
+            Bound<&<A::Record as Record>::Key>,
+            Bound<&<A::Record as Record>::Key>,
+        ),
+        ts: Timestamp,
+    ) -> bool {
+        let lower = match range.0 {
+            Bound::Included(key) => Bound::Included(TimestampedRef::new(key, u64::MAX.into())),
+            Bound::Excluded(key) => Bound::Excluded(TimestampedRef::new(key, EPOCH)),
+            Bound::Unbounded => Bound::Unbounded,
+        };
+        let upper = match range.1 {
+            Bound::Included(key) => Bound::Included(TimestampedRef::new(key, EPOCH)),
+            Bound::Excluded(key) => Bound::Excluded(TimestampedRef::new(key, u64::MAX.into())),
+            Bound::Unbounded => Bound::Unbounded,
+        };
+
+        self.index
+            .range::<dyn TimestampedKey<<A::Record as Record>::Key>, _>(TimestampedRange::new(
+                lower, upper,
+            ))
+            .any(|(key, _)| key.ts > ts)
+    }
 }
 
 pub struct ImmutableScan<'iter, R>
@@ -219,7 +286,7 @@ pub(crate) mod tests {
     use arrow::{
         array::{
             Array, BooleanArray, BooleanBufferBuilder, BooleanBuilder, PrimitiveBuilder,
-            RecordBatch, StringArray, StringBuilder, UInt32Array, UInt32Builder,
+            RecordBatch, StringArray, StringBuilder, UInt32Array, UInt64Array, UInt64Builder,
         },
         datatypes::{ArrowPrimitiveType, Schema, UInt32Type},
     };
@@ -235,7 +302,7 @@ pub(crate) mod tests {
     #[derive(Debug)]
     pub struct TestImmutableArrays {
         _null: Arc<BooleanArray>,
-        _ts: Arc<UInt32Array>,
+        _ts: Arc<UInt64Array>,
         vstring: Arc<StringArray>,
         vu32: Arc<UInt32Array>,
         vbool: Arc<BooleanArray>,
@@ -254,7 +321,7 @@ pub(crate) mod tests {
                 vu32: PrimitiveBuilder::<UInt32Type>::with_capacity(capacity),
                 vobool: BooleanBuilder::with_capacity(capacity),
                 _null: BooleanBufferBuilder::new(capacity),
-                _ts: UInt32Builder::with_capacity(capacity),
+                _ts: UInt64Builder::with_capacity(capacity),
             }
         }
 
@@ -296,7 +363,7 @@ pub(crate) mod tests {
         vu32: PrimitiveBuilder<UInt32Type>,
         vobool: BooleanBuilder,
         _null: BooleanBufferBuilder,
-        _ts: UInt32Builder,
+        _ts: UInt64Builder,
     }
 
     impl Builder<TestImmutableArrays> for TestBuilder {
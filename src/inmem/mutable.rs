@@ -1,32 +1,42 @@
-use std::{intrinsics::transmute, ops::Bound, sync::Arc};
+use std::{
+    intrinsics::transmute,
+    mem,
+    ops::Bound,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, OnceLock,
+    },
+};
 
+use arrow::array::RecordBatch;
 use async_lock::Mutex;
 use crossbeam_skiplist::{
     map::{Entry, Range},
     SkipMap,
 };
-use fusio::{buffered::BufWriter, DynFs, DynWrite};
+use fusio::{buffered::BufWriter, path::Path, DynFs, DynWrite};
+use parquet::arrow::ProjectionMask;
+use web_time::Instant;
 
 use crate::{
-    fs::{FileId, FileType},
+    fs::{self, FileId, FileType},
     inmem::immutable::Immutable,
-    record::{Key, KeyRef, Record, RecordInstance},
+    range_tombstone::RangeTombstone,
+    record::{Key, KeyRef, Record, RecordInstance, RecordRef},
+    replication::{Lsn, WalTailers},
     timestamp::{
-        timestamped::{Timestamped, TimestampedRef},
+        timestamped::{Timestamped, TimestampedKey, TimestampedRange, TimestampedRef},
         Timestamp, EPOCH,
     },
     trigger::Trigger,
     wal::{log::LogType, WalFile},
-    DbError, DbOption,
+    DbError, DbOption, FormatVersion, WalCompression, WalSyncPolicy,
 };
 
 pub(crate) type MutableScan<'scan, R> = Range<
     'scan,
-    TimestampedRef<<R as Record>::Key>,
-    (
-        Bound<&'scan TimestampedRef<<R as Record>::Key>>,
-        Bound<&'scan TimestampedRef<<R as Record>::Key>>,
-    ),
+    dyn TimestampedKey<<R as Record>::Key> + 'scan,
+    TimestampedRange<'scan, <R as Record>::Key>,
     Timestamped<<R as Record>::Key>,
     Option<R>,
 >;
@@ -36,8 +46,26 @@ where
     R: Record,
 {
     pub(crate) data: SkipMap<Timestamped<R::Key>, Option<R>>,
+    pub(crate) range_tombstones: SkipMap<Timestamp, RangeTombstone<R::Key>>,
     wal: Option<Mutex<WalFile<Box<dyn DynWrite>, R>>>,
+    /// the WAL segments rolled over, needed until the memtable is flushed
+    segments: std::sync::Mutex<Vec<FileId>>,
+    segment: WalSegment,
     pub(crate) trigger: Arc<Box<dyn Trigger<R> + Send + Sync>>,
+    pub(crate) tailers: Arc<WalTailers>,
+    /// encoded size of the keys and records inserted
+    size: AtomicUsize,
+    /// bytes written to the WAL segments, and to the ones rolled over
+    wal_size: AtomicUsize,
+    rolled_wal_size: AtomicUsize,
+    sync_on_commit: bool,
+    /// the WAL entries known to be flushed, see [`Mutable::sync_wal`]
+    synced: AtomicU64,
+    /// held by the commit flushing the WAL for the ones waiting behind it
+    sync_lock: Mutex<()>,
+    /// when the oldest write of the memtable was applied, see
+    /// [`DbOption::memtable_flush_interval`]
+    first_write: OnceLock<Instant>,
 }
 
 impl<R> Mutable<R>
@@ -49,27 +77,79 @@ where
         trigger: Arc<Box<dyn Trigger<R> + Send + Sync>>,
         fs: &Arc<dyn DynFs>,
     ) -> Result<Self, fusio::Error> {
+        let segment = WalSegment {
+            fs: fs.clone(),
+            dir: option.wal_dir_path(),
+            is_local: option.is_wal_local(),
+            buffer_size: option.wal_buffer_size,
+            max_size: option.wal_segment_size,
+            preallocation: option.wal_preallocation(),
+            compression: option.wal_compression,
+            format_version: option.format_version,
+        };
         let mut wal = None;
         if option.use_wal {
-            let file_id = FileId::new();
-
-            let file = Box::new(BufWriter::new(
-                fs.open_options(&option.wal_path(file_id), FileType::Wal.open_options(false))
-                    .await?,
-                option.wal_buffer_size,
-            )) as Box<dyn DynWrite>;
-
-            wal = Some(Mutex::new(WalFile::new(file, file_id)));
+            wal = Some(Mutex::new(segment.open(FileId::new()).await?));
         };
 
         Ok(Self {
             data: Default::default(),
+            range_tombstones: Default::default(),
             wal,
+            segments: Default::default(),
+            segment,
             trigger,
+            tailers: Default::default(),
+            size: AtomicUsize::new(0),
+            wal_size: AtomicUsize::new(0),
+            rolled_wal_size: AtomicUsize::new(0),
+            sync_on_commit: option.wal_sync_policy == WalSyncPolicy::PerCommit,
+            synced: AtomicU64::new(0),
+            sync_lock: Mutex::new(()),
+            first_write: OnceLock::new(),
         })
     }
 }
 
+/// opens the segments of the WAL of a memtable
+struct WalSegment {
+    fs: Arc<dyn DynFs>,
+    dir: Path,
+    is_local: bool,
+    buffer_size: usize,
+    max_size: Option<usize>,
+    /// see [`DbOption::preallocate_wal`]
+    preallocation: Option<u64>,
+    compression: WalCompression,
+    format_version: FormatVersion,
+}
+
+impl WalSegment {
+    fn path(&self, file_id: FileId) -> Path {
+        self.dir.child(format!("{}.{}", file_id, FileType::Wal))
+    }
+
+    async fn open<R>(
+        &self,
+        file_id: FileId,
+    ) -> Result<WalFile<Box<dyn DynWrite>, R>, fusio::Error> {
+        let path = self.path(file_id);
+        let file = Box::new(BufWriter::new(
+            self.fs
+                .open_options(&path, FileType::Wal.open_options(false))
+                .await?,
+            self.buffer_size,
+        )) as Box<dyn DynWrite>;
+        if let Some(len) = self.preallocation {
+            fs::fallocate(&path, len)?;
+        }
+
+        Ok(WalFile::new(file, file_id)
+            .with_compression(self.compression)
+            .with_format_version(self.format_version))
+    }
+}
+
 impl<R> Mutable<R>
 where
     R: Record + Send,
@@ -105,29 +185,223 @@ where
         if let (Some(log_ty), Some(wal)) = (log_ty, &self.wal) {
             let mut wal_guard = wal.lock().await;
 
+            let key = timestamped_key.map(|key| unsafe { transmute(key.as_key_ref()) });
+            let record = value.as_ref().map(R::as_record_ref);
+
             wal_guard
-                .write(
-                    log_ty,
-                    timestamped_key.map(|key| unsafe { transmute(key.as_key_ref()) }),
-                    value.as_ref().map(R::as_record_ref),
-                )
+                .write(log_ty, key.clone(), record.clone())
                 .await
                 .map_err(|e| DbError::WalWrite(Box::new(e)))?;
+            if !self.tailers.is_empty() {
+                self.tailers
+                    .send_record(Self::last_lsn(&wal_guard), log_ty, key, record)
+                    .await?;
+            }
+            // the entries of a transaction are kept in a single segment, as the recovery only
+            // replays them once it reads the last one
+            if matches!(log_ty, LogType::Full | LogType::Last) {
+                self.roll_wal(&mut wal_guard).await?;
+            }
         }
 
+        self.first_write.get_or_init(Instant::now);
         let is_exceeded = self.trigger.item(&value);
+        self.size.fetch_add(
+            timestamped_key.size() + value.as_ref().map_or(0, R::size),
+            Ordering::Relaxed,
+        );
         self.data.insert(timestamped_key, value);
 
         Ok(is_exceeded)
     }
 
+    /// append `records` to the WAL as a single batch, holding the WAL once for all of them, then
+    /// insert them
+    pub(crate) async fn insert_batch(
+        &self,
+        records: Vec<R>,
+        ts: Timestamp,
+    ) -> Result<bool, DbError<R>> {
+        let timestamped_keys = records
+            .iter()
+            .map(|record| Timestamped::new(record.key().to_key(), ts))
+            .collect::<Vec<_>>();
+
+        if let Some(wal) = &self.wal {
+            let mut wal_guard = wal.lock().await;
+            let count = records.len();
+
+            for (offset, (timestamped_key, record)) in
+                timestamped_keys.iter().zip(&records).enumerate()
+            {
+                let log_ty = match offset {
+                    0 if count == 1 => LogType::Full,
+                    0 => LogType::First,
+                    offset if offset == count - 1 => LogType::Last,
+                    _ => LogType::Middle,
+                };
+                let key = timestamped_key.map(|key| unsafe { transmute(key.as_key_ref()) });
+                let record = Some(record.as_record_ref());
+
+                wal_guard
+                    .write(log_ty, key.clone(), record.clone())
+                    .await
+                    .map_err(|e| DbError::WalWrite(Box::new(e)))?;
+                if !self.tailers.is_empty() {
+                    self.tailers
+                        .send_record(Self::last_lsn(&wal_guard), log_ty, key, record)
+                        .await?;
+                }
+            }
+            // the batch is kept in a single segment
+            self.roll_wal(&mut wal_guard).await?;
+        }
+
+        self.first_write.get_or_init(Instant::now);
+        let mut is_exceeded = false;
+        let mut size = 0;
+        for (timestamped_key, record) in timestamped_keys.into_iter().zip(records) {
+            let value = Some(record);
+            is_exceeded |= self.trigger.item(&value);
+            size += timestamped_key.size() + value.as_ref().map_or(0, R::size);
+            self.data.insert(timestamped_key, value);
+        }
+        self.size.fetch_add(size, Ordering::Relaxed);
+
+        Ok(is_exceeded)
+    }
+
+    /// append the rows of a `batch` holding the internal columns to the WAL as a single batch
+    pub(crate) async fn write_record_batch(&self, batch: &RecordBatch) -> Result<(), DbError<R>> {
+        let Some(wal) = &self.wal else {
+            return Ok(());
+        };
+        let mut wal_guard = wal.lock().await;
+        let schema = batch.schema();
+        let projection_mask = ProjectionMask::all();
+        let row_count = batch.num_rows();
+
+        for offset in 0..row_count {
+            let log_ty = match offset {
+                0 if row_count == 1 => LogType::Full,
+                0 => LogType::First,
+                offset if offset == row_count - 1 => LogType::Last,
+                _ => LogType::Middle,
+            };
+            let record = R::Ref::from_record_batch(batch, offset, &projection_mask, &schema);
+
+            wal_guard
+                .write(log_ty, record.value(), record.get())
+                .await
+                .map_err(|e| DbError::WalWrite(Box::new(e)))?;
+            if !self.tailers.is_empty() {
+                self.tailers
+                    .send_record(
+                        Self::last_lsn(&wal_guard),
+                        log_ty,
+                        record.value(),
+                        record.get(),
+                    )
+                    .await?;
+            }
+        }
+        self.roll_wal(&mut wal_guard).await?;
+        Ok(())
+    }
+
+    pub(crate) async fn delete_range(
+        &self,
+        lower: Bound<R::Key>,
+        upper: Bound<R::Key>,
+        ts: Timestamp,
+        is_recover: bool,
+    ) -> Result<(), DbError<R>> {
+        self.first_write.get_or_init(Instant::now);
+        let range_tombstone = RangeTombstone::new(lower, upper, ts);
+
+        if let (false, Some(wal)) = (is_recover, &self.wal) {
+            let mut wal_guard = wal.lock().await;
+
+            wal_guard
+                .write_range_tombstone(&range_tombstone)
+                .await
+                .map_err(|e| DbError::WalWrite(Box::new(e)))?;
+            if !self.tailers.is_empty() {
+                self.tailers
+                    .send_range_tombstone(Self::last_lsn(&wal_guard), &range_tombstone)
+                    .await?;
+            }
+            self.roll_wal(&mut wal_guard).await?;
+        }
+        self.range_tombstones.insert(ts, range_tombstone);
+
+        Ok(())
+    }
+
+    /// switch to a new WAL segment once the active one exceeds
+    /// [`DbOption::wal_segment_size`], the segment ids follow the order of their entries for
+    /// the recovery
+    async fn roll_wal(&self, wal: &mut WalFile<Box<dyn DynWrite>, R>) -> Result<(), DbError<R>> {
+        self.wal_size.store(
+            self.rolled_wal_size.load(Ordering::Relaxed) + wal.size(),
+            Ordering::Relaxed,
+        );
+        if self
+            .segment
+            .max_size
+            .map_or(true, |max_size| wal.size() <= max_size)
+        {
+            return Ok(());
+        }
+        self.switch_wal(wal).await
+    }
+
+    /// continue the WAL in a new segment, so that no entry is written after an incomplete one
+    /// left by a failed write, which is cut off the end of the old segment
+    pub(crate) async fn new_wal_segment(&self) -> Result<(), DbError<R>> {
+        if let Some(wal) = &self.wal {
+            let mut wal = wal.lock().await;
+            // only the entries written completely are counted
+            let (file_id, size) = (wal.file_id(), wal.size());
+            self.switch_wal(&mut wal).await?;
+            fs::truncate_file(
+                &self.segment.fs,
+                &self.segment.path(file_id),
+                size as u64,
+                self.segment.is_local,
+            )
+            .await?;
+        }
+        Ok(())
+    }
+
+    async fn switch_wal(&self, wal: &mut WalFile<Box<dyn DynWrite>, R>) -> Result<(), DbError<R>> {
+        let file_id = wal
+            .file_id()
+            .increment()
+            .map_or_else(FileId::new, |next| next.max(FileId::new()));
+        let mut segment = mem::replace(wal, self.segment.open(file_id).await?);
+        // removed along with the memtable even if flushing it fails
+        self.segments.lock().unwrap().push(segment.file_id());
+        self.rolled_wal_size
+            .fetch_add(segment.size(), Ordering::Relaxed);
+        segment.flush().await?;
+        if self.segment.preallocation.is_some() {
+            fs::truncate_table(&self.segment.path(segment.file_id()))?;
+        }
+        // the entries of the rolled segment are flushed, the new one counts from zero
+        self.synced.store(0, Ordering::Release);
+
+        Ok(())
+    }
+
     pub(crate) fn get(
         &self,
         key: &R::Key,
         ts: Timestamp,
     ) -> Option<Entry<'_, Timestamped<R::Key>, Option<R>>> {
         self.data
-            .range::<TimestampedRef<R::Key>, _>((
+            .range::<dyn TimestampedKey<R::Key>, _>(TimestampedRange::new(
                 Bound::Included(TimestampedRef::new(key, ts)),
                 Bound::Included(TimestampedRef::new(key, EPOCH)),
             ))
@@ -150,45 +424,117 @@ where
             Bound::Unbounded => Bound::Unbounded,
         };
 
-        self.data.range((lower, upper))
+        self.data.range(TimestampedRange::new(lower, upper))
+    }
+
+    pub(crate) fn size(&self) -> usize {
+        self.size.load(Ordering::Relaxed)
     }
 
     pub(crate) fn is_empty(&self) -> bool {
         self.data.is_empty()
     }
 
+    /// bytes written to the WAL of the memtable, see [`DbOption::max_total_wal_size`]
+    pub(crate) fn wal_size(&self) -> usize {
+        self.wal_size.load(Ordering::Relaxed)
+    }
+
+    /// when the oldest write still in the memtable was applied, none if empty
+    pub(crate) fn first_write(&self) -> Option<Instant> {
+        self.first_write.get().copied()
+    }
+
     pub(crate) fn check_conflict(&self, key: &R::Key, ts: Timestamp) -> bool {
         self.data
-            .range::<TimestampedRef<<R as Record>::Key>, _>((
-                Bound::Excluded(TimestampedRef::new(key, u32::MAX.into())),
+            .range::<dyn TimestampedKey<R::Key>, _>(TimestampedRange::new(
+                Bound::Excluded(TimestampedRef::new(key, u64::MAX.into())),
                 Bound::Excluded(TimestampedRef::new(key, ts)),
             ))
             .next()
             .is_some()
     }
 
+    /// whether a record with its key in `range` was written after `ts`
+    pub(crate) fn check_range_conflict(
+        &self,
+        range: (Bound<&R::Key>, Bound<&R::Key>),
+        ts: Timestamp,
+    ) -> bool {
+        self.scan(range, u64::MAX.into())
+            .any(|entry| entry.key().ts > ts)
+    }
+
     pub(crate) async fn into_immutable(
         self,
         instance: &RecordInstance,
-    ) -> Result<(Option<FileId>, Immutable<R::Columns>), fusio::Error> {
-        let mut file_id = None;
+    ) -> Result<(Vec<FileId>, Immutable<R::Columns>), fusio::Error> {
+        let mut file_ids = self.segments.into_inner().unwrap();
 
         if let Some(wal) = self.wal {
             let mut wal_guard = wal.lock().await;
             wal_guard.flush().await?;
-            file_id = Some(wal_guard.file_id());
+            file_ids.push(wal_guard.file_id());
         }
 
-        Ok((file_id, Immutable::from((self.data, instance))))
+        let mut immutable = Immutable::from((self.data, instance));
+        immutable.wal_size = self.wal_size.load(Ordering::Relaxed);
+        immutable.range_tombstones = self
+            .range_tombstones
+            .into_iter()
+            .map(|(_, range_tombstone)| range_tombstone)
+            .collect();
+
+        Ok((file_ids, immutable))
+    }
+
+    /// position of the next entry written to the WAL, `None` if the WAL is disabled
+    pub(crate) async fn next_lsn(&self) -> Option<Lsn> {
+        let wal_guard = self.wal.as_ref()?.lock().await;
+
+        Some(Lsn {
+            wal_id: wal_guard.file_id(),
+            index: wal_guard.entry_count(),
+        })
+    }
+
+    fn last_lsn(wal: &WalFile<Box<dyn DynWrite>, R>) -> Lsn {
+        Lsn {
+            wal_id: wal.file_id(),
+            index: wal.entry_count() - 1,
+        }
     }
 
     pub(crate) async fn flush_wal(&self) -> Result<(), DbError<R>> {
         if let Some(wal) = self.wal.as_ref() {
             let mut wal_guard = wal.lock().await;
             wal_guard.flush().await?;
+            self.synced
+                .store(wal_guard.entry_count(), Ordering::Release);
         }
         Ok(())
     }
+
+    pub(crate) fn sync_on_commit(&self) -> bool {
+        self.sync_on_commit
+    }
+
+    /// flush the WAL entries written so far unless they already are, returns whether this call
+    /// flushed. A commit waits for the one flushing ahead of it and returns right away if that
+    /// flush covered its entries, otherwise it flushes the entries of every commit waiting
+    /// meanwhile at once
+    pub(crate) async fn sync_wal(&self) -> Result<bool, DbError<R>> {
+        let Some(wal) = &self.wal else {
+            return Ok(false);
+        };
+        let written = wal.lock().await.entry_count();
+        let _leader = self.sync_lock.lock().await;
+        if self.synced.load(Ordering::Acquire) >= written {
+            return Ok(false);
+        }
+        self.flush_wal().await?;
+        Ok(true)
+    }
 }
 
 impl<R> Mutable<R>
@@ -238,7 +584,7 @@ mod tests {
                     vu32: 1,
                     vbool: Some(true),
                 },
-                0_u32.into(),
+                0_u64.into(),
             )
             .await
             .unwrap();
@@ -250,12 +596,12 @@ mod tests {
                     vu32: 2,
                     vbool: None,
                 },
-                1_u32.into(),
+                1_u64.into(),
             )
             .await
             .unwrap();
 
-        let entry = mem_table.get(&key_1, 0_u32.into()).unwrap();
+        let entry = mem_table.get(&key_1, 0_u64.into()).unwrap();
         assert_eq!(
             entry.value().as_ref().unwrap().as_record_ref(),
             TestRef {
@@ -264,8 +610,8 @@ mod tests {
                 vbool: Some(true)
             }
         );
-        assert!(mem_table.get(&key_2, 0_u32.into()).is_none());
-        assert!(mem_table.get(&key_2, 1_u32.into()).is_some());
+        assert!(mem_table.get(&key_2, 0_u64.into()).is_none());
+        assert!(mem_table.get(&key_2, 1_u64.into()).is_some());
     }
 
     #[tokio::test]
@@ -280,75 +626,75 @@ mod tests {
         let mutable = Mutable::<String>::new(&option, trigger, &fs).await.unwrap();
 
         mutable
-            .insert(LogType::Full, "1".into(), 0_u32.into())
+            .insert(LogType::Full, "1".into(), 0_u64.into())
             .await
             .unwrap();
         mutable
-            .insert(LogType::Full, "2".into(), 0_u32.into())
+            .insert(LogType::Full, "2".into(), 0_u64.into())
             .await
             .unwrap();
         mutable
-            .insert(LogType::Full, "2".into(), 1_u32.into())
+            .insert(LogType::Full, "2".into(), 1_u64.into())
             .await
             .unwrap();
         mutable
-            .insert(LogType::Full, "3".into(), 1_u32.into())
+            .insert(LogType::Full, "3".into(), 1_u64.into())
             .await
             .unwrap();
         mutable
-            .insert(LogType::Full, "4".into(), 0_u32.into())
+            .insert(LogType::Full, "4".into(), 0_u64.into())
             .await
             .unwrap();
 
-        let mut scan = mutable.scan((Bound::Unbounded, Bound::Unbounded), 0_u32.into());
+        let mut scan = mutable.scan((Bound::Unbounded, Bound::Unbounded), 0_u64.into());
 
         assert_eq!(
             scan.next().unwrap().key(),
-            &Timestamped::new("1".into(), 0_u32.into())
+            &Timestamped::new("1".into(), 0_u64.into())
         );
         assert_eq!(
             scan.next().unwrap().key(),
-            &Timestamped::new("2".into(), 1_u32.into())
+            &Timestamped::new("2".into(), 1_u64.into())
         );
         assert_eq!(
             scan.next().unwrap().key(),
-            &Timestamped::new("2".into(), 0_u32.into())
+            &Timestamped::new("2".into(), 0_u64.into())
         );
         assert_eq!(
             scan.next().unwrap().key(),
-            &Timestamped::new("3".into(), 1_u32.into())
+            &Timestamped::new("3".into(), 1_u64.into())
         );
         assert_eq!(
             scan.next().unwrap().key(),
-            &Timestamped::new("4".into(), 0_u32.into())
+            &Timestamped::new("4".into(), 0_u64.into())
         );
 
         let lower = "1".to_string();
         let upper = "4".to_string();
         let mut scan = mutable.scan(
             (Bound::Included(&lower), Bound::Included(&upper)),
-            1_u32.into(),
+            1_u64.into(),
         );
 
         assert_eq!(
             scan.next().unwrap().key(),
-            &Timestamped::new("1".into(), 0_u32.into())
+            &Timestamped::new("1".into(), 0_u64.into())
         );
         assert_eq!(
             scan.next().unwrap().key(),
-            &Timestamped::new("2".into(), 1_u32.into())
+            &Timestamped::new("2".into(), 1_u64.into())
         );
         assert_eq!(
             scan.next().unwrap().key(),
-            &Timestamped::new("2".into(), 0_u32.into())
+            &Timestamped::new("2".into(), 0_u64.into())
         );
         assert_eq!(
             scan.next().unwrap().key(),
-            &Timestamped::new("3".into(), 1_u32.into())
+            &Timestamped::new("3".into(), 1_u64.into())
         );
         assert_eq!(
             scan.next().unwrap().key(),
-            &Timestamped::new("4".into(), 0_u32.into())
+            &Timestamped::new("4".into(), 0_u64.into())
         );
     }
 
@@ -384,19 +730,19 @@ mod tests {
                     ],
                     0,
                 ),
-                0_u32.into(),
+                0_u64.into(),
             )
             .await
             .unwrap();
 
         {
-            let mut scan = mutable.scan((Bound::Unbounded, Bound::Unbounded), 0_u32.into());
+            let mut scan = mutable.scan((Bound::Unbounded, Bound::Unbounded), 0_u64.into());
             let entry = scan.next().unwrap();
             assert_eq!(
                 entry.key(),
                 &Timestamped::new(
                     Column::new(Datatype::Int8, "age".to_string(), Arc::new(1_i8), false),
-                    0_u32.into()
+                    0_u64.into()
                 )
             );
             dbg!(entry.clone().value().as_ref().unwrap());
@@ -0,0 +1,395 @@
+use std::{ops::Bound, sync::Arc};
+
+use fusio::{path::Path, DynFs};
+use futures_util::StreamExt;
+use parquet::{arrow::ProjectionMask, errors::ParquetError};
+use parquet_lru::NoCache;
+
+use crate::{
+    fs::{self, manager::StoreManager, parse_file_id, FileId, FileType},
+    ondisk::sstable::SsTable,
+    record::{Key, KeyRef, Record},
+    scope::Scope,
+    timestamp::{Timestamp, Timestamped, EPOCH},
+    version::{edit::VersionEdit, VersionError},
+    DbError, DbOption, DB,
+};
+
+/// A problem found by [`DB::verify_integrity`](crate::DB::verify_integrity) in a table of the
+/// current version.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IntegrityProblem {
+    /// the version references a table missing from the file system
+    MissingTable { level: usize, gen: FileId },
+    /// the table fails to decode, or a page of it fails its checksum
+    Unreadable {
+        level: usize,
+        gen: FileId,
+        error: String,
+    },
+    /// the rows of the table are not ordered by key and then by descending timestamp
+    Unsorted { level: usize, gen: FileId },
+    /// the table holds keys outside of the range the version records for it, which reads skip
+    OutOfScope { level: usize, gen: FileId },
+    /// the key ranges of two tables of a level below the first overlap
+    Overlap {
+        level: usize,
+        gens: (FileId, FileId),
+    },
+}
+
+/// What [`DB::repair`](crate::DB::repair) salvaged.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RepairReport {
+    /// the readable SSTs put back into the version, all at level 0
+    pub tables: usize,
+    /// the unreadable SSTs and the replaced version logs, moved into the `quarantine`
+    /// directory under the base path
+    pub quarantined: Vec<Path>,
+}
+
+/// the rows of a table as read back
+enum Contents<K> {
+    Empty,
+    Unsorted,
+    Keys { min: K, max: K, max_ts: Timestamp },
+}
+
+/// read every row of the table of `scope`
+async fn verify_table<R>(
+    fs: &Arc<dyn DynFs>,
+    option: &DbOption<R>,
+    level: usize,
+    scope: &Scope<R::Key>,
+) -> Option<IntegrityProblem>
+where
+    R: Record,
+{
+    let gen = scope.gen;
+    let Ok(file) = fs
+        .open_options(
+            &option.table_path(gen, level),
+            FileType::Parquet.open_options(true),
+        )
+        .await
+    else {
+        return Some(IntegrityProblem::MissingTable { level, gen });
+    };
+
+    match read_contents::<R>(SsTable::open(Arc::new(NoCache::default()), gen, file).await).await {
+        Ok(Contents::Empty) => None,
+        Ok(Contents::Unsorted) => Some(IntegrityProblem::Unsorted { level, gen }),
+        Ok(Contents::Keys { min, max, .. }) => (min < scope.min || max > scope.max)
+            .then_some(IntegrityProblem::OutOfScope { level, gen }),
+        Err(err) => Some(IntegrityProblem::Unreadable {
+            level,
+            gen,
+            error: err.to_string(),
+        }),
+    }
+}
+
+/// the scope and newest timestamp of the table at `path`, `None` if it cannot be read back
+/// into a version
+async fn salvage_table<R>(
+    fs: &Arc<dyn DynFs>,
+    path: &Path,
+    gen: FileId,
+) -> Option<(Scope<R::Key>, Timestamp)>
+where
+    R: Record,
+{
+    let file = fs
+        .open_options(path, FileType::Parquet.open_options(true))
+        .await;
+    let table = match file {
+        Ok(file) => SsTable::open(Arc::new(NoCache::default()), gen, file).await,
+        Err(err) => Err(err),
+    };
+
+    match read_contents::<R>(table).await {
+        Ok(Contents::Keys { min, max, max_ts }) => Some((
+            Scope {
+                min,
+                max,
+                gen,
+                wal_ids: None,
+                stats: None,
+            },
+            max_ts,
+        )),
+        _ => None,
+    }
+}
+
+async fn read_contents<R>(
+    table: Result<SsTable<R>, fusio::Error>,
+) -> Result<Contents<R::Key>, ParquetError>
+where
+    R: Record,
+{
+    let mut scan = table
+        .map_err(|err| ParquetError::External(Box::new(err)))?
+        .scan(
+            (Bound::Unbounded, Bound::Unbounded),
+            u64::MAX.into(),
+            None,
+            ProjectionMask::all(),
+        )
+        .await?;
+    let mut min = None;
+    let mut last: Option<Timestamped<R::Key>> = None;
+    let mut max_ts = EPOCH;
+
+    while let Some(entry) = scan.next().await.transpose()? {
+        let key = entry.internal_key();
+        let key = Timestamped::new(key.value.to_key(), key.ts);
+
+        if last.as_ref().is_some_and(|last| *last >= key) {
+            return Ok(Contents::Unsorted);
+        }
+        if min.is_none() {
+            min = Some(key.value.clone());
+        }
+        max_ts = max_ts.max(key.ts);
+        last = Some(key);
+    }
+    Ok(match (min, last) {
+        (Some(min), Some(max)) => Contents::Keys {
+            min,
+            max: max.value,
+            max_ts,
+        },
+        _ => Contents::Empty,
+    })
+}
+
+impl<R> DB<R>
+where
+    R: Record + Send + Sync,
+    R::Columns: Send + Sync,
+{
+    /// read every SST of the current version and report the ones missing, failing to decode,
+    /// holding rows out of order or keys outside of the range recorded for them, along with the
+    /// overlapping ones of the levels below the first. Reads and writes go on meanwhile, and a
+    /// [`DB::open_read_only`] one verifies a database without opening it for writing
+    pub async fn verify_integrity(&self) -> Result<Vec<IntegrityProblem>, DbError<R>> {
+        let version = self.version_set.current().await;
+        let option = version.option();
+        let mut problems = Vec::new();
+
+        for (level, scopes) in version.level_slice.iter().enumerate() {
+            let level_fs = self
+                .manager
+                .get_fs(option.level_fs_path(level).unwrap_or(&option.base_path));
+            for scope in scopes {
+                problems.extend(verify_table(level_fs, option, level, scope).await);
+            }
+            if level > 0 {
+                problems.extend(
+                    scopes
+                        .windows(2)
+                        .filter(|pair| pair[0].max >= pair[1].min)
+                        .map(|pair| IntegrityProblem::Overlap {
+                            level,
+                            gens: (pair[0].gen, pair[1].gen),
+                        }),
+                );
+            }
+        }
+        Ok(problems)
+    }
+
+    /// rebuild the version log of the database configured by `option` from the SSTs found in
+    /// its directories, for a database which fails to open after losing or corrupting it. The
+    /// readable SSTs are all put at level 0, ordered by their newest timestamp, while the
+    /// unreadable ones and the old version logs are moved into the `quarantine` directory. The
+    /// range deletes only recorded in the version log are lost, and the WALs are left to be
+    /// replayed by the next open. The database must not be opened while repairing.
+    pub async fn repair(option: &DbOption<R>) -> Result<RepairReport, DbError<R>> {
+        let _lock = Self::lock(option).await?;
+        let manager = StoreManager::new(option.base_fs.clone(), option.fs_paths())?;
+        let base_fs = manager.base_fs();
+        let quarantine_dir = option.quarantine_dir_path();
+        base_fs.create_dir_all(&quarantine_dir).await?;
+
+        let mut table_dirs = vec![(base_fs, &option.base_path)];
+        for (path, _) in option.level_paths.iter().flatten() {
+            if table_dirs.iter().all(|(_, dir)| *dir != path) {
+                table_dirs.push((manager.get_fs(path), path));
+            }
+        }
+        let mut report = RepairReport::default();
+        let mut tables = Vec::new();
+        for (table_fs, dir) in table_dirs {
+            for path in fs::list_files(table_fs, dir, FileType::Parquet).await? {
+                // not created by tonbo
+                let Ok(Some(gen)) = parse_file_id(&path, FileType::Parquet) else {
+                    continue;
+                };
+                match salvage_table::<R>(table_fs, &path, gen).await {
+                    Some(table) => tables.push(table),
+                    None => {
+                        let target = quarantine_dir.child(path.filename().unwrap());
+                        fs::copy_file(table_fs, &path, base_fs, &target).await?;
+                        table_fs.remove(&path).await?;
+                        report.quarantined.push(target);
+                    }
+                }
+            }
+        }
+        // the tables of level 0 are searched from the last one
+        tables.sort_by_key(|(_, max_ts)| *max_ts);
+        let latest_ts = tables.last().map_or(EPOCH, |(_, max_ts)| *max_ts);
+        report.tables = tables.len();
+        let mut version_edits = tables
+            .into_iter()
+            .map(|(scope, _)| VersionEdit::Add { level: 0, scope })
+            .collect::<Vec<_>>();
+        version_edits.push(VersionEdit::LatestTimeStamp { ts: latest_ts });
+        version_edits.push(VersionEdit::NewLogLength { len: 0 });
+
+        // the old logs go first, as the open prefers the older of two logs
+        let log_dir = option.version_log_dir_path();
+        base_fs.create_dir_all(&log_dir).await?;
+        for path in fs::list_files(base_fs, &log_dir, FileType::Log).await? {
+            let target = quarantine_dir.child(path.filename().unwrap());
+            fs::copy_file(base_fs, &path, base_fs, &target).await?;
+            base_fs.remove(&path).await?;
+            report.quarantined.push(target);
+        }
+        let mut log = base_fs
+            .open_options(
+                &option.version_log_path(FileId::new()),
+                FileType::Log.open_options(false),
+            )
+            .await?;
+        VersionEdit::encode_batch(&version_edits, &mut log, option.format_version)
+            .await
+            .map_err(VersionError::Encode)?;
+        log.close().await?;
+
+        Ok(report)
+    }
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod tests {
+    use fusio::path::Path;
+    use tempfile::TempDir;
+
+    use super::IntegrityProblem;
+    use crate::{
+        executor::tokio::TokioExecutor,
+        fs::{FileId, FileType},
+        tests::{test_items, Test},
+        trigger::TriggerType,
+        DbOption, DB,
+    };
+
+    #[tokio::test]
+    async fn verify_integrity() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap());
+        option.immutable_chunk_num = 1;
+        option.immutable_chunk_max_num = 1;
+        option.trigger_type = TriggerType::Length(5);
+
+        let db: DB<Test> = DB::new(option.clone(), TokioExecutor::new()).await.unwrap();
+        for (i, item) in test_items().into_iter().enumerate() {
+            db.insert(item).await.unwrap();
+            if i % 5 == 0 {
+                db.flush().await.unwrap();
+            }
+        }
+        assert_eq!(db.verify_integrity().await.unwrap(), Vec::new());
+
+        let version = db.version_set.current().await;
+        let (level, scope) = version
+            .level_slice
+            .iter()
+            .enumerate()
+            .find_map(|(level, scopes)| scopes.first().map(|scope| (level, scope)))
+            .unwrap();
+        let path = temp_dir
+            .path()
+            .join(format!("{}.{}", scope.gen, FileType::Parquet));
+        std::fs::write(&path, b"not a parquet file").unwrap();
+        assert!(matches!(
+            db.verify_integrity().await.unwrap()[..],
+            [IntegrityProblem::Unreadable { gen, .. }] if gen == scope.gen
+        ));
+
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            db.verify_integrity().await.unwrap(),
+            vec![IntegrityProblem::MissingTable {
+                level,
+                gen: scope.gen
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn repair() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap());
+        option.immutable_chunk_num = 1;
+        option.immutable_chunk_max_num = 1;
+        option.major_threshold_with_sst_size = 3;
+        option.major_default_oldest_table_num = 1;
+        option.trigger_type = TriggerType::Length(5);
+
+        let db: DB<Test> = DB::new(option.clone(), TokioExecutor::new()).await.unwrap();
+        for (i, item) in test_items().into_iter().enumerate() {
+            db.insert(item).await.unwrap();
+            if i % 5 == 0 {
+                db.flush().await.unwrap();
+            }
+        }
+        db.insert(Test {
+            vstring: "0".to_string(),
+            vu32: 100,
+            vbool: None,
+        })
+        .await
+        .unwrap();
+        db.flush().await.unwrap();
+        db.flush_wal().await.unwrap();
+        drop(db);
+
+        // lose the version log, and corrupt a table
+        for entry in std::fs::read_dir(temp_dir.path().join("version")).unwrap() {
+            std::fs::write(entry.unwrap().path(), b"corrupted").unwrap();
+        }
+        std::fs::write(
+            temp_dir
+                .path()
+                .join(format!("{}.{}", FileId::new(), FileType::Parquet)),
+            b"not a parquet file",
+        )
+        .unwrap();
+
+        let report = DB::<Test>::repair(&option).await.unwrap();
+        assert!(report.tables > 0);
+        // the corrupted table and the version log
+        assert_eq!(report.quarantined.len(), 2);
+
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+        assert_eq!(db.verify_integrity().await.unwrap(), Vec::new());
+        assert_eq!(
+            db.get(&"0".to_string(), |entry| entry.get().vu32)
+                .await
+                .unwrap(),
+            Some(100)
+        );
+        for item in test_items().into_iter().skip(1) {
+            assert_eq!(
+                db.get(&item.vstring, |entry| entry.get().vu32)
+                    .await
+                    .unwrap(),
+                Some(item.vu32)
+            );
+        }
+    }
+}
@@ -112,142 +112,346 @@
 //!     }
 //! }
 //! ```
+mod aggregate;
+pub mod backup;
+pub mod bulk_load;
+mod checkpoint;
 mod compaction;
+#[cfg(feature = "config")]
+pub mod config;
+#[cfg(feature = "datafusion")]
+pub mod datafusion;
+mod destroy;
+mod event;
 pub mod executor;
+pub mod export;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "flight")]
+pub mod flight;
 pub mod fs;
+pub mod gc;
+mod ingest;
 pub mod inmem;
+pub mod integrity;
+mod lock;
+mod merge;
+pub mod metrics;
 mod ondisk;
 pub mod option;
+pub mod properties;
+mod range_tombstone;
+mod rate_limit;
 pub mod record;
+pub mod replication;
 mod scope;
 pub mod serdes;
 pub mod snapshot;
+mod split;
+mod statistics;
 pub mod stream;
 pub mod timestamp;
 pub mod transaction;
 mod trigger;
 mod version;
 mod wal;
+pub mod watch;
+mod write_buffer;
+mod write_sink;
+mod write_validator;
 
 use std::{
-    collections::HashMap, io, io::Cursor, marker::PhantomData, mem, ops::Bound, pin::pin, sync::Arc,
+    collections::HashMap,
+    io,
+    io::Cursor,
+    mem,
+    ops::{Bound, RangeBounds},
+    pin::pin,
+    sync::{atomic::Ordering, Arc},
+    time::Duration,
 };
 
 pub use arrow;
-use async_lock::RwLock;
+use arrow::{
+    array::{ArrayRef, BooleanArray, RecordBatch, UInt64Array},
+    datatypes::FieldRef,
+    error::ArrowError,
+};
+use async_lock::{Mutex, RwLock, RwLockReadGuard, Semaphore};
 use async_stream::stream;
+use export::ExportError;
 use flume::{bounded, Sender};
-use fs::FileId;
+use fs::{FileId, FileLock};
+use fusio::{fs::OpenOptions, path::Path, DynFs, SeqRead};
+use fusio_dispatch::FsOptions;
+use fusio_parquet::writer::AsyncWriter;
 use futures_core::Stream;
 use futures_util::StreamExt;
-use inmem::{immutable::Immutable, mutable::Mutable};
-use lockable::LockableHashMap;
+use inmem::{
+    immutable::{ArrowArrays, Immutable},
+    mutable::Mutable,
+};
 pub use once_cell;
 pub use parquet;
 use parquet::{
-    arrow::{arrow_to_parquet_schema, ProjectionMask},
+    arrow::{arrow_to_parquet_schema, AsyncArrowWriter, ProjectionMask},
     errors::ParquetError,
 };
 use parquet_lru::{DynLruCache, NoCache};
-use record::{ColumnDesc, DynRecord, Record, RecordInstance};
+use record::{ColumnDesc, DynRecord, Key, KeyRef, Record, RecordInstance};
 use thiserror::Error;
-use timestamp::{Timestamp, TimestampedRef};
+use timestamp::{Timestamp, TimestampedRef, EPOCH};
 use tokio::sync::oneshot;
+use tokio_util::sync::CancellationToken;
 pub use tonbo_macros::{KeyAttributes, Record};
-use tracing::error;
-use transaction::{CommitError, Transaction, TransactionEntry};
-
-pub use crate::option::*;
+use tracing::{debug_span, error, instrument, warn, Span};
+use transaction::{CommitError, IsolationLevel, Transaction, TransactionEntry};
+use watch::{Change, Watchers};
+use web_time::Instant;
+
+pub use crate::{
+    compaction::filter::{CompactionFilter, FilterDecision},
+    event::{EventListener, RecoveryProgress},
+    integrity::{IntegrityProblem, RepairReport},
+    option::*,
+    properties::{
+        CompactionJob, CompactionScore, DbProperties, LevelProperties, MemoryUsage, RangeChecksum,
+        TableDump, TreeDump, WritePressure,
+    },
+    statistics::{QueryStats, QueryStatsCollector},
+    stream::cancel::ScanAborted,
+    version::cleaner::ObsoleteFilesHold,
+    write_buffer::WriteBufferManager,
+    write_sink::WriteSink,
+    write_validator::WriteValidator,
+};
 use crate::{
     compaction::{CompactTask, CompactionError, Compactor},
-    executor::Executor,
+    executor::{Clock, Executor},
     fs::{manager::StoreManager, parse_file_id, FileType},
-    serdes::Decode,
-    snapshot::Snapshot,
+    lock::LockTable,
+    metrics::Timer,
+    range_tombstone::RangeTombstone,
+    rate_limit::RateLimiter,
+    scope::TableStats,
+    serdes::{BytesReader, Decode, Encode},
+    snapshot::{ActiveSnapshots, Snapshot},
+    statistics::Statistics,
     stream::{
-        mem_projection::MemProjectionStream, merge::MergeStream, package::PackageStream, Entry,
-        ScanStream,
+        cancel::{CancelStream, ScanCancel},
+        mem_projection::MemProjectionStream,
+        merge::MergeStream,
+        package::PackageStream,
+        slow_log::SlowLogStream,
+        Entry, ScanStream,
     },
     timestamp::Timestamped,
     trigger::{Trigger, TriggerFactory},
-    version::{cleaner::Cleaner, set::VersionSet, TransactionTs, Version, VersionError},
-    wal::{log::LogType, RecoverError, WalFile},
+    version::{
+        cleaner::Cleaner, edit::VersionEdit, set::VersionSet, ScanLimits, TransactionTs, Version,
+        VersionError, MAX_LEVEL,
+    },
+    wal::{log::LogType, RecoverError, WalEntry, WalFile},
+    write_buffer::WriteBuffer,
 };
 
-pub struct DB<R, E>
+pub struct DB<R>
 where
     R: Record,
-    E: Executor,
 {
     schema: Arc<RwLock<Schema<R>>>,
     version_set: VersionSet<R>,
     lock_map: LockMap<R::Key>,
+    /// serializes the validation and writes of serializable transactions
+    serializable_lock: Arc<Mutex<()>>,
+    /// holds the writes back to [`DbOption::max_writes_per_sec`] and
+    /// [`DbOption::max_write_bytes_per_sec`]
+    rate_limiter: Arc<RateLimiter>,
     manager: Arc<StoreManager>,
     parquet_lru: ParquetLru,
-    _p: PhantomData<E>,
+    /// the `LOCK` file of the directory held while the database is open for writing
+    lock: Option<FileLock>,
 }
 
-impl<E> DB<DynRecord, E>
-where
-    E: Executor + Send + Sync + 'static,
-{
+impl DB<DynRecord> {
     /// Open [`DB`] with schema which determined by [`ColumnDesc`].
-    pub async fn with_schema(
+    pub async fn with_schema<E>(
         option: DbOption<DynRecord>,
         executor: E,
         column_descs: Vec<ColumnDesc>,
         primary_index: usize,
-    ) -> Result<Self, DbError<DynRecord>> {
+    ) -> Result<Self, DbError<DynRecord>>
+    where
+        E: Executor + Send + Sync + 'static,
+    {
         let option = Arc::new(option);
 
         let instance =
             RecordInstance::Runtime(DynRecord::empty_record(column_descs, primary_index));
 
-        Self::build(option, executor, instance, Arc::new(NoCache::default())).await
+        let lru_cache = option.lru_cache();
+        Self::build(option, executor, instance, lru_cache).await
     }
 }
 
-impl<R, E> DB<R, E>
+impl<R> DB<R>
 where
     R: Record + Send + Sync,
     R::Columns: Send + Sync,
-    E: Executor + Send + Sync + 'static,
 {
     /// Open [`DB`] with a [`DbOption`]. This will create a new directory at the
     /// path specified in [`DbOption`] (if it does not exist before) and run it
     /// according to the configuration of [`DbOption`].
     ///
     /// For more configurable options, please refer to [`DbOption`].
-    pub async fn new(option: DbOption<R>, executor: E) -> Result<Self, DbError<R>> {
+    pub async fn new<E>(option: DbOption<R>, executor: E) -> Result<Self, DbError<R>>
+    where
+        E: Executor + Send + Sync + 'static,
+    {
+        let lru_cache = option.lru_cache();
         Self::build(
             Arc::new(option),
             executor,
             RecordInstance::Normal,
-            Arc::new(NoCache::default()),
+            lru_cache,
         )
         .await
     }
+
+    /// Open the [`DB`] configured by `option` for reading only, e.g. from an analytics process
+    /// while another process keeps writing to it. Only the data flushed into SSTs when opening is
+    /// visible: the WALs are not replayed, no file of the database is created, written or removed,
+    /// and no compaction runs. Writes fail with [`DbError::ReadOnly`].
+    ///
+    /// SSTs compacted away by the writer are removed once its older versions are released, so
+    /// newer data should be picked up with [`DB::try_catch_up`] regularly, and long reads are
+    /// best done on backups or checkpoints.
+    pub async fn open_read_only<E>(option: DbOption<R>, executor: E) -> Result<Self, DbError<R>>
+    where
+        E: Executor + Send + Sync + 'static,
+    {
+        Self::open_secondary(option, executor, false).await
+    }
+
+    /// Open the [`DB`] configured by `option` as a read-only secondary of the process writing to
+    /// it, possibly on shared or object storage, see [`DB::open_read_only`]. With `tail_wal`, the
+    /// records not yet flushed into SSTs are also read from the WALs, as far as the writer has
+    /// flushed its WAL buffer. Call [`DB::try_catch_up`] to follow the writer.
+    pub async fn open_secondary<E>(
+        option: DbOption<R>,
+        executor: E,
+        tail_wal: bool,
+    ) -> Result<Self, DbError<R>>
+    where
+        E: Executor + Send + Sync + 'static,
+    {
+        option.validate()?;
+        let option = Arc::new(option.disable_wal());
+        let manager = Arc::new(StoreManager::new(
+            option.base_fs.clone(),
+            option.fs_paths(),
+        )?);
+        let executor = Arc::new(executor);
+        let clock = Clock::new(executor.clone());
+        // nothing is cleaned, the tags sent when versions are released are dropped
+        let (clean_sender, clean_rx) = bounded(option.clean_channel_buffer);
+        executor.spawn(async move { while clean_rx.recv_async().await.is_ok() {} });
+
+        let version_set =
+            VersionSet::read_only(clean_sender, option.clone(), manager.clone()).await?;
+        let (task_tx, _) = bounded(1);
+        let trigger = Arc::new(TriggerFactory::create(option.trigger_type));
+        let schema = Schema {
+            mutable: Mutable::new(&option, trigger.clone(), manager.base_fs()).await?,
+            immutables: Default::default(),
+            compaction_tx: task_tx,
+            recover_wal_ids: None,
+            trigger,
+            record_instance: Arc::new(RecordInstance::Normal),
+            watchers: Default::default(),
+            event_listener: option.event_listener.clone(),
+            snapshots: Default::default(),
+            statistics: version_set.statistics().clone(),
+            mode: OpenMode::ReadOnly { tail_wal },
+            poisoned: Default::default(),
+            write_buffer: None,
+            max_total_wal_size: option.max_total_wal_size,
+            clock: clock.clone(),
+        };
+        if tail_wal {
+            schema
+                .replay_live_wals(&option, &version_set, &manager)
+                .await?;
+        }
+
+        Ok(Self {
+            schema: Arc::new(RwLock::new(schema)),
+            version_set,
+            lock_map: Arc::new(LockTable::new(
+                option.lock_timeout,
+                option.lock_shards,
+                clock.clone(),
+            )),
+            serializable_lock: Arc::new(Default::default()),
+            rate_limiter: Arc::new(RateLimiter::new(&option, clock)),
+            manager,
+            parquet_lru: option.lru_cache(),
+            lock: None,
+        })
+    }
+
+    /// take the `LOCK` file of the database, so that another process opening it for writing fails
+    /// fast instead of corrupting it. The lock is released when the file is closed, even if the
+    /// process crashes. A database on a file system without locks, as object storage, is opened
+    /// unlocked with a warning, see [`DB::is_locked`].
+    async fn lock(option: &DbOption<R>) -> Result<FileLock, DbError<R>> {
+        match fs::lock_file(&option.base_fs, &option.lock_path()) {
+            Ok(FileLock::Unsupported) => {
+                warn!(
+                    path = %option.base_path,
+                    "the file system does not support locks, another process may open the \
+                     database for writing"
+                );
+                Ok(FileLock::Unsupported)
+            }
+            Ok(lock) => Ok(lock),
+            Err(fusio::Error::Io(err)) if err.kind() == std::io::ErrorKind::WouldBlock => {
+                Err(DbError::Locked(option.base_path.clone()))
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// whether the database holds the lock keeping other processes from opening it for writing,
+    /// which the file systems without locks, as object storage, do not support. A read-only
+    /// database takes no lock
+    pub fn is_locked(&self) -> bool {
+        matches!(self.lock, Some(FileLock::Local { .. }))
+    }
 }
 
-impl<R, E> DB<R, E>
+impl<R> DB<R>
 where
     R: Record + Send + Sync,
     R::Columns: Send + Sync,
-    E: Executor + Send + Sync + 'static,
 {
-    async fn build(
+    async fn build<E>(
         option: Arc<DbOption<R>>,
         executor: E,
         instance: RecordInstance,
         lru_cache: ParquetLru,
-    ) -> Result<Self, DbError<R>> {
+    ) -> Result<Self, DbError<R>>
+    where
+        E: Executor + Send + Sync + 'static,
+    {
+        option.validate()?;
         let manager = Arc::new(StoreManager::new(
             option.base_fs.clone(),
-            option.level_paths.clone(),
+            option.fs_paths(),
         )?);
         {
             manager
-                .base_fs()
+                .get_fs(&option.wal_dir_path())
                 .create_dir_all(&option.wal_dir_path())
                 .await
                 .map_err(DbError::Fusio)?;
@@ -256,15 +460,51 @@ where
                 .create_dir_all(&option.version_log_dir_path())
                 .await
                 .map_err(DbError::Fusio)?;
+            if let Some(archive_path) = option.wal_archive_path() {
+                manager
+                    .get_fs(archive_path)
+                    .create_dir_all(archive_path)
+                    .await
+                    .map_err(DbError::Fusio)?;
+            }
         }
+        let started = option.event_listener.as_ref().map(|_| Instant::now());
+        let lock = Self::lock(&option).await?;
         let (task_tx, task_rx) = bounded(1);
+        let executor = Arc::new(executor);
+        let clock = Clock::new(executor.clone());
 
-        let (mut cleaner, clean_sender) = Cleaner::<R>::new(option.clone(), manager.clone());
+        let (mut cleaner, clean_sender) =
+            Cleaner::<R>::new(option.clone(), manager.clone(), clock.clone());
 
         let version_set = VersionSet::new(clean_sender, option.clone(), manager.clone()).await?;
         let schema = Arc::new(RwLock::new(
-            Schema::new(option.clone(), task_tx, &version_set, instance, &manager).await?,
+            Schema::new(
+                option.clone(),
+                task_tx,
+                &version_set,
+                instance,
+                &manager,
+                started,
+                clock.clone(),
+            )
+            .await?,
         ));
+        if option.paranoid_checks {
+            if option.fast_open {
+                let version_set = version_set.clone();
+                let schema = Arc::downgrade(&schema);
+                executor.spawn(async move {
+                    if let Err(err) = version_set.check_current().await {
+                        if let Some(schema) = schema.upgrade() {
+                            schema.read().await.poison(&err);
+                        }
+                    }
+                });
+            } else {
+                version_set.check_current().await?;
+            }
+        }
         let mut compactor = Compactor::<R>::new(
             schema.clone(),
             option.clone(),
@@ -278,18 +518,41 @@ where
             }
         });
 
+        if option.max_background_compactions > 1 {
+            let (background_tx, background_rx) = bounded(option.max_background_compactions);
+            for _ in 0..option.max_background_compactions {
+                let compactor = compactor.clone();
+                let background_rx = background_rx.clone();
+                let parquet_lru = lru_cache.clone();
+                executor.spawn(async move {
+                    while let Ok(()) = background_rx.recv_async().await {
+                        if let Err(err) = compactor.background_compaction(parquet_lru.clone()).await
+                        {
+                            error!("[Compaction Error]: {}", err);
+                            compactor.schema.read().await.poison(&err);
+                        }
+                    }
+                });
+            }
+            compactor.background = Some(background_tx);
+        }
         let compact_task_cache = lru_cache.clone();
         executor.spawn(async move {
             while let Ok(task) = task_rx.recv_async().await {
                 if let Err(err) = match task {
                     CompactTask::Freeze => {
                         compactor
-                            .check_then_compaction(compact_task_cache.clone())
+                            .check_then_compaction(compact_task_cache.clone(), false)
+                            .await
+                    }
+                    CompactTask::Release => {
+                        compactor
+                            .check_then_compaction(compact_task_cache.clone(), true)
                             .await
                     }
                     CompactTask::Flush(option_tx) => {
                         let mut result = compactor
-                            .check_then_compaction(compact_task_cache.clone())
+                            .check_then_compaction(compact_task_cache.clone(), false)
                             .await;
                         if let Some(tx) = option_tx {
                             if result.is_ok() {
@@ -298,25 +561,120 @@ where
                         }
                         result
                     }
+                    CompactTask::Purge => compactor.purge(compact_task_cache.clone()).await,
                 } {
-                    error!("[Compaction Error]: {}", err)
+                    error!("[Compaction Error]: {}", err);
+                    if !matches!(err, CompactionError::ChannelClose) {
+                        compactor.schema.read().await.poison(&err);
+                    }
                 }
             }
         });
+        if let WalSyncPolicy::Interval(interval) = option.wal_sync_policy {
+            // stops once the database is dropped
+            let schema = Arc::downgrade(&schema);
+            let clock = clock.clone();
+            executor.spawn(async move {
+                loop {
+                    clock.sleep(interval).await;
+                    let Some(schema) = schema.upgrade() else {
+                        break;
+                    };
+                    let schema = schema.read().await;
+                    if let Err(err) = schema.sync_wal().await {
+                        error!("[WAL Sync Error]: {}", err);
+                        schema.poison(&err);
+                    }
+                }
+            });
+        }
+        if let Some(interval) = option.memtable_flush_interval {
+            // stops once the database is dropped
+            let schema = Arc::downgrade(&schema);
+            let clock = clock.clone();
+            executor.spawn(async move {
+                loop {
+                    let Some(schema) = schema.upgrade() else {
+                        break;
+                    };
+                    let wait = {
+                        let schema = schema.read().await;
+                        match schema.mutable.first_write().map(|first| first.elapsed()) {
+                            Some(age) if age < interval => interval - age,
+                            Some(_) => {
+                                // retried on the next tick if a flush is running already
+                                let _ = schema.compaction_tx.try_send(CompactTask::Release);
+                                interval
+                            }
+                            None => interval,
+                        }
+                    };
+                    drop(schema);
+                    clock.sleep(wait).await;
+                }
+            });
+        }
+        if let Some(retention) = option.obsolete_file_retention {
+            // stops once the database is dropped
+            let schema = Arc::downgrade(&schema);
+            let clean_sender = version_set.clean_sender().clone();
+            let clock = clock.clone();
+            executor.spawn(async move {
+                while schema.strong_count() > 0 {
+                    if clean_sender
+                        .send_async(version::cleaner::CleanTag::Expire)
+                        .await
+                        .is_err()
+                    {
+                        break;
+                    }
+                    clock.sleep(retention.min(Duration::from_secs(1))).await;
+                }
+            });
+        }
 
         Ok(Self {
             schema,
             version_set,
-            lock_map: Arc::new(Default::default()),
+            lock_map: Arc::new(LockTable::new(
+                option.lock_timeout,
+                option.lock_shards,
+                clock.clone(),
+            )),
+            serializable_lock: Arc::new(Default::default()),
+            rate_limiter: Arc::new(RateLimiter::new(&option, clock)),
             manager,
             parquet_lru: lru_cache,
-            _p: Default::default(),
+            lock: Some(lock),
         })
     }
 
-    /// open an optimistic ACID transaction
+    /// open an optimistic ACID transaction with [`IsolationLevel::Snapshot`]
     pub async fn transaction(&self) -> Transaction<'_, R> {
-        Transaction::new(self.snapshot().await, self.lock_map.clone())
+        self.transaction_with(IsolationLevel::Snapshot).await
+    }
+
+    /// open a transaction with the `isolation` level, where [`IsolationLevel::ReadCommitted`]
+    /// skips the key locks and conflict checks for cheaper transactions of read mostly workloads
+    pub async fn transaction_with(&self, isolation: IsolationLevel) -> Transaction<'_, R> {
+        let transaction = Transaction::new(self.snapshot().await, self.lock_map.clone())
+            .rate_limited(self.rate_limiter.clone());
+        match isolation {
+            IsolationLevel::ReadCommitted => transaction.read_committed(),
+            IsolationLevel::Snapshot => transaction,
+            IsolationLevel::Serializable => {
+                transaction.serializable(self.serializable_lock.clone())
+            }
+        }
+    }
+
+    /// open a transaction with serializable snapshot isolation: the keys and ranges it reads are
+    /// tracked, and the commit fails with [`CommitError::ReadConflict`] when one of them was
+    /// written by another transaction since the snapshot, so that transactions reading keys then
+    /// writing different ones can not interleave into a write skew. The guarantee holds among
+    /// serializable transactions, writes of the others are only checked against their snapshot.
+    pub async fn serializable_transaction(&self) -> Transaction<'_, R> {
+        self.transaction_with(IsolationLevel::Serializable).await
     }
 
     pub async fn snapshot(&self) -> Snapshot<'_, R> {
@@ -328,6 +686,54 @@ where
         )
     }
 
+    /// a snapshot reading the records as they were at the timestamp `ts`, see
+    /// [`Snapshot::read_at`]
+    pub async fn snapshot_at(&self, ts: Timestamp) -> Result<Snapshot<'_, R>, DbError<R>> {
+        let mut snapshot = self.snapshot().await;
+        snapshot.read_at(ts)?;
+        Ok(snapshot)
+    }
+
+    /// change the `options` of the open database, validated as when opening it. The flushes,
+    /// compactions and writes started afterwards pick them up. The other options, such as the
+    /// paths, the WAL sync policy and the cache, are fixed once opened
+    pub fn set_options(&self, options: MutableOptions) -> Result<(), DbError<R>> {
+        let option = self.version_set.update_option(|option| {
+            let option = options.apply(option.clone());
+            option.validate()?;
+            Ok::<_, DbError<R>>(option)
+        })?;
+        self.rate_limiter.set_rates(&option);
+        Ok(())
+    }
+
+    /// keep every SST dropped by the database on the file system until the returned hold is
+    /// dropped, beyond [`DbOption::obsolete_file_retention`], e.g. while a backup copies them
+    pub async fn hold_obsolete_files(&self) -> ObsoleteFilesHold {
+        ObsoleteFilesHold::new(self.version_set.clean_sender().clone()).await
+    }
+
+    /// the major compaction score of each level merged into the next one, in the order the
+    /// background compactions pick them, to tell why a level is compacted before another
+    pub async fn compaction_scores(&self) -> Vec<CompactionScore> {
+        let version = self.version_set.current().await;
+        compaction::compaction_scores(version.option(), &version)
+    }
+
+    /// the major compactions running, with the SSTs they merge and how far they got
+    pub fn compaction_jobs(&self) -> Vec<CompactionJob> {
+        self.version_set.jobs().list()
+    }
+
+    /// cancel the major compaction `id` of [`DB::compaction_jobs`], e.g. before shutting down
+    /// rather than waiting for a long one to complete. It stops at the next batch of records it
+    /// writes and removes the SSTs written so far, leaving its inputs to a later compaction,
+    /// while a cancelled [`DB::compact_range`] fails with [`CompactionError::Cancelled`].
+    /// `false` if the job is not running
+    pub fn cancel_compaction(&self, id: u64) -> bool {
+        self.version_set.jobs().cancel(id)
+    }
+
     /// insert a single tonbo record
     pub async fn insert(&self, record: R) -> Result<(), CommitError<R>> {
         Ok(self.write(record, self.version_set.increase_ts()).await?)
@@ -343,19 +749,194 @@ where
             .await?)
     }
 
+    /// insert the rows of a `RecordBatch` with the columns of `R` as a single batch. The rows are
+    /// appended to the WAL and become an immutable memtable directly, without being converted
+    /// into records one by one.
+    pub async fn insert_record_batch(&self, batch: RecordBatch) -> Result<(), DbError<R>> {
+        if batch.num_rows() == 0 {
+            return Ok(());
+        }
+        self.throttle(batch.num_rows(), || batch.get_array_memory_size())
+            .await;
+        // held back by the write stall triggers as the other writes
+        drop(self.schema_for_write().await);
+        let mut schema = self.schema.write().await;
+        schema.check_writable()?;
+        let batch = schema.with_internal_columns(batch, self.version_set.increase_ts())?;
+        let option = self.version_set.current().await.option().clone();
+
+        // the memtables are searched from the mutable one, so it must not hold older versions
+        if !schema.mutable.is_empty() {
+            let wal_fs = self.manager.get_fs(&option.wal_dir_path());
+            schema.freeze(&option, wal_fs).await?;
+        }
+        // the rows stay in the WAL of the mutable memtable, which is flushed after them
+        let changes = schema.watchers.put_record_batch(&batch).await?;
+        schema.check_wal(schema.mutable.write_record_batch(&batch).await)?;
+        schema.commit_wal().await?;
+        schema
+            .immutables
+            .push((Vec::new(), Immutable::from_record_batch(&batch)));
+        schema.account_memory();
+        for change in changes {
+            schema.watchers.send(change);
+        }
+        if schema.immutables.len() > option.immutable_chunk_max_num {
+            let _ = schema.compaction_tx.try_send(CompactTask::Freeze);
+        }
+
+        Ok(())
+    }
+
     /// delete the record with the primary key as the `key`
     pub async fn remove(&self, key: R::Key) -> Result<bool, CommitError<R>> {
-        Ok(self
-            .schema
-            .read()
-            .await
+        check_write(&self.version_set.option(), key.as_key_ref(), None)?;
+        self.throttle(1, || key.size()).await;
+        let schema = self.schema_for_write().await;
+        metrics::increment(metrics::WRITE_RECORDS, 1);
+
+        let is_excess = schema
             .remove(LogType::Full, key, self.version_set.increase_ts())
-            .await?)
+            .await?;
+        schema.commit_wal().await?;
+        Ok(is_excess)
+    }
+
+    /// insert a single tonbo record with the timestamp `ts` from a clock of the application, see
+    /// [`Transaction::commit_at`]
+    pub async fn insert_at(&self, record: R, ts: Timestamp) -> Result<(), CommitError<R>> {
+        let mut txn = self.transaction().await;
+        txn.insert(record);
+        txn.commit_at(ts).await
+    }
+
+    /// delete the record with the primary key as the `key` with the timestamp `ts` from a clock
+    /// of the application, see [`Transaction::commit_at`]
+    pub async fn remove_at(&self, key: R::Key, ts: Timestamp) -> Result<(), CommitError<R>> {
+        let mut txn = self.transaction().await;
+        txn.remove(key);
+        txn.commit_at(ts).await
+    }
+
+    /// atomically write `new` (or delete the record when `new` is `None`) if the current value of
+    /// `key` is `expected`, otherwise return [`CommitError::ConditionFailed`] with the actual
+    /// value
+    pub async fn compare_and_swap(
+        &self,
+        key: R::Key,
+        expected: Option<R>,
+        new: Option<R>,
+    ) -> Result<(), CommitError<R>> {
+        let mut txn = self.transaction().await;
+        txn.put_if(key, expected, new);
+        txn.commit().await
+    }
+
+    /// delete all records with primary keys in the `range` by writing a single range tombstone
+    pub async fn delete_range(
+        &self,
+        range: (Bound<&R::Key>, Bound<&R::Key>),
+    ) -> Result<(), CommitError<R>> {
+        let option = self.version_set.option();
+        for key in [range.0, range.1] {
+            if let Bound::Included(key) | Bound::Excluded(key) = key {
+                check_write_size(&option, || key.size(), None)?;
+            }
+        }
+        let schema = self.schema.read().await;
+        schema
+            .delete_range(
+                range.0.cloned(),
+                range.1.cloned(),
+                self.version_set.increase_ts(),
+            )
+            .await?;
+        Ok(schema.commit_wal().await?)
+    }
+
+    /// drop every SST file whose keys all fall in the `range` with a single version edit instead
+    /// of rewriting them, records of partially overlapping files and memtables are deleted by a
+    /// range tombstone
+    pub async fn delete_files_in_range(
+        &self,
+        range: (Bound<&R::Key>, Bound<&R::Key>),
+    ) -> Result<(), CommitError<R>> {
+        // hold off compaction, otherwise it may write the data of dropped files back
+        let schema = self.schema.upgradable_read().await;
+        schema.check_writable()?;
+        let version = self.version_set.current().await;
+        let mut version_edits = Vec::new();
+        let mut delete_gens = Vec::new();
+        let mut is_overlapped = schema.is_unflushed(range);
+
+        for (level, scopes) in version.level_slice.iter().enumerate() {
+            for scope in scopes {
+                if scope.is_contained_in(range) {
+                    version_edits.push(VersionEdit::Remove {
+                        level: level as u8,
+                        gen: scope.gen,
+                    });
+                    delete_gens.push((scope.gen, level));
+                } else if scope.meets_range(range) {
+                    is_overlapped = true;
+                }
+            }
+        }
+        if is_overlapped {
+            schema
+                .delete_range(
+                    range.0.cloned(),
+                    range.1.cloned(),
+                    self.version_set.increase_ts(),
+                )
+                .await?;
+        }
+        if !version_edits.is_empty() {
+            let ts = self.version_set.increase_ts();
+            version_edits.push(VersionEdit::LatestTimeStamp { ts });
+            self.version_set
+                .apply_edits(version_edits, Some(delete_gens), false)
+                .await
+                .map_err(DbError::from)?;
+            if !is_overlapped {
+                schema.watchers.send(Change::DeleteRange {
+                    lower: range.0.cloned(),
+                    upper: range.1.cloned(),
+                    ts,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// merge the SSTs overlapping the `range` in the levels down to `target_level` into it,
+    /// along with the SSTs overlapping those in turn, to reclaim the space of the records removed
+    /// or overwritten in the range or to defragment it. The records still in the memtables are
+    /// left to the flushes. Fails with [`CompactionError::Reserved`] if a background compaction
+    /// merges some of the SSTs already, which a retry may get past
+    pub async fn compact_range(
+        &self,
+        range: (Bound<&R::Key>, Bound<&R::Key>),
+        target_level: usize,
+    ) -> Result<(), CompactionError<R>> {
+        let option = self.version_set.option();
+        Compactor::new(
+            self.schema.clone(),
+            option,
+            self.version_set.clone(),
+            self.manager.clone(),
+        )
+        .compact_range(range, target_level, self.parquet_lru.clone())
+        .await
     }
 
     pub async fn flush(&self) -> Result<(), CommitError<R>> {
         let (tx, rx) = oneshot::channel();
-        let compaction_tx = { self.schema.read().await.compaction_tx.clone() };
+        let compaction_tx = {
+            let schema = self.schema.read().await;
+            schema.check_writable()?;
+            schema.compaction_tx.clone()
+        };
         compaction_tx
             .send_async(CompactTask::Flush(Some(tx)))
             .await?;
@@ -367,21 +948,47 @@ where
 
     /// get the record with `key` as the primary key and process it using closure `f`
     pub async fn get<T>(
+        &self,
+        key: &R::Key,
+        f: impl FnMut(TransactionEntry<'_, R>) -> Option<T>,
+    ) -> Result<Option<T>, CommitError<R>> {
+        self.get_collecting(key, f, None).await
+    }
+
+    /// same as [`DB::get`], along with the [`QueryStats`] of the get
+    pub async fn get_with_stats<T>(
+        &self,
+        key: &R::Key,
+        f: impl FnMut(TransactionEntry<'_, R>) -> Option<T>,
+    ) -> Result<(Option<T>, QueryStats), CommitError<R>> {
+        let stats = QueryStatsCollector::new();
+        let value = self.get_collecting(key, f, Some(&stats)).await?;
+        stats.rows_returned(value.is_some() as usize);
+        Ok((value, stats.stats()))
+    }
+
+    async fn get_collecting<T>(
         &self,
         key: &R::Key,
         mut f: impl FnMut(TransactionEntry<'_, R>) -> Option<T>,
+        stats: Option<&QueryStatsCollector>,
     ) -> Result<Option<T>, CommitError<R>> {
-        Ok(self
+        let timer = Timer::start();
+        let version = self.version_set.current().await;
+        let threshold = version.option().slow_operation_threshold;
+        let start = threshold.map(|_| Instant::now());
+        let value = self
             .schema
             .read()
             .await
             .get(
-                &*self.version_set.current().await,
+                &version,
                 &self.manager,
                 key,
                 self.version_set.load_ts(),
                 Projection::All,
                 self.parquet_lru.clone(),
+                stats,
             )
             .await?
             .and_then(|entry| {
@@ -390,7 +997,107 @@ where
                 } else {
                     f(TransactionEntry::Stream(entry))
                 }
-            }))
+            });
+        timer.record(metrics::GET_SECONDS);
+        if let (Some(threshold), Some(start)) = (threshold, start) {
+            let elapsed = start.elapsed();
+            if elapsed > threshold {
+                warn!(
+                    ?key,
+                    ?elapsed,
+                    rows = value.is_some() as usize,
+                    tables = ?version.tables_in_range((Bound::Included(key), Bound::Included(key))),
+                    "slow get"
+                );
+            }
+        }
+
+        Ok(value)
+    }
+
+    /// whether a live record with the primary key exists. It returns on a hit in the mutable
+    /// memtable without copying the record, and otherwise reads only the primary keys of the
+    /// immutable memtables and SSTs, skipping the SSTs whose bloom filters rule the key out.
+    pub async fn contains_key(&self, key: &R::Key) -> Result<bool, DbError<R>> {
+        let version = self.version_set.current().await;
+        Ok(self
+            .schema
+            .read()
+            .await
+            .get(
+                &version,
+                &self.manager,
+                key,
+                self.version_set.load_ts(),
+                Projection::Parts(vec![]),
+                self.parquet_lru.clone(),
+                None,
+            )
+            .await?
+            .is_some_and(|entry| entry.value().is_some()))
+    }
+
+    /// every retained version of the record with the primary key `key` committed in the
+    /// `ts_range`, newest first, with what `f` returns for it or `None` for a deletion. Range
+    /// deletions appear as a deletion at their timestamp.
+    pub async fn get_history<T>(
+        &self,
+        key: &R::Key,
+        ts_range: (Bound<Timestamp>, Bound<Timestamp>),
+        mut f: impl FnMut(TransactionEntry<'_, R>) -> T,
+    ) -> Result<Vec<(Timestamp, Option<T>)>, DbError<R>> {
+        let schema = self.schema.read().await;
+        let version = self.version_set.current().await;
+        let ts = self.version_set.load_ts();
+        let range = (Bound::Included(key), Bound::Included(key));
+
+        let mut streams = vec![schema.mutable.scan(range, ts).into()];
+        for (_, immutable) in schema.immutables.iter().rev() {
+            streams.push(immutable.scan(range, ts, ProjectionMask::all()).into());
+        }
+        version
+            .streams(
+                &self.manager,
+                &mut streams,
+                range,
+                ts,
+                None,
+                ProjectionMask::all(),
+                self.parquet_lru.clone(),
+                &ScanLimits::default(),
+                None,
+            )
+            .await?;
+        // the versions hidden by range tombstones are kept, as they were readable before them
+        let mut stream = MergeStream::from_vec(streams, ts)
+            .await?
+            .keep_versions_after(EPOCH);
+
+        let mut history = Vec::new();
+        let mut oldest = None;
+        while let Some(entry) = stream.next().await.transpose()? {
+            let version_ts = entry.key().ts;
+            oldest = Some(version_ts);
+            if ts_range.contains(&version_ts) {
+                let value = entry
+                    .value()
+                    .is_some()
+                    .then(|| f(TransactionEntry::Stream(entry)));
+                history.push((version_ts, value));
+            }
+        }
+        for range_tombstone in schema.range_tombstones(&version) {
+            if range_tombstone.ts <= ts
+                && oldest.is_some_and(|oldest| oldest < range_tombstone.ts)
+                && ts_range.contains(&range_tombstone.ts)
+                && range_tombstone.contains(&key.as_key_ref())
+            {
+                history.push((range_tombstone.ts, None));
+            }
+        }
+        history.sort_by(|(ts, _), (other, _)| other.cmp(ts));
+
+        Ok(history)
     }
 
     /// scan records with primary keys in the `range` and process them using closure `f`
@@ -408,7 +1115,7 @@ where
                 range,
                 self.version_set.load_ts(),
                 &*current,
-                Box::new(|_| None),
+                None,
                 self.parquet_lru.clone(),
             ).take().await?;
 
@@ -418,48 +1125,178 @@ where
         }
     }
 
-    pub(crate) async fn write(&self, record: R, ts: Timestamp) -> Result<(), DbError<R>> {
-        let schema = self.schema.read().await;
+    /// stream the primary keys of the live records in the `range` with the timestamps of their
+    /// latest versions, reading only the key columns of the SSTs
+    pub async fn keys<'scan>(
+        &'scan self,
+        range: (Bound<&'scan R::Key>, Bound<&'scan R::Key>),
+    ) -> impl Stream<Item = Result<(R::Key, Timestamp), CommitError<R>>> + 'scan {
+        stream! {
+            let schema = self.schema.read().await;
+            let current = self.version_set.current().await;
+            let mut scan = Scan::new(
+                &schema,
+                &self.manager,
+                range,
+                self.version_set.load_ts(),
+                &*current,
+                None,
+                self.parquet_lru.clone(),
+            )
+            .projection(vec![])
+            .take()
+            .await?;
 
-        if schema.write(LogType::Full, record, ts).await? {
-            let _ = schema.compaction_tx.try_send(CompactTask::Freeze);
+            while let Some(entry) = scan.next().await {
+                let entry = entry?;
+                if entry.value().is_some() {
+                    let key = entry.key();
+                    yield Ok((key.value.to_key(), key.ts));
+                }
+            }
         }
-
-        Ok(())
     }
 
-    pub(crate) async fn write_batch(
-        &self,
-        mut records: impl ExactSizeIterator<Item = R>,
-        ts: Timestamp,
-    ) -> Result<(), DbError<R>> {
-        let schema = self.schema.read().await;
-
-        if let Some(first) = records.next() {
-            let is_excess = if let Some(record) = records.next() {
-                schema.write(LogType::First, first, ts).await?;
-
-                let mut last_buf = record;
+    /// wait out the rate limits for writing `records` records of `bytes` bytes, before taking
+    /// the memtables so that a throttled writer holds back none of the others. The bytes are only
+    /// sized with [`DbOption::max_write_bytes_per_sec`]
+    async fn throttle(&self, records: usize, bytes: impl FnOnce() -> usize) {
+        let bytes = if self.rate_limiter.limits_bytes() {
+            bytes()
+        } else {
+            0
+        };
+        self.rate_limiter.acquire(records, bytes).await;
+    }
 
-                for record in records {
-                    schema
-                        .write(LogType::Middle, mem::replace(&mut last_buf, record), ts)
-                        .await?;
+    /// the memtables to write to, waiting for the flush or compaction holding them if any, and
+    /// for the flushes and compactions to catch up past the write stall triggers
+    async fn schema_for_write(&self) -> RwLockReadGuard<'_, Schema<R>> {
+        let timer = Timer::start();
+        let option = self.version_set.option();
+        let mut stalled = false;
+        let on_stall = |stalled: &mut bool| {
+            if let (false, Some(listener)) = (mem::replace(stalled, true), &option.event_listener) {
+                listener.on_stall();
+            }
+        };
+        let (mut slowed, mut stopped) = (false, false);
+        let schema = loop {
+            let schema = match self.schema.try_read() {
+                Some(schema) => schema,
+                None => {
+                    on_stall(&mut stalled);
+                    self.schema.read().await
                 }
-                schema.write(LogType::Last, last_buf, ts).await?
-            } else {
-                schema.write(LogType::Full, first, ts).await?
             };
-            if is_excess {
-                let _ = schema.compaction_tx.try_send(CompactTask::Freeze);
+            let immutables = schema.immutables.len();
+            let level0_tables = self.version_set.current().await.level_slice[0].len();
+            match option.write_stall(immutables, level0_tables) {
+                WriteStall::Stop => {
+                    if immutables > option.immutable_chunk_max_num {
+                        // the freeze flushing them may have been dropped by a full channel
+                        let _ = schema.compaction_tx.try_send(CompactTask::Freeze);
+                    }
+                    if !mem::replace(&mut stopped, true) {
+                        metrics::increment(metrics::WRITE_STOPS, 1);
+                    }
+                }
+                WriteStall::Slowdown if !slowed => {
+                    slowed = true;
+                    metrics::increment(metrics::WRITE_SLOWDOWNS, 1);
+                }
+                _ => break schema,
             }
+            let clock = schema.clock.clone();
+            drop(schema);
+            on_stall(&mut stalled);
+            clock.sleep(option.write_slowdown_delay).await;
         };
-
-        Ok(())
+        timer.record(metrics::WRITE_STALL_SECONDS);
+        schema
     }
 
-    pub async fn flush_wal(&self) -> Result<(), DbError<R>> {
-        self.schema.write().await.flush_wal().await?;
+    pub(crate) async fn write(&self, record: R, ts: Timestamp) -> Result<(), DbError<R>> {
+        check_write(&self.version_set.option(), record.key(), Some(&record))?;
+        self.throttle(1, || R::size(&record)).await;
+        let schema = self.schema_for_write().await;
+        metrics::increment(metrics::WRITE_RECORDS, 1);
+
+        let is_excess = schema.write(LogType::Full, record, ts).await?;
+        schema.commit_wal().await?;
+        if is_excess {
+            let _ = schema.compaction_tx.try_send(CompactTask::Freeze);
+        }
+
+        Ok(())
+    }
+
+    pub(crate) async fn write_batch(
+        &self,
+        records: impl ExactSizeIterator<Item = R>,
+        ts: Timestamp,
+    ) -> Result<(), DbError<R>> {
+        let records = records.collect::<Vec<_>>();
+        let option = self.version_set.option();
+        for record in &records {
+            check_write(&option, record.key(), Some(record))?;
+        }
+        self.throttle(records.len(), || records.iter().map(R::size).sum())
+            .await;
+        let schema = self.schema_for_write().await;
+        metrics::increment(metrics::WRITE_RECORDS, records.len() as u64);
+
+        if !records.is_empty() {
+            let is_excess = schema.write_batch(records, ts).await?;
+            schema.commit_wal().await?;
+            if is_excess {
+                let _ = schema.compaction_tx.try_send(CompactTask::Freeze);
+            }
+        };
+
+        Ok(())
+    }
+
+    pub async fn flush_wal(&self) -> Result<(), DbError<R>> {
+        self.schema.write().await.flush_wal().await?;
+        Ok(())
+    }
+
+    /// accept writes again after a failed WAL write or background flush stopped them with
+    /// [`DbError::Poisoned`], e.g. once space is freed on a full disk. The WAL continues in a new
+    /// segment, and the incomplete entry the failed writes may have left at the end of the
+    /// current one is cut off. Fails, and stays poisoned, if the WAL still cannot be written.
+    pub async fn resume(&self) -> Result<(), DbError<R>> {
+        let schema = self.schema.write().await;
+        if schema.poisoned.lock().unwrap().is_none() {
+            return Ok(());
+        }
+        schema.mutable.new_wal_segment().await?;
+        schema.mutable.flush_wal().await?;
+        *schema.poisoned.lock().unwrap() = None;
+
+        Ok(())
+    }
+
+    /// pick up the flushes and compactions done by the writer since a read-only [`DB`] was opened
+    /// or last caught up, by reloading the version log, and the records in the WALs when opened
+    /// with `tail_wal`. Reads wait until it completes. Does nothing for a [`DB`] opened for
+    /// writing.
+    pub async fn try_catch_up(&self) -> Result<(), DbError<R>> {
+        let mut schema = self.schema.write().await;
+        let OpenMode::ReadOnly { tail_wal } = schema.mode else {
+            return Ok(());
+        };
+        self.version_set.catch_up().await?;
+
+        if tail_wal {
+            let option = self.version_set.current().await.option().clone();
+            let base_fs = self.manager.base_fs();
+            schema.mutable = Mutable::new(&option, schema.trigger.clone(), base_fs).await?;
+            schema
+                .replay_live_wals(&option, &self.version_set, &self.manager)
+                .await?;
+        }
         Ok(())
     }
 }
@@ -469,11 +1306,38 @@ where
     R: Record,
 {
     pub mutable: Mutable<R>,
-    pub immutables: Vec<(Option<FileId>, Immutable<R::Columns>)>,
+    pub immutables: Vec<(Vec<FileId>, Immutable<R::Columns>)>,
     compaction_tx: Sender<CompactTask>,
     recover_wal_ids: Option<Vec<FileId>>,
     trigger: Arc<Box<dyn Trigger<R> + Send + Sync>>,
-    record_instance: RecordInstance,
+    record_instance: Arc<RecordInstance>,
+    watchers: Watchers<R>,
+    event_listener: Option<Arc<dyn EventListener>>,
+    snapshots: ActiveSnapshots,
+    statistics: Arc<Statistics>,
+    mode: OpenMode,
+    /// why writes stopped being accepted, see [`DB::resume`]
+    poisoned: std::sync::Mutex<Option<String>>,
+    /// the memory of the memtables accounted in [`DbOption::write_buffer_manager`]
+    write_buffer: Option<WriteBuffer>,
+    max_total_wal_size: Option<usize>,
+    /// the timer of the executor the database is opened with
+    clock: Clock,
+}
+
+/// WALs decoded ahead of the one being replayed by the recovery
+const WAL_RECOVER_CONCURRENCY: usize = 4;
+
+struct DecodedWal<R>
+where
+    R: Record,
+{
+    wal_id: FileId,
+    path: Path,
+    size: u64,
+    entries: Vec<WalEntry<R>>,
+    /// set if an incomplete or corrupted entry ends the WAL, to whether nothing follows it
+    corrupted: Option<bool>,
 }
 
 impl<R> Schema<R>
@@ -486,25 +1350,44 @@ where
         version_set: &VersionSet<R>,
         record_instance: RecordInstance,
         manager: &StoreManager,
+        started: Option<Instant>,
+        clock: Clock,
     ) -> Result<Self, DbError<R>> {
         let trigger = Arc::new(TriggerFactory::create(option.trigger_type));
         let mut schema = Schema {
-            mutable: Mutable::new(&option, trigger.clone(), manager.base_fs()).await?,
+            mutable: Mutable::new(
+                &option,
+                trigger.clone(),
+                manager.get_fs(&option.wal_dir_path()),
+            )
+            .await?,
             immutables: Default::default(),
-            compaction_tx,
+            compaction_tx: compaction_tx.clone(),
             recover_wal_ids: None,
             trigger,
-            record_instance,
+            record_instance: Arc::new(record_instance),
+            watchers: Default::default(),
+            event_listener: option.event_listener.clone(),
+            snapshots: Default::default(),
+            statistics: version_set.statistics().clone(),
+            mode: OpenMode::ReadWrite,
+            poisoned: Default::default(),
+            write_buffer: option
+                .write_buffer_manager
+                .as_ref()
+                .map(|manager| manager.register(compaction_tx.clone())),
+            max_total_wal_size: option.max_total_wal_size,
+            clock,
         };
 
-        let base_fs = manager.base_fs();
         let wal_dir_path = option.wal_dir_path();
+        let wal_fs = manager.get_fs(&wal_dir_path);
         let mut transaction_map = HashMap::new();
         let mut wal_ids = Vec::new();
 
         let wal_metas = {
             let mut wal_metas = Vec::new();
-            let mut wal_stream = base_fs.list(&wal_dir_path).await?;
+            let mut wal_stream = wal_fs.list(&wal_dir_path).await?;
 
             while let Some(file_meta) = wal_stream.next().await {
                 wal_metas.push(file_meta?);
@@ -512,63 +1395,304 @@ where
             wal_metas.sort_by(|meta_a, meta_b| meta_a.path.cmp(&meta_b.path));
             wal_metas
         };
+        // the newest WAL written, the empty one of the new mutable memtable is listed as well
+        let last_wal_path = wal_metas
+            .iter()
+            .rev()
+            .find(|wal_meta| wal_meta.size > 0)
+            .map(|wal_meta| wal_meta.path.clone());
+        let mut progress = RecoveryProgress {
+            wals_replayed: 0,
+            wals_total: wal_metas.len(),
+            bytes_replayed: 0,
+            bytes_total: wal_metas.iter().map(|wal_meta| wal_meta.size).sum(),
+            elapsed: Duration::ZERO,
+        };
+        let report = |progress: &mut RecoveryProgress| {
+            if let (Some(listener), Some(started)) = (&option.event_listener, started) {
+                progress.elapsed = started.elapsed();
+                listener.on_recovery_progress(progress);
+            }
+        };
+        report(&mut progress);
+
+        // the WALs are decoded ahead concurrently and replayed in order
+        let mut decoded_wals = pin!(futures_util::stream::iter(wal_metas)
+            .map(|wal_meta| Self::decode_wal(wal_fs, wal_meta.path))
+            .buffered(WAL_RECOVER_CONCURRENCY));
+
+        while let Some(decoded_wal) = decoded_wals.next().await {
+            let DecodedWal {
+                wal_id,
+                path,
+                size,
+                entries,
+                corrupted,
+            } = decoded_wal?;
+            wal_ids.push(wal_id);
+
+            for entry in entries {
+                schema
+                    .replay_entry(entry, version_set, &mut transaction_map)
+                    .await?;
+            }
+            if let Some(is_at_end) = corrupted {
+                // a crash may leave the last entry of the newest WAL partially written
+                let is_tail = Some(&path) == last_wal_path.as_ref() && is_at_end;
+                if option.wal_recovery_mode != WalRecoveryMode::SkipCorrupted && !is_tail {
+                    return Err(DbError::Recover(RecoverError::Checksum));
+                }
+                warn!(wal = %wal_id, "skipped the rest of the WAL from a corrupted entry");
+            }
+            progress.wals_replayed += 1;
+            progress.bytes_replayed += size;
+            report(&mut progress);
+        }
+        schema.recover_wal_ids = Some(wal_ids);
+
+        Ok(schema)
+    }
+
+    /// read the entries of the WAL at `path`, up to the first incomplete or corrupted one
+    #[instrument(level = "debug", skip(fs))]
+    async fn decode_wal(fs: &Arc<dyn DynFs>, path: Path) -> Result<DecodedWal<R>, DbError<R>> {
+        let file = fs::read_file(fs, &path).await?;
+        let size = file.len() as u64;
+        // SAFETY: wal_stream return only file name
+        let wal_id = parse_file_id(&path, FileType::Wal)?.unwrap();
+        let mut wal = WalFile::new(BytesReader::new(file), wal_id);
+        let mut entries = Vec::new();
+        let mut is_corrupted = false;
+
+        let mut recover_stream = pin!(wal.recover_buffered());
+        while let Some(entry) = recover_stream.next().await {
+            match entry {
+                Ok(entry) => entries.push(entry),
+                Err(RecoverError::Checksum) => is_corrupted = true,
+                Err(err) => return Err(err.into()),
+            }
+        }
+        let corrupted = if is_corrupted || wal.is_torn() {
+            Some(wal.is_at_end().await)
+        } else {
+            None
+        };
 
-        for wal_meta in wal_metas {
-            let wal_path = wal_meta.path;
+        Ok(DecodedWal {
+            wal_id,
+            path,
+            size,
+            entries,
+            corrupted,
+        })
+    }
 
-            let file = base_fs
-                .open_options(&wal_path, FileType::Wal.open_options(false))
+    /// replay the entries of a WAL into the mutable memtable at the timestamps they were written
+    /// with, the records written as a batch are replayed once its last entry is read
+    #[instrument(level = "debug", skip_all, fields(wal = %wal.file_id()))]
+    async fn replay_wal<F>(
+        &self,
+        wal: &mut WalFile<F, R>,
+        version_set: &VersionSet<R>,
+        transaction_map: &mut HashMap<Timestamp, Vec<(R::Key, Option<R>)>>,
+    ) -> Result<(), DbError<R>>
+    where
+        F: SeqRead,
+    {
+        let mut recover_stream = pin!(wal.recover());
+        while let Some(entry) = recover_stream.next().await {
+            self.replay_entry(entry?, version_set, transaction_map)
                 .await?;
-            // SAFETY: wal_stream return only file name
-            let wal_id = parse_file_id(&wal_path, FileType::Wal)?.unwrap();
-            let mut wal = WalFile::new(Cursor::new(file), wal_id);
-            wal_ids.push(wal_id);
+        }
+        Ok(())
+    }
 
-            let mut recover_stream = pin!(wal.recover());
-            while let Some(record) = recover_stream.next().await {
-                let (log_type, Timestamped { ts, value: key }, value_option) = record?;
+    async fn replay_entry(
+        &self,
+        entry: WalEntry<R>,
+        version_set: &VersionSet<R>,
+        transaction_map: &mut HashMap<Timestamp, Vec<(R::Key, Option<R>)>>,
+    ) -> Result<(), DbError<R>> {
+        let (log_type, Timestamped { ts, value: key }, value_option) = match entry {
+            WalEntry::Record {
+                log_type,
+                key,
+                value,
+            } => (log_type, key, value),
+            WalEntry::RangeTombstone(RangeTombstone { lower, upper, ts }) => {
+                version_set.advance_ts(ts);
+                return self.mutable.delete_range(lower, upper, ts, true).await;
+            }
+        };
 
-                let is_excess = match log_type {
-                    LogType::Full => {
-                        schema
-                            .recover_append(key, version_set.increase_ts(), value_option)
-                            .await?
-                    }
-                    LogType::First => {
-                        transaction_map.insert(ts, vec![(key, value_option)]);
-                        false
-                    }
-                    LogType::Middle => {
-                        transaction_map
-                            .get_mut(&ts)
-                            .unwrap()
-                            .push((key, value_option));
-                        false
-                    }
-                    LogType::Last => {
-                        let mut is_excess = false;
-                        let mut records = transaction_map.remove(&ts).unwrap();
-                        records.push((key, value_option));
-
-                        let ts = version_set.increase_ts();
-                        for (key, value_option) in records {
-                            is_excess = schema.recover_append(key, ts, value_option).await?;
-                        }
-                        is_excess
-                    }
+        // the timestamps may have been supplied by the application, see `Transaction::commit_at`
+        let is_excess = match log_type {
+            LogType::Full => {
+                version_set.advance_ts(ts);
+                self.recover_append(key, ts, value_option).await?
+            }
+            LogType::First => {
+                transaction_map.insert(ts, vec![(key, value_option)]);
+                false
+            }
+            LogType::Middle => {
+                match transaction_map.get_mut(&ts) {
+                    Some(records) => records.push((key, value_option)),
+                    None => warn!(
+                        ?ts,
+                        "skipped an entry of a transaction whose first is missing"
+                    ),
+                }
+                false
+            }
+            LogType::Last => {
+                let Some(mut records) = transaction_map.remove(&ts) else {
+                    warn!(
+                        ?ts,
+                        "skipped the last entry of a transaction whose first is missing"
+                    );
+                    return Ok(());
                 };
-                if is_excess {
-                    let _ = schema.compaction_tx.try_send(CompactTask::Freeze);
+                let mut is_excess = false;
+                records.push((key, value_option));
+
+                version_set.advance_ts(ts);
+                for (key, value_option) in records {
+                    is_excess = self.recover_append(key, ts, value_option).await?;
                 }
+                is_excess
             }
+            LogType::RangeDelete => unreachable!(),
+        };
+        if is_excess {
+            let _ = self.compaction_tx.try_send(CompactTask::Freeze);
         }
-        schema.recover_wal_ids = Some(wal_ids);
+        Ok(())
+    }
 
-        Ok(schema)
+    /// replay the WALs of a database written by another process, the one being written may end
+    /// with a partially written entry
+    async fn replay_live_wals(
+        &self,
+        option: &DbOption<R>,
+        version_set: &VersionSet<R>,
+        manager: &StoreManager,
+    ) -> Result<(), DbError<R>> {
+        let wal_fs = manager.get_fs(&option.wal_dir_path());
+        let mut wal_ids = Vec::new();
+        for path in fs::list_files(wal_fs, &option.wal_dir_path(), FileType::Wal).await? {
+            wal_ids.extend(parse_file_id(&path, FileType::Wal)?);
+        }
+        wal_ids.sort();
+        let mut transaction_map = HashMap::new();
+
+        for (i, wal_id) in wal_ids.iter().enumerate() {
+            // flushed WALs are removed by the writer
+            let Ok(file) = wal_fs
+                .open_options(&option.wal_path(*wal_id), OpenOptions::default().read(true))
+                .await
+            else {
+                continue;
+            };
+            let mut wal = WalFile::new(Cursor::new(file), *wal_id);
+            let result = self
+                .replay_wal(&mut wal, version_set, &mut transaction_map)
+                .await;
+            if i + 1 < wal_ids.len() {
+                result?;
+            }
+        }
+        Ok(())
+    }
+
+    /// memory held by the arrow arrays of the immutables
+    fn immutables_size(&self) -> usize {
+        self.immutables
+            .iter()
+            .map(|(_, immutable)| immutable.as_record_batch().get_array_memory_size())
+            .sum()
+    }
+
+    /// whether the WALs of the memtables not flushed yet outgrew
+    /// [`DbOption::max_total_wal_size`]
+    fn is_wal_exceeded(&self) -> bool {
+        self.max_total_wal_size.is_some_and(|max_total_wal_size| {
+            let wal_size = self.mutable.wal_size()
+                + self
+                    .immutables
+                    .iter()
+                    .map(|(_, immutable)| immutable.wal_size)
+                    .sum::<usize>();
+            wal_size > max_total_wal_size
+        })
+    }
+
+    fn check_writable(&self) -> Result<(), DbError<R>> {
+        if self.mode != OpenMode::ReadWrite {
+            return Err(DbError::ReadOnly);
+        }
+        if let Some(reason) = self.poisoned.lock().unwrap().as_ref() {
+            return Err(DbError::Poisoned(reason.clone()));
+        }
+        Ok(())
+    }
+
+    /// stop accepting writes until [`DB::resume`], as the ones following a failed WAL write
+    /// might not be recovered and the ones following a failed flush would pile up in memory
+    fn poison(&self, reason: &dyn std::fmt::Display) {
+        error!("stopped accepting writes: {}", reason);
+        self.poisoned
+            .lock()
+            .unwrap()
+            .get_or_insert_with(|| reason.to_string());
+    }
+
+    /// poison the database if writing the WAL of the mutable memtable failed
+    fn check_wal<T>(&self, result: Result<T, DbError<R>>) -> Result<T, DbError<R>> {
+        if let Err(err) = &result {
+            self.poison(err);
+        }
+        result
     }
 
     async fn write(&self, log_ty: LogType, record: R, ts: Timestamp) -> Result<bool, DbError<R>> {
-        self.mutable.insert(log_ty, record, ts).await
+        self.check_writable()?;
+        let change = self.watchers.put(&record, ts).await?;
+        metrics::memtable_grown(R::size(&record));
+        self.statistics
+            .user_bytes_written
+            .fetch_add(R::size(&record) as u64, Ordering::Relaxed);
+        let is_excess = self.check_wal(self.mutable.insert(log_ty, record, ts).await)?;
+        if let Some(write_buffer) = &self.write_buffer {
+            write_buffer.set_mutable(self.mutable.size());
+        }
+        if let Some(change) = change {
+            self.watchers.send(change);
+        }
+        Ok(is_excess || self.is_wal_exceeded())
+    }
+
+    /// write `records` as a single batch, see [`Mutable::insert_batch`]
+    async fn write_batch(&self, records: Vec<R>, ts: Timestamp) -> Result<bool, DbError<R>> {
+        self.check_writable()?;
+        let mut changes = Vec::new();
+        let mut size = 0;
+        for record in &records {
+            changes.extend(self.watchers.put(record, ts).await?);
+            size += R::size(record);
+        }
+        metrics::memtable_grown(size);
+        self.statistics
+            .user_bytes_written
+            .fetch_add(size as u64, Ordering::Relaxed);
+        let is_excess = self.check_wal(self.mutable.insert_batch(records, ts).await)?;
+        if let Some(write_buffer) = &self.write_buffer {
+            write_buffer.set_mutable(self.mutable.size());
+        }
+        for change in changes {
+            self.watchers.send(change);
+        }
+        Ok(is_excess || self.is_wal_exceeded())
     }
 
     async fn remove(
@@ -577,7 +1701,35 @@ where
         key: R::Key,
         ts: Timestamp,
     ) -> Result<bool, DbError<R>> {
-        self.mutable.remove(log_ty, key, ts).await
+        self.check_writable()?;
+        let change = self.watchers.is_watched(&key).then(|| Change::Delete {
+            key: key.clone(),
+            ts,
+        });
+        let is_excess = self.check_wal(self.mutable.remove(log_ty, key, ts).await)?;
+        if let Some(write_buffer) = &self.write_buffer {
+            write_buffer.set_mutable(self.mutable.size());
+        }
+        if let Some(change) = change {
+            self.watchers.send(change);
+        }
+        Ok(is_excess || self.is_wal_exceeded())
+    }
+
+    async fn delete_range(
+        &self,
+        lower: Bound<R::Key>,
+        upper: Bound<R::Key>,
+        ts: Timestamp,
+    ) -> Result<(), DbError<R>> {
+        self.check_writable()?;
+        self.check_wal(
+            self.mutable
+                .delete_range(lower.clone(), upper.clone(), ts, false)
+                .await,
+        )?;
+        self.watchers.send(Change::DeleteRange { lower, upper, ts });
+        Ok(())
     }
 
     async fn recover_append(
@@ -597,6 +1749,7 @@ where
         ts: Timestamp,
         projection: Projection,
         parquet_lru: ParquetLru,
+        stats: Option<&QueryStatsCollector>,
     ) -> Result<Option<Entry<'get, R>>, DbError<R>> {
         let primary_key_index = self.record_instance.primary_key_index::<R>();
 
@@ -616,28 +1769,82 @@ where
             }
         };
 
-        if let Some(entry) = self.mutable.get(key, ts) {
-            return Ok(Some(Entry::Projection((
+        let start = stats.map(|_| Instant::now());
+        let entry = match self.mutable.get(key, ts) {
+            Some(entry) => Some(Entry::Projection((
                 Box::new(Entry::Mutable(entry)),
-                Arc::new(projection),
-            ))));
+                Arc::new(projection.clone()),
+            ))),
+            None => self
+                .immutables
+                .iter()
+                .rev()
+                .find_map(|(_, immutable)| immutable.get(key, ts, projection.clone()))
+                .map(|entry| Entry::RecordBatch(entry)),
+        };
+        if let (Some(stats), Some(start)) = (stats, start) {
+            stats.opened(start.elapsed());
         }
-
-        for (_, immutable) in self.immutables.iter().rev() {
-            if let Some(entry) = immutable.get(key, ts, projection.clone()) {
-                return Ok(Some(Entry::RecordBatch(entry)));
+        let entry = match entry {
+            Some(entry) => Some(entry),
+            None => {
+                let start = stats.map(|_| Instant::now());
+                let entry = version
+                    .query(
+                        manager,
+                        TimestampedRef::new(key, ts),
+                        projection,
+                        parquet_lru,
+                        stats,
+                    )
+                    .await?
+                    .map(|entry| Entry::RecordBatch(entry));
+                if let (Some(stats), Some(start)) = (stats, start) {
+                    stats.read(start.elapsed());
+                }
+                entry
             }
+        };
+        if let Some(stats) = stats {
+            stats.rows_merged(entry.is_some() as u64);
         }
 
-        Ok(version
-            .query(
-                manager,
-                TimestampedRef::new(key, ts),
-                projection,
-                parquet_lru,
+        Ok(entry.filter(|entry| !self.is_range_deleted(version, &entry.key(), ts)))
+    }
+
+    fn range_tombstones(&self, version: &Version<R>) -> Vec<RangeTombstone<R::Key>> {
+        self.mutable
+            .range_tombstones
+            .iter()
+            .map(|entry| entry.value().clone())
+            .chain(
+                self.immutables
+                    .iter()
+                    .flat_map(|(_, immutable)| immutable.range_tombstones.iter().cloned()),
             )
-            .await?
-            .map(|entry| Entry::RecordBatch(entry)))
+            .chain(version.range_tombstones.iter().cloned())
+            .collect()
+    }
+
+    fn is_range_deleted(
+        &self,
+        version: &Version<R>,
+        key: &Timestamped<<R::Key as Key>::Ref<'_>>,
+        ts: Timestamp,
+    ) -> bool {
+        let is_covered = |range_tombstone: &RangeTombstone<R::Key>| {
+            range_tombstone.ts <= ts && range_tombstone.covers(key)
+        };
+
+        self.mutable
+            .range_tombstones
+            .iter()
+            .any(|entry| is_covered(entry.value()))
+            || self
+                .immutables
+                .iter()
+                .any(|(_, immutable)| immutable.range_tombstones.iter().any(is_covered))
+            || version.range_tombstones.covers(key, ts)
     }
 
     fn check_conflict(&self, key: &R::Key, ts: Timestamp) -> bool {
@@ -649,10 +1856,163 @@ where
                 .any(|(_, immutable)| immutable.check_conflict(key, ts))
     }
 
+    /// whether a record with its key in `range` was written or range deleted after `ts`
+    fn check_range_conflict(&self, range: (Bound<&R::Key>, Bound<&R::Key>), ts: Timestamp) -> bool {
+        let is_conflict = |range_tombstone: &RangeTombstone<R::Key>| {
+            range_tombstone.ts > ts && range_tombstone.meets_range(range)
+        };
+
+        self.mutable.check_range_conflict(range, ts)
+            || self
+                .mutable
+                .range_tombstones
+                .iter()
+                .any(|entry| is_conflict(entry.value()))
+            || self.immutables.iter().rev().any(|(_, immutable)| {
+                immutable.check_range_conflict(range, ts)
+                    || immutable.range_tombstones.iter().any(is_conflict)
+            })
+    }
+
     async fn flush_wal(&self) -> Result<(), DbError<R>> {
-        self.mutable.flush_wal().await?;
+        let timer = Timer::start();
+        self.check_wal(self.mutable.flush_wal().await)?;
+        timer.record(metrics::WAL_SYNC_SECONDS);
+        if let Some(listener) = &self.event_listener {
+            listener.on_wal_sync();
+        }
+        Ok(())
+    }
+
+    /// wait for the WAL entries of a write to be flushed with [`WalSyncPolicy::PerCommit`]
+    async fn commit_wal(&self) -> Result<(), DbError<R>> {
+        if !self.mutable.sync_on_commit() {
+            return Ok(());
+        }
+        self.sync_wal().await
+    }
+
+    /// flush the WAL entries not flushed yet
+    async fn sync_wal(&self) -> Result<(), DbError<R>> {
+        let timer = Timer::start();
+        if self.check_wal(self.mutable.sync_wal().await)? {
+            timer.record(metrics::WAL_SYNC_SECONDS);
+            if let Some(listener) = &self.event_listener {
+                listener.on_wal_sync();
+            }
+        }
+        Ok(())
+    }
+
+    /// move the mutable memtable into the immutables and start a new one with a new WAL
+    async fn freeze(
+        &mut self,
+        option: &DbOption<R>,
+        fs: &Arc<dyn DynFs>,
+    ) -> Result<(), fusio::Error> {
+        let mut mutable = Mutable::new(option, self.trigger.clone(), fs).await?;
+        mutable.tailers = self.mutable.tailers.clone();
+        let mutable = mem::replace(&mut self.mutable, mutable);
+        let (wal_ids, immutable) = mutable.into_immutable(&self.record_instance).await?;
+        self.immutables.push((wal_ids, immutable));
+        metrics::memtable_frozen();
+        self.account_memory();
+
         Ok(())
     }
+
+    /// update the memory of the memtables in the [`WriteBufferManager`] shared with other
+    /// databases, if any
+    fn account_memory(&self) {
+        if let Some(write_buffer) = &self.write_buffer {
+            write_buffer.set_mutable(self.mutable.size());
+            write_buffer.set_immutables(self.immutables_size());
+        }
+    }
+
+    /// prepend the `_null` and `_ts` columns to a `batch` holding the columns of the record
+    fn with_internal_columns(
+        &self,
+        batch: RecordBatch,
+        ts: Timestamp,
+    ) -> Result<RecordBatch, DbError<R>> {
+        let arrow_schema = self.record_instance.arrow_schema::<R>();
+
+        if !is_fields_matched(batch.schema().fields(), &arrow_schema.fields()[2..]) {
+            return Err(DbError::Ingest(
+                "schema of the record batch does not match the record".to_string(),
+            ));
+        }
+        let row_count = batch.num_rows();
+        let mut columns: Vec<ArrayRef> = vec![
+            Arc::new(BooleanArray::from(vec![false; row_count])),
+            Arc::new(UInt64Array::from(vec![u64::from(ts); row_count])),
+        ];
+        columns.extend(batch.columns().iter().cloned());
+
+        Ok(RecordBatch::try_new(arrow_schema, columns)?)
+    }
+
+    /// whether the memtables hold any record in the `range`
+    fn is_unflushed(&self, range: (Bound<&R::Key>, Bound<&R::Key>)) -> bool {
+        self.mutable.scan(range, u64::MAX.into()).next().is_some()
+            || self.immutables.iter().any(|(_, immutable)| {
+                immutable
+                    .scan(range, u64::MAX.into(), ProjectionMask::all())
+                    .next()
+                    .is_some()
+            })
+    }
+}
+
+/// whether `fields` have the names and data types of the `expected` fields
+fn is_fields_matched(fields: &[FieldRef], expected: &[FieldRef]) -> bool {
+    fields.len() == expected.len()
+        && fields.iter().zip(expected).all(|(field, expected)| {
+            field.name() == expected.name() && field.data_type() == expected.data_type()
+        })
+}
+
+/// write `columns` as a new SST of the `level` and return its id and statistics
+pub(crate) async fn write_table<R>(
+    option: &DbOption<R>,
+    manager: &StoreManager,
+    level: usize,
+    arrow_schema: &Arc<arrow::datatypes::Schema>,
+    columns: &R::Columns,
+) -> Result<(FileId, TableStats), DbError<R>>
+where
+    R: Record,
+{
+    let gen = FileId::new();
+    let path = option.table_path(gen, level);
+    let level_fs = manager.get_fs(option.level_fs_path(level).unwrap_or(&option.base_path));
+    let preallocation = option.level_preallocation(
+        level,
+        columns.as_record_batch().get_array_memory_size() as u64,
+    );
+    let mut writer = AsyncArrowWriter::try_new(
+        AsyncWriter::new(
+            fs::create_table(
+                level_fs,
+                &path,
+                option.level_direct_io(level),
+                preallocation,
+            )
+            .await?,
+        ),
+        arrow_schema.clone(),
+        Some(option.sst_properties(level)),
+    )?;
+    writer.write(columns.as_record_batch()).await?;
+    metrics::sst_written(level, writer.bytes_written() + writer.in_progress_size());
+    let metadata = writer.close().await?;
+    if preallocation.is_some() {
+        fs::truncate_table(&path)?;
+    }
+    let tombstones = TableStats::tombstones(columns.as_record_batch());
+
+    Ok((gen, TableStats::new(arrow_schema, &metadata, tombstones)?))
 }
 
 /// scan configuration intermediate structure
@@ -669,11 +2029,14 @@ where
 
     version: &'scan Version<R>,
     fn_pre_stream:
-        Box<dyn FnOnce(Option<ProjectionMask>) -> Option<ScanStream<'scan, R>> + Send + 'scan>,
+        Option<Box<dyn FnOnce(Option<ProjectionMask>) -> ScanStream<'scan, R> + Send + 'scan>>,
 
     limit: Option<usize>,
     projection_indices: Option<Vec<usize>>,
     projection: ProjectionMask,
+    limits: ScanLimits,
+    cancel: ScanCancel,
+    stats: Option<QueryStatsCollector>,
 
     parquet_lru: ParquetLru,
 }
@@ -688,8 +2051,8 @@ where
         (lower, upper): (Bound<&'range R::Key>, Bound<&'range R::Key>),
         ts: Timestamp,
         version: &'scan Version<R>,
-        fn_pre_stream: Box<
-            dyn FnOnce(Option<ProjectionMask>) -> Option<ScanStream<'scan, R>> + Send + 'scan,
+        fn_pre_stream: Option<
+            Box<dyn FnOnce(Option<ProjectionMask>) -> ScanStream<'scan, R> + Send + 'scan>,
         >,
         parquet_lru: ParquetLru,
     ) -> Self {
@@ -704,6 +2067,9 @@ where
             limit: None,
             projection_indices: None,
             projection: ProjectionMask::all(),
+            limits: ScanLimits::default(),
+            cancel: ScanCancel::default(),
+            stats: None,
             parquet_lru,
         }
     }
@@ -716,6 +2082,72 @@ where
         }
     }
 
+    /// open at most `max_open_tables` SSTs at once, e.g. for a range spanning hundreds of tables
+    /// of level 0 on an object storage. The tables of level 0 which do not overlap each other are
+    /// then read one after another, so the scan still opens at least a table per level below the
+    /// first and as many as the most tables of level 0 overlapping a key
+    pub fn max_open_tables(self, max_open_tables: usize) -> Self {
+        Self {
+            limits: ScanLimits {
+                max_open_tables: Some(max_open_tables),
+                ..self.limits
+            },
+            ..self
+        }
+    }
+
+    /// keep at most `max_inflight_reads` reads of the SSTs in flight at once, shared by the
+    /// tables of the scan and their read ahead
+    pub fn max_inflight_reads(self, max_inflight_reads: usize) -> Self {
+        Self {
+            limits: ScanLimits {
+                read_permits: Some(Arc::new(Semaphore::new(max_inflight_reads.max(1)))),
+                ..self.limits
+            },
+            ..self
+        }
+    }
+
+    /// abort the scan at `deadline`: the stream then yields a [`ScanAborted::DeadlineExceeded`]
+    /// error and ends, dropping the tables it reads and the version it pins, e.g. to bound a merge
+    /// over a slow object storage
+    pub fn deadline(self, deadline: Instant) -> Self {
+        Self {
+            cancel: ScanCancel {
+                deadline: Some(deadline),
+                ..self.cancel
+            },
+            ..self
+        }
+    }
+
+    /// abort the scan `timeout` from now, see [`Scan::deadline`]
+    pub fn timeout(self, timeout: Duration) -> Self {
+        self.deadline(Instant::now() + timeout)
+    }
+
+    /// abort the scan once `token` is cancelled: the stream then yields a
+    /// [`ScanAborted::Cancelled`] error and ends, dropping the tables it reads and the version it
+    /// pins even while it waits on a read
+    pub fn cancel_on(self, token: CancellationToken) -> Self {
+        Self {
+            cancel: ScanCancel {
+                token: Some(token),
+                ..self.cancel
+            },
+            ..self
+        }
+    }
+
+    /// collect the [`QueryStats`] of the scan into `stats`, read by
+    /// [`QueryStatsCollector::stats`] while or after the scan runs
+    pub fn collect_stats(self, stats: &QueryStatsCollector) -> Self {
+        Self {
+            stats: Some(stats.clone()),
+            ..self
+        }
+    }
+
     /// fields in projection Record by field indices
     pub fn projection(self, mut projection: Vec<usize>) -> Self {
         // skip two columns: _null and _ts
@@ -743,13 +2175,17 @@ where
     pub async fn take(
         self,
     ) -> Result<impl Stream<Item = Result<Entry<'scan, R>, ParquetError>>, DbError<R>> {
+        metrics::increment(metrics::SCANS, 1);
+        let opening = self.stats.as_ref().map(|_| Instant::now());
+        let (start, tables) = self.slow_log();
+        let span = self.span();
         let mut streams = Vec::new();
         let is_projection = self.projection_indices.is_some();
 
-        if let Some(pre_stream) =
-            (self.fn_pre_stream)(is_projection.then(|| self.projection.clone()))
-        {
-            streams.push(pre_stream);
+        if let Some(fn_pre_stream) = self.fn_pre_stream {
+            streams.push(fn_pre_stream(
+                is_projection.then(|| self.projection.clone()),
+            ));
         }
 
         // Mutable
@@ -781,14 +2217,29 @@ where
                 self.limit,
                 self.projection,
                 self.parquet_lru,
+                &self.limits,
+                self.stats.as_ref(),
             )
             .await?;
 
-        let mut merge_stream = MergeStream::from_vec(streams, self.ts).await?;
+        let range_tombstones = self.schema.range_tombstones(self.version);
+        let mut merge_stream =
+            MergeStream::with_range_tombstones(streams, self.ts, range_tombstones)
+                .await?
+                .paranoid(self.version.option().paranoid_checks)
+                .collect_stats(self.stats.clone());
         if let Some(limit) = self.limit {
             merge_stream = merge_stream.limit(limit);
         }
-        Ok(merge_stream)
+        if let (Some(stats), Some(opening)) = (&self.stats, opening) {
+            stats.opened(opening.elapsed());
+        }
+
+        Ok(CancelStream::new(
+            SlowLogStream::new(merge_stream, span, start, tables, |_| 1).collect_stats(self.stats),
+            self.cancel,
+            &self.schema.clock,
+        ))
     }
 
     /// Get a Stream that returns RecordBatch consisting of a `batch_size` number of records
@@ -796,13 +2247,17 @@ where
         self,
         batch_size: usize,
     ) -> Result<impl Stream<Item = Result<R::Columns, ParquetError>> + 'scan, DbError<R>> {
+        metrics::increment(metrics::SCANS, 1);
+        let opening = self.stats.as_ref().map(|_| Instant::now());
+        let (start, tables) = self.slow_log();
+        let span = self.span();
         let mut streams = Vec::new();
         let is_projection = self.projection_indices.is_some();
 
-        if let Some(pre_stream) =
-            (self.fn_pre_stream)(is_projection.then(|| self.projection.clone()))
-        {
-            streams.push(pre_stream);
+        if let Some(fn_pre_stream) = self.fn_pre_stream {
+            streams.push(fn_pre_stream(
+                is_projection.then(|| self.projection.clone()),
+            ));
         }
 
         // Mutable
@@ -834,17 +2289,67 @@ where
                 self.limit,
                 self.projection,
                 self.parquet_lru,
+                &self.limits,
+                self.stats.as_ref(),
             )
             .await?;
-        let merge_stream = MergeStream::from_vec(streams, self.ts).await?;
+        let range_tombstones = self.schema.range_tombstones(self.version);
+        let merge_stream = MergeStream::with_range_tombstones(streams, self.ts, range_tombstones)
+            .await?
+            .paranoid(self.version.option().paranoid_checks)
+            .collect_stats(self.stats.clone());
+        if let (Some(stats), Some(opening)) = (&self.stats, opening) {
+            stats.opened(opening.elapsed());
+        }
 
-        Ok(PackageStream::new(
-            batch_size,
-            merge_stream,
-            self.projection_indices,
-            &self.schema.record_instance,
+        Ok(CancelStream::new(
+            SlowLogStream::new(
+                PackageStream::new(
+                    batch_size,
+                    merge_stream,
+                    self.projection_indices,
+                    &self.schema.record_instance,
+                ),
+                span,
+                start,
+                tables,
+                |columns| columns.as_record_batch().num_rows(),
+            )
+            .collect_stats(self.stats),
+            self.cancel,
+            &self.schema.clock,
         ))
     }
+
+    /// get a Stream of arrow `RecordBatch`es of up to `batch_size` records, merged and resolved at
+    /// the timestamp of the scan as by [`Scan::take`], holding the columns of `R` without the
+    /// internal `_null` and `_ts` ones
+    pub async fn take_batches(
+        self,
+        batch_size: usize,
+    ) -> Result<impl Stream<Item = Result<RecordBatch, ParquetError>> + 'scan, DbError<R>> {
+        Ok(self.package(batch_size).await?.map(|columns| {
+            let batch = columns?.as_record_batch().clone();
+            let indices = (2..batch.num_columns()).collect::<Vec<_>>();
+            Ok(batch.project(&indices)?)
+        }))
+    }
+
+    fn span(&self) -> Span {
+        debug_span!("scan", lower = ?self.lower, upper = ?self.upper, limit = self.limit)
+    }
+
+    /// the start and the SSTs in range of the scan to log if it outlives the slow operation
+    /// threshold
+    fn slow_log(&self) -> (Option<(Instant, Duration)>, Vec<(usize, FileId)>) {
+        match self.version.option().slow_operation_threshold {
+            Some(threshold) => (
+                Some((Instant::now(), threshold)),
+                self.version.tables_in_range((self.lower, self.upper)),
+            ),
+            None => (None, Vec::new()),
+        }
+    }
 }
 
 #[derive(Debug, Error)]
@@ -852,31 +2357,195 @@ pub enum DbError<R>
 where
     R: Record,
 {
-    #[error("write io error: {0}")]
+    #[error("io error: {0}")]
     Io(#[from] io::Error),
-    #[error("write version error: {0}")]
+    #[error("version error: {0}")]
     Version(#[from] VersionError<R>),
-    #[error("write parquet error: {0}")]
+    #[error("parquet error: {0}")]
     Parquet(#[from] ParquetError),
-    #[error("write ulid decode error: {0}")]
+    #[error("ulid decode error: {0}")]
     UlidDecode(#[from] ulid::DecodeError),
-    #[error("write fusio error: {0}")]
+    #[error("fusio error: {0}")]
     Fusio(#[from] fusio::Error),
     // #[error("write encode error: {0}")]
     // Encode(<<R as Record>::Ref as Encode>::Error),
-    #[error("write recover error: {0}")]
+    #[error("recover error: {0}")]
     Recover(#[from] RecoverError<<R as Decode>::Error>),
     #[error("wal write error: {0}")]
     WalWrite(Box<dyn std::error::Error + Send + Sync + 'static>),
     #[error("exceeds the maximum level(0-6)")]
     ExceedsMaxLevel,
+    #[error("invalid option: {0}")]
+    InvalidOption(#[from] OptionError),
+    #[error("ingest error: {0}")]
+    Ingest(String),
+    #[error("export error: {0}")]
+    Export(#[from] ExportError),
+    #[error("arrow error: {0}")]
+    Arrow(#[from] ArrowError),
+    #[error("the database is opened read-only")]
+    ReadOnly,
+    #[error("the database at {0} is already in use")]
+    Locked(Path),
+    #[error("the database stopped accepting writes until resumed: {0}")]
+    Poisoned(String),
+    #[error("timestamp {ts:?} is not retained, readable ones are {oldest:?} to {latest:?}")]
+    TimestampNotRetained {
+        ts: Timestamp,
+        oldest: Timestamp,
+        latest: Timestamp,
+    },
+    #[error("key of {size} bytes exceeds the limit of {limit} bytes")]
+    KeyTooLarge { size: usize, limit: usize },
+    #[error("record of {size} bytes exceeds the limit of {limit} bytes")]
+    RecordTooLarge { size: usize, limit: usize },
+    #[error("write rejected by the validator: {0}")]
+    Rejected(Box<dyn std::error::Error + Send + Sync + 'static>),
 }
 
-type LockMap<K> = Arc<LockableHashMap<K, ()>>;
-
-pub enum Projection {
-    All,
-    Parts(Vec<usize>),
+/// What a [`DbError`] failed on, see [`DbError::kind`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DbErrorKind {
+    /// the options, the lock or the state of the files kept the database from being opened or
+    /// recovered
+    Open,
+    /// a read asked for what the database does not hold or cannot return
+    Read,
+    /// a write was refused, by the limits, the validator or the state of the database
+    Write,
+    /// the files of the database are corrupted, see [`DbError::is_corruption`]
+    Corruption,
+    /// the file system or the object storage failed, see [`DbError::is_retryable`]
+    Io,
+}
+
+impl<R> DbError<R>
+where
+    R: Record,
+{
+    /// what the error failed on, whatever the operation returning it
+    pub fn kind(&self) -> DbErrorKind {
+        if self.is_corruption() {
+            return DbErrorKind::Corruption;
+        }
+        match self {
+            DbError::Io(_)
+            | DbError::Version(_)
+            | DbError::Parquet(_)
+            | DbError::Fusio(_)
+            | DbError::Export(_)
+            | DbError::Recover(RecoverError::Io(_) | RecoverError::Fusio(_)) => DbErrorKind::Io,
+            DbError::UlidDecode(_) => DbErrorKind::Corruption,
+            DbError::Recover(_)
+            | DbError::ExceedsMaxLevel
+            | DbError::InvalidOption(_)
+            | DbError::Locked(_) => DbErrorKind::Open,
+            DbError::Arrow(_) | DbError::TimestampNotRetained { .. } => DbErrorKind::Read,
+            DbError::WalWrite(_)
+            | DbError::Ingest(_)
+            | DbError::ReadOnly
+            | DbError::Poisoned(_)
+            | DbError::KeyTooLarge { .. }
+            | DbError::RecordTooLarge { .. }
+            | DbError::Rejected(_) => DbErrorKind::Write,
+        }
+    }
+
+    /// whether retrying the operation as is may succeed, rather than fail again the same way
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            DbError::Io(err) | DbError::Fusio(fusio::Error::Io(err)) => is_transient(err),
+            DbError::Version(VersionError::Io(err))
+            | DbError::Version(VersionError::Fusio(fusio::Error::Io(err))) => is_transient(err),
+            DbError::Recover(RecoverError::Io(err))
+            | DbError::Recover(RecoverError::Fusio(fusio::Error::Io(err))) => is_transient(err),
+            // released once the other process closes the database
+            DbError::Locked(_) => true,
+            _ => false,
+        }
+    }
+
+    /// whether the files of the database are corrupted, which no retry recovers from
+    pub fn is_corruption(&self) -> bool {
+        matches!(
+            self,
+            DbError::Recover(RecoverError::Checksum)
+                | DbError::Recover(RecoverError::Decode(_))
+                | DbError::UlidDecode(_)
+                | DbError::Version(VersionError::UlidDecode(_))
+                | DbError::Version(VersionError::Inconsistent(_))
+                | DbError::Version(VersionError::Corrupted { .. })
+        )
+    }
+}
+
+/// fail a write of `record`, or a deletion of `key` if `None`, over the size limits or rejected
+/// by the [`WriteValidator`]
+pub(crate) fn check_write<R>(
+    option: &DbOption<R>,
+    key: <R::Key as Key>::Ref<'_>,
+    record: Option<&R>,
+) -> Result<(), DbError<R>>
+where
+    R: Record,
+{
+    check_write_size(option, || key.size(), record)?;
+    if let Some(validator) = &option.write_validator {
+        validator.validate(key, record).map_err(DbError::Rejected)?;
+    }
+    Ok(())
+}
+
+/// fail a write of the key sized by `key_size`, and of the `record` if not a deletion, over
+/// [`DbOption::max_key_size`] or [`DbOption::max_record_size`], unless the writes are unchecked
+fn check_write_size<R>(
+    option: &DbOption<R>,
+    key_size: impl FnOnce() -> usize,
+    record: Option<&R>,
+) -> Result<(), DbError<R>>
+where
+    R: Record,
+{
+    if option.unchecked_writes {
+        return Ok(());
+    }
+    if let Some(limit) = option.max_key_size {
+        let size = key_size();
+        if size > limit {
+            return Err(DbError::KeyTooLarge { size, limit });
+        }
+    }
+    if let (Some(limit), Some(record)) = (option.max_record_size, record) {
+        let size = R::size(record);
+        if size > limit {
+            return Err(DbError::RecordTooLarge { size, limit });
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn is_transient(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::Interrupted | io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+    )
+}
+
+type LockMap<K> = Arc<LockTable<K>>;
+
+/// how the [`DB`] was opened
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OpenMode {
+    ReadWrite,
+    /// a secondary of another process, see [`DB::open_secondary`]
+    ReadOnly {
+        tail_wal: bool,
+    },
+}
+
+pub enum Projection {
+    All,
+    Parts(Vec<usize>),
 }
 
 pub type ParquetLru = Arc<dyn DynLruCache<FileId> + Send + Sync>;
@@ -887,11 +2556,12 @@ pub(crate) mod tests {
         collections::{BTreeMap, Bound},
         mem,
         sync::Arc,
+        time::{Duration, Instant},
     };
 
     use arrow::{
-        array::{Array, AsArray, RecordBatch},
-        datatypes::{DataType, Field, Schema, UInt32Type},
+        array::{Array, AsArray, BooleanArray, RecordBatch, StringArray, UInt32Array},
+        datatypes::{DataType, Field, Schema, UInt32Type, UInt64Type},
     };
     use async_lock::RwLock;
     use flume::{bounded, Receiver};
@@ -899,27 +2569,37 @@ pub(crate) mod tests {
     use fusio_dispatch::FsOptions;
     use futures::StreamExt;
     use once_cell::sync::Lazy;
-    use parquet::{arrow::ProjectionMask, format::SortingColumn, schema::types::ColumnPath};
-    use parquet_lru::NoCache;
+    use parquet::{
+        arrow::ProjectionMask, basic::Compression, file::properties::WriterProperties,
+        format::SortingColumn, schema::types::ColumnPath,
+    };
+    use parquet_lru::{LruCache, NoCache};
     use tempfile::TempDir;
     use tracing::error;
 
     use crate::{
         compaction::{CompactTask, CompactionError, Compactor},
-        executor::{tokio::TokioExecutor, Executor},
-        fs::{manager::StoreManager, FileId},
+        executor::{tokio::TokioExecutor, Clock, Executor},
+        fs::{manager::StoreManager, FileId, FileType},
         inmem::{immutable::tests::TestImmutableArrays, mutable::Mutable},
         record::{
             internal::InternalRecordRef,
             runtime::test::{test_dyn_item_schema, test_dyn_items},
-            Column, Datatype, DynRecord, RecordDecodeError, RecordEncodeError, RecordInstance,
-            RecordRef,
+            Column, ColumnDesc, Datatype, DynRecord, RecordDecodeError, RecordEncodeError,
+            RecordInstance, RecordRef,
         },
         serdes::{Decode, Encode},
+        snapshot::Snapshot,
+        transaction::CommitError,
         trigger::{TriggerFactory, TriggerType},
-        version::{cleaner::Cleaner, set::tests::build_version_set, Version},
-        wal::log::LogType,
-        DbError, DbOption, Immutable, Projection, Record, DB,
+        version::{
+            cleaner::Cleaner, set::tests::build_version_set, TransactionTs, Version, VersionError,
+            MAX_LEVEL,
+        },
+        wal::{log::LogType, RecoverError},
+        CompactionStyle, DbError, DbErrorKind, DbOption, Immutable, MutableOptions, OptionError,
+        Projection, Record, Scan, SstCompression, VersionLogRecoveryMode, WalRecoveryMode,
+        WalSyncPolicy, WriteStall, DB,
     };
 
     #[derive(Debug, PartialEq, Eq, Clone)]
@@ -1006,7 +2686,7 @@ pub(crate) mod tests {
             static SCHEMA: Lazy<Arc<Schema>> = Lazy::new(|| {
                 Arc::new(Schema::new(vec![
                     Field::new("_null", DataType::Boolean, false),
-                    Field::new("_ts", DataType::UInt32, false),
+                    Field::new("_ts", DataType::UInt64, false),
                     Field::new("vstring", DataType::Utf8, false),
                     Field::new("vu32", DataType::UInt32, false),
                     Field::new("vbool", DataType::Boolean, true),
@@ -1095,7 +2775,7 @@ pub(crate) mod tests {
 
             let ts = record_batch
                 .column(1)
-                .as_primitive::<UInt32Type>()
+                .as_primitive::<UInt64Type>()
                 .value(offset)
                 .into();
 
@@ -1140,7 +2820,7 @@ pub(crate) mod tests {
         option: DbOption<Test>,
         executor: E,
     ) -> RecordBatch {
-        let db: DB<Test, E> = DB::new(option.clone(), executor).await.unwrap();
+        let db: DB<Test> = DB::new(option.clone(), executor).await.unwrap();
         let base_fs = db.manager.base_fs();
 
         db.write(
@@ -1193,7 +2873,7 @@ pub(crate) mod tests {
                     vu32: 1,
                     vbool: Some(true),
                 },
-                1_u32.into(),
+                1_u64.into(),
             )
             .await
             .unwrap();
@@ -1205,7 +2885,7 @@ pub(crate) mod tests {
                     vu32: 2,
                     vbool: Some(true),
                 },
-                1_u32.into(),
+                1_u64.into(),
             )
             .await
             .unwrap();
@@ -1217,7 +2897,7 @@ pub(crate) mod tests {
                     vu32: 3,
                     vbool: Some(true),
                 },
-                1_u32.into(),
+                1_u64.into(),
             )
             .await
             .unwrap();
@@ -1235,7 +2915,7 @@ pub(crate) mod tests {
                         vu32: 4,
                         vbool: Some(true),
                     },
-                    1_u32.into(),
+                    1_u64.into(),
                 )
                 .await
                 .unwrap();
@@ -1247,7 +2927,7 @@ pub(crate) mod tests {
                         vu32: 5,
                         vbool: Some(true),
                     },
-                    1_u32.into(),
+                    1_u64.into(),
                 )
                 .await
                 .unwrap();
@@ -1259,7 +2939,7 @@ pub(crate) mod tests {
                         vu32: 6,
                         vbool: Some(true),
                     },
-                    1_u32.into(),
+                    1_u64.into(),
                 )
                 .await
                 .unwrap();
@@ -1279,7 +2959,16 @@ pub(crate) mod tests {
                 compaction_tx,
                 recover_wal_ids: None,
                 trigger,
-                record_instance: RecordInstance::Normal,
+                record_instance: Arc::new(RecordInstance::Normal),
+                watchers: Default::default(),
+                event_listener: None,
+                snapshots: Default::default(),
+                statistics: Default::default(),
+                write_buffer: None,
+                max_total_wal_size: option.max_total_wal_size,
+                mode: crate::OpenMode::ReadWrite,
+                poisoned: Default::default(),
+                clock: Clock::new(Arc::new(TokioExecutor::new())),
             },
             compaction_rx,
         ))
@@ -1292,7 +2981,7 @@ pub(crate) mod tests {
         schema: crate::Schema<R>,
         version: Version<R>,
         manager: Arc<StoreManager>,
-    ) -> Result<DB<R, E>, DbError<R>>
+    ) -> Result<DB<R>, DbError<R>>
     where
         R: Record + Send + Sync,
         R::Columns: Send + Sync,
@@ -1305,9 +2994,11 @@ pub(crate) mod tests {
             let _ = base_fs.create_dir_all(&option.version_log_dir_path()).await;
         }
 
+        let clock = schema.clock.clone();
         let schema = Arc::new(RwLock::new(schema));
 
-        let (mut cleaner, clean_sender) = Cleaner::<R>::new(option.clone(), manager.clone());
+        let (mut cleaner, clean_sender) =
+            Cleaner::<R>::new(option.clone(), manager.clone(), clock.clone());
         let version_set =
             build_version_set(version, clean_sender, option.clone(), manager.clone()).await?;
         let mut compactor = Compactor::<R>::new(
@@ -1327,12 +3018,17 @@ pub(crate) mod tests {
                 if let Err(err) = match task {
                     CompactTask::Freeze => {
                         compactor
-                            .check_then_compaction(Arc::new(NoCache::default()))
+                            .check_then_compaction(Arc::new(NoCache::default()), false)
+                            .await
+                    }
+                    CompactTask::Release => {
+                        compactor
+                            .check_then_compaction(Arc::new(NoCache::default()), true)
                             .await
                     }
                     CompactTask::Flush(option_tx) => {
                         let mut result = compactor
-                            .check_then_compaction(Arc::new(NoCache::default()))
+                            .check_then_compaction(Arc::new(NoCache::default()), false)
                             .await;
                         if let Some(tx) = option_tx {
                             let channel_result =
@@ -1343,6 +3039,7 @@ pub(crate) mod tests {
                         }
                         result
                     }
+                    CompactTask::Purge => compactor.purge(Arc::new(NoCache::default())).await,
                 } {
                     error!("[Compaction Error]: {}", err)
                 }
@@ -1352,14 +3049,20 @@ pub(crate) mod tests {
         Ok(DB {
             schema,
             version_set,
-            lock_map: Arc::new(Default::default()),
+            lock_map: Arc::new(LockTable::new(
+                option.lock_timeout,
+                option.lock_shards,
+                clock.clone(),
+            )),
+            serializable_lock: Arc::new(Default::default()),
+            rate_limiter: Arc::new(RateLimiter::new(&option, clock)),
             manager,
             parquet_lru: Arc::new(NoCache::default()),
-            _p: Default::default(),
+            lock: None,
         })
     }
 
-    fn test_items() -> Vec<Test> {
+    pub(crate) fn test_items() -> Vec<Test> {
         vec![
             Test {
                 vstring: 0.to_string(),
@@ -1583,7 +3286,7 @@ pub(crate) mod tests {
         option.major_default_oldest_table_num = 1;
         option.trigger_type = TriggerType::Length(/* max_mutable_len */ 5);
 
-        let db: DB<Test, TokioExecutor> = DB::new(option, TokioExecutor::new()).await.unwrap();
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
 
         for (i, item) in test_items().into_iter().enumerate() {
             db.write(item, 0.into()).await.unwrap();
@@ -1607,201 +3310,2704 @@ pub(crate) mod tests {
     }
 
     #[tokio::test]
-    async fn test_flush() {
+    async fn test_flush() {
+        let temp_dir = TempDir::new().unwrap();
+
+        let mut option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap());
+        option.immutable_chunk_num = 1;
+        option.immutable_chunk_max_num = 1;
+        option.major_threshold_with_sst_size = 3;
+        option.level_sst_magnification = 10;
+        option.max_sst_file_size = 2 * 1024 * 1024;
+        option.major_default_oldest_table_num = 1;
+        option.trigger_type = TriggerType::Length(/* max_mutable_len */ 50);
+
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+
+        for item in &test_items()[0..10] {
+            db.write(item.clone(), 0.into()).await.unwrap();
+        }
+        db.flush().await.unwrap();
+        for item in &test_items()[10..20] {
+            db.write(item.clone(), 0.into()).await.unwrap();
+        }
+
+        dbg!(db.version_set.current().await);
+        db.flush().await.unwrap();
+        dbg!(db.version_set.current().await);
+
+        let version = db.version_set.current().await;
+        assert!(!version.level_slice[0].is_empty());
+    }
+
+    #[tokio::test]
+    async fn schema_recover() {
+        let temp_dir = TempDir::new().unwrap();
+        let fs = Arc::new(TokioFs) as Arc<dyn DynFs>;
+
+        let option = Arc::new(DbOption::from(
+            Path::from_filesystem_path(temp_dir.path()).unwrap(),
+        ));
+        fs.create_dir_all(&option.wal_dir_path()).await.unwrap();
+
+        let (task_tx, _task_rx) = bounded(1);
+
+        let trigger = Arc::new(TriggerFactory::create(option.trigger_type));
+        let schema: crate::Schema<Test> = crate::Schema {
+            mutable: Mutable::new(&option, trigger.clone(), &fs).await.unwrap(),
+            immutables: Default::default(),
+            compaction_tx: task_tx.clone(),
+            recover_wal_ids: None,
+            trigger,
+            record_instance: Arc::new(RecordInstance::Normal),
+            watchers: Default::default(),
+            event_listener: None,
+            snapshots: Default::default(),
+            statistics: Default::default(),
+            write_buffer: None,
+            max_total_wal_size: option.max_total_wal_size,
+            mode: crate::OpenMode::ReadWrite,
+            poisoned: Default::default(),
+            clock: Clock::new(Arc::new(TokioExecutor::new())),
+        };
+
+        for (i, item) in test_items().into_iter().enumerate() {
+            schema
+                .write(LogType::Full, item, (i as u64).into())
+                .await
+                .unwrap();
+        }
+        schema.flush_wal().await.unwrap();
+        drop(schema);
+
+        let db: DB<Test> = DB::new(option.as_ref().to_owned(), TokioExecutor::new())
+            .await
+            .unwrap();
+
+        let mut sort_items = BTreeMap::new();
+        for item in test_items() {
+            sort_items.insert(item.vstring.clone(), item);
+        }
+        {
+            let tx = db.transaction().await;
+            let mut scan = tx
+                .scan((Bound::Unbounded, Bound::Unbounded))
+                .take()
+                .await
+                .unwrap();
+
+            while let Some(entry) = scan.next().await.transpose().unwrap() {
+                let (_, test) = sort_items.pop_first().unwrap();
+
+                assert_eq!(entry.key().value, test.key());
+                assert_eq!(entry.value().as_ref().unwrap().vstring, test.vstring);
+                assert_eq!(entry.value().as_ref().unwrap().vu32, Some(test.vu32));
+                assert_eq!(entry.value().as_ref().unwrap().vbool, test.vbool);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn dyn_schema_recover() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = StoreManager::new(FsOptions::Local, vec![]).unwrap();
+
+        let (desc, primary_key_index) = test_dyn_item_schema();
+        let option = Arc::new(DbOption::with_path(
+            Path::from_filesystem_path(temp_dir.path()).unwrap(),
+            "id".to_owned(),
+            primary_key_index,
+        ));
+        manager
+            .base_fs()
+            .create_dir_all(&option.wal_dir_path())
+            .await
+            .unwrap();
+
+        let (task_tx, _task_rx) = bounded(1);
+
+        let trigger = Arc::new(TriggerFactory::create(option.trigger_type));
+        let schema: crate::Schema<DynRecord> = crate::Schema {
+            mutable: Mutable::new(&option, trigger.clone(), manager.base_fs())
+                .await
+                .unwrap(),
+            immutables: Default::default(),
+            compaction_tx: task_tx.clone(),
+            recover_wal_ids: None,
+            trigger,
+            record_instance: Arc::new(RecordInstance::Normal),
+            watchers: Default::default(),
+            event_listener: None,
+            snapshots: Default::default(),
+            statistics: Default::default(),
+            write_buffer: None,
+            max_total_wal_size: option.max_total_wal_size,
+            mode: crate::OpenMode::ReadWrite,
+            poisoned: Default::default(),
+            clock: Clock::new(Arc::new(TokioExecutor::new())),
+        };
+
+        for item in test_dyn_items().into_iter() {
+            schema
+                .write(LogType::Full, item, 0_u64.into())
+                .await
+                .unwrap();
+        }
+        schema.flush_wal().await.unwrap();
+        drop(schema);
+
+        let option = DbOption::with_path(
+            Path::from_filesystem_path(temp_dir.path()).unwrap(),
+            "id".to_owned(),
+            primary_key_index,
+        );
+        let db: DB<DynRecord> =
+            DB::with_schema(option, TokioExecutor::new(), desc, primary_key_index)
+                .await
+                .unwrap();
+
+        let mut sort_items = BTreeMap::new();
+        for item in test_dyn_items() {
+            sort_items.insert(item.key(), item);
+        }
+
+        {
+            let tx = db.transaction().await;
+            let mut scan = tx
+                .scan((Bound::Unbounded, Bound::Unbounded))
+                .projection(vec![0, 1, 2])
+                .take()
+                .await
+                .unwrap();
+
+            while let Some(entry) = scan.next().await.transpose().unwrap() {
+                let columns1 = entry.value().unwrap().columns;
+                let (_, record) = sort_items.pop_first().unwrap();
+                let columns2 = record.as_record_ref().columns;
+
+                assert_eq!(columns1.get(1), columns2.get(1));
+                assert_eq!(columns1.get(2), columns2.get(2));
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_removed() {
+        let temp_dir = TempDir::new().unwrap();
+
+        let mut option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap());
+        option.immutable_chunk_num = 1;
+        option.immutable_chunk_max_num = 1;
+        option.major_threshold_with_sst_size = 3;
+        option.major_default_oldest_table_num = 1;
+        option.trigger_type = TriggerType::Length(5);
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+
+        for (idx, item) in test_items().into_iter().enumerate() {
+            if idx % 2 == 0 {
+                db.write(item, 0.into()).await.unwrap();
+            } else {
+                db.remove(item.vstring).await.unwrap();
+            }
+        }
+
+        for i in 0..40 {
+            let vstring = db
+                .get(&i.to_string(), |e| Some(e.get().vstring.to_string()))
+                .await
+                .unwrap();
+            if i % 2 == 0 {
+                assert!(vstring.is_some());
+                assert_eq!(vstring, Some(i.to_string()));
+            } else {
+                assert!(vstring.is_none());
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_delete_range() {
+        let temp_dir = TempDir::new().unwrap();
+
+        let mut option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap());
+        option.immutable_chunk_num = 1;
+        option.immutable_chunk_max_num = 1;
+        option.major_threshold_with_sst_size = 3;
+        option.major_default_oldest_table_num = 1;
+        option.trigger_type = TriggerType::Length(5);
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+
+        for item in test_items() {
+            db.insert(item).await.unwrap();
+        }
+        let lower = "10".to_string();
+        let upper = "20".to_string();
+        db.delete_range((Bound::Included(&lower), Bound::Excluded(&upper)))
+            .await
+            .unwrap();
+        db.insert(Test {
+            vstring: "15".to_string(),
+            vu32: 15,
+            vbool: None,
+        })
+        .await
+        .unwrap();
+
+        let is_deleted = |key: &String| key != "15" && &lower <= key && key < &upper;
+        for _ in 0..2 {
+            for i in 0..40 {
+                let key = i.to_string();
+                let vstring = db
+                    .get(&key, |e| Some(e.get().vstring.to_string()))
+                    .await
+                    .unwrap();
+                assert_eq!(vstring.is_none(), is_deleted(&key));
+            }
+            {
+                let tx = db.transaction().await;
+                let mut scan = tx
+                    .scan((Bound::Unbounded, Bound::Unbounded))
+                    .take()
+                    .await
+                    .unwrap();
+
+                let mut count = 0;
+                while let Some(entry) = scan.next().await.transpose().unwrap() {
+                    assert!(!is_deleted(&entry.key().value.to_string()));
+                    count += 1;
+                }
+                assert_eq!(
+                    count,
+                    (0..40).filter(|i| !is_deleted(&i.to_string())).count()
+                );
+            }
+            // range tombstones are moved to the version along with the flushed memtables
+            db.flush().await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_drop_range_tombstone() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap());
+        option.immutable_chunk_num = 1;
+        option.immutable_chunk_max_num = 0;
+        option.major_threshold_with_sst_size = 10;
+        let db: DB<Test> = DB::new(option.clone(), TokioExecutor::new()).await.unwrap();
+
+        for item in test_items() {
+            db.insert(item).await.unwrap();
+        }
+        db.flush().await.unwrap();
+        let (lower, upper) = ("10".to_string(), "20".to_string());
+        db.delete_range((Bound::Included(&lower), Bound::Excluded(&upper)))
+            .await
+            .unwrap();
+        db.flush().await.unwrap();
+        assert_eq!(db.version_set.current().await.range_tombstones.len(), 1);
+
+        // no table is left holding the keys it deletes
+        db.compact_range((Bound::Unbounded, Bound::Unbounded), 2)
+            .await
+            .unwrap();
+        assert_eq!(db.version_set.current().await.range_tombstones.len(), 0);
+        drop(db);
+
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+        assert_eq!(db.version_set.current().await.range_tombstones.len(), 0);
+        for item in test_items() {
+            let vu32 = db
+                .get(&item.vstring, |entry| entry.get().vu32)
+                .await
+                .unwrap();
+            let is_deleted = lower <= item.vstring && item.vstring < upper;
+            assert_eq!(vu32, (!is_deleted).then_some(item.vu32));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_delete_files_in_range() {
+        let temp_dir = TempDir::new().unwrap();
+
+        let mut option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap());
+        option.immutable_chunk_num = 1;
+        option.immutable_chunk_max_num = 1;
+        option.trigger_type = TriggerType::Length(5);
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+
+        for item in test_items() {
+            db.insert(item).await.unwrap();
+        }
+        db.flush().await.unwrap();
+
+        let lower = "1".to_string();
+        let upper = "3".to_string();
+        db.delete_files_in_range((Bound::Included(&lower), Bound::Excluded(&upper)))
+            .await
+            .unwrap();
+
+        let version = db.version_set.current().await;
+        for scope in version.level_slice.iter().flatten() {
+            assert!(!scope.is_contained_in((Bound::Included(&lower), Bound::Excluded(&upper))));
+        }
+        drop(version);
+        for i in 0..40 {
+            let key = i.to_string();
+            let vstring = db
+                .get(&key, |e| Some(e.get().vstring.to_string()))
+                .await
+                .unwrap();
+            assert_eq!(vstring.is_none(), lower <= key && key < upper);
+        }
+
+        db.delete_files_in_range((Bound::Unbounded, Bound::Unbounded))
+            .await
+            .unwrap();
+        let version = db.version_set.current().await;
+        assert!(version.level_slice.iter().all(Vec::is_empty));
+        for i in 0..40 {
+            assert!(db
+                .get(&i.to_string(), |e| Some(e.get().vstring.to_string()))
+                .await
+                .unwrap()
+                .is_none());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_wal_archive() {
+        let temp_dir = TempDir::new().unwrap();
+        let archive_dir = TempDir::new().unwrap();
+
+        let mut option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap())
+            .wal_archive(
+                Path::from_filesystem_path(archive_dir.path()).unwrap(),
+                FsOptions::Local,
+            )
+            .wal_archive_max_files(2);
+        option.immutable_chunk_num = 1;
+        option.immutable_chunk_max_num = 1;
+        option.trigger_type = TriggerType::Length(5);
+
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+        for items in test_items().chunks(10) {
+            for item in items.iter().cloned() {
+                db.insert(item).await.unwrap();
+            }
+            db.flush().await.unwrap();
+        }
+
+        let archived = std::fs::read_dir(archive_dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect::<Vec<_>>();
+        assert_eq!(archived.len(), 2);
+        for entry in std::fs::read_dir(temp_dir.path().join("wal")).unwrap() {
+            assert!(!archived.contains(&entry.unwrap().file_name()));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_insert_record_batch() {
+        let temp_dir = TempDir::new().unwrap();
+        let option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap());
+        let db: DB<Test> = DB::new(option.clone(), TokioExecutor::new()).await.unwrap();
+        db.insert(Test {
+            vstring: "a".to_string(),
+            vu32: 0,
+            vbool: None,
+        })
+        .await
+        .unwrap();
+
+        let schema = Arc::new(Test::arrow_schema().project(&[2, 3, 4]).unwrap());
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(StringArray::from(vec!["a", "b", "c"])),
+                Arc::new(UInt32Array::from(vec![1, 2, 3])),
+                Arc::new(BooleanArray::from(vec![Some(true), None, Some(false)])),
+            ],
+        )
+        .unwrap();
+        db.insert_record_batch(batch.clone()).await.unwrap();
+        db.insert(Test {
+            vstring: "b".to_string(),
+            vu32: 4,
+            vbool: None,
+        })
+        .await
+        .unwrap();
+
+        let mismatched = batch.project(&[0, 1]).unwrap();
+        assert!(matches!(
+            db.insert_record_batch(mismatched).await,
+            Err(DbError::Ingest(_))
+        ));
+
+        let check = |db: DB<Test>| async move {
+            for (key, expected) in [("a", 1), ("b", 4), ("c", 3)] {
+                let vu32 = db
+                    .get(&key.to_string(), |entry| Some(entry.get().vu32.unwrap()))
+                    .await
+                    .unwrap();
+                assert_eq!(vu32, Some(expected));
+            }
+            db
+        };
+        let db = check(db).await;
+        db.flush_wal().await.unwrap();
+        drop(db);
+
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+        check(db).await;
+    }
+
+    #[tokio::test]
+    async fn test_lock() {
+        let temp_dir = TempDir::new().unwrap();
+        let option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap());
+        let db: DB<Test> = DB::new(option.clone(), TokioExecutor::new()).await.unwrap();
+        db.insert(test_items().remove(0)).await.unwrap();
+
+        let err = DB::<Test>::new(option.clone(), TokioExecutor::new())
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(err, DbError::Locked(_)));
+        assert_eq!(err.kind(), DbErrorKind::Open);
+        assert!(err.is_retryable());
+        assert!(db.is_locked());
+        // readers do not take the lock
+        let reader = DB::<Test>::open_read_only(option.clone(), TokioExecutor::new())
+            .await
+            .unwrap();
+        assert!(!reader.is_locked());
+
+        db.flush_wal().await.unwrap();
+        drop(db);
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+        assert_eq!(
+            db.get(&"0".to_string(), |entry| entry.get().vu32)
+                .await
+                .unwrap(),
+            Some(0)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_open_read_only() {
+        let temp_dir = TempDir::new().unwrap();
+        let option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap());
+        let db: DB<Test> = DB::new(option.clone(), TokioExecutor::new()).await.unwrap();
+
+        let mut items = test_items();
+        items.sort_by(|a, b| a.vstring.cmp(&b.vstring));
+        db.bulk_load(futures::stream::iter(items.clone()))
+            .await
+            .unwrap();
+        // only in the WAL
+        db.insert(Test {
+            vstring: "a".to_string(),
+            vu32: 0,
+            vbool: None,
+        })
+        .await
+        .unwrap();
+        db.flush_wal().await.unwrap();
+
+        let reader: DB<Test> = DB::open_read_only(option, TokioExecutor::new())
+            .await
+            .unwrap();
+        for item in items.iter() {
+            let vu32 = reader
+                .get(&item.vstring, |entry| entry.get().vu32)
+                .await
+                .unwrap();
+            assert_eq!(vu32, Some(item.vu32));
+        }
+        assert_eq!(
+            reader
+                .get(&"a".to_string(), |entry| entry.get().vu32)
+                .await
+                .unwrap(),
+            None
+        );
+
+        assert!(matches!(
+            reader.insert(items[0].clone()).await,
+            Err(CommitError::Database(DbError::ReadOnly))
+        ));
+        assert!(matches!(
+            reader.remove(items[0].vstring.clone()).await,
+            Err(CommitError::Database(DbError::ReadOnly))
+        ));
+        assert!(matches!(
+            reader.flush().await,
+            Err(CommitError::Database(DbError::ReadOnly))
+        ));
+        assert!(matches!(
+            reader
+                .bulk_load(futures::stream::iter(vec![Test {
+                    vstring: "z".to_string(),
+                    vu32: 0,
+                    vbool: None,
+                }]))
+                .await,
+            Err(DbError::ReadOnly)
+        ));
+
+        // the writer is not disturbed
+        db.remove(items[0].vstring.clone()).await.unwrap();
+        assert_eq!(
+            db.get(&items[0].vstring, |entry| entry.get().vu32)
+                .await
+                .unwrap(),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_open_secondary() {
+        let temp_dir = TempDir::new().unwrap();
+        let option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap());
+        let db: DB<Test> = DB::new(option.clone(), TokioExecutor::new()).await.unwrap();
+        let test = |key: &str, vu32: u32| Test {
+            vstring: key.to_string(),
+            vu32,
+            vbool: None,
+        };
+
+        db.insert(test("a", 0)).await.unwrap();
+        db.flush_wal().await.unwrap();
+
+        let reader: DB<Test> = DB::open_read_only(option.clone(), TokioExecutor::new())
+            .await
+            .unwrap();
+        let secondary: DB<Test> = DB::open_secondary(option, TokioExecutor::new(), true)
+            .await
+            .unwrap();
+        async fn get(db: &DB<Test>, key: &str) -> Option<u32> {
+            db.get(&key.to_string(), |entry| entry.get().vu32)
+                .await
+                .unwrap()
+        }
+        assert_eq!(get(&reader, "a").await, None);
+        assert_eq!(get(&secondary, "a").await, Some(0));
+
+        db.bulk_load(futures::stream::iter(
+            (0..10).map(|i| test(&format!("x{}", i), i)),
+        ))
+        .await
+        .unwrap();
+        db.insert(test("b", 1)).await.unwrap();
+        db.remove("a".to_string()).await.unwrap();
+        db.flush_wal().await.unwrap();
+        assert_eq!(get(&reader, "x1").await, None);
+        assert_eq!(get(&secondary, "b").await, None);
+
+        reader.try_catch_up().await.unwrap();
+        secondary.try_catch_up().await.unwrap();
+        assert_eq!(get(&reader, "x1").await, Some(1));
+        assert_eq!(get(&reader, "b").await, None);
+        assert_eq!(get(&secondary, "x1").await, Some(1));
+        assert_eq!(get(&secondary, "b").await, Some(1));
+        assert_eq!(get(&secondary, "a").await, None);
+
+        // nothing to catch up for the writer
+        db.try_catch_up().await.unwrap();
+        assert_eq!(get(&db, "b").await, Some(1));
+    }
+
+    #[tokio::test]
+    async fn test_scan_limits() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap());
+        option.immutable_chunk_num = 1;
+        option.immutable_chunk_max_num = 0;
+        option.major_threshold_with_sst_size = 100;
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+
+        // four tables apart from each other, and one overlapping all of them
+        for chunk in (0..40u32).collect::<Vec<_>>().chunks(10) {
+            for &i in chunk {
+                db.insert(Test {
+                    vstring: format!("{:02}", i),
+                    vu32: i,
+                    vbool: None,
+                })
+                .await
+                .unwrap();
+            }
+            db.flush().await.unwrap();
+        }
+        for i in (0..40u32).step_by(3) {
+            db.insert(Test {
+                vstring: format!("{:02}", i),
+                vu32: i + 100,
+                vbool: None,
+            })
+            .await
+            .unwrap();
+        }
+        db.flush().await.unwrap();
+        assert_eq!(db.dump_tree().await.unwrap().levels[0].len(), 5);
+
+        let tx = db.transaction().await;
+        let mut scan = tx
+            .scan((Bound::Unbounded, Bound::Unbounded))
+            .max_open_tables(1)
+            .max_inflight_reads(1)
+            .take()
+            .await
+            .unwrap();
+        let mut expected = 0..40u32;
+        while let Some(entry) = scan.next().await.transpose().unwrap() {
+            let i = expected.next().unwrap();
+            let value = entry.value().unwrap();
+            assert_eq!(value.vstring, format!("{:02}", i));
+            assert_eq!(value.vu32, Some(if i % 3 == 0 { i + 100 } else { i }));
+        }
+        assert_eq!(expected.next(), None);
+    }
+
+    #[tokio::test]
+    async fn test_keys() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap());
+        option.immutable_chunk_num = 1;
+        option.immutable_chunk_max_num = 0;
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+
+        for item in test_items() {
+            db.insert(item).await.unwrap();
+        }
+        db.flush().await.unwrap();
+        db.insert(test_items()[12].clone()).await.unwrap();
+        let ts = db.version_set.load_ts();
+        db.remove("13".to_string()).await.unwrap();
+
+        let (lower, upper) = ("1".to_string(), "2".to_string());
+        let keys = db
+            .keys((Bound::Included(&lower), Bound::Excluded(&upper)))
+            .await
+            .map(Result::unwrap)
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(
+            keys.iter().map(|(key, _)| key.as_str()).collect::<Vec<_>>(),
+            vec!["1", "10", "11", "12", "14", "15", "16", "17", "18", "19"]
+        );
+        // the overwritten key has the timestamp of its latest version
+        assert_eq!(keys[3].1, ts);
+        assert!(keys
+            .iter()
+            .enumerate()
+            .all(|(i, (_, key_ts))| i == 3 || *key_ts < ts));
+    }
+
+    #[tokio::test]
+    async fn test_contains_key() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap());
+        option.immutable_chunk_num = 1;
+        option.immutable_chunk_max_num = 0;
+        option.write_parquet_properties = WriterProperties::builder()
+            .set_column_bloom_filter_enabled(ColumnPath::new(vec!["vstring".to_string()]), true)
+            .build();
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+
+        for item in test_items() {
+            db.insert(item).await.unwrap();
+        }
+        db.flush().await.unwrap();
+        db.insert(Test {
+            vstring: "new".to_string(),
+            vu32: 0,
+            vbool: None,
+        })
+        .await
+        .unwrap();
+        db.remove("5".to_string()).await.unwrap();
+
+        assert!(db.contains_key(&"new".to_string()).await.unwrap());
+        assert!(db.contains_key(&"3".to_string()).await.unwrap());
+        assert!(!db.contains_key(&"5".to_string()).await.unwrap());
+        assert!(!db.contains_key(&"absent".to_string()).await.unwrap());
+
+        // the flushed key is found in the SST and the absent one is ruled out by its filter
+        db.flush().await.unwrap();
+        assert!(db.contains_key(&"3".to_string()).await.unwrap());
+        assert!(!db.contains_key(&"5".to_string()).await.unwrap());
+        assert!(!db.contains_key(&"absent".to_string()).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_time_travel() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap());
+        option.immutable_chunk_num = 1;
+        option.immutable_chunk_max_num = 0;
+        option.major_threshold_with_sst_size = 1;
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+
+        let items = test_items();
+        for item in items.iter().cloned() {
+            db.insert(item).await.unwrap();
+        }
+        let ts = db.snapshot().await.ts();
+        let mut item = items[0].clone();
+        item.vu32 = 100;
+        db.insert(item).await.unwrap();
+        db.remove(items[1].vstring.clone()).await.unwrap();
+
+        async fn get(snapshot: &Snapshot<'_, Test>, key: &str) -> Option<u32> {
+            snapshot
+                .get(&key.to_string(), Projection::All)
+                .await
+                .unwrap()
+                .map(|entry| entry.value().unwrap().vu32.unwrap())
+        }
+        for flushed in [false, true] {
+            if flushed {
+                // level 0 SSTs keep every version
+                db.flush().await.unwrap();
+            }
+            let snapshot = db.snapshot_at(ts).await.unwrap();
+            assert_eq!(get(&snapshot, "0").await, Some(0));
+            assert_eq!(get(&snapshot, "1").await, Some(1));
+            let count = snapshot
+                .scan((Bound::Unbounded, Bound::Unbounded))
+                .take()
+                .await
+                .unwrap()
+                .count()
+                .await;
+            assert_eq!(count, items.len());
+            drop(snapshot);
+
+            let snapshot = db.snapshot().await;
+            assert_eq!(get(&snapshot, "0").await, Some(100));
+            assert_eq!(get(&snapshot, "1").await, None);
+        }
+
+        let mut txn = db.transaction().await;
+        txn.read_at(ts).unwrap();
+        let value = txn
+            .get(&"0".to_string(), Projection::All)
+            .await
+            .unwrap()
+            .map(|entry| entry.get().vu32);
+        assert_eq!(value, Some(Some(0)));
+        // the record was written since, and flushed
+        txn.insert(items[0].clone());
+        assert!(matches!(
+            txn.commit().await,
+            Err(CommitError::WriteConflict(_))
+        ));
+
+        let latest = db.snapshot().await.ts();
+        let err = db
+            .snapshot_at((u64::from(latest) + 1).into())
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(err, DbError::TimestampNotRetained { .. }));
+        assert_eq!(err.kind(), DbErrorKind::Read);
+
+        // the major compaction keeps only the latest versions
+        db.insert(items[2].clone()).await.unwrap();
+        db.flush().await.unwrap();
+        assert!(!db.version_set.current().await.level_slice[1].is_empty());
+        assert!(matches!(
+            db.snapshot_at(ts).await,
+            Err(DbError::TimestampNotRetained { .. })
+        ));
+        let snapshot = db.snapshot().await;
+        assert_eq!(get(&snapshot, "0").await, Some(100));
+    }
+
+    #[tokio::test]
+    async fn test_get_history() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap());
+        option.immutable_chunk_num = 1;
+        option.immutable_chunk_max_num = 0;
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+
+        let mut item = test_items()[0].clone();
+        let key = item.vstring.clone();
+        db.insert(item.clone()).await.unwrap();
+        let first = db.snapshot().await.ts();
+        item.vu32 = 100;
+        db.insert(item.clone()).await.unwrap();
+        // versions in SSTs are read as well
+        db.flush().await.unwrap();
+        db.remove(key.clone()).await.unwrap();
+        item.vu32 = 200;
+        db.insert(item).await.unwrap();
+        db.delete_range((Bound::Included(&key), Bound::Included(&key)))
+            .await
+            .unwrap();
+
+        let history = db
+            .get_history(&key, (Bound::Unbounded, Bound::Unbounded), |entry| {
+                entry.get().vu32.unwrap()
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            history.iter().map(|(_, value)| *value).collect::<Vec<_>>(),
+            vec![None, Some(200), None, Some(100), Some(0)]
+        );
+        assert!(history.windows(2).all(|pair| pair[0].0 > pair[1].0));
+        assert_eq!(history[4].0, first);
+
+        let history = db
+            .get_history(&key, (Bound::Excluded(first), Bound::Unbounded), |entry| {
+                entry.get().vu32.unwrap()
+            })
+            .await
+            .unwrap();
+        assert_eq!(history.len(), 4);
+        assert!(db
+            .get_history(
+                &"missing".to_string(),
+                (Bound::Unbounded, Bound::Unbounded),
+                |_| ()
+            )
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_compaction_during_scan() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap());
+        option.immutable_chunk_num = 1;
+        option.immutable_chunk_max_num = 0;
+        option.major_threshold_with_sst_size = 1;
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+
+        let items = test_items();
+        for item in items.iter().cloned() {
+            db.insert(item).await.unwrap();
+        }
+        db.flush().await.unwrap();
+        // what a long scan reads through, while the compaction below removes its table
+        let version = db.version_set.current().await;
+        let gen = version.level_slice[0][0].gen;
+        let table = version.option().table_path(gen, 0);
+
+        let mut item = items[0].clone();
+        item.vu32 = 100;
+        db.insert(item).await.unwrap();
+        db.flush().await.unwrap();
+        assert!(db
+            .version_set
+            .current()
+            .await
+            .level_slice
+            .iter()
+            .flatten()
+            .all(|scope| scope.gen != gen));
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(fusio::path::path_to_local(&table).unwrap().exists());
+
+        let schema = db.schema.read().await;
+        let count = Scan::new(
+            &schema,
+            &db.manager,
+            (Bound::Unbounded, Bound::Unbounded),
+            version.load_ts(),
+            &version,
+            None,
+            db.parquet_lru.clone(),
+        )
+        .take()
+        .await
+        .unwrap()
+        .count()
+        .await;
+        assert_eq!(count, items.len());
+        drop(schema);
+
+        drop(version);
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(!fusio::path::path_to_local(&table).unwrap().exists());
+    }
+
+    #[tokio::test]
+    async fn test_sync_on_commit() {
+        let temp_dir = TempDir::new().unwrap();
+        let option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap())
+            .wal_sync_policy(WalSyncPolicy::PerCommit);
+        let db: DB<Test> = DB::new(option.clone(), TokioExecutor::new()).await.unwrap();
+
+        let items = test_items();
+        futures::future::join_all(items.iter().cloned().map(|item| db.insert(item)))
+            .await
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let mut txn = db.transaction().await;
+        txn.remove("0".to_string());
+        txn.commit().await.unwrap();
+        // every commit was flushed before returning, without flushing the WAL again
+        drop(db);
+
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+        assert_eq!(
+            db.get(&"0".to_string(), |entry| entry.get().vu32)
+                .await
+                .unwrap(),
+            None
+        );
+        for item in &items[1..] {
+            assert_eq!(
+                db.get(&item.vstring, |entry| entry.get().vu32)
+                    .await
+                    .unwrap(),
+                Some(item.vu32)
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_wal_sync_interval() {
+        let temp_dir = TempDir::new().unwrap();
+        let option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap())
+            .wal_sync_policy(WalSyncPolicy::Interval(Duration::from_millis(10)));
+        let db: DB<Test> = DB::new(option.clone(), TokioExecutor::new()).await.unwrap();
+
+        db.insert(test_items().remove(0)).await.unwrap();
+        // flushed by the timer
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        drop(db);
+
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+        assert_eq!(
+            db.get(&"0".to_string(), |entry| entry.get().vu32)
+                .await
+                .unwrap(),
+            Some(0)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_memtable_flush_interval() {
+        let temp_dir = TempDir::new().unwrap();
+        let option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap())
+            .memtable_flush_interval(Duration::from_millis(50));
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+
+        db.insert(test_items().remove(0)).await.unwrap();
+        assert!(db.version_set.current().await.level_slice[0].is_empty());
+        let mut flushed = false;
+        for _ in 0..100 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            if !db.version_set.current().await.level_slice[0].is_empty() {
+                flushed = true;
+                break;
+            }
+        }
+        assert!(flushed);
+        assert!(db.schema.read().await.mutable.is_empty());
+        assert_eq!(
+            db.get(&"0".to_string(), |entry| entry.get().vu32)
+                .await
+                .unwrap(),
+            Some(0)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_max_total_wal_size() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap())
+            .max_total_wal_size(256);
+        option.trigger_type = TriggerType::Length(usize::MAX);
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+
+        let items = test_items();
+        for item in items.clone() {
+            db.insert(item).await.unwrap();
+        }
+        let mut flushed = false;
+        for _ in 0..100 {
+            if !db.version_set.current().await.level_slice[0].is_empty() {
+                flushed = true;
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(flushed);
+        for item in &items {
+            assert_eq!(
+                db.get(&item.vstring, |entry| entry.get().vu32)
+                    .await
+                    .unwrap(),
+                Some(item.vu32)
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_insert_batch_recover() {
+        let temp_dir = TempDir::new().unwrap();
+        let option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap());
+        let db: DB<Test> = DB::new(option.clone(), TokioExecutor::new()).await.unwrap();
+
+        let items = test_items();
+        db.insert_batch(items.clone().into_iter()).await.unwrap();
+        db.insert_batch(std::iter::empty()).await.unwrap();
+        assert_eq!(db.schema.read().await.mutable.len(), items.len());
+        drop(db);
+
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+        for item in &items {
+            assert_eq!(
+                db.get(&item.vstring, |entry| entry.get().vu32)
+                    .await
+                    .unwrap(),
+                Some(item.vu32)
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_wal_segment_size() {
+        let temp_dir = TempDir::new().unwrap();
+        let option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap())
+            .wal_segment_size(64);
+        let db: DB<Test> = DB::new(option.clone(), TokioExecutor::new()).await.unwrap();
+
+        let items = test_items();
+        for item in items.clone() {
+            db.insert(item).await.unwrap();
+        }
+        db.flush_wal().await.unwrap();
+        let segments = std::fs::read_dir(temp_dir.path().join("wal"))
+            .unwrap()
+            .count();
+        assert!(segments > 1);
+        drop(db);
+
+        let db: DB<Test> = DB::new(option.clone(), TokioExecutor::new()).await.unwrap();
+        for item in &items {
+            assert_eq!(
+                db.get(&item.vstring, |entry| entry.get().vu32)
+                    .await
+                    .unwrap(),
+                Some(item.vu32)
+            );
+        }
+
+        // the segments of the flushed memtables are removed, only the new memtable's is left
+        db.flush().await.unwrap();
+        let segments = std::fs::read_dir(temp_dir.path().join("wal"))
+            .unwrap()
+            .count();
+        assert!(segments <= 1);
+
+        // a transaction is not split across segments
+        let mut txn = db.transaction().await;
+        for item in items.clone() {
+            txn.insert(Test { vu32: 1, ..item });
+        }
+        txn.commit().await.unwrap();
+        db.flush_wal().await.unwrap();
+        assert!(
+            std::fs::read_dir(temp_dir.path().join("wal"))
+                .unwrap()
+                .count()
+                <= segments + 1
+        );
+        drop(db);
+
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+        for item in &items {
+            assert_eq!(
+                db.get(&item.vstring, |entry| entry.get().vu32)
+                    .await
+                    .unwrap(),
+                Some(1)
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_recover_torn_wal() {
+        let temp_dir = TempDir::new().unwrap();
+        let option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap());
+        let db: DB<Test> = DB::new(option.clone(), TokioExecutor::new()).await.unwrap();
+        let items = test_items();
+        let (first_half, second_half) = items.split_at(items.len() / 2);
+        for item in first_half.iter().cloned() {
+            db.insert(item).await.unwrap();
+        }
+        db.flush_wal().await.unwrap();
+        let wal_path = std::fs::read_dir(temp_dir.path().join("wal"))
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap()
+            .path();
+        let first_half_len = std::fs::metadata(&wal_path).unwrap().len() as usize;
+        for item in second_half.iter().cloned() {
+            db.insert(item).await.unwrap();
+        }
+        db.flush_wal().await.unwrap();
+        drop(db);
+
+        let wal = std::fs::read(&wal_path).unwrap();
+        // the last entry is partially written
+        let torn = &wal[..wal.len() - 3];
+        std::fs::write(&wal_path, torn).unwrap();
+
+        let db: DB<Test> = DB::new(option.clone(), TokioExecutor::new()).await.unwrap();
+        let (last, recovered) = items.split_last().unwrap();
+        for item in recovered {
+            assert_eq!(
+                db.get(&item.vstring, |entry| entry.get().vu32)
+                    .await
+                    .unwrap(),
+                Some(item.vu32)
+            );
+        }
+        assert_eq!(
+            db.get(&last.vstring, |entry| entry.get().vu32)
+                .await
+                .unwrap(),
+            None
+        );
+        drop(db);
+
+        // entries follow the corrupted one
+        let skip = option
+            .clone()
+            .wal_recovery_mode(WalRecoveryMode::SkipCorrupted);
+        std::fs::write(&wal_path, [torn, &wal].concat()).unwrap();
+        assert!(matches!(
+            DB::<Test>::new(option.clone(), TokioExecutor::new()).await,
+            Err(DbError::Recover(RecoverError::Checksum))
+        ));
+        let db: DB<Test> = DB::new(skip.clone(), TokioExecutor::new()).await.unwrap();
+        assert_eq!(
+            db.get(&items[0].vstring, |entry| entry.get().vu32)
+                .await
+                .unwrap(),
+            Some(items[0].vu32)
+        );
+        drop(db);
+
+        // the checksum of an entry in the middle is corrupted
+        let mut corrupted = wal.clone();
+        corrupted[first_half_len - 1] ^= 0xff;
+        std::fs::write(&wal_path, &corrupted).unwrap();
+        assert!(matches!(
+            DB::<Test>::new(option, TokioExecutor::new()).await,
+            Err(DbError::Recover(RecoverError::Checksum))
+        ));
+        let db: DB<Test> = DB::new(skip, TokioExecutor::new()).await.unwrap();
+        assert_eq!(
+            db.get(&items[0].vstring, |entry| entry.get().vu32)
+                .await
+                .unwrap(),
+            Some(items[0].vu32)
+        );
+        for item in second_half {
+            assert_eq!(
+                db.get(&item.vstring, |entry| entry.get().vu32)
+                    .await
+                    .unwrap(),
+                None
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_recover_torn_version_log() {
+        let temp_dir = TempDir::new().unwrap();
+        let option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap());
+        let db: DB<Test> = DB::new(option.clone(), TokioExecutor::new()).await.unwrap();
+        let items = test_items();
+        for item in items.clone() {
+            db.insert(item).await.unwrap();
+        }
+        db.flush().await.unwrap();
+        drop(db);
+
+        let log_path = std::fs::read_dir(temp_dir.path().join("version"))
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap()
+            .path();
+        let mut log = std::fs::read(&log_path).unwrap();
+        // a batch of three edits cut short by a crash
+        log.extend([7, 3, 0, 0, 0, 1]);
+        std::fs::write(&log_path, log).unwrap();
+
+        let strict = option
+            .clone()
+            .version_log_recovery_mode(VersionLogRecoveryMode::Strict);
+        let err = DB::<Test>::new(strict.clone(), TokioExecutor::new())
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(
+            err,
+            DbError::Version(VersionError::Corrupted { .. })
+        ));
+        assert_eq!(err.kind(), DbErrorKind::Corruption);
+
+        let db: DB<Test> = DB::new(option.clone(), TokioExecutor::new()).await.unwrap();
+        for item in &items {
+            assert_eq!(
+                db.get(&item.vstring, |entry| entry.get().vu32)
+                    .await
+                    .unwrap(),
+                Some(item.vu32)
+            );
+        }
+        db.insert(items[0].clone()).await.unwrap();
+        db.flush().await.unwrap();
+        drop(db);
+
+        // the end discarded was rewritten away, the old log kept aside
+        assert_eq!(
+            std::fs::read_dir(temp_dir.path().join("quarantine"))
+                .unwrap()
+                .count(),
+            1
+        );
+        let db: DB<Test> = DB::new(strict, TokioExecutor::new()).await.unwrap();
+        assert_eq!(
+            db.get(&items[0].vstring, |entry| entry.get().vu32)
+                .await
+                .unwrap(),
+            Some(items[0].vu32)
+        );
+        drop(db);
+
+        // valid batches follow the corrupted one
+        let log_path = std::fs::read_dir(temp_dir.path().join("version"))
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap()
+            .path();
+        let log = std::fs::read(&log_path).unwrap();
+        std::fs::write(
+            &log_path,
+            [&log[..], &[7, 3, 0, 0, 0, 1], &log[..]].concat(),
+        )
+        .unwrap();
+        assert!(matches!(
+            DB::<Test>::new(option, TokioExecutor::new()).await,
+            Err(DbError::Version(VersionError::Corrupted { .. }))
+        ));
+        assert!(std::fs::read(&log_path).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_wal_dir() {
+        let temp_dir = TempDir::new().unwrap();
+        let wal_dir = TempDir::new().unwrap();
+        let option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap()).wal_dir(
+            Path::from_filesystem_path(wal_dir.path()).unwrap(),
+            FsOptions::Local,
+        );
+        let db: DB<Test> = DB::new(option.clone(), TokioExecutor::new()).await.unwrap();
+        db.insert(test_items().remove(0)).await.unwrap();
+        db.flush_wal().await.unwrap();
+        drop(db);
+
+        assert!(!temp_dir.path().join("wal").exists());
+        assert_eq!(std::fs::read_dir(wal_dir.path()).unwrap().count(), 1);
+
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+        assert_eq!(
+            db.get(&"0".to_string(), |entry| entry.get().vu32)
+                .await
+                .unwrap(),
+            Some(0)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_poisoned() {
+        let temp_dir = TempDir::new().unwrap();
+        let option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap());
+        let db: DB<Test> = DB::new(option.clone(), TokioExecutor::new()).await.unwrap();
+        let mut items = test_items().into_iter();
+        db.insert(items.next().unwrap()).await.unwrap();
+        db.flush_wal().await.unwrap();
+
+        // a write failing on a full disk leaves an incomplete entry
+        let wal_path = std::fs::read_dir(temp_dir.path().join("wal"))
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap()
+            .path();
+        let wal = std::fs::read(&wal_path).unwrap();
+        std::fs::write(&wal_path, [&wal[..], &[1, 2, 3]].concat()).unwrap();
+        db.schema.read().await.poison(&"No space left on device");
+        assert!(matches!(
+            db.insert(items.next().unwrap()).await,
+            Err(CommitError::Database(DbError::Poisoned(_)))
+        ));
+        // still readable
+        assert_eq!(
+            db.get(&"0".to_string(), |entry| entry.get().vu32)
+                .await
+                .unwrap(),
+            Some(0)
+        );
+
+        db.resume().await.unwrap();
+        assert_eq!(std::fs::read(&wal_path).unwrap(), wal);
+        db.insert(items.next().unwrap()).await.unwrap();
+        db.flush_wal().await.unwrap();
+        drop(db);
+
+        // the old segment no longer ends with the incomplete entry
+        let strict = option.wal_recovery_mode(WalRecoveryMode::Strict);
+        let db: DB<Test> = DB::new(strict, TokioExecutor::new()).await.unwrap();
+        for (key, value) in [("0", Some(0)), ("1", None), ("2", Some(2))] {
+            assert_eq!(
+                db.get(&key.to_string(), |entry| entry.get().vu32)
+                    .await
+                    .unwrap(),
+                value
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_paranoid_checks() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap())
+            .paranoid_checks(true);
+        option.immutable_chunk_num = 1;
+        option.immutable_chunk_max_num = 1;
+        option.major_threshold_with_sst_size = 3;
+        option.major_default_oldest_table_num = 1;
+        option.trigger_type = TriggerType::Length(5);
+
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+        for (i, item) in test_items().into_iter().enumerate() {
+            db.insert(item).await.unwrap();
+            if i % 5 == 0 {
+                db.flush().await.unwrap();
+            }
+        }
+        assert!(!db.version_set.current().await.level_slice[1].is_empty());
+
+        let tx = db.transaction().await;
+        let mut scan = tx
+            .scan((Bound::Unbounded, Bound::Unbounded))
+            .take()
+            .await
+            .unwrap();
+        let mut count = 0;
+        while let Some(entry) = scan.next().await {
+            entry.unwrap();
+            count += 1;
+        }
+        assert_eq!(count, test_items().len());
+    }
+
+    #[tokio::test]
+    async fn test_fast_open() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap())
+            .paranoid_checks(true);
+        option.immutable_chunk_num = 1;
+        option.immutable_chunk_max_num = 1;
+
+        let db: DB<Test> = DB::new(option.clone(), TokioExecutor::new()).await.unwrap();
+        for item in test_items() {
+            db.insert(item).await.unwrap();
+        }
+        db.flush().await.unwrap();
+        let gen = db.version_set.current().await.level_slice[0][0].gen;
+        drop(db);
+
+        std::fs::remove_file(
+            temp_dir
+                .path()
+                .join(format!("{}.{}", gen, FileType::Parquet)),
+        )
+        .unwrap();
+        assert!(matches!(
+            DB::<Test>::new(option.clone(), TokioExecutor::new()).await,
+            Err(DbError::Version(VersionError::Inconsistent(_)))
+        ));
+
+        // the missing table is found once the database is open
+        let db: DB<Test> = DB::new(option.fast_open(true), TokioExecutor::new())
+            .await
+            .unwrap();
+        let mut poisoned = false;
+        for _ in 0..100 {
+            if let Err(DbError::Poisoned(_)) = db.insert(test_items().remove(0)).await {
+                poisoned = true;
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(poisoned);
+    }
+
+    #[tokio::test]
+    async fn test_direct_io() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap())
+            .direct_io(true)
+            .paranoid_checks(true);
+        option.immutable_chunk_num = 1;
+        option.immutable_chunk_max_num = 1;
+        option.major_threshold_with_sst_size = 3;
+        option.major_default_oldest_table_num = 1;
+        option.trigger_type = TriggerType::Length(5);
+
+        let db: DB<Test> = DB::new(option.clone(), TokioExecutor::new()).await.unwrap();
+        for (i, item) in test_items().into_iter().enumerate() {
+            db.insert(item).await.unwrap();
+            if i % 5 == 0 {
+                db.flush().await.unwrap();
+            }
+        }
+        db.flush().await.unwrap();
+        drop(db);
+
+        // the tables flushed and compacted are read back without the page cache
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+        for i in 0..40 {
+            assert_eq!(
+                db.get(&i.to_string(), |entry| entry.get().vu32)
+                    .await
+                    .unwrap(),
+                Some(i)
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_preallocate_tables() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap())
+            .preallocate_tables(true)
+            .paranoid_checks(true);
+        option.immutable_chunk_num = 1;
+        option.immutable_chunk_max_num = 1;
+        option.major_threshold_with_sst_size = 3;
+        option.major_default_oldest_table_num = 1;
+        option.trigger_type = TriggerType::Length(5);
+
+        let db: DB<Test> = DB::new(option.clone(), TokioExecutor::new()).await.unwrap();
+        for (i, item) in test_items().into_iter().enumerate() {
+            db.insert(item).await.unwrap();
+            if i % 5 == 0 {
+                db.flush().await.unwrap();
+            }
+        }
+        db.flush().await.unwrap();
+        drop(db);
+
+        // the tables keep their own size once the disk reserved past them is released
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+        for i in 0..40 {
+            assert_eq!(
+                db.get(&i.to_string(), |entry| entry.get().vu32)
+                    .await
+                    .unwrap(),
+                Some(i)
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_preallocate_wal() {
+        let temp_dir = TempDir::new().unwrap();
+        let option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap())
+            .wal_segment_size(1024)
+            .preallocate_wal(true);
+
+        let db: DB<Test> = DB::new(option.clone(), TokioExecutor::new()).await.unwrap();
+        let items = test_items();
+        for item in items.clone() {
+            db.insert(item).await.unwrap();
+        }
+        db.flush_wal().await.unwrap();
+        // the segments keep the size of their entries, the disk reserved past them is not read
+        let segments = std::fs::read_dir(temp_dir.path().join("wal"))
+            .unwrap()
+            .map(|entry| entry.unwrap().metadata().unwrap().len())
+            .collect::<Vec<_>>();
+        assert!(segments.len() > 1);
+        assert!(segments.iter().all(|len| *len < 2048));
+        drop(db);
+
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+        for item in &items {
+            assert_eq!(
+                db.get(&item.vstring, |entry| entry.get().vu32)
+                    .await
+                    .unwrap(),
+                Some(item.vu32)
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sst_compression() {
+        let temp_dir = TempDir::new().unwrap();
+        let option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap())
+            .sst_compression(SstCompression::Snappy);
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+        for item in test_items() {
+            db.insert(item).await.unwrap();
+        }
+        db.flush().await.unwrap();
+
+        let version = db.version_set.current().await;
+        let scope = version.level_slice[0].first().unwrap();
+        let metadata = version
+            .table_metadata(&db.manager, 0, scope.gen, db.parquet_lru.clone())
+            .await
+            .unwrap();
+        let row_group = metadata.row_group(0);
+        assert!(row_group
+            .columns()
+            .iter()
+            .all(|column| column.compression() == Compression::SNAPPY));
+    }
+
+    #[tokio::test]
+    async fn test_level_sst_options() {
+        let temp_dir = TempDir::new().unwrap();
+        let option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap())
+            .sst_compression(SstCompression::Snappy)
+            .level_sst_compression(0, SstCompression::None)
+            .unwrap()
+            .level_sst_dictionary(0, false)
+            .unwrap();
+        assert!(option
+            .clone()
+            .level_sst_compression(MAX_LEVEL, SstCompression::None)
+            .is_err());
+        assert_eq!(
+            option
+                .sst_properties(1)
+                .compression(&ColumnPath::from("vstring")),
+            Compression::SNAPPY
+        );
+
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+        for item in test_items() {
+            db.insert(item).await.unwrap();
+        }
+        db.flush().await.unwrap();
+
+        let version = db.version_set.current().await;
+        let scope = version.level_slice[0].first().unwrap();
+        let metadata = version
+            .table_metadata(&db.manager, 0, scope.gen, db.parquet_lru.clone())
+            .await
+            .unwrap();
+        assert!(metadata.row_group(0).columns().iter().all(|column| {
+            column.compression() == Compression::UNCOMPRESSED
+                && column.dictionary_page_offset().is_none()
+        }));
+    }
+
+    #[tokio::test]
+    async fn test_sst_row_group_size() {
+        let temp_dir = TempDir::new().unwrap();
+        let option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap())
+            .sst_max_row_group_size(4)
+            .sst_data_page_size(64)
+            .sst_write_batch_size(2);
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+        for item in test_items() {
+            db.insert(item).await.unwrap();
+        }
+        db.flush().await.unwrap();
+
+        let version = db.version_set.current().await;
+        let scope = version.level_slice[0].first().unwrap();
+        let metadata = version
+            .table_metadata(&db.manager, 0, scope.gen, db.parquet_lru.clone())
+            .await
+            .unwrap();
+        assert!(metadata.num_row_groups() > 1);
+        assert!(metadata
+            .row_groups()
+            .iter()
+            .all(|row_group| row_group.num_rows() <= 4));
+
+        assert_eq!(
+            db.get(&"5".to_string(), |entry| entry.get().vu32)
+                .await
+                .unwrap(),
+            Some(5)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_row_groups() {
+        let temp_dir = TempDir::new().unwrap();
+        let option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap())
+            .sst_max_row_group_size(2);
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+        for item in test_items() {
+            db.insert(item).await.unwrap();
+        }
+        // the versions of the key span several row groups
+        for vu32 in 100..105 {
+            db.insert(Test {
+                vstring: "5".to_string(),
+                vu32,
+                vbool: None,
+            })
+            .await
+            .unwrap();
+        }
+        db.flush().await.unwrap();
+
+        for _ in 0..2 {
+            for item in test_items().into_iter().filter(|item| item.vstring != "5") {
+                assert_eq!(
+                    db.get(&item.vstring, |entry| entry.get().vu32)
+                        .await
+                        .unwrap(),
+                    Some(item.vu32)
+                );
+            }
+            assert_eq!(
+                db.get(&"5".to_string(), |entry| entry.get().vu32)
+                    .await
+                    .unwrap(),
+                Some(104)
+            );
+            assert!(db
+                .get(&"55".to_string(), |entry| entry.get().vu32)
+                .await
+                .unwrap()
+                .is_none());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_scan_page_index() {
+        let temp_dir = TempDir::new().unwrap();
+        // a page per row
+        let option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap())
+            .sst_data_page_size(1)
+            .sst_write_batch_size(1);
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+        for item in test_items() {
+            db.insert(item).await.unwrap();
+        }
+        db.flush().await.unwrap();
+
+        let (lower, upper) = ("12".to_string(), "25".to_string());
+        for range in [
+            (Bound::Included(&lower), Bound::Excluded(&upper)),
+            (Bound::Excluded(&lower), Bound::Included(&upper)),
+            (Bound::Excluded(&upper), Bound::Unbounded),
+            (Bound::Unbounded, Bound::Included(&lower)),
+        ] {
+            let mut expected: Vec<String> = test_items()
+                .into_iter()
+                .map(|item| item.vstring)
+                .filter(|key| std::ops::RangeBounds::contains(&range, key))
+                .collect();
+            expected.sort();
+
+            let snapshot = db.snapshot().await;
+            let mut scan = snapshot.scan(range).take().await.unwrap();
+            let mut keys = Vec::new();
+            while let Some(entry) = scan.next().await.transpose().unwrap() {
+                keys.push(entry.key().value.to_string());
+            }
+            assert_eq!(keys, expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_table_stats() {
+        let temp_dir = TempDir::new().unwrap();
+        let option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap());
+        let db: DB<Test> = DB::new(option.clone(), TokioExecutor::new()).await.unwrap();
+        for item in test_items() {
+            db.insert(item).await.unwrap();
+        }
+        db.remove("1".to_string()).await.unwrap();
+        db.flush().await.unwrap();
+
+        let stats = db.version_set.current().await.level_slice[0][0]
+            .stats
+            .clone()
+            .unwrap();
+        assert_eq!(stats.rows, 41);
+        assert_eq!(stats.tombstones, 1);
+        assert_eq!(stats.columns.len(), 3);
+        assert_eq!(stats.columns[0].min, Some(b"0".to_vec()));
+        assert_eq!(stats.columns[0].max, Some(b"9".to_vec()));
+        assert_eq!(stats.columns[0].null_count, Some(0));
+        assert_eq!(stats.columns[1].max, Some(39u32.to_le_bytes().to_vec()));
+        assert_eq!(db.approximate_len().await.unwrap(), 41);
+
+        db.flush_wal().await.unwrap();
+        drop(db);
+        // recovered from the version log
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+        assert_eq!(
+            db.version_set.current().await.level_slice[0][0].stats,
+            Some(stats)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_tiered_compaction() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap())
+            .compaction_style(CompactionStyle::Tiered {
+                max_runs: 3,
+                size_ratio: 50,
+                min_merge_width: 2,
+            });
+        option.immutable_chunk_num = 1;
+        option.immutable_chunk_max_num = 0;
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+
+        for round in 0..8 {
+            for mut item in test_items() {
+                // the tombstone has to outlive the merges of the older runs
+                if item.vstring == "1" && round >= 5 {
+                    continue;
+                }
+                item.vu32 += round * 100;
+                db.insert(item).await.unwrap();
+            }
+            if round == 5 {
+                db.remove("1".to_string()).await.unwrap();
+            }
+            db.flush().await.unwrap();
+
+            let version = db.version_set.current().await;
+            let runs = version.level_slice[0].len()
+                + version.level_slice[1..]
+                    .iter()
+                    .filter(|level| !level.is_empty())
+                    .count();
+            // the runs are merged before the flushed table is added
+            assert!(runs <= 4, "{} runs", runs);
+        }
+        assert!(db.verify_integrity().await.unwrap().is_empty());
+
+        for item in test_items() {
+            let vu32 = db
+                .get(&item.vstring, |entry| entry.get().vu32)
+                .await
+                .unwrap();
+            if item.vstring == "1" {
+                assert_eq!(vu32, None);
+            } else {
+                assert_eq!(vu32, Some(item.vu32 + 700));
+            }
+        }
+        let tx = db.transaction().await;
+        let mut scan = tx
+            .scan((Bound::Unbounded, Bound::Unbounded))
+            .take()
+            .await
+            .unwrap();
+        let mut len = 0;
+        while let Some(entry) = scan.next().await.transpose().unwrap() {
+            if let Some(value) = entry.value() {
+                assert!(value.vu32.unwrap() >= 700);
+                len += 1;
+            }
+        }
+        assert_eq!(len, test_items().len() - 1);
+    }
+
+    #[tokio::test]
+    async fn test_trivial_move() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap());
+        option.immutable_chunk_num = 1;
+        option.immutable_chunk_max_num = 0;
+        option.major_threshold_with_sst_size = 2;
+        option.level_sst_magnification = 10;
+        let db: DB<Test> = DB::new(option.clone(), TokioExecutor::new()).await.unwrap();
+
+        // sequential keys, so no table overlaps another
+        let mut flushed = Vec::new();
+        for i in 0..40u32 {
+            db.insert(Test {
+                vstring: format!("{:02}", i),
+                vu32: i,
+                vbool: Some(true),
+            })
+            .await
+            .unwrap();
+            if i % 10 == 9 {
+                db.flush().await.unwrap();
+                flushed.extend(
+                    db.version_set.current().await.level_slice[0]
+                        .iter()
+                        .map(|scope| scope.gen),
+                );
+            }
+        }
+        let version = db.version_set.current().await;
+        assert!(!version.level_slice[1].is_empty());
+        for scope in version.level_slice[1].iter() {
+            assert!(flushed.contains(&scope.gen));
+        }
+        let moved = version.level_slice[1].clone();
+        drop(version);
+        assert!(db.verify_integrity().await.unwrap().is_empty());
+
+        db.flush_wal().await.unwrap();
+        drop(db);
+        // the recovery keeps the files of the moved tables
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+        assert_eq!(db.version_set.current().await.level_slice[1], moved);
+        for i in 0..40u32 {
+            let vu32 = db
+                .get(&format!("{:02}", i), |entry| entry.get().vu32)
+                .await
+                .unwrap();
+            assert_eq!(vu32, Some(i));
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_background_compactions() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap())
+            .max_background_compactions(2);
+        option.immutable_chunk_num = 1;
+        option.immutable_chunk_max_num = 0;
+        option.major_threshold_with_sst_size = 2;
+        option.level_sst_magnification = 2;
+        let db: DB<Test> = DB::new(option.clone(), TokioExecutor::new()).await.unwrap();
+
+        for round in 0..8 {
+            for mut item in test_items() {
+                item.vu32 += round * 100;
+                db.insert(item).await.unwrap();
+            }
+            db.flush().await.unwrap();
+        }
+        // the flushes only wake the background compactions up
+        for _ in 0..500 {
+            let version = db.version_set.current().await;
+            if (0..MAX_LEVEL - 2).all(|level| !option.is_threshold_exceeded_major(&version, level))
+            {
+                break;
+            }
+            drop(version);
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let version = db.version_set.current().await;
+        assert!(version.level_slice[0].len() < 2);
+        assert!(!version.level_slice[1].is_empty());
+        drop(version);
+        assert!(db.verify_integrity().await.unwrap().is_empty());
+
+        for item in test_items() {
+            let vu32 = db
+                .get(&item.vstring, |entry| entry.get().vu32)
+                .await
+                .unwrap();
+            assert_eq!(vu32, Some(item.vu32 + 700));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_drop_tombstones() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap())
+            .tombstone_compaction_ratio(0.5);
+        option.immutable_chunk_num = 1;
+        option.immutable_chunk_max_num = 0;
+        option.major_threshold_with_sst_size = 2;
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+
+        for item in test_items() {
+            db.insert(item).await.unwrap();
+        }
+        db.flush().await.unwrap();
+        for item in test_items() {
+            db.remove(item.vstring).await.unwrap();
+        }
+        db.flush().await.unwrap();
+        db.insert(Test {
+            vstring: "x".to_string(),
+            vu32: 0,
+            vbool: None,
+        })
+        .await
+        .unwrap();
+        db.flush().await.unwrap();
+
+        // nothing is left below the compacted tables for the tombstones to hide
+        let version = db.version_set.current().await;
+        assert!(version.level_slice[1].is_empty());
+        assert_eq!(version.level_slice[0].len(), 1);
+        drop(version);
+        for item in test_items() {
+            let vu32 = db
+                .get(&item.vstring, |entry| entry.get().vu32)
+                .await
+                .unwrap();
+            assert_eq!(vu32, None);
+        }
+        assert_eq!(
+            db.get(&"x".to_string(), |entry| entry.get().vu32)
+                .await
+                .unwrap(),
+            Some(0)
+        );
+        assert!(db.verify_integrity().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_seek_compaction() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap())
+            .bytes_per_seek(u64::MAX);
+        option.immutable_chunk_num = 1;
+        option.immutable_chunk_max_num = 0;
+        option.major_threshold_with_sst_size = 2;
+        option.level_sst_magnification = 1;
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+        let flush = |keys: Vec<(String, u32)>| {
+            let db = &db;
+            async move {
+                for (vstring, vu32) in keys {
+                    db.insert(Test {
+                        vstring,
+                        vu32,
+                        vbool: None,
+                    })
+                    .await
+                    .unwrap();
+                }
+                db.flush().await.unwrap();
+            }
+        };
+        let keys = |range: std::ops::Range<u32>| {
+            range.map(|i| (format!("{:02}", i), i)).collect::<Vec<_>>()
+        };
+
+        // disjoint tables moved down, until the level 1 holds only two keys of a range of level 2
+        for range in [0..10, 10..20, 20..30, 30..40] {
+            flush(keys(range)).await;
+        }
+        flush(vec![("00".to_string(), 100), ("09".to_string(), 109)]).await;
+        flush(vec![("x".to_string(), 0)]).await;
+        flush(vec![("y".to_string(), 0)]).await;
+        let version = db.version_set.current().await;
+        let fragmented = version.level_slice[1]
+            .iter()
+            .find(|scope| scope.contains(&"05".to_string()))
+            .unwrap()
+            .gen;
+        assert!(version.level_slice[2]
+            .iter()
+            .any(|scope| scope.contains(&"05".to_string())));
+        drop(version);
+
+        // the gets of the keys only in level 2 read the table of level 1 first
+        for _ in 0..100 {
+            assert_eq!(
+                db.get(&"05".to_string(), |entry| entry.get().vu32)
+                    .await
+                    .unwrap(),
+                Some(5)
+            );
+        }
+        let version = db.version_set.current().await;
+        let (level, hot) = version.seeks().hot(&version).unwrap();
+        assert_eq!((level, hot.gen), (1, fragmented));
+        drop(version);
+
+        flush(vec![("z".to_string(), 0)]).await;
+        let version = db.version_set.current().await;
+        assert!(version
+            .level_slice
+            .iter()
+            .flatten()
+            .all(|scope| scope.gen != fragmented));
+        assert!(version.seeks().hot(&version).is_none());
+        drop(version);
+        for (key, vu32) in [("00", 100), ("05", 5), ("09", 109)] {
+            assert_eq!(
+                db.get(&key.to_string(), |entry| entry.get().vu32)
+                    .await
+                    .unwrap(),
+                Some(vu32)
+            );
+        }
+        assert!(db.verify_integrity().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_compaction_memory_budget() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap())
+            .compaction_memory_budget(1);
+        option.immutable_chunk_num = 1;
+        option.immutable_chunk_max_num = 0;
+        option.major_threshold_with_sst_size = 2;
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+
+        for round in 0..2 {
+            for mut item in test_items() {
+                item.vu32 += round * 100;
+                db.insert(item).await.unwrap();
+            }
+            db.flush().await.unwrap();
+        }
+        db.insert(Test {
+            vstring: "x".to_string(),
+            vu32: 0,
+            vbool: None,
+        })
+        .await
+        .unwrap();
+        db.flush().await.unwrap();
+
+        // each merged record is written out on its own
+        let version = db.version_set.current().await;
+        assert_eq!(version.level_slice[1].len(), 1);
+        let scope = &version.level_slice[1][0];
+        let metadata = version
+            .table_metadata(&db.manager, 1, scope.gen, db.parquet_lru.clone())
+            .await
+            .unwrap();
+        assert!(metadata.num_row_groups() > 1);
+        assert_eq!(scope.stats.as_ref().unwrap().rows, 40);
+        drop(version);
+        assert_eq!(db.memory_usage().await.compaction, 0);
+
+        for item in test_items() {
+            let vu32 = db
+                .get(&item.vstring, |entry| entry.get().vu32)
+                .await
+                .unwrap();
+            assert_eq!(vu32, Some(item.vu32 + 100));
+        }
+        assert!(db.verify_integrity().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_compact_range() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap());
+        option.immutable_chunk_num = 1;
+        option.immutable_chunk_max_num = 0;
+        option.major_threshold_with_sst_size = 10;
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+
+        for round in 0..2 {
+            for mut item in test_items() {
+                item.vu32 += round * 100;
+                db.insert(item).await.unwrap();
+            }
+            db.flush().await.unwrap();
+        }
+        db.remove("1".to_string()).await.unwrap();
+        db.flush().await.unwrap();
+        db.insert(Test {
+            vstring: "x".to_string(),
+            vu32: 0,
+            vbool: None,
+        })
+        .await
+        .unwrap();
+        db.flush().await.unwrap();
+
+        let (lower, upper) = ("1".to_string(), "2".to_string());
+        let range = (Bound::Included(&lower), Bound::Included(&upper));
+        assert!(matches!(
+            db.compact_range(range, MAX_LEVEL).await,
+            Err(CompactionError::ExceedsMaxLevel)
+        ));
+        db.compact_range(range, 2).await.unwrap();
+
+        // the tables overlapping the range and each other, but not the one of "x"
+        let version = db.version_set.current().await;
+        assert_eq!(version.level_slice[0].len(), 1);
+        assert_eq!(version.level_slice[0][0].min, "x".to_string());
+        assert!(version.level_slice[1].is_empty());
+        assert_eq!(
+            version.level_slice[2]
+                .iter()
+                .map(|scope| scope.stats.as_ref().unwrap().rows)
+                .sum::<u64>(),
+            39
+        );
+        drop(version);
+        for item in test_items() {
+            let vu32 = db
+                .get(&item.vstring, |entry| entry.get().vu32)
+                .await
+                .unwrap();
+            let expected = (item.vstring != "1").then_some(item.vu32 + 100);
+            assert_eq!(vu32, expected);
+        }
+
+        // nothing in the levels down to the target overlaps the range
+        db.compact_range(range, 1).await.unwrap();
+        assert_eq!(db.version_set.current().await.level_slice[0].len(), 1);
+        assert!(db.verify_integrity().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_periodic_compaction() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap())
+            .periodic_compaction_interval(Duration::ZERO);
+        option.immutable_chunk_num = 1;
+        option.immutable_chunk_max_num = 0;
+        option.major_threshold_with_sst_size = 10;
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+
+        for item in test_items() {
+            db.insert(item).await.unwrap();
+        }
+        db.flush().await.unwrap();
+        let flushed = db.version_set.current().await.level_slice[0][0].gen;
+        db.insert(Test {
+            vstring: "x".to_string(),
+            vu32: 0,
+            vbool: None,
+        })
+        .await
+        .unwrap();
+        db.flush().await.unwrap();
+
+        // rewritten although level 0 is far from its threshold, instead of being moved
+        let version = db.version_set.current().await;
+        assert_eq!(version.level_slice[0].len(), 1);
+        assert_eq!(version.level_slice[1].len(), 1);
+        assert_ne!(version.level_slice[1][0].gen, flushed);
+        assert_eq!(version.level_slice[1][0].stats.as_ref().unwrap().rows, 40);
+        drop(version);
+        for item in test_items() {
+            let vu32 = db
+                .get(&item.vstring, |entry| entry.get().vu32)
+                .await
+                .unwrap();
+            assert_eq!(vu32, Some(item.vu32));
+        }
+        assert!(db.verify_integrity().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_cancel_compaction() {
         let temp_dir = TempDir::new().unwrap();
-
         let mut option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap());
         option.immutable_chunk_num = 1;
-        option.immutable_chunk_max_num = 1;
-        option.major_threshold_with_sst_size = 3;
-        option.level_sst_magnification = 10;
-        option.max_sst_file_size = 2 * 1024 * 1024;
-        option.major_default_oldest_table_num = 1;
-        option.trigger_type = TriggerType::Length(/* max_mutable_len */ 50);
+        option.immutable_chunk_max_num = 0;
+        option.major_threshold_with_sst_size = 10;
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+
+        for round in 0..2 {
+            for mut item in test_items() {
+                item.vu32 += round * 100;
+                db.insert(item).await.unwrap();
+            }
+            db.flush().await.unwrap();
+        }
+        assert!(db.compaction_jobs().is_empty());
+        assert!(!db.cancel_compaction(u64::MAX));
+
+        let (lower, upper) = ("1".to_string(), "2".to_string());
+        let range = (Bound::Included(&lower), Bound::Included(&upper));
+        let cancel = async {
+            for _ in 0..1000 {
+                if let Some(job) = db.compaction_jobs().first() {
+                    assert_eq!(job.inputs.len(), 2);
+                    assert!(db.cancel_compaction(job.id));
+                    return;
+                }
+                tokio::task::yield_now().await;
+            }
+        };
+        let (result, _) = tokio::join!(db.compact_range(range, 1), cancel);
+        assert!(matches!(result, Err(CompactionError::Cancelled)));
+        assert!(db.compaction_jobs().is_empty());
 
-        let db: DB<Test, TokioExecutor> = DB::new(option, TokioExecutor::new()).await.unwrap();
+        // neither the version nor the directory holds the tables written
+        let version = db.version_set.current().await;
+        assert_eq!(version.level_slice[0].len(), 2);
+        assert!(version.level_slice[1].is_empty());
+        drop(version);
+        let tables = std::fs::read_dir(temp_dir.path())
+            .unwrap()
+            .filter(|entry| {
+                entry
+                    .as_ref()
+                    .unwrap()
+                    .path()
+                    .extension()
+                    .is_some_and(|extension| extension == "parquet")
+            })
+            .count();
+        assert_eq!(tables, 2);
 
-        for item in &test_items()[0..10] {
-            db.write(item.clone(), 0.into()).await.unwrap();
-        }
-        db.flush().await.unwrap();
-        for item in &test_items()[10..20] {
-            db.write(item.clone(), 0.into()).await.unwrap();
+        // nor is the database poisoned
+        db.compact_range(range, 1).await.unwrap();
+        for item in test_items() {
+            let vu32 = db
+                .get(&item.vstring, |entry| entry.get().vu32)
+                .await
+                .unwrap();
+            assert_eq!(vu32, Some(item.vu32 + 100));
         }
+    }
 
-        dbg!(db.version_set.current().await);
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_write_stall() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap())
+            .max_background_compactions(2)
+            .level0_slowdown_writes_trigger(1)
+            .level0_stop_writes_trigger(2)
+            .write_slowdown_delay(Duration::from_millis(50));
+        option.immutable_chunk_num = 1;
+        option.immutable_chunk_max_num = 0;
+        option.major_threshold_with_sst_size = 3;
+
+        // not blocked before level 0 is compacted
+        assert_eq!(option.write_stall(0, 2), WriteStall::Slowdown);
+        assert_eq!(option.write_stall(0, 3), WriteStall::Stop);
+        // nor before the immutables are flushed
+        let immutables = option.clone().immutable_stop_writes_trigger(0);
+        assert_eq!(immutables.write_stall(0, 0), WriteStall::None);
+        assert_eq!(immutables.write_stall(1, 0), WriteStall::Stop);
+        // level 0 is left to the flushes without background compactions
+        let serial = option.clone().max_background_compactions(1);
+        assert_eq!(serial.write_stall(0, 3), WriteStall::None);
+
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+        let item = |i: u32| Test {
+            vstring: i.to_string(),
+            vu32: i,
+            vbool: None,
+        };
+        db.insert(item(0)).await.unwrap();
+        db.flush().await.unwrap();
+        let start = std::time::Instant::now();
+        db.insert(item(1)).await.unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(50));
+        db.flush().await.unwrap();
+        db.insert(item(2)).await.unwrap();
         db.flush().await.unwrap();
-        dbg!(db.version_set.current().await);
 
-        let version = db.version_set.current().await;
-        assert!(!version.level_slice[0].is_empty());
+        // waits for the background compactions to take level 0 below 3 tables
+        db.insert(item(3)).await.unwrap();
+        assert!(db.version_set.current().await.level_slice[0].len() < 3);
+        for i in 0..4 {
+            let vu32 = db
+                .get(&i.to_string(), |entry| entry.get().vu32)
+                .await
+                .unwrap();
+            assert_eq!(vu32, Some(i));
+        }
     }
 
     #[tokio::test]
-    async fn schema_recover() {
-        let temp_dir = TempDir::new().unwrap();
-        let fs = Arc::new(TokioFs) as Arc<dyn DynFs>;
+    async fn test_shared_parquet_lru() {
+        #[derive(Default)]
+        struct CountingCache {
+            readers: std::sync::Mutex<Vec<FileId>>,
+        }
 
-        let option = Arc::new(DbOption::from(
-            Path::from_filesystem_path(temp_dir.path()).unwrap(),
-        ));
-        fs.create_dir_all(&option.wal_dir_path()).await.unwrap();
+        impl LruCache<FileId> for CountingCache {
+            type LruReader<R>
+                = R
+            where
+                R: parquet::arrow::async_reader::AsyncFileReader + 'static;
+
+            async fn get_reader<R>(&self, key: FileId, reader: R) -> R
+            where
+                R: parquet::arrow::async_reader::AsyncFileReader + 'static,
+            {
+                self.readers.lock().unwrap().push(key);
+                reader
+            }
 
-        let (task_tx, _task_rx) = bounded(1);
+            fn memory_usage(&self) -> usize {
+                42
+            }
+        }
 
-        let trigger = Arc::new(TriggerFactory::create(option.trigger_type));
-        let schema: crate::Schema<Test> = crate::Schema {
-            mutable: Mutable::new(&option, trigger.clone(), &fs).await.unwrap(),
-            immutables: Default::default(),
-            compaction_tx: task_tx.clone(),
-            recover_wal_ids: None,
-            trigger,
-            record_instance: RecordInstance::Normal,
-        };
+        let cache = Arc::new(CountingCache::default());
+        let dirs = [TempDir::new().unwrap(), TempDir::new().unwrap()];
+        let mut dbs = Vec::new();
+        for dir in &dirs {
+            let option = DbOption::from(Path::from_filesystem_path(dir.path()).unwrap())
+                .parquet_lru(cache.clone());
+            let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+            db.insert(test_items()[0].clone()).await.unwrap();
+            db.flush().await.unwrap();
+            dbs.push(db);
+        }
 
-        for (i, item) in test_items().into_iter().enumerate() {
-            schema
-                .write(LogType::Full, item, (i as u32).into())
-                .await
-                .unwrap();
+        for db in &dbs {
+            assert_eq!(
+                db.get(&test_items()[0].vstring, |entry| Some(entry.get().vu32))
+                    .await
+                    .unwrap(),
+                Some(0)
+            );
+            assert_eq!(db.memory_usage().await.cache, 42);
         }
-        schema.flush_wal().await.unwrap();
-        drop(schema);
+        let mut gens = Vec::new();
+        for db in &dbs {
+            gens.push(db.version_set.current().await.level_slice[0][0].gen);
+        }
+        let readers = cache.readers.lock().unwrap();
+        assert!(gens.iter().all(|gen| readers.contains(gen)));
+    }
 
-        let db: DB<Test, TokioExecutor> = DB::new(option.as_ref().to_owned(), TokioExecutor::new())
-            .await
-            .unwrap();
+    #[tokio::test]
+    async fn test_scan_prefetch_and_readahead() {
+        let mut expected = test_items()
+            .into_iter()
+            .map(|item| (item.vstring, item.vu32))
+            .collect::<Vec<_>>();
+        expected.sort();
+
+        for (prefetch, readahead) in [(0, 0), (1, 0), (3, 0), (0, 64), (1, 1 << 20)] {
+            let temp_dir = TempDir::new().unwrap();
+            let mut option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap())
+                .sst_max_row_group_size(4)
+                .scan_prefetch_batches(prefetch)
+                .scan_readahead_bytes(readahead);
+            option.immutable_chunk_num = 1;
+            option.immutable_chunk_max_num = 0;
+            let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+            // spread over the batches of two tables
+            for (i, item) in test_items().into_iter().enumerate() {
+                db.insert(item).await.unwrap();
+                if i == 10 {
+                    db.flush().await.unwrap();
+                }
+            }
+            db.flush().await.unwrap();
 
-        let mut sort_items = BTreeMap::new();
-        for item in test_items() {
-            sort_items.insert(item.vstring.clone(), item);
-        }
-        {
             let tx = db.transaction().await;
             let mut scan = tx
                 .scan((Bound::Unbounded, Bound::Unbounded))
                 .take()
                 .await
                 .unwrap();
-
+            let mut scanned = Vec::new();
             while let Some(entry) = scan.next().await.transpose().unwrap() {
-                let (_, test) = sort_items.pop_first().unwrap();
-
-                assert_eq!(entry.key().value, test.key());
-                assert_eq!(entry.value().as_ref().unwrap().vstring, test.vstring);
-                assert_eq!(entry.value().as_ref().unwrap().vu32, Some(test.vu32));
-                assert_eq!(entry.value().as_ref().unwrap().vbool, test.vbool);
+                let value = entry.value().unwrap();
+                scanned.push((value.vstring.to_string(), value.vu32.unwrap()));
             }
+            assert_eq!(scanned, expected);
         }
     }
 
     #[tokio::test]
-    async fn dyn_schema_recover() {
+    async fn test_take_batches() {
         let temp_dir = TempDir::new().unwrap();
-        let manager = StoreManager::new(FsOptions::Local, vec![]).unwrap();
+        let mut option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap());
+        option.immutable_chunk_num = 1;
+        option.immutable_chunk_max_num = 0;
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
 
-        let (desc, primary_key_index) = test_dyn_item_schema();
-        let option = Arc::new(DbOption::with_path(
-            Path::from_filesystem_path(temp_dir.path()).unwrap(),
-            "id".to_owned(),
-            primary_key_index,
-        ));
-        manager
-            .base_fs()
-            .create_dir_all(&option.wal_dir_path())
+        let items = test_items();
+        for item in items.iter().take(20).cloned() {
+            db.insert(item).await.unwrap();
+        }
+        db.flush().await.unwrap();
+        for item in items.iter().skip(20).cloned() {
+            db.insert(item).await.unwrap();
+        }
+        db.remove(items[0].vstring.clone()).await.unwrap();
+
+        let snapshot = db.snapshot().await;
+        let batches = snapshot
+            .scan((Bound::Unbounded, Bound::Unbounded))
+            .take_batches(8)
             .await
+            .unwrap()
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()
             .unwrap();
+        assert!(batches.len() > 1);
+        let names = batches[0]
+            .schema()
+            .fields()
+            .iter()
+            .map(|field| field.name().clone())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["vstring", "vu32", "vbool"]);
+        assert_eq!(
+            batches.iter().map(RecordBatch::num_rows).sum::<usize>(),
+            items.len() - 1
+        );
+    }
 
-        let (task_tx, _task_rx) = bounded(1);
+    #[tokio::test]
+    async fn test_write_size_limits() {
+        let record = |key: &str| Test {
+            vstring: key.to_string(),
+            vu32: 0,
+            vbool: None,
+        };
+        let max_record_size = Record::size(&record(&"k".repeat(8)));
+        let temp_dir = TempDir::new().unwrap();
+        let option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap())
+            .max_key_size(32)
+            .max_record_size(max_record_size);
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+
+        db.insert(record(&"k".repeat(8))).await.unwrap();
+        assert!(matches!(
+            db.insert(record(&"k".repeat(16))).await,
+            Err(CommitError::Database(DbError::RecordTooLarge { size, limit }))
+                if size > limit && limit == max_record_size
+        ));
+        assert!(matches!(
+            db.remove("k".repeat(64)).await,
+            Err(CommitError::Database(DbError::KeyTooLarge { .. }))
+        ));
+        let long_key = "k".repeat(64);
+        assert!(matches!(
+            db.delete_range((Bound::Unbounded, Bound::Excluded(&long_key)))
+                .await,
+            Err(CommitError::Database(DbError::KeyTooLarge { .. }))
+        ));
 
-        let trigger = Arc::new(TriggerFactory::create(option.trigger_type));
-        let schema: crate::Schema<DynRecord> = crate::Schema {
-            mutable: Mutable::new(&option, trigger.clone(), manager.base_fs())
+        // none of a batch or a transaction is written
+        assert!(db
+            .insert_batch(vec![record("a"), record(&"k".repeat(16))].into_iter())
+            .await
+            .is_err());
+        let mut txn = db.transaction().await;
+        txn.insert(record("b"));
+        txn.remove("k".repeat(64));
+        assert!(matches!(
+            txn.commit().await,
+            Err(CommitError::Database(DbError::KeyTooLarge { .. }))
+        ));
+        for key in ["a", "b"] {
+            assert_eq!(
+                db.get(&key.to_string(), |entry| entry.get().vu32)
+                    .await
+                    .unwrap(),
+                None
+            );
+        }
+        assert_eq!(
+            db.get(&"k".repeat(8), |entry| entry.get().vu32)
                 .await
                 .unwrap(),
-            immutables: Default::default(),
-            compaction_tx: task_tx.clone(),
-            recover_wal_ids: None,
-            trigger,
-            record_instance: RecordInstance::Normal,
-        };
+            Some(0)
+        );
+    }
 
-        for item in test_dyn_items().into_iter() {
-            schema
-                .write(LogType::Full, item, 0_u32.into())
-                .await
-                .unwrap();
-        }
-        schema.flush_wal().await.unwrap();
-        drop(schema);
+    #[tokio::test]
+    async fn test_invalid_option() {
+        let temp_dir = TempDir::new().unwrap();
+        let option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap());
+        assert_eq!(option.validate(), Ok(()));
 
-        let option = DbOption::with_path(
-            Path::from_filesystem_path(temp_dir.path()).unwrap(),
-            "id".to_owned(),
-            primary_key_index,
+        let stalls = option
+            .clone()
+            .level0_slowdown_writes_trigger(8)
+            .level0_stop_writes_trigger(4);
+        assert!(matches!(
+            stalls.validate(),
+            Err(OptionError::SlowdownPastStop {
+                slowdown: "level0_slowdown_writes_trigger",
+                ..
+            })
+        ));
+        assert!(matches!(
+            DB::<Test>::new(stalls, TokioExecutor::new()).await,
+            Err(DbError::InvalidOption(OptionError::SlowdownPastStop { .. }))
+        ));
+        assert_eq!(
+            option.clone().level_sst_magnification(0).validate(),
+            Err(OptionError::TooSmall {
+                option: "level_sst_magnification",
+                value: 0,
+                min: 1,
+            })
         );
-        let db: DB<DynRecord, TokioExecutor> =
-            DB::with_schema(option, TokioExecutor::new(), desc, primary_key_index)
-                .await
-                .unwrap();
-
-        let mut sort_items = BTreeMap::new();
-        for item in test_dyn_items() {
-            sort_items.insert(item.key(), item);
-        }
+        assert_eq!(
+            option.tombstone_compaction_ratio(1.5).validate(),
+            Err(OptionError::TombstoneRatio(1.5))
+        );
+    }
 
-        {
-            let tx = db.transaction().await;
-            let mut scan = tx
-                .scan((Bound::Unbounded, Bound::Unbounded))
-                .projection(vec![0, 1, 2])
-                .take()
-                .await
-                .unwrap();
+    #[tokio::test]
+    async fn test_set_options() {
+        let temp_dir = TempDir::new().unwrap();
+        let option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap());
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
 
-            while let Some(entry) = scan.next().await.transpose().unwrap() {
-                let columns1 = entry.value().unwrap().columns;
-                let (_, record) = sort_items.pop_first().unwrap();
-                let columns2 = record.as_record_ref().columns;
+        db.set_options(MutableOptions {
+            major_threshold_with_sst_size: Some(8),
+            max_writes_per_sec: Some(10),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(db.version_set.option().major_threshold_with_sst_size, 8);
+
+        // rejected as a whole, keeping the options as they were
+        assert!(matches!(
+            db.set_options(MutableOptions {
+                major_threshold_with_sst_size: Some(2),
+                level_sst_magnification: Some(0),
+                ..Default::default()
+            }),
+            Err(DbError::InvalidOption(OptionError::TooSmall { .. }))
+        ));
+        assert_eq!(db.version_set.option().major_threshold_with_sst_size, 8);
 
-                assert_eq!(columns1.get(1), columns2.get(1));
-                assert_eq!(columns1.get(2), columns2.get(2));
-            }
+        let start = std::time::Instant::now();
+        for item in test_items().into_iter().take(15) {
+            db.insert(item).await.unwrap();
         }
+        assert!(start.elapsed() >= Duration::from_millis(450));
     }
 
     #[tokio::test]
-    async fn test_get_removed() {
+    async fn test_commit_at() {
         let temp_dir = TempDir::new().unwrap();
-
         let mut option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap());
         option.immutable_chunk_num = 1;
-        option.immutable_chunk_max_num = 1;
-        option.major_threshold_with_sst_size = 3;
-        option.major_default_oldest_table_num = 1;
-        option.trigger_type = TriggerType::Length(5);
-        let db: DB<Test, TokioExecutor> = DB::new(option, TokioExecutor::new()).await.unwrap();
+        option.immutable_chunk_max_num = 0;
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+        let items = test_items();
+
+        db.insert_at(items[0].clone(), 100.into()).await.unwrap();
+        assert_eq!(db.snapshot().await.ts(), 100.into());
+        let entry = db
+            .snapshot()
+            .await
+            .latest_ts(&items[0].vstring)
+            .await
+            .unwrap();
+        assert_eq!(entry, Some(100.into()));
+        for ts in [50_u64, 100] {
+            assert!(matches!(
+                db.insert_at(items[0].clone(), ts.into()).await,
+                Err(CommitError::StaleTimestamp { latest, .. }) if latest == 100.into()
+            ));
+        }
+        db.remove_at(items[0].vstring.clone(), 101.into())
+            .await
+            .unwrap();
+        assert_eq!(
+            db.get(&items[0].vstring, |entry| Some(entry.get().vu32))
+                .await
+                .unwrap(),
+            None
+        );
 
-        for (idx, item) in test_items().into_iter().enumerate() {
-            if idx % 2 == 0 {
-                db.write(item, 0.into()).await.unwrap();
-            } else {
-                db.remove(item.vstring).await.unwrap();
-            }
+        // the timestamps allocated afterwards are newer
+        db.insert(items[1].clone()).await.unwrap();
+        assert!(matches!(
+            db.insert_at(items[1].clone(), 102.into()).await,
+            Err(CommitError::StaleTimestamp { .. })
+        ));
+
+        // versions in SSTs are checked too
+        db.flush().await.unwrap();
+        assert!(matches!(
+            db.insert_at(items[0].clone(), 101.into()).await,
+            Err(CommitError::StaleTimestamp { .. })
+        ));
+
+        let mut txn = db.transaction().await;
+        txn.insert(items[0].clone());
+        txn.insert(items[2].clone());
+        txn.commit_at(200.into()).await.unwrap();
+        let snapshot = db.snapshot().await;
+        assert_eq!(snapshot.ts(), 200.into());
+        assert_eq!(
+            snapshot.latest_ts(&items[2].vstring).await.unwrap(),
+            Some(200.into())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_commit_at_recover() {
+        let temp_dir = TempDir::new().unwrap();
+        let option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap());
+        let items = test_items();
+        {
+            let db: DB<Test> = DB::new(option.clone(), TokioExecutor::new()).await.unwrap();
+            db.insert_at(items[0].clone(), 100.into()).await.unwrap();
+            let mut item = items[0].clone();
+            item.vu32 = 100;
+            db.insert_at(item, 200.into()).await.unwrap();
+            let mut txn = db.transaction().await;
+            txn.insert(items[1].clone());
+            txn.insert(items[2].clone());
+            txn.commit_at(300.into()).await.unwrap();
         }
 
-        for i in 0..40 {
-            let vstring = db
-                .get(&i.to_string(), |e| Some(e.get().vstring.to_string()))
+        // the WAL is replayed at the timestamps supplied
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+        async fn get(snapshot: &Snapshot<'_, Test>, key: &str) -> Option<u32> {
+            snapshot
+                .get(&key.to_string(), Projection::All)
                 .await
-                .unwrap();
-            if i % 2 == 0 {
-                assert!(vstring.is_some());
-                assert_eq!(vstring, Some(i.to_string()));
-            } else {
-                assert!(vstring.is_none());
-            }
+                .unwrap()
+                .map(|entry| entry.value().unwrap().vu32.unwrap())
         }
+        let snapshot = db.snapshot().await;
+        assert_eq!(snapshot.ts(), 300.into());
+        assert_eq!(
+            snapshot.latest_ts(&items[0].vstring).await.unwrap(),
+            Some(200.into())
+        );
+        assert_eq!(
+            snapshot.latest_ts(&items[2].vstring).await.unwrap(),
+            Some(300.into())
+        );
+        drop(snapshot);
+        assert_eq!(
+            get(&db.snapshot_at(150.into()).await.unwrap(), "0").await,
+            Some(0)
+        );
+        assert_eq!(
+            get(&db.snapshot_at(250.into()).await.unwrap(), "0").await,
+            Some(100)
+        );
+        assert_eq!(
+            get(&db.snapshot_at(250.into()).await.unwrap(), "1").await,
+            None
+        );
+        assert!(matches!(
+            db.insert_at(items[0].clone(), 150.into()).await,
+            Err(CommitError::StaleTimestamp { .. })
+        ));
     }
 
     #[tokio::test]
@@ -1822,7 +6028,7 @@ pub(crate) mod tests {
         option.major_default_oldest_table_num = 1;
         option.trigger_type = TriggerType::Length(5);
 
-        let db: DB<DynRecord, TokioExecutor> =
+        let db: DB<DynRecord> =
             DB::with_schema(option, TokioExecutor::new(), cols_desc, primary_key_index)
                 .await
                 .unwrap();
@@ -2012,6 +6218,91 @@ pub(crate) mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_dyn_binary_columns() {
+        let temp_dir = TempDir::new().unwrap();
+        let cols_desc = vec![
+            ColumnDesc::new("hash".to_string(), Datatype::FixedSizeBytes(4), false),
+            ColumnDesc::new("blob".to_string(), Datatype::LargeBytes, true),
+            ColumnDesc::new("tag".to_string(), Datatype::FixedSizeBytes(2), true),
+        ];
+        let option = DbOption::with_path(
+            Path::from_filesystem_path(temp_dir.path()).unwrap(),
+            "hash".to_string(),
+            0,
+        );
+        let db: DB<DynRecord> = DB::with_schema(option, TokioExecutor::new(), cols_desc, 0)
+            .await
+            .unwrap();
+
+        let hash = |i: u32| {
+            Column::new(
+                Datatype::FixedSizeBytes(4),
+                "hash".to_string(),
+                Arc::new(i.to_be_bytes().to_vec()),
+                false,
+            )
+        };
+        for i in 0..10_u32 {
+            let columns = vec![
+                hash(i),
+                Column::new(
+                    Datatype::LargeBytes,
+                    "blob".to_string(),
+                    Arc::new(Some(vec![i as u8; i as usize])),
+                    true,
+                ),
+                Column::new(
+                    Datatype::FixedSizeBytes(2),
+                    "tag".to_string(),
+                    Arc::new((i % 2 == 0).then(|| vec![i as u8, 0])),
+                    true,
+                ),
+            ];
+            db.insert(DynRecord::new(columns, 0)).await.unwrap();
+        }
+        db.flush().await.unwrap();
+
+        let tx = db.transaction().await;
+        let entry = tx.get(&hash(4), Projection::All).await.unwrap().unwrap();
+        let columns = entry.get().columns;
+        assert_eq!(
+            columns[1].value.as_ref().downcast_ref::<Option<Vec<u8>>>(),
+            Some(&Some(vec![4; 4]))
+        );
+        assert_eq!(
+            columns[2].value.as_ref().downcast_ref::<Option<Vec<u8>>>(),
+            Some(&Some(vec![4, 0]))
+        );
+
+        let mut scan = tx
+            .scan((Bound::Included(&hash(3)), Bound::Excluded(&hash(6))))
+            .projection(vec![0, 2])
+            .take()
+            .await
+            .unwrap();
+        let mut i = 3_u32;
+        while let Some(entry) = scan.next().await.transpose().unwrap() {
+            let columns = entry.value().unwrap().columns;
+            assert_eq!(columns[0].datatype, Datatype::FixedSizeBytes(4));
+            assert_eq!(
+                columns[0].value.as_ref().downcast_ref::<Vec<u8>>(),
+                Some(&i.to_be_bytes().to_vec())
+            );
+            // projected out
+            assert_eq!(
+                columns[1].value.as_ref().downcast_ref::<Option<Vec<u8>>>(),
+                Some(&None)
+            );
+            assert_eq!(
+                columns[2].value.as_ref().downcast_ref::<Option<Vec<u8>>>(),
+                Some(&(i % 2 == 0).then(|| vec![i as u8, 0]))
+            );
+            i += 1;
+        }
+        assert_eq!(i, 6);
+    }
+
     #[tokio::test]
     async fn test_dyn_multiple_db() {
         let temp_dir1 = TempDir::with_prefix("db1").unwrap();
@@ -2052,7 +6343,7 @@ pub(crate) mod tests {
         option3.major_default_oldest_table_num = 1;
         option3.trigger_type = TriggerType::Length(5);
 
-        let db1: DB<DynRecord, TokioExecutor> = DB::with_schema(
+        let db1: DB<DynRecord> = DB::with_schema(
             option,
             TokioExecutor::new(),
             cols_desc.clone(),
@@ -2060,7 +6351,7 @@ pub(crate) mod tests {
         )
         .await
         .unwrap();
-        let db2: DB<DynRecord, TokioExecutor> = DB::with_schema(
+        let db2: DB<DynRecord> = DB::with_schema(
             option2,
             TokioExecutor::new(),
             cols_desc.clone(),
@@ -2068,7 +6359,7 @@ pub(crate) mod tests {
         )
         .await
         .unwrap();
-        let db3: DB<DynRecord, TokioExecutor> =
+        let db3: DB<DynRecord> =
             DB::with_schema(option3, TokioExecutor::new(), cols_desc, primary_key_index)
                 .await
                 .unwrap();
@@ -0,0 +1,255 @@
+use std::{
+    collections::HashMap,
+    hash::{BuildHasher, Hash, RandomState},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use lockable::{AsyncLimit, Lockable, LockableHashMap};
+
+use crate::executor::Clock;
+
+pub(crate) type TxnId = u64;
+
+type KeyGuard<K> = <LockableHashMap<K, ()> as Lockable<K, ()>>::OwnedGuard;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum LockError {
+    /// waiting would close a cycle of transactions waiting for the locks of each other
+    Deadlock,
+    Timeout,
+}
+
+/// The key locks of the transactions, with the graph of which transaction waits for which, so
+/// that a wait closing a cycle fails instead of blocking forever.
+pub(crate) struct LockTable<K> {
+    /// the keys are locked in the shard picked by their hash
+    shards: Vec<Arc<LockableHashMap<K, ()>>>,
+    hasher: RandomState,
+    waits_for: Mutex<WaitsFor<K>>,
+    next_txn: AtomicU64,
+    timeout: Option<Duration>,
+    clock: Clock,
+}
+
+struct WaitsFor<K> {
+    owners: HashMap<K, TxnId>,
+    waiting: HashMap<TxnId, K>,
+}
+
+impl<K> WaitsFor<K>
+where
+    K: Eq + Hash,
+{
+    /// whether `txn` waiting for `key` closes a cycle, every cycle is detected by the wait
+    /// closing it so the graph has none otherwise
+    fn is_cycle(&self, txn: TxnId, mut key: &K) -> bool {
+        for _ in 0..=self.waiting.len() {
+            let Some(owner) = self.owners.get(key) else {
+                return false;
+            };
+            if *owner == txn {
+                return true;
+            }
+            match self.waiting.get(owner) {
+                Some(next) => key = next,
+                None => return false,
+            }
+        }
+        false
+    }
+}
+
+impl<K> LockTable<K>
+where
+    K: Eq + Hash + Clone + Send + Sync + 'static,
+{
+    pub(crate) fn new(timeout: Option<Duration>, shards: usize, clock: Clock) -> Self {
+        LockTable {
+            shards: (0..shards.max(1)).map(|_| Default::default()).collect(),
+            hasher: RandomState::new(),
+            waits_for: Mutex::new(WaitsFor {
+                owners: HashMap::new(),
+                waiting: HashMap::new(),
+            }),
+            next_txn: AtomicU64::new(0),
+            timeout,
+            clock,
+        }
+    }
+
+    pub(crate) fn next_txn(&self) -> TxnId {
+        self.next_txn.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// lock `key` for `txn`, failing when the wait would deadlock or outlasts the timeout
+    pub(crate) async fn lock(
+        self: &Arc<Self>,
+        txn: TxnId,
+        key: K,
+    ) -> Result<KeyLock<K>, LockError> {
+        {
+            let mut waits_for = self.waits_for.lock().unwrap();
+            if waits_for.is_cycle(txn, &key) {
+                return Err(LockError::Deadlock);
+            }
+            waits_for.waiting.insert(txn, key.clone());
+        }
+        let waiting = Waiting {
+            waits_for: &self.waits_for,
+            txn,
+        };
+        let guard = self.acquire(key.clone()).await;
+        drop(waiting);
+
+        let guard = guard.ok_or(LockError::Timeout)?;
+        self.waits_for
+            .lock()
+            .unwrap()
+            .owners
+            .insert(key.clone(), txn);
+
+        Ok(KeyLock {
+            key,
+            table: self.clone(),
+            _guard: guard,
+        })
+    }
+
+    async fn acquire(&self, key: K) -> Option<KeyGuard<K>> {
+        let shard = self.hasher.hash_one(&key) as usize % self.shards.len();
+        let guard = self.shards[shard].async_lock_owned(key, AsyncLimit::no_limit());
+        // SAFETY: Error is Never
+        if let Some(timeout) = self.timeout {
+            return self
+                .clock
+                .timeout(timeout, guard)
+                .await
+                .map(|guard| guard.unwrap());
+        }
+        Some(guard.await.unwrap())
+    }
+}
+
+/// the wait of a transaction for a key, removed from the graph on drop so that a lock dropped
+/// while waiting leaves no edge behind
+struct Waiting<'a, K> {
+    waits_for: &'a Mutex<WaitsFor<K>>,
+    txn: TxnId,
+}
+
+impl<K> Drop for Waiting<'_, K> {
+    fn drop(&mut self) {
+        self.waits_for.lock().unwrap().waiting.remove(&self.txn);
+    }
+}
+
+/// a key locked by a transaction, unlocked on drop
+pub(crate) struct KeyLock<K>
+where
+    K: Eq + Hash,
+{
+    key: K,
+    table: Arc<LockTable<K>>,
+    _guard: KeyGuard<K>,
+}
+
+impl<K> Drop for KeyLock<K>
+where
+    K: Eq + Hash,
+{
+    fn drop(&mut self) {
+        self.table
+            .waits_for
+            .lock()
+            .unwrap()
+            .owners
+            .remove(&self.key);
+    }
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use super::{LockError, LockTable};
+    use crate::executor::{tokio::TokioExecutor, Clock};
+
+    #[tokio::test]
+    async fn deadlock() {
+        let table = Arc::new(LockTable::new(
+            None,
+            16,
+            Clock::new(Arc::new(TokioExecutor::new())),
+        ));
+        let (txn1, txn2) = (table.next_txn(), table.next_txn());
+
+        let _a = table.lock(txn1, "a").await.unwrap();
+        let b = table.lock(txn2, "b").await.unwrap();
+
+        let waiting = {
+            let table = table.clone();
+            tokio::spawn(async move { table.lock(txn1, "b").await.map(|_| ()) })
+        };
+        while !table.waits_for.lock().unwrap().waiting.contains_key(&txn1) {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(table.lock(txn2, "a").await.err(), Some(LockError::Deadlock));
+
+        drop(b);
+        waiting.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn timeout() {
+        let table = Arc::new(LockTable::new(
+            Some(Duration::from_millis(10)),
+            16,
+            Clock::new(Arc::new(TokioExecutor::new())),
+        ));
+        let (txn1, txn2) = (table.next_txn(), table.next_txn());
+
+        let a = table.lock(txn1, "a").await.unwrap();
+        assert_eq!(table.lock(txn2, "a").await.err(), Some(LockError::Timeout));
+
+        drop(a);
+        table.lock(txn2, "a").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn cancel_waiting() {
+        let table = Arc::new(LockTable::new(
+            None,
+            16,
+            Clock::new(Arc::new(TokioExecutor::new())),
+        ));
+        let (txn1, txn2) = (table.next_txn(), table.next_txn());
+
+        let a = table.lock(txn1, "a").await.unwrap();
+        let b = table.lock(txn2, "b").await.unwrap();
+        assert!(
+            tokio::time::timeout(Duration::from_millis(10), table.lock(txn2, "a"))
+                .await
+                .is_err()
+        );
+        assert!(table.waits_for.lock().unwrap().waiting.is_empty());
+
+        // txn2 no longer waits for txn1
+        let waiting = {
+            let table = table.clone();
+            tokio::spawn(async move { table.lock(txn1, "b").await.map(|_| ()) })
+        };
+        while !waiting.is_finished() && !table.waits_for.lock().unwrap().waiting.contains_key(&txn1)
+        {
+            tokio::task::yield_now().await;
+        }
+        drop(b);
+        waiting.await.unwrap().unwrap();
+
+        drop(a);
+        table.lock(txn2, "a").await.unwrap();
+    }
+}
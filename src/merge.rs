@@ -0,0 +1,264 @@
+use std::{ops::Bound, pin::pin, sync::Arc};
+
+use flume::unbounded;
+use futures_util::StreamExt;
+use parquet::arrow::ProjectionMask;
+use parquet_lru::NoCache;
+
+use crate::{
+    fs::manager::StoreManager,
+    inmem::immutable::{ArrowArrays, Builder},
+    record::{KeyRef, Record},
+    scope::Scope,
+    stream::merge::MergeStream,
+    timestamp::TimestampedRef,
+    version::{edit::VersionEdit, set::VersionSet, ScanLimits, TransactionTs},
+    write_table, DbError, DbOption, Schema, DB,
+};
+
+impl<R> DB<R>
+where
+    R: Record + Send + Sync,
+    R::Columns: Send + Sync,
+{
+    /// merge the SSTs of the database configured by `other`, holding records of `R` as well, e.g.
+    /// to consolidate shards or to restore a partial backup. The newest version of each key of
+    /// `other` is copied into level 0 with its timestamp if it is newer than every version of the
+    /// key in this database, and the range deletions of `other` are copied too, so the latest
+    /// write wins on both sides. Older versions of `other` are not copied. As with
+    /// [`DB::open_read_only`], only the data `other` has flushed into SSTs is read, and nothing of
+    /// it is changed. The timestamps allocated afterwards are newer than the merged ones. The keys
+    /// of `other` must not overlap data of this database not yet flushed. The SSTs are built
+    /// without blocking the writes, which are only held back while the version edit is applied.
+    pub async fn merge_from(&self, other: &DbOption<R>) -> Result<(), DbError<R>> {
+        let other_manager = Arc::new(StoreManager::new(other.base_fs.clone(), other.fs_paths())?);
+        // nothing is cleaned, the tags sent when the version is released are dropped
+        let (clean_sender, _clean_rx) = unbounded();
+        let other_version_set = VersionSet::<R>::read_only(
+            clean_sender,
+            Arc::new(other.clone()),
+            other_manager.clone(),
+        )
+        .await?;
+        let other_version = other_version_set.current().await;
+        let other_ts = other_version_set.load_ts();
+        let scopes = other_version.level_slice.iter().flatten();
+        let range = scopes
+            .clone()
+            .map(|scope| &scope.min)
+            .min()
+            .zip(scopes.map(|scope| &scope.max).max());
+        let check_unflushed = |schema: &Schema<R>| -> Result<(), DbError<R>> {
+            schema.check_writable()?;
+            match range {
+                Some((min, max))
+                    if schema.is_unflushed((Bound::Included(min), Bound::Included(max))) =>
+                {
+                    Err(DbError::Ingest(
+                        "merged SSTs overlap unflushed data".to_string(),
+                    ))
+                }
+                _ => Ok(()),
+            }
+        };
+        let arrow_schema = {
+            let schema = self.schema.read().await;
+            check_unflushed(&schema)?;
+            schema.record_instance.arrow_schema::<R>()
+        };
+        // the tables are built without blocking the writes, a key written meanwhile is resolved
+        // against the merged one by their timestamps
+        let version = self.version_set.current().await;
+        let option = version.option();
+
+        let mut streams = Vec::new();
+        other_version
+            .streams(
+                &other_manager,
+                &mut streams,
+                (Bound::Unbounded, Bound::Unbounded),
+                other_ts,
+                None,
+                ProjectionMask::all(),
+                Arc::new(NoCache::default()),
+                &ScanLimits::default(),
+                None,
+            )
+            .await?;
+        let mut merge = pin!(
+            MergeStream::with_range_tombstones(
+                streams,
+                other_ts,
+                other_version.range_tombstones.to_vec(),
+            )
+            .await?
+        );
+        let mut version_edits = Vec::new();
+        let mut builder = R::Columns::builder(&arrow_schema, 8192);
+        let mut bounds: Option<(R::Key, R::Key)> = None;
+
+        while let Some(entry) = merge.next().await.transpose()? {
+            let key = entry.key();
+            let owned_key = key.value.clone().to_key();
+            // the memtables do not hold the key, and a range deletion is resolved by the
+            // timestamps when read
+            let current = version
+                .query(
+                    &self.manager,
+                    TimestampedRef::new(&owned_key, u64::MAX.into()),
+                    ProjectionMask::all(),
+                    self.parquet_lru.clone(),
+                    None,
+                )
+                .await?;
+            if current.is_some_and(|current| current.internal_key().ts >= key.ts) {
+                continue;
+            }
+            builder.push(key, entry.value());
+            bounds = Some(match bounds.take() {
+                Some((min, _)) => (min, owned_key),
+                None => (owned_key.clone(), owned_key),
+            });
+
+            if builder.written_size() >= option.max_sst_file_size {
+                let (min, max) = bounds.take().unwrap();
+                version_edits.push(
+                    self.merged_table(option, &arrow_schema, min, max, &builder.finish(None))
+                        .await?,
+                );
+            }
+        }
+        if let Some((min, max)) = bounds {
+            version_edits.push(
+                self.merged_table(option, &arrow_schema, min, max, &builder.finish(None))
+                    .await?,
+            );
+        }
+        for range_tombstone in other_version.range_tombstones.iter() {
+            version_edits.push(VersionEdit::RangeTombstone {
+                range_tombstone: range_tombstone.clone(),
+            });
+        }
+        let tables = version_edits
+            .iter()
+            .filter_map(|edit| match edit {
+                VersionEdit::Add { scope, .. } => Some(scope.gen),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        drop(version);
+
+        // block writes and compaction only to apply the version edit
+        let schema = self.schema.write().await;
+        let result = match check_unflushed(&schema) {
+            Ok(()) => {
+                self.version_set.advance_ts(other_ts);
+                version_edits.push(VersionEdit::LatestTimeStamp {
+                    ts: self.version_set.increase_ts(),
+                });
+                self.version_set
+                    .apply_edits(version_edits, None, false)
+                    .await
+                    .map_err(DbError::from)
+            }
+            Err(err) => Err(err),
+        };
+        drop(schema);
+        if result.is_err() {
+            // no version refers to the tables built
+            let option = self.version_set.option();
+            let fs = self
+                .manager
+                .get_fs(option.level_fs_path(0).unwrap_or(&option.base_path));
+            for gen in tables {
+                let _ = fs.remove(&option.table_path(gen, 0)).await;
+            }
+        }
+
+        result
+    }
+
+    /// write the `columns` merged by [`DB::merge_from`] as a new SST of level 0
+    async fn merged_table(
+        &self,
+        option: &DbOption<R>,
+        arrow_schema: &Arc<arrow::datatypes::Schema>,
+        min: R::Key,
+        max: R::Key,
+        columns: &R::Columns,
+    ) -> Result<VersionEdit<R::Key>, DbError<R>> {
+        let (gen, stats) = write_table(option, &self.manager, 0, arrow_schema, columns).await?;
+
+        Ok(VersionEdit::Add {
+            level: 0,
+            scope: Scope {
+                min,
+                max,
+                gen,
+                wal_ids: None,
+                stats: Some(stats),
+            },
+        })
+    }
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod tests {
+    use fusio::path::Path;
+    use tempfile::TempDir;
+
+    use crate::{executor::tokio::TokioExecutor, tests::Test, DbOption, DB};
+
+    #[tokio::test]
+    async fn merge_from() {
+        let (temp_dir, other_dir) = (TempDir::new().unwrap(), TempDir::new().unwrap());
+        let option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap());
+        let other_option = DbOption::from(Path::from_filesystem_path(other_dir.path()).unwrap());
+        let test = |key: &str, vu32: u32| Test {
+            vstring: key.to_string(),
+            vu32,
+            vbool: None,
+        };
+
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+        db.insert_at(test("a", 1), 10.into()).await.unwrap();
+        db.insert_at(test("b", 2), 20.into()).await.unwrap();
+        db.insert_at(test("d", 4), 25.into()).await.unwrap();
+        db.flush().await.unwrap();
+        {
+            let other: DB<Test> = DB::new(other_option.clone(), TokioExecutor::new())
+                .await
+                .unwrap();
+            other.insert_at(test("a", 10), 5.into()).await.unwrap();
+            other.insert_at(test("c", 30), 7.into()).await.unwrap();
+            other.insert_at(test("b", 20), 30.into()).await.unwrap();
+            other.remove_at("d".to_string(), 40.into()).await.unwrap();
+            other.insert_at(test("e", 50), 50.into()).await.unwrap();
+            other.flush().await.unwrap();
+            // not flushed, so not merged
+            other.insert_at(test("f", 60), 60.into()).await.unwrap();
+        }
+
+        db.merge_from(&other_option).await.unwrap();
+        let get = |key: &str| {
+            let key = key.to_string();
+            let db = &db;
+            async move {
+                db.get(&key, |entry| Some(entry.get().vu32.unwrap()))
+                    .await
+                    .unwrap()
+            }
+        };
+        // the newer write wins on both sides
+        assert_eq!(get("a").await, Some(1));
+        assert_eq!(get("b").await, Some(20));
+        assert_eq!(get("c").await, Some(30));
+        assert_eq!(get("d").await, None);
+        assert_eq!(get("e").await, Some(50));
+        assert_eq!(get("f").await, None);
+
+        // the timestamps allocated afterwards are newer than the merged ones
+        db.insert(test("d", 5)).await.unwrap();
+        assert_eq!(get("d").await, Some(5));
+    }
+}
@@ -0,0 +1,150 @@
+//! Names of the metrics recorded with the `metrics` feature through the
+//! [`metrics`](https://docs.rs/metrics) facade, to be exported by any recorder installed by the
+//! application, e.g. to Prometheus. Without the feature nothing is recorded.
+
+/// counter of the records written, including deletions
+pub const WRITE_RECORDS: &str = "tonbo_write_records_total";
+/// histogram of the seconds writes waited for the memtables, held by flushes and compactions,
+/// including the delays and blocks of the write stall triggers
+pub const WRITE_STALL_SECONDS: &str = "tonbo_write_stall_seconds";
+/// counter of the writes delayed by a slowdown trigger, see
+/// [`DbOption::write_slowdown_delay`](crate::DbOption::write_slowdown_delay)
+pub const WRITE_SLOWDOWNS: &str = "tonbo_write_slowdowns_total";
+/// counter of the writes blocked by a stop trigger, see
+/// [`DbOption::level0_stop_writes_trigger`](crate::DbOption::level0_stop_writes_trigger)
+pub const WRITE_STOPS: &str = "tonbo_write_stops_total";
+/// counter of the writes held back by a rate limit, see
+/// [`DbOption::max_writes_per_sec`](crate::DbOption::max_writes_per_sec)
+pub const WRITE_THROTTLES: &str = "tonbo_write_throttles_total";
+/// histogram of the seconds taken by gets
+pub const GET_SECONDS: &str = "tonbo_get_seconds";
+/// counter of the scans started
+pub const SCANS: &str = "tonbo_scans_total";
+/// histogram of the seconds taken by flushes of immutable memtables into level 0
+pub const FLUSH_SECONDS: &str = "tonbo_flush_seconds";
+/// histogram of the seconds taken by major compactions
+pub const COMPACTION_SECONDS: &str = "tonbo_compaction_seconds";
+/// counter of the bytes of the SSTs written by flushes and compactions, labeled by `level`
+pub const SST_BYTES_WRITTEN: &str = "tonbo_sst_bytes_written_total";
+/// gauge of the encoded size of the records in the mutable memtable
+pub const MEMTABLE_BYTES: &str = "tonbo_memtable_bytes";
+/// counter of the obsolete files left behind after
+/// [`DbOption::file_deletion_retries`](crate::DbOption::file_deletion_retries) failed attempts to
+/// remove them
+pub const FILE_DELETION_FAILURES: &str = "tonbo_file_deletion_failures_total";
+/// histogram of the seconds taken by flushing the WAL buffer
+pub const WAL_SYNC_SECONDS: &str = "tonbo_wal_sync_seconds";
+
+#[cfg(feature = "metrics")]
+use web_time::Instant;
+
+/// measures the seconds until [`Timer::record`]
+pub(crate) struct Timer {
+    #[cfg(feature = "metrics")]
+    start: Instant,
+}
+
+impl Timer {
+    pub(crate) fn start() -> Self {
+        Timer {
+            #[cfg(feature = "metrics")]
+            start: Instant::now(),
+        }
+    }
+
+    #[allow(unused_variables)]
+    pub(crate) fn record(self, name: &'static str) {
+        #[cfg(feature = "metrics")]
+        ::metrics::histogram!(name).record(self.start.elapsed().as_secs_f64());
+    }
+}
+
+#[allow(unused_variables)]
+pub(crate) fn increment(name: &'static str, value: u64) {
+    #[cfg(feature = "metrics")]
+    ::metrics::counter!(name).increment(value);
+}
+
+#[allow(unused_variables)]
+pub(crate) fn sst_written(level: usize, bytes: usize) {
+    #[cfg(feature = "metrics")]
+    ::metrics::counter!(SST_BYTES_WRITTEN, "level" => level.to_string()).increment(bytes as u64);
+}
+
+#[allow(unused_variables)]
+pub(crate) fn memtable_grown(bytes: usize) {
+    #[cfg(feature = "metrics")]
+    ::metrics::gauge!(MEMTABLE_BYTES).increment(bytes as f64);
+}
+
+pub(crate) fn memtable_frozen() {
+    #[cfg(feature = "metrics")]
+    ::metrics::gauge!(MEMTABLE_BYTES).set(0.0);
+}
+
+#[cfg(all(test, feature = "tokio", feature = "metrics"))]
+mod tests {
+    use fusio::path::Path;
+    use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+    use tempfile::TempDir;
+
+    use crate::{
+        executor::tokio::TokioExecutor,
+        tests::{test_items, Test},
+        DbOption, DB,
+    };
+
+    #[tokio::test]
+    async fn record() {
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        // other tests may record into it too, so only lower bounds are checked
+        recorder.install().unwrap();
+
+        let temp_dir = TempDir::new().unwrap();
+        let mut option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap());
+        option.immutable_chunk_num = 1;
+        option.immutable_chunk_max_num = 0;
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+        for item in test_items() {
+            db.insert(item).await.unwrap();
+        }
+        db.get(&"1".to_string(), |entry| Some(entry.get().vu32))
+            .await
+            .unwrap();
+        db.flush_wal().await.unwrap();
+        db.flush().await.unwrap();
+
+        let metrics = snapshotter.snapshot().into_vec();
+        let metric = |name: &str| {
+            metrics
+                .iter()
+                .find(|(key, ..)| key.key().name() == name)
+                .map(|(.., value)| value.clone())
+        };
+        assert!(matches!(
+            metric(super::WRITE_RECORDS),
+            Some(DebugValue::Counter(count)) if count >= 40
+        ));
+        for name in [
+            super::WRITE_STALL_SECONDS,
+            super::GET_SECONDS,
+            super::WAL_SYNC_SECONDS,
+            super::FLUSH_SECONDS,
+        ] {
+            assert!(matches!(
+                metric(name),
+                Some(DebugValue::Histogram(values)) if !values.is_empty()
+            ));
+        }
+        assert!(metrics.iter().any(|(key, .., value)| {
+            key.key().name() == super::SST_BYTES_WRITTEN
+                && key.key().labels().any(|label| label.value() == "0")
+                && matches!(value, DebugValue::Counter(bytes) if *bytes > 0)
+        }));
+        assert!(matches!(
+            metric(super::MEMTABLE_BYTES),
+            Some(DebugValue::Gauge(_))
+        ));
+    }
+}
@@ -1,9 +1,16 @@
-use std::ops::Bound;
+use std::{ops::Bound, sync::Arc};
 
 use arrow::{
-    array::{BooleanArray, Datum},
+    array::{ArrayRef, AsArray, BooleanArray, Datum, RecordBatch},
     buffer::BooleanBuffer,
-    compute::kernels::cmp::{gt, gt_eq, lt_eq},
+    compute::{
+        cast,
+        kernels::cmp::{gt, gt_eq, lt_eq},
+    },
+    datatypes::{
+        DataType, Field, Int16Type, Int32Type, Int64Type, Int8Type, Schema, UInt16Type, UInt32Type,
+        UInt64Type, UInt8Type,
+    },
     error::ArrowError,
 };
 use parquet::{
@@ -65,7 +72,12 @@ where
 
     let mut predictions: Vec<Box<dyn ArrowPredicate>> = vec![Box::new(ArrowPredicateFn::new(
         ProjectionMask::roots(schema_descriptor, [1]),
-        move |record_batch| lt_eq(record_batch.column(0), &ts.to_arrow_scalar() as &dyn Datum),
+        move |record_batch| {
+            lt_eq(
+                &widen_ts(record_batch.column(0))?,
+                &ts.to_arrow_scalar() as &dyn Datum,
+            )
+        },
     ))];
     if let Some(lower_key) = lower_key {
         predictions.push(Box::new(ArrowPredicateFn::new(
@@ -86,3 +98,84 @@ where
 
     RowFilter::new(predictions)
 }
+
+/// SSTs written before timestamps were widened to 64 bits store `_ts` as `UInt32`, it is read
+/// back as `UInt64` like everywhere else
+pub(crate) fn widen_ts(ts: &ArrayRef) -> Result<ArrayRef, ArrowError> {
+    match ts.data_type() {
+        DataType::UInt32 => cast(ts, &DataType::UInt64),
+        _ => Ok(ts.clone()),
+    }
+}
+
+/// the schema with `_ts`, its second column, as `UInt64`
+pub(crate) fn widen_ts_schema(schema: &Arc<Schema>) -> Arc<Schema> {
+    if schema.field(1).data_type() != &DataType::UInt32 {
+        return schema.clone();
+    }
+    let mut fields = schema.fields().to_vec();
+    fields[1] = Arc::new(Field::new(fields[1].name(), DataType::UInt64, false));
+
+    Arc::new(Schema::new_with_metadata(fields, schema.metadata().clone()))
+}
+
+/// the batch with `_ts`, its second column, as `UInt64`
+pub(crate) fn widen_ts_batch(batch: RecordBatch) -> Result<RecordBatch, ArrowError> {
+    if batch.column(1).data_type() != &DataType::UInt32 {
+        return Ok(batch);
+    }
+    let mut columns = batch.columns().to_vec();
+    columns[1] = widen_ts(&columns[1])?;
+
+    RecordBatch::try_new(widen_ts_schema(batch.schema_ref()), columns)
+}
+
+/// the bytes of the plain encoded key hashed by parquet bloom filters, `None` for the types
+/// without one
+pub(crate) fn bloom_filter_bytes(key: &dyn Datum) -> Option<Vec<u8>> {
+    let (array, _) = key.get();
+    if array.is_null(0) {
+        return None;
+    }
+    Some(match array.data_type() {
+        DataType::Boolean => vec![array.as_boolean().value(0) as u8],
+        // the narrower integers are stored as INT32
+        DataType::Int8 => (array.as_primitive::<Int8Type>().value(0) as i32)
+            .to_le_bytes()
+            .to_vec(),
+        DataType::Int16 => (array.as_primitive::<Int16Type>().value(0) as i32)
+            .to_le_bytes()
+            .to_vec(),
+        DataType::Int32 => array
+            .as_primitive::<Int32Type>()
+            .value(0)
+            .to_le_bytes()
+            .to_vec(),
+        DataType::Int64 => array
+            .as_primitive::<Int64Type>()
+            .value(0)
+            .to_le_bytes()
+            .to_vec(),
+        DataType::UInt8 => (array.as_primitive::<UInt8Type>().value(0) as i32)
+            .to_le_bytes()
+            .to_vec(),
+        DataType::UInt16 => (array.as_primitive::<UInt16Type>().value(0) as i32)
+            .to_le_bytes()
+            .to_vec(),
+        DataType::UInt32 => array
+            .as_primitive::<UInt32Type>()
+            .value(0)
+            .to_le_bytes()
+            .to_vec(),
+        DataType::UInt64 => array
+            .as_primitive::<UInt64Type>()
+            .value(0)
+            .to_le_bytes()
+            .to_vec(),
+        DataType::Utf8 => array.as_string::<i32>().value(0).as_bytes().to_vec(),
+        DataType::LargeUtf8 => array.as_string::<i64>().value(0).as_bytes().to_vec(),
+        DataType::Binary => array.as_binary::<i32>().value(0).to_vec(),
+        DataType::LargeBinary => array.as_binary::<i64>().value(0).to_vec(),
+        _ => return None,
+    })
+}
@@ -0,0 +1,228 @@
+use std::{
+    collections::HashMap,
+    ops::{Bound, Range},
+    sync::{Arc, Mutex},
+};
+
+use arrow::{
+    array::{ArrayRef, BooleanArray},
+    compute::{
+        and,
+        kernels::cmp::{gt, gt_eq, lt, lt_eq},
+    },
+    datatypes::Schema,
+};
+use parquet::{
+    arrow::{
+        arrow_reader::{statistics::StatisticsConverter, RowSelection, RowSelector},
+        parquet_to_arrow_schema,
+    },
+    errors::Result as ParquetResult,
+    file::metadata::ParquetMetaData,
+};
+
+use crate::{fs::FileId, record::Key};
+
+/// The smallest and largest key of each row group of an SST, read from the statistics of the
+/// primary key column, so that a get only reads the row groups which may hold its key.
+#[derive(Debug)]
+pub(crate) struct Fences {
+    mins: ArrayRef,
+    maxes: ArrayRef,
+}
+
+impl Fences {
+    /// `None` if a row group of the table has no statistics on the primary key column
+    pub(crate) fn new(metadata: &ParquetMetaData) -> ParquetResult<Option<Self>> {
+        let schema = arrow_schema(metadata)?;
+        let converter = key_converter(metadata, &schema)?;
+        let mins = converter.row_group_mins(metadata.row_groups())?;
+        let maxes = converter.row_group_maxes(metadata.row_groups())?;
+
+        Ok((mins.null_count() == 0 && maxes.null_count() == 0).then_some(Fences { mins, maxes }))
+    }
+
+    /// the row groups whose keys range over `key`, the versions of a key may span consecutive
+    /// row groups
+    pub(crate) fn row_groups<K: Key>(&self, key: &K) -> ParquetResult<Range<usize>> {
+        let key = key.to_arrow_datum();
+        let start = count(lt(&self.maxes, key.as_ref())?);
+        let end = count(lt_eq(&self.mins, key.as_ref())?);
+
+        Ok(start..end.max(start))
+    }
+}
+
+fn count(array: BooleanArray) -> usize {
+    array.values().count_set_bits()
+}
+
+/// the rows of the pages whose keys may be in `range` according to the page index of the
+/// primary key column, `None` if the table has no page index
+pub(crate) fn page_selection<K: Key>(
+    metadata: &ParquetMetaData,
+    range: (Bound<&K>, Bound<&K>),
+) -> ParquetResult<Option<RowSelection>> {
+    let (Some(column_index), Some(offset_index)) =
+        (metadata.column_index(), metadata.offset_index())
+    else {
+        return Ok(None);
+    };
+    let schema = arrow_schema(metadata)?;
+    let converter = key_converter(metadata, &schema)?;
+    let row_groups: Vec<usize> = (0..metadata.num_row_groups()).collect();
+    let Some(row_counts) =
+        converter.data_page_row_counts(offset_index, metadata.row_groups(), &row_groups)?
+    else {
+        return Ok(None);
+    };
+    let mins = converter.data_page_mins(column_index, offset_index, &row_groups)?;
+    let maxes = converter.data_page_maxes(column_index, offset_index, &row_groups)?;
+
+    let after_lower = match range.0 {
+        Bound::Included(key) => Some(gt_eq(&maxes, key.to_arrow_datum().as_ref())?),
+        Bound::Excluded(key) => Some(gt(&maxes, key.to_arrow_datum().as_ref())?),
+        Bound::Unbounded => None,
+    };
+    let before_upper = match range.1 {
+        Bound::Included(key) => Some(lt_eq(&mins, key.to_arrow_datum().as_ref())?),
+        Bound::Excluded(key) => Some(lt(&mins, key.to_arrow_datum().as_ref())?),
+        Bound::Unbounded => None,
+    };
+    let keep = match (after_lower, before_upper) {
+        (Some(lower), Some(upper)) => and(&lower, &upper)?,
+        (Some(bound), None) | (None, Some(bound)) => bound,
+        (None, None) => return Ok(None),
+    };
+
+    // a page without statistics may hold any key
+    let selectors: Vec<RowSelector> = row_counts
+        .values()
+        .iter()
+        .enumerate()
+        .map(|(page, &rows)| {
+            if keep.is_null(page) || keep.value(page) {
+                RowSelector::select(rows as usize)
+            } else {
+                RowSelector::skip(rows as usize)
+            }
+        })
+        .collect();
+    Ok(Some(selectors.into()))
+}
+
+/// the row groups holding any row of the `selection`, given the rows of each row group
+pub(crate) fn selected_row_groups(
+    row_group_rows: impl IntoIterator<Item = usize>,
+    selection: &RowSelection,
+) -> usize {
+    let mut selectors = selection
+        .iter()
+        .filter(|selector| selector.row_count > 0)
+        .peekable();
+    // where the row group and the selector peeked start
+    let (mut start, mut offset) = (0, 0);
+    let mut selected = 0;
+    for rows in row_group_rows {
+        let end = start + rows;
+        let mut is_selected = false;
+        while let Some(selector) = selectors.peek() {
+            let selector_end = offset + selector.row_count;
+            is_selected |= !selector.skip && offset < end && selector_end > start;
+            if selector_end > end {
+                break;
+            }
+            offset = selector_end;
+            selectors.next();
+        }
+        selected += is_selected as usize;
+        start = end;
+    }
+    selected
+}
+
+fn arrow_schema(metadata: &ParquetMetaData) -> ParquetResult<Schema> {
+    let file_metadata = metadata.file_metadata();
+    parquet_to_arrow_schema(
+        file_metadata.schema_descr(),
+        file_metadata.key_value_metadata(),
+    )
+}
+
+fn key_converter<'a>(
+    metadata: &'a ParquetMetaData,
+    schema: &'a Schema,
+) -> ParquetResult<StatisticsConverter<'a>> {
+    // the primary key is the column after `_null` and `_ts`
+    StatisticsConverter::try_new(
+        schema.field(2).name(),
+        schema,
+        metadata.file_metadata().schema_descr(),
+    )
+}
+
+/// The [`Fences`] of the tables read by gets, shared by the versions of a
+/// [`VersionSet`](crate::version::set::VersionSet).
+#[derive(Debug, Default)]
+pub(crate) struct FenceIndex {
+    tables: Mutex<HashMap<FileId, Arc<Fences>>>,
+}
+
+impl FenceIndex {
+    pub(crate) fn get(&self, gen: FileId) -> Option<Arc<Fences>> {
+        self.tables.lock().unwrap().get(&gen).cloned()
+    }
+
+    /// insert the fences of `gen`, dropping the ones of the tables not in `live` once the
+    /// compacted tables make up half of the index
+    pub(crate) fn insert(&self, gen: FileId, fences: Arc<Fences>, live: &[FileId]) {
+        let mut tables = self.tables.lock().unwrap();
+        tables.insert(gen, fences);
+        if tables.len() > live.len() * 2 {
+            tables.retain(|gen, _| live.contains(gen));
+        }
+    }
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow::array::{ArrayRef, StringArray};
+    use parquet::arrow::arrow_reader::{RowSelection, RowSelector};
+
+    use super::{selected_row_groups, Fences};
+
+    #[test]
+    fn row_groups() {
+        let array = |keys: &[&str]| Arc::new(StringArray::from(keys.to_vec())) as ArrayRef;
+        let fences = Fences {
+            mins: array(&["a", "c", "e", "e"]),
+            maxes: array(&["b", "e", "e", "g"]),
+        };
+
+        assert_eq!(fences.row_groups(&"a".to_string()).unwrap(), 0..1);
+        assert_eq!(fences.row_groups(&"c".to_string()).unwrap(), 1..2);
+        assert_eq!(fences.row_groups(&"e".to_string()).unwrap(), 1..4);
+        assert_eq!(fences.row_groups(&"bb".to_string()).unwrap(), 1..1);
+        assert_eq!(fences.row_groups(&"h".to_string()).unwrap(), 4..4);
+    }
+
+    #[test]
+    fn selected() {
+        // three row groups of 10 rows, pages of 5 rows
+        let selection = RowSelection::from(vec![
+            RowSelector::skip(10),
+            RowSelector::select(5),
+            RowSelector::skip(10),
+            RowSelector::select(5),
+        ]);
+        assert_eq!(selected_row_groups([10, 10, 10], &selection), 2);
+
+        let selection = RowSelection::from(vec![RowSelector::skip(5), RowSelector::select(10)]);
+        assert_eq!(selected_row_groups([10, 10, 10], &selection), 2);
+
+        let selection = RowSelection::from(vec![RowSelector::skip(30)]);
+        assert_eq!(selected_row_groups([10, 10, 10], &selection), 0);
+    }
+}
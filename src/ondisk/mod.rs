@@ -1,3 +1,4 @@
 mod arrows;
+pub(crate) mod fence;
 pub(crate) mod scan;
 pub(crate) mod sstable;
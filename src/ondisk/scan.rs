@@ -1,18 +1,23 @@
 use std::{
+    collections::VecDeque,
     marker::PhantomData,
     pin::Pin,
     sync::Arc,
     task::{Context, Poll},
 };
 
-use arrow::datatypes::Schema;
+use arrow::{array::RecordBatch, datatypes::Schema};
 use futures_core::{ready, Stream};
-use parquet::arrow::{
-    async_reader::{AsyncFileReader, ParquetRecordBatchStream},
-    ProjectionMask,
+use parquet::{
+    arrow::{
+        async_reader::{AsyncFileReader, ParquetRecordBatchStream},
+        ProjectionMask,
+    },
+    errors::ParquetError,
 };
 use pin_project_lite::pin_project;
 
+use super::arrows::widen_ts_batch;
 use crate::{
     record::Record,
     stream::record_batch::{RecordBatchEntry, RecordBatchIterator},
@@ -26,6 +31,10 @@ pin_project! {
         iter: Option<RecordBatchIterator<R>>,
         projection_mask: ProjectionMask,
         full_schema: Arc<Schema>,
+        // the batches read ahead of the one iterated, up to `prefetch`
+        prefetched: VecDeque<Result<RecordBatch, ParquetError>>,
+        prefetch: usize,
+        exhausted: bool,
         _marker: PhantomData<&'scan ()>
     }
 }
@@ -41,9 +50,37 @@ impl<R> SsTableScan<'_, R> {
             iter: None,
             projection_mask,
             full_schema,
+            prefetched: VecDeque::new(),
+            prefetch: 0,
+            exhausted: false,
             _marker: PhantomData,
         }
     }
+
+    /// read up to `batches` record batches ahead of the one being iterated, see
+    /// [`DbOption::scan_prefetch_batches`](crate::DbOption::scan_prefetch_batches)
+    pub(crate) fn prefetch(self, batches: usize) -> Self {
+        SsTableScan {
+            prefetch: batches,
+            ..self
+        }
+    }
+
+    /// make progress on the batches read ahead without yielding any entry, e.g. while a merge
+    /// waits for another stream
+    pub(crate) fn poll_prefetch(self: Pin<&mut Self>, cx: &mut Context<'_>) {
+        let this = self.project();
+        while !*this.exhausted && this.prefetched.len() < *this.prefetch {
+            match this.stream.as_mut().poll_next(cx) {
+                Poll::Ready(Some(record_batch)) => {
+                    *this.exhausted = record_batch.is_err();
+                    this.prefetched.push_back(record_batch);
+                }
+                Poll::Ready(None) => *this.exhausted = true,
+                Poll::Pending => break,
+            }
+        }
+    }
 }
 
 impl<'scan, R> Stream for SsTableScan<'scan, R>
@@ -52,20 +89,25 @@ where
 {
     type Item = Result<RecordBatchEntry<R>, parquet::errors::ParquetError>;
 
-    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        let mut this = self.project();
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
         loop {
+            let this = self.as_mut().project();
             match this.iter {
                 Some(iter) => {
                     if let Some(entry) = iter.next() {
+                        self.poll_prefetch(cx);
                         return Poll::Ready(Some(Ok(entry)));
                     }
                     *this.iter = None;
                 }
                 None => {
-                    let record_batch = ready!(this.stream.as_mut().poll_next(cx)).transpose()?;
+                    let record_batch = match this.prefetched.pop_front() {
+                        Some(record_batch) => Some(record_batch?),
+                        None if *this.exhausted => None,
+                        None => ready!(this.stream.poll_next(cx)).transpose()?,
+                    };
                     let record_batch = match record_batch {
-                        Some(record_batch) => record_batch,
+                        Some(record_batch) => widen_ts_batch(record_batch)?,
                         None => return Poll::Ready(None),
                     };
                     *this.iter = Some(RecordBatchIterator::new(
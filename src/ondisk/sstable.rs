@@ -1,8 +1,16 @@
-use std::{marker::PhantomData, ops::Bound, sync::Arc};
+use std::{
+    marker::PhantomData,
+    ops::{Bound, Range},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
 
+use async_lock::Semaphore;
 use fusio::{dynamic::DynFile, DynRead};
 use fusio_parquet::reader::AsyncReader;
-use futures_util::StreamExt;
+use futures_util::{future::BoxFuture, StreamExt};
 use parquet::{
     arrow::{
         arrow_reader::{ArrowReaderBuilder, ArrowReaderOptions},
@@ -10,13 +18,20 @@ use parquet::{
         ParquetRecordBatchStreamBuilder, ProjectionMask,
     },
     errors::Result as ParquetResult,
+    file::metadata::ParquetMetaData,
 };
 use parquet_lru::{BoxedFileReader, DynLruCache};
+use tokio_util::bytes::Bytes;
 use ulid::Ulid;
 
-use super::{arrows::get_range_filter, scan::SsTableScan};
+use super::{
+    arrows::{bloom_filter_bytes, get_range_filter, widen_ts_schema},
+    fence::{page_selection, selected_row_groups, Fences},
+    scan::SsTableScan,
+};
 use crate::{
-    record::Record,
+    record::{Key, Record},
+    statistics::QueryStatsCollector,
     stream::record_batch::RecordBatchEntry,
     timestamp::{Timestamp, TimestampedRef},
 };
@@ -26,6 +41,8 @@ where
     R: Record,
 {
     reader: BoxedFileReader,
+    size: usize,
+    stats: Option<QueryStatsCollector>,
     _marker: PhantomData<R>,
 }
 
@@ -47,10 +64,83 @@ where
                     BoxedFileReader::new(AsyncReader::new(file, size).await?),
                 )
                 .await,
+            size: size as usize,
+            stats: None,
             _marker: PhantomData,
         })
     }
 
+    /// add the bytes fetched from the file from now on to `bytes`, including the ones served by
+    /// the parquet LRU cache
+    pub(crate) fn count_reads(self, bytes: Arc<AtomicU64>) -> Self {
+        SsTable {
+            reader: BoxedFileReader::new(CountingReader {
+                inner: self.reader,
+                bytes,
+            }),
+            size: self.size,
+            stats: self.stats,
+            _marker: PhantomData,
+        }
+    }
+
+    /// hold a permit of `permits` for each read of the file, shared by the tables of a scan, see
+    /// [`Scan::max_inflight_reads`](crate::Scan::max_inflight_reads)
+    pub(crate) fn limit_reads(self, permits: Arc<Semaphore>) -> Self {
+        SsTable {
+            reader: BoxedFileReader::new(LimitedReader {
+                inner: self.reader,
+                permits,
+            }),
+            size: self.size,
+            stats: self.stats,
+            _marker: PhantomData,
+        }
+    }
+
+    /// read at least `readahead` bytes at once when a read follows the previous one, and serve
+    /// the next reads from them, see
+    /// [`DbOption::scan_readahead_bytes`](crate::DbOption::scan_readahead_bytes)
+    pub(crate) fn readahead(self, readahead: usize) -> Self {
+        if readahead == 0 {
+            return self;
+        }
+        SsTable {
+            reader: BoxedFileReader::new(ReadaheadReader {
+                inner: self.reader,
+                readahead,
+                size: self.size,
+                last_end: None,
+                buffer: None,
+            }),
+            size: self.size,
+            stats: self.stats,
+            _marker: PhantomData,
+        }
+    }
+
+    /// count the table into `stats`, along with the row groups it reads and whether its bloom
+    /// filters rule out the key of a get
+    pub(crate) fn collect_stats(self, stats: Option<&QueryStatsCollector>) -> Self {
+        if let Some(stats) = stats {
+            stats.file_consulted();
+        }
+        SsTable {
+            stats: stats.cloned(),
+            ..self
+        }
+    }
+
+    /// the footer of the table, with its row counts and the statistics of its row groups
+    pub(crate) async fn metadata(mut self) -> ParquetResult<Arc<ParquetMetaData>> {
+        self.reader.get_metadata().await
+    }
+
+    /// the fences of the row groups of the table, read from its footer
+    pub(crate) async fn fences(&mut self) -> ParquetResult<Option<Fences>> {
+        Fences::new(&self.reader.get_metadata().await?)
+    }
+
     async fn into_parquet_builder(
         self,
         limit: Option<usize>,
@@ -68,23 +158,71 @@ where
         Ok(builder.with_projection(projection_mask))
     }
 
+    /// the table is skipped without reading its pages if the bloom filters of the primary key
+    /// column rule the key out of every row group. Only `row_groups` are read when given, as
+    /// found by the [`Fences`] of the table
     pub(crate) async fn get(
         self,
-        key: &TimestampedRef<R::Key>,
+        key: TimestampedRef<'_, R::Key>,
         projection_mask: ProjectionMask,
+        row_groups: Option<Range<usize>>,
     ) -> ParquetResult<Option<RecordBatchEntry<R>>> {
-        self.scan(
+        let stats = self.stats.clone();
+        let mut builder = self
+            .into_parquet_builder(Some(1), projection_mask.clone())
+            .await?;
+        let row_groups: Vec<usize> = row_groups
+            .unwrap_or(0..builder.metadata().num_row_groups())
+            .collect();
+        if !Self::may_contain(&mut builder, &row_groups, key.value()).await? {
+            if let Some(stats) = stats.as_ref().filter(|_| !row_groups.is_empty()) {
+                stats.bloom_filter_hit();
+            }
+            return Ok(None);
+        }
+        if let Some(stats) = &stats {
+            stats.row_groups_read(row_groups.len());
+        }
+        let builder = builder.with_row_groups(row_groups);
+
+        Self::scan_builder(
+            builder,
             (Bound::Included(key.value()), Bound::Included(key.value())),
             key.ts(),
-            Some(1),
             projection_mask,
-        )
-        .await?
+        )?
         .next()
         .await
         .transpose()
     }
 
+    /// whether any row group may hold the key, which row groups written without a bloom filter
+    /// on the primary key column always may
+    async fn may_contain(
+        builder: &mut ArrowReaderBuilder<ParquetAsyncReader<Box<dyn AsyncFileReader + 'static>>>,
+        row_groups: &[usize],
+        key: &R::Key,
+    ) -> ParquetResult<bool> {
+        if row_groups.is_empty() {
+            return Ok(false);
+        }
+        let Some(bytes) = bloom_filter_bytes(key.to_arrow_datum().as_ref()) else {
+            return Ok(true);
+        };
+        for &row_group in row_groups {
+            // the primary key is the column after `_null` and `_ts`
+            match builder
+                .get_row_group_column_bloom_filter(row_group, 2)
+                .await?
+            {
+                Some(filter) if !filter.check(bytes.as_slice()) => continue,
+                _ => return Ok(true),
+            }
+        }
+        Ok(false)
+    }
+
+    /// the pages the page index of the primary key column rules out of the range are skipped
     pub(crate) async fn scan<'scan>(
         self,
         range: (Bound<&'scan R::Key>, Bound<&'scan R::Key>),
@@ -92,12 +230,39 @@ where
         limit: Option<usize>,
         projection_mask: ProjectionMask,
     ) -> Result<SsTableScan<R>, parquet::errors::ParquetError> {
-        let builder = self
+        let stats = self.stats.clone();
+        let mut builder = self
             .into_parquet_builder(limit, projection_mask.clone())
             .await?;
+        let metadata = builder.metadata().clone();
+        let selection = page_selection(&metadata, range)?;
+        if let Some(stats) = &stats {
+            stats.row_groups_read(match &selection {
+                Some(selection) => selected_row_groups(
+                    metadata
+                        .row_groups()
+                        .iter()
+                        .map(|row_group| row_group.num_rows() as usize),
+                    selection,
+                ),
+                None => metadata.num_row_groups(),
+            });
+        }
+        if let Some(selection) = selection {
+            builder = builder.with_row_selection(selection);
+        }
 
+        Self::scan_builder(builder, range, ts, projection_mask)
+    }
+
+    fn scan_builder<'scan>(
+        builder: ArrowReaderBuilder<ParquetAsyncReader<Box<dyn AsyncFileReader + 'static>>>,
+        range: (Bound<&'scan R::Key>, Bound<&'scan R::Key>),
+        ts: Timestamp,
+        projection_mask: ProjectionMask,
+    ) -> Result<SsTableScan<R>, parquet::errors::ParquetError> {
         let schema_descriptor = builder.metadata().file_metadata().schema_descr();
-        let full_schema = builder.schema().clone();
+        let full_schema = widen_ts_schema(builder.schema());
 
         // Safety: filter's lifetime relies on range's lifetime, sstable must not live longer than
         // it
@@ -111,11 +276,136 @@ where
     }
 }
 
+struct CountingReader {
+    inner: BoxedFileReader,
+    bytes: Arc<AtomicU64>,
+}
+
+impl AsyncFileReader for CountingReader {
+    fn get_bytes(&mut self, range: Range<usize>) -> BoxFuture<'_, ParquetResult<Bytes>> {
+        self.bytes.fetch_add(range.len() as u64, Ordering::Relaxed);
+        self.inner.get_bytes(range)
+    }
+
+    fn get_metadata(&mut self) -> BoxFuture<'_, ParquetResult<Arc<ParquetMetaData>>> {
+        self.inner.get_metadata()
+    }
+
+    fn get_byte_ranges(
+        &mut self,
+        ranges: Vec<Range<usize>>,
+    ) -> BoxFuture<'_, ParquetResult<Vec<Bytes>>> {
+        let bytes = ranges.iter().map(|range| range.len() as u64).sum();
+        self.bytes.fetch_add(bytes, Ordering::Relaxed);
+        self.inner.get_byte_ranges(ranges)
+    }
+}
+
+struct LimitedReader {
+    inner: BoxedFileReader,
+    permits: Arc<Semaphore>,
+}
+
+impl AsyncFileReader for LimitedReader {
+    fn get_bytes(&mut self, range: Range<usize>) -> BoxFuture<'_, ParquetResult<Bytes>> {
+        Box::pin(async move {
+            let _permit = self.permits.acquire().await;
+            self.inner.get_bytes(range).await
+        })
+    }
+
+    fn get_metadata(&mut self) -> BoxFuture<'_, ParquetResult<Arc<ParquetMetaData>>> {
+        Box::pin(async move {
+            let _permit = self.permits.acquire().await;
+            self.inner.get_metadata().await
+        })
+    }
+
+    fn get_byte_ranges(
+        &mut self,
+        ranges: Vec<Range<usize>>,
+    ) -> BoxFuture<'_, ParquetResult<Vec<Bytes>>> {
+        Box::pin(async move {
+            let _permit = self.permits.acquire().await;
+            self.inner.get_byte_ranges(ranges).await
+        })
+    }
+}
+
+struct ReadaheadReader {
+    inner: BoxedFileReader,
+    readahead: usize,
+    size: usize,
+    /// where the previous read ended
+    last_end: Option<usize>,
+    /// the bytes read ahead, with their offset in the file
+    buffer: Option<(usize, Bytes)>,
+}
+
+impl ReadaheadReader {
+    fn buffered(&self, range: &Range<usize>) -> Option<Bytes> {
+        let (offset, bytes) = self.buffer.as_ref()?;
+        (range.start >= *offset && range.end <= offset + bytes.len())
+            .then(|| bytes.slice(range.start - offset..range.end - offset))
+    }
+
+    /// a read starting at the end of the previous one, or skipping less than the readahead, is
+    /// taken as a sequential scan
+    fn is_sequential(&self, start: usize) -> bool {
+        self.last_end
+            .is_some_and(|end| start >= end && start - end < self.readahead)
+    }
+
+    async fn read(&mut self, ranges: Vec<Range<usize>>) -> ParquetResult<Vec<Bytes>> {
+        let (Some(start), Some(end)) = (
+            ranges.iter().map(|range| range.start).min(),
+            ranges.iter().map(|range| range.end).max(),
+        ) else {
+            return Ok(Vec::new());
+        };
+        if ranges.iter().any(|range| self.buffered(range).is_none()) {
+            if !self.is_sequential(start) {
+                self.last_end = Some(end);
+                return self.inner.get_byte_ranges(ranges).await;
+            }
+            let ahead = (start + self.readahead).min(self.size).max(end);
+            self.buffer = Some((start, self.inner.get_bytes(start..ahead).await?));
+        }
+        self.last_end = Some(end);
+
+        Ok(ranges
+            .iter()
+            .map(|range| self.buffered(range).unwrap())
+            .collect())
+    }
+}
+
+impl AsyncFileReader for ReadaheadReader {
+    fn get_bytes(&mut self, range: Range<usize>) -> BoxFuture<'_, ParquetResult<Bytes>> {
+        Box::pin(async move { Ok(self.read(vec![range]).await?.remove(0)) })
+    }
+
+    fn get_metadata(&mut self) -> BoxFuture<'_, ParquetResult<Arc<ParquetMetaData>>> {
+        self.inner.get_metadata()
+    }
+
+    fn get_byte_ranges(
+        &mut self,
+        ranges: Vec<Range<usize>>,
+    ) -> BoxFuture<'_, ParquetResult<Vec<Bytes>>> {
+        Box::pin(self.read(ranges))
+    }
+}
+
 #[cfg(all(test, feature = "tokio"))]
 pub(crate) mod tests {
     use std::{borrow::Borrow, fs::File, ops::Bound, sync::Arc};
 
-    use arrow::array::RecordBatch;
+    use arrow::{
+        array::RecordBatch,
+        compute::cast,
+        datatypes::{DataType, Field, Schema},
+    };
     use fusio::{dynamic::DynFile, path::Path, DynFs};
     use fusio_dispatch::FsOptions;
     use fusio_parquet::writer::AsyncWriter;
@@ -214,6 +504,7 @@ pub(crate) mod tests {
                         &arrow_to_parquet_schema(Test::arrow_schema()).unwrap(),
                         [0, 1, 2, 3],
                     ),
+                    None,
                 )
                 .await
                 .unwrap()
@@ -231,6 +522,7 @@ pub(crate) mod tests {
                         &arrow_to_parquet_schema(Test::arrow_schema()).unwrap(),
                         [0, 1, 2, 4],
                     ),
+                    None,
                 )
                 .await
                 .unwrap()
@@ -248,6 +540,7 @@ pub(crate) mod tests {
                         &arrow_to_parquet_schema(Test::arrow_schema()).unwrap(),
                         [0, 1, 2],
                     ),
+                    None,
                 )
                 .await
                 .unwrap()
@@ -283,7 +576,7 @@ pub(crate) mod tests {
                 .await
                 .scan(
                     (Bound::Unbounded, Bound::Unbounded),
-                    1_u32.into(),
+                    1_u64.into(),
                     None,
                     ProjectionMask::roots(
                         &arrow_to_parquet_schema(Test::arrow_schema()).unwrap(),
@@ -308,7 +601,7 @@ pub(crate) mod tests {
                 .await
                 .scan(
                     (Bound::Unbounded, Bound::Unbounded),
-                    1_u32.into(),
+                    1_u64.into(),
                     None,
                     ProjectionMask::roots(
                         &arrow_to_parquet_schema(Test::arrow_schema()).unwrap(),
@@ -333,7 +626,7 @@ pub(crate) mod tests {
                 .await
                 .scan(
                     (Bound::Unbounded, Bound::Unbounded),
-                    1_u32.into(),
+                    1_u64.into(),
                     None,
                     ProjectionMask::roots(
                         &arrow_to_parquet_schema(Test::arrow_schema()).unwrap(),
@@ -354,4 +647,61 @@ pub(crate) mod tests {
             assert_eq!(entry_1.get().unwrap().vbool, None);
         }
     }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn read_32_bit_timestamps() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let manager = StoreManager::new(FsOptions::Local, vec![]).unwrap();
+        let base_fs = manager.base_fs();
+        let record_batch = get_test_record_batch::<TokioExecutor>(
+            DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap()),
+            TokioExecutor::new(),
+        )
+        .await;
+
+        // an SST written before `_ts` was widened to `UInt64`
+        let mut fields = Test::arrow_schema().fields().to_vec();
+        fields[1] = Arc::new(Field::new("_ts", DataType::UInt32, false));
+        let schema = Arc::new(Schema::new(fields));
+        let mut columns = record_batch.columns().to_vec();
+        columns[1] = cast(&columns[1], &DataType::UInt32).unwrap();
+        let record_batch = RecordBatch::try_new(schema.clone(), columns).unwrap();
+
+        let table_path = temp_dir.path().join("read_32_bit_timestamps.parquet");
+        let _ = File::create(&table_path).unwrap();
+        let table_path = Path::from_filesystem_path(table_path).unwrap();
+        let file = base_fs
+            .open_options(&table_path, FileType::Parquet.open_options(false))
+            .await
+            .unwrap();
+        let mut writer = AsyncArrowWriter::try_new(AsyncWriter::new(file), schema, None).unwrap();
+        writer.write(&record_batch).await.unwrap();
+        writer.close().await.unwrap();
+
+        let key = Timestamped::new("hello".to_owned(), 1.into());
+        let entry = open_sstable::<Test>(base_fs, &table_path)
+            .await
+            .get(key.borrow(), ProjectionMask::all(), None)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(entry.get().unwrap().vstring, "hello");
+
+        let mut scan = open_sstable::<Test>(base_fs, &table_path)
+            .await
+            .scan(
+                (Bound::Unbounded, Bound::Unbounded),
+                1_u64.into(),
+                None,
+                ProjectionMask::all(),
+            )
+            .await
+            .unwrap();
+        let entry_0 = scan.next().await.unwrap().unwrap();
+        assert_eq!(entry_0.get().unwrap().vstring, "hello");
+        assert_eq!(entry_0.get().unwrap().vu32, Some(12));
+        let entry_1 = scan.next().await.unwrap().unwrap();
+        assert_eq!(entry_1.get().unwrap().vstring, "world");
+        assert!(scan.next().await.is_none());
+    }
 }
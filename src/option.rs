@@ -1,6 +1,8 @@
 use std::{
     fmt::{Debug, Formatter},
     marker::PhantomData,
+    sync::Arc,
+    time::Duration,
 };
 
 use fusio::path::Path;
@@ -11,21 +13,276 @@ use parquet::{
     format::SortingColumn,
     schema::types::ColumnPath,
 };
+use parquet_lru::NoCache;
+use thiserror::Error;
 
 use crate::{
+    compaction::filter::CompactionFilter,
+    event::EventListener,
     fs::{FileId, FileType},
     record::Record,
+    scope::Scope,
     trigger::TriggerType,
     version::{Version, MAX_LEVEL},
-    DbError,
+    write_buffer::WriteBufferManager,
+    write_validator::WriteValidator,
+    DbError, ParquetLru,
 };
 
 const DEFAULT_WAL_BUFFER_SIZE: usize = 4 * 1024;
 
+/// When the WAL is flushed to the file system, trading the writes lost on a crash for the
+/// latency of each write.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "config",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum WalSyncPolicy {
+    /// flush before every write or transaction commit returns, the commits running concurrently
+    /// share a single flush
+    PerCommit,
+    /// flush on a background timer of the executor, losing at most the writes of the last
+    /// interval
+    Interval(#[cfg_attr(feature = "config", serde(with = "crate::config::millis"))] Duration),
+    /// flush when the WAL buffer is full or the memtable is frozen, and leave the rest to the
+    /// operating system
+    #[default]
+    Os,
+}
+
+/// Compression of the WAL entries, each entry is compressed on its own.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "config",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum WalCompression {
+    #[default]
+    None,
+    Lz4,
+    /// zstd at the compression level, with the `zstd` feature
+    #[cfg(feature = "zstd")]
+    Zstd(i32),
+}
+
+/// Encoding of the WAL entries and the version edits written. Every version is read back, so a
+/// database keeps opening after the version is changed either way.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "config",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum FormatVersion {
+    /// integers and lengths of a fixed width, readable by the releases before
+    /// [`FormatVersion::V2`]
+    V1,
+    /// integers and lengths as varints, the signed integers zigzag encoded, shrinking the small
+    /// records dominated by the encoding overhead
+    #[default]
+    V2,
+}
+
+/// Compression of the pages of the SSTs written by flushes and compactions.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "config",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum SstCompression {
+    None,
+    Snappy,
+    #[default]
+    Lz4,
+    /// zstd at the compression level, from 1 to 22, with the `zstd` feature
+    #[cfg(feature = "zstd")]
+    Zstd(i32),
+}
+
+impl From<SstCompression> for Compression {
+    fn from(compression: SstCompression) -> Self {
+        match compression {
+            SstCompression::None => Compression::UNCOMPRESSED,
+            SstCompression::Snappy => Compression::SNAPPY,
+            SstCompression::Lz4 => Compression::LZ4,
+            #[cfg(feature = "zstd")]
+            SstCompression::Zstd(level) => {
+                Compression::ZSTD(parquet::basic::ZstdLevel::try_new(level.clamp(1, 22)).unwrap())
+            }
+        }
+    }
+}
+
+/// settings of the SSTs written to a level, overriding the ones of all levels
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct LevelSstOptions {
+    compression: Option<SstCompression>,
+    dictionary: Option<bool>,
+}
+
+/// How the major compactions pick the tables they merge.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "config",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum CompactionStyle {
+    /// each level holds [`DbOption::level_sst_magnification`] times the tables, or
+    /// [`DbOption::level_bytes_magnification`] times the bytes, of the one above it, whose
+    /// tables are merged into the overlapping ones of the next level
+    #[default]
+    Leveled,
+    /// the tables of level 0 and the levels below it are sorted runs, the adjacent runs of
+    /// similar sizes are merged once there are more than `max_runs` of them. Rewrites each
+    /// record fewer times than [`CompactionStyle::Leveled`] at the cost of more space and reads
+    Tiered {
+        max_runs: usize,
+        /// percentage by which the largest run merged may exceed the smallest one
+        size_ratio: usize,
+        /// the fewest runs merged at once
+        min_merge_width: usize,
+    },
+}
+
+/// How writes are held back to let the flushes and compactions catch up, see
+/// [`DbOption::immutable_stop_writes_trigger`] and [`DbOption::level0_stop_writes_trigger`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WriteStall {
+    #[default]
+    None,
+    /// each write is delayed by [`DbOption::write_slowdown_delay`]
+    Slowdown,
+    /// writes are blocked until the stop triggers are no longer reached
+    Stop,
+}
+
+/// How the recovery handles an incomplete or corrupted WAL entry.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "config",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum WalRecoveryMode {
+    /// only skip an incomplete or corrupted last entry of the newest WAL, as left by a crash,
+    /// and fail with [`RecoverError::Checksum`](crate::wal::RecoverError::Checksum) on one
+    /// anywhere else
+    #[default]
+    Strict,
+    /// replay each WAL up to its first incomplete or corrupted entry and skip the rest of it,
+    /// still replaying the WALs after it: the writes skipped are lost while later ones are
+    /// recovered, so only to salvage a database failing to open otherwise
+    SkipCorrupted,
+}
+
+/// How opening the database handles an incomplete or corrupted end of the version log, which
+/// records the SSTs of each level.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "config",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum VersionLogRecoveryMode {
+    /// recover the version up to the last valid edit, logging the end of the log discarded,
+    /// which is then rewritten without it
+    #[default]
+    SkipCorrupted,
+    /// fail to open with [`DbError::Version`](crate::DbError::Version) instead
+    Strict,
+}
+
+/// A combination of [`DbOption`]s a [`DB`](crate::DB) can not run with, returned by
+/// [`DbOption::validate`] and when opening the database.
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum OptionError {
+    #[error("{option} is {value}, it must be at least {min}")]
+    TooSmall {
+        option: &'static str,
+        value: usize,
+        min: usize,
+    },
+    #[error("tombstone_compaction_ratio is {0}, it must be above 0 and at most 1")]
+    TombstoneRatio(f64),
+    #[error("{slowdown} is {slowdown_value}, past {stop} of {stop_value} it never applies")]
+    SlowdownPastStop {
+        slowdown: &'static str,
+        slowdown_value: usize,
+        stop: &'static str,
+        stop_value: usize,
+    },
+}
+
+/// The options of a [`DB`](crate::DB) that can be changed while it is open, with
+/// [`DB::set_options`](crate::DB::set_options). The ones left to `None` are kept, each is
+/// described by the builder of the same name of [`DbOption`].
+#[derive(Debug, Clone, Default)]
+pub struct MutableOptions {
+    pub immutable_chunk_num: Option<usize>,
+    pub immutable_chunk_max_num: Option<usize>,
+    pub major_threshold_with_sst_size: Option<usize>,
+    pub level_sst_magnification: Option<usize>,
+    pub level_bytes_magnification: Option<usize>,
+    pub tombstone_compaction_ratio: Option<f64>,
+    pub periodic_compaction_interval: Option<Duration>,
+    pub immutable_slowdown_writes_trigger: Option<usize>,
+    pub immutable_stop_writes_trigger: Option<usize>,
+    pub level0_slowdown_writes_trigger: Option<usize>,
+    pub level0_stop_writes_trigger: Option<usize>,
+    pub write_slowdown_delay: Option<Duration>,
+    pub max_writes_per_sec: Option<u64>,
+    pub max_write_bytes_per_sec: Option<u64>,
+}
+
+impl MutableOptions {
+    pub(crate) fn apply<R>(&self, mut option: DbOption<R>) -> DbOption<R>
+    where
+        R: Record,
+    {
+        macro_rules! set {
+            ($($field:ident),* $(,)?) => {
+                $(
+                    if let Some(value) = self.$field {
+                        option = option.$field(value);
+                    }
+                )*
+            };
+        }
+        set!(
+            immutable_chunk_num,
+            immutable_chunk_max_num,
+            major_threshold_with_sst_size,
+            level_sst_magnification,
+            level_bytes_magnification,
+            tombstone_compaction_ratio,
+            periodic_compaction_interval,
+            immutable_slowdown_writes_trigger,
+            immutable_stop_writes_trigger,
+            level0_slowdown_writes_trigger,
+            level0_stop_writes_trigger,
+            write_slowdown_delay,
+            max_writes_per_sec,
+            max_write_bytes_per_sec,
+        );
+        option
+    }
+}
+
 /// configure the operating parameters of each component in the [`DB`](crate::DB)
 #[derive(Clone)]
-pub struct DbOption<R> {
+pub struct DbOption<R>
+where
+    R: Record,
+{
     pub(crate) clean_channel_buffer: usize,
+    pub(crate) file_deletion_batch_size: usize,
+    pub(crate) max_file_deletes_per_sec: Option<u64>,
+    pub(crate) file_deletion_retries: usize,
     pub(crate) base_path: Path,
     pub(crate) base_fs: FsOptions,
     // TODO: DEBUG
@@ -36,12 +293,64 @@ pub struct DbOption<R> {
     pub(crate) major_default_oldest_table_num: usize,
     pub(crate) major_l_selection_table_max_num: usize,
     pub(crate) major_threshold_with_sst_size: usize,
+    pub(crate) level_base_bytes: Option<usize>,
+    pub(crate) level_bytes_magnification: usize,
+    pub(crate) compaction_style: CompactionStyle,
+    pub(crate) max_subcompactions: usize,
+    pub(crate) max_background_compactions: usize,
+    pub(crate) tombstone_compaction_ratio: Option<f64>,
+    pub(crate) bytes_per_seek: Option<u64>,
+    pub(crate) compaction_memory_budget: usize,
+    pub(crate) periodic_compaction_interval: Option<Duration>,
+    pub(crate) immutable_slowdown_writes_trigger: Option<usize>,
+    pub(crate) immutable_stop_writes_trigger: Option<usize>,
+    pub(crate) level0_slowdown_writes_trigger: Option<usize>,
+    pub(crate) level0_stop_writes_trigger: Option<usize>,
+    pub(crate) write_slowdown_delay: Duration,
+    pub(crate) write_buffer_manager: Option<Arc<WriteBufferManager>>,
+    pub(crate) memtable_flush_interval: Option<Duration>,
+    pub(crate) max_total_wal_size: Option<usize>,
+    pub(crate) scan_prefetch_batches: usize,
+    pub(crate) scan_readahead_bytes: usize,
+    pub(crate) max_writes_per_sec: Option<u64>,
+    pub(crate) max_write_bytes_per_sec: Option<u64>,
+    pub(crate) obsolete_file_retention: Option<Duration>,
     pub(crate) max_sst_file_size: usize,
     pub(crate) version_log_snapshot_threshold: u32,
     pub(crate) trigger_type: TriggerType,
     pub(crate) use_wal: bool,
+    pub(crate) wal_sync_policy: WalSyncPolicy,
+    pub(crate) wal_recovery_mode: WalRecoveryMode,
+    pub(crate) version_log_recovery_mode: VersionLogRecoveryMode,
     pub(crate) wal_buffer_size: usize,
+    pub(crate) wal_segment_size: Option<usize>,
+    pub(crate) wal_compression: WalCompression,
+    pub(crate) format_version: FormatVersion,
+    pub(crate) wal_dir: Option<(Path, FsOptions)>,
+    pub(crate) wal_archive: Option<(Path, FsOptions)>,
+    pub(crate) wal_archive_max_files: Option<usize>,
     pub(crate) write_parquet_properties: WriterProperties,
+    pub(crate) sst_compression: SstCompression,
+    pub(crate) level_sst_options: Vec<LevelSstOptions>,
+    pub(crate) sst_max_row_group_size: Option<usize>,
+    pub(crate) sst_data_page_size: Option<usize>,
+    pub(crate) sst_write_batch_size: Option<usize>,
+    pub(crate) compaction_filter: Option<Arc<dyn CompactionFilter<R>>>,
+    pub(crate) write_validator: Option<Arc<dyn WriteValidator<R>>>,
+    pub(crate) slow_operation_threshold: Option<Duration>,
+    pub(crate) lock_timeout: Option<Duration>,
+    pub(crate) lock_shards: usize,
+    pub(crate) max_transaction_size: Option<usize>,
+    pub(crate) max_key_size: Option<usize>,
+    pub(crate) max_record_size: Option<usize>,
+    pub(crate) unchecked_writes: bool,
+    pub(crate) paranoid_checks: bool,
+    pub(crate) fast_open: bool,
+    pub(crate) direct_io: bool,
+    pub(crate) preallocate_tables: bool,
+    pub(crate) preallocate_wal: bool,
+    pub(crate) event_listener: Option<Arc<dyn EventListener>>,
+    pub(crate) parquet_lru: Option<ParquetLru>,
     _p: PhantomData<R>,
 }
 
@@ -59,22 +368,76 @@ where
             immutable_chunk_max_num: 5,
             major_threshold_with_sst_size: 4,
             level_sst_magnification: 10,
+            level_base_bytes: None,
+            level_bytes_magnification: 10,
+            compaction_style: CompactionStyle::default(),
+            max_subcompactions: 1,
+            max_background_compactions: 1,
+            tombstone_compaction_ratio: None,
+            bytes_per_seek: None,
+            compaction_memory_budget: 64 * 1024 * 1024,
+            periodic_compaction_interval: None,
+            immutable_slowdown_writes_trigger: None,
+            immutable_stop_writes_trigger: None,
+            level0_slowdown_writes_trigger: None,
+            level0_stop_writes_trigger: None,
+            write_slowdown_delay: Duration::from_millis(1),
+            write_buffer_manager: None,
+            memtable_flush_interval: None,
+            max_total_wal_size: None,
+            scan_prefetch_batches: 1,
+            scan_readahead_bytes: 0,
+            max_writes_per_sec: None,
+            max_write_bytes_per_sec: None,
+            obsolete_file_retention: None,
             max_sst_file_size: 256 * 1024 * 1024,
             clean_channel_buffer: 10,
+            file_deletion_batch_size: 64,
+            max_file_deletes_per_sec: None,
+            file_deletion_retries: 3,
             base_path,
             write_parquet_properties: WriterProperties::builder()
-                .set_compression(Compression::LZ4)
                 .set_column_statistics_enabled(column_paths.clone(), EnabledStatistics::Page)
                 .set_column_bloom_filter_enabled(column_paths.clone(), true)
                 .set_sorting_columns(Some(sorting_columns))
                 .set_created_by(concat!("tonbo version ", env!("CARGO_PKG_VERSION")).to_owned())
                 .build(),
+            sst_compression: SstCompression::default(),
+            level_sst_options: vec![LevelSstOptions::default(); MAX_LEVEL],
+            sst_max_row_group_size: None,
+            sst_data_page_size: None,
+            sst_write_batch_size: None,
 
             use_wal: true,
+            wal_sync_policy: WalSyncPolicy::default(),
+            wal_recovery_mode: WalRecoveryMode::default(),
+            version_log_recovery_mode: VersionLogRecoveryMode::default(),
             wal_buffer_size: DEFAULT_WAL_BUFFER_SIZE,
+            wal_segment_size: None,
+            wal_compression: WalCompression::default(),
+            format_version: FormatVersion::default(),
+            wal_dir: None,
+            wal_archive: None,
+            wal_archive_max_files: None,
             major_default_oldest_table_num: 3,
             major_l_selection_table_max_num: 4,
             trigger_type: TriggerType::SizeOfMem(64 * 1024 * 1024),
+            compaction_filter: None,
+            write_validator: None,
+            slow_operation_threshold: None,
+            lock_timeout: None,
+            lock_shards: 16,
+            max_transaction_size: None,
+            max_key_size: None,
+            max_record_size: None,
+            unchecked_writes: false,
+            paranoid_checks: false,
+            fast_open: false,
+            direct_io: false,
+            preallocate_tables: false,
+            preallocate_wal: false,
+            event_listener: None,
+            parquet_lru: None,
             _p: Default::default(),
             version_log_snapshot_threshold: 200,
             level_paths: vec![None; MAX_LEVEL],
@@ -108,23 +471,77 @@ where
             immutable_chunk_max_num: 5,
             major_threshold_with_sst_size: 4,
             level_sst_magnification: 10,
+            level_base_bytes: None,
+            level_bytes_magnification: 10,
+            compaction_style: CompactionStyle::default(),
+            max_subcompactions: 1,
+            max_background_compactions: 1,
+            tombstone_compaction_ratio: None,
+            bytes_per_seek: None,
+            compaction_memory_budget: 64 * 1024 * 1024,
+            periodic_compaction_interval: None,
+            immutable_slowdown_writes_trigger: None,
+            immutable_stop_writes_trigger: None,
+            level0_slowdown_writes_trigger: None,
+            level0_stop_writes_trigger: None,
+            write_slowdown_delay: Duration::from_millis(1),
+            write_buffer_manager: None,
+            memtable_flush_interval: None,
+            max_total_wal_size: None,
+            scan_prefetch_batches: 1,
+            scan_readahead_bytes: 0,
+            max_writes_per_sec: None,
+            max_write_bytes_per_sec: None,
+            obsolete_file_retention: None,
             max_sst_file_size: 256 * 1024 * 1024,
             clean_channel_buffer: 10,
+            file_deletion_batch_size: 64,
+            max_file_deletes_per_sec: None,
+            file_deletion_retries: 3,
             base_path,
             base_fs: FsOptions::Local,
             write_parquet_properties: WriterProperties::builder()
-                .set_compression(Compression::LZ4)
                 .set_column_statistics_enabled(column_paths.clone(), EnabledStatistics::Page)
                 .set_column_bloom_filter_enabled(column_paths.clone(), true)
                 .set_sorting_columns(Some(sorting_columns))
                 .set_created_by(concat!("tonbo version ", env!("CARGO_PKG_VERSION")).to_owned())
                 .build(),
+            sst_compression: SstCompression::default(),
+            level_sst_options: vec![LevelSstOptions::default(); MAX_LEVEL],
+            sst_max_row_group_size: None,
+            sst_data_page_size: None,
+            sst_write_batch_size: None,
 
             use_wal: true,
+            wal_sync_policy: WalSyncPolicy::default(),
+            wal_recovery_mode: WalRecoveryMode::default(),
+            version_log_recovery_mode: VersionLogRecoveryMode::default(),
             wal_buffer_size: DEFAULT_WAL_BUFFER_SIZE,
+            wal_segment_size: None,
+            wal_compression: WalCompression::default(),
+            format_version: FormatVersion::default(),
+            wal_dir: None,
+            wal_archive: None,
+            wal_archive_max_files: None,
             major_default_oldest_table_num: 3,
             major_l_selection_table_max_num: 4,
             trigger_type: TriggerType::SizeOfMem(64 * 1024 * 1024),
+            compaction_filter: None,
+            write_validator: None,
+            slow_operation_threshold: None,
+            lock_timeout: None,
+            lock_shards: 16,
+            max_transaction_size: None,
+            max_key_size: None,
+            max_record_size: None,
+            unchecked_writes: false,
+            paranoid_checks: false,
+            fast_open: false,
+            direct_io: false,
+            preallocate_tables: false,
+            preallocate_wal: false,
+            event_listener: None,
+            parquet_lru: None,
             _p: Default::default(),
             version_log_snapshot_threshold: 200,
             level_paths: vec![None; MAX_LEVEL],
@@ -152,6 +569,15 @@ where
         }
     }
 
+    /// the number of `immutables` kept in memory, the oldest
+    /// [`DbOption::immutable_chunk_num`] ones are flushed past it
+    pub fn immutable_chunk_max_num(self, immutable_chunk_max_num: usize) -> Self {
+        DbOption {
+            immutable_chunk_max_num,
+            ..self
+        }
+    }
+
     /// threshold for the number of `parquet` when major compaction is triggered
     pub fn major_threshold_with_sst_size(self, major_threshold_with_sst_size: usize) -> Self {
         DbOption {
@@ -168,6 +594,224 @@ where
         }
     }
 
+    /// target bytes of level 1, the levels below level 0 are compacted by their bytes rather
+    /// than their number of tables, which is off when the sizes of the tables vary
+    pub fn level_base_bytes(self, level_base_bytes: usize) -> Self {
+        DbOption {
+            level_base_bytes: Some(level_base_bytes),
+            ..self
+        }
+    }
+
+    /// times the target bytes of each level exceed the ones of the level above it, with
+    /// [`DbOption::level_base_bytes`]
+    pub fn level_bytes_magnification(self, level_bytes_magnification: usize) -> Self {
+        DbOption {
+            level_bytes_magnification,
+            ..self
+        }
+    }
+
+    /// how the major compactions pick the tables they merge, [`CompactionStyle::Leveled`] by
+    /// default
+    pub fn compaction_style(self, compaction_style: CompactionStyle) -> Self {
+        DbOption {
+            compaction_style,
+            ..self
+        }
+    }
+
+    /// most ranges of keys a major compaction is split into, merged concurrently into their
+    /// own tables, defaults to 1
+    pub fn max_subcompactions(self, max_subcompactions: usize) -> Self {
+        DbOption {
+            max_subcompactions,
+            ..self
+        }
+    }
+
+    /// major compactions running at once in the background on levels apart from each other,
+    /// defaults to 1 which runs them after each flush instead
+    pub fn max_background_compactions(self, max_background_compactions: usize) -> Self {
+        DbOption {
+            max_background_compactions,
+            ..self
+        }
+    }
+
+    /// share of tombstones among the rows of a table below level 0 from which it is compacted
+    /// into the next level even if its level is not over its target, so that the space of the
+    /// removed records is reclaimed soon after a bulk deletion
+    pub fn tombstone_compaction_ratio(self, tombstone_compaction_ratio: f64) -> Self {
+        DbOption {
+            tombstone_compaction_ratio: Some(tombstone_compaction_ratio),
+            ..self
+        }
+    }
+
+    /// bytes of a table paying for a read of it by a get or scan which reads other tables for
+    /// the same keys after it. A table is compacted into the next level once it paid for as
+    /// many such reads as it has of these bytes, and for at least 100 of them
+    pub fn bytes_per_seek(self, bytes_per_seek: u64) -> Self {
+        DbOption {
+            bytes_per_seek: Some(bytes_per_seek),
+            ..self
+        }
+    }
+
+    /// memory a major compaction, or each of its subcompactions, holds for the merged records
+    /// before writing them to its output table, and then again in the row group being written
+    pub fn compaction_memory_budget(self, compaction_memory_budget: usize) -> Self {
+        DbOption {
+            compaction_memory_budget,
+            ..self
+        }
+    }
+
+    /// age of an SST from which it is rewritten even if its level is not over its target, so
+    /// that compaction filters and the current table format eventually apply to every record.
+    /// It should be well above the time a compaction takes
+    pub fn periodic_compaction_interval(self, periodic_compaction_interval: Duration) -> Self {
+        DbOption {
+            periodic_compaction_interval: Some(periodic_compaction_interval),
+            ..self
+        }
+    }
+
+    /// immutable memtables waiting to be flushed from which each write is delayed by
+    /// [`DbOption::write_slowdown_delay`]. Off by default
+    pub fn immutable_slowdown_writes_trigger(
+        self,
+        immutable_slowdown_writes_trigger: usize,
+    ) -> Self {
+        DbOption {
+            immutable_slowdown_writes_trigger: Some(immutable_slowdown_writes_trigger),
+            ..self
+        }
+    }
+
+    /// immutable memtables waiting to be flushed from which writes are blocked until the
+    /// flushes catch up. As the immutables are only flushed once there are more than
+    /// `immutable_chunk_max_num` of them, writes are not blocked before. Off by default
+    pub fn immutable_stop_writes_trigger(self, immutable_stop_writes_trigger: usize) -> Self {
+        DbOption {
+            immutable_stop_writes_trigger: Some(immutable_stop_writes_trigger),
+            ..self
+        }
+    }
+
+    /// tables of level 0 from which each write is delayed by
+    /// [`DbOption::write_slowdown_delay`]. Off by default
+    pub fn level0_slowdown_writes_trigger(self, level0_slowdown_writes_trigger: usize) -> Self {
+        DbOption {
+            level0_slowdown_writes_trigger: Some(level0_slowdown_writes_trigger),
+            ..self
+        }
+    }
+
+    /// tables of level 0 from which writes are blocked until the compactions catch up, applied
+    /// with [`DbOption::max_background_compactions`] of the
+    /// [`CompactionStyle::Leveled`] style, as level 0 is otherwise compacted by the flushes of
+    /// the writes blocked. As level 0 is only compacted from
+    /// [`DbOption::major_threshold_with_sst_size`] tables, writes are not blocked before. Off
+    /// by default
+    pub fn level0_stop_writes_trigger(self, level0_stop_writes_trigger: usize) -> Self {
+        DbOption {
+            level0_stop_writes_trigger: Some(level0_stop_writes_trigger),
+            ..self
+        }
+    }
+
+    /// how long each write is delayed past a slowdown trigger, and how often the writes blocked
+    /// past a stop trigger check whether they may go on, defaults to 1ms
+    pub fn write_slowdown_delay(self, write_slowdown_delay: Duration) -> Self {
+        DbOption {
+            write_slowdown_delay,
+            ..self
+        }
+    }
+
+    /// share a budget of memtable memory with the other databases opened with the same
+    /// `manager`: past it, the database with the most memtable memory is flushed
+    pub fn write_buffer_manager(self, manager: Arc<WriteBufferManager>) -> Self {
+        DbOption {
+            write_buffer_manager: Some(manager),
+            ..self
+        }
+    }
+
+    /// flush the mutable memtable once its oldest write is `memtable_flush_interval` old, even
+    /// if it is not full, to bound the WAL replayed on recovery and the writes lost without
+    /// synced WALs. Off by default
+    pub fn memtable_flush_interval(self, memtable_flush_interval: Duration) -> Self {
+        DbOption {
+            memtable_flush_interval: Some(memtable_flush_interval),
+            ..self
+        }
+    }
+
+    /// flush every memtable once the WALs written for the memtables not flushed yet hold more
+    /// than `max_total_wal_size` bytes, whatever the trigger of the memtable. Bounds the WALs
+    /// replayed on recovery when the records are large. Off by default
+    pub fn max_total_wal_size(self, max_total_wal_size: usize) -> Self {
+        DbOption {
+            max_total_wal_size: Some(max_total_wal_size),
+            ..self
+        }
+    }
+
+    /// the record batches each scan of an SST reads ahead of the one being merged, so that the
+    /// tables of a scan or a compaction fetch their next batches concurrently instead of when
+    /// the merge gets to them. Hides the latency of object storage at the cost of the memory
+    /// of the batches. Defaults to 1, 0 disables it
+    pub fn scan_prefetch_batches(self, scan_prefetch_batches: usize) -> Self {
+        DbOption {
+            scan_prefetch_batches,
+            ..self
+        }
+    }
+
+    /// once a scan of an SST reads where its previous read ended, read at least
+    /// `scan_readahead_bytes` at once and serve the next reads from them. Fewer and larger reads
+    /// speed up cold scans and compactions on disks and object storage. 0, the default,
+    /// disables it
+    pub fn scan_readahead_bytes(self, scan_readahead_bytes: usize) -> Self {
+        DbOption {
+            scan_readahead_bytes,
+            ..self
+        }
+    }
+
+    /// hold the writes back to `max_writes_per_sec` records a second, to throttle a writer
+    /// before it overwhelms the flushes and compactions. The inserts and removals wait before
+    /// being written, the commits of transactions after. Off by default
+    pub fn max_writes_per_sec(self, max_writes_per_sec: u64) -> Self {
+        DbOption {
+            max_writes_per_sec: Some(max_writes_per_sec),
+            ..self
+        }
+    }
+
+    /// hold the writes back to `max_write_bytes_per_sec` bytes of records a second, as
+    /// [`DbOption::max_writes_per_sec`]. Off by default
+    pub fn max_write_bytes_per_sec(self, max_write_bytes_per_sec: u64) -> Self {
+        DbOption {
+            max_write_bytes_per_sec: Some(max_write_bytes_per_sec),
+            ..self
+        }
+    }
+
+    /// keep the SSTs dropped by compactions on the file system for `obsolete_file_retention`
+    /// after no version references them anymore, for the backup tools, external readers and
+    /// secondary instances still reading them. They are removed right away by default, see
+    /// [`DB::hold_obsolete_files`](crate::DB::hold_obsolete_files) to keep them until released
+    pub fn obsolete_file_retention(self, obsolete_file_retention: Duration) -> Self {
+        DbOption {
+            obsolete_file_retention: Some(obsolete_file_retention),
+            ..self
+        }
+    }
+
     /// Maximum size of each parquet
     pub fn max_sst_file_size(self, max_sst_file_size: usize) -> Self {
         DbOption {
@@ -184,7 +828,36 @@ where
         }
     }
 
-    /// specific settings for Parquet
+    /// remove up to `file_deletion_batch_size` obsolete files at once. 64 by default
+    pub fn file_deletion_batch_size(self, file_deletion_batch_size: usize) -> Self {
+        DbOption {
+            file_deletion_batch_size,
+            ..self
+        }
+    }
+
+    /// pace the removal of obsolete files to `max_file_deletes_per_sec` a second, so a large
+    /// compaction does not issue thousands of deletes at once against an object storage. Off by
+    /// default
+    pub fn max_file_deletes_per_sec(self, max_file_deletes_per_sec: u64) -> Self {
+        DbOption {
+            max_file_deletes_per_sec: Some(max_file_deletes_per_sec),
+            ..self
+        }
+    }
+
+    /// retry to remove an obsolete file `file_deletion_retries` times before leaving it behind,
+    /// counted by [`metrics::FILE_DELETION_FAILURES`](crate::metrics::FILE_DELETION_FAILURES).
+    /// 3 by default
+    pub fn file_deletion_retries(self, file_deletion_retries: usize) -> Self {
+        DbOption {
+            file_deletion_retries,
+            ..self
+        }
+    }
+
+    /// specific settings for Parquet, the compression is set by
+    /// [`DbOption::sst_compression`]
     pub fn write_parquet_option(self, write_parquet_properties: WriterProperties) -> Self {
         DbOption {
             write_parquet_properties,
@@ -192,6 +865,72 @@ where
         }
     }
 
+    /// compression of the SSTs written by flushes and compactions, default value is
+    /// [`SstCompression::Lz4`]. SSTs are read whatever compression they were written with
+    pub fn sst_compression(self, sst_compression: SstCompression) -> Self {
+        DbOption {
+            sst_compression,
+            ..self
+        }
+    }
+
+    /// maximum rows of a row group of the SSTs written by flushes and compactions. Smaller row
+    /// groups let point lookups read less of a table, at the cost of larger metadata, defaults
+    /// to the setting of [`DbOption::write_parquet_option`]
+    pub fn sst_max_row_group_size(self, sst_max_row_group_size: usize) -> Self {
+        DbOption {
+            sst_max_row_group_size: Some(sst_max_row_group_size),
+            ..self
+        }
+    }
+
+    /// bytes of a data page of the SSTs written by flushes and compactions, the unit the page
+    /// index lets reads skip. Defaults to the setting of [`DbOption::write_parquet_option`]
+    pub fn sst_data_page_size(self, sst_data_page_size: usize) -> Self {
+        DbOption {
+            sst_data_page_size: Some(sst_data_page_size),
+            ..self
+        }
+    }
+
+    /// rows written to the column writers at a time, the granularity at which the page size is
+    /// checked. Defaults to the setting of [`DbOption::write_parquet_option`]
+    pub fn sst_write_batch_size(self, sst_write_batch_size: usize) -> Self {
+        DbOption {
+            sst_write_batch_size: Some(sst_write_batch_size),
+            ..self
+        }
+    }
+
+    /// compression of the SSTs written to `level`, e.g. none for the first level rewritten by
+    /// every compaction and a high zstd level for the last one, defaults to
+    /// [`DbOption::sst_compression`]
+    pub fn level_sst_compression(
+        mut self,
+        level: usize,
+        compression: SstCompression,
+    ) -> Result<Self, DbError<R>> {
+        if level >= MAX_LEVEL {
+            Err(DbError::ExceedsMaxLevel)?;
+        }
+        self.level_sst_options[level].compression = Some(compression);
+        Ok(self)
+    }
+
+    /// whether the columns of the SSTs written to `level` are dictionary encoded, defaults to
+    /// the setting of [`DbOption::write_parquet_option`]
+    pub fn level_sst_dictionary(
+        mut self,
+        level: usize,
+        dictionary: bool,
+    ) -> Result<Self, DbError<R>> {
+        if level >= MAX_LEVEL {
+            Err(DbError::ExceedsMaxLevel)?;
+        }
+        self.level_sst_options[level].dictionary = Some(dictionary);
+        Ok(self)
+    }
+
     /// disable WAL
     ///
     /// tips: risk of data loss during downtime
@@ -202,6 +941,35 @@ where
         }
     }
 
+    /// when the WAL is flushed, default value is [`WalSyncPolicy::Os`]
+    pub fn wal_sync_policy(self, wal_sync_policy: WalSyncPolicy) -> Self {
+        DbOption {
+            wal_sync_policy,
+            ..self
+        }
+    }
+
+    /// how the recovery handles an incomplete or corrupted WAL entry, default value is
+    /// [`WalRecoveryMode::Strict`]
+    pub fn wal_recovery_mode(self, wal_recovery_mode: WalRecoveryMode) -> Self {
+        DbOption {
+            wal_recovery_mode,
+            ..self
+        }
+    }
+
+    /// how opening the database handles an incomplete or corrupted end of the version log,
+    /// default value is [`VersionLogRecoveryMode::SkipCorrupted`]
+    pub fn version_log_recovery_mode(
+        self,
+        version_log_recovery_mode: VersionLogRecoveryMode,
+    ) -> Self {
+        DbOption {
+            version_log_recovery_mode,
+            ..self
+        }
+    }
+
     /// Maximum size of WAL buffer, default value is 4KB
     pub fn wal_buffer_size(self, wal_buffer_size: usize) -> Self {
         DbOption {
@@ -210,6 +978,63 @@ where
         }
     }
 
+    /// roll the WAL of the mutable memtable to a new segment once it holds more bytes than
+    /// `wal_segment_size`, the segments are removed when the memtable is flushed. By default a
+    /// memtable has a single WAL
+    pub fn wal_segment_size(self, wal_segment_size: usize) -> Self {
+        DbOption {
+            wal_segment_size: Some(wal_segment_size),
+            ..self
+        }
+    }
+
+    /// compress the entries written to the WAL, default value is [`WalCompression::None`]. WALs
+    /// are recovered whatever compression they were written with
+    pub fn wal_compression(self, wal_compression: WalCompression) -> Self {
+        DbOption {
+            wal_compression,
+            ..self
+        }
+    }
+
+    /// encode the WAL entries and the version edits in `format_version`, default value is
+    /// [`FormatVersion::V2`]. [`FormatVersion::V1`] keeps the files readable by the releases
+    /// without varints, e.g. before a downgrade
+    pub fn format_version(self, format_version: FormatVersion) -> Self {
+        DbOption {
+            format_version,
+            ..self
+        }
+    }
+
+    /// write the WALs into `path` of the file system described by `fs_options` instead of the
+    /// `wal` directory of the base path, e.g. to keep them on a local disk while the SSTs are on
+    /// an object storage
+    pub fn wal_dir(self, path: impl Into<Path>, fs_options: FsOptions) -> Self {
+        DbOption {
+            wal_dir: Some((path.into(), fs_options)),
+            ..self
+        }
+    }
+
+    /// move the WALs into `path` of the file system described by `fs_options` instead of removing
+    /// them after their data is flushed, which enables point-in-time recovery and replication
+    pub fn wal_archive(self, path: impl Into<Path>, fs_options: FsOptions) -> Self {
+        DbOption {
+            wal_archive: Some((path.into(), fs_options)),
+            ..self
+        }
+    }
+
+    /// number of archived WALs kept, the oldest ones are removed first. All of them are kept by
+    /// default
+    pub fn wal_archive_max_files(self, wal_archive_max_files: usize) -> Self {
+        DbOption {
+            wal_archive_max_files: Some(wal_archive_max_files),
+            ..self
+        }
+    }
+
     /// When selecting the compaction level during major compaction, if there are no sstables with
     /// intersecting targets, the oldest sstables will be selected by default.
     pub fn major_default_oldest_table_num(self, major_default_oldest_table_num: usize) -> Self {
@@ -219,8 +1044,9 @@ where
         }
     }
 
-    /// VersionLog will use version_log_snapshot_threshold as the cycle to SnapShot to reduce the
-    /// size.
+    /// roll the version log over once it holds `version_log_snapshot_threshold` edits: a snapshot
+    /// of the current version is written to a new log, and the old one is removed once the
+    /// snapshot is closed, so the log replayed on open stays bounded. 200 by default
     pub fn version_log_snapshot_threshold(self, version_log_snapshot_threshold: u32) -> Self {
         DbOption {
             version_log_snapshot_threshold,
@@ -228,6 +1054,11 @@ where
         }
     }
 
+    /// store the SSTs of `level` at `path` of the file system of `fs_options`, e.g. to keep the
+    /// upper levels on local disk and the bottom ones on object storage. Compactions write their
+    /// outputs to the path of the level merged into, so tables move across the tiers as they
+    /// sink, and reads open each table at the path of the level the version holds it in. A
+    /// table is only moved to the next level without being rewritten when both share the path
     pub fn level_path(
         mut self,
         level: usize,
@@ -245,6 +1076,262 @@ where
         self.base_fs = base_fs;
         self
     }
+
+    /// callback deciding whether each record rewritten by major compaction is kept, removed or
+    /// changed
+    pub fn compaction_filter(self, compaction_filter: impl CompactionFilter<R> + 'static) -> Self {
+        DbOption {
+            compaction_filter: Some(Arc::new(compaction_filter)),
+            ..self
+        }
+    }
+
+    /// callback checking every record and deletion before it is written, rejecting the writes it
+    /// fails. Range deletions and the rows of
+    /// [`DB::insert_record_batch`](crate::DB::insert_record_batch) are not checked
+    pub fn write_validator(self, write_validator: impl WriteValidator<R> + 'static) -> Self {
+        DbOption {
+            write_validator: Some(Arc::new(write_validator)),
+            ..self
+        }
+    }
+
+    /// gets and scans taking longer than the threshold are logged at the `warn` level with the
+    /// SSTs and rows they touched
+    pub fn slow_operation_threshold(self, slow_operation_threshold: Duration) -> Self {
+        DbOption {
+            slow_operation_threshold: Some(slow_operation_threshold),
+            ..self
+        }
+    }
+
+    /// how long a transaction waits for the lock of a key held by another one before failing
+    /// with [`CommitError::LockTimeout`](crate::transaction::CommitError::LockTimeout). Waits
+    /// closing a cycle fail with
+    /// [`CommitError::Deadlock`](crate::transaction::CommitError::Deadlock) regardless
+    pub fn lock_timeout(self, lock_timeout: Duration) -> Self {
+        DbOption {
+            lock_timeout: Some(lock_timeout),
+            ..self
+        }
+    }
+
+    /// the number of maps the key locks of the transactions are spread over by the hash of the
+    /// keys, so that locking different keys rarely contends. Defaults to 16
+    pub fn lock_shards(self, lock_shards: usize) -> Self {
+        DbOption {
+            lock_shards,
+            ..self
+        }
+    }
+
+    /// the bytes of writes a transaction buffers before commit, the commit of a transaction
+    /// buffering more fails with
+    /// [`CommitError::TooLarge`](crate::transaction::CommitError::TooLarge), see
+    /// [`Transaction::size`](crate::transaction::Transaction::size)
+    pub fn max_transaction_size(self, max_transaction_size: usize) -> Self {
+        DbOption {
+            max_transaction_size: Some(max_transaction_size),
+            ..self
+        }
+    }
+
+    /// the encoded bytes of the largest primary key written, a write of a larger key fails with
+    /// [`DbError::KeyTooLarge`](crate::DbError::KeyTooLarge) before any of its batch or
+    /// transaction is written, as does a range deletion bounded by one
+    pub fn max_key_size(self, max_key_size: usize) -> Self {
+        DbOption {
+            max_key_size: Some(max_key_size),
+            ..self
+        }
+    }
+
+    /// the bytes of the largest record written as given by
+    /// [`Record::size`](crate::record::Record::size), its key included, a write of a larger
+    /// record fails with [`DbError::RecordTooLarge`](crate::DbError::RecordTooLarge) before any
+    /// of its batch or transaction is written. The rows of
+    /// [`DB::insert_record_batch`](crate::DB::insert_record_batch) are not checked
+    pub fn max_record_size(self, max_record_size: usize) -> Self {
+        DbOption {
+            max_record_size: Some(max_record_size),
+            ..self
+        }
+    }
+
+    /// skip the checks and the bookkeeping of writes, for the throughput of a trusted bulk writer
+    /// in benchmarks or ETL: the sizes of their records are not computed unless
+    /// [`DbOption::max_write_bytes_per_sec`] limits them, and transactions commit without taking
+    /// the locks of their keys nor checking them for write conflicts, as with
+    /// [`IsolationLevel::ReadCommitted`](crate::transaction::IsolationLevel::ReadCommitted),
+    /// and without tracking their size against [`DbOption::max_transaction_size`]. Nor are the
+    /// keys and records checked against [`DbOption::max_key_size`] and
+    /// [`DbOption::max_record_size`]. Not safe with transactions writing the same keys
+    /// concurrently, whose updates may be lost. Disabled by default
+    pub fn unchecked_writes(self, unchecked_writes: bool) -> Self {
+        DbOption {
+            unchecked_writes,
+            ..self
+        }
+    }
+
+    /// check that scans and compactions merge the entries in order, and that flushes and
+    /// compactions leave the tables of each level below the first sorted without overlapping
+    /// and only reference tables which exist, failing on a violation instead of spreading the
+    /// corruption. The version recovered when opening is checked the same, see
+    /// [`DbOption::fast_open`]. Costs some CPU and a file open per table written, disabled by
+    /// default
+    pub fn paranoid_checks(self, paranoid_checks: bool) -> Self {
+        DbOption {
+            paranoid_checks,
+            ..self
+        }
+    }
+
+    /// check the tables of the version recovered by [`DB::new`](crate::DB::new) with
+    /// [`DbOption::paranoid_checks`] in the background once the database is open, rather than
+    /// before it returns. A problem found stops the database from accepting writes as a failed
+    /// check of a compaction does. Disabled by default
+    pub fn fast_open(self, fast_open: bool) -> Self {
+        DbOption { fast_open, ..self }
+    }
+
+    /// read and write the SSTs on the local file system with `O_DIRECT`, bypassing the page cache
+    /// so that the hosts dedicated to the database do not keep a second copy of the pages
+    /// cached by [`DbOption::parquet_lru`]. Reads are widened to aligned blocks, writes are
+    /// buffered 1 MiB at a time, and both block the thread of the task issuing them. Only on
+    /// Linux, and the file systems refusing `O_DIRECT`, such as tmpfs, keep the page cache.
+    /// Disabled by default
+    pub fn direct_io(self, direct_io: bool) -> Self {
+        DbOption { direct_io, ..self }
+    }
+
+    /// reserve the disk of the SSTs on the local file system as flushes and compactions create
+    /// them, sized by the records flushed or the tables compacted and bounded by
+    /// [`DbOption::max_sst_file_size`] for compactions, and release what is left once they are
+    /// written. Keeps large tables from being fragmented by the file system. Only on Linux,
+    /// disabled by default
+    pub fn preallocate_tables(self, preallocate_tables: bool) -> Self {
+        DbOption {
+            preallocate_tables,
+            ..self
+        }
+    }
+
+    /// reserve [`DbOption::wal_segment_size`] bytes of disk for each WAL segment on the local
+    /// file system as it is created, and release what is left once the WAL rolls to the next
+    /// one, so that appending to a segment does not allocate on every sync. The size of the
+    /// segment is kept, the recovery reads no more than its entries. Only on Linux with a
+    /// segment size, disabled by default
+    pub fn preallocate_wal(self, preallocate_wal: bool) -> Self {
+        DbOption {
+            preallocate_wal,
+            ..self
+        }
+    }
+
+    /// callbacks notified of flushes, compactions, WAL syncs, write stalls and of the progress of
+    /// the recovery
+    pub fn event_listener(self, event_listener: impl EventListener + 'static) -> Self {
+        DbOption {
+            event_listener: Some(Arc::new(event_listener)),
+            ..self
+        }
+    }
+
+    /// cache the metadata and pages of the SSTs read in `parquet_lru`, e.g. a
+    /// `parquet_lru::foyer::FoyerCache` with the `foyer` feature, which keeps the pages evicted
+    /// from memory on local disk for SSTs on object storage. The same cache may be passed to
+    /// several databases to bound their memory together, the tables are keyed by ids unique
+    /// across databases. Not cached by default
+    pub fn parquet_lru(self, parquet_lru: ParquetLru) -> Self {
+        DbOption {
+            parquet_lru: Some(parquet_lru),
+            ..self
+        }
+    }
+
+    /// check that the options can be run with, which the database does when opened instead of
+    /// misbehaving later on
+    pub fn validate(&self) -> Result<(), OptionError> {
+        let at_least = |option: &'static str, value: usize, min: usize| {
+            if value < min {
+                Err(OptionError::TooSmall { option, value, min })
+            } else {
+                Ok(())
+            }
+        };
+        at_least("immutable_chunk_num", self.immutable_chunk_num, 1)?;
+        at_least("level_sst_magnification", self.level_sst_magnification, 1)?;
+        at_least(
+            "level_bytes_magnification",
+            self.level_bytes_magnification,
+            1,
+        )?;
+        at_least(
+            "major_threshold_with_sst_size",
+            self.major_threshold_with_sst_size,
+            1,
+        )?;
+        at_least("max_subcompactions", self.max_subcompactions, 1)?;
+        at_least(
+            "max_background_compactions",
+            self.max_background_compactions,
+            1,
+        )?;
+        at_least("max_sst_file_size", self.max_sst_file_size, 1)?;
+        at_least("lock_shards", self.lock_shards, 1)?;
+        at_least("file_deletion_batch_size", self.file_deletion_batch_size, 1)?;
+        if let CompactionStyle::Tiered {
+            max_runs,
+            min_merge_width,
+            ..
+        } = self.compaction_style
+        {
+            at_least("max_runs", max_runs, 1)?;
+            at_least("min_merge_width", min_merge_width, 2)?;
+        }
+        if let Some(ratio) = self.tombstone_compaction_ratio {
+            if !(ratio > 0.0 && ratio <= 1.0) {
+                return Err(OptionError::TombstoneRatio(ratio));
+            }
+        }
+
+        let triggers = [
+            (
+                (
+                    "immutable_slowdown_writes_trigger",
+                    self.immutable_slowdown_writes_trigger,
+                ),
+                (
+                    "immutable_stop_writes_trigger",
+                    self.immutable_stop_writes_trigger,
+                ),
+            ),
+            (
+                (
+                    "level0_slowdown_writes_trigger",
+                    self.level0_slowdown_writes_trigger,
+                ),
+                (
+                    "level0_stop_writes_trigger",
+                    self.level0_stop_writes_trigger,
+                ),
+            ),
+        ];
+        for ((slowdown, slowdown_value), (stop, stop_value)) in triggers {
+            if let (Some(slowdown_value), Some(stop_value)) = (slowdown_value, stop_value) {
+                if slowdown_value > stop_value {
+                    return Err(OptionError::SlowdownPastStop {
+                        slowdown,
+                        slowdown_value,
+                        stop,
+                        stop_value,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
 }
 
 impl<R> DbOption<R>
@@ -259,8 +1346,45 @@ where
             .child(format!("{}.{}", gen, FileType::Parquet))
     }
 
+    /// the properties of the SSTs written to `level` by flushes and compactions
+    pub(crate) fn sst_properties(&self, level: usize) -> WriterProperties {
+        let options = self.level_sst_options[level];
+        let mut builder = self
+            .write_parquet_properties
+            .clone()
+            .into_builder()
+            .set_compression(options.compression.unwrap_or(self.sst_compression).into());
+        if let Some(dictionary) = options.dictionary {
+            builder = builder.set_dictionary_enabled(dictionary);
+        }
+        if let Some(size) = self.sst_max_row_group_size {
+            builder = builder.set_max_row_group_size(size);
+        }
+        if let Some(size) = self.sst_data_page_size {
+            builder = builder.set_data_page_size_limit(size);
+        }
+        if let Some(size) = self.sst_write_batch_size {
+            builder = builder.set_write_batch_size(size);
+        }
+        builder.build()
+    }
+
+    /// the cache set with [`DbOption::parquet_lru`], or none
+    pub(crate) fn lru_cache(&self) -> ParquetLru {
+        self.parquet_lru
+            .clone()
+            .unwrap_or_else(|| Arc::new(NoCache::default()))
+    }
+
     pub(crate) fn wal_dir_path(&self) -> Path {
-        self.base_path.child("wal")
+        match &self.wal_dir {
+            Some((path, _)) => path.clone(),
+            None => self.base_path.child("wal"),
+        }
+    }
+
+    pub(crate) fn quarantine_dir_path(&self) -> Path {
+        self.base_path.child("quarantine")
     }
 
     pub(crate) fn wal_path(&self, gen: FileId) -> Path {
@@ -268,6 +1392,10 @@ where
             .child(format!("{}.{}", gen, FileType::Wal))
     }
 
+    pub(crate) fn lock_path(&self) -> Path {
+        self.base_path.child("LOCK")
+    }
+
     pub(crate) fn version_log_dir_path(&self) -> Path {
         self.base_path.child("version")
     }
@@ -281,16 +1409,149 @@ where
         self.level_paths[level].as_ref().map(|(path, _)| path)
     }
 
+    pub(crate) fn is_wal_local(&self) -> bool {
+        let fs = self
+            .wal_dir
+            .as_ref()
+            .map(|(_, fs)| fs)
+            .unwrap_or(&self.base_fs);
+
+        matches!(fs, FsOptions::Local)
+    }
+
+    fn is_level_local(&self, level: usize) -> bool {
+        let fs = self.level_paths[level]
+            .as_ref()
+            .map(|(_, fs)| fs)
+            .unwrap_or(&self.base_fs);
+
+        matches!(fs, FsOptions::Local)
+    }
+
+    /// whether the SSTs of `level` are opened with [`DbOption::direct_io`], only on the local file
+    /// system
+    pub(crate) fn level_direct_io(&self, level: usize) -> bool {
+        self.direct_io && self.is_level_local(level)
+    }
+
+    /// the bytes reserved by [`DbOption::preallocate_tables`] for an SST of `level` expected to
+    /// take `bytes`, only on the local file system
+    pub(crate) fn level_preallocation(&self, level: usize, bytes: u64) -> Option<u64> {
+        (self.preallocate_tables && bytes > 0 && self.is_level_local(level)).then_some(bytes)
+    }
+
+    /// the bytes reserved by [`DbOption::preallocate_wal`] for each WAL segment, only on the
+    /// local file system
+    pub(crate) fn wal_preallocation(&self) -> Option<u64> {
+        self.wal_segment_size
+            .filter(|_| self.preallocate_wal && self.is_wal_local())
+            .map(|size| size as u64)
+    }
+
+    pub(crate) fn wal_archive_path(&self) -> Option<&Path> {
+        self.wal_archive.as_ref().map(|(path, _)| path)
+    }
+
+    /// file systems other than the base one, for the
+    /// [`StoreManager`](crate::fs::manager::StoreManager)
+    pub(crate) fn fs_paths(&self) -> Vec<Option<(Path, FsOptions)>> {
+        self.level_paths
+            .iter()
+            .cloned()
+            .chain([self.wal_dir.clone(), self.wal_archive.clone()])
+            .collect()
+    }
+
+    /// the size of `level` and its target: level 0 by its number of tables and the levels below
+    /// it by their bytes if [`DbOption::level_base_bytes`] is set
+    fn level_target(&self, version: &Version<R>, level: usize) -> (u64, u64) {
+        match self.level_base_bytes {
+            Some(base) if level > 0 => {
+                let target = (base as u64).saturating_mul(
+                    (self.level_bytes_magnification as u64).saturating_pow(level as u32 - 1),
+                );
+                (version.level_bytes(level), target)
+            }
+            _ => (
+                Version::<R>::tables_len(version, level) as u64,
+                (self.major_threshold_with_sst_size
+                    * self.level_sst_magnification.pow(level as u32)) as u64,
+            ),
+        }
+    }
+
     pub(crate) fn is_threshold_exceeded_major(&self, version: &Version<R>, level: usize) -> bool {
-        Version::<R>::tables_len(version, level)
-            >= (self.major_threshold_with_sst_size * self.level_sst_magnification.pow(level as u32))
+        let (size, target) = self.level_target(version, level);
+        size >= target
+    }
+
+    /// how writes are held back with `immutables` memtables waiting to be flushed and
+    /// `level0_tables` tables in level 0
+    pub(crate) fn write_stall(&self, immutables: usize, level0_tables: usize) -> WriteStall {
+        let exceeds = |trigger: Option<usize>, count: usize| trigger.is_some_and(|n| count >= n);
+        // the triggers of level 0 only apply when it is compacted without waiting for a flush
+        let level0_tables = if self.compaction_style == CompactionStyle::Leveled
+            && self.max_background_compactions > 1
+        {
+            level0_tables
+        } else {
+            0
+        };
+        let stop_immutables = self
+            .immutable_stop_writes_trigger
+            .map(|n| n.max(self.immutable_chunk_max_num + 1));
+        let stop_level0_tables = self
+            .level0_stop_writes_trigger
+            .map(|n| n.max(self.major_threshold_with_sst_size));
+
+        if exceeds(stop_immutables, immutables) || exceeds(stop_level0_tables, level0_tables) {
+            WriteStall::Stop
+        } else if exceeds(self.immutable_slowdown_writes_trigger, immutables)
+            || exceeds(self.level0_slowdown_writes_trigger, level0_tables)
+        {
+            WriteStall::Slowdown
+        } else {
+            WriteStall::None
+        }
+    }
+
+    /// whether tombstones make up [`DbOption::tombstone_compaction_ratio`] of the rows of a table
+    pub(crate) fn is_tombstone_heavy(&self, scope: &Scope<R::Key>) -> bool {
+        match (self.tombstone_compaction_ratio, &scope.stats) {
+            (Some(ratio), Some(stats)) if stats.rows > 0 => {
+                stats.tombstones as f64 >= ratio * stats.rows as f64
+            }
+            _ => false,
+        }
+    }
+
+    /// whether the table written at `gen` is due for a periodic compaction at `now`, in
+    /// milliseconds since the Unix epoch
+    pub(crate) fn is_periodic_due(&self, gen: FileId, now: u64) -> bool {
+        self.periodic_compaction_interval.is_some_and(|interval| {
+            u128::from(now.saturating_sub(gen.timestamp_ms())) >= interval.as_millis()
+        })
+    }
+
+    /// the size of `level` over its target, the level is compacted from 1
+    pub(crate) fn compaction_score(&self, version: &Version<R>, level: usize) -> f64 {
+        match self.level_target(version, level) {
+            (_, 0) => f64::INFINITY,
+            (size, target) => size as f64 / target as f64,
+        }
     }
 }
 
-impl<R> Debug for DbOption<R> {
+impl<R> Debug for DbOption<R>
+where
+    R: Record,
+{
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("DbOption")
             .field("clean_channel_buffer", &self.clean_channel_buffer)
+            .field("file_deletion_batch_size", &self.file_deletion_batch_size)
+            .field("max_file_deletes_per_sec", &self.max_file_deletes_per_sec)
+            .field("file_deletion_retries", &self.file_deletion_retries)
             .field("base_path", &self.base_path)
             // TODO
             // .field("level_paths", &self.level_paths)
@@ -309,6 +1570,49 @@ impl<R> Debug for DbOption<R> {
                 "major_threshold_with_sst_size",
                 &self.major_threshold_with_sst_size,
             )
+            .field("level_base_bytes", &self.level_base_bytes)
+            .field("level_bytes_magnification", &self.level_bytes_magnification)
+            .field("compaction_style", &self.compaction_style)
+            .field("max_subcompactions", &self.max_subcompactions)
+            .field(
+                "max_background_compactions",
+                &self.max_background_compactions,
+            )
+            .field(
+                "tombstone_compaction_ratio",
+                &self.tombstone_compaction_ratio,
+            )
+            .field("bytes_per_seek", &self.bytes_per_seek)
+            .field("compaction_memory_budget", &self.compaction_memory_budget)
+            .field(
+                "periodic_compaction_interval",
+                &self.periodic_compaction_interval,
+            )
+            .field(
+                "immutable_slowdown_writes_trigger",
+                &self.immutable_slowdown_writes_trigger,
+            )
+            .field(
+                "immutable_stop_writes_trigger",
+                &self.immutable_stop_writes_trigger,
+            )
+            .field(
+                "level0_slowdown_writes_trigger",
+                &self.level0_slowdown_writes_trigger,
+            )
+            .field(
+                "level0_stop_writes_trigger",
+                &self.level0_stop_writes_trigger,
+            )
+            .field("write_slowdown_delay", &self.write_slowdown_delay)
+            .field("write_buffer_manager", &self.write_buffer_manager)
+            .field("memtable_flush_interval", &self.memtable_flush_interval)
+            .field("max_total_wal_size", &self.max_total_wal_size)
+            .field("scan_prefetch_batches", &self.scan_prefetch_batches)
+            .field("scan_readahead_bytes", &self.scan_readahead_bytes)
+            .field("max_writes_per_sec", &self.max_writes_per_sec)
+            .field("max_write_bytes_per_sec", &self.max_write_bytes_per_sec)
+            .field("obsolete_file_retention", &self.obsolete_file_retention)
             .field("max_sst_file_size", &self.max_sst_file_size)
             .field(
                 "version_log_snapshot_threshold",
@@ -316,7 +1620,40 @@ impl<R> Debug for DbOption<R> {
             )
             .field("trigger_type", &self.trigger_type)
             .field("use_wal", &self.use_wal)
+            .field("wal_sync_policy", &self.wal_sync_policy)
+            .field("wal_recovery_mode", &self.wal_recovery_mode)
+            .field("version_log_recovery_mode", &self.version_log_recovery_mode)
+            .field("wal_segment_size", &self.wal_segment_size)
+            .field("wal_compression", &self.wal_compression)
+            .field("format_version", &self.format_version)
+            .field("wal_dir", &self.wal_dir.as_ref().map(|(path, _)| path))
+            .field(
+                "wal_archive_path",
+                &self.wal_archive.as_ref().map(|(path, _)| path),
+            )
+            .field("wal_archive_max_files", &self.wal_archive_max_files)
             .field("write_parquet_properties", &self.write_parquet_properties)
+            .field("sst_compression", &self.sst_compression)
+            .field("level_sst_options", &self.level_sst_options)
+            .field("sst_max_row_group_size", &self.sst_max_row_group_size)
+            .field("sst_data_page_size", &self.sst_data_page_size)
+            .field("sst_write_batch_size", &self.sst_write_batch_size)
+            .field("compaction_filter", &self.compaction_filter.is_some())
+            .field("write_validator", &self.write_validator.is_some())
+            .field("slow_operation_threshold", &self.slow_operation_threshold)
+            .field("lock_timeout", &self.lock_timeout)
+            .field("lock_shards", &self.lock_shards)
+            .field("max_transaction_size", &self.max_transaction_size)
+            .field("max_key_size", &self.max_key_size)
+            .field("max_record_size", &self.max_record_size)
+            .field("unchecked_writes", &self.unchecked_writes)
+            .field("paranoid_checks", &self.paranoid_checks)
+            .field("fast_open", &self.fast_open)
+            .field("direct_io", &self.direct_io)
+            .field("preallocate_tables", &self.preallocate_tables)
+            .field("preallocate_wal", &self.preallocate_wal)
+            .field("event_listener", &self.event_listener.is_some())
+            .field("parquet_lru", &self.parquet_lru.is_some())
             .finish()
     }
 }
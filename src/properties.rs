@@ -0,0 +1,617 @@
+use std::{
+    collections::HashSet,
+    fmt::{Debug, Display, Write},
+    sync::atomic::Ordering,
+    time::Duration,
+};
+
+use crate::{
+    compaction,
+    fs::{self, FileId, FileType},
+    record::Record,
+    timestamp::Timestamp,
+    version::MAX_LEVEL,
+    DbError, WriteStall, DB,
+};
+
+/// A report of the internal state of a [`DB`](crate::DB), returned by
+/// [`DB::properties`](crate::DB::properties).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DbProperties {
+    /// the SSTs of every level, starting from level 0
+    pub levels: Vec<LevelProperties>,
+    /// records and tombstones in the mutable memtable
+    pub memtable_entries: usize,
+    /// encoded size of the keys and records in the mutable memtable
+    pub memtable_bytes: usize,
+    /// memtables frozen and waiting to be flushed
+    pub immutables: usize,
+    /// memory held by the arrow arrays of the immutables
+    pub immutable_bytes: usize,
+    pub wal_files: usize,
+    /// size of the WALs on the file system, the buffered tail is not counted
+    pub wal_bytes: u64,
+    /// timestamp read by the oldest live snapshot or transaction
+    pub oldest_snapshot_ts: Option<Timestamp>,
+    /// encoded size of the records written since the database was opened, against which the
+    /// bytes written by flushes and compactions give the write amplification
+    pub user_bytes_written: u64,
+}
+
+/// The SSTs of a level, and the bytes moved through it since the database was opened.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LevelProperties {
+    pub files: usize,
+    pub bytes: u64,
+    /// size of the SSTs of the level merged into the next one by major compactions
+    pub compaction_bytes_read: u64,
+    /// size of the SSTs written to the level, by flushes for level 0 and by major compactions
+    /// for the others
+    pub compaction_bytes_written: u64,
+    /// bytes fetched from the SSTs of the level to serve gets, including the ones served by the
+    /// parquet LRU cache
+    pub get_bytes_read: u64,
+}
+
+/// How pressing a major compaction of a level is, returned by
+/// [`DB::compaction_scores`](crate::DB::compaction_scores) in the order the background
+/// compactions pick the levels: level 0 first once it is over its threshold, then the levels the
+/// most over their target, then the ones holding the oldest table.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CompactionScore {
+    pub level: usize,
+    /// the tables of level 0, or the tables or bytes of a level below it, over their target. A
+    /// level is compacted from 1
+    pub score: f64,
+    /// the oldest table of the level, whose id orders the tables by the time they were written
+    pub oldest: Option<FileId>,
+}
+
+/// A major compaction in flight, returned by
+/// [`DB::compaction_jobs`](crate::DB::compaction_jobs).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompactionJob {
+    /// the id to cancel the job by, see [`DB::cancel_compaction`](crate::DB::cancel_compaction)
+    pub id: u64,
+    /// the level and id of the SSTs merged
+    pub inputs: Vec<(usize, FileId)>,
+    /// size of the SSTs merged, as recorded in their statistics
+    pub input_bytes: u64,
+    /// bytes fetched from the SSTs merged so far, including the ones served by the parquet LRU
+    /// cache
+    pub bytes_read: u64,
+    pub elapsed: Duration,
+    /// the time left at the pace of the bytes read so far, once any is read
+    pub eta: Option<Duration>,
+}
+
+/// How far the flushes and compactions lag behind the writes, returned by
+/// [`DB::pressure`](crate::DB::pressure) for applications to shed or defer load before the writes
+/// are held back.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WritePressure {
+    /// share of the size, or number of writes, at which the mutable memtable is frozen that it
+    /// holds, past 1 while its freeze waits
+    pub memtable_fill: f64,
+    /// memtables frozen and waiting to be flushed
+    pub immutables: usize,
+    pub level0_tables: usize,
+    /// how writes are held back at this pressure
+    pub stall: WriteStall,
+}
+
+/// The memory held by a [`DB`](crate::DB), returned by
+/// [`DB::memory_usage`](crate::DB::memory_usage).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MemoryUsage {
+    /// encoded size of the keys and records in the mutable memtable
+    pub mutable: usize,
+    /// arrow arrays of the memtables frozen and waiting to be flushed
+    pub immutables: usize,
+    /// the parquet LRU cache, as weighed by it, with the tables of every database sharing it
+    pub cache: usize,
+    /// records buffered by an in-flight major compaction before being written
+    pub compaction: usize,
+}
+
+impl MemoryUsage {
+    pub fn total(&self) -> usize {
+        self.mutable + self.immutables + self.cache + self.compaction
+    }
+}
+
+/// The SSTs of every level with their key ranges and sizes, along with the compactions pending
+/// and running, returned by [`DB::dump_tree`](crate::DB::dump_tree) to visualize and debug how
+/// the tree is compacted, as JSON by [`TreeDump::to_json`] or as a Graphviz graph by
+/// [`TreeDump::to_dot`]. The keys are written in their `Debug` format.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TreeDump<K> {
+    /// the SSTs of every level, starting from level 0
+    pub levels: Vec<Vec<TableDump<K>>>,
+    pub compaction_scores: Vec<CompactionScore>,
+    pub compaction_jobs: Vec<CompactionJob>,
+}
+
+/// An SST of a [`TreeDump`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableDump<K> {
+    pub id: FileId,
+    pub min: K,
+    pub max: K,
+    /// size of the file
+    pub bytes: u64,
+    /// recorded for the tables written since the statistics are kept
+    pub rows: Option<u64>,
+    pub tombstones: Option<u64>,
+    /// merged by one of the compaction jobs
+    pub compacting: bool,
+}
+
+impl<K> TreeDump<K>
+where
+    K: Debug,
+{
+    pub fn to_json(&self) -> String {
+        let mut json = String::from("{\"levels\":[");
+        for (level, tables) in self.levels.iter().enumerate() {
+            if level > 0 {
+                json.push(',');
+            }
+            let _ = write!(json, "{{\"level\":{},\"tables\":[", level);
+            for (i, table) in tables.iter().enumerate() {
+                if i > 0 {
+                    json.push(',');
+                }
+                let _ = write!(
+                    json,
+                    "{{\"id\":\"{}\",\"min\":{},\"max\":{},\"bytes\":{},\"rows\":{},\"tombstones\"\
+                     :{},\"compacting\":{}}}",
+                    table.id,
+                    json_string(&format!("{:?}", table.min)),
+                    json_string(&format!("{:?}", table.max)),
+                    table.bytes,
+                    json_option(table.rows),
+                    json_option(table.tombstones),
+                    table.compacting,
+                );
+            }
+            json.push_str("]}");
+        }
+        json.push_str("],\"compaction_scores\":[");
+        for (i, score) in self.compaction_scores.iter().enumerate() {
+            if i > 0 {
+                json.push(',');
+            }
+            let _ = write!(
+                json,
+                "{{\"level\":{},\"score\":{},\"oldest\":{}}}",
+                score.level,
+                // JSON has no infinity
+                json_option(Some(score.score).filter(|score| score.is_finite())),
+                json_option(score.oldest.map(|oldest| json_string(&oldest.to_string()))),
+            );
+        }
+        json.push_str("],\"compaction_jobs\":[");
+        for (i, job) in self.compaction_jobs.iter().enumerate() {
+            if i > 0 {
+                json.push(',');
+            }
+            let _ = write!(json, "{{\"id\":{},\"inputs\":[", job.id);
+            for (j, (level, id)) in job.inputs.iter().enumerate() {
+                if j > 0 {
+                    json.push(',');
+                }
+                let _ = write!(json, "{{\"level\":{},\"id\":\"{}\"}}", level, id);
+            }
+            let _ = write!(
+                json,
+                "],\"input_bytes\":{},\"bytes_read\":{},\"elapsed_ms\":{},\"eta_ms\":{}}}",
+                job.input_bytes,
+                job.bytes_read,
+                job.elapsed.as_millis(),
+                json_option(job.eta.map(|eta| eta.as_millis())),
+            );
+        }
+        json.push_str("]}");
+        json
+    }
+
+    /// a Graphviz graph of a cluster per level, with the SSTs merged by each compaction job
+    /// pointing to it
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph tonbo {\n    rankdir=TB;\n    node [shape=box];\n");
+        for (level, tables) in self.levels.iter().enumerate() {
+            let bytes = tables.iter().map(|table| table.bytes).sum::<u64>();
+            let score = self
+                .compaction_scores
+                .iter()
+                .find(|score| score.level == level)
+                .map_or(String::new(), |score| format!(", score {:.2}", score.score));
+            let _ = writeln!(
+                dot,
+                "    subgraph cluster_level_{} {{\n        label=\"level {}: {} tables, {} \
+                 bytes{}\";",
+                level,
+                level,
+                tables.len(),
+                bytes,
+                score,
+            );
+            for table in tables {
+                let label = format!(
+                    "{}\nmin: {:?}\nmax: {:?}\n{} bytes",
+                    table.id, table.min, table.max, table.bytes
+                );
+                let _ = writeln!(
+                    dot,
+                    "        \"{}\" [label=\"{}\"{}];",
+                    table.id,
+                    dot_escape(&label),
+                    if table.compacting {
+                        ", style=filled, fillcolor=orange"
+                    } else {
+                        ""
+                    },
+                );
+            }
+            dot.push_str("    }\n");
+        }
+        for job in &self.compaction_jobs {
+            let _ = writeln!(
+                dot,
+                "    \"job_{}\" [shape=ellipse, label=\"compaction {}\\n{} / {} bytes\"];",
+                job.id, job.id, job.bytes_read, job.input_bytes,
+            );
+            for (_, id) in &job.inputs {
+                let _ = writeln!(dot, "    \"{}\" -> \"job_{}\";", id, job.id);
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+fn json_string(value: &str) -> String {
+    let mut json = String::with_capacity(value.len() + 2);
+    json.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(json, "\\u{:04x}", c as u32);
+            }
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+fn json_option(value: Option<impl Display>) -> String {
+    value.map_or_else(|| "null".to_string(), |value| value.to_string())
+}
+
+/// escape `label` in a quoted DOT string, keeping its line breaks
+fn dot_escape(label: &str) -> String {
+    let mut escaped = String::with_capacity(label.len());
+    for c in label.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// A checksum of the records of a range, returned by
+/// [`DB::checksum_range`](crate::DB::checksum_range). Two copies of a database hold the same
+/// records in the range when their checksums are equal, up to a crc32 collision.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RangeChecksum {
+    /// the live records of the range
+    pub records: u64,
+    /// crc32 of the keys and values of the records in the order of their keys
+    pub checksum: u32,
+}
+
+impl<R> DB<R>
+where
+    R: Record + Send + Sync,
+    R::Columns: Send + Sync,
+{
+    /// report the SSTs of every level, the memtables, the WALs, the oldest live snapshot and the
+    /// bytes moved through each level to quantify the read and write amplification
+    pub async fn properties(&self) -> Result<DbProperties, DbError<R>> {
+        let version = self.version_set.current().await;
+        let option = version.option();
+        let statistics = self.version_set.statistics();
+
+        let mut levels = Vec::with_capacity(MAX_LEVEL);
+        for (level, scopes) in version.level_slice.iter().enumerate() {
+            let level_statistics = &statistics.levels[level];
+            let level_path = option.level_fs_path(level).unwrap_or(&option.base_path);
+            let level_fs = self.manager.get_fs(level_path);
+            let mut bytes = 0;
+            for scope in scopes {
+                bytes += fs::file_size(level_fs, &option.table_path(scope.gen, level)).await?;
+            }
+            levels.push(LevelProperties {
+                files: scopes.len(),
+                bytes,
+                compaction_bytes_read: level_statistics
+                    .compaction_bytes_read
+                    .load(Ordering::Relaxed),
+                compaction_bytes_written: level_statistics
+                    .compaction_bytes_written
+                    .load(Ordering::Relaxed),
+                get_bytes_read: level_statistics.get_bytes_read.load(Ordering::Relaxed),
+            });
+        }
+        let (mut wal_files, mut wal_bytes) = (0, 0);
+        let wal_fs = self.manager.get_fs(&option.wal_dir_path());
+        for path in fs::list_files(wal_fs, &option.wal_dir_path(), FileType::Wal).await? {
+            // removed by a flush since listed
+            let Ok(bytes) = fs::file_size(wal_fs, &path).await else {
+                continue;
+            };
+            wal_files += 1;
+            wal_bytes += bytes;
+        }
+
+        let schema = self.schema.read().await;
+        Ok(DbProperties {
+            levels,
+            memtable_entries: schema.mutable.data.len(),
+            memtable_bytes: schema.mutable.size(),
+            immutables: schema.immutables.len(),
+            immutable_bytes: schema.immutables_size(),
+            wal_files,
+            wal_bytes,
+            oldest_snapshot_ts: schema.snapshots.oldest(),
+            user_bytes_written: statistics.user_bytes_written.load(Ordering::Relaxed),
+        })
+    }
+
+    /// how far the flushes and compactions lag behind the writes, cheap enough to be checked
+    /// before each batch of writes by an application backing off ahead of the write stall
+    /// triggers
+    pub async fn pressure(&self) -> WritePressure {
+        let level0_tables = self.version_set.current().await.level_slice[0].len();
+        let schema = self.schema.read().await;
+        let immutables = schema.immutables.len();
+
+        WritePressure {
+            memtable_fill: schema.trigger.fill(),
+            immutables,
+            level0_tables,
+            stall: self
+                .version_set
+                .option()
+                .write_stall(immutables, level0_tables),
+        }
+    }
+
+    /// report the memory held by the memtables, the parquet LRU cache and the compaction in
+    /// flight
+    pub async fn memory_usage(&self) -> MemoryUsage {
+        let schema = self.schema.read().await;
+        MemoryUsage {
+            mutable: schema.mutable.size(),
+            immutables: schema.immutables_size(),
+            cache: self.parquet_lru.memory_usage(),
+            compaction: self
+                .version_set
+                .statistics()
+                .compaction_memory
+                .load(Ordering::Relaxed),
+        }
+    }
+
+    /// describe the SSTs of every level with their key ranges and sizes, along with the
+    /// compactions pending and running, to be written as JSON or as a Graphviz graph
+    pub async fn dump_tree(&self) -> Result<TreeDump<R::Key>, DbError<R>> {
+        let version = self.version_set.current().await;
+        let option = version.option();
+        let compaction_jobs = self.compaction_jobs();
+        let compacting = compaction_jobs
+            .iter()
+            .flat_map(|job| job.inputs.iter().map(|(_, gen)| *gen))
+            .collect::<HashSet<_>>();
+
+        let mut levels = Vec::with_capacity(MAX_LEVEL);
+        for (level, scopes) in version.level_slice.iter().enumerate() {
+            let level_fs = self
+                .manager
+                .get_fs(option.level_fs_path(level).unwrap_or(&option.base_path));
+            let mut tables = Vec::with_capacity(scopes.len());
+            for scope in scopes {
+                tables.push(TableDump {
+                    id: scope.gen,
+                    min: scope.min.clone(),
+                    max: scope.max.clone(),
+                    bytes: fs::file_size(level_fs, &option.table_path(scope.gen, level)).await?,
+                    rows: scope.stats.as_ref().map(|stats| stats.rows),
+                    tombstones: scope.stats.as_ref().map(|stats| stats.tombstones),
+                    compacting: compacting.contains(&scope.gen),
+                });
+            }
+            levels.push(tables);
+        }
+
+        Ok(TreeDump {
+            levels,
+            compaction_scores: compaction::compaction_scores(option, &version),
+            compaction_jobs,
+        })
+    }
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod tests {
+    use fusio::path::Path;
+    use tempfile::TempDir;
+
+    use super::WritePressure;
+    use crate::{
+        executor::tokio::TokioExecutor,
+        tests::{test_items, Test},
+        trigger::TriggerType,
+        version::MAX_LEVEL,
+        DbOption, WriteStall, DB,
+    };
+
+    #[tokio::test]
+    async fn properties() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap());
+        option.immutable_chunk_num = 1;
+        option.immutable_chunk_max_num = 0;
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+
+        let properties = db.properties().await.unwrap();
+        assert_eq!(properties.levels.len(), MAX_LEVEL);
+        assert!(properties.levels.iter().all(|level| level.files == 0));
+        assert_eq!(properties.memtable_entries, 0);
+        assert_eq!(properties.oldest_snapshot_ts, None);
+
+        for item in test_items() {
+            db.insert(item).await.unwrap();
+        }
+        db.flush_wal().await.unwrap();
+        let properties = db.properties().await.unwrap();
+        assert_eq!(properties.memtable_entries, 40);
+        assert!(properties.memtable_bytes > 0);
+        assert_eq!(properties.wal_files, 1);
+        assert!(properties.wal_bytes > 0);
+
+        db.flush().await.unwrap();
+        db.insert(Test {
+            vstring: "a".to_string(),
+            vu32: 0,
+            vbool: None,
+        })
+        .await
+        .unwrap();
+        let snapshot = db.snapshot().await;
+        let ts = snapshot.ts();
+        let properties = db.properties().await.unwrap();
+        assert_eq!(properties.levels[0].files, 1);
+        assert!(properties.levels[0].bytes > 0);
+        assert_eq!(properties.memtable_entries, 1);
+        assert_eq!(properties.immutables, 0);
+        assert_eq!(properties.oldest_snapshot_ts, Some(ts));
+
+        drop(snapshot);
+        assert_eq!(db.properties().await.unwrap().oldest_snapshot_ts, None);
+    }
+
+    #[tokio::test]
+    async fn pressure() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap())
+            .max_background_compactions(2)
+            .level0_slowdown_writes_trigger(1);
+        option.immutable_chunk_num = 1;
+        option.immutable_chunk_max_num = 0;
+        option.major_threshold_with_sst_size = 10;
+        option.trigger_type = TriggerType::Length(40);
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+
+        for item in test_items().into_iter().take(10) {
+            db.insert(item).await.unwrap();
+        }
+        assert_eq!(
+            db.pressure().await,
+            WritePressure {
+                memtable_fill: 0.25,
+                immutables: 0,
+                level0_tables: 0,
+                stall: WriteStall::None,
+            }
+        );
+
+        db.flush().await.unwrap();
+        assert_eq!(
+            db.pressure().await,
+            WritePressure {
+                memtable_fill: 0.0,
+                immutables: 0,
+                level0_tables: 1,
+                stall: WriteStall::Slowdown,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn memory_usage() {
+        let temp_dir = TempDir::new().unwrap();
+        let option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap());
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+        assert_eq!(db.memory_usage().await.total(), 0);
+
+        for item in test_items() {
+            db.insert(item).await.unwrap();
+        }
+        let usage = db.memory_usage().await;
+        assert!(usage.mutable > 0);
+        assert_eq!(usage.immutables, 0);
+        assert_eq!(usage.compaction, 0);
+
+        let option = db.version_set.option();
+        db.schema
+            .write()
+            .await
+            .freeze(&option, db.manager.base_fs())
+            .await
+            .unwrap();
+        let usage = db.memory_usage().await;
+        assert_eq!(usage.mutable, 0);
+        assert!(usage.immutables > 0);
+        assert_eq!(usage.total(), usage.immutables);
+    }
+
+    #[tokio::test]
+    async fn dump_tree() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap());
+        option.immutable_chunk_num = 1;
+        option.immutable_chunk_max_num = 0;
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+
+        for item in test_items() {
+            db.insert(item).await.unwrap();
+        }
+        db.flush().await.unwrap();
+
+        let dump = db.dump_tree().await.unwrap();
+        assert_eq!(dump.levels.len(), MAX_LEVEL);
+        assert_eq!(dump.levels[0].len(), 1);
+        let table = &dump.levels[0][0];
+        assert_eq!((table.min.as_str(), table.max.as_str()), ("0", "9"));
+        assert!(table.bytes > 0);
+        assert_eq!(table.rows, Some(40));
+        assert!(!table.compacting);
+        assert!(dump.compaction_jobs.is_empty());
+
+        let json = dump.to_json();
+        assert!(json.starts_with("{\"levels\":[{\"level\":0,\"tables\":[{\"id\":"));
+        assert!(json.contains(&format!(
+            "\"id\":\"{}\",\"min\":\"\\\"0\\\"\",\"max\":\"\\\"9\\\"\"",
+            table.id
+        )));
+        assert!(json.ends_with("\"compaction_jobs\":[]}"));
+
+        let dot = dump.to_dot();
+        assert!(dot.starts_with("digraph tonbo {"));
+        assert!(dot.contains(&format!(
+            "\"{}\" [label=\"{}\\nmin: \\\"0\\\"",
+            table.id, table.id
+        )));
+    }
+}
@@ -0,0 +1,408 @@
+use std::{
+    cmp::Ordering,
+    mem::{size_of, transmute},
+    ops::Bound,
+};
+
+use fusio::{SeqRead, Write};
+
+use crate::{
+    record::Key,
+    serdes::{decode_with, encode_with, BytesReader, Decode, Encode},
+    timestamp::{Timestamp, Timestamped},
+};
+
+/// deletion of every key in `[lower, upper]` whose version is older than `ts`
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub(crate) struct RangeTombstone<K> {
+    pub(crate) lower: Bound<K>,
+    pub(crate) upper: Bound<K>,
+    pub(crate) ts: Timestamp,
+}
+
+impl<K> RangeTombstone<K>
+where
+    K: Key,
+{
+    pub(crate) fn new(lower: Bound<K>, upper: Bound<K>, ts: Timestamp) -> Self {
+        RangeTombstone { lower, upper, ts }
+    }
+
+    pub(crate) fn contains(&self, key: &K::Ref<'_>) -> bool {
+        self.is_after_lower(key) && self.is_before_upper(key)
+    }
+
+    fn is_after_lower(&self, key: &K::Ref<'_>) -> bool {
+        match &self.lower {
+            Bound::Included(lower) => Self::compare(lower, key) != Ordering::Greater,
+            Bound::Excluded(lower) => Self::compare(lower, key) == Ordering::Less,
+            Bound::Unbounded => true,
+        }
+    }
+
+    fn is_before_upper(&self, key: &K::Ref<'_>) -> bool {
+        match &self.upper {
+            Bound::Included(upper) => Self::compare(upper, key) != Ordering::Less,
+            Bound::Excluded(upper) => Self::compare(upper, key) == Ordering::Greater,
+            Bound::Unbounded => true,
+        }
+    }
+
+    /// whether the version `key` is hidden by this tombstone
+    pub(crate) fn covers(&self, key: &Timestamped<K::Ref<'_>>) -> bool {
+        key.ts < self.ts && self.contains(&key.value)
+    }
+
+    /// whether the tombstone deletes any key in `range`
+    pub(crate) fn meets_range(&self, range: (Bound<&K>, Bound<&K>)) -> bool {
+        !Self::is_before(self.upper.as_ref(), range.0)
+            && !Self::is_before(range.1, self.lower.as_ref())
+    }
+
+    /// whether no key is both within `upper` and after `lower`
+    fn is_before(upper: Bound<&K>, lower: Bound<&K>) -> bool {
+        match (upper, lower) {
+            (Bound::Unbounded, _) | (_, Bound::Unbounded) => false,
+            (Bound::Included(upper), Bound::Included(lower)) => upper < lower,
+            (
+                Bound::Included(upper) | Bound::Excluded(upper),
+                Bound::Included(lower) | Bound::Excluded(lower),
+            ) => upper <= lower,
+        }
+    }
+
+    fn compare<'k>(bound: &'k K, key: &K::Ref<'_>) -> Ordering {
+        // Safety: shorter lifetime of the key must be safe
+        let key = unsafe { transmute::<&K::Ref<'_>, &K::Ref<'k>>(key) };
+        bound.as_key_ref().cmp(key)
+    }
+}
+
+/// the order of the lower bounds, from the one admitting the smallest keys
+fn cmp_lower<K: Ord>(a: &Bound<K>, b: &Bound<K>) -> Ordering {
+    match (a, b) {
+        (Bound::Unbounded, Bound::Unbounded) => Ordering::Equal,
+        (Bound::Unbounded, _) => Ordering::Less,
+        (_, Bound::Unbounded) => Ordering::Greater,
+        (Bound::Included(a), Bound::Excluded(b)) => a.cmp(b).then(Ordering::Less),
+        (Bound::Excluded(a), Bound::Included(b)) => a.cmp(b).then(Ordering::Greater),
+        (Bound::Included(a), Bound::Included(b)) | (Bound::Excluded(a), Bound::Excluded(b)) => {
+            a.cmp(b)
+        }
+    }
+}
+
+/// the order of the upper bounds, up to the one admitting the greatest keys
+fn cmp_upper<K: Ord>(a: &Bound<K>, b: &Bound<K>) -> Ordering {
+    match (a, b) {
+        (Bound::Unbounded, Bound::Unbounded) => Ordering::Equal,
+        (Bound::Unbounded, _) => Ordering::Greater,
+        (_, Bound::Unbounded) => Ordering::Less,
+        (Bound::Included(a), Bound::Excluded(b)) => a.cmp(b).then(Ordering::Greater),
+        (Bound::Excluded(a), Bound::Included(b)) => a.cmp(b).then(Ordering::Less),
+        (Bound::Included(a), Bound::Included(b)) | (Bound::Excluded(a), Bound::Excluded(b)) => {
+            a.cmp(b)
+        }
+    }
+}
+
+/// The range tombstones of a [`Version`](crate::version::Version) sorted by their lower bound,
+/// along with the one reaching the furthest among each tombstone and the ones before it, so the
+/// tombstones covering a key are found without going through all of them.
+#[derive(Debug, Clone)]
+pub(crate) struct RangeTombstoneSet<K> {
+    tombstones: Vec<RangeTombstone<K>>,
+    /// the index of the greatest upper bound among the tombstones up to each one
+    reach: Vec<usize>,
+}
+
+impl<K> Default for RangeTombstoneSet<K> {
+    fn default() -> Self {
+        RangeTombstoneSet {
+            tombstones: Vec::new(),
+            reach: Vec::new(),
+        }
+    }
+}
+
+impl<K> RangeTombstoneSet<K>
+where
+    K: Key,
+{
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &RangeTombstone<K>> {
+        self.tombstones.iter()
+    }
+
+    pub(crate) fn to_vec(&self) -> Vec<RangeTombstone<K>> {
+        self.tombstones.clone()
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.tombstones.len()
+    }
+
+    pub(crate) fn insert(&mut self, range_tombstone: RangeTombstone<K>) {
+        let pos = self.tombstones.partition_point(|other| {
+            cmp_lower(&other.lower, &range_tombstone.lower) != Ordering::Greater
+        });
+        self.tombstones.insert(pos, range_tombstone);
+        self.index();
+    }
+
+    /// remove the tombstone written at `ts`
+    pub(crate) fn remove(&mut self, ts: Timestamp) {
+        self.tombstones
+            .retain(|range_tombstone| range_tombstone.ts != ts);
+        self.index();
+    }
+
+    fn index(&mut self) {
+        self.reach.clear();
+        for (i, range_tombstone) in self.tombstones.iter().enumerate() {
+            let furthest = match self.reach.last() {
+                Some(&furthest)
+                    if cmp_upper(&self.tombstones[furthest].upper, &range_tombstone.upper)
+                        == Ordering::Greater =>
+                {
+                    furthest
+                }
+                _ => i,
+            };
+            self.reach.push(furthest);
+        }
+    }
+
+    /// whether a tombstone written at or before `ts` hides the version `key`
+    pub(crate) fn covers(&self, key: &Timestamped<K::Ref<'_>>, ts: Timestamp) -> bool {
+        // the tombstones from `end` start after the key
+        let end = self
+            .tombstones
+            .partition_point(|range_tombstone| range_tombstone.is_after_lower(&key.value));
+
+        self.tombstones[..end]
+            .iter()
+            .zip(&self.reach[..end])
+            .rev()
+            .take_while(|(_, furthest)| self.tombstones[**furthest].is_before_upper(&key.value))
+            .any(|(range_tombstone, _)| range_tombstone.ts <= ts && range_tombstone.covers(key))
+    }
+}
+
+impl<K> Encode for RangeTombstone<K>
+where
+    K: Encode + Sync,
+{
+    type Error = <K as Encode>::Error;
+
+    async fn encode<W>(&self, writer: &mut W) -> Result<(), Self::Error>
+    where
+        W: Write,
+    {
+        self.encode_as(writer, false).await
+    }
+
+    async fn encode_compact<W>(&self, writer: &mut W) -> Result<(), Self::Error>
+    where
+        W: Write,
+    {
+        self.encode_as(writer, true).await
+    }
+
+    fn size(&self) -> usize {
+        let bound_size = |bound: &Bound<K>| match bound {
+            Bound::Included(key) | Bound::Excluded(key) => size_of::<u8>() + key.size(),
+            Bound::Unbounded => size_of::<u8>(),
+        };
+        bound_size(&self.lower) + bound_size(&self.upper) + self.ts.size()
+    }
+}
+
+impl<K> RangeTombstone<K>
+where
+    K: Encode + Sync,
+{
+    async fn encode_as<W>(&self, writer: &mut W, compact: bool) -> Result<(), K::Error>
+    where
+        W: Write,
+    {
+        for bound in [&self.lower, &self.upper] {
+            match bound {
+                Bound::Included(key) => {
+                    0u8.encode(writer).await?;
+                    encode_with(key, writer, compact).await?;
+                }
+                Bound::Excluded(key) => {
+                    1u8.encode(writer).await?;
+                    encode_with(key, writer, compact).await?;
+                }
+                Bound::Unbounded => 2u8.encode(writer).await?,
+            }
+        }
+        encode_with(&self.ts, writer, compact).await?;
+
+        Ok(())
+    }
+}
+
+impl<K> Decode for RangeTombstone<K>
+where
+    K: Decode,
+{
+    type Error = <K as Decode>::Error;
+
+    async fn decode<R: SeqRead>(reader: &mut R) -> Result<Self, Self::Error> {
+        Self::decode_as(reader, false).await
+    }
+
+    async fn decode_compact<R: SeqRead>(reader: &mut R) -> Result<Self, Self::Error> {
+        Self::decode_as(reader, true).await
+    }
+
+    async fn decode_buffered(reader: &mut BytesReader, compact: bool) -> Result<Self, Self::Error> {
+        let mut bounds = Vec::with_capacity(2);
+
+        for _ in 0..2 {
+            bounds.push(match u8::decode(reader).await? {
+                0 => Bound::Included(K::decode_buffered(reader, compact).await?),
+                1 => Bound::Excluded(K::decode_buffered(reader, compact).await?),
+                2 => Bound::Unbounded,
+                _ => unreachable!(),
+            });
+        }
+        let ts: Timestamp = decode_with(reader, compact).await?;
+        let upper = bounds.pop().unwrap();
+        let lower = bounds.pop().unwrap();
+
+        Ok(RangeTombstone { lower, upper, ts })
+    }
+}
+
+impl<K> RangeTombstone<K>
+where
+    K: Decode,
+{
+    async fn decode_as<R: SeqRead>(reader: &mut R, compact: bool) -> Result<Self, K::Error> {
+        let mut bounds = Vec::with_capacity(2);
+
+        for _ in 0..2 {
+            bounds.push(match u8::decode(reader).await? {
+                0 => Bound::Included(decode_with(reader, compact).await?),
+                1 => Bound::Excluded(decode_with(reader, compact).await?),
+                2 => Bound::Unbounded,
+                _ => unreachable!(),
+            });
+        }
+        let ts: Timestamp = decode_with(reader, compact).await?;
+        let upper = bounds.pop().unwrap();
+        let lower = bounds.pop().unwrap();
+
+        Ok(RangeTombstone { lower, upper, ts })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Cursor, ops::Bound};
+
+    use tokio::io::AsyncSeekExt;
+
+    use super::{RangeTombstone, RangeTombstoneSet};
+    use crate::{
+        serdes::{Decode, Encode},
+        timestamp::Timestamped,
+    };
+
+    #[tokio::test]
+    async fn encode_and_decode() {
+        let tombstones = vec![
+            RangeTombstone::new(
+                Bound::Included("a".to_string()),
+                Bound::Excluded("c".to_string()),
+                1.into(),
+            ),
+            RangeTombstone::new(Bound::Unbounded, Bound::Included("z".to_string()), 2.into()),
+        ];
+
+        let mut bytes = Vec::new();
+        let mut cursor = Cursor::new(&mut bytes);
+        for tombstone in tombstones.iter() {
+            tombstone.encode(&mut cursor).await.unwrap();
+        }
+
+        cursor.seek(std::io::SeekFrom::Start(0)).await.unwrap();
+        for tombstone in tombstones {
+            let decoded = RangeTombstone::<String>::decode(&mut cursor).await.unwrap();
+            assert_eq!(tombstone, decoded);
+        }
+    }
+
+    #[test]
+    fn covers() {
+        let tombstone = RangeTombstone::new(
+            Bound::Included("b".to_string()),
+            Bound::Excluded("d".to_string()),
+            5.into(),
+        );
+
+        assert!(!tombstone.covers(&Timestamped::new("a", 1.into())));
+        assert!(tombstone.covers(&Timestamped::new("b", 1.into())));
+        assert!(tombstone.covers(&Timestamped::new("c", 4.into())));
+        assert!(!tombstone.covers(&Timestamped::new("c", 5.into())));
+        assert!(!tombstone.covers(&Timestamped::new("d", 1.into())));
+    }
+
+    #[test]
+    fn set_covers() {
+        let mut set = RangeTombstoneSet::default();
+        for (lower, upper, ts) in [
+            (Bound::Included("m"), Bound::Excluded("p"), 3),
+            (Bound::Excluded("a"), Bound::Included("z"), 1),
+            (Bound::Included("c"), Bound::Excluded("e"), 5),
+            (Bound::Unbounded, Bound::Excluded("b"), 7),
+        ] {
+            set.insert(RangeTombstone::new(
+                lower.map(str::to_string),
+                upper.map(str::to_string),
+                ts.into(),
+            ));
+        }
+        assert_eq!(set.len(), 4);
+
+        // the tombstone of "a" to "z" reaches past the ones starting after it
+        assert!(set.covers(&Timestamped::new("x", 0.into()), 1.into()));
+        assert!(!set.covers(&Timestamped::new("x", 1.into()), 7.into()));
+        assert!(!set.covers(&Timestamped::new("a", 0.into()), 1.into()));
+        assert!(set.covers(&Timestamped::new("a", 0.into()), 7.into()));
+        assert!(set.covers(&Timestamped::new("d", 4.into()), 5.into()));
+        assert!(!set.covers(&Timestamped::new("d", 4.into()), 4.into()));
+        assert!(set.covers(&Timestamped::new("n", 2.into()), 3.into()));
+
+        set.remove(1.into());
+        assert_eq!(set.len(), 3);
+        assert!(!set.covers(&Timestamped::new("x", 0.into()), 7.into()));
+        assert!(set.covers(&Timestamped::new("n", 2.into()), 3.into()));
+        assert!(!set.covers(&Timestamped::new("f", 0.into()), 7.into()));
+    }
+
+    #[test]
+    fn meets_range() {
+        let tombstone = RangeTombstone::new(
+            Bound::Included("b".to_string()),
+            Bound::Excluded("d".to_string()),
+            5.into(),
+        );
+        let (a, b, c, d) = (
+            "a".to_string(),
+            "b".to_string(),
+            "c".to_string(),
+            "d".to_string(),
+        );
+
+        assert!(tombstone.meets_range((Bound::Included(&a), Bound::Included(&b))));
+        assert!(!tombstone.meets_range((Bound::Included(&a), Bound::Excluded(&b))));
+        assert!(tombstone.meets_range((Bound::Included(&c), Bound::Included(&c))));
+        assert!(!tombstone.meets_range((Bound::Included(&d), Bound::Unbounded)));
+        assert!(tombstone.meets_range((Bound::Excluded(&a), Bound::Unbounded)));
+        assert!(tombstone.meets_range((Bound::Unbounded, Bound::Unbounded)));
+    }
+}
@@ -0,0 +1,210 @@
+use std::{sync::Mutex, time::Duration};
+
+use web_time::Instant;
+
+use crate::{executor::Clock, metrics, DbOption};
+
+/// Token buckets holding the writes of a [`DB`](crate::DB) back to the rates of
+/// [`DbOption::max_writes_per_sec`] and [`DbOption::max_write_bytes_per_sec`]. A write larger
+/// than a second of the rate still goes through, the ones after it wait for the debt to be paid
+/// back.
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+    writes: Mutex<Option<Bucket>>,
+    bytes: Mutex<Option<Bucket>>,
+    clock: Clock,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    /// tokens per second, and the most the bucket holds
+    rate: f64,
+    /// the tokens available, negative while in debt
+    available: f64,
+    last: Instant,
+}
+
+impl Bucket {
+    fn new(rate: u64, now: Instant) -> Self {
+        let rate = rate.max(1) as f64;
+        Bucket {
+            rate,
+            available: rate,
+            last: now,
+        }
+    }
+
+    /// take `amount` tokens, returning how long until the bucket is out of debt
+    fn take(&mut self, amount: f64, now: Instant) -> Duration {
+        let refilled = now.saturating_duration_since(self.last).as_secs_f64() * self.rate;
+        self.available = (self.available + refilled).min(self.rate) - amount;
+        self.last = now.max(self.last);
+
+        if self.available >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.available / self.rate)
+        }
+    }
+
+    /// change the rate of `bucket`, keeping its debt
+    fn set(bucket: &Mutex<Option<Bucket>>, rate: Option<u64>, now: Instant) {
+        let mut bucket = bucket.lock().unwrap();
+        match (bucket.as_mut(), rate) {
+            (Some(bucket), Some(rate)) => {
+                bucket.rate = rate.max(1) as f64;
+                bucket.available = bucket.available.min(bucket.rate);
+            }
+            (_, rate) => *bucket = rate.map(|rate| Bucket::new(rate, now)),
+        }
+    }
+}
+
+impl RateLimiter {
+    pub(crate) fn new<R>(option: &DbOption<R>, clock: Clock) -> Self {
+        let now = Instant::now();
+        RateLimiter {
+            writes: Mutex::new(option.max_writes_per_sec.map(|rate| Bucket::new(rate, now))),
+            bytes: Mutex::new(
+                option
+                    .max_write_bytes_per_sec
+                    .map(|rate| Bucket::new(rate, now)),
+            ),
+            clock,
+        }
+    }
+
+    /// change the rates, see [`DB::set_options`](crate::DB::set_options)
+    pub(crate) fn set_rates<R>(&self, option: &DbOption<R>) {
+        let now = Instant::now();
+        Bucket::set(&self.writes, option.max_writes_per_sec, now);
+        Bucket::set(&self.bytes, option.max_write_bytes_per_sec, now);
+    }
+
+    /// how long the writes of `records` records and `bytes` bytes are held back
+    fn take(&self, records: usize, bytes: usize, now: Instant) -> Duration {
+        let writes = self
+            .writes
+            .lock()
+            .unwrap()
+            .as_mut()
+            .map_or(Duration::ZERO, |bucket| bucket.take(records as f64, now));
+        let bytes = self
+            .bytes
+            .lock()
+            .unwrap()
+            .as_mut()
+            .map_or(Duration::ZERO, |bucket| bucket.take(bytes as f64, now));
+        writes.max(bytes)
+    }
+
+    /// whether the bytes written are limited, so that they have to be sized
+    pub(crate) fn limits_bytes(&self) -> bool {
+        self.bytes.lock().unwrap().is_some()
+    }
+
+    /// wait out the rates for writing `records` records and `bytes` bytes
+    pub(crate) async fn acquire(&self, records: usize, bytes: usize) {
+        let delay = self.take(records, bytes, Instant::now());
+        if delay.is_zero() {
+            return;
+        }
+        metrics::increment(metrics::WRITE_THROTTLES, 1);
+        self.clock.sleep(delay).await;
+    }
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod tests {
+    use std::time::Duration;
+
+    use fusio::path::Path;
+    use tempfile::TempDir;
+    use web_time::Instant;
+
+    use super::Bucket;
+    use crate::{executor::tokio::TokioExecutor, tests::Test, DbOption, DB};
+
+    #[test]
+    fn bucket_debt() {
+        let now = Instant::now();
+        let mut bucket = Bucket::new(4, now);
+
+        assert_eq!(bucket.take(4.0, now), Duration::ZERO);
+        assert_eq!(bucket.take(2.0, now), Duration::from_millis(500));
+        // refilled by 4 tokens a second, 1 token in debt after another 250ms
+        assert_eq!(
+            bucket.take(0.0, now + Duration::from_millis(250)),
+            Duration::from_millis(250)
+        );
+        // never holds more than a second of tokens
+        assert_eq!(
+            bucket.take(4.0, now + Duration::from_secs(10)),
+            Duration::ZERO
+        );
+        assert_eq!(
+            bucket.take(8.0, now + Duration::from_secs(10)),
+            Duration::from_secs(2)
+        );
+    }
+
+    #[tokio::test]
+    async fn max_writes_per_sec() {
+        let temp_dir = TempDir::new().unwrap();
+        let option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap())
+            .max_writes_per_sec(20);
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+
+        let start = Instant::now();
+        for i in 0..20 {
+            db.insert(Test {
+                vstring: i.to_string(),
+                vu32: i,
+                vbool: None,
+            })
+            .await
+            .unwrap();
+        }
+        // a second of writes goes through at once
+        assert!(start.elapsed() < Duration::from_millis(500));
+
+        let mut txn = db.transaction().await;
+        for i in 20..30 {
+            txn.insert(Test {
+                vstring: i.to_string(),
+                vu32: i,
+                vbool: None,
+            });
+        }
+        txn.commit().await.unwrap();
+        // the commit is charged with its 10 writes, half a second of the rate
+        assert!(start.elapsed() >= Duration::from_millis(450));
+        assert_eq!(
+            db.get(&29.to_string(), |entry| entry.get().vu32)
+                .await
+                .unwrap(),
+            Some(29)
+        );
+        drop(db);
+
+        // unchecked writes are held back as well
+        let temp_dir = TempDir::new().unwrap();
+        let option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap())
+            .max_writes_per_sec(20)
+            .unchecked_writes(true);
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+
+        let start = Instant::now();
+        let mut txn = db.transaction().await;
+        for i in 0..30 {
+            txn.insert(Test {
+                vstring: i.to_string(),
+                vu32: i,
+                vbool: None,
+            });
+        }
+        txn.commit().await.unwrap();
+        // the commit waits for its 10 writes past a second of the rate
+        assert!(start.elapsed() >= Duration::from_millis(450));
+    }
+}
@@ -0,0 +1,217 @@
+use std::{
+    cmp::Ordering,
+    fmt,
+    hash::{Hash, Hasher},
+    mem::size_of,
+    sync::Arc,
+};
+
+use arrow::array::{Datum, UInt32Array, UInt64Array};
+use fusio::{SeqRead, Write};
+
+use crate::{
+    record::{Key, KeyRef},
+    serdes::{Decode, Encode},
+};
+
+macro_rules! implement_float_key {
+    ($(#[$meta:meta])* $struct_name:ident, $float:ident, $bits:ident, $array_name:ident) => {
+        $(#[$meta])*
+        #[derive(Clone, Copy)]
+        pub struct $struct_name($float);
+
+        impl $struct_name {
+            const SIGN: $bits = 1 << ($bits::BITS - 1);
+
+            /// every NaN is turned into the positive quiet NaN
+            pub fn new(value: $float) -> Self {
+                if value.is_nan() {
+                    $struct_name($float::NAN)
+                } else {
+                    $struct_name(value)
+                }
+            }
+
+            pub fn get(self) -> $float {
+                self.0
+            }
+
+            /// the bits of the value as an unsigned integer of the same order: the sign bit is
+            /// set on the positive values, and every bit is flipped on the negative ones
+            pub fn to_ordered_bits(self) -> $bits {
+                let bits = self.0.to_bits();
+                if bits & Self::SIGN == 0 {
+                    bits | Self::SIGN
+                } else {
+                    !bits
+                }
+            }
+
+            pub fn from_ordered_bits(bits: $bits) -> Self {
+                let bits = if bits & Self::SIGN == 0 {
+                    !bits
+                } else {
+                    bits & !Self::SIGN
+                };
+                $struct_name::new($float::from_bits(bits))
+            }
+        }
+
+        impl From<$float> for $struct_name {
+            fn from(value: $float) -> Self {
+                $struct_name::new(value)
+            }
+        }
+
+        impl From<$struct_name> for $float {
+            fn from(value: $struct_name) -> Self {
+                value.0
+            }
+        }
+
+        impl PartialEq for $struct_name {
+            fn eq(&self, other: &Self) -> bool {
+                self.to_ordered_bits() == other.to_ordered_bits()
+            }
+        }
+
+        impl Eq for $struct_name {}
+
+        impl PartialOrd for $struct_name {
+            fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+                Some(self.cmp(other))
+            }
+        }
+
+        impl Ord for $struct_name {
+            fn cmp(&self, other: &Self) -> Ordering {
+                self.to_ordered_bits().cmp(&other.to_ordered_bits())
+            }
+        }
+
+        impl Hash for $struct_name {
+            fn hash<H: Hasher>(&self, state: &mut H) {
+                self.to_ordered_bits().hash(state)
+            }
+        }
+
+        impl fmt::Debug for $struct_name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                fmt::Debug::fmt(&self.0, f)
+            }
+        }
+
+        impl Encode for $struct_name {
+            type Error = fusio::Error;
+
+            async fn encode<W: Write>(&self, writer: &mut W) -> Result<(), Self::Error> {
+                self.to_ordered_bits().encode(writer).await
+            }
+
+            fn size(&self) -> usize {
+                size_of::<$bits>()
+            }
+        }
+
+        impl Decode for $struct_name {
+            type Error = fusio::Error;
+
+            async fn decode<R: SeqRead>(reader: &mut R) -> Result<Self, Self::Error> {
+                Ok($struct_name::from_ordered_bits($bits::decode(reader).await?))
+            }
+        }
+
+        impl Key for $struct_name {
+            type Ref<'r> = $struct_name;
+
+            fn as_key_ref(&self) -> Self::Ref<'_> {
+                *self
+            }
+
+            fn to_arrow_datum(&self) -> Arc<dyn Datum> {
+                Arc::new($array_name::new_scalar(self.to_ordered_bits()))
+            }
+        }
+
+        impl<'a> KeyRef<'a> for $struct_name {
+            type Key = $struct_name;
+
+            fn to_key(self) -> Self::Key {
+                self
+            }
+        }
+    };
+}
+
+implement_float_key!(
+    /// An `f32` primary key ordered by [`f32::total_cmp`]: `-0.0` sorts before `0.0` and every
+    /// NaN is the same key, sorting after the infinity. The key column holds
+    /// [`F32::to_ordered_bits`] as a `UInt32` column, so that the statistics of the SSTs prune
+    /// the gets and range scans as on integers
+    F32,
+    f32,
+    u32,
+    UInt32Array
+);
+implement_float_key!(
+    /// An `f64` primary key ordered by [`f64::total_cmp`], held by a `UInt64` column, see
+    /// [`F32`]
+    F64,
+    f64,
+    u64,
+    UInt64Array
+);
+
+#[cfg(all(test, feature = "tokio"))]
+mod tests {
+    use std::io::Cursor;
+
+    use tokio::io::AsyncSeekExt;
+
+    use super::{F32, F64};
+    use crate::serdes::{Decode, Encode};
+
+    #[test]
+    fn total_order() {
+        let keys = [
+            f32::NEG_INFINITY,
+            -1.5,
+            -f32::MIN_POSITIVE,
+            -0.0,
+            0.0,
+            f32::MIN_POSITIVE,
+            1.5,
+            f32::INFINITY,
+            f32::NAN,
+        ]
+        .map(F32::new);
+        for pair in keys.windows(2) {
+            assert!(pair[0] < pair[1]);
+            assert!(pair[0].to_ordered_bits() < pair[1].to_ordered_bits());
+        }
+        for key in keys {
+            assert_eq!(F32::from_ordered_bits(key.to_ordered_bits()), key);
+        }
+
+        assert_eq!(F32::new(-f32::NAN), F32::new(f32::NAN));
+        assert_eq!(
+            F64::new(f64::from_bits(f64::NAN.to_bits() | 1)),
+            F64::new(f64::NAN)
+        );
+        assert!(F64::new(-0.0) < F64::new(0.0));
+        assert!(F64::new(f64::INFINITY) < F64::new(-f64::NAN));
+    }
+
+    #[tokio::test]
+    async fn encode_decode() {
+        let mut bytes = Vec::new();
+        let mut cursor = Cursor::new(&mut bytes);
+
+        F32::new(-2.5).encode(&mut cursor).await.unwrap();
+        F64::new(f64::NAN).encode(&mut cursor).await.unwrap();
+
+        cursor.seek(std::io::SeekFrom::Start(0)).await.unwrap();
+        assert_eq!(F32::decode(&mut cursor).await.unwrap().get(), -2.5);
+        assert!(F64::decode(&mut cursor).await.unwrap().get().is_nan());
+    }
+}
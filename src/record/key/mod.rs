@@ -1,9 +1,11 @@
+mod float;
 mod num;
 mod str;
 
 use std::{hash::Hash, sync::Arc};
 
 use arrow::array::Datum;
+pub use float::{F32, F64};
 
 use crate::serdes::{Decode, Encode};
 
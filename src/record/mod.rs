@@ -8,7 +8,7 @@ use std::{error::Error, fmt::Debug, io, sync::Arc};
 
 use arrow::{array::RecordBatch, datatypes::Schema};
 use internal::InternalRecordRef;
-pub use key::{Key, KeyRef};
+pub use key::{Key, KeyRef, F32, F64};
 use parquet::{arrow::ProjectionMask, format::SortingColumn, schema::types::ColumnPath};
 pub use runtime::*;
 use thiserror::Error;
@@ -19,6 +19,7 @@ use crate::{
 };
 
 #[allow(unused)]
+#[derive(Clone)]
 pub(crate) enum RecordInstance {
     Normal,
     Runtime(DynRecord),
@@ -3,8 +3,9 @@ use std::{any::Any, mem, sync::Arc};
 use arrow::{
     array::{
         Array, ArrayBuilder, ArrayRef, ArrowPrimitiveType, BooleanArray, BooleanBufferBuilder,
-        BooleanBuilder, GenericBinaryArray, GenericBinaryBuilder, PrimitiveArray, PrimitiveBuilder,
-        StringArray, StringBuilder, UInt32Builder,
+        BooleanBuilder, FixedSizeBinaryArray, FixedSizeBinaryBuilder, GenericBinaryArray,
+        GenericBinaryBuilder, PrimitiveArray, PrimitiveBuilder, StringArray, StringBuilder,
+        UInt64Builder,
     },
     datatypes::{
         Int16Type, Int32Type, Int64Type, Int8Type, Schema, UInt16Type, UInt32Type, UInt64Type,
@@ -22,7 +23,7 @@ use crate::{
 #[allow(unused)]
 pub struct DynRecordImmutableArrays {
     _null: Arc<arrow::array::BooleanArray>,
-    _ts: Arc<arrow::array::UInt32Array>,
+    _ts: Arc<arrow::array::UInt64Array>,
     columns: Vec<Column>,
     record_batch: arrow::record_batch::RecordBatch,
 }
@@ -89,6 +90,16 @@ impl ArrowArrays for DynRecordImmutableArrays {
                         capacity, 0,
                     )));
                 }
+                Datatype::LargeBytes => {
+                    builders.push(Box::new(GenericBinaryBuilder::<i64>::with_capacity(
+                        capacity, 0,
+                    )));
+                }
+                Datatype::FixedSizeBytes(size) => {
+                    builders.push(Box::new(FixedSizeBinaryBuilder::with_capacity(
+                        capacity, size,
+                    )));
+                }
             }
             datatypes.push(datatype);
         }
@@ -96,7 +107,7 @@ impl ArrowArrays for DynRecordImmutableArrays {
             builders,
             datatypes,
             _null: arrow::array::BooleanBufferBuilder::new(capacity),
-            _ts: arrow::array::UInt32Builder::with_capacity(capacity),
+            _ts: arrow::array::UInt64Builder::with_capacity(capacity),
             schema: schema.clone(),
         }
     }
@@ -152,6 +163,22 @@ impl ArrowArrays for DynRecordImmutableArrays {
                             .value(offset)
                             .to_owned(),
                     ),
+                    Datatype::LargeBytes => Arc::new(
+                        col.value
+                            .as_ref()
+                            .downcast_ref::<GenericBinaryArray<i64>>()
+                            .unwrap()
+                            .value(offset)
+                            .to_owned(),
+                    ),
+                    Datatype::FixedSizeBytes(_) => Arc::new(
+                        col.value
+                            .as_ref()
+                            .downcast_ref::<FixedSizeBinaryArray>()
+                            .unwrap()
+                            .value(offset)
+                            .to_owned(),
+                    ),
                 };
                 columns.push(Column {
                     datatype,
@@ -187,7 +214,7 @@ pub struct DynRecordBuilder {
     builders: Vec<Box<dyn ArrayBuilder + Send + Sync>>,
     datatypes: Vec<Datatype>,
     _null: BooleanBufferBuilder,
-    _ts: UInt32Builder,
+    _ts: UInt64Builder,
     schema: Arc<Schema>,
 }
 
@@ -329,6 +356,34 @@ impl Builder<DynRecordImmutableArrays> for DynRecordBuilder {
                                 None => bd.append_null(),
                             }
                         }
+                        Datatype::LargeBytes => {
+                            let bd =
+                                Self::as_builder_mut::<GenericBinaryBuilder<i64>>(builder.as_mut());
+                            let value = col
+                                .value
+                                .as_ref()
+                                .downcast_ref::<Option<Vec<u8>>>()
+                                .unwrap();
+                            match value {
+                                Some(value) => bd.append_value(value),
+                                None => bd.append_null(),
+                            }
+                        }
+                        Datatype::FixedSizeBytes(_) => {
+                            let bd =
+                                Self::as_builder_mut::<FixedSizeBinaryBuilder>(builder.as_mut());
+                            let value = col
+                                .value
+                                .as_ref()
+                                .downcast_ref::<Option<Vec<u8>>>()
+                                .unwrap();
+                            match value {
+                                Some(value) => bd
+                                    .append_value(value)
+                                    .expect("the value must be of the size of its column"),
+                                None => bd.append_null(),
+                            }
+                        }
                     }
                 }
             }
@@ -387,6 +442,15 @@ impl Builder<DynRecordImmutableArrays> for DynRecordBuilder {
                             Self::as_builder_mut::<GenericBinaryBuilder<i32>>(builder.as_mut())
                                 .append_value(Vec::<u8>::default());
                         }
+                        Datatype::LargeBytes => {
+                            Self::as_builder_mut::<GenericBinaryBuilder<i64>>(builder.as_mut())
+                                .append_value(Vec::<u8>::default());
+                        }
+                        Datatype::FixedSizeBytes(size) => {
+                            Self::as_builder_mut::<FixedSizeBinaryBuilder>(builder.as_mut())
+                                .append_value(vec![0u8; *size as usize])
+                                .unwrap();
+                        }
                     }
                 }
             }
@@ -442,6 +506,11 @@ impl Builder<DynRecordImmutableArrays> for DynRecordBuilder {
                         Self::as_builder::<GenericBinaryBuilder<i32>>(builder.as_ref())
                             .values_slice(),
                     ),
+                    Datatype::LargeBytes => mem::size_of_val(
+                        Self::as_builder::<GenericBinaryBuilder<i64>>(builder.as_ref())
+                            .values_slice(),
+                    ),
+                    Datatype::FixedSizeBytes(size) => builder.len() * *size as usize,
                 }
             })
     }
@@ -600,6 +669,31 @@ impl Builder<DynRecordImmutableArrays> for DynRecordBuilder {
                     });
                     array_refs.push(value);
                 }
+                Datatype::LargeBytes => {
+                    let value = Arc::new(
+                        Self::as_builder_mut::<GenericBinaryBuilder<i64>>(builder.as_mut())
+                            .finish(),
+                    );
+                    columns.push(Column {
+                        datatype: Datatype::LargeBytes,
+                        name: field.name().to_owned(),
+                        value: value.clone(),
+                        is_nullable,
+                    });
+                    array_refs.push(value);
+                }
+                Datatype::FixedSizeBytes(size) => {
+                    let value = Arc::new(
+                        Self::as_builder_mut::<FixedSizeBinaryBuilder>(builder.as_mut()).finish(),
+                    );
+                    columns.push(Column {
+                        datatype: Datatype::FixedSizeBytes(*size),
+                        name: field.name().to_owned(),
+                        value: value.clone(),
+                        is_nullable,
+                    });
+                    array_refs.push(value);
+                }
             };
         }
 
@@ -667,6 +761,15 @@ impl DynRecordBuilder {
                 .append_value(*col.value.as_ref().downcast_ref::<bool>().unwrap()),
             Datatype::Bytes => Self::as_builder_mut::<GenericBinaryBuilder<i32>>(builder.as_mut())
                 .append_value(col.value.as_ref().downcast_ref::<Vec<u8>>().unwrap()),
+            Datatype::LargeBytes => {
+                Self::as_builder_mut::<GenericBinaryBuilder<i64>>(builder.as_mut())
+                    .append_value(col.value.as_ref().downcast_ref::<Vec<u8>>().unwrap())
+            }
+            Datatype::FixedSizeBytes(_) => {
+                Self::as_builder_mut::<FixedSizeBinaryBuilder>(builder.as_mut())
+                    .append_value(col.value.as_ref().downcast_ref::<Vec<u8>>().unwrap())
+                    .expect("the primary key must be of the size of its column")
+            }
         };
     }
 
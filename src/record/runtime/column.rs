@@ -2,8 +2,8 @@ use std::{any::Any, fmt::Debug, hash::Hash, sync::Arc};
 
 use arrow::{
     array::{
-        BooleanArray, GenericBinaryArray, Int16Array, Int32Array, Int64Array, Int8Array,
-        StringArray, UInt16Array, UInt32Array, UInt64Array, UInt8Array,
+        BooleanArray, FixedSizeBinaryArray, GenericBinaryArray, Int16Array, Int32Array, Int64Array,
+        Int8Array, StringArray, UInt16Array, UInt32Array, UInt64Array, UInt8Array,
     },
     datatypes::{DataType, Field},
 };
@@ -12,7 +12,7 @@ use fusio::{SeqRead, Write};
 use super::Datatype;
 use crate::{
     record::{Key, KeyRef},
-    serdes::{option::DecodeError, Decode, Encode},
+    serdes::{decode_with, encode_with, option::DecodeError, Decode, Encode},
 };
 
 #[derive(Debug, Clone)]
@@ -86,7 +86,7 @@ impl Column {
             Datatype::Boolean => {
                 Self::new(datatype, name, Arc::<Option<bool>>::new(None), is_nullable)
             }
-            Datatype::Bytes => Self::new(
+            Datatype::Bytes | Datatype::LargeBytes | Datatype::FixedSizeBytes(_) => Self::new(
                 datatype,
                 name,
                 Arc::<Option<Vec<u8>>>::new(None),
@@ -105,12 +105,12 @@ impl PartialOrd for Column {
 }
 
 macro_rules! implement_col {
-    ([], $({$Type:ty, $Datatype:ident}), *) => {
+    ([], $({$Type:ty, $Datatype:pat}), *) => {
         impl Ord for Column {
             fn cmp(&self, other: &Self) -> std::cmp::Ordering {
                 match self.datatype {
                     $(
-                        Datatype::$Datatype => self
+                        $Datatype => self
                             .value
                             .downcast_ref::<$Type>()
                             .cmp(&other.value.downcast_ref::<$Type>()),
@@ -125,7 +125,7 @@ macro_rules! implement_col {
                     && self.is_nullable == other.is_nullable
                     && match self.datatype {
                         $(
-                            Datatype::$Datatype => self
+                            $Datatype => self
                                 .value
                                 .downcast_ref::<$Type>()
                                 .eq(&other.value.downcast_ref::<$Type>()),
@@ -138,7 +138,7 @@ macro_rules! implement_col {
             fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
                 match self.datatype {
                     $(
-                        Datatype::$Datatype => self.value.downcast_ref::<$Type>().hash(state),
+                        $Datatype => self.value.downcast_ref::<$Type>().hash(state),
                     )*
                 }
             }
@@ -149,7 +149,7 @@ macro_rules! implement_col {
                 let mut debug_struct = f.debug_struct("Column");
                 match self.datatype {
                     $(
-                        Datatype::$Datatype => {
+                        $Datatype => {
                             debug_struct.field("datatype", &stringify!($Type));
                             if let Some(value) = self.value.as_ref().downcast_ref::<$Type>() {
                                 debug_struct.field("value", value);
@@ -210,6 +210,20 @@ macro_rules! implement_key_col {
                             .downcast_ref::<Vec<u8>>()
                             .expect("unexpected datatype, expected bytes"),
                     )),
+                    Datatype::LargeBytes => Arc::new(GenericBinaryArray::<i64>::new_scalar(
+                        self
+                            .value
+                            .as_ref()
+                            .downcast_ref::<Vec<u8>>()
+                            .expect("unexpected datatype, expected bytes"),
+                    )),
+                    Datatype::FixedSizeBytes(_) => Arc::new(FixedSizeBinaryArray::new_scalar(
+                        self
+                            .value
+                            .as_ref()
+                            .downcast_ref::<Vec<u8>>()
+                            .expect("unexpected datatype, expected bytes"),
+                    )),
                 }
             }
         }
@@ -225,7 +239,7 @@ impl<'r> KeyRef<'r> for Column {
 }
 
 macro_rules! implement_decode_col {
-    ([], $({$Type:ty, $Datatype:ident}), *) => {
+    ([], $({$Type:ty, $Datatype:pat}), *) => {
         impl Decode for Column {
             type Error = fusio::Error;
 
@@ -233,26 +247,44 @@ macro_rules! implement_decode_col {
             where
                 R: SeqRead,
             {
-                let tag = u8::decode(reader).await?;
-                let datatype = Self::tag_to_datatype(tag);
+                Self::decode_as(reader, false).await
+            }
+
+            async fn decode_compact<R>(reader: &mut R) -> Result<Self, Self::Error>
+            where
+                R: SeqRead,
+            {
+                Self::decode_as(reader, true).await
+            }
+        }
+
+        impl Column {
+            async fn decode_as<R>(reader: &mut R, compact: bool) -> Result<Self, fusio::Error>
+            where
+                R: SeqRead,
+            {
+                let datatype = match u8::decode(reader).await? {
+                    Self::FIXED_SIZE_BYTES_TAG => Datatype::FixedSizeBytes(decode_with(reader, compact).await?),
+                    tag => Self::tag_to_datatype(tag),
+                };
                 let is_nullable = bool::decode(reader).await?;
                 let is_some = !bool::decode(reader).await?;
                 let value =
                     match datatype {
                         $(
-                            Datatype::$Datatype => match is_some {
-                                true => Arc::new(Option::<$Type>::decode(reader).await.map_err(
+                            $Datatype => match is_some {
+                                true => Arc::new(decode_with::<Option<$Type>, _>(reader, compact).await.map_err(
                                     |err| match err {
                                         DecodeError::Io(error) => fusio::Error::Io(error),
                                         DecodeError::Fusio(error) => error,
                                         DecodeError::Inner(error) => fusio::Error::Other(Box::new(error)),
                                     },
                                 )?) as Arc<dyn Any + Send + Sync>,
-                                false => Arc::new(<$Type>::decode(reader).await?) as Arc<dyn Any + Send + Sync>,
+                                false => Arc::new(decode_with::<$Type, _>(reader, compact).await?) as Arc<dyn Any + Send + Sync>,
                             },
                         )*
                     };
-                let name = String::decode(reader).await?;
+                let name = decode_with(reader, compact).await?;
                 Ok(Column {
                     datatype,
                     is_nullable,
@@ -265,61 +297,87 @@ macro_rules! implement_decode_col {
 }
 
 macro_rules! implement_encode_col {
-    ([], $({$Type:ty, $Datatype:ident}), *) => {
+    ([], $({$Type:ty, $Datatype:pat}), *) => {
         impl Encode for Column {
             type Error = fusio::Error;
 
             async fn encode<W>(&self, writer: &mut W) -> Result<(), Self::Error>
+            where
+                W: Write,
+            {
+                self.encode_as(writer, false).await
+            }
+
+            async fn encode_compact<W>(&self, writer: &mut W) -> Result<(), Self::Error>
+            where
+                W: Write,
+            {
+                self.encode_as(writer, true).await
+            }
+
+            fn size(&self) -> usize {
+                let width = match self.datatype {
+                    Datatype::FixedSizeBytes(size) => size.size(),
+                    _ => 0,
+                };
+                3 + width + self.name.size() + match self.datatype {
+                    $(
+                        $Datatype => {
+                            if let Some(value) = self.value.as_ref().downcast_ref::<$Type>() {
+                                value.size()
+                            } else {
+                                self.value
+                                    .as_ref()
+                                    .downcast_ref::<Option<$Type>>()
+                                    .unwrap()
+                                    .size()
+                            }
+                        }
+                    )*
+                }
+            }
+        }
+
+        impl Column {
+            async fn encode_as<W>(&self, writer: &mut W, compact: bool) -> Result<(), fusio::Error>
             where
                 W: Write,
             {
                 Self::tag(self.datatype).encode(writer).await?;
+                if let Datatype::FixedSizeBytes(size) = self.datatype {
+                    encode_with(&size, writer, compact).await?;
+                }
                 self.is_nullable.encode(writer).await?;
                 match self.datatype {
                         $(
-                            Datatype::$Datatype => {
+                            $Datatype => {
                                 if let Some(value) = self.value.as_ref().downcast_ref::<$Type>() {
                                     true.encode(writer).await?;
-                                    value.encode(writer).await?
+                                    encode_with(value, writer, compact).await?
                                 } else {
                                     false.encode(writer).await?;
-                                    self.value
+                                    let value = self.value
                                         .as_ref()
                                         .downcast_ref::<Option<$Type>>()
-                                        .unwrap()
-                                        .encode(writer)
+                                        .unwrap();
+                                    encode_with(value, writer, compact)
                                         .await
                                         .map_err(|err| fusio::Error::Other(Box::new(err)))?;
                                 }
                             }
                         )*
                 };
-                self.name.encode(writer).await?;
+                encode_with(&self.name, writer, compact).await?;
                 Ok(())
             }
-
-            fn size(&self) -> usize {
-                3 + self.name.size() + match self.datatype {
-                    $(
-                        Datatype::$Datatype => {
-                            if let Some(value) = self.value.as_ref().downcast_ref::<$Type>() {
-                                value.size()
-                            } else {
-                                self.value
-                                    .as_ref()
-                                    .downcast_ref::<Option<$Type>>()
-                                    .unwrap()
-                                    .size()
-                            }
-                        }
-                    )*
-                }
-            }
         }
     }
 }
 
 impl Column {
+    /// followed by the size of the values
+    const FIXED_SIZE_BYTES_TAG: u8 = 12;
+
     fn tag(datatype: Datatype) -> u8 {
         match datatype {
             Datatype::UInt8 => 0,
@@ -333,6 +391,8 @@ impl Column {
             Datatype::String => 8,
             Datatype::Boolean => 9,
             Datatype::Bytes => 10,
+            Datatype::LargeBytes => 11,
+            Datatype::FixedSizeBytes(_) => Self::FIXED_SIZE_BYTES_TAG,
         }
     }
 
@@ -349,6 +409,7 @@ impl Column {
             8 => Datatype::String,
             9 => Datatype::Boolean,
             10 => Datatype::Bytes,
+            11 => Datatype::LargeBytes,
             _ => panic!("invalid datatype tag"),
         }
     }
@@ -368,6 +429,10 @@ impl From<&Column> for Field {
             Datatype::String => Field::new(&col.name, DataType::Utf8, col.is_nullable),
             Datatype::Boolean => Field::new(&col.name, DataType::Boolean, col.is_nullable),
             Datatype::Bytes => Field::new(&col.name, DataType::Binary, col.is_nullable),
+            Datatype::LargeBytes => Field::new(&col.name, DataType::LargeBinary, col.is_nullable),
+            Datatype::FixedSizeBytes(size) => {
+                Field::new(&col.name, DataType::FixedSizeBinary(size), col.is_nullable)
+            }
         }
     }
 }
@@ -376,17 +441,17 @@ macro_rules! for_datatype {
     ($macro:tt $(, $x:tt)*) => {
         $macro! {
             [$($x),*],
-                { u8, UInt8 },
-                { u16, UInt16 },
-                { u32, UInt32 },
-                { u64, UInt64 },
-                { i8, Int8 },
-                { i16, Int16 },
-                { i32, Int32 },
-                { i64, Int64 },
-                { String, String },
-                { bool, Boolean },
-                { Vec<u8>, Bytes }
+                { u8, Datatype::UInt8 },
+                { u16, Datatype::UInt16 },
+                { u32, Datatype::UInt32 },
+                { u64, Datatype::UInt64 },
+                { i8, Datatype::Int8 },
+                { i16, Datatype::Int16 },
+                { i32, Datatype::Int32 },
+                { i64, Datatype::Int64 },
+                { String, Datatype::String },
+                { bool, Datatype::Boolean },
+                { Vec<u8>, Datatype::Bytes | Datatype::LargeBytes | Datatype::FixedSizeBytes(_) }
         }
     };
 }
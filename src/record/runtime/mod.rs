@@ -2,11 +2,13 @@ mod array;
 mod column;
 mod record;
 mod record_ref;
+mod value;
 
 use arrow::datatypes::DataType;
 pub use column::*;
 pub use record::*;
 pub use record_ref::*;
+pub use value::*;
 
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Datatype {
@@ -20,7 +22,12 @@ pub enum Datatype {
     Int64,
     String,
     Boolean,
+    /// a `Binary` column
     Bytes,
+    /// a `LargeBinary` column, for values over 2GB in total per batch
+    LargeBytes,
+    /// a `FixedSizeBinary` column of values of the given size, such as hashes
+    FixedSizeBytes(i32),
 }
 
 impl From<&DataType> for Datatype {
@@ -37,6 +44,8 @@ impl From<&DataType> for Datatype {
             DataType::Utf8 => Datatype::String,
             DataType::Boolean => Datatype::Boolean,
             DataType::Binary => Datatype::Bytes,
+            DataType::LargeBinary => Datatype::LargeBytes,
+            DataType::FixedSizeBinary(size) => Datatype::FixedSizeBytes(*size),
             _ => todo!(),
         }
     }
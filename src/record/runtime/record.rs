@@ -7,10 +7,10 @@ use parquet::{format::SortingColumn, schema::types::ColumnPath};
 use super::{array::DynRecordImmutableArrays, Column, ColumnDesc, Datatype, DynRecordRef};
 use crate::{
     record::{Record, RecordDecodeError},
-    serdes::{Decode, Encode},
+    serdes::{decode_with, Decode, Encode},
 };
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct DynRecord {
     columns: Vec<Column>,
     primary_index: usize,
@@ -32,7 +32,7 @@ impl DynRecord {
     pub(crate) fn arrow_schema(&self) -> Arc<Schema> {
         let mut fields = vec![
             Field::new("_null", DataType::Boolean, false),
-            Field::new("_ts", DataType::UInt32, false),
+            Field::new("_ts", DataType::UInt64, false),
         ];
 
         for (idx, col) in self.columns.iter().enumerate() {
@@ -96,10 +96,14 @@ impl DynRecord {
                     true => Arc::<Option<bool>>::new(None),
                     false => Arc::new(bool::default()),
                 },
-                Datatype::Bytes => match desc.is_nullable {
+                Datatype::Bytes | Datatype::LargeBytes => match desc.is_nullable {
                     true => Arc::<Option<Vec<u8>>>::new(None),
                     false => Arc::new(Vec::<u8>::default()),
                 },
+                Datatype::FixedSizeBytes(size) => match desc.is_nullable {
+                    true => Arc::<Option<Vec<u8>>>::new(None),
+                    false => Arc::new(vec![0u8; size as usize]),
+                },
             };
             columns.push(Column::new(
                 desc.datatype,
@@ -120,12 +124,28 @@ impl Decode for DynRecord {
     where
         R: SeqRead,
     {
-        let len = u32::decode(reader).await? as usize;
-        let primary_index = u32::decode(reader).await? as usize;
+        Self::decode_as(reader, false).await
+    }
+
+    async fn decode_compact<R>(reader: &mut R) -> Result<Self, Self::Error>
+    where
+        R: SeqRead,
+    {
+        Self::decode_as(reader, true).await
+    }
+}
+
+impl DynRecord {
+    async fn decode_as<R>(reader: &mut R, compact: bool) -> Result<Self, RecordDecodeError>
+    where
+        R: SeqRead,
+    {
+        let len = decode_with::<u32, _>(reader, compact).await? as usize;
+        let primary_index = decode_with::<u32, _>(reader, compact).await? as usize;
         let mut columns = vec![];
         // keep invariant for record: nullable --> Some(v); non-nullable --> v
         for i in 0..len {
-            let mut col = Column::decode(reader).await?;
+            let mut col: Column = decode_with(reader, compact).await?;
             if i != primary_index && !col.is_nullable {
                 match col.datatype {
                     Datatype::UInt8 => {
@@ -168,7 +188,7 @@ impl Decode for DynRecord {
                         let value = col.value.as_ref().downcast_ref::<Option<bool>>().unwrap();
                         col.value = Arc::new(value.unwrap());
                     }
-                    Datatype::Bytes => {
+                    Datatype::Bytes | Datatype::LargeBytes | Datatype::FixedSizeBytes(_) => {
                         let value = col
                             .value
                             .as_ref()
@@ -245,13 +265,15 @@ impl Record for DynRecord {
                     Datatype::Boolean => {
                         Arc::new(Some(*col.value.as_ref().downcast_ref::<bool>().unwrap()))
                     }
-                    Datatype::Bytes => Arc::new(Some(
-                        col.value
-                            .as_ref()
-                            .downcast_ref::<Vec<u8>>()
-                            .unwrap()
-                            .to_owned(),
-                    )),
+                    Datatype::Bytes | Datatype::LargeBytes | Datatype::FixedSizeBytes(_) => {
+                        Arc::new(Some(
+                            col.value
+                                .as_ref()
+                                .downcast_ref::<Vec<u8>>()
+                                .unwrap()
+                                .to_owned(),
+                        ))
+                    }
                 };
             }
 
@@ -12,7 +12,7 @@ use fusio::Write;
 use super::{Column, Datatype, DynRecord};
 use crate::{
     record::{internal::InternalRecordRef, Key, Record, RecordEncodeError, RecordRef},
-    serdes::Encode,
+    serdes::{encode_with, Encode},
 };
 
 #[derive(Clone)]
@@ -40,12 +40,14 @@ impl<'r> Encode for DynRecordRef<'r> {
     where
         W: Write,
     {
-        (self.columns.len() as u32).encode(writer).await?;
-        (self.primary_index as u32).encode(writer).await?;
-        for col in self.columns.iter() {
-            col.encode(writer).await.map_err(RecordEncodeError::Fusio)?;
-        }
-        Ok(())
+        self.encode_as(writer, false).await
+    }
+
+    async fn encode_compact<W>(&self, writer: &mut W) -> Result<(), Self::Error>
+    where
+        W: Write,
+    {
+        self.encode_as(writer, true).await
     }
 
     fn size(&self) -> usize {
@@ -57,6 +59,22 @@ impl<'r> Encode for DynRecordRef<'r> {
     }
 }
 
+impl DynRecordRef<'_> {
+    async fn encode_as<W>(&self, writer: &mut W, compact: bool) -> Result<(), RecordEncodeError>
+    where
+        W: Write,
+    {
+        encode_with(&(self.columns.len() as u32), writer, compact).await?;
+        encode_with(&(self.primary_index as u32), writer, compact).await?;
+        for col in self.columns.iter() {
+            encode_with(col, writer, compact)
+                .await
+                .map_err(RecordEncodeError::Fusio)?;
+        }
+        Ok(())
+    }
+}
+
 impl<'r> RecordRef<'r> for DynRecordRef<'r> {
     type Record = DynRecord;
 
@@ -83,7 +101,7 @@ impl<'r> RecordRef<'r> for DynRecordRef<'r> {
             .unwrap();
         let ts = record_batch
             .column(1)
-            .as_primitive::<arrow::datatypes::UInt32Type>()
+            .as_primitive::<arrow::datatypes::UInt64Type>()
             .value(offset)
             .into();
 
@@ -196,6 +214,26 @@ impl<'r> RecordRef<'r> for DynRecordRef<'r> {
                         Arc::new(value) as Arc<dyn Any + Send + Sync>
                     }
                 }
+                Datatype::LargeBytes => {
+                    let v = col.as_binary::<i64>();
+                    if primary_index == idx - 2 {
+                        Arc::new(v.value(offset).to_owned()) as Arc<dyn Any + Send + Sync>
+                    } else {
+                        let value = (!v.is_null(offset) && projection_mask.leaf_included(idx))
+                            .then_some(v.value(offset).to_owned());
+                        Arc::new(value) as Arc<dyn Any + Send + Sync>
+                    }
+                }
+                Datatype::FixedSizeBytes(_) => {
+                    let v = col.as_fixed_size_binary();
+                    if primary_index == idx - 2 {
+                        Arc::new(v.value(offset).to_owned()) as Arc<dyn Any + Send + Sync>
+                    } else {
+                        let value = (!v.is_null(offset) && projection_mask.leaf_included(idx))
+                            .then_some(v.value(offset).to_owned());
+                        Arc::new(value) as Arc<dyn Any + Send + Sync>
+                    }
+                }
             };
             columns.push(Column::new(
                 datatype,
@@ -227,7 +265,9 @@ impl<'r> RecordRef<'r> for DynRecordRef<'r> {
                     Datatype::Int64 => col.value = Arc::<Option<i64>>::new(None),
                     Datatype::String => col.value = Arc::<Option<String>>::new(None),
                     Datatype::Boolean => col.value = Arc::<Option<bool>>::new(None),
-                    Datatype::Bytes => col.value = Arc::<Option<Vec<u8>>>::new(None),
+                    Datatype::Bytes | Datatype::LargeBytes | Datatype::FixedSizeBytes(_) => {
+                        col.value = Arc::<Option<Vec<u8>>>::new(None)
+                    }
                 };
             }
         }
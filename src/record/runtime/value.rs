@@ -0,0 +1,214 @@
+use std::sync::Arc;
+
+use super::{Column, Datatype};
+
+/// A type held by the columns of dynamic records as the values of one of the [`Datatype`]s,
+/// which is how the types of other crates, behind the features of the same names, are written
+/// to arrow:
+///
+/// - `uuid::Uuid` as its 16 bytes in a [`Datatype::FixedSizeBytes`] column
+/// - `chrono::DateTime<Utc>`, `chrono::NaiveDateTime` and `time::OffsetDateTime` as the
+///   microseconds since the epoch in an [`Datatype::Int64`] column, the offset of the last is not
+///   kept
+/// - `rust_decimal::Decimal` as its text in a [`Datatype::String`] column, which does not sort as
+///   the numbers do
+pub trait ColumnValue: Sized {
+    /// the value held by [`Column::value`]
+    type Value: Send + Sync + 'static;
+
+    const DATATYPE: Datatype;
+
+    fn to_value(&self) -> Self::Value;
+
+    /// `None` if no `Self` is written as `value`
+    fn from_value(value: &Self::Value) -> Option<Self>;
+}
+
+impl Column {
+    /// a column of `value` that is not nullable
+    pub fn from_value<T: ColumnValue>(name: String, value: &T) -> Self {
+        Column::new(T::DATATYPE, name, Arc::new(value.to_value()), false)
+    }
+
+    /// a nullable column of `value`
+    pub fn from_option<T: ColumnValue>(name: String, value: Option<&T>) -> Self {
+        Column::new(T::DATATYPE, name, Arc::new(value.map(T::to_value)), true)
+    }
+
+    /// the value of the column, `None` if it is null or not of `T`
+    pub fn get<T: ColumnValue>(&self) -> Option<T> {
+        if self.datatype != T::DATATYPE {
+            return None;
+        }
+        match self.value.downcast_ref::<T::Value>() {
+            Some(value) => T::from_value(value),
+            None => T::from_value(self.value.downcast_ref::<Option<T::Value>>()?.as_ref()?),
+        }
+    }
+}
+
+macro_rules! implement_column_value {
+    ($({$Type:ty, $Datatype:expr}), *) => {
+        $(
+            impl ColumnValue for $Type {
+                type Value = $Type;
+
+                const DATATYPE: Datatype = $Datatype;
+
+                fn to_value(&self) -> Self::Value {
+                    self.to_owned()
+                }
+
+                fn from_value(value: &Self::Value) -> Option<Self> {
+                    Some(value.to_owned())
+                }
+            }
+        )*
+    };
+}
+
+implement_column_value!(
+    { u8, Datatype::UInt8 }, { u16, Datatype::UInt16 }, { u32, Datatype::UInt32 }, { u64, Datatype::UInt64 },
+    { i8, Datatype::Int8 }, { i16, Datatype::Int16 }, { i32, Datatype::Int32 }, { i64, Datatype::Int64 },
+    { String, Datatype::String }, { bool, Datatype::Boolean }, { Vec<u8>, Datatype::Bytes }
+);
+
+#[cfg(feature = "uuid")]
+impl ColumnValue for uuid::Uuid {
+    type Value = Vec<u8>;
+
+    const DATATYPE: Datatype = Datatype::FixedSizeBytes(16);
+
+    fn to_value(&self) -> Self::Value {
+        self.as_bytes().to_vec()
+    }
+
+    fn from_value(value: &Self::Value) -> Option<Self> {
+        uuid::Uuid::from_slice(value).ok()
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl ColumnValue for chrono::DateTime<chrono::Utc> {
+    type Value = i64;
+
+    const DATATYPE: Datatype = Datatype::Int64;
+
+    fn to_value(&self) -> Self::Value {
+        self.timestamp_micros()
+    }
+
+    fn from_value(value: &Self::Value) -> Option<Self> {
+        chrono::DateTime::from_timestamp_micros(*value)
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl ColumnValue for chrono::NaiveDateTime {
+    type Value = i64;
+
+    const DATATYPE: Datatype = Datatype::Int64;
+
+    fn to_value(&self) -> Self::Value {
+        self.and_utc().timestamp_micros()
+    }
+
+    fn from_value(value: &Self::Value) -> Option<Self> {
+        chrono::DateTime::from_timestamp_micros(*value).map(|date_time| date_time.naive_utc())
+    }
+}
+
+#[cfg(feature = "time")]
+impl ColumnValue for time::OffsetDateTime {
+    type Value = i64;
+
+    const DATATYPE: Datatype = Datatype::Int64;
+
+    fn to_value(&self) -> Self::Value {
+        (self.unix_timestamp_nanos() / 1_000) as i64
+    }
+
+    fn from_value(value: &Self::Value) -> Option<Self> {
+        time::OffsetDateTime::from_unix_timestamp_nanos(*value as i128 * 1_000).ok()
+    }
+}
+
+#[cfg(feature = "rust_decimal")]
+impl ColumnValue for rust_decimal::Decimal {
+    type Value = String;
+
+    const DATATYPE: Datatype = Datatype::String;
+
+    fn to_value(&self) -> Self::Value {
+        self.to_string()
+    }
+
+    fn from_value(value: &Self::Value) -> Option<Self> {
+        value.parse().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::record::Column;
+
+    #[test]
+    fn builtin() {
+        let column = Column::from_value("id".to_string(), &42i64);
+        assert_eq!(column.get::<i64>(), Some(42));
+        assert_eq!(column.get::<u64>(), None);
+
+        let column = Column::from_option::<String>("name".to_string(), None);
+        assert!(column.is_nullable);
+        assert_eq!(column.get::<String>(), None);
+        let column = Column::from_option("name".to_string(), Some(&"tonbo".to_string()));
+        assert_eq!(column.get::<String>(), Some("tonbo".to_string()));
+    }
+
+    #[cfg(feature = "uuid")]
+    #[test]
+    fn uuid() {
+        let uuid = uuid::Uuid::from_u128(0x936d_a01f_9abd_4d9d_80c7_02af_85c8_22a8);
+        let column = Column::from_value("id".to_string(), &uuid);
+        assert_eq!(
+            arrow::datatypes::Field::from(&column).data_type(),
+            &arrow::datatypes::DataType::FixedSizeBinary(16)
+        );
+        assert_eq!(column.get::<uuid::Uuid>(), Some(uuid));
+        assert_eq!(column.get::<Vec<u8>>(), None);
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn chrono() {
+        let date_time = chrono::DateTime::from_timestamp(1_700_000_000, 123_456_000).unwrap();
+        let column = Column::from_value("at".to_string(), &date_time);
+        assert_eq!(column.get::<i64>(), Some(1_700_000_000_123_456));
+        assert_eq!(
+            column.get::<chrono::DateTime<chrono::Utc>>(),
+            Some(date_time)
+        );
+        assert_eq!(
+            column.get::<chrono::NaiveDateTime>(),
+            Some(date_time.naive_utc())
+        );
+    }
+
+    #[cfg(feature = "time")]
+    #[test]
+    fn time() {
+        let date_time = time::OffsetDateTime::from_unix_timestamp(-1_700_000_000).unwrap();
+        let column = Column::from_option("at".to_string(), Some(&date_time));
+        assert_eq!(column.get::<i64>(), Some(-1_700_000_000_000_000));
+        assert_eq!(column.get::<time::OffsetDateTime>(), Some(date_time));
+    }
+
+    #[cfg(feature = "rust_decimal")]
+    #[test]
+    fn decimal() {
+        let decimal: rust_decimal::Decimal = "-12345.678900".parse().unwrap();
+        let column = Column::from_value("price".to_string(), &decimal);
+        assert_eq!(column.get::<String>().as_deref(), Some("-12345.678900"));
+        assert_eq!(column.get::<rust_decimal::Decimal>(), Some(decimal));
+    }
+}
@@ -3,9 +3,9 @@ use std::{mem, string::ToString, sync::Arc};
 use arrow::{
     array::{
         Array, AsArray, BooleanArray, BooleanBufferBuilder, RecordBatch, StringArray,
-        StringBuilder, UInt32Array, UInt32Builder,
+        StringBuilder, UInt64Array, UInt64Builder,
     },
-    datatypes::{DataType, Field, Schema, UInt32Type},
+    datatypes::{DataType, Field, Schema, UInt64Type},
 };
 use once_cell::sync::Lazy;
 use parquet::{arrow::ProjectionMask, format::SortingColumn, schema::types::ColumnPath};
@@ -54,7 +54,7 @@ impl Record for String {
         static SCHEMA: Lazy<Arc<Schema>> = Lazy::new(|| {
             Arc::new(Schema::new(vec![
                 Field::new("_null", DataType::Boolean, false),
-                Field::new("_ts", DataType::UInt32, false),
+                Field::new("_ts", DataType::UInt64, false),
                 Field::new(PRIMARY_FIELD_NAME, DataType::Utf8, false),
             ]))
         });
@@ -84,7 +84,7 @@ impl<'r> RecordRef<'r> for &'r str {
     ) -> InternalRecordRef<'r, Self> {
         let ts = record_batch
             .column(1)
-            .as_primitive::<UInt32Type>()
+            .as_primitive::<UInt64Type>()
             .value(offset)
             .into();
         let vstring = record_batch.column(2).as_string::<i32>().value(offset);
@@ -97,7 +97,7 @@ impl<'r> RecordRef<'r> for &'r str {
 #[derive(Debug)]
 pub struct StringColumns {
     _null: Arc<BooleanArray>,
-    _ts: Arc<UInt32Array>,
+    _ts: Arc<UInt64Array>,
     string: Arc<StringArray>,
 
     record_batch: RecordBatch,
@@ -111,7 +111,7 @@ impl ArrowArrays for StringColumns {
     fn builder(_schema: &Arc<Schema>, capacity: usize) -> Self::Builder {
         StringColumnsBuilder {
             _null: BooleanBufferBuilder::new(capacity),
-            _ts: UInt32Builder::with_capacity(capacity),
+            _ts: UInt64Builder::with_capacity(capacity),
             string: StringBuilder::with_capacity(capacity, 0),
         }
     }
@@ -140,7 +140,7 @@ impl ArrowArrays for StringColumns {
 #[derive(Debug)]
 pub struct StringColumnsBuilder {
     _null: BooleanBufferBuilder,
-    _ts: UInt32Builder,
+    _ts: UInt64Builder,
     string: StringBuilder,
 }
 
@@ -0,0 +1,430 @@
+use std::{
+    collections::BTreeSet,
+    io::{self, Cursor},
+    ops::Bound,
+    pin::pin,
+    sync::{Arc, Mutex},
+};
+
+use arrow::error::ArrowError;
+use async_stream::stream;
+use flume::{unbounded, Receiver, Sender};
+use fusio::{fs::OpenOptions, path::Path, DynFs};
+use futures_core::Stream;
+use futures_util::StreamExt;
+
+pub use crate::wal::log::LogType;
+use crate::{
+    fs::{self, parse_file_id, FileId, FileType},
+    properties::RangeChecksum,
+    range_tombstone::RangeTombstone,
+    record::{Key, Record},
+    serdes::{BytesReader, Encode},
+    timestamp::{Timestamp, Timestamped},
+    version::TransactionTs,
+    wal::{WalEntry, WalFile},
+    DbError, Scan, DB,
+};
+
+/// Log sequence number, the position of an entry in the WAL.
+///
+/// WALs are ordered by their ids, so the numbers increase in the order the entries are written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Lsn {
+    pub wal_id: FileId,
+    /// index of the entry in the WAL
+    pub index: u64,
+}
+
+/// A decoded WAL entry reported by [`DB::tail_wal`](crate::DB::tail_wal).
+#[derive(Debug, PartialEq)]
+pub enum WalRecord<R>
+where
+    R: Record,
+{
+    /// a put, or a delete if `value` is `None`. Records written by a batch or a transaction
+    /// start with [`LogType::First`] and end with [`LogType::Last`].
+    Record {
+        log_type: LogType,
+        key: R::Key,
+        ts: Timestamp,
+        value: Option<R>,
+    },
+    RangeTombstone {
+        lower: Bound<R::Key>,
+        upper: Bound<R::Key>,
+        ts: Timestamp,
+    },
+}
+
+impl<R> From<WalEntry<R>> for WalRecord<R>
+where
+    R: Record,
+{
+    fn from(entry: WalEntry<R>) -> Self {
+        match entry {
+            WalEntry::Record {
+                log_type,
+                key,
+                value,
+            } => WalRecord::Record {
+                log_type,
+                key: key.value,
+                ts: key.ts,
+                value,
+            },
+            WalEntry::RangeTombstone(range_tombstone) => WalRecord::RangeTombstone {
+                lower: range_tombstone.lower,
+                upper: range_tombstone.upper,
+                ts: range_tombstone.ts,
+            },
+        }
+    }
+}
+
+/// subscribers of the entries written to the WAL of the mutable memtable, the entries are sent
+/// encoded as in the WAL
+#[derive(Default)]
+pub(crate) struct WalTailers {
+    senders: Mutex<Vec<Sender<(Lsn, Vec<u8>)>>>,
+}
+
+impl WalTailers {
+    pub(crate) fn subscribe(&self) -> Receiver<(Lsn, Vec<u8>)> {
+        let (tx, rx) = unbounded();
+        self.senders.lock().unwrap().push(tx);
+        rx
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.senders.lock().unwrap().is_empty()
+    }
+
+    pub(crate) async fn send_record<R>(
+        &self,
+        lsn: Lsn,
+        log_ty: LogType,
+        key: Timestamped<<R::Key as Key>::Ref<'_>>,
+        value: Option<R::Ref<'_>>,
+    ) -> Result<(), DbError<R>>
+    where
+        R: Record,
+    {
+        let mut entry = Vec::new();
+        WalFile::<_, R>::new(Cursor::new(&mut entry), lsn.wal_id)
+            .write(log_ty, key, value)
+            .await
+            .map_err(|e| DbError::WalWrite(Box::new(e)))?;
+        self.send(lsn, entry);
+        Ok(())
+    }
+
+    pub(crate) async fn send_range_tombstone<R>(
+        &self,
+        lsn: Lsn,
+        range_tombstone: &RangeTombstone<R::Key>,
+    ) -> Result<(), DbError<R>>
+    where
+        R: Record,
+    {
+        let mut entry = Vec::new();
+        WalFile::<_, R>::new(Cursor::new(&mut entry), lsn.wal_id)
+            .write_range_tombstone(range_tombstone)
+            .await
+            .map_err(|e| DbError::WalWrite(Box::new(e)))?;
+        self.send(lsn, entry);
+        Ok(())
+    }
+
+    /// send to the subscribers, dropping the closed ones
+    fn send(&self, lsn: Lsn, entry: Vec<u8>) {
+        self.senders
+            .lock()
+            .unwrap()
+            .retain(|tx| tx.send((lsn, entry.clone())).is_ok());
+    }
+}
+
+/// decode a single entry sent by [`WalTailers`]
+pub(crate) async fn decode_entry<R>(
+    wal_id: FileId,
+    entry: Vec<u8>,
+) -> Result<WalRecord<R>, DbError<R>>
+where
+    R: Record,
+{
+    let mut wal = WalFile::<_, R>::new(BytesReader::new(entry), wal_id);
+    let mut recover = pin!(wal.recover_buffered());
+    let entry = recover
+        .next()
+        .await
+        .expect("an encoded WAL entry is sent")?;
+
+    Ok(entry.into())
+}
+
+/// read the entries of a WAL file in the first of `dirs` holding it
+pub(crate) async fn read_wal<R>(
+    dirs: &[(&Arc<dyn DynFs>, Path)],
+    wal_id: FileId,
+) -> Result<Vec<WalRecord<R>>, DbError<R>>
+where
+    R: Record,
+{
+    let filename = format!("{}.{}", wal_id, FileType::Wal);
+    let mut last_err = None;
+
+    for (wal_fs, dir) in dirs {
+        // the WAL may have been archived since it was listed
+        let file = match wal_fs
+            .open_options(
+                &dir.child(filename.as_str()),
+                OpenOptions::default().read(true),
+            )
+            .await
+        {
+            Ok(file) => file,
+            Err(err) => {
+                last_err = Some(err);
+                continue;
+            }
+        };
+        let mut wal = WalFile::<_, R>::new(Cursor::new(file), wal_id);
+        let mut recover = pin!(wal.recover());
+        let mut records = Vec::new();
+
+        while let Some(entry) = recover.next().await {
+            records.push(entry?.into());
+        }
+        return Ok(records);
+    }
+    Err(last_err.expect("WAL directories are given").into())
+}
+
+impl<R> DB<R>
+where
+    R: Record + Send + Sync,
+    R::Columns: Send + Sync,
+{
+    /// subscribe to the entries of the WAL after the position `after`, or from the oldest WAL kept
+    /// if `None`, e.g. to ship the changes to a follower. The entries already written are read
+    /// from the WAL files, which are removed once flushed into SSTs unless archived by
+    /// [`DbOption::wal_archive`], and are followed by the entries written from now on.
+    pub async fn tail_wal(
+        &self,
+        after: Option<Lsn>,
+    ) -> Result<impl Stream<Item = Result<(Lsn, WalRecord<R>), DbError<R>>> + '_, DbError<R>> {
+        let (live, next) = {
+            // block writes, so that every entry is either flushed or sent to the subscription
+            let schema = self.schema.write().await;
+            let next =
+                schema.mutable.next_lsn().await.ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::Unsupported, "the WAL is disabled")
+                })?;
+            schema.flush_wal().await?;
+
+            (schema.mutable.tailers.subscribe(), next)
+        };
+        let option = self.version_set.current().await.option().clone();
+        let wal_dir_path = option.wal_dir_path();
+        let mut wal_dirs = vec![(self.manager.get_fs(&wal_dir_path), wal_dir_path)];
+        if let Some(archive_path) = option.wal_archive_path() {
+            wal_dirs.push((self.manager.get_fs(archive_path), archive_path.clone()));
+        }
+        let mut wal_ids = BTreeSet::new();
+        for (wal_fs, dir) in wal_dirs.iter() {
+            for path in fs::list_files(wal_fs, dir, FileType::Wal).await? {
+                wal_ids.extend(parse_file_id(&path, FileType::Wal)?);
+            }
+        }
+
+        Ok(stream! {
+            for wal_id in wal_ids {
+                // later WALs are created after subscribing
+                if wal_id > next.wal_id || after.is_some_and(|after| wal_id < after.wal_id) {
+                    continue;
+                }
+                let records = read_wal(&wal_dirs, wal_id).await?;
+                for (index, record) in records.into_iter().enumerate() {
+                    let lsn = Lsn { wal_id, index: index as u64 };
+                    if lsn >= next {
+                        break;
+                    }
+                    if !after.is_some_and(|after| lsn <= after) {
+                        yield Ok((lsn, record));
+                    }
+                }
+            }
+            while let Ok((lsn, entry)) = live.recv_async().await {
+                yield decode_entry(lsn.wal_id, entry)
+                    .await
+                    .map(|record| (lsn, record));
+            }
+        })
+    }
+
+    /// checksum the latest version of every live record in the `range`, to compare the range
+    /// with the one of another copy of the database, e.g. a replica or a restored backup, without
+    /// moving the records. The timestamps are left out, as a copy may write the same records at
+    /// others
+    pub async fn checksum_range(
+        &self,
+        range: (Bound<&R::Key>, Bound<&R::Key>),
+    ) -> Result<RangeChecksum, DbError<R>> {
+        let schema = self.schema.read().await;
+        let current = self.version_set.current().await;
+        let mut scan = Scan::new(
+            &schema,
+            &self.manager,
+            range,
+            self.version_set.load_ts(),
+            &*current,
+            None,
+            self.parquet_lru.clone(),
+        )
+        .take()
+        .await?;
+
+        let mut hasher = crc32fast::Hasher::new();
+        let mut records = 0;
+        let mut bytes = Vec::new();
+        while let Some(entry) = scan.next().await.transpose()? {
+            let Some(value) = entry.value() else {
+                continue;
+            };
+            let key = entry.key();
+            bytes.clear();
+            let mut writer = Cursor::new(&mut bytes);
+            key.value
+                .encode(&mut writer)
+                .await
+                .map_err(|err| DbError::Arrow(ArrowError::ExternalError(Box::new(err))))?;
+            value
+                .encode(&mut writer)
+                .await
+                .map_err(|err| DbError::Arrow(ArrowError::ExternalError(Box::new(err))))?;
+            hasher.update(&bytes);
+            records += 1;
+        }
+
+        Ok(RangeChecksum {
+            records,
+            checksum: hasher.finalize(),
+        })
+    }
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod tests {
+    use std::ops::Bound;
+
+    use fusio::path::Path;
+    use futures_util::StreamExt;
+    use tempfile::TempDir;
+
+    use super::{LogType, WalRecord};
+    use crate::{
+        executor::tokio::TokioExecutor,
+        tests::{test_items, Test},
+        DbOption, DB,
+    };
+
+    #[tokio::test]
+    async fn tail_wal() {
+        let temp_dir = TempDir::new().unwrap();
+        let option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap());
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+
+        let items = test_items();
+        db.insert(items[0].clone()).await.unwrap();
+        db.insert_batch(items[1..3].iter().cloned()).await.unwrap();
+
+        let mut tail = Box::pin(db.tail_wal(None).await.unwrap());
+        db.remove(items[0].vstring.clone()).await.unwrap();
+        db.delete_range((Bound::Included(&items[1].vstring), Bound::Unbounded))
+            .await
+            .unwrap();
+
+        let mut lsns = Vec::new();
+        for (i, item) in items[..3].iter().enumerate() {
+            let (lsn, record) = tail.next().await.unwrap().unwrap();
+            let expected_log_type = [LogType::Full, LogType::First, LogType::Last][i];
+            assert!(matches!(
+                record,
+                WalRecord::Record { log_type, key, value: Some(value), .. }
+                    if log_type == expected_log_type && key == item.vstring && value == *item
+            ));
+            lsns.push(lsn);
+        }
+        let (lsn, record) = tail.next().await.unwrap().unwrap();
+        assert!(matches!(
+            record,
+            WalRecord::Record { key, value: None, .. } if key == items[0].vstring
+        ));
+        lsns.push(lsn);
+        let (lsn, record) = tail.next().await.unwrap().unwrap();
+        assert!(matches!(
+            record,
+            WalRecord::RangeTombstone {
+                upper: Bound::Unbounded,
+                ..
+            }
+        ));
+        lsns.push(lsn);
+        assert!(lsns.windows(2).all(|pair| pair[0] < pair[1]));
+
+        // catch up after a position
+        let mut tail = Box::pin(db.tail_wal(Some(lsns[1])).await.unwrap());
+        for lsn in &lsns[2..] {
+            assert_eq!(tail.next().await.unwrap().unwrap().0, *lsn);
+        }
+    }
+
+    #[tokio::test]
+    async fn checksum_range() {
+        let (primary_dir, replica_dir) = (TempDir::new().unwrap(), TempDir::new().unwrap());
+        let primary: DB<Test> = DB::new(
+            DbOption::from(Path::from_filesystem_path(primary_dir.path()).unwrap()),
+            TokioExecutor::new(),
+        )
+        .await
+        .unwrap();
+        let replica: DB<Test> = DB::new(
+            DbOption::from(Path::from_filesystem_path(replica_dir.path()).unwrap()),
+            TokioExecutor::new(),
+        )
+        .await
+        .unwrap();
+        // the replica writes the records at other timestamps
+        replica.insert(test_items()[0].clone()).await.unwrap();
+        replica
+            .remove(test_items()[0].vstring.clone())
+            .await
+            .unwrap();
+        for item in test_items() {
+            primary.insert(item.clone()).await.unwrap();
+            replica.insert(item).await.unwrap();
+        }
+        // flushed on one side only
+        primary.flush().await.unwrap();
+
+        let all = (Bound::Unbounded, Bound::Unbounded);
+        let checksum = primary.checksum_range(all).await.unwrap();
+        assert_eq!(checksum.records, 40);
+        assert_eq!(replica.checksum_range(all).await.unwrap(), checksum);
+
+        let (lower, upper) = ("1".to_string(), "2".to_string());
+        let range = (Bound::Included(&lower), Bound::Excluded(&upper));
+        let range_checksum = primary.checksum_range(range).await.unwrap();
+        assert_eq!(range_checksum.records, 11);
+        assert_ne!(range_checksum, checksum);
+
+        primary.remove("15".to_string()).await.unwrap();
+        replica.remove("16".to_string()).await.unwrap();
+        assert_eq!(primary.checksum_range(range).await.unwrap().records, 10);
+        assert_ne!(
+            primary.checksum_range(range).await.unwrap(),
+            replica.checksum_range(range).await.unwrap()
+        );
+    }
+}
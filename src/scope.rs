@@ -1,10 +1,21 @@
-use std::ops::Bound;
+use std::{ops::Bound, sync::Arc};
 
+use arrow::{
+    array::{Array, ArrayRef, AsArray, RecordBatch},
+    compute::{sort_to_indices, SortOptions},
+    datatypes::Schema,
+};
 use fusio::{SeqRead, Write};
+use parquet::{
+    arrow::{arrow_reader::statistics::StatisticsConverter, arrow_to_parquet_schema},
+    errors::Result as ParquetResult,
+    file::metadata::RowGroupMetaData,
+    format::FileMetaData,
+};
 
 use crate::{
     fs::FileId,
-    serdes::{Decode, Encode},
+    serdes::{decode_with, encode_with, Decode, Encode},
 };
 
 #[derive(Debug, Eq, PartialEq)]
@@ -13,6 +24,119 @@ pub(crate) struct Scope<K> {
     pub(crate) max: K,
     pub(crate) gen: FileId,
     pub(crate) wal_ids: Option<Vec<FileId>>,
+    /// recorded in the version edit adding the table, `None` for the tables added before
+    pub(crate) stats: Option<TableStats>,
+}
+
+/// Statistics of an SST kept in the version, so that gets, pruning and compaction picking can
+/// use them without opening the file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct TableStats {
+    pub(crate) rows: u64,
+    /// the rows removing their key
+    pub(crate) tombstones: u64,
+    /// the compressed bytes of its row groups, the size of the file without its footer
+    pub(crate) bytes: u64,
+    /// the statistics of each column of the record, in the order of its schema
+    pub(crate) columns: Vec<ColumnStats>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct ColumnStats {
+    /// the smallest value in the plain encoding of parquet, `None` if a row group has no
+    /// statistics for the column
+    pub(crate) min: Option<Vec<u8>>,
+    pub(crate) max: Option<Vec<u8>>,
+    pub(crate) null_count: Option<u64>,
+}
+
+impl TableStats {
+    /// the rows of `batch` removing their key, marked in its `_null` column
+    pub(crate) fn tombstones(batch: &RecordBatch) -> u64 {
+        batch.column(0).as_boolean().true_count() as u64
+    }
+
+    /// the statistics of a table written with `schema`, from the footer returned by its writer
+    pub(crate) fn new(
+        schema: &Schema,
+        metadata: &FileMetaData,
+        tombstones: u64,
+    ) -> ParquetResult<Self> {
+        let parquet_schema = Arc::new(arrow_to_parquet_schema(schema)?);
+        let row_groups = metadata
+            .row_groups
+            .iter()
+            .map(|row_group| {
+                RowGroupMetaData::from_thrift(parquet_schema.clone(), row_group.clone())
+            })
+            .collect::<ParquetResult<Vec<_>>>()?;
+
+        // the columns of the record follow `_null` and `_ts`
+        let columns = schema
+            .fields()
+            .iter()
+            .skip(2)
+            .map(|field| {
+                let converter =
+                    StatisticsConverter::try_new(field.name(), schema, &parquet_schema)?;
+                let Some(column) = converter.parquet_column_index() else {
+                    return Ok(ColumnStats::default());
+                };
+                let bytes = |row_group: Option<usize>, min: bool| {
+                    let statistics = row_groups[row_group?].column(column).statistics()?;
+                    if min {
+                        statistics.min_bytes_opt()
+                    } else {
+                        statistics.max_bytes_opt()
+                    }
+                    .map(<[u8]>::to_vec)
+                };
+                let null_counts = converter.row_group_null_counts(&row_groups)?;
+
+                Ok(ColumnStats {
+                    min: bytes(
+                        extreme(&converter.row_group_mins(&row_groups)?, false)?,
+                        true,
+                    ),
+                    max: bytes(
+                        extreme(&converter.row_group_maxes(&row_groups)?, true)?,
+                        false,
+                    ),
+                    null_count: (null_counts.null_count() == 0)
+                        .then(|| null_counts.values().iter().sum()),
+                })
+            })
+            .collect::<ParquetResult<_>>()?;
+
+        Ok(TableStats {
+            rows: metadata.num_rows as u64,
+            tombstones,
+            bytes: metadata
+                .row_groups
+                .iter()
+                .map(|row_group| {
+                    row_group
+                        .total_compressed_size
+                        .unwrap_or(row_group.total_byte_size) as u64
+                })
+                .sum(),
+            columns,
+        })
+    }
+}
+
+/// the row group holding the smallest value, or the largest one if `descending`, `None` if a
+/// row group has no statistics
+fn extreme(values: &ArrayRef, descending: bool) -> ParquetResult<Option<usize>> {
+    if values.null_count() > 0 {
+        return Ok(None);
+    }
+    let options = SortOptions {
+        descending,
+        nulls_first: false,
+    };
+    let indices = sort_to_indices(values, Some(options), Some(1))?;
+    Ok(indices.values().first().map(|&index| index as usize))
 }
 
 impl<K> Clone for Scope<K>
@@ -25,6 +149,7 @@ where
             max: self.max.clone(),
             gen: self.gen,
             wal_ids: self.wal_ids.clone(),
+            stats: self.stats.clone(),
         }
     }
 }
@@ -72,6 +197,20 @@ where
         }
     }
 
+    pub(crate) fn is_contained_in(&self, range: (Bound<&K>, Bound<&K>)) -> bool {
+        let after_lower = match range.0 {
+            Bound::Included(start) => start <= &self.min,
+            Bound::Excluded(start) => start < &self.min,
+            Bound::Unbounded => true,
+        };
+        let before_upper = match range.1 {
+            Bound::Included(end) => &self.max <= end,
+            Bound::Excluded(end) => &self.max < end,
+            Bound::Unbounded => true,
+        };
+        after_lower && before_upper
+    }
+
     pub(crate) fn gen(&self) -> FileId {
         self.gen
     }
@@ -87,8 +226,32 @@ where
     where
         W: Write,
     {
-        self.min.encode(writer).await?;
-        self.max.encode(writer).await?;
+        self.encode_as(writer, false).await
+    }
+
+    async fn encode_compact<W>(&self, writer: &mut W) -> Result<(), Self::Error>
+    where
+        W: Write,
+    {
+        self.encode_as(writer, true).await
+    }
+
+    fn size(&self) -> usize {
+        // ProcessUniqueId: usize + u64
+        self.min.size() + self.max.size() + 16
+    }
+}
+
+impl<K> Scope<K>
+where
+    K: Encode + Sync,
+{
+    async fn encode_as<W>(&self, writer: &mut W, compact: bool) -> Result<(), K::Error>
+    where
+        W: Write,
+    {
+        encode_with(&self.min, writer, compact).await?;
+        encode_with(&self.max, writer, compact).await?;
 
         let (result, _) = writer.write_all(&self.gen.to_bytes()[..]).await;
         result?;
@@ -99,7 +262,7 @@ where
             }
             Some(ids) => {
                 1u8.encode(writer).await?;
-                (ids.len() as u32).encode(writer).await?;
+                encode_with(&(ids.len() as u32), writer, compact).await?;
                 for id in ids {
                     let (result, _) = writer.write_all(&id.to_bytes()[..]).await;
                     result?;
@@ -108,11 +271,6 @@ where
         }
         Ok(())
     }
-
-    fn size(&self) -> usize {
-        // ProcessUniqueId: usize + u64
-        self.min.size() + self.max.size() + 16
-    }
 }
 
 impl<K> Decode for Scope<K>
@@ -122,9 +280,22 @@ where
     type Error = <K as Decode>::Error;
 
     async fn decode<R: SeqRead>(reader: &mut R) -> Result<Self, Self::Error> {
+        Self::decode_as(reader, false).await
+    }
+
+    async fn decode_compact<R: SeqRead>(reader: &mut R) -> Result<Self, Self::Error> {
+        Self::decode_as(reader, true).await
+    }
+}
+
+impl<K> Scope<K>
+where
+    K: Decode,
+{
+    async fn decode_as<R: SeqRead>(reader: &mut R, compact: bool) -> Result<Self, K::Error> {
         let mut buf = [0u8; 16];
-        let min = K::decode(reader).await?;
-        let max = K::decode(reader).await?;
+        let min = decode_with(reader, compact).await?;
+        let max = decode_with(reader, compact).await?;
 
         let gen = {
             let (result, _) = reader.read_exact(buf.as_mut_slice()).await;
@@ -134,7 +305,7 @@ where
         let wal_ids = match u8::decode(reader).await? {
             0 => None,
             1 => {
-                let len = u32::decode(reader).await? as usize;
+                let len = decode_with::<u32, _>(reader, compact).await? as usize;
                 let mut ids = Vec::with_capacity(len);
 
                 for _ in 0..len {
@@ -152,6 +323,116 @@ where
             max,
             gen,
             wal_ids,
+            stats: None,
+        })
+    }
+}
+
+async fn encode_bytes<W>(
+    bytes: &Option<Vec<u8>>,
+    writer: &mut W,
+    compact: bool,
+) -> Result<(), fusio::Error>
+where
+    W: Write,
+{
+    match bytes {
+        None => 0u8.encode(writer).await,
+        Some(bytes) => {
+            1u8.encode(writer).await?;
+            encode_with(bytes, writer, compact).await
+        }
+    }
+}
+
+async fn decode_bytes<R>(reader: &mut R, compact: bool) -> Result<Option<Vec<u8>>, fusio::Error>
+where
+    R: SeqRead,
+{
+    Ok(match u8::decode(reader).await? {
+        0 => None,
+        _ => Some(decode_with(reader, compact).await?),
+    })
+}
+
+impl Encode for TableStats {
+    type Error = fusio::Error;
+
+    async fn encode<W>(&self, writer: &mut W) -> Result<(), Self::Error>
+    where
+        W: Write,
+    {
+        self.encode_as(writer, false).await
+    }
+
+    async fn encode_compact<W>(&self, writer: &mut W) -> Result<(), Self::Error>
+    where
+        W: Write,
+    {
+        self.encode_as(writer, true).await
+    }
+
+    fn size(&self) -> usize {
+        let bytes = |bytes: &Option<Vec<u8>>| 1 + bytes.as_ref().map_or(0, Encode::size);
+        28 + self
+            .columns
+            .iter()
+            .map(|column| bytes(&column.min) + bytes(&column.max) + 8)
+            .sum::<usize>()
+    }
+}
+
+impl Decode for TableStats {
+    type Error = fusio::Error;
+
+    async fn decode<R: SeqRead>(reader: &mut R) -> Result<Self, Self::Error> {
+        Self::decode_as(reader, false).await
+    }
+
+    async fn decode_compact<R: SeqRead>(reader: &mut R) -> Result<Self, Self::Error> {
+        Self::decode_as(reader, true).await
+    }
+}
+
+impl TableStats {
+    async fn encode_as<W>(&self, writer: &mut W, compact: bool) -> Result<(), fusio::Error>
+    where
+        W: Write,
+    {
+        encode_with(&self.rows, writer, compact).await?;
+        encode_with(&self.tombstones, writer, compact).await?;
+        encode_with(&self.bytes, writer, compact).await?;
+        encode_with(&(self.columns.len() as u32), writer, compact).await?;
+        for column in &self.columns {
+            encode_bytes(&column.min, writer, compact).await?;
+            encode_bytes(&column.max, writer, compact).await?;
+            // `u64::MAX` for an unknown count
+            encode_with(&column.null_count.unwrap_or(u64::MAX), writer, compact).await?;
+        }
+        Ok(())
+    }
+
+    async fn decode_as<R: SeqRead>(reader: &mut R, compact: bool) -> Result<Self, fusio::Error> {
+        let rows = decode_with(reader, compact).await?;
+        let tombstones = decode_with(reader, compact).await?;
+        let bytes = decode_with(reader, compact).await?;
+        let len = decode_with::<u32, _>(reader, compact).await? as usize;
+        let mut columns = Vec::with_capacity(len);
+        for _ in 0..len {
+            let min = decode_bytes(reader, compact).await?;
+            let max = decode_bytes(reader, compact).await?;
+            let null_count: u64 = decode_with(reader, compact).await?;
+            columns.push(ColumnStats {
+                min,
+                max,
+                null_count: (null_count != u64::MAX).then_some(null_count),
+            });
+        }
+        Ok(TableStats {
+            rows,
+            tombstones,
+            bytes,
+            columns,
         })
     }
 }
@@ -170,6 +451,7 @@ mod test {
             max: 200,
             gen: FileId::new(),
             wal_ids: None,
+            stats: None,
         };
 
         // test out of range
@@ -224,4 +506,22 @@ mod test {
             assert!(scope.meets_range((Bound::Excluded(&100), Bound::Excluded(&200))));
         }
     }
+
+    #[test]
+    fn test_is_contained_in() {
+        let scope = Scope {
+            min: 100,
+            max: 200,
+            gen: FileId::new(),
+            wal_ids: None,
+            stats: None,
+        };
+
+        assert!(scope.is_contained_in((Bound::Unbounded, Bound::Unbounded)));
+        assert!(scope.is_contained_in((Bound::Included(&100), Bound::Included(&200))));
+        assert!(scope.is_contained_in((Bound::Excluded(&99), Bound::Excluded(&201))));
+        assert!(!scope.is_contained_in((Bound::Excluded(&100), Bound::Included(&200))));
+        assert!(!scope.is_contained_in((Bound::Included(&100), Bound::Excluded(&200))));
+        assert!(!scope.is_contained_in((Bound::Included(&150), Bound::Unbounded)));
+    }
 }
@@ -2,7 +2,7 @@ use std::sync::Arc;
 
 use fusio::{SeqRead, Write};
 
-use super::{Decode, Encode};
+use super::{BytesReader, Decode, Encode};
 
 impl<T> Decode for Arc<T>
 where
@@ -16,6 +16,17 @@ where
     {
         Ok(Arc::from(T::decode(reader).await?))
     }
+
+    async fn decode_compact<R>(reader: &mut R) -> Result<Self, Self::Error>
+    where
+        R: SeqRead,
+    {
+        Ok(Arc::from(T::decode_compact(reader).await?))
+    }
+
+    async fn decode_buffered(reader: &mut BytesReader, compact: bool) -> Result<Self, Self::Error> {
+        Ok(Arc::from(T::decode_buffered(reader, compact).await?))
+    }
 }
 
 impl<T> Encode for Arc<T>
@@ -31,6 +42,13 @@ where
         self.as_ref().encode(writer).await
     }
 
+    async fn encode_compact<W>(&self, writer: &mut W) -> Result<(), Self::Error>
+    where
+        W: Write,
+    {
+        self.as_ref().encode_compact(writer).await
+    }
+
     fn size(&self) -> usize {
         Encode::size(self.as_ref())
     }
@@ -1,7 +1,10 @@
 use bytes::Bytes;
 use fusio::{IoBuf, SeqRead, Write};
 
-use crate::serdes::{Decode, Encode};
+use crate::serdes::{
+    varint::{decode_varint, encode_varint},
+    BytesReader, Decode, Encode,
+};
 
 impl Encode for &[u8] {
     type Error = fusio::Error;
@@ -14,6 +17,14 @@ impl Encode for &[u8] {
         Ok(())
     }
 
+    async fn encode_compact<W: Write>(&self, writer: &mut W) -> Result<(), Self::Error> {
+        encode_varint(self.len() as u64, writer).await?;
+        let (result, _) = writer.write_all(*self).await;
+        result?;
+
+        Ok(())
+    }
+
     fn size(&self) -> usize {
         self.len()
     }
@@ -30,6 +41,14 @@ impl Encode for Bytes {
         Ok(())
     }
 
+    async fn encode_compact<W: Write>(&self, writer: &mut W) -> Result<(), Self::Error> {
+        encode_varint(self.len() as u64, writer).await?;
+        let (result, _) = writer.write_all(self.as_slice()).await;
+        result?;
+
+        Ok(())
+    }
+
     fn size(&self) -> usize {
         self.len()
     }
@@ -45,6 +64,25 @@ impl Decode for Bytes {
 
         Ok(buf.as_bytes())
     }
+
+    async fn decode_compact<R: SeqRead>(reader: &mut R) -> Result<Self, Self::Error> {
+        let len = decode_varint(reader).await?;
+        let (result, buf) = reader.read_exact(vec![0u8; len as usize]).await;
+        result?;
+
+        Ok(buf.as_bytes())
+    }
+
+    /// shares the buffer of `reader`
+    async fn decode_buffered(reader: &mut BytesReader, compact: bool) -> Result<Self, Self::Error> {
+        let len = if compact {
+            decode_varint(reader).await?
+        } else {
+            u32::decode(reader).await?.into()
+        };
+
+        reader.read_bytes(len as usize)
+    }
 }
 
 #[cfg(test)]
@@ -54,7 +92,7 @@ mod tests {
     use bytes::Bytes;
     use tokio::io::AsyncSeekExt;
 
-    use crate::serdes::{Decode, Encode};
+    use crate::serdes::{encode_with, BytesReader, Decode, Encode};
 
     #[tokio::test]
     async fn test_encode_decode() {
@@ -70,4 +108,27 @@ mod tests {
 
         assert_eq!(source, decoded);
     }
+
+    #[tokio::test]
+    async fn test_decode_buffered() {
+        let source = Bytes::from_static(b"hello! Tonbo");
+
+        let mut bytes = Vec::new();
+        for compact in [false, true] {
+            encode_with(&source, &mut Cursor::new(&mut bytes), compact)
+                .await
+                .unwrap();
+            let buffer = Bytes::from(bytes.split_off(0));
+            let mut reader = BytesReader::new(buffer.clone());
+            let decoded = Bytes::decode_buffered(&mut reader, compact).await.unwrap();
+
+            assert_eq!(source, decoded);
+            assert_eq!(reader.remaining(), 0);
+            // the blob is a slice of the buffer it was read from
+            assert_eq!(
+                decoded.as_ptr(),
+                buffer[buffer.len() - source.len()..].as_ptr()
+            );
+        }
+    }
 }
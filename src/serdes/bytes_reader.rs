@@ -0,0 +1,71 @@
+use std::io;
+
+use fusio::{Error, IoBufMut, SeqRead};
+use tokio_util::bytes::Bytes;
+
+/// A [`SeqRead`] over a buffer held in memory, out of which [`Decode::decode_buffered`] takes
+/// the strings and blobs without copying them into buffers of their own.
+///
+/// [`Decode::decode_buffered`]: super::Decode::decode_buffered
+#[derive(Debug, Clone)]
+pub struct BytesReader {
+    bytes: Bytes,
+    position: usize,
+}
+
+impl BytesReader {
+    pub fn new(bytes: impl Into<Bytes>) -> Self {
+        Self {
+            bytes: bytes.into(),
+            position: 0,
+        }
+    }
+
+    /// the bytes read so far
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.bytes.len() - self.position
+    }
+
+    /// the next `len` bytes, sharing the buffer
+    pub fn read_bytes(&mut self, len: usize) -> Result<Bytes, Error> {
+        let start = self.advance(len)?;
+        Ok(self.bytes.slice(start..self.position))
+    }
+
+    /// the next `len` bytes
+    pub fn read_slice(&mut self, len: usize) -> Result<&[u8], Error> {
+        let start = self.advance(len)?;
+        Ok(&self.bytes[start..self.position])
+    }
+
+    /// move past the next `len` bytes, returns where they start
+    fn advance(&mut self, len: usize) -> Result<usize, Error> {
+        if len > self.remaining() {
+            return Err(Error::Io(io::ErrorKind::UnexpectedEof.into()));
+        }
+        let start = self.position;
+        self.position += len;
+        Ok(start)
+    }
+
+    /// the bytes read since `start`
+    pub(crate) fn read_since(&self, start: usize) -> &[u8] {
+        &self.bytes[start..self.position]
+    }
+}
+
+impl SeqRead for BytesReader {
+    async fn read_exact<B: IoBufMut>(&mut self, mut buf: B) -> (Result<(), Error>, B) {
+        match self.read_slice(buf.bytes_init()) {
+            Ok(slice) => {
+                buf.as_slice_mut().copy_from_slice(slice);
+                (Ok(()), buf)
+            }
+            Err(err) => (Err(err), buf),
+        }
+    }
+}
@@ -0,0 +1,126 @@
+use std::mem::size_of;
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use fusio::{SeqRead, Write};
+
+use super::{decode_with, encode_with, Decode, Encode};
+
+/// the seconds since the epoch followed by the nanoseconds into the second, as an `i64` and a
+/// `u32`
+impl Encode for DateTime<Utc> {
+    type Error = fusio::Error;
+
+    async fn encode<W: Write>(&self, writer: &mut W) -> Result<(), Self::Error> {
+        encode_timestamp(self, writer, false).await
+    }
+
+    async fn encode_compact<W: Write>(&self, writer: &mut W) -> Result<(), Self::Error> {
+        encode_timestamp(self, writer, true).await
+    }
+
+    fn size(&self) -> usize {
+        size_of::<i64>() + size_of::<u32>()
+    }
+}
+
+impl Decode for DateTime<Utc> {
+    type Error = fusio::Error;
+
+    async fn decode<R: SeqRead>(reader: &mut R) -> Result<Self, Self::Error> {
+        decode_timestamp(reader, false).await
+    }
+
+    async fn decode_compact<R: SeqRead>(reader: &mut R) -> Result<Self, Self::Error> {
+        decode_timestamp(reader, true).await
+    }
+}
+
+/// encoded as the [`DateTime<Utc>`] of the same date and time
+impl Encode for NaiveDateTime {
+    type Error = fusio::Error;
+
+    async fn encode<W: Write>(&self, writer: &mut W) -> Result<(), Self::Error> {
+        encode_timestamp(&self.and_utc(), writer, false).await
+    }
+
+    async fn encode_compact<W: Write>(&self, writer: &mut W) -> Result<(), Self::Error> {
+        encode_timestamp(&self.and_utc(), writer, true).await
+    }
+
+    fn size(&self) -> usize {
+        size_of::<i64>() + size_of::<u32>()
+    }
+}
+
+impl Decode for NaiveDateTime {
+    type Error = fusio::Error;
+
+    async fn decode<R: SeqRead>(reader: &mut R) -> Result<Self, Self::Error> {
+        Ok(decode_timestamp(reader, false).await?.naive_utc())
+    }
+
+    async fn decode_compact<R: SeqRead>(reader: &mut R) -> Result<Self, Self::Error> {
+        Ok(decode_timestamp(reader, true).await?.naive_utc())
+    }
+}
+
+async fn encode_timestamp<W: Write>(
+    timestamp: &DateTime<Utc>,
+    writer: &mut W,
+    compact: bool,
+) -> Result<(), fusio::Error> {
+    encode_with(&timestamp.timestamp(), writer, compact).await?;
+    encode_with(&timestamp.timestamp_subsec_nanos(), writer, compact).await
+}
+
+async fn decode_timestamp<R: SeqRead>(
+    reader: &mut R,
+    compact: bool,
+) -> Result<DateTime<Utc>, fusio::Error> {
+    let secs = decode_with::<i64, _>(reader, compact).await?;
+    let nanos = decode_with::<u32, _>(reader, compact).await?;
+
+    DateTime::from_timestamp(secs, nanos)
+        .ok_or_else(|| fusio::Error::Other("timestamp out of range".into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use chrono::{DateTime, NaiveDateTime, Utc};
+    use tokio::io::AsyncSeekExt;
+
+    use crate::serdes::{Decode, Encode};
+
+    #[tokio::test]
+    async fn test_encode_decode() {
+        let source = DateTime::<Utc>::from_timestamp(-1_700_000_000, 123_456_789).unwrap();
+
+        let mut bytes = Vec::new();
+        let mut cursor = Cursor::new(&mut bytes);
+
+        source.encode(&mut cursor).await.unwrap();
+        source.encode_compact(&mut cursor).await.unwrap();
+        source.naive_utc().encode(&mut cursor).await.unwrap();
+
+        cursor.seek(std::io::SeekFrom::Start(0)).await.unwrap();
+        assert_eq!(DateTime::<Utc>::decode(&mut cursor).await.unwrap(), source);
+        assert_eq!(
+            DateTime::<Utc>::decode_compact(&mut cursor).await.unwrap(),
+            source
+        );
+        assert_eq!(
+            NaiveDateTime::decode(&mut cursor).await.unwrap(),
+            source.naive_utc()
+        );
+
+        // nanoseconds past a second are rejected rather than wrapped
+        let mut bytes = Vec::new();
+        let mut cursor = Cursor::new(&mut bytes);
+        0i64.encode(&mut cursor).await.unwrap();
+        2_000_000_000u32.encode(&mut cursor).await.unwrap();
+        cursor.seek(std::io::SeekFrom::Start(0)).await.unwrap();
+        assert!(DateTime::<Utc>::decode(&mut cursor).await.is_err());
+    }
+}
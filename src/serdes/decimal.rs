@@ -0,0 +1,65 @@
+use fusio::{SeqRead, Write};
+use rust_decimal::Decimal;
+
+use super::{Decode, Encode};
+
+/// the 16 bytes of [`Decimal::serialize`]
+impl Encode for Decimal {
+    type Error = fusio::Error;
+
+    async fn encode<W: Write>(&self, writer: &mut W) -> Result<(), Self::Error> {
+        let (result, _) = writer.write_all(&self.serialize()[..]).await;
+        result
+    }
+
+    fn size(&self) -> usize {
+        16
+    }
+}
+
+impl Decode for Decimal {
+    type Error = fusio::Error;
+
+    async fn decode<R: SeqRead>(reader: &mut R) -> Result<Self, Self::Error> {
+        let (result, buf) = reader.read_exact(vec![0u8; 16]).await;
+        result?;
+
+        Ok(Decimal::deserialize(
+            buf.try_into().expect("16 bytes are read"),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Cursor, str::FromStr};
+
+    use rust_decimal::Decimal;
+    use tokio::io::AsyncSeekExt;
+
+    use crate::serdes::{Decode, Encode};
+
+    #[tokio::test]
+    async fn test_encode_decode() {
+        let sources = [
+            Decimal::from_str("-12345.678900").unwrap(),
+            Decimal::MAX,
+            Decimal::ZERO,
+        ];
+
+        let mut bytes = Vec::new();
+        let mut cursor = Cursor::new(&mut bytes);
+
+        for source in &sources {
+            source.encode(&mut cursor).await.unwrap();
+        }
+
+        cursor.seek(std::io::SeekFrom::Start(0)).await.unwrap();
+        for source in sources {
+            let decoded = Decimal::decode(&mut cursor).await.unwrap();
+            assert_eq!(decoded, source);
+            // the scale is kept along with the value
+            assert_eq!(decoded.to_string(), source.to_string());
+        }
+    }
+}
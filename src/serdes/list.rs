@@ -1,6 +1,9 @@
 use fusio::{SeqRead, Write};
 
-use super::{Decode, Encode};
+use super::{
+    varint::{decode_varint, encode_varint},
+    BytesReader, Decode, Encode,
+};
 
 impl Decode for Vec<u8> {
     type Error = fusio::Error;
@@ -17,6 +20,27 @@ impl Decode for Vec<u8> {
 
         Ok(buf)
     }
+
+    async fn decode_compact<R>(reader: &mut R) -> Result<Self, Self::Error>
+    where
+        R: SeqRead,
+    {
+        let len = decode_varint(reader).await?;
+        let (result, buf) = reader.read_exact(vec![0u8; len as usize]).await;
+        result?;
+
+        Ok(buf)
+    }
+
+    async fn decode_buffered(reader: &mut BytesReader, compact: bool) -> Result<Self, Self::Error> {
+        let len = if compact {
+            decode_varint(reader).await?
+        } else {
+            u32::decode(reader).await?.into()
+        };
+
+        Ok(reader.read_slice(len as usize)?.to_vec())
+    }
 }
 
 impl Encode for Vec<u8> {
@@ -33,6 +57,17 @@ impl Encode for Vec<u8> {
         Ok(())
     }
 
+    async fn encode_compact<W>(&self, writer: &mut W) -> Result<(), Self::Error>
+    where
+        W: Write,
+    {
+        encode_varint(self.len() as u64, writer).await?;
+        let (result, _) = writer.write_all(self.as_slice()).await;
+        result?;
+
+        Ok(())
+    }
+
     fn size(&self) -> usize {
         size_of::<u32>() + size_of::<u8>() * self.len()
     }
@@ -2,14 +2,27 @@ mod arc;
 mod boolean;
 #[cfg(feature = "bytes")]
 mod bytes;
+mod bytes_reader;
+#[cfg(feature = "chrono")]
+mod chrono;
+#[cfg(feature = "rust_decimal")]
+mod decimal;
 mod list;
 mod num;
 pub(crate) mod option;
 mod string;
+#[cfg(feature = "time")]
+mod time;
+#[cfg(feature = "uuid")]
+mod uuid;
+pub(crate) mod varint;
 
 use std::future::Future;
 
 use fusio::{MaybeSend, SeqRead, Write};
+pub use tonbo_macros::{Decode, Encode};
+
+pub use self::bytes_reader::BytesReader;
 
 pub trait Encode {
     type Error: From<fusio::Error> + std::error::Error + Send + Sync + 'static;
@@ -21,6 +34,18 @@ pub trait Encode {
     where
         W: Write;
 
+    /// write the value in the compact encoding, with the integers and the lengths as varints,
+    /// read back by [`Decode::decode_compact`]. The same as [`Encode::encode`] unless overridden
+    fn encode_compact<W>(
+        &self,
+        writer: &mut W,
+    ) -> impl Future<Output = Result<(), Self::Error>> + MaybeSend
+    where
+        W: Write,
+    {
+        self.encode(writer)
+    }
+
     fn size(&self) -> usize;
 }
 
@@ -34,6 +59,13 @@ impl<T: Encode + Sync> Encode for &T {
         Encode::encode(*self, writer).await
     }
 
+    async fn encode_compact<W>(&self, writer: &mut W) -> Result<(), Self::Error>
+    where
+        W: Write,
+    {
+        Encode::encode_compact(*self, writer).await
+    }
+
     fn size(&self) -> usize {
         Encode::size(*self)
     }
@@ -45,6 +77,55 @@ pub trait Decode: Sized {
     fn decode<R>(reader: &mut R) -> impl Future<Output = Result<Self, Self::Error>>
     where
         R: SeqRead;
+
+    /// read a value written by [`Encode::encode_compact`]
+    fn decode_compact<R>(reader: &mut R) -> impl Future<Output = Result<Self, Self::Error>>
+    where
+        R: SeqRead,
+    {
+        Self::decode(reader)
+    }
+
+    /// read a value out of a buffer held in memory, in the compact encoding if `compact`, taking
+    /// the strings and blobs from it by [`BytesReader::read_bytes`] rather than reading them
+    /// into buffers of their own. The same as [`Decode::decode`] and [`Decode::decode_compact`]
+    /// unless overridden
+    fn decode_buffered(
+        reader: &mut BytesReader,
+        compact: bool,
+    ) -> impl Future<Output = Result<Self, Self::Error>> {
+        decode_with(reader, compact)
+    }
+}
+
+/// encode `value` in the compact encoding if `compact`
+pub(crate) async fn encode_with<T, W>(
+    value: &T,
+    writer: &mut W,
+    compact: bool,
+) -> Result<(), T::Error>
+where
+    T: Encode + ?Sized,
+    W: Write,
+{
+    if compact {
+        value.encode_compact(writer).await
+    } else {
+        value.encode(writer).await
+    }
+}
+
+/// decode a value in the compact encoding if `compact`
+pub(crate) async fn decode_with<T, R>(reader: &mut R, compact: bool) -> Result<T, T::Error>
+where
+    T: Decode,
+    R: SeqRead,
+{
+    if compact {
+        T::decode_compact(reader).await
+    } else {
+        T::decode(reader).await
+    }
 }
 
 #[cfg(test)]
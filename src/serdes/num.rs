@@ -2,48 +2,82 @@ use std::mem::size_of;
 
 use fusio::{SeqRead, Write};
 
-use super::{Decode, Encode};
+use super::{
+    varint::{decode_varint, encode_varint, out_of_range, unzigzag, zigzag},
+    Decode, Encode,
+};
 
 #[macro_export]
 macro_rules! implement_encode_decode {
     ($struct_name:ident) => {
         impl Encode for $struct_name {
-            type Error = fusio::Error;
+            $crate::implement_encode_decode!(@encode);
+        }
 
-            async fn encode<W: Write>(&self, writer: &mut W) -> Result<(), Self::Error> {
-                let (result, _) = writer.write_all(&self.to_le_bytes()[..]).await;
-                result?;
+        impl Decode for $struct_name {
+            $crate::implement_encode_decode!(@decode);
+        }
+    };
+    // `$to_varint` and `$from_varint` map the integer to the varint of its compact encoding and
+    // back
+    ($struct_name:ident, $to_varint:expr, $from_varint:expr) => {
+        impl Encode for $struct_name {
+            $crate::implement_encode_decode!(@encode);
 
-                Ok(())
+            async fn encode_compact<W: Write>(&self, writer: &mut W) -> Result<(), Self::Error> {
+                encode_varint(($to_varint)(*self), writer).await
             }
+        }
+
+        impl Decode for $struct_name {
+            $crate::implement_encode_decode!(@decode);
 
-            fn size(&self) -> usize {
-                size_of::<Self>()
+            async fn decode_compact<R: SeqRead>(reader: &mut R) -> Result<Self, Self::Error> {
+                ($from_varint)(decode_varint(reader).await?)
             }
         }
+    };
+    (@encode) => {
+        type Error = fusio::Error;
 
-        impl Decode for $struct_name {
-            type Error = fusio::Error;
+        async fn encode<W: Write>(&self, writer: &mut W) -> Result<(), Self::Error> {
+            let (result, _) = writer.write_all(&self.to_le_bytes()[..]).await;
+            result?;
 
-            async fn decode<R: SeqRead>(reader: &mut R) -> Result<Self, Self::Error> {
-                let mut bytes = [0u8; size_of::<Self>()];
-                let (result, _) = reader.read_exact(&mut bytes[..]).await;
-                result?;
+            Ok(())
+        }
 
-                Ok(Self::from_le_bytes(bytes))
-            }
+        fn size(&self) -> usize {
+            size_of::<Self>()
+        }
+    };
+    (@decode) => {
+        type Error = fusio::Error;
+
+        async fn decode<R: SeqRead>(reader: &mut R) -> Result<Self, Self::Error> {
+            let mut bytes = [0u8; size_of::<Self>()];
+            let (result, _) = reader.read_exact(&mut bytes[..]).await;
+            result?;
+
+            Ok(Self::from_le_bytes(bytes))
         }
     };
 }
 
 implement_encode_decode!(i8);
-implement_encode_decode!(i16);
-implement_encode_decode!(i32);
-implement_encode_decode!(i64);
+implement_encode_decode!(i16, |value: i16| zigzag(value.into()), |varint| {
+    i16::try_from(unzigzag(varint)).map_err(out_of_range)
+});
+implement_encode_decode!(i32, |value: i32| zigzag(value.into()), |varint| {
+    i32::try_from(unzigzag(varint)).map_err(out_of_range)
+});
+implement_encode_decode!(i64, zigzag, |varint| Ok(unzigzag(varint)));
 implement_encode_decode!(u8);
-implement_encode_decode!(u16);
-implement_encode_decode!(u32);
-implement_encode_decode!(u64);
+implement_encode_decode!(u16, u64::from, |varint| u16::try_from(varint)
+    .map_err(out_of_range));
+implement_encode_decode!(u32, u64::from, |varint| u32::try_from(varint)
+    .map_err(out_of_range));
+implement_encode_decode!(u64, |value| value, |varint| Ok(varint));
 
 #[cfg(test)]
 mod tests {
@@ -95,4 +129,32 @@ mod tests {
         assert_eq!(source_6, decoded_6);
         assert_eq!(source_7, decoded_7);
     }
+
+    #[tokio::test]
+    async fn test_encode_decode_compact() {
+        let mut bytes = Vec::new();
+        let mut cursor = Cursor::new(&mut bytes);
+
+        16u16.encode_compact(&mut cursor).await.unwrap();
+        u32::MAX.encode_compact(&mut cursor).await.unwrap();
+        300u64.encode_compact(&mut cursor).await.unwrap();
+        (-1i16).encode_compact(&mut cursor).await.unwrap();
+        i32::MIN.encode_compact(&mut cursor).await.unwrap();
+        (-64i64).encode_compact(&mut cursor).await.unwrap();
+
+        cursor.seek(std::io::SeekFrom::Start(0)).await.unwrap();
+        assert_eq!(u16::decode_compact(&mut cursor).await.unwrap(), 16);
+        assert_eq!(u32::decode_compact(&mut cursor).await.unwrap(), u32::MAX);
+        assert_eq!(u64::decode_compact(&mut cursor).await.unwrap(), 300);
+        assert_eq!(i16::decode_compact(&mut cursor).await.unwrap(), -1);
+        assert_eq!(i32::decode_compact(&mut cursor).await.unwrap(), i32::MIN);
+        assert_eq!(i64::decode_compact(&mut cursor).await.unwrap(), -64);
+        assert_eq!(bytes.len(), 1 + 5 + 2 + 1 + 5 + 1);
+
+        let mut bytes = Vec::new();
+        let mut cursor = Cursor::new(&mut bytes);
+        u64::MAX.encode_compact(&mut cursor).await.unwrap();
+        cursor.seek(std::io::SeekFrom::Start(0)).await.unwrap();
+        assert!(u32::decode_compact(&mut cursor).await.is_err());
+    }
 }
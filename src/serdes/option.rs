@@ -3,7 +3,7 @@ use std::io;
 use fusio::{SeqRead, Write};
 use thiserror::Error;
 
-use super::{Decode, Encode};
+use super::{BytesReader, Decode, Encode};
 
 #[derive(Debug, Error)]
 #[error("option encode error")]
@@ -53,6 +53,20 @@ where
         Ok(())
     }
 
+    async fn encode_compact<W>(&self, writer: &mut W) -> Result<(), Self::Error>
+    where
+        W: Write,
+    {
+        match self {
+            None => 0u8.encode(writer).await?,
+            Some(v) => {
+                1u8.encode(writer).await?;
+                v.encode_compact(writer).await.map_err(EncodeError::Inner)?;
+            }
+        }
+        Ok(())
+    }
+
     fn size(&self) -> usize {
         match self {
             None => 1,
@@ -71,7 +85,31 @@ where
         match u8::decode(reader).await? {
             0 => Ok(None),
             1 => Ok(Some(V::decode(reader).await.map_err(DecodeError::Inner)?)),
-            _ => panic!("invalid option tag"),
+            tag => Err(fusio::Error::Other(format!("invalid option tag {}", tag).into()).into()),
+        }
+    }
+
+    async fn decode_compact<R: SeqRead>(reader: &mut R) -> Result<Self, Self::Error> {
+        match u8::decode(reader).await? {
+            0 => Ok(None),
+            1 => Ok(Some(
+                V::decode_compact(reader)
+                    .await
+                    .map_err(DecodeError::Inner)?,
+            )),
+            tag => Err(fusio::Error::Other(format!("invalid option tag {}", tag).into()).into()),
+        }
+    }
+
+    async fn decode_buffered(reader: &mut BytesReader, compact: bool) -> Result<Self, Self::Error> {
+        match u8::decode(reader).await? {
+            0 => Ok(None),
+            1 => Ok(Some(
+                V::decode_buffered(reader, compact)
+                    .await
+                    .map_err(DecodeError::Inner)?,
+            )),
+            tag => Err(fusio::Error::Other(format!("invalid option tag {}", tag).into()).into()),
         }
     }
 }
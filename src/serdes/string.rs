@@ -2,7 +2,10 @@ use std::mem::size_of;
 
 use fusio::{SeqRead, Write};
 
-use super::{Decode, Encode};
+use super::{
+    varint::{decode_varint, encode_varint},
+    BytesReader, Decode, Encode,
+};
 
 impl<'r> Encode for &'r str {
     type Error = fusio::Error;
@@ -18,6 +21,17 @@ impl<'r> Encode for &'r str {
         Ok(())
     }
 
+    async fn encode_compact<W>(&self, writer: &mut W) -> Result<(), Self::Error>
+    where
+        W: Write,
+    {
+        encode_varint(self.len() as u64, writer).await?;
+        let (result, _) = writer.write_all(self.as_bytes()).await;
+        result?;
+
+        Ok(())
+    }
+
     fn size(&self) -> usize {
         size_of::<u16>() + self.len()
     }
@@ -33,6 +47,13 @@ impl Encode for String {
         self.as_str().encode(writer).await
     }
 
+    async fn encode_compact<W>(&self, writer: &mut W) -> Result<(), Self::Error>
+    where
+        W: Write,
+    {
+        self.as_str().encode_compact(writer).await
+    }
+
     fn size(&self) -> usize {
         self.as_str().size()
     }
@@ -48,6 +69,25 @@ impl Decode for String {
 
         Ok(unsafe { String::from_utf8_unchecked(buf.as_slice().to_vec()) })
     }
+
+    async fn decode_compact<R: SeqRead>(reader: &mut R) -> Result<Self, Self::Error> {
+        let len = decode_varint(reader).await?;
+        let (result, buf) = reader.read_exact(vec![0u8; len as usize]).await;
+        result?;
+
+        Ok(unsafe { String::from_utf8_unchecked(buf.as_slice().to_vec()) })
+    }
+
+    async fn decode_buffered(reader: &mut BytesReader, compact: bool) -> Result<Self, Self::Error> {
+        let len = if compact {
+            decode_varint(reader).await?
+        } else {
+            u16::decode(reader).await?.into()
+        };
+        let bytes = reader.read_slice(len as usize)?;
+
+        Ok(unsafe { String::from_utf8_unchecked(bytes.to_vec()) })
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,97 @@
+use std::mem::size_of;
+
+use fusio::{SeqRead, Write};
+use time::{OffsetDateTime, UtcOffset};
+
+use super::{decode_with, encode_with, Decode, Encode};
+
+/// the seconds since the epoch, the nanoseconds into the second and the seconds of the offset,
+/// as an `i64`, a `u32` and an `i32`
+impl Encode for OffsetDateTime {
+    type Error = fusio::Error;
+
+    async fn encode<W: Write>(&self, writer: &mut W) -> Result<(), Self::Error> {
+        encode_date_time(self, writer, false).await
+    }
+
+    async fn encode_compact<W: Write>(&self, writer: &mut W) -> Result<(), Self::Error> {
+        encode_date_time(self, writer, true).await
+    }
+
+    fn size(&self) -> usize {
+        size_of::<i64>() + size_of::<u32>() + size_of::<i32>()
+    }
+}
+
+impl Decode for OffsetDateTime {
+    type Error = fusio::Error;
+
+    async fn decode<R: SeqRead>(reader: &mut R) -> Result<Self, Self::Error> {
+        decode_date_time(reader, false).await
+    }
+
+    async fn decode_compact<R: SeqRead>(reader: &mut R) -> Result<Self, Self::Error> {
+        decode_date_time(reader, true).await
+    }
+}
+
+async fn encode_date_time<W: Write>(
+    date_time: &OffsetDateTime,
+    writer: &mut W,
+    compact: bool,
+) -> Result<(), fusio::Error> {
+    encode_with(&date_time.unix_timestamp(), writer, compact).await?;
+    encode_with(&date_time.nanosecond(), writer, compact).await?;
+    encode_with(&date_time.offset().whole_seconds(), writer, compact).await
+}
+
+async fn decode_date_time<R: SeqRead>(
+    reader: &mut R,
+    compact: bool,
+) -> Result<OffsetDateTime, fusio::Error> {
+    let secs = decode_with::<i64, _>(reader, compact).await?;
+    let nanos = decode_with::<u32, _>(reader, compact).await?;
+    let offset = decode_with::<i32, _>(reader, compact).await?;
+
+    let out_of_range = |err| fusio::Error::Other(Box::new(err));
+    let date_time = OffsetDateTime::from_unix_timestamp(secs)
+        .and_then(|date_time| date_time.replace_nanosecond(nanos))
+        .map_err(out_of_range)?;
+    let offset = UtcOffset::from_whole_seconds(offset).map_err(out_of_range)?;
+
+    Ok(date_time.to_offset(offset))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use time::{OffsetDateTime, UtcOffset};
+    use tokio::io::AsyncSeekExt;
+
+    use crate::serdes::{Decode, Encode};
+
+    #[tokio::test]
+    async fn test_encode_decode() {
+        let source = OffsetDateTime::from_unix_timestamp(1_700_000_000)
+            .unwrap()
+            .replace_nanosecond(123_456_789)
+            .unwrap()
+            .to_offset(UtcOffset::from_hms(-3, -30, 0).unwrap());
+
+        let mut bytes = Vec::new();
+        let mut cursor = Cursor::new(&mut bytes);
+
+        source.encode(&mut cursor).await.unwrap();
+        source.encode_compact(&mut cursor).await.unwrap();
+
+        cursor.seek(std::io::SeekFrom::Start(0)).await.unwrap();
+        for decoded in [
+            OffsetDateTime::decode(&mut cursor).await.unwrap(),
+            OffsetDateTime::decode_compact(&mut cursor).await.unwrap(),
+        ] {
+            assert_eq!(decoded, source);
+            assert_eq!(decoded.offset(), source.offset());
+        }
+    }
+}
@@ -0,0 +1,55 @@
+use fusio::{SeqRead, Write};
+use uuid::Uuid;
+
+use super::{Decode, Encode};
+
+/// the 16 bytes of the UUID, in the order the UUIDs compare by
+impl Encode for Uuid {
+    type Error = fusio::Error;
+
+    async fn encode<W: Write>(&self, writer: &mut W) -> Result<(), Self::Error> {
+        let (result, _) = writer.write_all(&self.as_bytes()[..]).await;
+        result
+    }
+
+    fn size(&self) -> usize {
+        16
+    }
+}
+
+impl Decode for Uuid {
+    type Error = fusio::Error;
+
+    async fn decode<R: SeqRead>(reader: &mut R) -> Result<Self, Self::Error> {
+        let (result, buf) = reader.read_exact(vec![0u8; 16]).await;
+        result?;
+
+        Ok(Uuid::from_slice(&buf).expect("16 bytes are read"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use tokio::io::AsyncSeekExt;
+    use uuid::Uuid;
+
+    use crate::serdes::{Decode, Encode};
+
+    #[tokio::test]
+    async fn test_encode_decode() {
+        let source = Uuid::from_u128(0x936d_a01f_9abd_4d9d_80c7_02af_85c8_22a8);
+
+        let mut bytes = Vec::new();
+        let mut cursor = Cursor::new(&mut bytes);
+
+        source.encode(&mut cursor).await.unwrap();
+        source.encode_compact(&mut cursor).await.unwrap();
+        assert_eq!(cursor.get_ref().len(), 32);
+
+        cursor.seek(std::io::SeekFrom::Start(0)).await.unwrap();
+        assert_eq!(Uuid::decode(&mut cursor).await.unwrap(), source);
+        assert_eq!(Uuid::decode_compact(&mut cursor).await.unwrap(), source);
+    }
+}
@@ -0,0 +1,111 @@
+//! LEB128 varints of the compact encoding, the signed integers being zigzag encoded first so
+//! that the small negative ones stay short.
+
+use fusio::{SeqRead, Write};
+
+use super::Decode;
+
+const MAX_VARINT_LEN: usize = 10;
+
+pub(crate) fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+pub(crate) fn unzigzag(value: u64) -> i64 {
+    ((value >> 1) as i64) ^ -((value & 1) as i64)
+}
+
+/// the number of bytes of `value` as a varint
+pub(crate) fn varint_size(value: u64) -> usize {
+    (u64::BITS - (value | 1).leading_zeros()).div_ceil(7) as usize
+}
+
+pub(crate) async fn encode_varint<W>(mut value: u64, writer: &mut W) -> Result<(), fusio::Error>
+where
+    W: Write,
+{
+    let mut bytes = [0u8; MAX_VARINT_LEN];
+    let mut len = 0;
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            bytes[len] = byte;
+            len += 1;
+            break;
+        }
+        bytes[len] = byte | 0x80;
+        len += 1;
+    }
+    let (result, _) = writer.write_all(&bytes[..len]).await;
+    result
+}
+
+pub(crate) async fn decode_varint<R>(reader: &mut R) -> Result<u64, fusio::Error>
+where
+    R: SeqRead,
+{
+    let mut value = 0u64;
+    for shift in (0..u64::BITS).step_by(7) {
+        let byte = u8::decode(reader).await?;
+        if shift == 63 && byte > 1 {
+            break;
+        }
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(fusio::Error::Other("varint overflows 64 bits".into()))
+}
+
+/// the error of a varint out of the range of the decoded integer
+pub(crate) fn out_of_range<E: std::error::Error + Send + Sync + 'static>(err: E) -> fusio::Error {
+    fusio::Error::Other(Box::new(err))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::{decode_varint, encode_varint, unzigzag, varint_size, zigzag};
+
+    #[tokio::test]
+    async fn test_varint() {
+        let values = [0, 1, 127, 128, 300, u32::MAX as u64, u64::MAX];
+
+        let mut bytes = Vec::new();
+        let mut cursor = Cursor::new(&mut bytes);
+        for value in values {
+            encode_varint(value, &mut cursor).await.unwrap();
+        }
+        assert_eq!(
+            bytes.len(),
+            values
+                .iter()
+                .map(|value| varint_size(*value))
+                .sum::<usize>()
+        );
+        assert_eq!(&bytes[..5], &[0, 1, 127, 0x80, 1]);
+
+        let mut cursor = Cursor::new(&mut bytes);
+        for value in values {
+            assert_eq!(decode_varint(&mut cursor).await.unwrap(), value);
+        }
+
+        let mut overflow = vec![0xff; 10];
+        assert!(decode_varint(&mut Cursor::new(&mut overflow))
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn test_zigzag() {
+        for (value, encoded) in [(0, 0), (-1, 1), (1, 2), (-2, 3), (i64::MAX, u64::MAX - 1)] {
+            assert_eq!(zigzag(value), encoded);
+            assert_eq!(unzigzag(encoded), value);
+        }
+        assert_eq!(zigzag(i64::MIN), u64::MAX);
+        assert_eq!(unzigzag(u64::MAX), i64::MIN);
+    }
+}
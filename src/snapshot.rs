@@ -1,6 +1,10 @@
-use std::{collections::Bound, sync::Arc};
+use std::{
+    collections::{BTreeMap, Bound},
+    sync::{Arc, Mutex},
+};
 
 use async_lock::RwLockReadGuard;
+use futures_util::StreamExt;
 use parquet::arrow::ProjectionMask;
 
 use crate::{
@@ -9,7 +13,7 @@ use crate::{
     stream,
     stream::ScanStream,
     timestamp::Timestamp,
-    version::{TransactionTs, VersionRef},
+    version::{TransactionTs, Version, VersionRef},
     DbError, ParquetLru, Projection, Scan, Schema,
 };
 
@@ -18,12 +22,43 @@ where
     R: Record,
 {
     ts: Timestamp,
+    /// the timestamp the snapshot was taken at, the writes after it are held by the memtables
+    /// `share` keeps from being flushed
+    taken_at: Timestamp,
     share: RwLockReadGuard<'s, Schema<R>>,
     version: VersionRef<R>,
     manager: Arc<StoreManager>,
     parquet_lru: ParquetLru,
 }
 
+/// the timestamps read by the live snapshots, with the number of snapshots reading each
+#[derive(Default)]
+pub(crate) struct ActiveSnapshots(Mutex<BTreeMap<Timestamp, usize>>);
+
+impl ActiveSnapshots {
+    pub(crate) fn register(&self, ts: Timestamp) {
+        *self.0.lock().unwrap().entry(ts).or_default() += 1;
+    }
+
+    pub(crate) fn release(&self, ts: Timestamp) {
+        let mut snapshots = self.0.lock().unwrap();
+        if let Some(count) = snapshots.get_mut(&ts) {
+            *count -= 1;
+            if *count == 0 {
+                snapshots.remove(&ts);
+            }
+        }
+    }
+
+    pub(crate) fn oldest(&self) -> Option<Timestamp> {
+        self.0.lock().unwrap().keys().next().copied()
+    }
+
+    pub(crate) fn newest(&self) -> Option<Timestamp> {
+        self.0.lock().unwrap().keys().next_back().copied()
+    }
+}
+
 impl<'s, R> Snapshot<'s, R>
 where
     R: Record,
@@ -32,6 +67,16 @@ where
         &'get self,
         key: &'get R::Key,
         projection: Projection,
+    ) -> Result<Option<stream::Entry<'get, R>>, DbError<R>> {
+        self.get_at(key, projection, self.ts).await
+    }
+
+    /// get the record with `key` at `ts`, which is not older than the snapshot
+    pub(crate) async fn get_at<'get>(
+        &'get self,
+        key: &'get R::Key,
+        projection: Projection,
+        ts: Timestamp,
     ) -> Result<Option<stream::Entry<'get, R>>, DbError<R>> {
         Ok(self
             .share
@@ -39,9 +84,10 @@ where
                 &self.version,
                 &self.manager,
                 key,
-                self.ts,
+                ts,
                 projection,
                 self.parquet_lru.clone(),
+                None,
             )
             .await?
             .and_then(|entry| {
@@ -63,7 +109,7 @@ where
             range,
             self.ts,
             &self.version,
-            Box::new(move |_: Option<ProjectionMask>| None),
+            None,
             self.parquet_lru.clone(),
         )
     }
@@ -74,8 +120,12 @@ where
         manager: Arc<StoreManager>,
         parquet_lru: ParquetLru,
     ) -> Self {
+        let ts = version.load_ts();
+        share.snapshots.register(ts);
+
         Self {
-            ts: version.load_ts(),
+            ts,
+            taken_at: ts,
             share,
             version,
             manager,
@@ -83,37 +133,148 @@ where
         }
     }
 
-    pub(crate) fn ts(&self) -> Timestamp {
+    /// the timestamp the snapshot reads at, which [`Snapshot::read_at`] accepts later on to read
+    /// the same records again
+    pub fn ts(&self) -> Timestamp {
         self.ts
     }
 
+    /// read the records as they were at the earlier timestamp `ts`. Major compactions drop the
+    /// versions superseded at the GC watermark, see
+    /// [`DB::set_gc_watermark`](crate::DB::set_gc_watermark), so it fails with
+    /// [`DbError::TimestampNotRetained`] for a timestamp older than the watermark of the last
+    /// of them or newer than the snapshot.
+    pub fn read_at(&mut self, ts: Timestamp) -> Result<(), DbError<R>> {
+        let oldest = self.version.retained_ts();
+        if ts < oldest || ts > self.ts {
+            return Err(DbError::TimestampNotRetained {
+                ts,
+                oldest,
+                latest: self.ts,
+            });
+        }
+        self.share.snapshots.release(self.ts);
+        self.share.snapshots.register(ts);
+        self.ts = ts;
+        Ok(())
+    }
+
+    /// the timestamp of the latest commit, newer than the snapshot when others committed since
+    pub(crate) fn load_ts(&self) -> Timestamp {
+        self.version.load_ts()
+    }
+
     pub(crate) fn increase_ts(&self) -> Timestamp {
         self.version.increase_ts()
     }
 
+    pub(crate) fn advance_ts(&self, ts: Timestamp) {
+        self.version.advance_ts(ts)
+    }
+
+    /// the timestamp of the latest version of `key`, a tombstone included
+    pub(crate) async fn latest_ts(&self, key: &R::Key) -> Result<Option<Timestamp>, DbError<R>> {
+        Ok(self
+            .share
+            .get(
+                &self.version,
+                &self.manager,
+                key,
+                u64::MAX.into(),
+                Projection::Parts(vec![]),
+                self.parquet_lru.clone(),
+                None,
+            )
+            .await?
+            .map(|entry| entry.key().ts))
+    }
+
+    /// whether `key` was written after `ts`, in the memtables or, for a `ts` earlier than the
+    /// snapshot was taken at, in the SSTs flushed since
+    pub(crate) async fn is_written(&self, key: &R::Key, ts: Timestamp) -> Result<bool, DbError<R>> {
+        if self.share.check_conflict(key, ts) {
+            return Ok(true);
+        }
+        if ts >= self.taken_at {
+            return Ok(false);
+        }
+        Ok(self.latest_ts(key).await?.is_some_and(|latest| latest > ts))
+    }
+
+    /// whether a record with its key in `range` was written or range deleted after the
+    /// timestamp read at, in the memtables or, when read at an earlier timestamp with
+    /// [`Snapshot::read_at`], in the SSTs flushed since
+    pub(crate) async fn is_range_written(
+        &self,
+        range: (Bound<&R::Key>, Bound<&R::Key>),
+    ) -> Result<bool, DbError<R>> {
+        if self.share.check_range_conflict(range, self.ts) {
+            return Ok(true);
+        }
+        if self.ts >= self.taken_at {
+            return Ok(false);
+        }
+        if self.version.range_tombstones.iter().any(|range_tombstone| {
+            range_tombstone.ts > self.ts && range_tombstone.meets_range(range)
+        }) {
+            return Ok(true);
+        }
+        let mut scan = Scan::new(
+            &self.share,
+            &self.manager,
+            range,
+            u64::MAX.into(),
+            &self.version,
+            None,
+            self.parquet_lru.clone(),
+        )
+        .take()
+        .await?;
+        while let Some(entry) = scan.next().await.transpose()? {
+            if entry.key().ts > self.ts {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
     pub(crate) fn schema(&self) -> &Schema<R> {
         &self.share
     }
 
+    pub(crate) fn version(&self) -> &Version<R> {
+        &self.version
+    }
+
     pub(crate) fn _scan<'scan, 'range>(
         &'scan self,
         range: (Bound<&'range R::Key>, Bound<&'range R::Key>),
+        ts: Timestamp,
         fn_pre_stream: Box<
-            dyn FnOnce(Option<ProjectionMask>) -> Option<ScanStream<'scan, R>> + Send + 'scan,
+            dyn FnOnce(Option<ProjectionMask>) -> ScanStream<'scan, R> + Send + 'scan,
         >,
     ) -> Scan<'scan, 'range, R> {
         Scan::new(
             &self.share,
             &self.manager,
             range,
-            self.ts,
+            ts,
             &self.version,
-            fn_pre_stream,
+            Some(fn_pre_stream),
             self.parquet_lru.clone(),
         )
     }
 }
 
+impl<R> Drop for Snapshot<'_, R>
+where
+    R: Record,
+{
+    fn drop(&mut self) {
+        self.share.snapshots.release(self.ts);
+    }
+}
+
 #[cfg(all(test, feature = "tokio"))]
 mod tests {
     use std::{collections::Bound, sync::Arc};
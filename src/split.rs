@@ -0,0 +1,271 @@
+use std::{ops::Bound, sync::Arc};
+
+use fusio::path::Path;
+use futures_util::StreamExt;
+use parquet::arrow::ProjectionMask;
+use parquet_lru::NoCache;
+
+use crate::{
+    fs::{self, manager::StoreManager},
+    inmem::immutable::{ArrowArrays, Builder},
+    ondisk::sstable::SsTable,
+    record::{Key, KeyRef, Record},
+    scope::Scope,
+    timestamp::Timestamped,
+    version::{edit::VersionEdit, TransactionTs},
+    write_table, DbError, DbOption, DB,
+};
+
+impl<R> DB<R>
+where
+    R: Record + Send + Sync,
+    R::Columns: Send + Sync,
+{
+    /// write the records of the keys in the `range` into the empty directory `path` of the base
+    /// file system as a new database, e.g. to shard a database grown too large by key range. The
+    /// SSTs with all their keys in the range are shared as by [`DB::checkpoint`], while the SSTs
+    /// overlapping the range partially and the memtables are rewritten with only the records in
+    /// it, every version kept. The range deletions overlapping the range are copied too. The
+    /// range is not removed from this database, which [`DB::delete_files_in_range`] does cheaply
+    /// afterwards.
+    pub async fn split_range(
+        &self,
+        range: (Bound<&R::Key>, Bound<&R::Key>),
+        path: impl Into<Path>,
+    ) -> Result<(), DbError<R>> {
+        // block writes and compaction, so that the memtables, SSTs and the version stay consistent
+        let schema = self.schema.write().await;
+
+        let version = self.version_set.current().await;
+        let option = version.option();
+        let target = self.create_copy(option, path.into()).await?;
+        let arrow_schema = schema.record_instance.arrow_schema::<R>();
+        let mut version_edits = Vec::new();
+
+        for (level, scopes) in version.level_slice.iter().enumerate() {
+            let level_fs = self
+                .manager
+                .get_fs(option.level_fs_path(level).unwrap_or(&option.base_path));
+
+            for scope in scopes {
+                let scope = if scope.is_contained_in(range) {
+                    self.share_table(option, &target, level, scope.gen).await?;
+                    Some(Scope {
+                        wal_ids: None,
+                        ..scope.clone()
+                    })
+                } else if scope.meets_range(range) {
+                    let file = fs::open_table(
+                        level_fs,
+                        &option.table_path(scope.gen, level),
+                        true,
+                        option.level_direct_io(level),
+                    )
+                    .await?;
+                    let mut scan =
+                        SsTable::<R>::open(Arc::new(NoCache::default()), scope.gen, file)
+                            .await?
+                            .scan(range, u64::MAX.into(), None, ProjectionMask::all())
+                            .await?;
+                    let mut split = SplitTable::new(&arrow_schema);
+                    while let Some(entry) = scan.next().await.transpose()? {
+                        split.push(entry.internal_key(), entry.get());
+                    }
+                    split
+                        .write(&target, &self.manager, level, &arrow_schema)
+                        .await?
+                } else {
+                    None
+                };
+                version_edits.extend(scope.map(|scope| VersionEdit::Add {
+                    level: level as u8,
+                    scope,
+                }));
+            }
+        }
+        // the memtables are newer than every SST, the oldest first
+        for (_, immutable) in schema.immutables.iter() {
+            let mut split = SplitTable::new(&arrow_schema);
+            for entry in immutable.scan(range, u64::MAX.into(), ProjectionMask::all()) {
+                split.push(entry.internal_key(), entry.get());
+            }
+            version_edits.extend(
+                split
+                    .write(&target, &self.manager, 0, &arrow_schema)
+                    .await?
+                    .map(|scope| VersionEdit::Add { level: 0, scope }),
+            );
+        }
+        let mut split = SplitTable::new(&arrow_schema);
+        for entry in schema.mutable.scan(range, u64::MAX.into()) {
+            let key = entry.key();
+            split.push(
+                Timestamped::new(key.value.as_key_ref(), key.ts),
+                entry.value().as_ref().map(R::as_record_ref),
+            );
+        }
+        version_edits.extend(
+            split
+                .write(&target, &self.manager, 0, &arrow_schema)
+                .await?
+                .map(|scope| VersionEdit::Add { level: 0, scope }),
+        );
+        for range_tombstone in schema.range_tombstones(&version) {
+            if range_tombstone.meets_range(range) {
+                version_edits.push(VersionEdit::RangeTombstone { range_tombstone });
+            }
+        }
+        version_edits.push(VersionEdit::LatestTimeStamp {
+            ts: self.version_set.load_ts(),
+        });
+        version_edits.push(VersionEdit::NewLogLength { len: 0 });
+        Self::write_copy_log(&target, self.manager.base_fs(), &version_edits).await?;
+        drop(schema);
+
+        Ok(())
+    }
+}
+
+/// the records of a table rewritten by [`DB::split_range`], pushed in the order of their keys
+struct SplitTable<R>
+where
+    R: Record,
+{
+    builder: <R::Columns as ArrowArrays>::Builder,
+    bounds: Option<(R::Key, R::Key)>,
+}
+
+impl<R> SplitTable<R>
+where
+    R: Record,
+{
+    fn new(arrow_schema: &Arc<arrow::datatypes::Schema>) -> Self {
+        SplitTable {
+            builder: R::Columns::builder(arrow_schema, 8192),
+            bounds: None,
+        }
+    }
+
+    fn push(&mut self, key: Timestamped<<R::Key as Key>::Ref<'_>>, value: Option<R::Ref<'_>>) {
+        let owned_key = key.value.clone().to_key();
+        self.bounds = Some(match self.bounds.take() {
+            Some((min, _)) => (min, owned_key),
+            None => (owned_key.clone(), owned_key),
+        });
+        self.builder.push(key, value);
+    }
+
+    /// write the records as a new SST of the `level`, `None` if there are none
+    async fn write(
+        mut self,
+        option: &DbOption<R>,
+        manager: &StoreManager,
+        level: usize,
+        arrow_schema: &Arc<arrow::datatypes::Schema>,
+    ) -> Result<Option<Scope<R::Key>>, DbError<R>> {
+        let Some((min, max)) = self.bounds.take() else {
+            return Ok(None);
+        };
+        let columns = self.builder.finish(None);
+        let (gen, stats) = write_table(option, manager, level, arrow_schema, &columns).await?;
+
+        Ok(Some(Scope {
+            min,
+            max,
+            gen,
+            wal_ids: None,
+            stats: Some(stats),
+        }))
+    }
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod tests {
+    use std::ops::Bound;
+
+    use fusio::path::Path;
+    use tempfile::TempDir;
+
+    use crate::{
+        executor::tokio::TokioExecutor,
+        tests::{test_items, Test},
+        trigger::TriggerType,
+        DbOption, DB,
+    };
+
+    #[tokio::test]
+    async fn split_range() {
+        let temp_dir = TempDir::new().unwrap();
+        let split_dir = TempDir::new().unwrap();
+        let split_path = Path::from_filesystem_path(split_dir.path())
+            .unwrap()
+            .child("split");
+
+        let mut option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap());
+        option.immutable_chunk_num = 1;
+        option.immutable_chunk_max_num = 1;
+        option.trigger_type = TriggerType::Length(5);
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+        for item in test_items() {
+            db.insert(item).await.unwrap();
+        }
+        db.flush().await.unwrap();
+        // only in the memtables
+        db.remove("12".to_string()).await.unwrap();
+        db.insert(Test {
+            vstring: "15".to_string(),
+            vu32: 150,
+            vbool: None,
+        })
+        .await
+        .unwrap();
+        let (three, six) = ("3".to_string(), "6".to_string());
+        db.delete_range((Bound::Included(&three), Bound::Excluded(&six)))
+            .await
+            .unwrap();
+
+        let (lower, upper) = ("1".to_string(), "4".to_string());
+        db.split_range(
+            (Bound::Included(&lower), Bound::Excluded(&upper)),
+            split_path.clone(),
+        )
+        .await
+        .unwrap();
+        assert!(db
+            .split_range(
+                (Bound::Included(&lower), Bound::Excluded(&upper)),
+                split_path.clone(),
+            )
+            .await
+            .is_err());
+
+        let split: DB<Test> = DB::new(DbOption::from(split_path), TokioExecutor::new())
+            .await
+            .unwrap();
+        for i in 0..40 {
+            let key = i.to_string();
+            let expected = match key.as_str() {
+                "12" => None,
+                "15" => Some(150),
+                _ if key.as_str() >= "1" && key.as_str() < "3" => Some(i),
+                _ => None,
+            };
+            assert_eq!(
+                split
+                    .get(&key, |entry| Some(entry.get().vu32.unwrap()))
+                    .await
+                    .unwrap(),
+                expected,
+                "key {}",
+                key
+            );
+        }
+        // the range stays in the database
+        assert_eq!(
+            db.get(&"20".to_string(), |entry| Some(entry.get().vu32.unwrap()))
+                .await
+                .unwrap(),
+            Some(20)
+        );
+    }
+}
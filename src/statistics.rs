@@ -0,0 +1,285 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use crate::version::MAX_LEVEL;
+
+/// Running totals of the bytes moved by writes, gets, flushes and compactions since the
+/// database was opened, reported by [`DB::properties`](crate::DB::properties).
+#[derive(Debug, Default)]
+pub(crate) struct Statistics {
+    pub(crate) user_bytes_written: AtomicU64,
+    pub(crate) levels: [LevelStatistics; MAX_LEVEL],
+    /// memory held by the records buffered by major compactions before being written
+    pub(crate) compaction_memory: AtomicUsize,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct LevelStatistics {
+    /// SSTs of the level merged by major compactions
+    pub(crate) compaction_bytes_read: AtomicU64,
+    /// SSTs written to the level, by flushes for level 0
+    pub(crate) compaction_bytes_written: AtomicU64,
+    /// shared with the readers of the SSTs probed by gets
+    pub(crate) get_bytes_read: Arc<AtomicU64>,
+}
+
+/// the memory held by a compaction, added to a counter as it grows and released when dropped
+pub(crate) struct InFlight<'a> {
+    counter: &'a AtomicUsize,
+    bytes: usize,
+}
+
+impl<'a> InFlight<'a> {
+    pub(crate) fn new(counter: &'a AtomicUsize) -> Self {
+        InFlight { counter, bytes: 0 }
+    }
+
+    pub(crate) fn set(&mut self, bytes: usize) {
+        if bytes > self.bytes {
+            self.counter
+                .fetch_add(bytes - self.bytes, Ordering::Relaxed);
+        } else {
+            self.counter
+                .fetch_sub(self.bytes - bytes, Ordering::Relaxed);
+        }
+        self.bytes = bytes;
+    }
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.set(0);
+    }
+}
+
+/// What a scan or a get did, collected by a [`QueryStatsCollector`] given to
+/// [`Scan::collect_stats`](crate::Scan::collect_stats) or returned by
+/// [`DB::get_with_stats`](crate::DB::get_with_stats), to tell why a query is slow.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct QueryStats {
+    /// SSTs opened
+    pub files_consulted: u64,
+    /// SSTs a get skipped without reading their pages, as the bloom filters of the primary key
+    /// ruled the key out
+    pub bloom_filter_hits: u64,
+    /// row groups of the SSTs read, leaving out the ones the fences or page index ruled out
+    pub row_groups_read: u64,
+    /// versions read from the memtables and SSTs, including the older versions and tombstones
+    /// the merge dropped
+    pub rows_merged: u64,
+    /// records yielded to the caller, or rows of the batches yielded
+    pub rows_returned: u64,
+    /// time opening the memtables and SSTs up to the first row merged, or for a get looking up
+    /// the memtables
+    pub open_time: Duration,
+    /// time reading and merging the rows once opened, or for a get reading the SSTs
+    pub read_time: Duration,
+}
+
+/// Collects the [`QueryStats`] of the queries it is given to, summed if given to several. The
+/// clones share the counters, so one is kept to read them while the query holds another.
+#[derive(Debug, Clone, Default)]
+pub struct QueryStatsCollector {
+    counters: Arc<QueryCounters>,
+}
+
+#[derive(Debug, Default)]
+struct QueryCounters {
+    files_consulted: AtomicU64,
+    bloom_filter_hits: AtomicU64,
+    row_groups_read: AtomicU64,
+    rows_merged: AtomicU64,
+    rows_returned: AtomicU64,
+    open_nanos: AtomicU64,
+    read_nanos: AtomicU64,
+}
+
+impl QueryStatsCollector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// the stats collected so far
+    pub fn stats(&self) -> QueryStats {
+        let counters = &self.counters;
+        QueryStats {
+            files_consulted: counters.files_consulted.load(Ordering::Relaxed),
+            bloom_filter_hits: counters.bloom_filter_hits.load(Ordering::Relaxed),
+            row_groups_read: counters.row_groups_read.load(Ordering::Relaxed),
+            rows_merged: counters.rows_merged.load(Ordering::Relaxed),
+            rows_returned: counters.rows_returned.load(Ordering::Relaxed),
+            open_time: Duration::from_nanos(counters.open_nanos.load(Ordering::Relaxed)),
+            read_time: Duration::from_nanos(counters.read_nanos.load(Ordering::Relaxed)),
+        }
+    }
+
+    pub(crate) fn file_consulted(&self) {
+        self.counters
+            .files_consulted
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn bloom_filter_hit(&self) {
+        self.counters
+            .bloom_filter_hits
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn row_groups_read(&self, row_groups: usize) {
+        self.counters
+            .row_groups_read
+            .fetch_add(row_groups as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn rows_merged(&self, rows: u64) {
+        self.counters.rows_merged.fetch_add(rows, Ordering::Relaxed);
+    }
+
+    pub(crate) fn rows_returned(&self, rows: usize) {
+        self.counters
+            .rows_returned
+            .fetch_add(rows as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn opened(&self, elapsed: Duration) {
+        self.counters
+            .open_nanos
+            .fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn read(&self, elapsed: Duration) {
+        self.counters
+            .read_nanos
+            .fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+    }
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod tests {
+    use std::ops::Bound;
+
+    use fusio::path::Path;
+    use futures_util::StreamExt;
+    use tempfile::TempDir;
+
+    use super::QueryStatsCollector;
+    use crate::{
+        executor::tokio::TokioExecutor,
+        tests::{test_items, Test},
+        DbOption, DB,
+    };
+
+    #[tokio::test]
+    async fn amplification_statistics() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap());
+        option.immutable_chunk_num = 1;
+        option.immutable_chunk_max_num = 0;
+        option.major_threshold_with_sst_size = 1;
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+
+        // the second flush compacts the level 0 SST written by the first into level 1
+        for _ in 0..2 {
+            for item in test_items() {
+                db.insert(item).await.unwrap();
+            }
+            db.flush().await.unwrap();
+        }
+        let properties = db.properties().await.unwrap();
+        assert!(properties.user_bytes_written > 0);
+        assert!(properties.levels[0].compaction_bytes_written > 0);
+        assert!(properties.levels[0].compaction_bytes_read > 0);
+        assert_eq!(
+            properties.levels[1].compaction_bytes_written,
+            properties.levels[1].bytes
+        );
+        assert_eq!(properties.levels[0].get_bytes_read, 0);
+
+        assert_eq!(
+            db.get(&"1".to_string(), |entry| entry.get().vu32)
+                .await
+                .unwrap(),
+            Some(1)
+        );
+        let properties = db.properties().await.unwrap();
+        assert!(properties.levels[0].get_bytes_read > 0);
+        assert_eq!(properties.levels[1].get_bytes_read, 0);
+    }
+
+    #[tokio::test]
+    async fn query_stats() {
+        let temp_dir = TempDir::new().unwrap();
+        let option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap());
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+        for i in (0..20u32).step_by(2) {
+            db.insert(Test {
+                vstring: format!("{:02}", i),
+                vu32: i,
+                vbool: None,
+            })
+            .await
+            .unwrap();
+        }
+        db.flush().await.unwrap();
+        // not flushed
+        db.insert(Test {
+            vstring: "20".to_string(),
+            vu32: 20,
+            vbool: None,
+        })
+        .await
+        .unwrap();
+
+        let stats = QueryStatsCollector::new();
+        let tx = db.transaction().await;
+        let mut scan = tx
+            .scan((Bound::Unbounded, Bound::Unbounded))
+            .collect_stats(&stats)
+            .take()
+            .await
+            .unwrap();
+        let mut rows = 0;
+        while let Some(entry) = scan.next().await {
+            entry.unwrap();
+            rows += 1;
+        }
+        assert_eq!(rows, 11);
+        let scanned = stats.stats();
+        assert_eq!(scanned.files_consulted, 1);
+        assert_eq!(scanned.row_groups_read, 1);
+        assert_eq!(scanned.rows_merged, 11);
+        assert_eq!(scanned.rows_returned, 11);
+
+        let (vu32, got) = db
+            .get_with_stats(&"04".to_string(), |entry| Some(entry.get().vu32.unwrap()))
+            .await
+            .unwrap();
+        assert_eq!(vu32, Some(4));
+        assert_eq!(got.files_consulted, 1);
+        assert_eq!(got.row_groups_read, 1);
+        assert_eq!(got.rows_returned, 1);
+
+        let (vu32, got) = db
+            .get_with_stats(&"20".to_string(), |entry| Some(entry.get().vu32.unwrap()))
+            .await
+            .unwrap();
+        assert_eq!(vu32, Some(20));
+        assert_eq!(got.files_consulted, 0);
+        assert_eq!(got.rows_returned, 1);
+
+        // in the range of the table, so either its bloom filter rules the key out or it is read
+        let (vu32, got) = db
+            .get_with_stats(&"05".to_string(), |entry| Some(entry.get().vu32.unwrap()))
+            .await
+            .unwrap();
+        assert_eq!(vu32, None);
+        assert_eq!(got.files_consulted, 1);
+        assert_eq!(got.bloom_filter_hits + got.row_groups_read, 1);
+        assert_eq!(got.rows_returned, 0);
+    }
+}
@@ -0,0 +1,225 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures_core::Stream;
+use parquet::errors::ParquetError;
+use thiserror::Error;
+use tokio_util::sync::{CancellationToken, WaitForCancellationFutureOwned};
+use web_time::Instant;
+
+use crate::executor::{Clock, Sleep};
+
+/// The error a scan ends with once aborted by [`Scan::deadline`](crate::Scan::deadline) or
+/// [`Scan::cancel_on`](crate::Scan::cancel_on), wrapped in
+/// [`ParquetError::External`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum ScanAborted {
+    #[error("scan cancelled")]
+    Cancelled,
+    #[error("scan deadline exceeded")]
+    DeadlineExceeded,
+}
+
+impl ScanAborted {
+    /// the reason a scan ended with `err`, if it was aborted
+    pub fn from_error(err: &ParquetError) -> Option<ScanAborted> {
+        match err {
+            ParquetError::External(err) => err.downcast_ref::<ScanAborted>().copied(),
+            _ => None,
+        }
+    }
+}
+
+/// the deadline and the cancellation token of a scan
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ScanCancel {
+    pub(crate) deadline: Option<Instant>,
+    pub(crate) token: Option<CancellationToken>,
+}
+
+/// ends the scan stream with [`ScanAborted`] once the deadline passes or the token is cancelled,
+/// dropping the stream so that the files it reads are closed and the version it reads is
+/// released without waiting for the caller to drop it. The deadline wakes a pending scan through
+/// the timer of the executor.
+pub struct CancelStream<S> {
+    stream: Option<Pin<Box<S>>>,
+    cancelled: Option<Pin<Box<WaitForCancellationFutureOwned>>>,
+    deadline: Option<Instant>,
+    sleep: Option<Sleep>,
+}
+
+impl<S> CancelStream<S> {
+    pub(crate) fn new(stream: S, cancel: ScanCancel, clock: &Clock) -> Self {
+        CancelStream {
+            stream: Some(Box::pin(stream)),
+            cancelled: cancel.token.map(|token| Box::pin(token.cancelled_owned())),
+            deadline: cancel.deadline,
+            sleep: cancel
+                .deadline
+                .map(|deadline| clock.sleep(deadline.saturating_duration_since(Instant::now()))),
+        }
+    }
+
+    fn poll_aborted(&mut self, cx: &mut Context<'_>) -> Option<ScanAborted> {
+        if let Some(cancelled) = &mut self.cancelled {
+            if cancelled.as_mut().poll(cx).is_ready() {
+                return Some(ScanAborted::Cancelled);
+            }
+        }
+        if let Some(sleep) = &mut self.sleep {
+            if sleep.as_mut().poll(cx).is_ready() {
+                return Some(ScanAborted::DeadlineExceeded);
+            }
+        }
+        if self
+            .deadline
+            .is_some_and(|deadline| deadline <= Instant::now())
+        {
+            return Some(ScanAborted::DeadlineExceeded);
+        }
+        None
+    }
+}
+
+impl<S, T> Stream for CancelStream<S>
+where
+    S: Stream<Item = Result<T, ParquetError>>,
+{
+    type Item = Result<T, ParquetError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        if this.stream.is_none() {
+            return Poll::Ready(None);
+        }
+        if let Some(aborted) = this.poll_aborted(cx) {
+            this.stream = None;
+            this.cancelled = None;
+            this.sleep = None;
+            return Poll::Ready(Some(Err(ParquetError::External(Box::new(aborted)))));
+        }
+        this.stream.as_mut().unwrap().as_mut().poll_next(cx)
+    }
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod tests {
+    use std::{ops::Bound, sync::Arc, time::Duration};
+
+    use fusio::path::Path;
+    use futures_util::{stream, StreamExt};
+    use parquet::errors::ParquetError;
+    use tempfile::TempDir;
+    use tokio_util::sync::CancellationToken;
+    use web_time::Instant;
+
+    use super::{CancelStream, ScanAborted, ScanCancel};
+    use crate::{
+        executor::{tokio::TokioExecutor, Clock},
+        tests::Test,
+        DbOption, DB,
+    };
+
+    #[tokio::test]
+    async fn cancel_pending() {
+        let token = CancellationToken::new();
+        let mut stream = CancelStream::new(
+            stream::iter([Ok::<_, ParquetError>(1)]).chain(stream::pending()),
+            ScanCancel {
+                deadline: None,
+                token: Some(token.clone()),
+            },
+            &Clock::new(Arc::new(TokioExecutor::new())),
+        );
+        assert_eq!(stream.next().await.unwrap().unwrap(), 1);
+
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            token.cancel();
+        });
+        let err = stream.next().await.unwrap().unwrap_err();
+        assert_eq!(ScanAborted::from_error(&err), Some(ScanAborted::Cancelled));
+        assert!(stream.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn deadline_pending() {
+        let mut stream = CancelStream::new(
+            stream::pending::<Result<(), ParquetError>>(),
+            ScanCancel {
+                deadline: Some(Instant::now() + Duration::from_millis(10)),
+                token: None,
+            },
+            &Clock::new(Arc::new(TokioExecutor::new())),
+        );
+        let err = stream.next().await.unwrap().unwrap_err();
+        assert_eq!(
+            ScanAborted::from_error(&err),
+            Some(ScanAborted::DeadlineExceeded)
+        );
+        assert!(stream.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn scan_cancel() {
+        let temp_dir = TempDir::new().unwrap();
+        let option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap());
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+        for i in 0..10u32 {
+            db.insert(Test {
+                vstring: format!("{:02}", i),
+                vu32: i,
+                vbool: None,
+            })
+            .await
+            .unwrap();
+        }
+        db.flush().await.unwrap();
+
+        let tx = db.transaction().await;
+        let token = CancellationToken::new();
+        let mut scan = tx
+            .scan((Bound::Unbounded, Bound::Unbounded))
+            .cancel_on(token.clone())
+            .take()
+            .await
+            .unwrap();
+        assert_eq!(
+            scan.next().await.unwrap().unwrap().value().unwrap().vstring,
+            "00"
+        );
+        token.cancel();
+        let err = scan.next().await.unwrap().unwrap_err();
+        assert_eq!(ScanAborted::from_error(&err), Some(ScanAborted::Cancelled));
+        assert!(scan.next().await.is_none());
+
+        let mut scan = tx
+            .scan((Bound::Unbounded, Bound::Unbounded))
+            .deadline(Instant::now())
+            .take_batches(4)
+            .await
+            .unwrap();
+        let err = scan.next().await.unwrap().unwrap_err();
+        assert_eq!(
+            ScanAborted::from_error(&err),
+            Some(ScanAborted::DeadlineExceeded)
+        );
+        assert!(scan.next().await.is_none());
+
+        let mut scan = tx
+            .scan((Bound::Unbounded, Bound::Unbounded))
+            .timeout(Duration::from_secs(60))
+            .take()
+            .await
+            .unwrap();
+        let mut count = 0;
+        while let Some(entry) = scan.next().await {
+            entry.unwrap();
+            count += 1;
+        }
+        assert_eq!(count, 10);
+    }
+}
@@ -2,10 +2,11 @@ use std::{
     collections::{Bound, VecDeque},
     future::Future,
     pin::Pin,
-    sync::Arc,
+    sync::{atomic::AtomicU64, Arc},
     task::{Context, Poll},
 };
 
+use async_lock::Semaphore;
 use fusio::{
     dynamic::{DynFile, MaybeSendFuture},
     path::Path,
@@ -17,10 +18,11 @@ use parquet_lru::DynLruCache;
 use ulid::Ulid;
 
 use crate::{
-    fs::{FileId, FileType},
+    fs::{self, FileId},
     ondisk::{scan::SsTableScan, sstable::SsTable},
     record::Record,
     scope::Scope,
+    statistics::QueryStatsCollector,
     stream::record_batch::RecordBatchEntry,
     timestamp::Timestamp,
     version::Version,
@@ -59,6 +61,9 @@ where
     fs: Arc<dyn DynFs>,
     path: Option<Path>,
     parquet_lru: Arc<dyn DynLruCache<Ulid> + Send + Sync>,
+    read_bytes: Option<Arc<AtomicU64>>,
+    read_permits: Option<Arc<Semaphore>>,
+    stats: Option<QueryStatsCollector>,
 }
 
 impl<'level, R> LevelStream<'level, R>
@@ -79,11 +84,38 @@ where
         fs: Arc<dyn DynFs>,
         parquet_lru: Arc<dyn DynLruCache<Ulid> + Send + Sync>,
     ) -> Option<Self> {
-        let (lower, upper) = range;
-        let mut gens: VecDeque<FileId> = version.level_slice[level][start..end + 1]
+        let gens = version.level_slice[level][start..end + 1]
             .iter()
             .map(Scope::gen)
             .collect();
+        Self::tables(
+            version,
+            level,
+            gens,
+            range,
+            ts,
+            limit,
+            projection_mask,
+            fs,
+            parquet_lru,
+        )
+    }
+
+    /// scan the tables `gens` of `level` one after another, which must not overlap each other
+    /// and be in the order of their keys
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn tables(
+        version: &Version<R>,
+        level: usize,
+        mut gens: VecDeque<FileId>,
+        range: (Bound<&'level R::Key>, Bound<&'level R::Key>),
+        ts: Timestamp,
+        limit: Option<usize>,
+        projection_mask: ProjectionMask,
+        fs: Arc<dyn DynFs>,
+        parquet_lru: Arc<dyn DynLruCache<Ulid> + Send + Sync>,
+    ) -> Option<Self> {
+        let (lower, upper) = range;
         let first_gen = gens.pop_front()?;
         let status = FutureStatus::Init(first_gen);
 
@@ -100,8 +132,39 @@ where
             fs,
             path: None,
             parquet_lru,
+            read_bytes: None,
+            read_permits: None,
+            stats: None,
         })
     }
+
+    /// add the bytes fetched from the tables to `bytes`, see [`SsTable::count_reads`]
+    pub(crate) fn count_reads(self, bytes: Arc<AtomicU64>) -> Self {
+        LevelStream {
+            read_bytes: Some(bytes),
+            ..self
+        }
+    }
+
+    /// see [`SsTable::limit_reads`]
+    pub(crate) fn limit_reads(self, permits: Arc<Semaphore>) -> Self {
+        LevelStream {
+            read_permits: Some(permits),
+            ..self
+        }
+    }
+
+    /// see [`SsTable::collect_stats`]
+    pub(crate) fn collect_stats(self, stats: Option<QueryStatsCollector>) -> Self {
+        LevelStream { stats, ..self }
+    }
+
+    /// see [`SsTableScan::poll_prefetch`], the next table is only opened once polled
+    pub(crate) fn poll_prefetch(self: Pin<&mut Self>, cx: &mut Context<'_>) {
+        if let FutureStatus::Ready(scan) = &mut self.get_mut().status {
+            Pin::new(scan).poll_prefetch(cx);
+        }
+    }
 }
 
 impl<'level, R> Stream for LevelStream<'level, R>
@@ -117,10 +180,14 @@ where
                     let gen = *gen;
                     self.path = Some(self.option.table_path(gen, self.level));
 
-                    let reader = self.fs.open_options(
+                    let reader: Pin<
+                        Box<dyn MaybeSendFuture<Output = Result<Box<dyn DynFile>, Error>> + '_>,
+                    > = Box::pin(fs::open_table(
+                        &self.fs,
                         self.path.as_ref().unwrap(),
-                        FileType::Parquet.open_options(true),
-                    );
+                        true,
+                        self.option.level_direct_io(self.level),
+                    ));
                     #[allow(clippy::missing_transmute_annotations)]
                     let reader = unsafe {
                         std::mem::transmute::<
@@ -142,10 +209,17 @@ where
                         Some(gen) => {
                             self.path = Some(self.option.table_path(gen, self.level));
 
-                            let reader = self.fs.open_options(
+                            let reader: Pin<
+                                Box<
+                                    dyn MaybeSendFuture<Output = Result<Box<dyn DynFile>, Error>>
+                                        + '_,
+                                >,
+                            > = Box::pin(fs::open_table(
+                                &self.fs,
                                 self.path.as_ref().unwrap(),
-                                FileType::Parquet.open_options(true),
-                            );
+                                true,
+                                self.option.level_direct_io(self.level),
+                            ));
                             #[allow(clippy::missing_transmute_annotations)]
                             let reader = unsafe {
                                 std::mem::transmute::<
@@ -188,6 +262,16 @@ where
                 },
                 FutureStatus::OpenSst(sst_future) => match Pin::new(sst_future).poll(cx) {
                     Poll::Ready(Ok(sst)) => {
+                        let sst = match &self.read_bytes {
+                            Some(bytes) => sst.count_reads(bytes.clone()),
+                            None => sst,
+                        }
+                        .collect_stats(self.stats.as_ref());
+                        let sst = match &self.read_permits {
+                            Some(permits) => sst.limit_reads(permits.clone()),
+                            None => sst,
+                        }
+                        .readahead(self.option.scan_readahead_bytes);
                         self.status = FutureStatus::LoadStream(Box::pin(sst.scan(
                             (self.lower, self.upper),
                             self.ts,
@@ -203,7 +287,8 @@ where
                 },
                 FutureStatus::LoadStream(stream_future) => match Pin::new(stream_future).poll(cx) {
                     Poll::Ready(Ok(scan)) => {
-                        self.status = FutureStatus::Ready(scan);
+                        self.status =
+                            FutureStatus::Ready(scan.prefetch(self.option.scan_prefetch_batches));
                         continue;
                     }
                     Poll::Ready(Err(err)) => Poll::Ready(Some(Err(err))),
@@ -258,7 +343,7 @@ mod tests {
                 0,
                 1,
                 (Bound::Unbounded, Bound::Unbounded),
-                1_u32.into(),
+                1_u64.into(),
                 None,
                 ProjectionMask::roots(
                     &arrow_to_parquet_schema(Test::arrow_schema()).unwrap(),
@@ -295,7 +380,7 @@ mod tests {
                 0,
                 1,
                 (Bound::Unbounded, Bound::Unbounded),
-                1_u32.into(),
+                1_u64.into(),
                 None,
                 ProjectionMask::roots(
                     &arrow_to_parquet_schema(Test::arrow_schema()).unwrap(),
@@ -332,7 +417,7 @@ mod tests {
                 0,
                 1,
                 (Bound::Unbounded, Bound::Unbounded),
-                1_u32.into(),
+                1_u64.into(),
                 None,
                 ProjectionMask::roots(
                     &arrow_to_parquet_schema(Test::arrow_schema()).unwrap(),
@@ -0,0 +1,124 @@
+use std::mem;
+
+/// A tournament tree over one slot per merged stream, the exhausted ones being empty. Replacing
+/// the smallest item replays the path from its slot to the root, one comparison per level,
+/// where a binary heap sifts an item down and another one up.
+#[derive(Debug)]
+pub(crate) struct LoserTree<T> {
+    slots: Vec<Option<T>>,
+    /// the slot losing the match of each inner node, with the overall winner at 0
+    nodes: Vec<usize>,
+}
+
+impl<T> LoserTree<T>
+where
+    T: Ord,
+{
+    pub(crate) fn new(slots: Vec<Option<T>>) -> Self {
+        let mut tree = LoserTree {
+            nodes: vec![0; slots.len()],
+            slots,
+        };
+        if !tree.slots.is_empty() {
+            tree.nodes[0] = tree.build(1);
+        }
+        tree
+    }
+
+    /// the smallest item with its slot, the one of the smaller slot first among equal items
+    pub(crate) fn peek(&self) -> Option<(usize, &T)> {
+        let winner = *self.nodes.first()?;
+        self.slots[winner].as_ref().map(|item| (winner, item))
+    }
+
+    /// take the smallest item and put `next` in its slot
+    pub(crate) fn replace_top(&mut self, next: Option<T>) -> Option<T> {
+        let winner = *self.nodes.first()?;
+        let top = mem::replace(&mut self.slots[winner], next);
+        self.replay(winner);
+        top
+    }
+
+    /// whether `slot` wins over `other`, an empty slot losing to any item
+    fn beats(&self, slot: usize, other: usize) -> bool {
+        match (&self.slots[slot], &self.slots[other]) {
+            (Some(item), Some(other_item)) => (item, slot) < (other_item, other),
+            (Some(_), None) => true,
+            (None, Some(_)) => false,
+            (None, None) => slot < other,
+        }
+    }
+
+    /// the slots are the leaves past the inner nodes, the children of `node` being at `2 * node`
+    /// and `2 * node + 1`
+    fn build(&mut self, node: usize) -> usize {
+        let len = self.slots.len();
+        if node >= len {
+            return node - len;
+        }
+        let left = self.build(2 * node);
+        let right = self.build(2 * node + 1);
+        let (winner, loser) = if self.beats(left, right) {
+            (left, right)
+        } else {
+            (right, left)
+        };
+        self.nodes[node] = loser;
+        winner
+    }
+
+    fn replay(&mut self, slot: usize) {
+        let mut winner = slot;
+        let mut node = (slot + self.slots.len()) / 2;
+        while node > 0 {
+            if self.beats(self.nodes[node], winner) {
+                mem::swap(&mut self.nodes[node], &mut winner);
+            }
+            node /= 2;
+        }
+        self.nodes[0] = winner;
+    }
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod tests {
+    use super::LoserTree;
+
+    #[test]
+    fn merge_sorted() {
+        for len in 0..9 {
+            let runs = (0..len)
+                .map(|run| (0..run * 3).map(|i| i * len + run).collect::<Vec<_>>())
+                .collect::<Vec<_>>();
+            let mut iters = runs
+                .iter()
+                .map(|run| run.iter().copied())
+                .collect::<Vec<_>>();
+            let mut tree = LoserTree::new(iters.iter_mut().map(Iterator::next).collect());
+
+            let mut merged = Vec::new();
+            while let Some((slot, _)) = tree.peek() {
+                let next = iters[slot].next();
+                merged.push(tree.replace_top(next).unwrap());
+            }
+            let mut expected = runs.concat();
+            expected.sort();
+            assert_eq!(merged, expected);
+            assert_eq!(tree.replace_top(None), None);
+        }
+    }
+
+    #[test]
+    fn ties_by_slot() {
+        let mut tree = LoserTree::new(vec![Some(1), None, Some(1), Some(0)]);
+        assert_eq!(tree.peek(), Some((3, &0)));
+        tree.replace_top(Some(1));
+        assert_eq!(tree.peek(), Some((0, &1)));
+        tree.replace_top(None);
+        assert_eq!(tree.peek(), Some((2, &1)));
+        tree.replace_top(None);
+        assert_eq!(tree.peek(), Some((3, &1)));
+        tree.replace_top(None);
+        assert_eq!(tree.peek(), None);
+    }
+}
@@ -1,16 +1,19 @@
 use std::{
     cmp::Ordering,
-    collections::BinaryHeap,
     pin::Pin,
     task::{Context, Poll},
 };
 
-use futures_core::{ready, Stream};
+use futures_core::Stream;
 use futures_util::stream::StreamExt;
+use parquet::errors::ParquetError;
 use pin_project_lite::pin_project;
 
-use super::{Entry, ScanStream};
-use crate::{record::Record, timestamp::Timestamp};
+use super::{loser_tree::LoserTree, Entry, ScanStream};
+use crate::{
+    range_tombstone::RangeTombstone, record::Record, statistics::QueryStatsCollector,
+    timestamp::Timestamp,
+};
 
 pin_project! {
     pub struct MergeStream<'merge, R>
@@ -18,10 +21,16 @@ pin_project! {
         R: Record,
     {
         streams: Vec<ScanStream<'merge, R>>,
-        peeked: BinaryHeap<CmpEntry<'merge, R>>,
+        peeked: LoserTree<CmpEntry<'merge, R>>,
         buf: Option<Entry<'merge, R>>,
         ts: Timestamp,
         limit: Option<usize>,
+        range_tombstones: Vec<RangeTombstone<R::Key>>,
+        watermark: Option<Timestamp>,
+        paranoid: bool,
+        // entries taken from the streams
+        merged: u64,
+        stats: Option<QueryStatsCollector>,
     }
 }
 
@@ -30,16 +39,26 @@ where
     R: Record,
 {
     pub(crate) async fn from_vec(
+        streams: Vec<ScanStream<'merge, R>>,
+        ts: Timestamp,
+    ) -> Result<Self, ParquetError> {
+        Self::with_range_tombstones(streams, ts, Vec::new()).await
+    }
+
+    /// same as [`MergeStream::from_vec`], but entries hidden by any of the `range_tombstones`
+    /// visible at `ts` are skipped
+    pub(crate) async fn with_range_tombstones(
         mut streams: Vec<ScanStream<'merge, R>>,
         ts: Timestamp,
-    ) -> Result<Self, parquet::errors::ParquetError> {
-        let mut peeked = BinaryHeap::with_capacity(streams.len());
+        mut range_tombstones: Vec<RangeTombstone<R::Key>>,
+    ) -> Result<Self, ParquetError> {
+        range_tombstones.retain(|range_tombstone| range_tombstone.ts <= ts);
+        let mut slots = Vec::with_capacity(streams.len());
 
-        for (offset, stream) in streams.iter_mut().enumerate() {
-            if let Some(entry) = stream.next().await {
-                peeked.push(CmpEntry::new(offset, entry?));
-            }
+        for stream in streams.iter_mut() {
+            slots.push(stream.next().await.transpose()?.map(CmpEntry::new));
         }
+        let peeked = LoserTree::new(slots);
 
         let mut merge_stream = Self {
             streams,
@@ -47,6 +66,11 @@ where
             buf: None,
             ts,
             limit: None,
+            range_tombstones,
+            watermark: None,
+            paranoid: false,
+            merged: 0,
+            stats: None,
         };
         merge_stream.next().await;
 
@@ -60,13 +84,37 @@ where
             ..self
         }
     }
+
+    /// fail on an entry not ordered after the previous one, as yielded by a corrupted table,
+    /// see [`DbOption::paranoid_checks`](crate::DbOption::paranoid_checks)
+    pub(crate) fn paranoid(self, paranoid: bool) -> Self {
+        Self { paranoid, ..self }
+    }
+
+    /// count the entries taken from the streams into `stats`, the ones taken when created
+    /// included
+    pub(crate) fn collect_stats(self, stats: Option<QueryStatsCollector>) -> Self {
+        if let Some(stats) = &stats {
+            stats.rows_merged(self.merged);
+        }
+        Self { stats, ..self }
+    }
+
+    /// keep every version newer than the `watermark` along with the newest one at or before it,
+    /// instead of only the newest version of each key
+    pub(crate) fn keep_versions_after(self, watermark: Timestamp) -> Self {
+        Self {
+            watermark: Some(watermark),
+            ..self
+        }
+    }
 }
 
 impl<'merge, R> Stream for MergeStream<'merge, R>
 where
     R: Record,
 {
-    type Item = Result<Entry<'merge, R>, parquet::errors::ParquetError>;
+    type Item = Result<Entry<'merge, R>, ParquetError>;
 
     fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
         let this = self.project();
@@ -76,23 +124,56 @@ where
                 return Poll::Ready(None);
             }
         }
-        while let Some(offset) = this.peeked.peek().map(|entry| entry.offset) {
-            let next = ready!(Pin::new(&mut this.streams[offset]).poll_next(cx)).transpose()?;
-            let peeked = match this.peeked.pop() {
+        while let Some(offset) = this.peeked.peek().map(|(offset, _)| offset) {
+            let next = match Pin::new(&mut this.streams[offset]).poll_next(cx) {
+                Poll::Ready(next) => next.transpose()?,
+                Poll::Pending => {
+                    // the other streams read ahead while this one waits
+                    for stream in this.streams.iter_mut() {
+                        Pin::new(stream).poll_prefetch(cx);
+                    }
+                    return Poll::Pending;
+                }
+            };
+            let peeked = match this.peeked.replace_top(next.map(CmpEntry::new)) {
                 Some(peeked) => peeked,
                 None => return Poll::Ready(None),
             };
-            if let Some(next) = next {
-                this.peeked.push(CmpEntry::new(offset, next));
+            *this.merged += 1;
+            if let Some(stats) = this.stats {
+                stats.rows_merged(1);
             }
             if peeked.entry.key().ts > *ts {
                 continue;
             }
+            // older versions of the same key are covered as well, so they are skipped too
+            if this
+                .range_tombstones
+                .iter()
+                .any(|range_tombstone| range_tombstone.covers(&peeked.entry.key()))
+            {
+                continue;
+            }
             if let Some(buf) = this.buf {
-                if buf.key().value == peeked.entry.key().value {
+                let (buf, peeked) = (buf.key(), peeked.entry.key());
+                if buf.value == peeked.value
+                    && (buf.ts == peeked.ts
+                        || this.watermark.map_or(true, |watermark| buf.ts <= watermark))
+                {
                     continue;
                 }
             }
+            if *this.paranoid {
+                if let Some(buf) = this.buf {
+                    if buf.key() >= peeked.entry.key() {
+                        return Poll::Ready(Some(Err(ParquetError::General(format!(
+                            "merged entries out of order: {:?} is followed by {:?}",
+                            buf.key(),
+                            peeked.entry.key()
+                        )))));
+                    }
+                }
+            }
             if let Some(limit) = this.limit.as_ref() {
                 this.limit.replace(*limit - 1);
             }
@@ -103,12 +184,13 @@ where
     }
 }
 
+/// an entry ordered by its key, the streams holding equal keys being ordered by the
+/// [`LoserTree`]
 #[derive(Debug)]
 struct CmpEntry<'stream, R>
 where
     R: Record,
 {
-    offset: usize,
     entry: Entry<'stream, R>,
 }
 
@@ -116,8 +198,8 @@ impl<'stream, R> CmpEntry<'stream, R>
 where
     R: Record,
 {
-    fn new(offset: usize, entry: Entry<'stream, R>) -> Self {
-        Self { offset, entry }
+    fn new(entry: Entry<'stream, R>) -> Self {
+        Self { entry }
     }
 }
 
@@ -146,11 +228,7 @@ where
     R: Record,
 {
     fn cmp(&self, other: &Self) -> Ordering {
-        self.entry
-            .key()
-            .cmp(&other.entry.key())
-            .then(self.offset.cmp(&other.offset))
-            .reverse()
+        self.entry.key().cmp(&other.entry.key())
     }
 }
 
@@ -163,8 +241,8 @@ mod tests {
 
     use super::MergeStream;
     use crate::{
-        inmem::mutable::Mutable, stream::Entry, trigger::TriggerFactory, wal::log::LogType,
-        DbOption,
+        inmem::mutable::Mutable, range_tombstone::RangeTombstone, stream::Entry,
+        trigger::TriggerFactory, wal::log::LogType, DbOption,
     };
 
     #[tokio::test]
@@ -272,19 +350,19 @@ mod tests {
         let trigger = Arc::new(TriggerFactory::create(option.trigger_type));
 
         let m1 = Mutable::<String>::new(&option, trigger, &fs).await.unwrap();
-        m1.insert(LogType::Full, "1".into(), 0_u32.into())
+        m1.insert(LogType::Full, "1".into(), 0_u64.into())
             .await
             .unwrap();
-        m1.insert(LogType::Full, "2".into(), 0_u32.into())
+        m1.insert(LogType::Full, "2".into(), 0_u64.into())
             .await
             .unwrap();
-        m1.insert(LogType::Full, "2".into(), 1_u32.into())
+        m1.insert(LogType::Full, "2".into(), 1_u64.into())
             .await
             .unwrap();
-        m1.insert(LogType::Full, "3".into(), 1_u32.into())
+        m1.insert(LogType::Full, "3".into(), 1_u64.into())
             .await
             .unwrap();
-        m1.insert(LogType::Full, "4".into(), 0_u32.into())
+        m1.insert(LogType::Full, "4".into(), 0_u64.into())
             .await
             .unwrap();
 
@@ -347,6 +425,124 @@ mod tests {
         };
     }
 
+    #[tokio::test]
+    async fn merge_mutable_range_tombstone() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let fs = Arc::new(TokioFs) as Arc<dyn DynFs>;
+        let option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap());
+
+        fs.create_dir_all(&option.wal_dir_path()).await.unwrap();
+
+        let trigger = Arc::new(TriggerFactory::create(option.trigger_type));
+
+        let m1 = Mutable::<String>::new(&option, trigger, &fs).await.unwrap();
+        m1.insert(LogType::Full, "1".into(), 0_u64.into())
+            .await
+            .unwrap();
+        m1.insert(LogType::Full, "2".into(), 0_u64.into())
+            .await
+            .unwrap();
+        m1.insert(LogType::Full, "3".into(), 0_u64.into())
+            .await
+            .unwrap();
+        m1.insert(LogType::Full, "3".into(), 2_u64.into())
+            .await
+            .unwrap();
+
+        let range_tombstones = vec![RangeTombstone::new(
+            Bound::Included("2".to_string()),
+            Bound::Unbounded,
+            1.into(),
+        )];
+        {
+            let mut merge = MergeStream::<String>::with_range_tombstones(
+                vec![m1
+                    .scan((Bound::Unbounded, Bound::Unbounded), 2.into())
+                    .into()],
+                2.into(),
+                range_tombstones.clone(),
+            )
+            .await
+            .unwrap();
+
+            if let Some(Ok(Entry::Mutable(entry))) = merge.next().await {
+                assert_eq!(entry.key().value, "1");
+            } else {
+                unreachable!()
+            };
+            if let Some(Ok(Entry::Mutable(entry))) = merge.next().await {
+                assert_eq!(entry.key().value, "3");
+                assert_eq!(entry.key().ts, 2.into());
+            } else {
+                unreachable!()
+            };
+            assert!(merge.next().await.is_none());
+        }
+        {
+            // the range tombstone is not visible at ts 0
+            let mut merge = MergeStream::<String>::with_range_tombstones(
+                vec![m1
+                    .scan((Bound::Unbounded, Bound::Unbounded), 0.into())
+                    .into()],
+                0.into(),
+                range_tombstones,
+            )
+            .await
+            .unwrap();
+
+            for key in ["1", "2", "3"] {
+                if let Some(Ok(Entry::Mutable(entry))) = merge.next().await {
+                    assert_eq!(entry.key().value, key);
+                } else {
+                    unreachable!()
+                };
+            }
+            assert!(merge.next().await.is_none());
+        }
+    }
+
+    #[tokio::test]
+    async fn merge_keep_versions_after() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let fs = Arc::new(TokioFs) as Arc<dyn DynFs>;
+        let option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap());
+
+        fs.create_dir_all(&option.wal_dir_path()).await.unwrap();
+
+        let trigger = Arc::new(TriggerFactory::create(option.trigger_type));
+
+        let m1 = Mutable::<String>::new(&option, trigger, &fs).await.unwrap();
+        for ts in 0..3_u64 {
+            m1.insert(LogType::Full, "1".into(), ts.into())
+                .await
+                .unwrap();
+        }
+        m1.insert(LogType::Full, "2".into(), 0_u64.into())
+            .await
+            .unwrap();
+
+        let mut merge = MergeStream::<String>::from_vec(
+            vec![m1
+                .scan((Bound::Unbounded, Bound::Unbounded), u64::MAX.into())
+                .into()],
+            u64::MAX.into(),
+        )
+        .await
+        .unwrap()
+        .keep_versions_after(1.into());
+
+        // the version 0 of "1" is superseded at the watermark
+        for (key, ts) in [("1", 2), ("1", 1), ("2", 0)] {
+            if let Some(Ok(Entry::Mutable(entry))) = merge.next().await {
+                assert_eq!(entry.key().value, key);
+                assert_eq!(entry.key().ts, ts.into());
+            } else {
+                unreachable!()
+            };
+        }
+        assert!(merge.next().await.is_none());
+    }
+
     #[tokio::test]
     async fn merge_mutable_limit() {
         let temp_dir = tempfile::tempdir().unwrap();
@@ -358,13 +554,13 @@ mod tests {
         let trigger = Arc::new(TriggerFactory::create(option.trigger_type));
 
         let m1 = Mutable::<String>::new(&option, trigger, &fs).await.unwrap();
-        m1.insert(LogType::Full, "1".into(), 0_u32.into())
+        m1.insert(LogType::Full, "1".into(), 0_u64.into())
             .await
             .unwrap();
-        m1.insert(LogType::Full, "2".into(), 1_u32.into())
+        m1.insert(LogType::Full, "2".into(), 1_u64.into())
             .await
             .unwrap();
-        m1.insert(LogType::Full, "3".into(), 1_u32.into())
+        m1.insert(LogType::Full, "3".into(), 1_u64.into())
             .await
             .unwrap();
 
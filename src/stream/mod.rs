@@ -1,8 +1,11 @@
+pub(crate) mod cancel;
 pub(crate) mod level;
+mod loser_tree;
 pub(crate) mod mem_projection;
 pub(crate) mod merge;
 pub(crate) mod package;
 pub(crate) mod record_batch;
+pub(crate) mod slow_log;
 
 use std::{
     fmt::{self, Debug, Formatter},
@@ -23,7 +26,7 @@ use crate::{
     ondisk::scan::SsTableScan,
     record::{Key, Record, RecordRef},
     stream::{level::LevelStream, mem_projection::MemProjectionStream},
-    timestamp::Timestamped,
+    timestamp::{Timestamp, Timestamped},
     transaction::TransactionScan,
 };
 
@@ -61,6 +64,15 @@ where
         }
     }
 
+    /// the commit timestamp of the version, `None` for a write of the transaction reading it
+    pub fn ts(&self) -> Option<Timestamp> {
+        match self {
+            Entry::Transaction(_) => None,
+            Entry::Projection((entry, _)) => entry.ts(),
+            _ => Some(self.key().ts),
+        }
+    }
+
     pub fn value(&self) -> Option<R::Ref<'_>> {
         match self {
             Entry::Transaction((_, value)) => value.as_ref().map(R::as_record_ref),
@@ -198,6 +210,21 @@ where
     }
 }
 
+impl<R> ScanStream<'_, R>
+where
+    R: Record,
+{
+    /// make progress on the record batches read ahead by the scans of SSTs, see
+    /// [`SsTableScan::poll_prefetch`]
+    pub(crate) fn poll_prefetch(self: Pin<&mut Self>, cx: &mut Context<'_>) {
+        match self.project() {
+            ScanStreamProject::SsTable { inner } => inner.poll_prefetch(cx),
+            ScanStreamProject::Level { inner } => inner.poll_prefetch(cx),
+            _ => (),
+        }
+    }
+}
+
 impl<'scan, R> Stream for ScanStream<'scan, R>
 where
     R: Record,
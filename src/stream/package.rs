@@ -81,7 +81,7 @@ where
 mod tests {
     use std::{collections::Bound, sync::Arc};
 
-    use arrow::array::{BooleanArray, RecordBatch, StringArray, UInt32Array};
+    use arrow::array::{BooleanArray, RecordBatch, StringArray, UInt32Array, UInt64Array};
     use fusio::{disk::TokioFs, path::Path, DynFs};
     use futures_util::StreamExt;
     use tempfile::TempDir;
@@ -204,7 +204,7 @@ mod tests {
                     Arc::new(BooleanArray::from(vec![
                         false, false, false, false, false, false
                     ])),
-                    Arc::new(UInt32Array::from(vec![0, 1, 2, 3, 4, 5])),
+                    Arc::new(UInt64Array::from(vec![0, 1, 2, 3, 4, 5])),
                     Arc::new(StringArray::from(vec!["a", "b", "c", "d", "e", "f"])),
                     Arc::new(UInt32Array::from(vec![0, 1, 2, 3, 4, 5])),
                 ],
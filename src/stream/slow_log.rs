@@ -0,0 +1,160 @@
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use futures_core::Stream;
+use futures_util::ready;
+use parquet::errors::ParquetError;
+use pin_project_lite::pin_project;
+use tracing::{warn, Span};
+use web_time::Instant;
+
+use crate::{fs::FileId, statistics::QueryStatsCollector};
+
+pin_project! {
+    /// polls the scan stream in its span and logs it once exhausted if it took longer than the
+    /// slow operation threshold, counting the time polling it and the rows it yields into the
+    /// stats collected by the scan
+    pub struct SlowLogStream<S, T> {
+        #[pin]
+        stream: S,
+        span: Span,
+        // only taken with a threshold
+        start: Option<(Instant, Duration)>,
+        tables: Vec<(usize, FileId)>,
+        rows: usize,
+        count: fn(&T) -> usize,
+        stats: Option<QueryStatsCollector>,
+    }
+}
+
+impl<S, T> SlowLogStream<S, T>
+where
+    S: Stream<Item = Result<T, ParquetError>>,
+{
+    pub(crate) fn new(
+        stream: S,
+        span: Span,
+        start: Option<(Instant, Duration)>,
+        tables: Vec<(usize, FileId)>,
+        count: fn(&T) -> usize,
+    ) -> Self {
+        Self {
+            stream,
+            span,
+            start,
+            tables,
+            rows: 0,
+            count,
+            stats: None,
+        }
+    }
+
+    pub(crate) fn collect_stats(self, stats: Option<QueryStatsCollector>) -> Self {
+        Self { stats, ..self }
+    }
+}
+
+impl<S, T> Stream for SlowLogStream<S, T>
+where
+    S: Stream<Item = Result<T, ParquetError>>,
+{
+    type Item = Result<T, ParquetError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.project();
+        let _enter = this.span.enter();
+
+        let polled = this.stats.as_ref().map(|_| Instant::now());
+        let item = this.stream.poll_next(cx);
+        if let (Some(stats), Some(polled)) = (this.stats.as_ref(), polled) {
+            stats.read(polled.elapsed());
+        }
+        let item = ready!(item);
+        match &item {
+            Some(Ok(item)) => {
+                let rows = (this.count)(item);
+                *this.rows += rows;
+                if let Some(stats) = this.stats {
+                    stats.rows_returned(rows);
+                }
+            }
+            Some(Err(_)) => (),
+            None => {
+                if let Some((start, threshold)) = this.start.take() {
+                    let elapsed = start.elapsed();
+                    if elapsed > threshold {
+                        warn!(
+                            ?elapsed,
+                            rows = *this.rows,
+                            tables = ?this.tables,
+                            "slow scan"
+                        );
+                    }
+                }
+            }
+        }
+        Poll::Ready(item)
+    }
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod tests {
+    use std::{ops::Bound, time::Duration};
+
+    use fusio::path::Path;
+    use futures_util::StreamExt;
+    use tempfile::TempDir;
+
+    use crate::{
+        executor::tokio::TokioExecutor,
+        tests::{test_items, Test},
+        DbOption, DB,
+    };
+
+    #[tokio::test]
+    async fn slow_operation_log() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap())
+            .slow_operation_threshold(Duration::ZERO);
+        option.immutable_chunk_num = 1;
+        option.immutable_chunk_max_num = 0;
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+        for item in test_items() {
+            db.insert(item).await.unwrap();
+        }
+        db.flush().await.unwrap();
+
+        let key = "1".to_string();
+        let version = db.version_set.current().await;
+        assert_eq!(
+            version
+                .tables_in_range((Bound::Included(&key), Bound::Included(&key)))
+                .len(),
+            1
+        );
+        assert!(version
+            .tables_in_range((Bound::Excluded(&"9".to_string()), Bound::Unbounded))
+            .is_empty());
+
+        // logging the slow operations leaves their results untouched
+        assert_eq!(
+            db.get(&key, |entry| entry.get().vu32).await.unwrap(),
+            Some(1)
+        );
+        let snapshot = db.snapshot().await;
+        let mut scan = snapshot
+            .scan((Bound::Unbounded, Bound::Unbounded))
+            .take()
+            .await
+            .unwrap();
+        let mut rows = 0;
+        while let Some(entry) = scan.next().await {
+            entry.unwrap();
+            rows += 1;
+        }
+        assert_eq!(rows, 40);
+    }
+}
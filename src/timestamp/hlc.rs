@@ -0,0 +1,77 @@
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::timestamp::Timestamp;
+
+const LOGICAL_BITS: u32 = 16;
+
+/// A hybrid logical clock, producing [`Timestamp`]s for
+/// [`DB::insert_at`](crate::DB::insert_at) and
+/// [`Transaction::commit_at`](crate::transaction::Transaction::commit_at).
+///
+/// The high 48 bits of a timestamp are the milliseconds since the unix epoch and the low 16 bits
+/// a counter, so timestamps follow the wall clock while staying strictly increasing when it
+/// stalls or steps back.
+#[derive(Debug, Default)]
+pub struct HybridClock {
+    last: AtomicU64,
+}
+
+impl HybridClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// a timestamp newer than every one this clock has produced or observed
+    pub fn now(&self) -> Timestamp {
+        let physical = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_millis() as u64)
+            << LOGICAL_BITS;
+        let previous = self
+            .last
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |last| {
+                Some(physical.max(last + 1))
+            })
+            .unwrap();
+
+        physical.max(previous + 1).into()
+    }
+
+    /// merge a timestamp from another node, so the following ones are newer than it
+    pub fn observe(&self, ts: Timestamp) {
+        self.last.fetch_max(ts.into(), Ordering::AcqRel);
+    }
+
+    /// the milliseconds since the unix epoch `ts` was produced at
+    pub fn physical_millis(ts: Timestamp) -> u64 {
+        u64::from(ts) >> LOGICAL_BITS
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    use super::HybridClock;
+
+    #[test]
+    fn monotonic() {
+        let clock = HybridClock::new();
+        let before = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+
+        let first = clock.now();
+        let second = clock.now();
+        assert!(second > first);
+        assert!(HybridClock::physical_millis(first) >= before);
+
+        let ahead = (u64::from(second) + (1 << 20)).into();
+        clock.observe(ahead);
+        assert!(clock.now() > ahead);
+    }
+}
@@ -1,35 +1,42 @@
+#[cfg(not(target_arch = "wasm32"))]
+mod hlc;
 pub mod timestamped;
 
 use arrow::{
     array::{PrimitiveArray, Scalar},
-    datatypes::UInt32Type,
+    datatypes::UInt64Type,
 };
 use fusio::{SeqRead, Write};
 
+#[cfg(not(target_arch = "wasm32"))]
+pub use self::hlc::HybridClock;
 pub(crate) use self::timestamped::*;
 use crate::serdes::{Decode, Encode};
 
+/// The version of a record, stored in the `UInt64` `_ts` column.
+///
+/// SSTs, version logs and WALs written while timestamps were 32 bits are still read.
 #[repr(transparent)]
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
-pub struct Timestamp(u32);
+pub struct Timestamp(u64);
 
 pub(crate) const EPOCH: Timestamp = Timestamp(0);
 
-impl From<u32> for Timestamp {
-    fn from(ts: u32) -> Self {
+impl From<u64> for Timestamp {
+    fn from(ts: u64) -> Self {
         Self(ts)
     }
 }
 
-impl From<Timestamp> for u32 {
+impl From<Timestamp> for u64 {
     fn from(value: Timestamp) -> Self {
         value.0
     }
 }
 
 impl Timestamp {
-    pub(crate) fn to_arrow_scalar(self) -> Scalar<PrimitiveArray<UInt32Type>> {
-        PrimitiveArray::<UInt32Type>::new_scalar(self.0)
+    pub(crate) fn to_arrow_scalar(self) -> Scalar<PrimitiveArray<UInt64Type>> {
+        PrimitiveArray::<UInt64Type>::new_scalar(self.0)
     }
 }
 
@@ -41,6 +48,13 @@ impl Encode for Timestamp {
     {
         self.0.encode(writer).await
     }
+
+    async fn encode_compact<W>(&self, writer: &mut W) -> Result<(), Self::Error>
+    where
+        W: Write,
+    {
+        self.0.encode_compact(writer).await
+    }
     fn size(&self) -> usize {
         self.0.size()
     }
@@ -51,6 +65,13 @@ impl Decode for Timestamp {
     where
         R: SeqRead,
     {
-        u32::decode(reader).await.map(Timestamp)
+        u64::decode(reader).await.map(Timestamp)
+    }
+
+    async fn decode_compact<R>(reader: &mut R) -> Result<Self, Self::Error>
+    where
+        R: SeqRead,
+    {
+        u64::decode_compact(reader).await.map(Timestamp)
     }
 }
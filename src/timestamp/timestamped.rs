@@ -1,9 +1,14 @@
-use std::{borrow::Borrow, cmp::Ordering, marker::PhantomData, mem::size_of, ptr};
+use std::{
+    borrow::Borrow,
+    cmp::Ordering,
+    mem::size_of,
+    ops::{Bound, RangeBounds},
+};
 
 use fusio::{SeqRead, Write};
 
 use crate::{
-    serdes::{Decode, Encode},
+    serdes::{decode_with, BytesReader, Decode, Encode},
     timestamp::Timestamp,
 };
 
@@ -47,7 +52,7 @@ where
     V: Encode,
 {
     pub(crate) fn size(&self) -> usize {
-        self.value.size() + size_of::<u32>()
+        self.value.size() + size_of::<u64>()
     }
 }
 
@@ -56,8 +61,7 @@ where
     V: PartialOrd,
 {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        TimestampedRef::new(&self.value, self.ts)
-            .partial_cmp(TimestampedRef::new(&other.value, other.ts))
+        partial_cmp_keys(self, other)
     }
 }
 
@@ -66,79 +70,177 @@ where
     V: Ord,
 {
     fn cmp(&self, other: &Self) -> Ordering {
-        TimestampedRef::new(&self.value, self.ts).cmp(TimestampedRef::new(&other.value, other.ts))
+        cmp_keys(self, other)
     }
 }
 
-#[derive(Debug)]
-#[repr(transparent)]
-pub(crate) struct TimestampedRef<V> {
-    _marker: PhantomData<V>,
-    _mem: [()],
+/// A key and its timestamp, ordered by the key and then from the newest timestamp. The maps
+/// keyed by [`Timestamped`] are searched through `dyn TimestampedKey` with a [`TimestampedRef`],
+/// which borrows the key instead of owning it
+pub(crate) trait TimestampedKey<V> {
+    fn value(&self) -> &V;
+
+    fn ts(&self) -> Timestamp;
+}
+
+fn partial_cmp_keys<V: PartialOrd>(
+    this: &(impl TimestampedKey<V> + ?Sized),
+    other: &(impl TimestampedKey<V> + ?Sized),
+) -> Option<Ordering> {
+    this.value()
+        .partial_cmp(other.value())
+        .map(|ordering| ordering.then_with(|| other.ts().cmp(&this.ts())))
 }
 
-impl<V> TimestampedRef<V> {
-    pub(crate) fn new(value: &V, ts: Timestamp) -> &Self {
-        let value = value as *const _ as usize;
-        let ts: u32 = ts.into();
+fn cmp_keys<V: Ord>(
+    this: &(impl TimestampedKey<V> + ?Sized),
+    other: &(impl TimestampedKey<V> + ?Sized),
+) -> Ordering {
+    this.value()
+        .cmp(other.value())
+        .then_with(|| other.ts().cmp(&this.ts()))
+}
 
-        let mem = ptr::slice_from_raw_parts(value as *const V, ts as usize) as *const Self;
-        unsafe { &*mem }
+impl<V> TimestampedKey<V> for Timestamped<V> {
+    fn value(&self) -> &V {
+        &self.value
     }
 
-    fn to_timestamped(&self) -> (&V, Timestamp) {
-        let i = self as *const TimestampedRef<V> as *const [()];
-        unsafe { (&*(i as *const ()).cast::<V>(), (i.len() as u32).into()) }
+    fn ts(&self) -> Timestamp {
+        self.ts
     }
+}
 
-    pub(crate) fn value(&self) -> &V {
-        self.to_timestamped().0
+impl<'a, V: 'a> Borrow<dyn TimestampedKey<V> + 'a> for Timestamped<V> {
+    fn borrow(&self) -> &(dyn TimestampedKey<V> + 'a) {
+        self
     }
+}
 
-    pub(crate) fn ts(&self) -> Timestamp {
-        self.to_timestamped().1
+impl<V> PartialEq for dyn TimestampedKey<V> + '_
+where
+    V: PartialEq,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.value() == other.value() && self.ts() == other.ts()
     }
 }
 
-impl<Q, V> Borrow<TimestampedRef<Q>> for Timestamped<V>
+impl<V> Eq for dyn TimestampedKey<V> + '_ where V: Eq {}
+
+impl<V> PartialOrd for dyn TimestampedKey<V> + '_
 where
-    V: Borrow<Q>,
+    V: PartialOrd,
 {
-    fn borrow(&self) -> &TimestampedRef<Q> {
-        TimestampedRef::new(self.value.borrow(), self.ts)
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        partial_cmp_keys(self, other)
     }
 }
 
-impl<V> PartialEq for TimestampedRef<V>
+impl<V> Ord for dyn TimestampedKey<V> + '_
+where
+    V: Ord,
+{
+    fn cmp(&self, other: &Self) -> Ordering {
+        cmp_keys(self, other)
+    }
+}
+
+/// A borrowed key with its timestamp, see [`TimestampedKey`]
+#[derive(Debug)]
+pub(crate) struct TimestampedRef<'a, V> {
+    value: &'a V,
+    ts: Timestamp,
+}
+
+impl<V> Clone for TimestampedRef<'_, V> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<V> Copy for TimestampedRef<'_, V> {}
+
+impl<'a, V> TimestampedRef<'a, V> {
+    pub(crate) fn new(value: &'a V, ts: Timestamp) -> Self {
+        TimestampedRef { value, ts }
+    }
+
+    pub(crate) fn value(&self) -> &'a V {
+        self.value
+    }
+
+    pub(crate) fn ts(&self) -> Timestamp {
+        self.ts
+    }
+}
+
+impl<V> TimestampedKey<V> for TimestampedRef<'_, V> {
+    fn value(&self) -> &V {
+        self.value
+    }
+
+    fn ts(&self) -> Timestamp {
+        self.ts
+    }
+}
+
+impl<V> PartialEq for TimestampedRef<'_, V>
 where
     V: PartialEq,
 {
     fn eq(&self, other: &Self) -> bool {
-        self.value() == other.value() && self.ts() == other.ts()
+        self.value == other.value && self.ts == other.ts
     }
 }
 
-impl<V> Eq for TimestampedRef<V> where V: Eq {}
+impl<V> Eq for TimestampedRef<'_, V> where V: Eq {}
 
-impl<V> PartialOrd<Self> for TimestampedRef<V>
+impl<V> PartialOrd for TimestampedRef<'_, V>
 where
     V: PartialOrd,
 {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        self.value()
-            .partial_cmp(other.value())
-            .map(|ordering| ordering.then_with(|| other.ts().cmp(&self.ts())))
+        partial_cmp_keys(self, other)
     }
 }
 
-impl<K> Ord for TimestampedRef<K>
+impl<V> Ord for TimestampedRef<'_, V>
 where
-    K: Ord,
+    V: Ord,
 {
     fn cmp(&self, other: &Self) -> Ordering {
-        self.value()
-            .cmp(other.value())
-            .then_with(|| other.ts().cmp(&self.ts()))
+        cmp_keys(self, other)
+    }
+}
+
+/// The bounds of a range of the maps keyed by [`Timestamped`], searched as
+/// `dyn TimestampedKey`
+pub(crate) struct TimestampedRange<'a, V> {
+    lower: Bound<TimestampedRef<'a, V>>,
+    upper: Bound<TimestampedRef<'a, V>>,
+}
+
+impl<'a, V> TimestampedRange<'a, V> {
+    pub(crate) fn new(
+        lower: Bound<TimestampedRef<'a, V>>,
+        upper: Bound<TimestampedRef<'a, V>>,
+    ) -> Self {
+        TimestampedRange { lower, upper }
+    }
+}
+
+impl<'a, V: 'a> RangeBounds<dyn TimestampedKey<V> + 'a> for TimestampedRange<'a, V> {
+    fn start_bound(&self) -> Bound<&(dyn TimestampedKey<V> + 'a)> {
+        self.lower
+            .as_ref()
+            .map(|key| key as &(dyn TimestampedKey<V> + 'a))
+    }
+
+    fn end_bound(&self) -> Bound<&(dyn TimestampedKey<V> + 'a)> {
+        self.upper
+            .as_ref()
+            .map(|key| key as &(dyn TimestampedKey<V> + 'a))
     }
 }
 
@@ -156,6 +258,14 @@ where
         self.value.encode(writer).await
     }
 
+    async fn encode_compact<W>(&self, writer: &mut W) -> Result<(), Self::Error>
+    where
+        W: Write,
+    {
+        self.ts.encode_compact(writer).await?;
+        self.value.encode_compact(writer).await
+    }
+
     fn size(&self) -> usize {
         self.ts.size() + self.value.size()
     }
@@ -175,58 +285,94 @@ where
         let value = V::decode(reader).await?;
         Ok(Timestamped::new(value, ts))
     }
+
+    async fn decode_compact<R>(reader: &mut R) -> Result<Self, Self::Error>
+    where
+        R: SeqRead,
+    {
+        let ts = Timestamp::decode_compact(reader).await?;
+        let value = V::decode_compact(reader).await?;
+        Ok(Timestamped::new(value, ts))
+    }
+
+    async fn decode_buffered(reader: &mut BytesReader, compact: bool) -> Result<Self, Self::Error> {
+        let ts = decode_with::<Timestamp, _>(reader, compact).await?;
+        let value = V::decode_buffered(reader, compact).await?;
+        Ok(Timestamped::new(value, ts))
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{Timestamped, TimestampedRef};
+    use std::{collections::BTreeMap, ops::Bound};
+
+    use super::{Timestamped, TimestampedKey, TimestampedRange, TimestampedRef};
 
     #[test]
     fn test_value_cmp() {
-        let value1 = Timestamped::new(&1, 1_u32.into());
-        let value2 = Timestamped::new(&2, 2_u32.into());
+        let value1 = Timestamped::new(&1, 1_u64.into());
+        let value2 = Timestamped::new(&2, 2_u64.into());
         assert!(value1 < value2);
 
-        let value1 = Timestamped::new(&1, 1_u32.into());
-        let value2 = Timestamped::new(&1, 2_u32.into());
+        let value1 = Timestamped::new(&1, 1_u64.into());
+        let value2 = Timestamped::new(&1, 2_u64.into());
         assert!(value1 > value2);
     }
 
     #[test]
     fn test_value_eq() {
-        let value1 = Timestamped::new(&1, 1_u32.into());
-        let value2 = Timestamped::new(&1, 1_u32.into());
+        let value1 = Timestamped::new(&1, 1_u64.into());
+        let value2 = Timestamped::new(&1, 1_u64.into());
         assert_eq!(value1, value2);
 
-        let value1 = Timestamped::new(&1, 1_u32.into());
-        let value2 = Timestamped::new(&2, 1_u32.into());
+        let value1 = Timestamped::new(&1, 1_u64.into());
+        let value2 = Timestamped::new(&2, 1_u64.into());
         assert_ne!(value1, value2);
 
-        let value1 = Timestamped::new(&1, 1_u32.into());
-        let value2 = Timestamped::new(&1, 2_u32.into());
+        let value1 = Timestamped::new(&1, 1_u64.into());
+        let value2 = Timestamped::new(&1, 2_u64.into());
         assert_ne!(value1, value2);
     }
 
     #[test]
     fn test_timestamped_ref() {
-        let value = Timestamped::new(1, 1_u32.into());
+        let value = Timestamped::new(1, 1_u64.into());
         let value_ref = TimestampedRef::new(&value.value, value.ts);
         assert_eq!(value_ref.value(), &1);
-        assert_eq!(value_ref.ts(), 1_u32.into());
+        assert_eq!(value_ref.ts(), 1_u64.into());
     }
 
     #[test]
     fn test_timestamped_ref_cmp() {
-        let value1 = Timestamped::new(1, 1_u32.into());
-        let value2 = Timestamped::new(2, 2_u32.into());
+        let value1 = Timestamped::new(1, 1_u64.into());
+        let value2 = Timestamped::new(2, 2_u64.into());
         let value_ref1 = TimestampedRef::new(&value1.value, value1.ts);
         let value_ref2 = TimestampedRef::new(&value2.value, value2.ts);
         assert!(value_ref1 < value_ref2);
 
-        let value1 = Timestamped::new(1, 1_u32.into());
-        let value2 = Timestamped::new(1, 2_u32.into());
+        let value1 = Timestamped::new(1, 1_u64.into());
+        let value2 = Timestamped::new(1, 2_u64.into());
         let value_ref1 = TimestampedRef::new(&value1.value, value1.ts);
         let value_ref2 = TimestampedRef::new(&value2.value, value2.ts);
         assert!(value_ref1 > value_ref2);
     }
+
+    #[test]
+    fn test_timestamped_range() {
+        // the timestamps of a hybrid logical clock are past `u32::MAX`
+        let base = 1_u64 << 40;
+        let map: BTreeMap<_, _> = [(1, base), (1, base + 1), (1, base + 2), (2, base)]
+            .into_iter()
+            .map(|(key, ts)| (Timestamped::new(key, ts.into()), ts))
+            .collect();
+
+        let newest = map
+            .range::<dyn TimestampedKey<i32>, _>(TimestampedRange::new(
+                Bound::Included(TimestampedRef::new(&1, (base + 1).into())),
+                Bound::Included(TimestampedRef::new(&1, 0_u64.into())),
+            ))
+            .map(|(_, ts)| *ts)
+            .collect::<Vec<_>>();
+        assert_eq!(newest, vec![base + 1, base]);
+    }
 }
@@ -3,18 +3,25 @@ use std::{
         btree_map::{Entry, Range},
         BTreeMap, Bound,
     },
+    error::Error,
     io,
+    io::Cursor,
     mem::transmute,
+    sync::{Arc, Mutex},
 };
 
 use flume::SendError;
-use lockable::AsyncLimit;
 use parquet::{arrow::ProjectionMask, errors::ParquetError};
 use thiserror::Error;
 
 use crate::{
+    check_write,
     compaction::CompactTask,
+    is_transient,
+    lock::{KeyLock, LockError, TxnId},
+    rate_limit::RateLimiter,
     record::{Key, KeyRef},
+    serdes::{Decode, Encode},
     snapshot::Snapshot,
     stream,
     stream::mem_projection::MemProjectionStream,
@@ -40,6 +47,24 @@ where
             .map(|(key, value)| (Timestamped::new(key.as_key_ref(), self.ts), value))
     }
 }
+/// how much a [`Transaction`] is isolated from the ones committing alongside it, see
+/// [`DB::transaction_with`](crate::DB::transaction_with)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IsolationLevel {
+    /// every read sees the records committed before it, and the commit writes without taking
+    /// the key locks or checking for conflicts so the last writer wins. [`Transaction::put_if`]
+    /// and [`Transaction::commit_at`] still lock the keys to check atomically
+    ReadCommitted,
+    /// the reads see a snapshot taken on open, and the commit fails with
+    /// [`CommitError::WriteConflict`] on a key written by another transaction since
+    #[default]
+    Snapshot,
+    /// snapshot isolation, with the commit also failing with [`CommitError::ReadConflict`] on a
+    /// key or range read that another transaction wrote since, see
+    /// [`DB::serializable_transaction`](crate::DB::serializable_transaction)
+    Serializable,
+}
+
 /// optimistic ACID transaction, open with
 /// [`DB::transaction`](crate::DB::transaction) method
 pub struct Transaction<'txn, R>
@@ -47,8 +72,39 @@ where
     R: Record,
 {
     local: BTreeMap<R::Key, Option<R>>,
+    /// the bytes of the keys and records in `local`
+    size: usize,
+    conditions: BTreeMap<R::Key, Option<R>>,
     snapshot: Snapshot<'txn, R>,
     lock_map: LockMap<R::Key>,
+    txn: TxnId,
+    /// the keys locked by [`Transaction::lock`], with the timestamp they were locked at
+    locks: BTreeMap<R::Key, (Timestamp, KeyLock<R::Key>)>,
+    isolation: IsolationLevel,
+    reads: Option<ReadSet<R::Key>>,
+    /// the previous values of the writes, kept from the first [`Transaction::savepoint`] on
+    undo: Option<Vec<Undo<R>>>,
+    /// charged with the writes once committed
+    rate_limiter: Option<Arc<RateLimiter>>,
+}
+
+/// a point in the writes of a [`Transaction`] to roll back to, see [`Transaction::savepoint`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Savepoint(usize);
+
+enum Undo<R>
+where
+    R: Record,
+{
+    Local(R::Key, Option<Option<R>>),
+    Condition(R::Key, Option<Option<R>>),
+}
+
+/// the key ranges read by a serializable transaction, see
+/// [`DB::serializable_transaction`](crate::DB::serializable_transaction)
+struct ReadSet<K> {
+    ranges: Mutex<Vec<(Bound<K>, Bound<K>)>>,
+    commit_lock: Arc<async_lock::Mutex<()>>,
 }
 
 impl<'txn, R> Transaction<'txn, R>
@@ -58,11 +114,106 @@ where
     pub(crate) fn new(snapshot: Snapshot<'txn, R>, lock_map: LockMap<R::Key>) -> Self {
         Self {
             local: BTreeMap::new(),
+            size: 0,
+            conditions: BTreeMap::new(),
             snapshot,
+            txn: lock_map.next_txn(),
             lock_map,
+            locks: BTreeMap::new(),
+            isolation: IsolationLevel::Snapshot,
+            reads: None,
+            undo: None,
+            rate_limiter: None,
+        }
+    }
+
+    pub(crate) fn read_committed(self) -> Self {
+        Self {
+            isolation: IsolationLevel::ReadCommitted,
+            ..self
+        }
+    }
+
+    pub(crate) fn rate_limited(self, rate_limiter: Arc<RateLimiter>) -> Self {
+        Self {
+            rate_limiter: Some(rate_limiter),
+            ..self
         }
     }
 
+    pub(crate) fn serializable(self, commit_lock: Arc<async_lock::Mutex<()>>) -> Self {
+        Self {
+            isolation: IsolationLevel::Serializable,
+            reads: Some(ReadSet {
+                ranges: Mutex::new(Vec::new()),
+                commit_lock,
+            }),
+            ..self
+        }
+    }
+
+    /// the isolation level the transaction was opened with
+    pub fn isolation(&self) -> IsolationLevel {
+        self.isolation
+    }
+
+    /// the timestamp the reads see, the latest commit under [`IsolationLevel::ReadCommitted`]
+    fn read_ts(&self) -> Timestamp {
+        match self.isolation {
+            IsolationLevel::ReadCommitted => self.snapshot.load_ts(),
+            IsolationLevel::Snapshot | IsolationLevel::Serializable => self.snapshot.ts(),
+        }
+    }
+
+    /// the timestamp the reads of `key` see, the one it was locked at when newer
+    fn key_read_ts(&self, key: &R::Key) -> Timestamp {
+        let ts = self.read_ts();
+        self.locks
+            .get(key)
+            .map_or(ts, |(locked, _)| ts.max(*locked))
+    }
+
+    fn track_read(&self, range: (Bound<&R::Key>, Bound<&R::Key>)) {
+        if let Some(reads) = &self.reads {
+            reads
+                .ranges
+                .lock()
+                .unwrap()
+                .push((range.0.cloned(), range.1.cloned()));
+        }
+    }
+
+    /// read the records as they were at the earlier timestamp `ts`, see
+    /// [`Snapshot::read_at`](crate::snapshot::Snapshot::read_at). The commit then fails with a
+    /// write conflict on any key written since `ts`.
+    pub fn read_at(&mut self, ts: Timestamp) -> Result<(), DbError<R>> {
+        self.snapshot.read_at(ts)
+    }
+
+    /// lock `key` until the transaction is committed or dropped, like `SELECT ... FOR UPDATE`.
+    /// The others committing `key` wait for the lock meanwhile, so [`Transaction::get`] of it
+    /// sees its latest record and the commit does not fail with a write conflict on it. Lock the
+    /// keys in the same order across transactions, a wait that would deadlock fails with
+    /// [`CommitError::Deadlock`] and the transaction should be dropped then
+    pub async fn lock(&mut self, key: R::Key) -> Result<(), CommitError<R>> {
+        if self.locks.contains_key(&key) {
+            return Ok(());
+        }
+        let guard = self.lock_key(&key).await?;
+        self.locks.insert(key, (self.snapshot.load_ts(), guard));
+        Ok(())
+    }
+
+    async fn lock_key(&self, key: &R::Key) -> Result<KeyLock<R::Key>, CommitError<R>> {
+        self.lock_map
+            .lock(self.txn, key.clone())
+            .await
+            .map_err(|err| match err {
+                LockError::Deadlock => CommitError::Deadlock(key.clone()),
+                LockError::Timeout => CommitError::LockTimeout(key.clone()),
+            })
+    }
+
     /// get the record with `key` as the primary key and get only the data specified in
     /// [`Projection`]
     pub async fn get<'get>(
@@ -70,11 +221,14 @@ where
         key: &'get R::Key,
         projection: Projection,
     ) -> Result<Option<TransactionEntry<'get, R>>, DbError<R>> {
-        Ok(match self.local.get(key).and_then(|v| v.as_ref()) {
-            Some(v) => Some(TransactionEntry::Local(v.as_record_ref())),
+        self.track_read((Bound::Included(key), Bound::Included(key)));
+        Ok(match self.local.get(key) {
+            Some(Some(v)) => Some(TransactionEntry::Local(v.as_record_ref())),
+            // removed by the transaction
+            Some(None) => None,
             None => self
                 .snapshot
-                .get(key, projection)
+                .get_at(key, projection, self.key_read_ts(key))
                 .await?
                 .map(TransactionEntry::Stream),
         })
@@ -85,16 +239,20 @@ where
         &'scan self,
         range: (Bound<&'range R::Key>, Bound<&'range R::Key>),
     ) -> Scan<'scan, 'range, R> {
-        let ts = self.snapshot.ts();
+        self.track_read(range);
+        let ts = self.read_ts();
         let inner = self.local.range(range);
+        // the local writes are the first stream merged, so they take precedence over a committed
+        // version at the same timestamp and their removes hide the committed records
         self.snapshot._scan(
             range,
+            ts,
             Box::new(move |projection_mask: Option<ProjectionMask>| {
                 let mut transaction_scan = TransactionScan { inner, ts }.into();
                 if let Some(mask) = projection_mask {
                     transaction_scan = MemProjectionStream::new(transaction_scan, mask).into();
                 }
-                Some(transaction_scan)
+                transaction_scan
             }),
         )
     }
@@ -109,36 +267,222 @@ where
         self.entry(key, None)
     }
 
+    /// write `new` (or delete the record when `new` is `None`) on this transaction only if the
+    /// current value of `key` is `expected` (or absent when `expected` is `None`), the check is
+    /// done on commit and fails with [`CommitError::ConditionFailed`] carrying the actual value
+    pub fn put_if(&mut self, key: R::Key, expected: Option<R>, new: Option<R>) {
+        let previous = self.conditions.insert(key.clone(), expected);
+        if let Some(undo) = &mut self.undo {
+            undo.push(Undo::Condition(key.clone(), previous));
+        }
+        self.entry(key, new)
+    }
+
+    /// the bytes of the writes buffered by the transaction, not tracked with
+    /// [`DbOption::unchecked_writes`](crate::DbOption::unchecked_writes). The commit fails with
+    /// [`CommitError::TooLarge`] while it exceeds
+    /// [`DbOption::max_transaction_size`](crate::DbOption::max_transaction_size), so a transaction
+    /// may check it to roll back or stop writing early
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    fn entry_size(key: &R::Key, value: &Option<R>) -> usize {
+        key.size() + value.as_ref().map_or(0, Record::size)
+    }
+
+    /// whether the writes are committed without the key locks, conflict checks and size
+    /// tracking, see [`DbOption::unchecked_writes`](crate::DbOption::unchecked_writes)
+    fn is_unchecked(&self) -> bool {
+        self.snapshot.version().option().unchecked_writes
+    }
+
     fn entry(&mut self, key: R::Key, value: Option<R>) {
-        match self.local.entry(key) {
-            Entry::Vacant(v) => {
-                v.insert(value);
+        if !self.is_unchecked() {
+            let replaced = self
+                .local
+                .get(&key)
+                .map_or(0, |replaced| Self::entry_size(&key, replaced));
+            self.size = self.size - replaced + Self::entry_size(&key, &value);
+        }
+
+        match &mut self.undo {
+            Some(undo) => {
+                let previous = self.local.insert(key.clone(), value);
+                undo.push(Undo::Local(key, previous));
+            }
+            None => match self.local.entry(key) {
+                Entry::Vacant(v) => {
+                    v.insert(value);
+                }
+                Entry::Occupied(mut o) => *o.get_mut() = value,
+            },
+        }
+    }
+
+    /// mark the writes so far, for [`Transaction::rollback_to`] to undo the later ones
+    pub fn savepoint(&mut self) -> Savepoint {
+        Savepoint(self.undo.get_or_insert_with(Vec::new).len())
+    }
+
+    /// undo the inserts, removes and [`Transaction::put_if`]s since `savepoint`, keeping the
+    /// locks and reads of the transaction. It does nothing for a savepoint taken after the one
+    /// last rolled back to
+    pub fn rollback_to(&mut self, savepoint: Savepoint) {
+        let is_unchecked = self.is_unchecked();
+        let Some(undo) = &mut self.undo else {
+            return;
+        };
+        while undo.len() > savepoint.0 {
+            match undo.pop().unwrap() {
+                Undo::Local(key, previous) => {
+                    if let (false, Some(value)) = (is_unchecked, self.local.get(&key)) {
+                        self.size -= Self::entry_size(&key, value);
+                    }
+                    match previous {
+                        Some(value) => {
+                            if !is_unchecked {
+                                self.size += Self::entry_size(&key, &value);
+                            }
+                            self.local.insert(key, value);
+                        }
+                        None => {
+                            self.local.remove(&key);
+                        }
+                    }
+                }
+                Undo::Condition(key, Some(expected)) => {
+                    self.conditions.insert(key, expected);
+                }
+                Undo::Condition(key, None) => {
+                    self.conditions.remove(&key);
+                }
             }
-            Entry::Occupied(mut o) => *o.get_mut() = value,
         }
     }
 
     /// commit the data in the [`Transaction`] to the corresponding
     /// [`DB`](crate::DB)
-    pub async fn commit(mut self) -> Result<(), CommitError<R>> {
-        let mut _key_guards = Vec::new();
+    pub async fn commit(self) -> Result<(), CommitError<R>> {
+        self.commit_with(None).await
+    }
 
-        for (key, _) in self.local.iter() {
-            // SAFETY: Error is Never
-            _key_guards.push(
-                self.lock_map
-                    .async_lock(key.clone(), AsyncLimit::no_limit())
-                    .await
-                    .unwrap(),
-            );
+    /// commit the data in the [`Transaction`] with the timestamp `ts` from a clock of the
+    /// application instead of one allocated by the [`DB`](crate::DB). It fails with
+    /// [`CommitError::StaleTimestamp`] unless `ts` is newer than every version of the keys
+    /// written, and the timestamps allocated afterwards are newer than `ts`.
+    pub async fn commit_at(self, ts: Timestamp) -> Result<(), CommitError<R>> {
+        self.commit_with(Some(ts)).await
+    }
+
+    /// commit, then wait out the rate limits for the writes once the snapshot is released, so
+    /// that a throttled transaction holds back none of the others
+    async fn commit_with(self, ts: Option<Timestamp>) -> Result<(), CommitError<R>> {
+        let rate_limiter = self.rate_limiter.clone();
+        let records = self.local.len();
+        // the size of unchecked writes is not tracked, they are only sized for a limit of bytes
+        let bytes = match &rate_limiter {
+            Some(rate_limiter) if self.is_unchecked() && rate_limiter.limits_bytes() => self
+                .local
+                .iter()
+                .map(|(key, value)| Self::entry_size(key, value))
+                .sum(),
+            _ => self.size,
+        };
+        self.write(ts).await?;
+        if let Some(rate_limiter) = rate_limiter.filter(|_| records > 0) {
+            rate_limiter.acquire(records, bytes).await;
+        }
+        Ok(())
+    }
+
+    async fn write(mut self, ts: Option<Timestamp>) -> Result<(), CommitError<R>> {
+        let option = self.snapshot.version().option();
+        if option
+            .max_transaction_size
+            .is_some_and(|max| self.size > max)
+        {
+            return Err(CommitError::TooLarge(self.size));
         }
-        for (key, _) in self.local.iter() {
-            if self
+        for (key, value) in self.local.iter() {
+            check_write(option, key.as_key_ref(), value.as_ref())?;
+        }
+        let mut _key_guards = Vec::new();
+        let is_read_committed =
+            self.isolation == IsolationLevel::ReadCommitted || self.is_unchecked();
+
+        if !is_read_committed || !self.conditions.is_empty() || ts.is_some() {
+            for key in self
+                .local
+                .keys()
+                .filter(|key| !self.locks.contains_key(key))
+            {
+                _key_guards.push(self.lock_key(key).await?);
+            }
+        }
+        if !is_read_committed {
+            for (key, _) in self.local.iter() {
+                if self.snapshot.is_written(key, self.key_read_ts(key)).await? {
+                    return Err(CommitError::WriteConflict(key.clone()));
+                }
+            }
+        }
+        // a read only transaction is serialized at its snapshot
+        let mut _commit_guard = None;
+        if let Some(reads) = self.reads.as_ref().filter(|_| !self.local.is_empty()) {
+            _commit_guard = Some(reads.commit_lock.lock().await);
+
+            let ranges = reads.ranges.lock().unwrap().clone();
+            for (lower, upper) in ranges {
+                if self
+                    .snapshot
+                    .is_range_written((lower.as_ref(), upper.as_ref()))
+                    .await?
+                {
+                    return Err(CommitError::ReadConflict(lower, upper));
+                }
+            }
+        }
+        if let Some(ts) = ts {
+            for key in self.local.keys() {
+                match self.snapshot.latest_ts(key).await? {
+                    Some(latest) if latest >= ts => {
+                        return Err(CommitError::StaleTimestamp {
+                            key: key.clone(),
+                            ts,
+                            latest,
+                        })
+                    }
+                    _ => (),
+                }
+            }
+        }
+        for (key, expected) in self.conditions.iter() {
+            let actual = match self
                 .snapshot
-                .schema()
-                .check_conflict(key, self.snapshot.ts())
+                .get_at(key, Projection::All, self.key_read_ts(key))
+                .await?
             {
-                return Err(CommitError::WriteConflict(key.clone()));
+                Some(entry) => Some(Self::encode_record(entry.value().unwrap()).await?),
+                None => None,
+            };
+            let expected = match expected {
+                Some(record) => Some(Self::encode_record(record.as_record_ref()).await?),
+                None => None,
+            };
+            if actual != expected {
+                let actual = match actual {
+                    Some(mut bytes) => Some(
+                        R::decode(&mut Cursor::new(&mut bytes))
+                            .await
+                            .map_err(|err| CommitError::Serde(Box::new(err)))?,
+                    ),
+                    None => None,
+                };
+                return Err(CommitError::ConditionFailed {
+                    key: key.clone(),
+                    actual,
+                });
             }
         }
 
@@ -146,12 +490,12 @@ where
         let is_excess = match len {
             0 => false,
             1 => {
-                let new_ts = self.snapshot.increase_ts();
+                let new_ts = self.commit_ts(ts);
                 let (key, record) = self.local.pop_first().unwrap();
                 Self::append(self.snapshot.schema(), LogType::Full, key, record, new_ts).await?
             }
             _ => {
-                let new_ts = self.snapshot.increase_ts();
+                let new_ts = self.commit_ts(ts);
                 let mut iter = self.local.into_iter();
 
                 let (key, record) = iter.next().unwrap();
@@ -166,6 +510,7 @@ where
                 Self::append(self.snapshot.schema(), LogType::Last, key, record, new_ts).await?
             }
         };
+        self.snapshot.schema().commit_wal().await?;
         if is_excess {
             let _ = self
                 .snapshot
@@ -176,6 +521,25 @@ where
         Ok(())
     }
 
+    fn commit_ts(&self, ts: Option<Timestamp>) -> Timestamp {
+        match ts {
+            Some(ts) => {
+                self.snapshot.advance_ts(ts);
+                ts
+            }
+            None => self.snapshot.increase_ts(),
+        }
+    }
+
+    async fn encode_record(record: R::Ref<'_>) -> Result<Vec<u8>, CommitError<R>> {
+        let mut bytes = Vec::new();
+        record
+            .encode(&mut Cursor::new(&mut bytes))
+            .await
+            .map_err(|err| CommitError::Serde(Box::new(err)))?;
+        Ok(bytes)
+    }
+
     async fn append(
         schema: &Schema<R>,
         log_ty: LogType,
@@ -211,6 +575,15 @@ where
             }
         }
     }
+
+    /// the commit timestamp of the record, to compare with a later read or pass to
+    /// [`Transaction::commit_at`]. `None` for a write of the transaction itself
+    pub fn ts(&self) -> Option<Timestamp> {
+        match self {
+            TransactionEntry::Stream(entry) => entry.ts(),
+            TransactionEntry::Local(_) => None,
+        }
+    }
 }
 
 #[derive(Debug, Error)]
@@ -226,12 +599,49 @@ where
     Database(#[from] DbError<R>),
     #[error("transaction write conflict: {:?}", .0)]
     WriteConflict(R::Key),
+    #[error("transaction read conflict in {:?}..{:?}", .0, .1)]
+    ReadConflict(Bound<R::Key>, Bound<R::Key>),
+    #[error("transaction deadlocked waiting for the lock of {:?}", .0)]
+    Deadlock(R::Key),
+    #[error("transaction timed out waiting for the lock of {:?}", .0)]
+    LockTimeout(R::Key),
+    #[error("transaction writes exceed the limit, {} bytes were buffered", .0)]
+    TooLarge(usize),
+    #[error("transaction condition failed on {:?}, actual value: {:?}", .key, .actual)]
+    ConditionFailed { key: R::Key, actual: Option<R> },
+    #[error("transaction commit timestamp {:?} is not newer than {:?} of {:?}", .ts, .latest, .key)]
+    StaleTimestamp {
+        key: R::Key,
+        ts: Timestamp,
+        latest: Timestamp,
+    },
+    #[error("transaction record serde error {:?}", .0)]
+    Serde(Box<dyn Error + Send + Sync + 'static>),
     #[error("Failed to send compact task")]
     SendCompactTaskError(#[from] SendError<CompactTask>),
     #[error("Channel is closed")]
     ChannelClose,
 }
 
+impl<R> CommitError<R>
+where
+    R: Record,
+{
+    /// whether committing the transaction again from the start may succeed, as it does after
+    /// conflicting with concurrent transactions
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            CommitError::WriteConflict(_)
+            | CommitError::ReadConflict(..)
+            | CommitError::Deadlock(_)
+            | CommitError::LockTimeout(_) => true,
+            CommitError::Io(err) => is_transient(err),
+            CommitError::Database(err) => err.is_retryable(),
+            _ => false,
+        }
+    }
+}
+
 #[cfg(all(test, feature = "tokio"))]
 mod tests {
     use std::{collections::Bound, sync::Arc};
@@ -250,7 +660,7 @@ mod tests {
             ColumnDesc,
         },
         tests::{build_db, build_schema, Test},
-        transaction::CommitError,
+        transaction::{CommitError, IsolationLevel},
         version::TransactionTs,
         DbOption, Projection, DB,
     };
@@ -259,7 +669,7 @@ mod tests {
     async fn transaction_read_write() {
         let temp_dir = TempDir::new().unwrap();
 
-        let db = DB::<String, TokioExecutor>::new(
+        let db = DB::<String>::new(
             DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap()),
             TokioExecutor::new(),
         )
@@ -291,6 +701,532 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn transaction_entry_ts() {
+        let temp_dir = TempDir::new().unwrap();
+
+        let db = DB::<String>::new(
+            DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap()),
+            TokioExecutor::new(),
+        )
+        .await
+        .unwrap();
+        db.insert("foo".to_string()).await.unwrap();
+        let ts = db.snapshot().await.ts();
+
+        let mut txn = db.transaction().await;
+        let entry = txn
+            .get(&"foo".to_string(), Projection::All)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(entry.ts(), Some(ts));
+        drop(entry);
+        txn.insert("bar".to_string());
+        let entry = txn
+            .get(&"bar".to_string(), Projection::All)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(entry.ts(), None);
+        drop(entry);
+
+        let mut scan = txn
+            .scan((Bound::Unbounded, Bound::Unbounded))
+            .take()
+            .await
+            .unwrap();
+        let entries = [
+            scan.next().await.unwrap().unwrap().ts(),
+            scan.next().await.unwrap().unwrap().ts(),
+        ];
+        assert_eq!(entries, [None, Some(ts)]);
+    }
+
+    #[tokio::test]
+    async fn transaction_read_your_writes() {
+        let temp_dir = TempDir::new().unwrap();
+
+        let db: DB<Test> = DB::new(
+            DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap()),
+            TokioExecutor::new(),
+        )
+        .await
+        .unwrap();
+        for (key, vu32) in [("a", 1), ("c", 3)] {
+            db.insert(Test {
+                vstring: key.to_string(),
+                vu32,
+                vbool: None,
+            })
+            .await
+            .unwrap();
+        }
+        db.flush().await.unwrap();
+        // committed at the timestamp the transaction reads at
+        db.insert(Test {
+            vstring: "b".to_string(),
+            vu32: 2,
+            vbool: None,
+        })
+        .await
+        .unwrap();
+
+        let mut txn = db.transaction().await;
+        txn.insert(Test {
+            vstring: "b".to_string(),
+            vu32: 20,
+            vbool: None,
+        });
+        txn.remove("c".to_string());
+        txn.insert(Test {
+            vstring: "d".to_string(),
+            vu32: 4,
+            vbool: None,
+        });
+        txn.remove("e".to_string());
+
+        let mut scan = txn
+            .scan((Bound::Unbounded, Bound::Unbounded))
+            .take()
+            .await
+            .unwrap();
+        let mut entries = Vec::new();
+        while let Some(entry) = scan.next().await {
+            let entry = entry.unwrap();
+            entries.push((
+                entry.key().value.to_string(),
+                entry.value().map(|value| value.vu32.unwrap()),
+                entry.ts().is_none(),
+            ));
+        }
+        drop(scan);
+        assert_eq!(
+            entries,
+            vec![
+                ("a".to_string(), Some(1), false),
+                ("b".to_string(), Some(20), true),
+                ("c".to_string(), None, true),
+                ("d".to_string(), Some(4), true),
+                ("e".to_string(), None, true),
+            ]
+        );
+
+        let b = txn
+            .get(&"b".to_string(), Projection::All)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(b.get().vu32, Some(20));
+        drop(b);
+        assert!(txn
+            .get(&"c".to_string(), Projection::All)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn transaction_too_large() {
+        let temp_dir = TempDir::new().unwrap();
+
+        let db = DB::<String>::new(
+            DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap())
+                .max_transaction_size(64),
+            TokioExecutor::new(),
+        )
+        .await
+        .unwrap();
+
+        let mut txn = db.transaction().await;
+        txn.insert("foo".to_string());
+        let size = txn.size();
+        assert!(size > 0);
+        // replacing the record does not count it twice
+        txn.insert("foo".to_string());
+        assert_eq!(txn.size(), size);
+        let savepoint = txn.savepoint();
+        txn.insert("bar".to_string());
+        txn.rollback_to(savepoint);
+        assert_eq!(txn.size(), size);
+        txn.commit().await.unwrap();
+
+        let mut txn = db.transaction().await;
+        let savepoint = txn.savepoint();
+        txn.insert("a".repeat(64));
+        assert!(txn.size() > 64);
+        // the write is still seen by the transaction
+        assert!(txn
+            .get(&"a".repeat(64), Projection::All)
+            .await
+            .unwrap()
+            .is_some());
+        txn.rollback_to(savepoint);
+        assert_eq!(txn.size(), 0);
+        txn.insert("foo".to_string());
+        txn.commit().await.unwrap();
+
+        let mut txn = db.transaction().await;
+        txn.insert("a".repeat(64));
+        txn.insert("foo".to_string());
+        assert!(matches!(
+            txn.commit().await,
+            Err(CommitError::TooLarge(size)) if size > 64
+        ));
+    }
+
+    #[tokio::test]
+    async fn transaction_unchecked() {
+        let temp_dir = TempDir::new().unwrap();
+
+        let db = DB::<String>::new(
+            DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap())
+                .max_transaction_size(64)
+                .unchecked_writes(true),
+            TokioExecutor::new(),
+        )
+        .await
+        .unwrap();
+
+        let mut txn1 = db.transaction().await;
+        let mut txn2 = db.transaction().await;
+        txn1.insert("foo".to_string());
+        // the size is not tracked against the limit
+        txn1.insert("a".repeat(64));
+        assert_eq!(txn1.size(), 0);
+        let savepoint = txn1.savepoint();
+        txn1.insert("bar".to_string());
+        txn1.rollback_to(savepoint);
+        txn2.insert("foo".to_string());
+        txn2.commit().await.unwrap();
+        // no write conflict on "foo"
+        txn1.commit().await.unwrap();
+
+        let txn = db.transaction().await;
+        for key in ["foo".to_string(), "a".repeat(64)] {
+            assert!(txn.get(&key, Projection::All).await.unwrap().is_some());
+        }
+        assert!(txn
+            .get(&"bar".to_string(), Projection::All)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn transaction_lock() {
+        let temp_dir = TempDir::new().unwrap();
+
+        let db = DB::<String>::new(
+            DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap()),
+            TokioExecutor::new(),
+        )
+        .await
+        .unwrap();
+
+        let mut txn1 = db.transaction().await;
+        let mut txn2 = db.transaction().await;
+        txn2.insert("foo".to_string());
+        txn2.commit().await.unwrap();
+
+        txn1.lock("foo".to_string()).await.unwrap();
+        txn1.lock("foo".to_string()).await.unwrap();
+        assert!(txn1
+            .get(&"foo".to_string(), Projection::All)
+            .await
+            .unwrap()
+            .is_some());
+        txn1.remove("foo".to_string());
+        txn1.commit().await.unwrap();
+
+        let mut txn3 = db.transaction().await;
+        txn3.lock("foo".to_string()).await.unwrap();
+        assert!(txn3
+            .get(&"foo".to_string(), Projection::All)
+            .await
+            .unwrap()
+            .is_none());
+        drop(txn3);
+
+        // the lock is released on drop
+        let mut txn4 = db.transaction().await;
+        txn4.insert("foo".to_string());
+        txn4.commit().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn transaction_savepoint() {
+        let temp_dir = TempDir::new().unwrap();
+
+        let db = DB::<String>::new(
+            DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap()),
+            TokioExecutor::new(),
+        )
+        .await
+        .unwrap();
+        db.insert("foo".to_string()).await.unwrap();
+
+        let mut txn = db.transaction().await;
+        txn.insert("bar".to_string());
+        let savepoint = txn.savepoint();
+        txn.remove("bar".to_string());
+        txn.remove("foo".to_string());
+        txn.insert("baz".to_string());
+        let inner = txn.savepoint();
+        txn.put_if("qux".to_string(), Some("qux".to_string()), None);
+        txn.rollback_to(inner);
+        txn.insert("quux".to_string());
+        txn.rollback_to(savepoint);
+        // rolled back past it already
+        txn.rollback_to(inner);
+
+        for (key, exists) in [
+            ("foo", true),
+            ("bar", true),
+            ("baz", false),
+            ("quux", false),
+        ] {
+            assert_eq!(
+                txn.get(&key.to_string(), Projection::All)
+                    .await
+                    .unwrap()
+                    .is_some(),
+                exists
+            );
+        }
+        // the condition on qux was rolled back too
+        txn.commit().await.unwrap();
+
+        let txn = db.transaction().await;
+        assert!(txn
+            .get(&"bar".to_string(), Projection::All)
+            .await
+            .unwrap()
+            .is_some());
+    }
+
+    #[tokio::test]
+    async fn read_committed() {
+        let temp_dir = TempDir::new().unwrap();
+
+        let db = DB::<String>::new(
+            DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap()),
+            TokioExecutor::new(),
+        )
+        .await
+        .unwrap();
+
+        let mut txn1 = db.transaction_with(IsolationLevel::ReadCommitted).await;
+        let mut txn2 = db.transaction_with(IsolationLevel::ReadCommitted).await;
+        assert_eq!(txn1.isolation(), IsolationLevel::ReadCommitted);
+        assert!(txn1
+            .get(&"foo".to_string(), Projection::All)
+            .await
+            .unwrap()
+            .is_none());
+        txn1.insert("foo".to_string());
+        txn2.insert("foo".to_string());
+        txn2.commit().await.unwrap();
+
+        let mut scan = txn1
+            .scan((Bound::Unbounded, Bound::Unbounded))
+            .take()
+            .await
+            .unwrap();
+        assert_eq!(scan.next().await.unwrap().unwrap().ts(), None);
+        assert!(scan.next().await.is_none());
+        drop(scan);
+        // no write conflict with txn2
+        txn1.commit().await.unwrap();
+
+        let mut txn3 = db.transaction_with(IsolationLevel::ReadCommitted).await;
+        db.insert("bar".to_string()).await.unwrap();
+        assert!(txn3
+            .get(&"bar".to_string(), Projection::All)
+            .await
+            .unwrap()
+            .is_some());
+        txn3.remove("foo".to_string());
+        txn3.commit().await.unwrap();
+
+        let txn4 = db.transaction().await;
+        assert!(txn4
+            .get(&"foo".to_string(), Projection::All)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn serializable_write_skew() {
+        let temp_dir = TempDir::new().unwrap();
+
+        let db = DB::<String>::new(
+            DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap()),
+            TokioExecutor::new(),
+        )
+        .await
+        .unwrap();
+        {
+            let mut txn1 = db.transaction().await;
+            let mut txn2 = db.transaction().await;
+            assert!(txn1
+                .get(&"a".to_string(), Projection::All)
+                .await
+                .unwrap()
+                .is_none());
+            assert!(txn2
+                .get(&"b".to_string(), Projection::All)
+                .await
+                .unwrap()
+                .is_none());
+            txn1.insert("b".to_string());
+            txn2.insert("a".to_string());
+
+            txn1.commit().await.unwrap();
+            txn2.commit().await.unwrap();
+        }
+        {
+            let mut txn1 = db.serializable_transaction().await;
+            let mut txn2 = db.serializable_transaction().await;
+            assert!(txn1
+                .get(&"c".to_string(), Projection::All)
+                .await
+                .unwrap()
+                .is_none());
+            assert!(txn2
+                .get(&"d".to_string(), Projection::All)
+                .await
+                .unwrap()
+                .is_none());
+            txn1.insert("d".to_string());
+            txn2.insert("c".to_string());
+
+            txn1.commit().await.unwrap();
+            match txn2.commit().await {
+                Err(CommitError::ReadConflict(Bound::Included(lower), Bound::Included(upper))) => {
+                    assert_eq!(lower, "d");
+                    assert_eq!(upper, "d");
+                }
+                _ => panic!("expected a read conflict on d"),
+            }
+        }
+        {
+            let mut txn1 = db.serializable_transaction().await;
+            let mut txn2 = db.serializable_transaction().await;
+            let lower = "e".to_string();
+            let upper = "g".to_string();
+            let mut scan = txn1
+                .scan((Bound::Included(&lower), Bound::Excluded(&upper)))
+                .take()
+                .await
+                .unwrap();
+            assert!(scan.next().await.is_none());
+            drop(scan);
+            txn1.insert("z".to_string());
+            txn2.insert("f".to_string());
+
+            txn2.commit().await.unwrap();
+            assert!(matches!(
+                txn1.commit().await,
+                Err(CommitError::ReadConflict(..))
+            ));
+        }
+        {
+            let txn1 = db.serializable_transaction().await;
+            let mut txn2 = db.serializable_transaction().await;
+            assert!(txn1
+                .get(&"h".to_string(), Projection::All)
+                .await
+                .unwrap()
+                .is_none());
+            txn2.insert("h".to_string());
+
+            txn2.commit().await.unwrap();
+            txn1.commit().await.unwrap();
+        }
+        {
+            // read at a timestamp older than a write flushed since
+            let ts = db.snapshot().await.ts();
+            db.insert("i".to_string()).await.unwrap();
+            db.flush().await.unwrap();
+
+            let mut txn = db.serializable_transaction().await;
+            txn.read_at(ts).unwrap();
+            assert!(txn
+                .get(&"i".to_string(), Projection::All)
+                .await
+                .unwrap()
+                .is_none());
+            txn.insert("j".to_string());
+            assert!(matches!(
+                txn.commit().await,
+                Err(CommitError::ReadConflict(..))
+            ));
+
+            let mut txn = db.serializable_transaction().await;
+            txn.read_at(ts).unwrap();
+            assert!(txn
+                .get(&"k".to_string(), Projection::All)
+                .await
+                .unwrap()
+                .is_none());
+            txn.insert("j".to_string());
+            txn.commit().await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn transaction_put_if() {
+        let temp_dir = TempDir::new().unwrap();
+
+        let db = DB::<Test>::new(
+            DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap()),
+            TokioExecutor::new(),
+        )
+        .await
+        .unwrap();
+        let record = |vu32| Test {
+            vstring: "foo".to_string(),
+            vu32,
+            vbool: Some(true),
+        };
+
+        db.compare_and_swap("foo".to_string(), None, Some(record(0)))
+            .await
+            .unwrap();
+        match db
+            .compare_and_swap("foo".to_string(), None, Some(record(1)))
+            .await
+        {
+            Err(CommitError::ConditionFailed { key, actual }) => {
+                assert_eq!(key, "foo");
+                assert_eq!(actual, Some(record(0)));
+            }
+            _ => unreachable!(),
+        }
+        {
+            let mut txn = db.transaction().await;
+            txn.put_if("foo".to_string(), Some(record(2)), Some(record(3)));
+            match txn.commit().await {
+                Err(CommitError::ConditionFailed { actual, .. }) => {
+                    assert_eq!(actual, Some(record(0)));
+                }
+                _ => unreachable!(),
+            }
+        }
+        {
+            let mut txn = db.transaction().await;
+            txn.put_if("foo".to_string(), Some(record(0)), None);
+            txn.commit().await.unwrap();
+        }
+        assert!(db
+            .get(&"foo".to_string(), |_| Some(()))
+            .await
+            .unwrap()
+            .is_none());
+    }
+
     #[tokio::test]
     async fn transaction_get() {
         let temp_dir = TempDir::new().unwrap();
@@ -387,7 +1323,7 @@ mod tests {
         let temp_dir = TempDir::new().unwrap();
         let option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap());
 
-        let db = DB::<String, TokioExecutor>::new(option, TokioExecutor::new())
+        let db = DB::<String>::new(option, TokioExecutor::new())
             .await
             .unwrap();
 
@@ -407,7 +1343,9 @@ mod tests {
 
         txn_0.commit().await.unwrap();
 
-        if let Err(CommitError::WriteConflict(conflict_key)) = txn_1.commit().await {
+        let err = txn_1.commit().await.err();
+        assert!(err.as_ref().is_some_and(CommitError::is_retryable));
+        if let Some(CommitError::WriteConflict(conflict_key)) = err {
             assert_eq!(conflict_key, 1.to_string());
             txn_2.commit().await.unwrap();
             return;
@@ -420,9 +1358,7 @@ mod tests {
         let temp_dir = TempDir::new().unwrap();
         let option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap());
 
-        let db = DB::<Test, TokioExecutor>::new(option, TokioExecutor::new())
-            .await
-            .unwrap();
+        let db = DB::<Test>::new(option, TokioExecutor::new()).await.unwrap();
 
         let mut txn1 = db.transaction().await;
         txn1.insert(Test {
@@ -11,6 +11,10 @@ pub trait Trigger<R: Record>: fmt::Debug {
     fn item(&self, item: &Option<R>) -> bool;
 
     fn reset(&self);
+
+    /// the share of the threshold reached since the last reset, from 0 to 1 and past it until
+    /// the memtable is frozen
+    fn fill(&self) -> f64;
 }
 #[derive(Debug)]
 pub struct SizeOfMemTrigger<R> {
@@ -38,6 +42,10 @@ impl<R: Record> Trigger<R> for SizeOfMemTrigger<R> {
     fn reset(&self) {
         self.current_size.store(0, Ordering::SeqCst);
     }
+
+    fn fill(&self) -> f64 {
+        self.current_size.load(Ordering::SeqCst) as f64 / self.threshold.max(1) as f64
+    }
 }
 
 #[derive(Debug)]
@@ -65,6 +73,10 @@ impl<R: Record> Trigger<R> for LengthTrigger<R> {
     fn reset(&self) {
         self.count.store(0, Ordering::SeqCst);
     }
+
+    fn fill(&self) -> f64 {
+        self.count.load(Ordering::SeqCst) as f64 / self.threshold.max(1) as f64
+    }
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -151,6 +163,33 @@ mod tests {
             "Trigger should not be exceeded after reset"
         );
     }
+
+    #[test]
+    fn test_fill() {
+        let record = Some(Test {
+            vstring: "test".to_string(),
+            vu32: 0,
+            vbool: None,
+        });
+        let size_of_mem_trigger = SizeOfMemTrigger::<Test>::new(16);
+        let length_trigger = LengthTrigger::<Test>::new(4);
+        assert_eq!(size_of_mem_trigger.fill(), 0.0);
+
+        for _ in 0..2 {
+            size_of_mem_trigger.item(&record);
+            length_trigger.item(&record);
+        }
+        assert_eq!(size_of_mem_trigger.fill(), 1.0);
+        assert_eq!(length_trigger.fill(), 0.5);
+
+        length_trigger.item(&record);
+        length_trigger.item(&record);
+        length_trigger.item(&record);
+        assert_eq!(length_trigger.fill(), 1.25);
+        length_trigger.reset();
+        assert_eq!(length_trigger.fill(), 0.0);
+    }
+
     #[tokio::test]
     async fn test_trigger_factory() {
         let size_of_mem_trigger = TriggerFactory::<Test>::create(TriggerType::SizeOfMem(9));
@@ -1,9 +1,18 @@
-use std::{collections::BTreeMap, sync::Arc};
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    sync::Arc,
+    time::Duration,
+};
 
 use flume::{Receiver, Sender};
+use futures_util::future::join_all;
+use tracing::{error, warn};
+use web_time::Instant;
 
 use crate::{
+    executor::Clock,
     fs::{manager::StoreManager, FileId},
+    metrics,
     record::Record,
     timestamp::Timestamp,
     DbError, DbOption,
@@ -21,16 +30,68 @@ pub enum CleanTag {
         wal_id: FileId,
         level: usize,
     },
+    /// the SSTs of a version installed as the current one, which are not removed until the
+    /// version is released by the snapshots and scans reading it and sends the matching `Unpin`
+    Pin {
+        gens: Vec<FileId>,
+    },
+    Unpin {
+        gens: Vec<FileId>,
+    },
+    /// taken and released by an [`ObsoleteFilesHold`]
+    Hold,
+    Release,
+    /// remove the SSTs kept past [`DbOption::obsolete_file_retention`]
+    Expire,
+}
+
+/// Keeps the SSTs dropped by the database on the file system until it is dropped, for backup
+/// tools, external readers and secondary instances that may still read them, see
+/// [`DB::hold_obsolete_files`](crate::DB::hold_obsolete_files).
+pub struct ObsoleteFilesHold {
+    clean_sender: Sender<CleanTag>,
+}
+
+impl ObsoleteFilesHold {
+    pub(crate) async fn new(clean_sender: Sender<CleanTag>) -> Self {
+        // a read-only database removes nothing
+        let _ = clean_sender.send_async(CleanTag::Hold).await;
+        ObsoleteFilesHold { clean_sender }
+    }
+}
+
+impl Drop for ObsoleteFilesHold {
+    fn drop(&mut self) {
+        let _ = self.clean_sender.send(CleanTag::Release);
+    }
 }
 
+const DELETION_RETRY_BACKOFF: Duration = Duration::from_millis(100);
+
 pub(crate) struct Cleaner<R>
 where
     R: Record,
 {
     tag_recv: Receiver<CleanTag>,
     gens_map: BTreeMap<Timestamp, (Vec<(FileId, usize)>, bool)>,
+    /// the number of live versions referencing each SST
+    pins: HashMap<FileId, usize>,
+    /// the SSTs to remove once no version references them anymore
+    pinned: Vec<(FileId, usize)>,
+    /// the SSTs no version references anymore, with the instant they were dropped at, kept
+    /// until [`DbOption::obsolete_file_retention`] passed and no [`ObsoleteFilesHold`] is held
+    retired: VecDeque<(Instant, FileId, usize)>,
+    holds: usize,
+    retention: Option<Duration>,
+    /// the SSTs to remove with the attempts failed already, removed in batches of
+    /// [`DbOption::file_deletion_batch_size`]
+    deletions: VecDeque<(FileId, usize, usize)>,
+    /// the next batch of deletions is not started before, see
+    /// [`DbOption::max_file_deletes_per_sec`]
+    next_batch: Instant,
     option: Arc<DbOption<R>>,
     manager: Arc<StoreManager>,
+    clock: Clock,
 }
 
 impl<R> Cleaner<R>
@@ -40,6 +101,7 @@ where
     pub(crate) fn new(
         option: Arc<DbOption<R>>,
         manager: Arc<StoreManager>,
+        clock: Clock,
     ) -> (Self, Sender<CleanTag>) {
         let (tag_send, tag_recv) = flume::bounded(option.clean_channel_buffer);
 
@@ -47,49 +109,180 @@ where
             Cleaner {
                 tag_recv,
                 gens_map: Default::default(),
+                pins: Default::default(),
+                pinned: Vec::new(),
+                retired: VecDeque::new(),
+                holds: 0,
+                retention: option.obsolete_file_retention,
+                deletions: VecDeque::new(),
+                next_batch: Instant::now(),
                 option,
                 manager,
+                clock,
             },
             tag_send,
         )
     }
 
     pub(crate) async fn listen(&mut self) -> Result<(), DbError<R>> {
-        while let Ok(tag) = self.tag_recv.recv_async().await {
+        loop {
+            let tag = if self.deletions.is_empty() {
+                match self.tag_recv.recv_async().await {
+                    Ok(tag) => Some(tag),
+                    Err(_) => break,
+                }
+            } else {
+                self.poll_tag().await
+            };
             match tag {
-                CleanTag::Add { ts, gens } => {
-                    let _ = self.gens_map.insert(ts, (gens, false));
+                Some(tag) => self.handle(tag),
+                None => self.delete_batch().await,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// the next tag received before the next batch of deletions is due, if any
+    async fn poll_tag(&self) -> Option<CleanTag> {
+        if let Ok(tag) = self.tag_recv.try_recv() {
+            return Some(tag);
+        }
+        let wait = self.next_batch.saturating_duration_since(Instant::now());
+        if !wait.is_zero() {
+            if let Some(Ok(tag)) = self.clock.timeout(wait, self.tag_recv.recv_async()).await {
+                return Some(tag);
+            }
+            // the deletions left are still paced once every sender is gone
+            let wait = self.next_batch.saturating_duration_since(Instant::now());
+            self.clock.sleep(wait).await;
+        }
+        None
+    }
+
+    fn handle(&mut self, tag: CleanTag) {
+        match tag {
+            CleanTag::Add { ts, gens } => {
+                let _ = self.gens_map.insert(ts, (gens, false));
+            }
+            CleanTag::Clean { ts: version_num } => {
+                if let Some((_, dropped)) = self.gens_map.get_mut(&version_num) {
+                    *dropped = true;
                 }
-                CleanTag::Clean { ts: version_num } => {
-                    if let Some((_, dropped)) = self.gens_map.get_mut(&version_num) {
-                        *dropped = true;
+                while let Some((first_version, (gens, dropped))) = self.gens_map.pop_first() {
+                    if !dropped {
+                        let _ = self.gens_map.insert(first_version, (gens, false));
+                        break;
                     }
-                    while let Some((first_version, (gens, dropped))) = self.gens_map.pop_first() {
-                        if !dropped {
-                            let _ = self.gens_map.insert(first_version, (gens, false));
-                            break;
+                    for (gen, level) in gens {
+                        if self.pins.contains_key(&gen) {
+                            self.pinned.push((gen, level));
+                        } else {
+                            self.retire(gen, level);
                         }
-                        for (gen, level) in gens {
-                            let fs = self
-                                .option
-                                .level_fs_path(level)
-                                .map(|path| self.manager.get_fs(path))
-                                .unwrap_or(self.manager.base_fs());
-                            fs.remove(&self.option.table_path(gen, level)).await?;
+                    }
+                }
+            }
+            CleanTag::RecoverClean { wal_id: gen, level } => {
+                self.deletions.push_back((gen, level, 0));
+            }
+            CleanTag::Pin { gens } => {
+                for gen in gens {
+                    *self.pins.entry(gen).or_default() += 1;
+                }
+            }
+            CleanTag::Unpin { gens } => {
+                for gen in gens {
+                    if let Some(count) = self.pins.get_mut(&gen) {
+                        *count -= 1;
+                        if *count == 0 {
+                            self.pins.remove(&gen);
                         }
                     }
                 }
-                CleanTag::RecoverClean { wal_id: gen, level } => {
-                    let fs = self
-                        .option
-                        .level_fs_path(level)
-                        .map(|path| self.manager.get_fs(path))
-                        .unwrap_or(self.manager.base_fs());
-                    fs.remove(&self.option.table_path(gen, level)).await?;
+                let (unpinned, pinned) = std::mem::take(&mut self.pinned)
+                    .into_iter()
+                    .partition::<Vec<_>, _>(|(gen, _)| !self.pins.contains_key(gen));
+                self.pinned = pinned;
+                for (gen, level) in unpinned {
+                    self.retire(gen, level);
                 }
             }
+            CleanTag::Hold => self.holds += 1,
+            CleanTag::Release => self.holds = self.holds.saturating_sub(1),
+            CleanTag::Expire => (),
         }
+        self.expire();
+    }
+
+    /// remove an SST no version references anymore, or keep it for a while
+    fn retire(&mut self, gen: FileId, level: usize) {
+        if self.retention.is_none() && self.holds == 0 {
+            self.deletions.push_back((gen, level, 0));
+        } else {
+            self.retired.push_back((Instant::now(), gen, level));
+        }
+    }
 
+    /// remove the SSTs kept for their retention, unless held
+    fn expire(&mut self) {
+        if self.holds > 0 {
+            return;
+        }
+        while let Some((retired_at, gen, level)) = self.retired.front().copied() {
+            if self
+                .retention
+                .is_some_and(|retention| retired_at.elapsed() < retention)
+            {
+                break;
+            }
+            self.retired.pop_front();
+            self.deletions.push_back((gen, level, 0));
+        }
+    }
+
+    /// remove the next batch of SSTs at once, queueing the failed ones again up to
+    /// [`DbOption::file_deletion_retries`] times
+    async fn delete_batch(&mut self) {
+        let batch_size = self
+            .option
+            .file_deletion_batch_size
+            .clamp(1, self.deletions.len());
+        let batch = self.deletions.drain(..batch_size).collect::<Vec<_>>();
+        let results = join_all(
+            batch
+                .iter()
+                .map(|(gen, level, _)| self.remove(*gen, *level)),
+        )
+        .await;
+
+        let now = Instant::now();
+        if let Some(rate) = self.option.max_file_deletes_per_sec {
+            self.next_batch = now + Duration::from_secs_f64(batch_size as f64 / rate.max(1) as f64);
+        }
+        for ((gen, level, attempts), result) in batch.into_iter().zip(results) {
+            let Err(err) = result else {
+                continue;
+            };
+            if attempts < self.option.file_deletion_retries {
+                warn!("[Cleaner Error]: retrying to remove {}: {}", gen, err);
+                self.deletions.push_back((gen, level, attempts + 1));
+                // backs off the transient failures of the storage
+                self.next_batch = self.next_batch.max(now + DELETION_RETRY_BACKOFF);
+            } else {
+                error!("[Cleaner Error]: failed to remove {}: {}", gen, err);
+                metrics::increment(metrics::FILE_DELETION_FAILURES, 1);
+            }
+        }
+    }
+
+    async fn remove(&self, gen: FileId, level: usize) -> Result<(), DbError<R>> {
+        let fs = self
+            .option
+            .level_fs_path(level)
+            .map(|path| self.manager.get_fs(path))
+            .unwrap_or(self.manager.base_fs());
+        fs.remove(&self.option.table_path(gen, level)).await?;
         Ok(())
     }
 }
@@ -105,10 +298,10 @@ pub(crate) mod tests {
     use tracing::error;
 
     use crate::{
-        executor::{tokio::TokioExecutor, Executor},
+        executor::{tokio::TokioExecutor, Clock, Executor},
         fs::{manager::StoreManager, FileId, FileType},
         tests::Test,
-        version::cleaner::{CleanTag, Cleaner},
+        version::cleaner::{CleanTag, Cleaner, ObsoleteFilesHold},
         DbOption,
     };
 
@@ -155,7 +348,11 @@ pub(crate) mod tests {
             .unwrap();
         }
 
-        let (mut cleaner, tx) = Cleaner::<Test>::new(option.clone(), manager.clone());
+        let (mut cleaner, tx) = Cleaner::<Test>::new(
+            option.clone(),
+            manager.clone(),
+            Clock::new(Arc::new(TokioExecutor::new())),
+        );
 
         let executor = TokioExecutor::new();
 
@@ -244,4 +441,191 @@ pub(crate) mod tests {
             .unwrap()
             .exists());
     }
+
+    #[tokio::test]
+    async fn test_cleaner_pins() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = Arc::new(StoreManager::new(FsOptions::Local, vec![]).unwrap());
+        let option = Arc::new(DbOption::from(
+            Path::from_filesystem_path(temp_dir.path()).unwrap(),
+        ));
+
+        let gen = FileId::new();
+        manager
+            .base_fs()
+            .open_options(
+                &option.table_path(gen, 0),
+                FileType::Parquet.open_options(false),
+            )
+            .await
+            .unwrap();
+
+        let (mut cleaner, tx) = Cleaner::<Test>::new(
+            option.clone(),
+            manager.clone(),
+            Clock::new(Arc::new(TokioExecutor::new())),
+        );
+        TokioExecutor::new().spawn(async move {
+            if let Err(err) = cleaner.listen().await {
+                error!("[Cleaner Error]: {}", err)
+            }
+        });
+
+        // referenced by two versions
+        for _ in 0..2 {
+            tx.send_async(CleanTag::Pin { gens: vec![gen] })
+                .await
+                .unwrap();
+        }
+        tx.send_async(CleanTag::Add {
+            ts: 0.into(),
+            gens: vec![(gen, 0)],
+        })
+        .await
+        .unwrap();
+        tx.send_async(CleanTag::Clean { ts: 0.into() })
+            .await
+            .unwrap();
+        tx.send_async(CleanTag::Unpin { gens: vec![gen] })
+            .await
+            .unwrap();
+        sleep(Duration::from_millis(10)).await;
+        assert!(path_to_local(&option.table_path(gen, 0)).unwrap().exists());
+
+        tx.send_async(CleanTag::Unpin { gens: vec![gen] })
+            .await
+            .unwrap();
+        sleep(Duration::from_millis(10)).await;
+        assert!(!path_to_local(&option.table_path(gen, 0)).unwrap().exists());
+    }
+
+    #[tokio::test]
+    async fn test_cleaner_retention() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = Arc::new(StoreManager::new(FsOptions::Local, vec![]).unwrap());
+        let option = Arc::new(
+            DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap())
+                .obsolete_file_retention(Duration::from_millis(50)),
+        );
+
+        let (gen_0, gen_1) = (FileId::new(), FileId::new());
+        for gen in [gen_0, gen_1] {
+            manager
+                .base_fs()
+                .open_options(
+                    &option.table_path(gen, 0),
+                    FileType::Parquet.open_options(false),
+                )
+                .await
+                .unwrap();
+        }
+
+        let (mut cleaner, tx) = Cleaner::<Test>::new(
+            option.clone(),
+            manager.clone(),
+            Clock::new(Arc::new(TokioExecutor::new())),
+        );
+        TokioExecutor::new().spawn(async move {
+            if let Err(err) = cleaner.listen().await {
+                error!("[Cleaner Error]: {}", err)
+            }
+        });
+
+        tx.send_async(CleanTag::Add {
+            ts: 0.into(),
+            gens: vec![(gen_0, 0)],
+        })
+        .await
+        .unwrap();
+        tx.send_async(CleanTag::Clean { ts: 0.into() })
+            .await
+            .unwrap();
+        tx.send_async(CleanTag::Expire).await.unwrap();
+        sleep(Duration::from_millis(10)).await;
+        assert!(path_to_local(&option.table_path(gen_0, 0))
+            .unwrap()
+            .exists());
+
+        sleep(Duration::from_millis(50)).await;
+        tx.send_async(CleanTag::Expire).await.unwrap();
+        sleep(Duration::from_millis(10)).await;
+        assert!(!path_to_local(&option.table_path(gen_0, 0))
+            .unwrap()
+            .exists());
+
+        // kept past the retention while held
+        let hold = ObsoleteFilesHold::new(tx.clone()).await;
+        tx.send_async(CleanTag::Add {
+            ts: 1.into(),
+            gens: vec![(gen_1, 0)],
+        })
+        .await
+        .unwrap();
+        tx.send_async(CleanTag::Clean { ts: 1.into() })
+            .await
+            .unwrap();
+        sleep(Duration::from_millis(60)).await;
+        tx.send_async(CleanTag::Expire).await.unwrap();
+        sleep(Duration::from_millis(10)).await;
+        assert!(path_to_local(&option.table_path(gen_1, 0))
+            .unwrap()
+            .exists());
+
+        drop(hold);
+        sleep(Duration::from_millis(10)).await;
+        assert!(!path_to_local(&option.table_path(gen_1, 0))
+            .unwrap()
+            .exists());
+    }
+
+    #[tokio::test]
+    async fn test_cleaner_batches() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = Arc::new(StoreManager::new(FsOptions::Local, vec![]).unwrap());
+        let option = Arc::new(
+            DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap())
+                .file_deletion_batch_size(2)
+                .max_file_deletes_per_sec(10),
+        );
+
+        let gens = (0..4).map(|_| FileId::new()).collect::<Vec<_>>();
+        for gen in &gens {
+            manager
+                .base_fs()
+                .open_options(
+                    &option.table_path(*gen, 0),
+                    FileType::Parquet.open_options(false),
+                )
+                .await
+                .unwrap();
+        }
+
+        let (mut cleaner, tx) = Cleaner::<Test>::new(
+            option.clone(),
+            manager.clone(),
+            Clock::new(Arc::new(TokioExecutor::new())),
+        );
+        TokioExecutor::new().spawn(async move {
+            if let Err(err) = cleaner.listen().await {
+                error!("[Cleaner Error]: {}", err)
+            }
+        });
+
+        tx.send_async(CleanTag::Add {
+            ts: 0.into(),
+            gens: gens.iter().map(|gen| (*gen, 0)).collect(),
+        })
+        .await
+        .unwrap();
+        tx.send_async(CleanTag::Clean { ts: 0.into() })
+            .await
+            .unwrap();
+        sleep(Duration::from_millis(50)).await;
+        let exists = |gen: &FileId| path_to_local(&option.table_path(*gen, 0)).unwrap().exists();
+        // the second batch is due 200ms after the first one at 10 deletes a second
+        assert_eq!(gens.iter().filter(|gen| exists(gen)).count(), 2);
+
+        sleep(Duration::from_millis(250)).await;
+        assert_eq!(gens.iter().filter(|gen| exists(gen)).count(), 0);
+    }
 }
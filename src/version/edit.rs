@@ -1,51 +1,260 @@
 use std::mem::size_of;
 
 use fusio::{SeqRead, Write};
+use tokio_util::bytes::Bytes;
 
 use crate::{
     fs::FileId,
-    scope::Scope,
-    serdes::{Decode, Encode},
+    range_tombstone::RangeTombstone,
+    scope::{Scope, TableStats},
+    serdes::{decode_with, encode_with, BytesReader, Decode, Encode},
     timestamp::Timestamp,
+    wal::checksum::{is_eof, HashReader, HashWriter},
+    FormatVersion,
 };
 
+/// the tag of the edits written together by [`VersionEdit::encode_batch`]
+const BATCH: u8 = 7;
+/// the tag of a batch in the compact encoding of [`FormatVersion::V2`]
+const COMPACT_BATCH: u8 = 8;
+
+/// The end of a version log left out by [`VersionEdit::recover`], from the first edit which is
+/// incomplete, fails to decode or fails its checksum, as left by a crash while writing it unless
+/// [`VersionEdit::has_valid_batch`] finds a batch following it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct DiscardedTail {
+    /// the bytes of the edits recovered before it
+    pub(crate) offset: u64,
+    pub(crate) checksum_failed: bool,
+}
+
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub(crate) enum VersionEdit<K> {
-    Add { level: u8, scope: Scope<K> },
-    Remove { level: u8, gen: FileId },
-    LatestTimeStamp { ts: Timestamp },
-    NewLogLength { len: u32 },
+    Add {
+        level: u8,
+        scope: Scope<K>,
+    },
+    Remove {
+        level: u8,
+        gen: FileId,
+    },
+    LatestTimeStamp {
+        ts: Timestamp,
+    },
+    NewLogLength {
+        len: u32,
+    },
+    RangeTombstone {
+        range_tombstone: RangeTombstone<K>,
+    },
+    /// the range tombstone written at `ts` no longer hides a record of any table
+    RemoveRangeTombstone {
+        ts: Timestamp,
+    },
 }
 
 impl<K> VersionEdit<K>
 where
     K: Decode,
 {
-    pub(crate) async fn recover<R: SeqRead>(reader: &mut R) -> Vec<VersionEdit<K>> {
+    /// decode the edits of a version log up to the first batch incomplete or failing its
+    /// checksum, which is left out as a whole along with the rest of the log
+    pub(crate) async fn recover<R: SeqRead>(
+        reader: &mut R,
+    ) -> Result<(Vec<VersionEdit<K>>, Option<DiscardedTail>), fusio::Error> {
         let mut edits = Vec::new();
+        let mut offset = 0;
 
-        while let Ok(edit) = VersionEdit::decode(reader).await {
-            edits.push(edit)
+        loop {
+            let mut reader = HashReader::new(&mut *reader);
+            let discarded = move |checksum_failed| DiscardedTail {
+                offset,
+                checksum_failed,
+            };
+            let Ok(tag) = u8::decode(&mut reader).await else {
+                return match reader.take_error() {
+                    Some(err) => Err(err),
+                    None => Ok((edits, None)),
+                };
+            };
+            if tag != BATCH && tag != COMPACT_BATCH {
+                // written one by one before the edits were checksummed
+                let Ok(edit) = VersionEdit::decode_tagged(tag, &mut reader, false).await else {
+                    // a failing disk is not mistaken for an incomplete edit
+                    return match reader.take_error() {
+                        Some(err) => Err(err),
+                        None => Ok((edits, Some(discarded(false)))),
+                    };
+                };
+                offset += reader.read() as u64;
+                edits.push(edit);
+                continue;
+            }
+            let Ok(batch) = VersionEdit::decode_batch(&mut reader, tag == COMPACT_BATCH).await
+            else {
+                return match reader.take_error() {
+                    Some(err) => Err(err),
+                    None => Ok((edits, Some(discarded(false)))),
+                };
+            };
+            let read = reader.read() as u64;
+            match reader.checksum().await {
+                Ok(true) => {
+                    offset += read + size_of::<u64>() as u64;
+                    edits.extend(batch);
+                }
+                Ok(false) => return Ok((edits, Some(discarded(true)))),
+                Err(err) if is_eof(&err) => return Ok((edits, Some(discarded(false)))),
+                Err(err) => return Err(err),
+            }
         }
-        edits
+    }
+
+    /// whether a batch passing its checksum starts anywhere in `tail`, the end of a log left out
+    /// by [`VersionEdit::recover`], which then was corrupted rather than cut short by a crash
+    pub(crate) async fn has_valid_batch(tail: Bytes) -> bool {
+        for start in 0..tail.len() {
+            if tail[start] != BATCH && tail[start] != COMPACT_BATCH {
+                continue;
+            }
+            let mut bytes = BytesReader::new(tail.slice(start..));
+            let mut reader = HashReader::new(&mut bytes);
+            let Ok(tag) = u8::decode(&mut reader).await else {
+                continue;
+            };
+            if VersionEdit::<K>::decode_batch(&mut reader, tag == COMPACT_BATCH)
+                .await
+                .is_ok()
+                && matches!(reader.checksum().await, Ok(true))
+            {
+                return true;
+            }
+        }
+        false
+    }
+
+    async fn decode_batch<R: SeqRead>(
+        reader: &mut R,
+        compact: bool,
+    ) -> Result<Vec<VersionEdit<K>>, K::Error> {
+        let len: u32 = decode_with(reader, compact).await?;
+        // the length is not trusted until the checksum is
+        let mut batch = Vec::with_capacity((len as usize).min(1024));
+        for _ in 0..len {
+            batch.push(decode_with(reader, compact).await?);
+        }
+        Ok(batch)
+    }
+
+    async fn decode_tagged<R: SeqRead>(
+        edit_type: u8,
+        reader: &mut R,
+        compact: bool,
+    ) -> Result<Self, K::Error> {
+        Ok(match edit_type {
+            0 => {
+                let level = u8::decode(reader).await?;
+                let scope = decode_with::<Scope<K>, _>(reader, compact).await?;
+
+                VersionEdit::Add { level, scope }
+            }
+            6 => {
+                let level = u8::decode(reader).await?;
+                let mut scope = decode_with::<Scope<K>, _>(reader, compact).await?;
+                scope.stats = Some(decode_with::<TableStats, _>(reader, compact).await?);
+
+                VersionEdit::Add { level, scope }
+            }
+            1 => {
+                let level = u8::decode(reader).await?;
+                let gen = {
+                    let mut buf = [0u8; 16];
+                    let (result, _) = reader.read_exact(&mut buf[..]).await;
+                    result?;
+                    FileId::from_bytes(buf)
+                };
+                VersionEdit::Remove { level, gen }
+            }
+            // written before timestamps were widened to 64 bits
+            2 => {
+                let ts = u32::decode(reader).await?;
+                VersionEdit::LatestTimeStamp {
+                    ts: u64::from(ts).into(),
+                }
+            }
+            3 => {
+                let len = decode_with(reader, compact).await?;
+                VersionEdit::NewLogLength { len }
+            }
+            4 => {
+                let range_tombstone = decode_with::<RangeTombstone<K>, _>(reader, compact).await?;
+                VersionEdit::RangeTombstone { range_tombstone }
+            }
+            5 => {
+                let ts = decode_with::<Timestamp, _>(reader, compact).await?;
+                VersionEdit::LatestTimeStamp { ts }
+            }
+            // the tags 7 and 8 are taken by the batches
+            9 => {
+                let ts = decode_with::<Timestamp, _>(reader, compact).await?;
+                VersionEdit::RemoveRangeTombstone { ts }
+            }
+            _ => {
+                return Err(fusio::Error::Other(
+                    format!("unknown version edit: {}", edit_type).into(),
+                )
+                .into())
+            }
+        })
     }
 }
 
-impl<K> Encode for VersionEdit<K>
+impl<K> VersionEdit<K>
 where
     K: Encode + Sync,
 {
-    type Error = <K as Encode>::Error;
+    /// write `edits` as a single record followed by its checksum, which the recovery applies all
+    /// or none of, so a crash while writing them can not leave a half applied version edit
+    pub(crate) async fn encode_batch<W>(
+        edits: &[VersionEdit<K>],
+        writer: &mut W,
+        format_version: FormatVersion,
+    ) -> Result<(), K::Error>
+    where
+        W: Write,
+    {
+        let compact = format_version == FormatVersion::V2;
+        let mut writer = HashWriter::new(writer);
+        if compact {
+            COMPACT_BATCH.encode(&mut writer).await?;
+        } else {
+            BATCH.encode(&mut writer).await?;
+        }
+        encode_with(&(edits.len() as u32), &mut writer, compact).await?;
+        for edit in edits {
+            encode_with(edit, &mut writer, compact).await?;
+        }
+        writer.eol().await?;
+        Ok(())
+    }
 
-    async fn encode<W>(&self, writer: &mut W) -> Result<(), Self::Error>
+    async fn encode_as<W>(&self, writer: &mut W, compact: bool) -> Result<(), K::Error>
     where
         W: Write,
     {
         match self {
             VersionEdit::Add { scope, level } => {
-                0u8.encode(writer).await?;
+                // the tables added with their statistics have a tag of their own, so that the
+                // edits written before are still decoded
+                match &scope.stats {
+                    None => 0u8.encode(writer).await?,
+                    Some(_) => 6u8.encode(writer).await?,
+                }
                 level.encode(writer).await?;
-                scope.encode(writer).await?;
+                encode_with(scope, writer, compact).await?;
+                if let Some(stats) = &scope.stats {
+                    encode_with(stats, writer, compact).await?;
+                }
             }
             VersionEdit::Remove { gen, level } => {
                 1u8.encode(writer).await?;
@@ -54,26 +263,60 @@ where
                 result?;
             }
             VersionEdit::LatestTimeStamp { ts } => {
-                2u8.encode(writer).await?;
-                ts.encode(writer).await?;
+                5u8.encode(writer).await?;
+                encode_with(ts, writer, compact).await?;
             }
             VersionEdit::NewLogLength { len } => {
                 3u8.encode(writer).await?;
-                len.encode(writer).await?;
+                encode_with(len, writer, compact).await?;
+            }
+            VersionEdit::RangeTombstone { range_tombstone } => {
+                4u8.encode(writer).await?;
+                encode_with(range_tombstone, writer, compact).await?;
+            }
+            VersionEdit::RemoveRangeTombstone { ts } => {
+                9u8.encode(writer).await?;
+                encode_with(ts, writer, compact).await?;
             }
         }
 
         Ok(())
     }
+}
+
+impl<K> Encode for VersionEdit<K>
+where
+    K: Encode + Sync,
+{
+    type Error = <K as Encode>::Error;
+
+    async fn encode<W>(&self, writer: &mut W) -> Result<(), Self::Error>
+    where
+        W: Write,
+    {
+        self.encode_as(writer, false).await
+    }
+
+    async fn encode_compact<W>(&self, writer: &mut W) -> Result<(), Self::Error>
+    where
+        W: Write,
+    {
+        self.encode_as(writer, true).await
+    }
 
     fn size(&self) -> usize {
         size_of::<u8>()
             + size_of::<u8>()
             + match self {
-                VersionEdit::Add { scope, .. } => scope.size(),
+                VersionEdit::Add { scope, .. } => {
+                    scope.size() + scope.stats.as_ref().map_or(0, Encode::size)
+                }
                 VersionEdit::Remove { .. } => 16,
-                VersionEdit::LatestTimeStamp { ts } => ts.size(),
+                VersionEdit::LatestTimeStamp { ts } | VersionEdit::RemoveRangeTombstone { ts } => {
+                    ts.size()
+                }
                 VersionEdit::NewLogLength { .. } => size_of::<u32>(),
+                VersionEdit::RangeTombstone { range_tombstone } => range_tombstone.size(),
             }
     }
 }
@@ -86,44 +329,30 @@ where
 
     async fn decode<R: SeqRead>(reader: &mut R) -> Result<Self, Self::Error> {
         let edit_type = u8::decode(reader).await?;
+        VersionEdit::decode_tagged(edit_type, reader, false).await
+    }
 
-        Ok(match edit_type {
-            0 => {
-                let level = u8::decode(reader).await?;
-                let scope = Scope::<K>::decode(reader).await?;
-
-                VersionEdit::Add { level, scope }
-            }
-            1 => {
-                let level = u8::decode(reader).await?;
-                let gen = {
-                    let mut buf = [0u8; 16];
-                    let (result, _) = reader.read_exact(&mut buf[..]).await;
-                    result?;
-                    FileId::from_bytes(buf)
-                };
-                VersionEdit::Remove { level, gen }
-            }
-            2 => {
-                let ts = Timestamp::decode(reader).await?;
-                VersionEdit::LatestTimeStamp { ts }
-            }
-            3 => {
-                let len = u32::decode(reader).await?;
-                VersionEdit::NewLogLength { len }
-            }
-            _ => unreachable!(),
-        })
+    async fn decode_compact<R: SeqRead>(reader: &mut R) -> Result<Self, Self::Error> {
+        let edit_type = u8::decode(reader).await?;
+        VersionEdit::decode_tagged(edit_type, reader, true).await
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::io::Cursor;
+    use std::{io::Cursor, ops::Bound};
 
     use tokio::io::AsyncSeekExt;
+    use tokio_util::bytes::Bytes;
 
-    use crate::{fs::FileId, scope::Scope, serdes::Encode, version::edit::VersionEdit};
+    use crate::{
+        fs::FileId,
+        range_tombstone::RangeTombstone,
+        scope::{ColumnStats, Scope, TableStats},
+        serdes::Encode,
+        version::edit::{DiscardedTail, VersionEdit},
+        FormatVersion,
+    };
 
     #[tokio::test]
     async fn encode_and_decode() {
@@ -135,6 +364,29 @@ mod tests {
                     max: "Max".to_string(),
                     gen: Default::default(),
                     wal_ids: Some(vec![FileId::new(), FileId::new()]),
+                    stats: None,
+                },
+            },
+            VersionEdit::Add {
+                level: 1,
+                scope: Scope {
+                    min: "a".to_string(),
+                    max: "b".to_string(),
+                    gen: FileId::new(),
+                    wal_ids: None,
+                    stats: Some(TableStats {
+                        rows: 3,
+                        tombstones: 1,
+                        bytes: 1024,
+                        columns: vec![
+                            ColumnStats {
+                                min: Some(b"a".to_vec()),
+                                max: Some(b"b".to_vec()),
+                                null_count: Some(0),
+                            },
+                            ColumnStats::default(),
+                        ],
+                    }),
                 },
             },
             VersionEdit::Remove {
@@ -143,6 +395,14 @@ mod tests {
             },
             VersionEdit::LatestTimeStamp { ts: 10.into() },
             VersionEdit::NewLogLength { len: 233 },
+            VersionEdit::RemoveRangeTombstone { ts: 9.into() },
+            VersionEdit::RangeTombstone {
+                range_tombstone: RangeTombstone::new(
+                    Bound::Excluded("a".to_string()),
+                    Bound::Included("b".to_string()),
+                    11.into(),
+                ),
+            },
         ];
 
         let mut buf = Vec::new();
@@ -153,8 +413,118 @@ mod tests {
         }
 
         cursor.seek(std::io::SeekFrom::Start(0)).await.unwrap();
-        let decode_edits = { VersionEdit::<String>::recover(&mut cursor).await };
+        let (decode_edits, discarded) = VersionEdit::<String>::recover(&mut cursor).await.unwrap();
 
         assert_eq!(edits, decode_edits);
+        assert_eq!(discarded, None);
+
+        let mut buf = Vec::new();
+        let mut cursor = Cursor::new(&mut buf);
+        VersionEdit::encode_batch(&edits, &mut cursor, FormatVersion::V1)
+            .await
+            .unwrap();
+        let len = buf.len();
+        let mut cursor = Cursor::new(&mut buf);
+        cursor.seek(std::io::SeekFrom::End(0)).await.unwrap();
+        VersionEdit::encode_batch(&edits, &mut cursor, FormatVersion::V2)
+            .await
+            .unwrap();
+        assert!(buf.len() - len < len);
+
+        let mut cursor = Cursor::new(&mut buf);
+        let (decode_edits, discarded) = VersionEdit::<String>::recover(&mut cursor).await.unwrap();
+        assert_eq!(decode_edits, [edits.clone(), edits].concat());
+        assert_eq!(discarded, None);
+    }
+
+    #[tokio::test]
+    async fn decode_32_bit_timestamp() {
+        let mut buf = Vec::new();
+        let mut cursor = Cursor::new(&mut buf);
+        2u8.encode(&mut cursor).await.unwrap();
+        7u32.encode(&mut cursor).await.unwrap();
+
+        cursor.seek(std::io::SeekFrom::Start(0)).await.unwrap();
+        let (decode_edits, _) = VersionEdit::<String>::recover(&mut cursor).await.unwrap();
+
+        assert_eq!(
+            decode_edits,
+            vec![VersionEdit::LatestTimeStamp { ts: 7.into() }]
+        );
+    }
+
+    #[tokio::test]
+    async fn torn_batch() {
+        let batch = vec![
+            VersionEdit::Remove {
+                level: 1,
+                gen: FileId::new(),
+            },
+            VersionEdit::LatestTimeStamp { ts: 12.into() },
+        ];
+        let torn = vec![
+            VersionEdit::<String>::Remove {
+                level: 2,
+                gen: FileId::new(),
+            },
+            VersionEdit::NewLogLength { len: 4 },
+        ];
+
+        let mut buf = Vec::new();
+        let mut cursor = Cursor::new(&mut buf);
+        // written before the edits were checksummed
+        VersionEdit::<String>::NewLogLength { len: 1 }
+            .encode(&mut cursor)
+            .await
+            .unwrap();
+        VersionEdit::encode_batch(&batch, &mut cursor, FormatVersion::V1)
+            .await
+            .unwrap();
+        VersionEdit::encode_batch(&torn, &mut cursor, FormatVersion::V1)
+            .await
+            .unwrap();
+        buf.pop();
+
+        let mut cursor = Cursor::new(&mut buf);
+        let mut expected = vec![VersionEdit::NewLogLength { len: 1 }];
+        expected.extend(batch.clone());
+        let (edits, discarded) = VersionEdit::recover(&mut cursor).await.unwrap();
+        assert_eq!(edits, expected);
+        let offset = discarded.unwrap().offset;
+        assert!(!discarded.unwrap().checksum_failed);
+
+        // a corrupted batch is left out as a whole
+        let mut buf = Vec::new();
+        let mut cursor = Cursor::new(&mut buf);
+        VersionEdit::encode_batch(&batch, &mut cursor, FormatVersion::V1)
+            .await
+            .unwrap();
+        VersionEdit::encode_batch(&torn, &mut cursor, FormatVersion::V1)
+            .await
+            .unwrap();
+        let len = buf.len();
+        buf[len - 10] ^= 1;
+
+        let mut cursor = Cursor::new(&mut buf);
+        let (edits, discarded) = VersionEdit::recover(&mut cursor).await.unwrap();
+        assert_eq!(edits, batch);
+        assert_eq!(
+            discarded,
+            Some(DiscardedTail {
+                offset: offset - 5,
+                checksum_failed: true,
+            })
+        );
+        let tail = Bytes::from(buf.clone()).slice(offset as usize - 5..);
+        assert!(!VersionEdit::<String>::has_valid_batch(tail).await);
+
+        // valid batches follow the corrupted one
+        let mut cursor = Cursor::new(&mut buf);
+        cursor.seek(std::io::SeekFrom::End(0)).await.unwrap();
+        VersionEdit::encode_batch(&batch, &mut cursor, FormatVersion::V2)
+            .await
+            .unwrap();
+        let tail = Bytes::from(buf).slice(offset as usize - 5..);
+        assert!(VersionEdit::<String>::has_valid_batch(tail).await);
     }
 }
@@ -1,41 +1,61 @@
 pub(crate) mod cleaner;
 pub(crate) mod edit;
+pub(crate) mod seek;
 pub(crate) mod set;
 
 use std::{
     ops::Bound,
     sync::{
-        atomic::{AtomicU32, Ordering},
+        atomic::{AtomicU64, Ordering},
         Arc,
     },
 };
 
+use async_lock::Semaphore;
 use flume::{SendError, Sender};
 use fusio::DynFs;
-use parquet::arrow::ProjectionMask;
+use parquet::{arrow::ProjectionMask, file::metadata::ParquetMetaData};
 use thiserror::Error;
 use tracing::error;
 
 use crate::{
-    fs::{manager::StoreManager, FileId, FileType},
-    ondisk::sstable::SsTable,
+    fs::{self, manager::StoreManager, FileId},
+    ondisk::{fence::FenceIndex, sstable::SsTable},
+    range_tombstone::RangeTombstoneSet,
     record::Record,
     scope::Scope,
     serdes::Encode,
+    statistics::{QueryStatsCollector, Statistics},
     stream::{level::LevelStream, record_batch::RecordBatchEntry, ScanStream},
     timestamp::{Timestamp, TimestampedRef},
-    version::{cleaner::CleanTag, edit::VersionEdit},
+    version::{
+        cleaner::CleanTag,
+        edit::VersionEdit,
+        seek::{Reads, SeekIndex},
+    },
     DbOption, ParquetLru,
 };
 
 pub(crate) const MAX_LEVEL: usize = 7;
 
+/// The limits of a scan on the SSTs it reads, see
+/// [`Scan::max_open_tables`](crate::Scan::max_open_tables) and
+/// [`Scan::max_inflight_reads`](crate::Scan::max_inflight_reads).
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ScanLimits {
+    pub(crate) max_open_tables: Option<usize>,
+    pub(crate) read_permits: Option<Arc<Semaphore>>,
+}
+
 pub(crate) type VersionRef<R> = Arc<Version<R>>;
 
 pub(crate) trait TransactionTs {
     fn load_ts(&self) -> Timestamp;
 
     fn increase_ts(&self) -> Timestamp;
+
+    /// move the timestamp forward to `ts` if it is behind, for a timestamp given by the user
+    fn advance_ts(&self, ts: Timestamp);
 }
 
 #[derive(Debug)]
@@ -45,36 +65,92 @@ where
 {
     ts: Timestamp,
     pub(crate) level_slice: [Vec<Scope<R::Key>>; MAX_LEVEL],
+    pub(crate) range_tombstones: RangeTombstoneSet<R::Key>,
     clean_sender: Sender<CleanTag>,
     option: Arc<DbOption<R>>,
-    timestamp: Arc<AtomicU32>,
+    timestamp: Arc<AtomicU64>,
+    statistics: Arc<Statistics>,
+    fences: Arc<FenceIndex>,
+    seeks: Arc<SeekIndex>,
+    /// the oldest timestamp still readable, major compactions keep only the latest version of
+    /// each key they merge
+    retained_ts: Timestamp,
     log_length: u32,
+    /// whether the SSTs were pinned in the cleaner on install, see [`Version::pin`]
+    pinned: bool,
 }
 
 impl<R> Version<R>
 where
     R: Record,
 {
-    #[cfg(test)]
-    #[allow(unused)]
     pub(crate) fn new(
         option: Arc<DbOption<R>>,
         clean_sender: Sender<CleanTag>,
-        timestamp: Arc<AtomicU32>,
+        timestamp: Arc<AtomicU64>,
+        statistics: Arc<Statistics>,
     ) -> Self {
         Version {
             ts: Timestamp::from(0),
             level_slice: [const { Vec::new() }; MAX_LEVEL],
+            range_tombstones: Default::default(),
             clean_sender,
             option: option.clone(),
             timestamp,
+            statistics,
+            fences: Default::default(),
+            seeks: Default::default(),
+            retained_ts: Timestamp::from(0),
             log_length: 0,
+            pinned: false,
         }
     }
 
     pub(crate) fn option(&self) -> &Arc<DbOption<R>> {
         &self.option
     }
+
+    pub(crate) fn statistics(&self) -> &Arc<Statistics> {
+        &self.statistics
+    }
+
+    pub(crate) fn retained_ts(&self) -> Timestamp {
+        self.retained_ts
+    }
+
+    /// keep the SSTs of the version from being removed by the cleaner after a compaction drops
+    /// them from a newer version, until this one is dropped by the last snapshot or scan reading
+    /// it
+    pub(crate) async fn pin(&mut self) -> Result<(), VersionError<R>> {
+        self.clean_sender
+            .send_async(CleanTag::Pin { gens: self.gens() })
+            .await?;
+        self.pinned = true;
+        Ok(())
+    }
+
+    pub(crate) fn seeks(&self) -> &SeekIndex {
+        &self.seeks
+    }
+
+    fn gens(&self) -> Vec<FileId> {
+        self.level_slice
+            .iter()
+            .flatten()
+            .map(|scope| scope.gen)
+            .collect()
+    }
+
+    /// after recovery the compactions that ran before are unknown, so only the history of a
+    /// database without SSTs below level 0 is kept
+    fn recovered(&mut self) {
+        if self.level_slice[1..]
+            .iter()
+            .any(|scopes| !scopes.is_empty())
+        {
+            self.retained_ts = self.load_ts();
+        }
+    }
 }
 
 impl<R> TransactionTs for Version<R>
@@ -88,6 +164,10 @@ where
     fn increase_ts(&self) -> Timestamp {
         (self.timestamp.fetch_add(1, Ordering::Release) + 1).into()
     }
+
+    fn advance_ts(&self, ts: Timestamp) {
+        self.timestamp.fetch_max(ts.into(), Ordering::Release);
+    }
 }
 
 impl<R> Clone for Version<R>
@@ -104,10 +184,16 @@ where
         Self {
             ts: self.ts,
             level_slice,
+            range_tombstones: self.range_tombstones.clone(),
             clean_sender: self.clean_sender.clone(),
             option: self.option.clone(),
             timestamp: self.timestamp.clone(),
+            statistics: self.statistics.clone(),
+            fences: self.fences.clone(),
+            seeks: self.seeks.clone(),
+            retained_ts: self.retained_ts,
             log_length: self.log_length,
+            pinned: false,
         }
     }
 }
@@ -119,19 +205,22 @@ where
     pub(crate) async fn query(
         &self,
         manager: &StoreManager,
-        key: &TimestampedRef<R::Key>,
+        key: TimestampedRef<'_, R::Key>,
         projection_mask: ProjectionMask,
         parquet_lru: ParquetLru,
+        stats: Option<&QueryStatsCollector>,
     ) -> Result<Option<RecordBatchEntry<R>>, VersionError<R>> {
         let level_0_path = self
             .option
             .level_fs_path(0)
             .unwrap_or(&self.option.base_path);
         let level_0_fs = manager.get_fs(level_0_path);
+        let mut reads = Reads::new();
         for scope in self.level_slice[0].iter().rev() {
             if !scope.contains(key.value()) {
                 continue;
             }
+            self.read(&mut reads, 0, scope);
             if let Some(entry) = self
                 .table_query(
                     level_0_fs,
@@ -140,6 +229,7 @@ where
                     scope.gen,
                     projection_mask.clone(),
                     parquet_lru.clone(),
+                    stats,
                 )
                 .await?
             {
@@ -160,6 +250,7 @@ where
             if !sort_runs[index].contains(key.value()) {
                 continue;
             }
+            self.read(&mut reads, leve, &sort_runs[index]);
             if let Some(entry) = self
                 .table_query(
                     level_fs,
@@ -168,6 +259,7 @@ where
                     sort_runs[index].gen,
                     projection_mask.clone(),
                     parquet_lru.clone(),
+                    stats,
                 )
                 .await?
             {
@@ -178,25 +270,58 @@ where
         Ok(None)
     }
 
+    /// charge the first table read by a get or scan once it reads another one, if
+    /// [`DbOption::bytes_per_seek`] is set
+    fn read<'a>(&self, reads: &mut Reads<'a, R::Key>, level: usize, scope: &'a Scope<R::Key>) {
+        if let Some(bytes_per_seek) = self.option.bytes_per_seek {
+            if let Some((level, scope)) = reads.read(level, scope) {
+                self.seeks.charge(self, bytes_per_seek, level, scope);
+            }
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
     async fn table_query(
         &self,
         store: &Arc<dyn DynFs>,
-        key: &TimestampedRef<<R as Record>::Key>,
+        key: TimestampedRef<'_, <R as Record>::Key>,
         level: usize,
         gen: FileId,
         projection_mask: ProjectionMask,
         parquet_lru: ParquetLru,
+        stats: Option<&QueryStatsCollector>,
     ) -> Result<Option<RecordBatchEntry<R>>, VersionError<R>> {
-        let file = store
-            .open_options(
-                &self.option.table_path(gen, level),
-                FileType::Parquet.open_options(true),
-            )
-            .await
-            .map_err(VersionError::Fusio)?;
-        SsTable::<R>::open(parquet_lru, gen, file)
+        let file = fs::open_table(
+            store,
+            &self.option.table_path(gen, level),
+            true,
+            self.option.level_direct_io(level),
+        )
+        .await
+        .map_err(VersionError::Fusio)?;
+        let mut table = SsTable::<R>::open(parquet_lru, gen, file)
             .await?
-            .get(key, projection_mask)
+            .count_reads(self.statistics.levels[level].get_bytes_read.clone())
+            .collect_stats(stats);
+        let fences = match self.fences.get(gen) {
+            Some(fences) => Some(fences),
+            None => table.fences().await?.map(|fences| {
+                let fences = Arc::new(fences);
+                self.fences.insert(gen, fences.clone(), &self.gens());
+                fences
+            }),
+        };
+        let row_groups = fences
+            .map(|fences| fences.row_groups(key.value()))
+            .transpose()?;
+        if row_groups
+            .as_ref()
+            .is_some_and(|row_groups| row_groups.is_empty())
+        {
+            return Ok(None);
+        }
+        table
+            .get(key, projection_mask, row_groups)
             .await
             .map_err(VersionError::Parquet)
     }
@@ -207,10 +332,90 @@ where
             .unwrap_or_else(|index| index.saturating_sub(1))
     }
 
+    /// the SSTs and their levels overlapping the range, the ones gets and scans of it may read
+    pub(crate) fn tables_in_range(
+        &self,
+        range: (Bound<&R::Key>, Bound<&R::Key>),
+    ) -> Vec<(usize, FileId)> {
+        self.level_slice
+            .iter()
+            .enumerate()
+            .flat_map(|(level, scopes)| {
+                scopes
+                    .iter()
+                    .filter(move |scope| scope.meets_range(range))
+                    .map(move |scope| (level, scope.gen))
+            })
+            .collect()
+    }
+
+    /// the rows of the SSTs overlapping the range, counted whole even when only partly in it.
+    /// Only the SSTs added without statistics are opened to read their footers
+    pub(crate) async fn approximate_rows(
+        &self,
+        manager: &StoreManager,
+        range: (Bound<&R::Key>, Bound<&R::Key>),
+        parquet_lru: ParquetLru,
+    ) -> Result<usize, VersionError<R>> {
+        let mut rows = 0;
+        for (level, scopes) in self.level_slice.iter().enumerate() {
+            for scope in scopes.iter().filter(|scope| scope.meets_range(range)) {
+                rows += match &scope.stats {
+                    Some(stats) => stats.rows as usize,
+                    None => self
+                        .table_metadata(manager, level, scope.gen, parquet_lru.clone())
+                        .await?
+                        .file_metadata()
+                        .num_rows() as usize,
+                };
+            }
+        }
+        Ok(rows)
+    }
+
+    pub(crate) async fn table_metadata(
+        &self,
+        manager: &StoreManager,
+        level: usize,
+        gen: FileId,
+        parquet_lru: ParquetLru,
+    ) -> Result<Arc<ParquetMetaData>, VersionError<R>> {
+        let level_path = self
+            .option
+            .level_fs_path(level)
+            .unwrap_or(&self.option.base_path);
+        let file = fs::open_table(
+            manager.get_fs(level_path),
+            &self.option.table_path(gen, level),
+            true,
+            self.option.level_direct_io(level),
+        )
+        .await
+        .map_err(VersionError::Fusio)?;
+        SsTable::<R>::open(parquet_lru, gen, file)
+            .await?
+            .metadata()
+            .await
+            .map_err(VersionError::Parquet)
+    }
+
     pub(crate) fn tables_len(&self, level: usize) -> usize {
         self.level_slice[level].len()
     }
 
+    /// the bytes of the tables of `level`, counting the ones without statistics as full tables
+    pub(crate) fn level_bytes(&self, level: usize) -> u64 {
+        self.level_slice[level]
+            .iter()
+            .map(|scope| {
+                scope
+                    .stats
+                    .as_ref()
+                    .map_or(self.option.max_sst_file_size as u64, |stats| stats.bytes)
+            })
+            .sum()
+    }
+
     #[allow(clippy::too_many_arguments)]
     pub(crate) async fn streams<'streams>(
         &self,
@@ -221,31 +426,78 @@ where
         limit: Option<usize>,
         projection_mask: ProjectionMask,
         parquet_lru: ParquetLru,
+        limits: &ScanLimits,
+        stats: Option<&QueryStatsCollector>,
     ) -> Result<(), VersionError<R>> {
         let level_0_path = self
             .option
             .level_fs_path(0)
             .unwrap_or(&self.option.base_path);
         let level_0_fs = manager.get_fs(level_0_path);
-        for scope in self.level_slice[0].iter() {
-            if !scope.meets_range(range) {
-                continue;
+        let mut reads = Reads::new();
+        let level_0 = self.level_slice[0]
+            .iter()
+            .filter(|scope| scope.meets_range(range))
+            .collect::<Vec<_>>();
+        for &scope in &level_0 {
+            self.read(&mut reads, 0, scope);
+        }
+        // the levels below the first open a table at a time
+        let lower_levels = self.level_slice[1..]
+            .iter()
+            .filter(|scopes| scopes.iter().any(|scope| scope.meets_range(range)))
+            .count();
+        let level_0_budget = limits
+            .max_open_tables
+            .map(|max_open_tables| max_open_tables.saturating_sub(lower_levels));
+
+        if level_0_budget.is_some_and(|budget| level_0.len() > budget) {
+            for run in Self::sorted_runs(level_0) {
+                let mut inner = LevelStream::tables(
+                    self,
+                    0,
+                    run.iter().map(|scope| scope.gen).collect(),
+                    range,
+                    ts,
+                    limit,
+                    projection_mask.clone(),
+                    level_0_fs.clone(),
+                    parquet_lru.clone(),
+                )
+                .unwrap();
+                if let Some(permits) = &limits.read_permits {
+                    inner = inner.limit_reads(permits.clone());
+                }
+                streams.push(ScanStream::Level {
+                    inner: inner.collect_stats(stats.cloned()),
+                });
             }
-            let file = level_0_fs
-                .open_options(
+        } else {
+            for scope in level_0 {
+                let file = fs::open_table(
+                    level_0_fs,
                     &self.option.table_path(scope.gen, 0),
-                    FileType::Parquet.open_options(true),
+                    true,
+                    self.option.level_direct_io(0),
                 )
                 .await
                 .map_err(VersionError::Fusio)?;
-            let table = SsTable::open(parquet_lru.clone(), scope.gen, file).await?;
+                let mut table = SsTable::open(parquet_lru.clone(), scope.gen, file)
+                    .await?
+                    .collect_stats(stats);
+                if let Some(permits) = &limits.read_permits {
+                    table = table.limit_reads(permits.clone());
+                }
+                let table = table.readahead(self.option.scan_readahead_bytes);
 
-            streams.push(ScanStream::SsTable {
-                inner: table
-                    .scan(range, ts, limit, projection_mask.clone())
-                    .await
-                    .map_err(VersionError::Parquet)?,
-            })
+                streams.push(ScanStream::SsTable {
+                    inner: table
+                        .scan(range, ts, limit, projection_mask.clone())
+                        .await
+                        .map_err(VersionError::Parquet)?
+                        .prefetch(self.option.scan_prefetch_batches),
+                })
+            }
         }
         for (i, scopes) in self.level_slice[1..].iter().enumerate() {
             if scopes.is_empty() {
@@ -267,30 +519,52 @@ where
                     end = Some(idx);
                 }
             }
-            if start.is_none() {
+            let Some(start) = start else {
                 continue;
-            }
+            };
+            self.read(&mut reads, i + 1, &scopes[start]);
 
+            // SAFETY: checked scopes no empty
+            let mut inner = LevelStream::new(
+                self,
+                i + 1,
+                start,
+                end.unwrap(),
+                range,
+                ts,
+                limit,
+                projection_mask.clone(),
+                level_fs.clone(),
+                parquet_lru.clone(),
+            )
+            .unwrap();
+            if let Some(permits) = &limits.read_permits {
+                inner = inner.limit_reads(permits.clone());
+            }
             streams.push(ScanStream::Level {
-                // SAFETY: checked scopes no empty
-                inner: LevelStream::new(
-                    self,
-                    i + 1,
-                    start.unwrap(),
-                    end.unwrap(),
-                    range,
-                    ts,
-                    limit,
-                    projection_mask.clone(),
-                    level_fs.clone(),
-                    parquet_lru.clone(),
-                )
-                .unwrap(),
+                inner: inner.collect_stats(stats.cloned()),
             });
         }
         Ok(())
     }
 
+    /// pack the tables of level 0 into the fewest runs of tables which do not overlap each
+    /// other, in the order of their keys, each read a table at a time
+    fn sorted_runs(mut scopes: Vec<&Scope<R::Key>>) -> Vec<Vec<&Scope<R::Key>>> {
+        scopes.sort_by(|a, b| a.min.cmp(&b.min));
+        let mut runs: Vec<Vec<&Scope<R::Key>>> = Vec::new();
+        for scope in scopes {
+            match runs
+                .iter_mut()
+                .find(|run| run.last().is_some_and(|last| last.max < scope.min))
+            {
+                Some(run) => run.push(scope),
+                None => runs.push(vec![scope]),
+            }
+        }
+        runs
+    }
+
     pub(crate) fn to_edits(&self) -> Vec<VersionEdit<R::Key>> {
         let mut edits = Vec::new();
 
@@ -302,6 +576,11 @@ where
                 })
             }
         }
+        for range_tombstone in self.range_tombstones.iter() {
+            edits.push(VersionEdit::RangeTombstone {
+                range_tombstone: range_tombstone.clone(),
+            })
+        }
         edits.push(VersionEdit::LatestTimeStamp { ts: self.load_ts() });
         edits.push(VersionEdit::NewLogLength { len: 0 });
         edits
@@ -313,6 +592,14 @@ where
     R: Record,
 {
     fn drop(&mut self) {
+        if self.pinned {
+            if let Err(err) = self
+                .clean_sender
+                .send(CleanTag::Unpin { gens: self.gens() })
+            {
+                error!("[Version Drop Error]: {}", err)
+            }
+        }
         if let Err(err) = self.clean_sender.send(CleanTag::Clean { ts: self.ts }) {
             error!("[Version Drop Error]: {}", err)
         }
@@ -336,4 +623,8 @@ where
     UlidDecode(#[from] ulid::DecodeError),
     #[error("version send error: {0}")]
     Send(#[from] SendError<CleanTag>),
+    #[error("version edit is inconsistent: {0}")]
+    Inconsistent(String),
+    #[error("version log {log_id} is incomplete or corrupted from byte {offset}")]
+    Corrupted { log_id: FileId, offset: u64 },
 }
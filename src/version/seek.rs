@@ -0,0 +1,181 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use crate::{fs::FileId, record::Record, scope::Scope, version::Version};
+
+/// the fewest seeks a table is allowed, so that small tables are not compacted as soon as they
+/// are read along with another one
+const MIN_ALLOWED_SEEKS: u64 = 100;
+
+/// The seeks left to the tables read by gets and scans, shared by the versions of a
+/// [`VersionSet`](crate::version::set::VersionSet). A get or scan reading other tables after
+/// the first one for the same keys spends a seek of the first table, and the table running out
+/// of them is compacted with the tables it overlaps, as merging a fragmented range once costs
+/// less than reading it from many tables again and again.
+#[derive(Debug, Default)]
+pub(crate) struct SeekIndex {
+    allowed: Mutex<HashMap<FileId, u64>>,
+    hot: Mutex<Option<(usize, FileId)>>,
+}
+
+impl SeekIndex {
+    /// spend a seek of the table, allowed one per [`DbOption::bytes_per_seek`] of it
+    ///
+    /// [`DbOption::bytes_per_seek`]: crate::DbOption::bytes_per_seek
+    pub(crate) fn charge<R>(
+        &self,
+        version: &Version<R>,
+        bytes_per_seek: u64,
+        level: usize,
+        scope: &Scope<R::Key>,
+    ) where
+        R: Record,
+    {
+        let mut allowed = self.allowed.lock().unwrap();
+        let seeks = allowed.entry(scope.gen).or_insert_with(|| {
+            let bytes = scope.stats.as_ref().map_or(0, |stats| stats.bytes);
+            (bytes / bytes_per_seek.max(1)).max(MIN_ALLOWED_SEEKS)
+        });
+        *seeks = seeks.saturating_sub(1);
+        // level 0 is left to its own compactions, which merge all of its overlapping tables
+        if *seeks == 0 && level > 0 {
+            self.hot.lock().unwrap().get_or_insert((level, scope.gen));
+        }
+        let live = version.level_slice.iter().map(Vec::len).sum::<usize>();
+        if allowed.len() > live * 2 {
+            let gens = version.gens();
+            allowed.retain(|gen, _| gens.contains(gen));
+        }
+    }
+
+    /// the level and the table out of seeks, if it is still there in `version`
+    pub(crate) fn hot<'a, R>(&self, version: &'a Version<R>) -> Option<(usize, &'a Scope<R::Key>)>
+    where
+        R: Record,
+    {
+        let mut hot = self.hot.lock().unwrap();
+        let (level, gen) = (*hot)?;
+        match version.level_slice[level]
+            .iter()
+            .find(|scope| scope.gen == gen)
+        {
+            Some(scope) => Some((level, scope)),
+            None => {
+                // compacted, or moved to the next level, since
+                *hot = None;
+                None
+            }
+        }
+    }
+}
+
+/// The first table read by a get or scan, charged once another one is read after it.
+pub(crate) struct Reads<'a, K> {
+    first: Option<(usize, &'a Scope<K>)>,
+    charged: bool,
+}
+
+impl<'a, K> Reads<'a, K> {
+    pub(crate) fn new() -> Self {
+        Reads {
+            first: None,
+            charged: false,
+        }
+    }
+
+    /// the table to charge for the read of `scope`, at most one per get or scan
+    pub(crate) fn read(
+        &mut self,
+        level: usize,
+        scope: &'a Scope<K>,
+    ) -> Option<(usize, &'a Scope<K>)> {
+        match self.first {
+            None => {
+                self.first = Some((level, scope));
+                None
+            }
+            Some(first) => (!std::mem::replace(&mut self.charged, true)).then_some(first),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod tests {
+    use std::sync::{atomic::AtomicU64, Arc};
+
+    use flume::bounded;
+    use fusio::path::Path;
+
+    use super::{Reads, SeekIndex, MIN_ALLOWED_SEEKS};
+    use crate::{
+        fs::FileId,
+        scope::{Scope, TableStats},
+        tests::Test,
+        version::Version,
+        DbOption,
+    };
+
+    #[test]
+    fn run_out_of_seeks() {
+        let option = DbOption::<Test>::from(Path::from("run_out_of_seeks"));
+        let (sender, _) = bounded(1);
+        let mut version = Version::<Test>::new(
+            Arc::new(option),
+            sender,
+            Arc::new(AtomicU64::default()),
+            Default::default(),
+        );
+        for bytes in [1024, 200 * 1024] {
+            version.level_slice[1].push(Scope {
+                min: bytes.to_string(),
+                max: bytes.to_string(),
+                gen: FileId::new(),
+                wal_ids: None,
+                stats: Some(TableStats {
+                    bytes,
+                    ..Default::default()
+                }),
+            });
+        }
+        let seeks = SeekIndex::default();
+        let (small, large) = (&version.level_slice[1][0], &version.level_slice[1][1]);
+
+        for _ in 0..MIN_ALLOWED_SEEKS - 1 {
+            seeks.charge(&version, 1024, 1, small);
+            seeks.charge(&version, 1024, 1, large);
+        }
+        assert!(seeks.hot(&version).is_none());
+        seeks.charge(&version, 1024, 1, small);
+        assert_eq!(seeks.hot(&version).unwrap().1.gen, small.gen);
+
+        // gone from the version
+        let mut version = version.clone();
+        version.level_slice[1].remove(0);
+        assert!(seeks.hot(&version).is_none());
+
+        // 200 seeks, 99 spent before
+        for _ in 0..101 {
+            seeks.charge(&version, 1024, 1, &version.level_slice[1][0]);
+        }
+        assert_eq!(
+            seeks.hot(&version).unwrap().1.gen,
+            version.level_slice[1][0].gen
+        );
+    }
+
+    #[test]
+    fn charge_the_first_read() {
+        let scope = |key: &str| Scope {
+            min: key.to_string(),
+            max: key.to_string(),
+            gen: FileId::new(),
+            wal_ids: None,
+            stats: None,
+        };
+        let (first, second, third) = (scope("a"), scope("b"), scope("c"));
+        let mut reads = Reads::new();
+
+        assert!(reads.read(0, &first).is_none());
+        assert_eq!(reads.read(1, &second).unwrap().1.gen, first.gen);
+        assert!(reads.read(2, &third).is_none());
+    }
+}
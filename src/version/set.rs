@@ -1,28 +1,52 @@
 use std::{
-    collections::BinaryHeap,
+    collections::{BinaryHeap, HashSet},
     io::Cursor,
     mem,
+    ops::Range,
     sync::{
-        atomic::{AtomicU32, Ordering},
-        Arc,
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
     },
 };
 
 use async_lock::RwLock;
 use flume::Sender;
-use fusio::{dynamic::DynFile, fs::FileMeta};
+use fusio::{
+    dynamic::DynFile,
+    fs::{FileMeta, OpenOptions},
+};
 use futures_util::StreamExt;
+use tokio_util::bytes::Bytes;
+use tracing::warn;
 
-use super::{TransactionTs, MAX_LEVEL};
+use super::TransactionTs;
 use crate::{
-    fs::{manager::StoreManager, parse_file_id, FileId, FileType},
+    compaction::job::Jobs,
+    fs::{self, manager::StoreManager, parse_file_id, FileId, FileType},
+    gc::GcWatermark,
     record::Record,
-    serdes::Encode,
+    scope::Scope,
+    statistics::Statistics,
     timestamp::Timestamp,
     version::{cleaner::CleanTag, edit::VersionEdit, Version, VersionError, VersionRef},
-    DbOption,
+    DbOption, VersionLogRecoveryMode,
 };
 
+/// The tables reserved by [`VersionSet::reserve`], released once dropped.
+pub(crate) struct Reservation {
+    gens: Vec<FileId>,
+    compacting: Arc<Mutex<HashSet<FileId>>>,
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        let mut compacting = self.compacting.lock().unwrap();
+        for gen in self.gens.iter() {
+            compacting.remove(gen);
+        }
+    }
+}
+
 struct CmpMeta(FileMeta);
 
 impl Eq for CmpMeta {}
@@ -59,9 +83,18 @@ where
 {
     inner: Arc<RwLock<VersionSetInner<R>>>,
     clean_sender: Sender<CleanTag>,
-    timestamp: Arc<AtomicU32>,
-    option: Arc<DbOption<R>>,
+    timestamp: Arc<AtomicU64>,
+    statistics: Arc<Statistics>,
+    gc: Arc<GcWatermark>,
+    /// the tables merged by the running background compactions
+    compacting: Arc<Mutex<HashSet<FileId>>>,
+    jobs: Arc<Jobs>,
+    /// swapped by [`VersionSet::update_option`], the versions installed afterwards carry the new
+    /// one
+    option: Arc<Mutex<Arc<DbOption<R>>>>,
     manager: Arc<StoreManager>,
+    /// recovered without touching the log, WALs or SSTs, and never edited
+    read_only: bool,
 }
 
 impl<R> Clone for VersionSet<R>
@@ -73,8 +106,13 @@ where
             inner: self.inner.clone(),
             clean_sender: self.clean_sender.clone(),
             timestamp: self.timestamp.clone(),
+            statistics: self.statistics.clone(),
+            gc: self.gc.clone(),
+            compacting: self.compacting.clone(),
+            jobs: self.jobs.clone(),
             option: self.option.clone(),
             manager: self.manager.clone(),
+            read_only: self.read_only,
         }
     }
 }
@@ -90,12 +128,76 @@ where
     fn increase_ts(&self) -> Timestamp {
         (self.timestamp.fetch_add(1, Ordering::Release) + 1).into()
     }
+
+    fn advance_ts(&self, ts: Timestamp) {
+        self.timestamp.fetch_max(ts.into(), Ordering::Release);
+    }
 }
 
 impl<R> VersionSet<R>
 where
     R: Record,
 {
+    pub(crate) fn option(&self) -> Arc<DbOption<R>> {
+        self.option.lock().unwrap().clone()
+    }
+
+    pub(crate) fn clean_sender(&self) -> &Sender<CleanTag> {
+        &self.clean_sender
+    }
+
+    /// replace the option by the one `update` makes of it, unless it fails
+    pub(crate) fn update_option<E>(
+        &self,
+        update: impl FnOnce(&DbOption<R>) -> Result<DbOption<R>, E>,
+    ) -> Result<Arc<DbOption<R>>, E> {
+        let mut option = self.option.lock().unwrap();
+        *option = Arc::new(update(&option)?);
+        Ok(option.clone())
+    }
+
+    pub(crate) fn statistics(&self) -> &Arc<Statistics> {
+        &self.statistics
+    }
+
+    pub(crate) fn gc(&self) -> &Arc<GcWatermark> {
+        &self.gc
+    }
+
+    pub(crate) fn jobs(&self) -> &Arc<Jobs> {
+        &self.jobs
+    }
+
+    /// reserve the tables of `levels` in `version` for a background compaction, `None` if
+    /// another one merges any of them already
+    pub(crate) fn reserve(
+        &self,
+        version: &Version<R>,
+        levels: Range<usize>,
+    ) -> Option<Reservation> {
+        self.reserve_tables(
+            version.level_slice[levels]
+                .iter()
+                .flatten()
+                .map(Scope::gen)
+                .collect(),
+        )
+    }
+
+    /// reserve the tables `gens`, `None` if a background compaction merges any of them already
+    pub(crate) fn reserve_tables(&self, gens: Vec<FileId>) -> Option<Reservation> {
+        let mut compacting = self.compacting.lock().unwrap();
+        if gens.iter().any(|gen| compacting.contains(gen)) {
+            return None;
+        }
+        compacting.extend(gens.iter().copied());
+
+        Some(Reservation {
+            gens,
+            compacting: self.compacting.clone(),
+        })
+    }
+
     pub(crate) async fn new(
         clean_sender: Sender<CleanTag>,
         option: Arc<DbOption<R>>,
@@ -137,118 +239,254 @@ where
             .flatten()
             .unwrap_or_else(FileId::new);
 
-        let mut log = fs
+        let log = fs
             .open_options(
                 &option.version_log_path(log_id),
                 FileType::Log.open_options(false),
             )
             .await?;
+        drop(log_stream);
 
-        let edits = VersionEdit::recover(&mut Cursor::new(&mut log)).await;
+        Self::recover(clean_sender, option, manager, log, log_id, false).await
+    }
 
-        let timestamp = Arc::new(AtomicU32::default());
+    /// recover the version of a database that may be opened by another process, without removing
+    /// or writing any version log
+    pub(crate) async fn read_only(
+        clean_sender: Sender<CleanTag>,
+        option: Arc<DbOption<R>>,
+        manager: Arc<StoreManager>,
+    ) -> Result<Self, VersionError<R>> {
+        let (log, log_id) = Self::open_log_read_only(&option, &manager).await?;
+
+        Self::recover(clean_sender, option, manager, log, log_id, true).await
+    }
+
+    /// reload the version of a read-only version set from the log, which may have been appended
+    /// to or rewritten by the process owning the database since
+    pub(crate) async fn catch_up(&self) -> Result<(), VersionError<R>> {
+        debug_assert!(self.read_only);
+        let option = self.option();
+        let (mut log, log_id) = Self::open_log_read_only(&option, &self.manager).await?;
+        // the end may still be written by the process owning the database
+        let (edits, _) = VersionEdit::recover(&mut Cursor::new(&mut log)).await?;
+
+        let mut guard = self.inner.write().await;
+        let mut new_version = Version::new(
+            option,
+            self.clean_sender.clone(),
+            self.timestamp.clone(),
+            self.statistics.clone(),
+        );
+        for version_edit in edits {
+            self.apply_edit(&mut new_version, version_edit, true)
+                .await?;
+        }
+        new_version.recovered();
+        new_version.pin().await?;
+        guard.current = Arc::new(new_version);
+        guard.log_with_id = (log, log_id);
+
+        Ok(())
+    }
+
+    async fn open_log_read_only(
+        option: &DbOption<R>,
+        manager: &StoreManager,
+    ) -> Result<(Box<dyn DynFile>, FileId), VersionError<R>> {
+        let fs = manager.base_fs();
+        let mut log_ids = Vec::new();
+        let mut log_stream = fs.list(&option.version_log_dir_path()).await?;
+
+        while let Some(result) = log_stream.next().await {
+            log_ids.extend(parse_file_id(&result?.path, FileType::Log)?);
+        }
         drop(log_stream);
+        // a newer log may still be written by the snapshot of the live database, which removes
+        // the older one once done
+        log_ids.sort();
+        let mut last_err = None;
+        for log_id in log_ids {
+            match fs
+                .open_options(
+                    &option.version_log_path(log_id),
+                    OpenOptions::default().read(true),
+                )
+                .await
+            {
+                Ok(log) => return Ok((log, log_id)),
+                Err(err) => last_err = Some(err),
+            }
+        }
+        Err(match last_err {
+            Some(err) => err.into(),
+            None => {
+                std::io::Error::new(std::io::ErrorKind::NotFound, "version log not found").into()
+            }
+        })
+    }
+
+    async fn recover(
+        clean_sender: Sender<CleanTag>,
+        option: Arc<DbOption<R>>,
+        manager: Arc<StoreManager>,
+        mut log: Box<dyn DynFile>,
+        log_id: FileId,
+        read_only: bool,
+    ) -> Result<Self, VersionError<R>> {
+        let (edits, discarded) = VersionEdit::recover(&mut Cursor::new(&mut log)).await?;
+        if let Some(discarded) = discarded {
+            let log_path = option.version_log_path(log_id);
+            let tail = Bytes::from(fs::read_file(manager.base_fs(), &log_path).await?)
+                .slice(discarded.offset as usize..);
+            // only the end of the log is torn by a crash
+            if VersionEdit::<R::Key>::has_valid_batch(tail).await {
+                return Err(VersionError::Corrupted {
+                    log_id,
+                    offset: discarded.offset,
+                });
+            }
+        }
+        let (log, log_id) = match discarded {
+            Some(discarded) if !read_only => {
+                if option.version_log_recovery_mode == VersionLogRecoveryMode::Strict {
+                    return Err(VersionError::Corrupted {
+                        log_id,
+                        offset: discarded.offset,
+                    });
+                }
+                warn!(
+                    "[Version Recover]: discarding the end of version log {} from byte {}, {}",
+                    log_id,
+                    discarded.offset,
+                    if discarded.checksum_failed {
+                        "which fails its checksum"
+                    } else {
+                        "which is incomplete"
+                    }
+                );
+                drop(log);
+                Self::rewrite_log(&option, &manager, &edits, log_id).await?
+            }
+            _ => (log, log_id),
+        };
+
+        let timestamp = Arc::new(AtomicU64::default());
+        let statistics = Arc::new(Statistics::default());
         let set = VersionSet::<R> {
             inner: Arc::new(RwLock::new(VersionSetInner {
-                current: Arc::new(Version::<R> {
-                    ts: Timestamp::from(0),
-                    level_slice: [const { Vec::new() }; MAX_LEVEL],
-                    clean_sender: clean_sender.clone(),
-                    option: option.clone(),
-                    timestamp: timestamp.clone(),
-                    log_length: 0,
-                }),
+                current: Arc::new(Version::new(
+                    option.clone(),
+                    clean_sender.clone(),
+                    timestamp.clone(),
+                    statistics.clone(),
+                )),
                 log_with_id: (log, log_id),
             })),
             clean_sender,
             timestamp,
-            option,
+            statistics,
+            gc: Default::default(),
+            compacting: Default::default(),
+            jobs: Default::default(),
+            option: Arc::new(Mutex::new(option)),
             manager,
+            read_only,
         };
         set.apply_edits(edits, None, true).await?;
 
         Ok(set)
     }
 
+    /// copy the `edits` recovered from the log `log_id` into a new log replacing it, so the edits
+    /// appended afterwards do not follow the end discarded. The old log is moved into the
+    /// `quarantine` directory rather than removed.
+    async fn rewrite_log(
+        option: &DbOption<R>,
+        manager: &StoreManager,
+        edits: &[VersionEdit<R::Key>],
+        log_id: FileId,
+    ) -> Result<(Box<dyn DynFile>, FileId), VersionError<R>> {
+        let fs = manager.base_fs();
+        let new_log_id = FileId::new();
+        let mut log = fs
+            .open_options(
+                &option.version_log_path(new_log_id),
+                FileType::Log.open_options(false),
+            )
+            .await?;
+        VersionEdit::encode_batch(edits, &mut log, option.format_version)
+            .await
+            .map_err(VersionError::Encode)?;
+        log.close().await?;
+
+        let log_path = option.version_log_path(log_id);
+        let quarantine_dir = option.quarantine_dir_path();
+        fs.create_dir_all(&quarantine_dir).await?;
+        fs::copy_file(
+            fs,
+            &log_path,
+            fs,
+            &quarantine_dir.child(log_path.filename().unwrap()),
+        )
+        .await?;
+        fs.remove(&log_path).await?;
+
+        Ok((log, new_log_id))
+    }
+
     pub(crate) async fn current(&self) -> VersionRef<R> {
         self.inner.read().await.current.clone()
     }
 
     pub(crate) async fn apply_edits(
+        &self,
+        version_edits: Vec<VersionEdit<R::Key>>,
+        delete_gens: Option<Vec<(FileId, usize)>>,
+        is_recover: bool,
+    ) -> Result<(), VersionError<R>> {
+        self.apply_compaction_edits(version_edits, delete_gens, is_recover, None)
+            .await
+    }
+
+    /// same as [`VersionSet::apply_edits`], for a compaction which kept the versions newer than
+    /// the GC `watermark`
+    pub(crate) async fn apply_compaction_edits(
         &self,
         mut version_edits: Vec<VersionEdit<R::Key>>,
         delete_gens: Option<Vec<(FileId, usize)>>,
         is_recover: bool,
+        watermark: Option<Timestamp>,
     ) -> Result<(), VersionError<R>> {
-        let timestamp = &self.timestamp;
-        let option = &self.option;
+        let option = self.option();
         let mut guard = self.inner.write().await;
         let mut new_version = Version::clone(&guard.current);
+        new_version.option = option.clone();
         let (log, log_id) = &mut guard.log_with_id;
         let edit_len = new_version.log_length + version_edits.len() as u32;
 
+        if option.paranoid_checks && !is_recover {
+            self.check_edits(&new_version, &version_edits).await?;
+        }
         if !is_recover {
             version_edits.push(VersionEdit::NewLogLength { len: edit_len });
+            VersionEdit::encode_batch(&version_edits, log, option.format_version)
+                .await
+                .map_err(VersionError::Encode)?;
         }
+        let compacted = version_edits
+            .iter()
+            .any(|edit| matches!(edit, VersionEdit::Remove { .. }));
         for version_edit in version_edits {
-            if !is_recover {
-                version_edit
-                    .encode(log)
-                    .await
-                    .map_err(VersionError::Encode)?;
-            }
-            match version_edit {
-                VersionEdit::Add { mut scope, level } => {
-                    if let Some(wal_ids) = scope.wal_ids.take() {
-                        for wal_id in wal_ids {
-                            // may have been removed after multiple starts
-                            let _ = self
-                                .manager
-                                .base_fs()
-                                .remove(&option.wal_path(wal_id))
-                                .await;
-                        }
-                    }
-                    if level == 0 {
-                        new_version.level_slice[level as usize].push(scope);
-                    } else {
-                        // TODO: Add is often consecutive, so repeated queries can be avoided
-                        let sort_runs = &mut new_version.level_slice[level as usize];
-                        let pos = sort_runs
-                            .binary_search_by(|s| s.min.cmp(&scope.min))
-                            .unwrap_or_else(|index| index);
-                        sort_runs.insert(pos, scope);
-                    }
-                }
-                VersionEdit::Remove { gen, level } => {
-                    if let Some(i) = new_version.level_slice[level as usize]
-                        .iter()
-                        .position(|scope| scope.gen == gen)
-                    {
-                        new_version.level_slice[level as usize].remove(i);
-                    }
-                    if is_recover {
-                        // issue: https://github.com/tonbo-io/tonbo/issues/123
-                        new_version
-                            .clean_sender
-                            .send_async(CleanTag::RecoverClean {
-                                wal_id: gen,
-                                level: level as usize,
-                            })
-                            .await
-                            .map_err(VersionError::Send)?;
-                    }
-                }
-                VersionEdit::LatestTimeStamp { ts } => {
-                    if is_recover {
-                        timestamp.store(u32::from(ts), Ordering::Release);
-                    }
-                    new_version.ts = ts;
-                }
-                VersionEdit::NewLogLength { len } => {
-                    new_version.log_length = len;
-                }
-            }
+            self.apply_edit(&mut new_version, version_edit, is_recover)
+                .await?;
+        }
+        if is_recover {
+            new_version.recovered();
+        } else if compacted {
+            let load_ts = self.load_ts();
+            let retained = watermark.map_or(load_ts, |watermark| watermark.min(load_ts));
+            new_version.retained_ts = new_version.retained_ts.max(retained);
         }
         if let Some(delete_gens) = delete_gens {
             new_version
@@ -260,6 +498,11 @@ where
                 .await
                 .map_err(VersionError::Send)?;
         }
+        new_version.pin().await?;
+        if self.read_only {
+            guard.current = Arc::new(new_version);
+            return Ok(());
+        }
         log.close().await?;
         if edit_len >= option.version_log_snapshot_threshold {
             let fs = self.manager.base_fs();
@@ -270,18 +513,217 @@ where
                     FileType::Log.open_options(false),
                 )
                 .await?;
-            let _old_log = mem::replace(log, new_log);
+            let old_log = mem::replace(log, new_log);
 
             new_version.log_length = 0;
-            for new_edit in new_version.to_edits() {
-                new_edit.encode(log).await.map_err(VersionError::Encode)?;
-            }
+            VersionEdit::encode_batch(&new_version.to_edits(), log, option.format_version)
+                .await
+                .map_err(VersionError::Encode)?;
             log.close().await?;
+            // Windows keeps the name of a file removed while open until it is closed
+            drop(old_log);
             fs.remove(&option.version_log_path(old_log_id)).await?;
         }
         guard.current = Arc::new(new_version);
         Ok(())
     }
+
+    /// check that the `version_edits` only remove tables of the `version` and add tables which
+    /// exist, and leave the tables of each level below the first sorted without overlapping,
+    /// before they are written to the version log
+    async fn check_edits(
+        &self,
+        version: &Version<R>,
+        version_edits: &[VersionEdit<R::Key>],
+    ) -> Result<(), VersionError<R>> {
+        let option = self.option();
+        let mut level_slice = version.level_slice.clone();
+
+        for version_edit in version_edits {
+            match version_edit {
+                VersionEdit::Add { level, scope } => {
+                    let level = *level as usize;
+                    if scope.min > scope.max {
+                        return Err(VersionError::Inconsistent(format!(
+                            "table {} added to level {} has its min key above its max key",
+                            scope.gen, level
+                        )));
+                    }
+                    self.check_table(&option, scope, level, "added to").await?;
+                    level_slice[level].push(scope.clone());
+                }
+                VersionEdit::Remove { level, gen } => {
+                    let scopes = &mut level_slice[*level as usize];
+                    let Some(i) = scopes.iter().position(|scope| scope.gen == *gen) else {
+                        return Err(VersionError::Inconsistent(format!(
+                            "table {} removed from level {} is not in it",
+                            gen, level
+                        )));
+                    };
+                    scopes.remove(i);
+                }
+                _ => (),
+            }
+        }
+        Self::check_overlaps(&mut level_slice)
+    }
+
+    /// check that the tables of the current version exist and that the ones of each level below
+    /// the first do not overlap, as [`VersionSet::check_edits`] does for the tables added
+    pub(crate) async fn check_current(&self) -> Result<(), VersionError<R>> {
+        let option = self.option();
+        let mut level_slice = self.current().await.level_slice.clone();
+
+        for (level, scopes) in level_slice.iter().enumerate() {
+            for scope in scopes {
+                self.check_table(&option, scope, level, "of").await?;
+            }
+        }
+        Self::check_overlaps(&mut level_slice)
+    }
+
+    async fn check_table(
+        &self,
+        option: &DbOption<R>,
+        scope: &Scope<R::Key>,
+        level: usize,
+        relation: &str,
+    ) -> Result<(), VersionError<R>> {
+        let path = option.table_path(scope.gen, level);
+        self.manager
+            .get_fs(option.level_fs_path(level).unwrap_or(&option.base_path))
+            .open_options(&path, FileType::Parquet.open_options(true))
+            .await
+            .map_err(|err| {
+                VersionError::Inconsistent(format!(
+                    "table {} {} level {} cannot be opened: {}",
+                    scope.gen, relation, level, err
+                ))
+            })?;
+        Ok(())
+    }
+
+    fn check_overlaps(level_slice: &mut [Vec<Scope<R::Key>>]) -> Result<(), VersionError<R>> {
+        // the tables of level 0 are the flushed memtables, which may overlap
+        for (level, scopes) in level_slice.iter_mut().enumerate().skip(1) {
+            scopes.sort_by(|a, b| a.min.cmp(&b.min));
+            if let Some(pair) = scopes.windows(2).find(|pair| pair[0].max >= pair[1].min) {
+                return Err(VersionError::Inconsistent(format!(
+                    "tables {} and {} of level {} overlap",
+                    pair[0].gen, pair[1].gen, level
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    async fn apply_edit(
+        &self,
+        new_version: &mut Version<R>,
+        version_edit: VersionEdit<R::Key>,
+        is_recover: bool,
+    ) -> Result<(), VersionError<R>> {
+        match version_edit {
+            VersionEdit::Add { mut scope, level } => {
+                if let Some(wal_ids) = scope.wal_ids.take().filter(|_| !self.read_only) {
+                    for wal_id in wal_ids {
+                        self.archive_wal(wal_id, is_recover).await?;
+                    }
+                }
+                if level == 0 {
+                    new_version.level_slice[level as usize].push(scope);
+                } else {
+                    // TODO: Add is often consecutive, so repeated queries can be avoided
+                    let sort_runs = &mut new_version.level_slice[level as usize];
+                    let pos = sort_runs
+                        .binary_search_by(|s| s.min.cmp(&scope.min))
+                        .unwrap_or_else(|index| index);
+                    sort_runs.insert(pos, scope);
+                }
+            }
+            VersionEdit::Remove { gen, level } => {
+                if let Some(i) = new_version.level_slice[level as usize]
+                    .iter()
+                    .position(|scope| scope.gen == gen)
+                {
+                    new_version.level_slice[level as usize].remove(i);
+                }
+                // a table moved to the next level by a compaction is added there first
+                let moved = new_version
+                    .level_slice
+                    .iter()
+                    .flatten()
+                    .any(|scope| scope.gen == gen);
+                if is_recover && !self.read_only && !moved {
+                    // issue: https://github.com/tonbo-io/tonbo/issues/123
+                    new_version
+                        .clean_sender
+                        .send_async(CleanTag::RecoverClean {
+                            wal_id: gen,
+                            level: level as usize,
+                        })
+                        .await
+                        .map_err(VersionError::Send)?;
+                }
+            }
+            VersionEdit::LatestTimeStamp { ts } => {
+                if is_recover {
+                    self.timestamp.store(u64::from(ts), Ordering::Release);
+                }
+                new_version.ts = ts;
+            }
+            VersionEdit::NewLogLength { len } => {
+                new_version.log_length = len;
+            }
+            VersionEdit::RangeTombstone { range_tombstone } => {
+                new_version.range_tombstones.insert(range_tombstone);
+            }
+            VersionEdit::RemoveRangeTombstone { ts } => {
+                new_version.range_tombstones.remove(ts);
+            }
+        }
+        Ok(())
+    }
+
+    /// move the flushed WAL into the archive if enabled, otherwise remove it
+    async fn archive_wal(&self, wal_id: FileId, is_recover: bool) -> Result<(), VersionError<R>> {
+        let option = self.option();
+        let wal_fs = self.manager.get_fs(&option.wal_dir_path());
+        let wal_path = option.wal_path(wal_id);
+
+        if let Some(archive_path) = option.wal_archive_path() {
+            let archive_fs = self.manager.get_fs(archive_path);
+            let result = fs::copy_file(
+                wal_fs,
+                &wal_path,
+                archive_fs,
+                &archive_path.child(wal_path.filename().unwrap()),
+            )
+            .await;
+            // may have been archived after multiple starts
+            if !is_recover {
+                result?;
+            }
+            if let Some(max_files) = option.wal_archive_max_files {
+                let archived = fs::list_files(archive_fs, archive_path, FileType::Wal).await?;
+
+                if archived.len() > max_files {
+                    let mut archived = archived
+                        .into_iter()
+                        .map(|path| (path.filename().unwrap().to_string(), path))
+                        .collect::<Vec<_>>();
+                    archived.sort();
+                    for (_, path) in &archived[..archived.len() - max_files] {
+                        archive_fs.remove(path).await?;
+                    }
+                }
+            }
+        }
+        // may have been removed after multiple starts
+        let _ = wal_fs.remove(&wal_path).await;
+
+        Ok(())
+    }
 }
 
 #[cfg(all(test, feature = "tokio"))]
@@ -290,13 +732,17 @@ pub(crate) mod tests {
 
     use async_lock::RwLock;
     use flume::{bounded, Sender};
-    use fusio::path::Path;
+    use fusio::{disk::TokioFs, path::Path};
     use fusio_dispatch::FsOptions;
     use futures_util::StreamExt;
     use tempfile::TempDir;
 
     use crate::{
-        fs::{manager::StoreManager, FileId, FileType},
+        fs::{
+            fault::{Faults, FaultyFs},
+            manager::StoreManager,
+            FileId, FileType,
+        },
         record::Record,
         scope::Scope,
         version::{
@@ -326,6 +772,7 @@ pub(crate) mod tests {
             )
             .await?;
         let timestamp = version.timestamp.clone();
+        let statistics = version.statistics.clone();
 
         Ok(VersionSet::<R> {
             inner: Arc::new(RwLock::new(VersionSetInner {
@@ -334,8 +781,13 @@ pub(crate) mod tests {
             })),
             clean_sender,
             timestamp,
+            statistics,
+            gc: Default::default(),
+            compacting: Default::default(),
+            jobs: Default::default(),
             option,
             manager,
+            read_only: false,
         })
     }
 
@@ -360,7 +812,7 @@ pub(crate) mod tests {
 
         version_set
             .apply_edits(
-                vec![VersionEdit::LatestTimeStamp { ts: 20_u32.into() }],
+                vec![VersionEdit::LatestTimeStamp { ts: 20_u64.into() }],
                 None,
                 false,
             )
@@ -373,7 +825,134 @@ pub(crate) mod tests {
             VersionSet::new(sender.clone(), option.clone(), manager)
                 .await
                 .unwrap();
-        assert_eq!(version_set.load_ts(), 20_u32.into());
+        assert_eq!(version_set.load_ts(), 20_u64.into());
+    }
+
+    /// apply edits through partial writes and delayed flushes until one fails, crash, and recover
+    /// through read errors. The timestamp recovered must include every edit flushed durably, and
+    /// a failed read must not be taken for the end of the log
+    #[tokio::test]
+    async fn version_log_crash_recovery() {
+        let write_faults = Faults {
+            partial_write: 0.05,
+            delayed_flush: 0.3,
+            ..Default::default()
+        };
+        let read_faults = Faults {
+            read_error: 0.3,
+            ..Default::default()
+        };
+
+        for seed in 0..64 {
+            let temp_dir = TempDir::new().unwrap();
+            let mut option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap());
+            // the edits all stay in the first log
+            option.version_log_snapshot_threshold = u32::MAX;
+            let option = Arc::new(option);
+            let (sender, _) = bounded(1);
+
+            let fs = Arc::new(FaultyFs::new(TokioFs, write_faults, seed));
+            let manager = Arc::new(StoreManager::with_base_fs(fs.clone()));
+            manager
+                .base_fs()
+                .create_dir_all(&option.version_log_dir_path())
+                .await
+                .unwrap();
+            let (mut written, mut durable) = (0, 0);
+            {
+                let version_set: VersionSet<String> =
+                    VersionSet::new(sender.clone(), option.clone(), manager)
+                        .await
+                        .unwrap();
+                for ts in 1..64_u64 {
+                    if version_set
+                        .apply_edits(
+                            vec![VersionEdit::LatestTimeStamp { ts: ts.into() }],
+                            None,
+                            false,
+                        )
+                        .await
+                        .is_err()
+                    {
+                        break;
+                    }
+                    written = ts;
+                    if fs.is_durable() {
+                        durable = ts;
+                    }
+                }
+            }
+            fs.crash().unwrap();
+
+            let manager = Arc::new(StoreManager::new(FsOptions::Local, vec![]).unwrap());
+            let version_set: VersionSet<String> =
+                VersionSet::new(sender.clone(), option.clone(), manager)
+                    .await
+                    .unwrap();
+            let recovered = u64::from(version_set.load_ts());
+            assert!(
+                durable <= recovered && recovered <= written,
+                "seed {}",
+                seed
+            );
+            drop(version_set);
+
+            let fs = Arc::new(FaultyFs::new(TokioFs, read_faults, seed));
+            let manager = Arc::new(StoreManager::with_base_fs(fs));
+            match VersionSet::<String>::new(sender.clone(), option.clone(), manager).await {
+                Ok(version_set) => {
+                    assert_eq!(u64::from(version_set.load_ts()), recovered, "seed {}", seed)
+                }
+                Err(VersionError::Fusio(_) | VersionError::Io(_)) => {}
+                Err(err) => panic!("seed {}: {}", seed, err),
+            }
+
+            // nothing is lost by the failed reads
+            let manager = Arc::new(StoreManager::new(FsOptions::Local, vec![]).unwrap());
+            let version_set: VersionSet<String> =
+                VersionSet::new(sender, option, manager).await.unwrap();
+            assert_eq!(u64::from(version_set.load_ts()), recovered, "seed {}", seed);
+        }
+    }
+
+    #[tokio::test]
+    async fn reserve_tables() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = Arc::new(StoreManager::new(FsOptions::Local, vec![]).unwrap());
+        let (sender, _) = bounded(1);
+        let option = Arc::new(DbOption::from(
+            Path::from_filesystem_path(temp_dir.path()).unwrap(),
+        ));
+        manager
+            .base_fs()
+            .create_dir_all(&option.version_log_dir_path())
+            .await
+            .unwrap();
+
+        let version_set: VersionSet<String> =
+            VersionSet::new(sender, option, manager).await.unwrap();
+        let edits = (0..4)
+            .map(|level| VersionEdit::Add {
+                level,
+                scope: Scope {
+                    min: "0".to_string(),
+                    max: "9".to_string(),
+                    gen: FileId::new(),
+                    wal_ids: None,
+                    stats: None,
+                },
+            })
+            .collect();
+        version_set.apply_edits(edits, None, false).await.unwrap();
+        let version = version_set.current().await;
+
+        let first = version_set.reserve(&version, 0..2).unwrap();
+        assert!(version_set.reserve(&version, 1..3).is_none());
+        let second = version_set.reserve(&version, 2..4).unwrap();
+        drop(first);
+        assert!(version_set.reserve(&version, 1..3).is_none());
+        drop(second);
+        assert!(version_set.reserve(&version, 1..3).is_some());
     }
 
     #[tokio::test]
@@ -409,6 +988,7 @@ pub(crate) mod tests {
                             max: "1".to_string(),
                             gen: gen_0,
                             wal_ids: None,
+                            stats: None,
                         },
                     },
                     VersionEdit::Add {
@@ -418,6 +998,7 @@ pub(crate) mod tests {
                             max: "3".to_string(),
                             gen: gen_1,
                             wal_ids: None,
+                            stats: None,
                         },
                     },
                     VersionEdit::Add {
@@ -427,6 +1008,7 @@ pub(crate) mod tests {
                             max: "5".to_string(),
                             gen: gen_2,
                             wal_ids: None,
+                            stats: None,
                         },
                     },
                     VersionEdit::Remove {
@@ -447,7 +1029,9 @@ pub(crate) mod tests {
         let mut guard = version_set.inner.write().await;
         let log = &mut guard.log_with_id.0;
 
-        let edits = VersionEdit::<String>::recover(&mut Cursor::new(log)).await;
+        let (edits, _) = VersionEdit::<String>::recover(&mut Cursor::new(log))
+            .await
+            .unwrap();
 
         assert_eq!(edits.len(), 3);
         assert_eq!(
@@ -460,6 +1044,7 @@ pub(crate) mod tests {
                         max: "3".to_string(),
                         gen: gen_1,
                         wal_ids: None,
+                        stats: None,
                     },
                 },
                 VersionEdit::LatestTimeStamp { ts: 0.into() },
@@ -483,7 +1068,9 @@ pub(crate) mod tests {
             .open_options(&logs.pop().unwrap().path, FileType::Log.open_options(false))
             .await
             .unwrap();
-        let edits = VersionEdit::<String>::recover(&mut Cursor::new(log)).await;
+        let (edits, _) = VersionEdit::<String>::recover(&mut Cursor::new(log))
+            .await
+            .unwrap();
 
         assert_eq!(edits.len(), 3);
         assert_eq!(
@@ -496,6 +1083,7 @@ pub(crate) mod tests {
                         max: "3".to_string(),
                         gen: gen_1,
                         wal_ids: None,
+                        stats: None,
                     },
                 },
                 VersionEdit::LatestTimeStamp { ts: 0.into() },
@@ -536,6 +1124,7 @@ pub(crate) mod tests {
                         max: "6".to_string(),
                         gen: gen_0,
                         wal_ids: None,
+                        stats: None,
                     },
                 }],
                 None,
@@ -553,6 +1142,7 @@ pub(crate) mod tests {
                             max: "3".to_string(),
                             gen: gen_1,
                             wal_ids: None,
+                            stats: None,
                         },
                     },
                     VersionEdit::Add {
@@ -562,6 +1152,7 @@ pub(crate) mod tests {
                             max: "9".to_string(),
                             gen: gen_2,
                             wal_ids: None,
+                            stats: None,
                         },
                     },
                     VersionEdit::Add {
@@ -571,6 +1162,7 @@ pub(crate) mod tests {
                             max: "0".to_string(),
                             gen: gen_3,
                             wal_ids: None,
+                            stats: None,
                         },
                     },
                 ],
@@ -595,4 +1187,88 @@ pub(crate) mod tests {
             ]
         );
     }
+
+    #[tokio::test]
+    async fn paranoid_checks() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = Arc::new(StoreManager::new(FsOptions::Local, vec![]).unwrap());
+        let mut option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap());
+        option.paranoid_checks = true;
+        let option = Arc::new(option);
+
+        let (sender, _) = bounded(1);
+        manager
+            .base_fs()
+            .create_dir_all(&option.version_log_dir_path())
+            .await
+            .unwrap();
+
+        let version_set: VersionSet<String> =
+            VersionSet::new(sender.clone(), option.clone(), manager.clone())
+                .await
+                .unwrap();
+        let add = |min: &str, max: &str, gen| VersionEdit::Add {
+            level: 1,
+            scope: Scope {
+                min: min.to_string(),
+                max: max.to_string(),
+                gen,
+                wal_ids: None,
+                stats: None,
+            },
+        };
+        let (gen_0, gen_1) = (FileId::new(), FileId::new());
+        for gen in [gen_0, gen_1] {
+            manager
+                .base_fs()
+                .open_options(
+                    &option.table_path(gen, 1),
+                    FileType::Parquet.open_options(false),
+                )
+                .await
+                .unwrap();
+        }
+
+        // the table is missing
+        assert!(matches!(
+            version_set
+                .apply_edits(vec![add("0", "1", FileId::new())], None, false)
+                .await,
+            Err(VersionError::Inconsistent(_))
+        ));
+        assert!(matches!(
+            version_set
+                .apply_edits(
+                    vec![VersionEdit::Remove {
+                        level: 1,
+                        gen: gen_0,
+                    }],
+                    None,
+                    false,
+                )
+                .await,
+            Err(VersionError::Inconsistent(_))
+        ));
+        assert!(matches!(
+            version_set
+                .apply_edits(
+                    vec![add("0", "2", gen_0), add("2", "3", gen_1)],
+                    None,
+                    false
+                )
+                .await,
+            Err(VersionError::Inconsistent(_))
+        ));
+        assert!(version_set.current().await.level_slice[1].is_empty());
+
+        version_set
+            .apply_edits(
+                vec![add("0", "1", gen_0), add("2", "3", gen_1)],
+                None,
+                false,
+            )
+            .await
+            .unwrap();
+        assert_eq!(version_set.current().await.level_slice[1].len(), 2);
+    }
 }
@@ -2,11 +2,12 @@ use std::{future::Future, hash::Hasher};
 
 use fusio::{Error, IoBuf, IoBufMut, MaybeSend, SeqRead, Write};
 
-use crate::serdes::{Decode, Encode};
+use crate::serdes::{BytesReader, Decode, Encode};
 
 pub(crate) struct HashWriter<W: Write> {
     hasher: crc32fast::Hasher,
     writer: W,
+    written: usize,
 }
 
 impl<W: Write + Unpin> HashWriter<W> {
@@ -14,12 +15,15 @@ impl<W: Write + Unpin> HashWriter<W> {
         Self {
             hasher: crc32fast::Hasher::new(),
             writer,
+            written: 0,
         }
     }
 
-    pub(crate) async fn eol(mut self) -> Result<(), fusio::Error> {
+    /// write the checksum, returns the bytes written along with it
+    pub(crate) async fn eol(mut self) -> Result<usize, fusio::Error> {
         let i = self.hasher.finish();
-        i.encode(&mut self.writer).await
+        i.encode(&mut self.writer).await?;
+        Ok(self.written + i.size())
     }
 }
 
@@ -27,6 +31,7 @@ impl<W: Write> Write for HashWriter<W> {
     async fn write_all<B: IoBuf>(&mut self, buf: B) -> (Result<(), Error>, B) {
         let (result, buf) = self.writer.write_all(buf).await;
         self.hasher.write(buf.as_slice());
+        self.written += buf.as_slice().len();
 
         (result, buf)
     }
@@ -43,6 +48,8 @@ impl<W: Write> Write for HashWriter<W> {
 pub(crate) struct HashReader<R: SeqRead> {
     hasher: crc32fast::Hasher,
     reader: R,
+    error: Option<Error>,
+    read: usize,
 }
 
 impl<R: SeqRead> HashReader<R> {
@@ -50,9 +57,22 @@ impl<R: SeqRead> HashReader<R> {
         Self {
             hasher: crc32fast::Hasher::new(),
             reader,
+            error: None,
+            read: 0,
         }
     }
 
+    /// the bytes read so far, the checksum aside
+    pub(crate) fn read(&self) -> usize {
+        self.read
+    }
+
+    /// the first read failing other than at the end of the file, which the decoders only see
+    /// the message of
+    pub(crate) fn take_error(&mut self) -> Option<Error> {
+        self.error.take()
+    }
+
     pub(crate) async fn checksum(mut self) -> Result<bool, fusio::Error> {
         let checksum = u64::decode(&mut self.reader).await?;
 
@@ -60,16 +80,46 @@ impl<R: SeqRead> HashReader<R> {
     }
 }
 
+impl HashReader<&mut BytesReader> {
+    /// [`Decode::decode_buffered`], hashing the bytes the value is read from
+    pub(crate) async fn decode_buffered<T: Decode>(
+        &mut self,
+        compact: bool,
+    ) -> Result<T, T::Error> {
+        let start = self.reader.position();
+        let result = T::decode_buffered(self.reader, compact).await;
+        let read = self.reader.read_since(start);
+        self.hasher.write(read);
+        self.read += read.len();
+
+        result
+    }
+}
+
 impl<R: SeqRead> SeqRead for HashReader<R> {
     async fn read_exact<B: IoBufMut>(&mut self, buf: B) -> (Result<(), Error>, B) {
         let (result, buf) = self.reader.read_exact(buf).await;
-        if result.is_ok() {
-            self.hasher.write(buf.as_slice());
+        match result {
+            Ok(()) => {
+                self.hasher.write(buf.as_slice());
+                self.read += buf.as_slice().len();
+            }
+            Err(err) if !is_eof(&err) => {
+                let message = err.to_string();
+                self.error.get_or_insert(err);
+                return (Err(Error::Other(message.into())), buf);
+            }
+            Err(err) => return (Err(err), buf),
         }
-        (result, buf)
+        (Ok(()), buf)
     }
 }
 
+/// whether reading failed for reaching the end of the file, as it does at an incomplete entry
+pub(crate) fn is_eof(err: &Error) -> bool {
+    matches!(err, Error::Io(err) if err.kind() == std::io::ErrorKind::UnexpectedEof)
+}
+
 #[cfg(test)]
 pub(crate) mod tests {
     use std::io::Cursor;
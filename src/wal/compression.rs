@@ -0,0 +1,56 @@
+use fusio::{SeqRead, Write};
+
+use crate::{
+    serdes::{Decode, Encode},
+    WalCompression,
+};
+
+/// set on the log type of an entry whose payload is compressed
+pub(crate) const COMPRESSED: u8 = 0x80;
+
+const LZ4: u8 = 1;
+#[cfg(feature = "zstd")]
+const ZSTD: u8 = 2;
+
+/// write the compressed `payload` along with the codec and its length
+pub(crate) async fn encode<W>(
+    compression: WalCompression,
+    payload: &[u8],
+    writer: &mut W,
+) -> Result<(), fusio::Error>
+where
+    W: Write,
+{
+    let (codec, compressed) = match compression {
+        WalCompression::None => unreachable!("uncompressed payloads are written as is"),
+        WalCompression::Lz4 => (LZ4, lz4_flex::block::compress(payload)),
+        #[cfg(feature = "zstd")]
+        WalCompression::Zstd(level) => (
+            ZSTD,
+            zstd::bulk::compress(payload, level).map_err(fusio::Error::Io)?,
+        ),
+    };
+    codec.encode(writer).await?;
+    (payload.len() as u32).encode(writer).await?;
+    compressed.encode(writer).await
+}
+
+/// read a payload written by [`encode`] and decompress it
+pub(crate) async fn decode<R>(reader: &mut R) -> Result<Vec<u8>, fusio::Error>
+where
+    R: SeqRead,
+{
+    let codec = u8::decode(reader).await?;
+    let len = u32::decode(reader).await? as usize;
+    let compressed = Vec::<u8>::decode(reader).await?;
+
+    match codec {
+        LZ4 => lz4_flex::block::decompress(&compressed, len)
+            .map_err(|err| fusio::Error::Other(Box::new(err))),
+        #[cfg(feature = "zstd")]
+        ZSTD => zstd::bulk::decompress(&compressed, len).map_err(fusio::Error::Io),
+        codec => Err(fusio::Error::Other(
+            format!("unsupported WAL compression {}", codec).into(),
+        )),
+    }
+}
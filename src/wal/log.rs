@@ -4,6 +4,14 @@ use fusio::{SeqRead, Write};
 
 use crate::serdes::{Decode, Encode};
 
+/// set on the log type of an entry whose payload is in the compact encoding of
+/// [`FormatVersion::V2`](crate::FormatVersion::V2)
+pub(crate) const COMPACT: u8 = 0x40;
+/// set on the log type of an uncompressed entry in the fixed-width encoding of
+/// [`FormatVersion::V1`](crate::FormatVersion::V1) whose timestamp takes 64 bits, the ones
+/// written before timestamps were widened go without it
+pub(crate) const WIDE: u8 = 0x20;
+
 #[derive(Debug)]
 pub struct Log<Re> {
     pub log_type: LogType,
@@ -16,24 +24,29 @@ impl<Re> Log<Re> {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u8)]
 pub enum LogType {
     Full,
     First,
     Middle,
     Last,
+    RangeDelete,
 }
 
-impl From<u8> for LogType {
-    fn from(value: u8) -> Self {
-        match value {
+impl TryFrom<u8> for LogType {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(match value {
             0 => Self::Full,
             1 => Self::First,
             2 => Self::Middle,
             3 => Self::Last,
-            _ => unreachable!(),
-        }
+            4 => Self::RangeDelete,
+            // a corrupted entry
+            value => return Err(value),
+        })
     }
 }
 
@@ -47,10 +60,18 @@ where
     where
         W: Write,
     {
-        (self.log_type as u8).encode(writer).await?;
+        (self.log_type as u8 | WIDE).encode(writer).await?;
         self.record.encode(writer).await
     }
 
+    async fn encode_compact<W>(&self, writer: &mut W) -> Result<(), Self::Error>
+    where
+        W: Write,
+    {
+        (self.log_type as u8 | COMPACT).encode(writer).await?;
+        self.record.encode_compact(writer).await
+    }
+
     fn size(&self) -> usize {
         size_of::<u8>() + self.record.size()
     }
@@ -66,7 +87,8 @@ where
     where
         R: SeqRead,
     {
-        let log_type = LogType::from(u8::decode(reader).await?);
+        let log_type = LogType::try_from(u8::decode(reader).await? & !WIDE)
+            .map_err(|value| fusio::Error::Other(format!("invalid log type {}", value).into()))?;
         let log = Re::decode(reader).await?;
 
         Ok(Self {
@@ -74,4 +96,18 @@ where
             record: log,
         })
     }
+
+    async fn decode_compact<R>(reader: &mut R) -> Result<Self, Self::Error>
+    where
+        R: SeqRead,
+    {
+        let log_type = LogType::try_from(u8::decode(reader).await? & !COMPACT)
+            .map_err(|value| fusio::Error::Other(format!("invalid log type {}", value).into()))?;
+        let log = Re::decode_compact(reader).await?;
+
+        Ok(Self {
+            log_type,
+            record: log,
+        })
+    }
 }
@@ -1,28 +1,49 @@
-mod checksum;
+pub(crate) mod checksum;
+mod compression;
 pub(crate) mod log;
 pub(crate) mod record_entry;
 
-use std::marker::PhantomData;
+use std::{future::Future, io::Cursor, marker::PhantomData};
 
 use async_stream::stream;
-use checksum::{HashReader, HashWriter};
+use checksum::{is_eof, HashReader, HashWriter};
 use fusio::{SeqRead, Write};
 use futures_core::Stream;
-use log::Log;
+use log::{Log, COMPACT, WIDE};
 use thiserror::Error;
 
 use crate::{
     fs::FileId,
+    range_tombstone::RangeTombstone,
     record::{Key, Record},
-    serdes::{Decode, Encode},
+    serdes::{decode_with, encode_with, BytesReader, Decode, Encode},
     timestamp::Timestamped,
-    wal::{log::LogType, record_entry::RecordEntry},
+    wal::{compression::COMPRESSED, log::LogType, record_entry::RecordEntry},
+    FormatVersion, WalCompression,
 };
 
+pub(crate) enum WalEntry<R>
+where
+    R: Record,
+{
+    Record {
+        log_type: LogType,
+        key: Timestamped<R::Key>,
+        value: Option<R>,
+    },
+    RangeTombstone(RangeTombstone<R::Key>),
+}
+
 #[derive(Debug)]
 pub(crate) struct WalFile<F, R> {
     file: F,
     file_id: FileId,
+    entry_count: u64,
+    size: usize,
+    /// the recovery stopped at an incomplete entry
+    torn: bool,
+    compression: WalCompression,
+    format_version: FormatVersion,
     _marker: PhantomData<R>,
 }
 
@@ -31,13 +52,52 @@ impl<F, R> WalFile<F, R> {
         Self {
             file,
             file_id,
+            entry_count: 0,
+            size: 0,
+            torn: false,
+            compression: WalCompression::None,
+            format_version: FormatVersion::V1,
             _marker: PhantomData,
         }
     }
 
+    /// compress the entries written, the ones recovered are decompressed whatever the
+    /// compression they were written with
+    pub(crate) fn with_compression(self, compression: WalCompression) -> Self {
+        Self {
+            compression,
+            ..self
+        }
+    }
+
+    /// encode the entries written in `format_version`, the ones recovered are decoded whatever
+    /// the version they were written in
+    pub(crate) fn with_format_version(self, format_version: FormatVersion) -> Self {
+        Self {
+            format_version,
+            ..self
+        }
+    }
+
     pub(crate) fn file_id(&self) -> FileId {
         self.file_id
     }
+
+    /// number of entries written by this handle
+    pub(crate) fn entry_count(&self) -> u64 {
+        self.entry_count
+    }
+
+    /// bytes written by this handle
+    pub(crate) fn size(&self) -> usize {
+        self.size
+    }
+
+    /// whether [`WalFile::recover`] stopped at an entry failing to decode, rather than at the
+    /// end of the file
+    pub(crate) fn is_torn(&self) -> bool {
+        self.torn
+    }
 }
 
 impl<F, R> WalFile<F, R>
@@ -51,11 +111,39 @@ where
         key: Timestamped<<R::Key as Key>::Ref<'r>>,
         value: Option<R::Ref<'r>>,
     ) -> Result<(), <R::Ref<'r> as Encode>::Error> {
-        let mut writer = HashWriter::new(&mut self.file);
-        Log::new(log_ty, RecordEntry::<R>::Encode((key, value)))
-            .encode(&mut writer)
+        self.write_log(log_ty, RecordEntry::<R>::Encode((key, value)))
             .await?;
-        writer.eol().await?;
+        Ok(())
+    }
+
+    pub(crate) async fn write_range_tombstone(
+        &mut self,
+        range_tombstone: &RangeTombstone<R::Key>,
+    ) -> Result<(), <R::Key as Encode>::Error> {
+        self.write_log(LogType::RangeDelete, range_tombstone).await
+    }
+
+    async fn write_log<E>(&mut self, log_ty: LogType, payload: E) -> Result<(), E::Error>
+    where
+        E: Encode + Sync,
+    {
+        let mut writer = HashWriter::new(&mut self.file);
+        let compact = self.format_version == FormatVersion::V2;
+        if let WalCompression::None = self.compression {
+            encode_with(&Log::new(log_ty, payload), &mut writer, compact).await?;
+        } else {
+            let mut bytes = Vec::with_capacity(payload.size());
+            encode_with(&payload, &mut Cursor::new(&mut bytes), compact).await?;
+            let tag = if compact {
+                COMPRESSED | COMPACT
+            } else {
+                COMPRESSED
+            };
+            (log_ty as u8 | tag).encode(&mut writer).await?;
+            compression::encode(self.compression, &bytes, &mut writer).await?;
+        }
+        self.size += writer.eol().await?;
+        self.entry_count += 1;
         Ok(())
     }
 
@@ -71,32 +159,180 @@ where
 {
     pub(crate) fn recover(
         &mut self,
-    ) -> impl Stream<
-        Item = Result<
-            (LogType, Timestamped<R::Key>, Option<R>),
-            RecoverError<<R as Decode>::Error>,
-        >,
-    > + '_ {
+    ) -> impl Stream<Item = Result<WalEntry<R>, RecoverError<<R as Decode>::Error>>> + '_ {
+        self.recover_with::<Streamed>()
+    }
+
+    fn recover_with<D>(
+        &mut self,
+    ) -> impl Stream<Item = Result<WalEntry<R>, RecoverError<<R as Decode>::Error>>> + '_
+    where
+        D: for<'a> DecodePayload<HashReader<&'a mut F>>,
+    {
         stream! {
             loop {
                 let mut reader = HashReader::new(&mut self.file);
 
-                let record = match Log::<RecordEntry<'static, R>>::decode(&mut reader).await {
-                    Ok(record) => record,
-                    Err(_) => return,
+                let Ok(tag) = u8::decode(&mut reader).await else {
+                    if let Some(err) = reader.take_error() {
+                        yield Err(RecoverError::Fusio(err));
+                    }
+                    return;
                 };
-                if !reader.checksum().await? {
+                let Ok(log_type) = LogType::try_from(tag & !(COMPRESSED | COMPACT | WIDE)) else {
                     yield Err(RecoverError::Checksum);
                     return;
-                }
-                if let RecordEntry::Decode((key, value)) = record.record {
-                    yield Ok((record.log_type, key, value));
+                };
+                let compact = tag & COMPACT != 0;
+                let entry = if tag & (COMPRESSED | COMPACT | WIDE) == 0 {
+                    if log_type == LogType::RangeDelete {
+                        yield Err(RecoverError::Checksum);
+                        return;
+                    }
+                    Self::decode_narrow_entry::<D, _>(log_type, &mut reader).await
+                } else if tag & COMPRESSED == 0 {
+                    Self::decode_entry::<D, _>(log_type, &mut reader, compact).await
                 } else {
-                    unreachable!()
+                    match compression::decode(&mut reader).await {
+                        Ok(payload) => {
+                            let mut payload = BytesReader::new(payload);
+                            Self::decode_entry::<Buffered, _>(log_type, &mut payload, compact)
+                                .await
+                        }
+                        Err(_) => None,
+                    }
+                };
+                let Some(entry) = entry else {
+                    // a failing disk is not mistaken for an incomplete entry
+                    match reader.take_error() {
+                        Some(err) => yield Err(RecoverError::Fusio(err)),
+                        None => self.torn = true,
+                    }
+                    return;
+                };
+                match reader.checksum().await {
+                    Ok(true) => {
+                        yield Ok(entry);
+                    }
+                    Ok(false) => {
+                        yield Err(RecoverError::Checksum);
+                        return;
+                    }
+                    Err(err) if is_eof(&err) => {
+                        self.torn = true;
+                        return;
+                    }
+                    Err(err) => {
+                        yield Err(RecoverError::Fusio(err));
+                        return;
+                    }
                 }
             }
         }
     }
+
+    async fn decode_entry<D, S>(
+        log_type: LogType,
+        reader: &mut S,
+        compact: bool,
+    ) -> Option<WalEntry<R>>
+    where
+        D: DecodePayload<S>,
+    {
+        if let LogType::RangeDelete = log_type {
+            return D::decode::<RangeTombstone<R::Key>>(reader, compact)
+                .await
+                .ok()
+                .map(WalEntry::RangeTombstone);
+        }
+        match D::decode::<RecordEntry<'static, R>>(reader, compact)
+            .await
+            .ok()?
+        {
+            RecordEntry::Decode((key, value)) => Some(WalEntry::Record {
+                log_type,
+                key,
+                value,
+            }),
+            RecordEntry::Encode(_) => unreachable!(),
+        }
+    }
+
+    /// decode a record written before timestamps were widened to 64 bits
+    async fn decode_narrow_entry<D, S>(log_type: LogType, reader: &mut S) -> Option<WalEntry<R>>
+    where
+        D: DecodePayload<S>,
+    {
+        let ts = D::decode::<u32>(reader, false).await.ok()?;
+        let key = D::decode::<R::Key>(reader, false).await.ok()?;
+        let value = D::decode::<Option<R>>(reader, false).await.ok()?;
+
+        Some(WalEntry::Record {
+            log_type,
+            key: Timestamped::new(key, u64::from(ts).into()),
+            value,
+        })
+    }
+
+    /// whether nothing follows the entries read, so that the one failing to recover was the
+    /// last
+    pub(crate) async fn is_at_end(&mut self) -> bool {
+        u8::decode(&mut self.file).await.is_err()
+    }
+}
+
+impl<R> WalFile<BytesReader, R>
+where
+    R: Record,
+{
+    /// [`WalFile::recover`] of a WAL read into memory, whose entries take their strings and blobs
+    /// out of the buffer rather than copying them
+    pub(crate) fn recover_buffered(
+        &mut self,
+    ) -> impl Stream<Item = Result<WalEntry<R>, RecoverError<<R as Decode>::Error>>> + '_ {
+        self.recover_with::<Buffered>()
+    }
+}
+
+/// how [`WalFile::recover`] decodes the payloads of the entries
+trait DecodePayload<S> {
+    fn decode<T: Decode>(
+        reader: &mut S,
+        compact: bool,
+    ) -> impl Future<Output = Result<T, T::Error>>;
+}
+
+/// reads the payloads from any reader
+struct Streamed;
+
+/// takes the strings and blobs of the payloads out of the buffer they are read from
+struct Buffered;
+
+impl<S: SeqRead> DecodePayload<S> for Streamed {
+    fn decode<T: Decode>(
+        reader: &mut S,
+        compact: bool,
+    ) -> impl Future<Output = Result<T, T::Error>> {
+        decode_with(reader, compact)
+    }
+}
+
+impl DecodePayload<BytesReader> for Buffered {
+    fn decode<T: Decode>(
+        reader: &mut BytesReader,
+        compact: bool,
+    ) -> impl Future<Output = Result<T, T::Error>> {
+        T::decode_buffered(reader, compact)
+    }
+}
+
+impl DecodePayload<HashReader<&mut BytesReader>> for Buffered {
+    fn decode<T: Decode>(
+        reader: &mut HashReader<&mut BytesReader>,
+        compact: bool,
+    ) -> impl Future<Output = Result<T, T::Error>> {
+        reader.decode_buffered(compact)
+    }
 }
 
 #[derive(Debug, Error)]
@@ -113,13 +349,19 @@ pub enum RecoverError<E: std::error::Error> {
 
 #[cfg(test)]
 mod tests {
-    use std::{io::Cursor, pin::pin};
+    use std::{io::Cursor, ops::Bound, pin::pin};
 
     use futures_util::StreamExt;
     use tokio::io::AsyncSeekExt;
 
-    use super::{log::LogType, FileId, WalFile};
-    use crate::timestamp::Timestamped;
+    use super::{checksum::HashWriter, log::LogType, FileId, RecoverError, WalEntry, WalFile};
+    use crate::{
+        fs::fault::{Faults, FaultyFile},
+        range_tombstone::RangeTombstone,
+        serdes::{BytesReader, Encode},
+        timestamp::Timestamped,
+        FormatVersion, WalCompression,
+    };
 
     #[tokio::test]
     async fn write_and_recover() {
@@ -142,7 +384,9 @@ mod tests {
 
             {
                 let mut stream = pin!(wal.recover());
-                let (_, key, value) = stream.next().await.unwrap().unwrap();
+                let Some(Ok(WalEntry::Record { key, value, .. })) = stream.next().await else {
+                    unreachable!()
+                };
                 assert_eq!(key.ts, 0.into());
                 assert_eq!(value, Some("hello".to_string()));
             }
@@ -156,6 +400,13 @@ mod tests {
             )
             .await
             .unwrap();
+            wal.write_range_tombstone(&RangeTombstone::new(
+                Bound::Included("a".to_string()),
+                Bound::Unbounded,
+                2.into(),
+            ))
+            .await
+            .unwrap();
         }
 
         {
@@ -164,13 +415,374 @@ mod tests {
 
             {
                 let mut stream = pin!(wal.recover());
-                let (_, key, value) = stream.next().await.unwrap().unwrap();
+                let Some(Ok(WalEntry::Record { key, value, .. })) = stream.next().await else {
+                    unreachable!()
+                };
                 assert_eq!(key.ts, 0.into());
                 assert_eq!(value, Some("hello".to_string()));
-                let (_, key, value) = stream.next().await.unwrap().unwrap();
+                let Some(Ok(WalEntry::Record { key, value, .. })) = stream.next().await else {
+                    unreachable!()
+                };
                 assert_eq!(key.ts, 1.into());
                 assert_eq!(value, Some("world".to_string()));
+                let Some(Ok(WalEntry::RangeTombstone(range_tombstone))) = stream.next().await
+                else {
+                    unreachable!()
+                };
+                assert_eq!(range_tombstone.lower, Bound::Included("a".to_string()));
+                assert_eq!(range_tombstone.ts, 2.into());
+                assert!(stream.next().await.is_none());
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn recover_format_versions() {
+        let mut bytes = Vec::new();
+        let mut file = Cursor::new(&mut bytes);
+        let mut sizes = Vec::new();
+        for (format_version, compression) in [
+            (FormatVersion::V1, WalCompression::None),
+            (FormatVersion::V2, WalCompression::None),
+            (FormatVersion::V2, WalCompression::Lz4),
+        ] {
+            let mut wal = WalFile::<_, String>::new(&mut file, FileId::new())
+                .with_format_version(format_version)
+                .with_compression(compression);
+            wal.write(
+                LogType::Full,
+                Timestamped::new("hello", 300.into()),
+                Some("world"),
+            )
+            .await
+            .unwrap();
+            wal.write_range_tombstone(&RangeTombstone::new(
+                Bound::Included("a".to_string()),
+                Bound::Excluded("b".to_string()),
+                301.into(),
+            ))
+            .await
+            .unwrap();
+            wal.flush().await.unwrap();
+            sizes.push(wal.size());
+        }
+        // the timestamps and the lengths take 2 and 1 bytes instead of 8 and 2
+        assert_eq!(sizes[0] - sizes[1], 6 + 1 + 1 + 6 + 1 + 1);
+
+        file.seek(std::io::SeekFrom::Start(0)).await.unwrap();
+        let mut wal = WalFile::<_, String>::new(&mut file, FileId::new());
+        let mut stream = pin!(wal.recover());
+        for _ in 0..3 {
+            let Some(Ok(WalEntry::Record { key, value, .. })) = stream.next().await else {
+                unreachable!()
+            };
+            assert_eq!(key, Timestamped::new("hello".to_string(), 300.into()));
+            assert_eq!(value, Some("world".to_string()));
+            let Some(Ok(WalEntry::RangeTombstone(range_tombstone))) = stream.next().await else {
+                unreachable!()
+            };
+            assert_eq!(range_tombstone.upper, Bound::Excluded("b".to_string()));
+            assert_eq!(range_tombstone.ts, 301.into());
+        }
+        assert!(stream.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn recover_32_bit_timestamp() {
+        let mut bytes = Vec::new();
+        let mut file = Cursor::new(&mut bytes);
+        {
+            // the layout written before timestamps were widened to 64 bits
+            let mut writer = HashWriter::new(&mut file);
+            (LogType::Full as u8).encode(&mut writer).await.unwrap();
+            7u32.encode(&mut writer).await.unwrap();
+            "hello".encode(&mut writer).await.unwrap();
+            Some("world").encode(&mut writer).await.unwrap();
+            writer.eol().await.unwrap();
+        }
+        let mut wal = WalFile::<_, String>::new(&mut file, FileId::new());
+        wal.write(
+            LogType::Full,
+            Timestamped::new("hello", 8.into()),
+            Some("world"),
+        )
+        .await
+        .unwrap();
+        wal.flush().await.unwrap();
+
+        file.seek(std::io::SeekFrom::Start(0)).await.unwrap();
+        let mut wal = WalFile::<_, String>::new(&mut file, FileId::new());
+        let mut stream = pin!(wal.recover());
+        for ts in [7_u64, 8] {
+            let Some(Ok(WalEntry::Record { key, value, .. })) = stream.next().await else {
+                unreachable!()
+            };
+            assert_eq!(key, Timestamped::new("hello".to_string(), ts.into()));
+            assert_eq!(value, Some("world".to_string()));
+        }
+        assert!(stream.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn recover_buffered() {
+        let mut bytes = Vec::new();
+        for (format_version, compression) in [
+            (FormatVersion::V1, WalCompression::None),
+            (FormatVersion::V2, WalCompression::None),
+            (FormatVersion::V2, WalCompression::Lz4),
+        ] {
+            let mut wal = WalFile::<_, String>::new(Cursor::new(&mut bytes), FileId::new())
+                .with_format_version(format_version)
+                .with_compression(compression);
+            wal.file.seek(std::io::SeekFrom::End(0)).await.unwrap();
+            wal.write(
+                LogType::Full,
+                Timestamped::new("hello", 300.into()),
+                Some("world"),
+            )
+            .await
+            .unwrap();
+            wal.flush().await.unwrap();
+        }
+
+        let mut wal = WalFile::<_, String>::new(BytesReader::new(bytes.clone()), FileId::new());
+        {
+            let mut stream = pin!(wal.recover_buffered());
+            for _ in 0..3 {
+                let Some(Ok(WalEntry::Record { key, value, .. })) = stream.next().await else {
+                    unreachable!()
+                };
+                assert_eq!(key, Timestamped::new("hello".to_string(), 300.into()));
+                assert_eq!(value, Some("world".to_string()));
+            }
+            assert!(stream.next().await.is_none());
+        }
+        assert!(!wal.is_torn());
+
+        // an entry cut short is not mistaken for a corrupted one
+        bytes.pop();
+        let mut wal = WalFile::<_, String>::new(BytesReader::new(bytes), FileId::new());
+        {
+            let mut stream = pin!(wal.recover_buffered());
+            assert!(matches!(stream.next().await, Some(Ok(_))));
+            assert!(matches!(stream.next().await, Some(Ok(_))));
+            assert!(stream.next().await.is_none());
+        }
+        assert!(wal.is_torn());
+    }
+
+    #[tokio::test]
+    async fn recover_corrupted() {
+        let mut bytes = Vec::new();
+        {
+            let mut wal = WalFile::<_, String>::new(Cursor::new(&mut bytes), FileId::new());
+            for (key, ts) in [("hello", 0), ("world", 1)] {
+                wal.write(LogType::Full, Timestamped::new(key, ts.into()), Some(key))
+                    .await
+                    .unwrap();
+            }
+            wal.flush().await.unwrap();
+        }
+        // flip a byte of the second entry, which still decodes
+        let offset = bytes
+            .windows(5)
+            .position(|window| window == b"world")
+            .unwrap();
+        bytes[offset] ^= 1;
+
+        let mut wal = WalFile::<_, String>::new(Cursor::new(&mut bytes), FileId::new());
+        let mut stream = pin!(wal.recover());
+        assert!(matches!(
+            stream.next().await,
+            Some(Ok(WalEntry::Record { .. }))
+        ));
+        assert!(matches!(
+            stream.next().await,
+            Some(Err(RecoverError::Checksum))
+        ));
+        assert!(stream.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn write_and_recover_compressed() {
+        let value = "hello".repeat(100);
+        let mut bytes = Vec::new();
+        {
+            let mut wal = WalFile::<_, String>::new(Cursor::new(&mut bytes), FileId::new())
+                .with_compression(WalCompression::Lz4);
+            wal.write(
+                LogType::Full,
+                Timestamped::new("hello", 0.into()),
+                Some(value.as_str()),
+            )
+            .await
+            .unwrap();
+            assert!(wal.size() < value.len());
+            // written by a WAL without compression
+            let mut wal = WalFile::<_, String>::new(wal.file, FileId::new());
+            wal.write_range_tombstone(&RangeTombstone::new(
+                Bound::Included("a".to_string()),
+                Bound::Unbounded,
+                1.into(),
+            ))
+            .await
+            .unwrap();
+            wal.flush().await.unwrap();
+        }
+
+        let mut wal = WalFile::<_, String>::new(Cursor::new(&mut bytes), FileId::new());
+        let mut stream = pin!(wal.recover());
+        let Some(Ok(WalEntry::Record {
+            key,
+            value: recovered,
+            ..
+        })) = stream.next().await
+        else {
+            unreachable!()
+        };
+        assert_eq!(key.value, "hello");
+        assert_eq!(recovered, Some(value));
+        let Some(Ok(WalEntry::RangeTombstone(range_tombstone))) = stream.next().await else {
+            unreachable!()
+        };
+        assert_eq!(range_tombstone.ts, 1.into());
+        assert!(stream.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn recover_io_error() {
+        let mut bytes = Vec::new();
+        {
+            let mut wal = WalFile::<_, String>::new(Cursor::new(&mut bytes), FileId::new());
+            wal.write(
+                LogType::Full,
+                Timestamped::new("hello", 0.into()),
+                Some("hello"),
+            )
+            .await
+            .unwrap();
+            wal.flush().await.unwrap();
+        }
+
+        // failing at the log type and in the middle of the entry
+        for budget in [0, 1] {
+            let faults = Faults {
+                budget: Some(budget),
+                ..Default::default()
+            };
+            let file = FaultyFile::new(Cursor::new(&mut bytes), faults, 0);
+            let mut wal = WalFile::<_, String>::new(file, FileId::new());
+            {
+                let mut stream = pin!(wal.recover());
+                assert!(matches!(
+                    stream.next().await,
+                    Some(Err(RecoverError::Fusio(_)))
+                ));
+                assert!(stream.next().await.is_none());
+            }
+            assert!(!wal.is_torn());
+        }
+    }
+
+    #[tokio::test]
+    async fn write_io_error() {
+        let mut bytes = Vec::new();
+        let faults = Faults {
+            budget: Some(1),
+            ..Default::default()
+        };
+        let file = FaultyFile::new(Cursor::new(&mut bytes), faults, 0);
+        let mut wal = WalFile::<_, String>::new(file, FileId::new());
+        assert!(wal
+            .write(
+                LogType::Full,
+                Timestamped::new("hello", 0.into()),
+                Some("hello"),
+            )
+            .await
+            .is_err());
+        assert_eq!(wal.entry_count(), 0);
+    }
+
+    /// write through partial writes and delayed flushes until a write fails, crash, and recover
+    /// through read errors. The entries recovered must be the first ones written, including
+    /// every one flushed durably
+    #[tokio::test]
+    async fn crash_recovery() {
+        let write_faults = Faults {
+            partial_write: 0.01,
+            delayed_flush: 0.3,
+            ..Default::default()
+        };
+        let read_faults = Faults {
+            read_error: 0.01,
+            ..Default::default()
+        };
+
+        for seed in 0..256 {
+            let mut bytes = Vec::new();
+            let (mut written, mut durable) = (0, 0);
+            let len = {
+                let file = FaultyFile::new(Cursor::new(&mut bytes), write_faults, seed);
+                let mut wal = WalFile::<_, String>::new(file, FileId::new());
+                for i in 0..64u64 {
+                    let key = i.to_string();
+                    let entry = Timestamped::new(key.as_str(), i.into());
+                    if wal
+                        .write(LogType::Full, entry, Some(key.as_str()))
+                        .await
+                        .is_err()
+                    {
+                        break;
+                    }
+                    written += 1;
+                    if wal.flush().await.is_err() {
+                        break;
+                    }
+                    if wal.file.is_durable() {
+                        durable = written;
+                    }
+                }
+                wal.file.crash()
+            };
+            bytes.truncate(len);
+
+            let mut recovered = 0;
+            {
+                let mut wal = WalFile::<_, String>::new(Cursor::new(&mut bytes), FileId::new());
+                let mut stream = pin!(wal.recover());
+                while let Some(entry) = stream.next().await {
+                    match entry {
+                        Ok(WalEntry::Record { key, value, .. }) => {
+                            assert_eq!(key.value, recovered.to_string(), "seed {}", seed);
+                            assert_eq!(value, Some(key.value), "seed {}", seed);
+                        }
+                        _ => panic!("seed {}: entry {} fails to recover", seed, recovered),
+                    }
+                    recovered += 1;
+                }
+            }
+            assert!(
+                durable <= recovered && recovered <= written,
+                "seed {}",
+                seed
+            );
+
+            let file = FaultyFile::new(Cursor::new(&mut bytes), read_faults, seed);
+            let mut wal = WalFile::<_, String>::new(file, FileId::new());
+            let mut stream = pin!(wal.recover());
+            let mut read = 0;
+            while let Some(entry) = stream.next().await {
+                match entry {
+                    Ok(WalEntry::Record { key, .. }) => {
+                        assert_eq!(key.value, read.to_string(), "seed {}", seed)
+                    }
+                    Err(RecoverError::Fusio(_)) => break,
+                    _ => panic!("seed {}: entry {} fails to recover", seed, read),
+                }
+                read += 1;
             }
+            assert!(read <= recovered, "seed {}", seed);
         }
     }
 }
@@ -2,7 +2,7 @@ use fusio::{SeqRead, Write};
 
 use crate::{
     record::{Key, Record},
-    serdes::{Decode, Encode},
+    serdes::{decode_with, encode_with, BytesReader, Decode, Encode},
     timestamp::Timestamped,
 };
 
@@ -24,13 +24,14 @@ where
     where
         W: Write,
     {
-        if let RecordEntry::Encode((key, recode_ref)) = self {
-            key.encode(writer).await.unwrap();
-            recode_ref.encode(writer).await.unwrap();
+        self.encode_as(writer, false).await
+    }
 
-            return Ok(());
-        }
-        unreachable!()
+    async fn encode_compact<W>(&self, writer: &mut W) -> Result<(), Self::Error>
+    where
+        W: Write,
+    {
+        self.encode_as(writer, true).await
     }
 
     fn size(&self) -> usize {
@@ -41,6 +42,28 @@ where
     }
 }
 
+impl<R> RecordEntry<'_, R>
+where
+    R: Record,
+{
+    async fn encode_as<W>(&self, writer: &mut W, compact: bool) -> Result<(), fusio::Error>
+    where
+        W: Write,
+    {
+        if let RecordEntry::Encode((key, recode_ref)) = self {
+            encode_with(key, writer, compact)
+                .await
+                .map_err(|err| fusio::Error::Other(Box::new(err)))?;
+            encode_with(recode_ref, writer, compact)
+                .await
+                .map_err(|err| fusio::Error::Other(Box::new(err)))?;
+
+            return Ok(());
+        }
+        unreachable!()
+    }
+}
+
 impl<Re> Decode for RecordEntry<'_, Re>
 where
     Re: Record,
@@ -51,8 +74,43 @@ where
     where
         R: SeqRead,
     {
-        let key = Timestamped::<Re::Key>::decode(reader).await.unwrap();
-        let record = Option::<Re>::decode(reader).await.unwrap();
+        Self::decode_as(reader, false).await
+    }
+
+    async fn decode_compact<R>(reader: &mut R) -> Result<Self, Self::Error>
+    where
+        R: SeqRead,
+    {
+        Self::decode_as(reader, true).await
+    }
+
+    async fn decode_buffered(reader: &mut BytesReader, compact: bool) -> Result<Self, Self::Error> {
+        let key = Timestamped::<Re::Key>::decode_buffered(reader, compact)
+            .await
+            .map_err(|err| fusio::Error::Other(Box::new(err)))?;
+        let record = Option::<Re>::decode_buffered(reader, compact)
+            .await
+            .map_err(|err| fusio::Error::Other(Box::new(err)))?;
+
+        Ok(RecordEntry::Decode((key, record)))
+    }
+}
+
+impl<Re> RecordEntry<'_, Re>
+where
+    Re: Record,
+{
+    async fn decode_as<R>(reader: &mut R, compact: bool) -> Result<Self, fusio::Error>
+    where
+        R: SeqRead,
+    {
+        // a torn entry fails to decode rather than panicking, for the recovery to skip it
+        let key = decode_with::<Timestamped<Re::Key>, _>(reader, compact)
+            .await
+            .map_err(|err| fusio::Error::Other(Box::new(err)))?;
+        let record = decode_with::<Option<Re>, _>(reader, compact)
+            .await
+            .map_err(|err| fusio::Error::Other(Box::new(err)))?;
 
         Ok(RecordEntry::Decode((key, record)))
     }
@@ -0,0 +1,476 @@
+use std::{
+    io::Cursor,
+    ops::{Bound, RangeBounds},
+    sync::{Arc, Mutex},
+};
+
+use arrow::{
+    array::{ArrayRef, RecordBatch, StringArray},
+    datatypes::{DataType, Field, Schema, SchemaRef},
+    error::ArrowError,
+};
+use async_stream::stream;
+use flume::{unbounded, Receiver, Sender};
+use futures_core::Stream;
+use parquet::arrow::ProjectionMask;
+
+use crate::{
+    inmem::immutable::{ArrowArrays, Builder},
+    record::{Key, KeyRef, Record, RecordRef},
+    serdes::{Decode, Encode},
+    timestamp::{Timestamp, Timestamped},
+    wal::RecoverError,
+    DbError, DB,
+};
+
+/// A committed mutation reported by [`DB::watch`](crate::DB::watch).
+#[derive(Debug, PartialEq)]
+pub enum Mutation<R>
+where
+    R: Record,
+{
+    Put {
+        key: R::Key,
+        value: R,
+        ts: Timestamp,
+    },
+    Delete {
+        key: R::Key,
+        ts: Timestamp,
+    },
+    /// a range deletion overlapping the watched range, its bounds are not clipped to the range
+    DeleteRange {
+        lower: Bound<R::Key>,
+        upper: Bound<R::Key>,
+        ts: Timestamp,
+    },
+}
+
+/// `_op` of the rows of [`DB::watch_batches`](crate::DB::watch_batches) written by a put
+pub const OP_INSERT: &str = "insert";
+/// `_op` of the rows of [`DB::watch_batches`](crate::DB::watch_batches) written by a delete
+pub const OP_DELETE: &str = "delete";
+
+/// the schema of the batches of [`DB::watch_batches`](crate::DB::watch_batches): the internal
+/// `_null` column of `arrow_schema` replaced by `_op`
+pub(crate) fn change_schema(arrow_schema: &Schema) -> SchemaRef {
+    let mut fields = vec![Arc::new(Field::new("_op", DataType::Utf8, false))];
+    fields.extend(arrow_schema.fields().iter().skip(1).cloned());
+    Arc::new(Schema::new_with_metadata(
+        fields,
+        arrow_schema.metadata().clone(),
+    ))
+}
+
+/// the puts and deletes of `mutations` as a batch of `schema`, see [`change_schema`]. Range
+/// deletions are left out.
+pub(crate) fn change_batch<R>(
+    mutations: &[Mutation<R>],
+    arrow_schema: &Arc<Schema>,
+    schema: &SchemaRef,
+) -> Result<RecordBatch, ArrowError>
+where
+    R: Record,
+{
+    let mut builder = R::Columns::builder(arrow_schema, mutations.len());
+    let mut ops = Vec::with_capacity(mutations.len());
+
+    for mutation in mutations {
+        match mutation {
+            Mutation::Put { key, value, ts } => {
+                builder.push(
+                    Timestamped::new(key.as_key_ref(), *ts),
+                    Some(value.as_record_ref()),
+                );
+                ops.push(OP_INSERT);
+            }
+            Mutation::Delete { key, ts } => {
+                builder.push(Timestamped::new(key.as_key_ref(), *ts), None);
+                ops.push(OP_DELETE);
+            }
+            Mutation::DeleteRange { .. } => {}
+        }
+    }
+    let columns = builder.finish(None);
+    let batch = columns.as_record_batch();
+
+    let mut arrays: Vec<ArrayRef> = vec![Arc::new(StringArray::from(ops))];
+    arrays.extend(batch.columns().iter().skip(1).cloned());
+    RecordBatch::try_new(schema.clone(), arrays)
+}
+
+/// mutation sent to the watchers, values are encoded once and decoded by every watcher
+#[derive(Clone)]
+pub(crate) enum Change<K> {
+    Put {
+        key: K,
+        value: Arc<Vec<u8>>,
+        ts: Timestamp,
+    },
+    Delete {
+        key: K,
+        ts: Timestamp,
+    },
+    DeleteRange {
+        lower: Bound<K>,
+        upper: Bound<K>,
+        ts: Timestamp,
+    },
+}
+
+impl<K> Change<K>
+where
+    K: Ord,
+{
+    fn is_in(&self, (lower, upper): &(Bound<K>, Bound<K>)) -> bool {
+        let range = (lower.as_ref(), upper.as_ref());
+        match self {
+            Change::Put { key, .. } | Change::Delete { key, .. } => range.contains(key),
+            Change::DeleteRange {
+                lower: start,
+                upper: end,
+                ..
+            } => !is_before(end.as_ref(), range.0) && !is_before(range.1, start.as_ref()),
+        }
+    }
+
+    pub(crate) async fn into_mutation<R>(self) -> Result<Mutation<R>, DbError<R>>
+    where
+        R: Record<Key = K>,
+    {
+        Ok(match self {
+            Change::Put { key, value, ts } => {
+                let mut value = Arc::unwrap_or_clone(value);
+                Mutation::Put {
+                    key,
+                    value: R::decode(&mut Cursor::new(&mut value))
+                        .await
+                        .map_err(RecoverError::Decode)?,
+                    ts,
+                }
+            }
+            Change::Delete { key, ts } => Mutation::Delete { key, ts },
+            Change::DeleteRange { lower, upper, ts } => Mutation::DeleteRange { lower, upper, ts },
+        })
+    }
+}
+
+/// whether a range ending at `upper` ends before a range starting at `lower`
+fn is_before<K: Ord>(upper: Bound<&K>, lower: Bound<&K>) -> bool {
+    match (upper, lower) {
+        (Bound::Unbounded, _) | (_, Bound::Unbounded) => false,
+        (Bound::Included(upper), Bound::Included(lower)) => upper < lower,
+        (Bound::Included(upper) | Bound::Excluded(upper), Bound::Excluded(lower))
+        | (Bound::Excluded(upper), Bound::Included(lower)) => upper <= lower,
+    }
+}
+
+type Watcher<K> = ((Bound<K>, Bound<K>), Sender<Change<K>>);
+
+/// watchers of the committed mutations, fed by the write path of the mutable memtable
+pub(crate) struct Watchers<R>
+where
+    R: Record,
+{
+    watchers: Mutex<Vec<Watcher<R::Key>>>,
+}
+
+impl<R> Default for Watchers<R>
+where
+    R: Record,
+{
+    fn default() -> Self {
+        Watchers {
+            watchers: Mutex::new(Vec::new()),
+        }
+    }
+}
+
+impl<R> Watchers<R>
+where
+    R: Record,
+{
+    pub(crate) fn register(
+        &self,
+        range: (Bound<R::Key>, Bound<R::Key>),
+    ) -> Receiver<Change<R::Key>> {
+        let (tx, rx) = unbounded();
+        self.watchers.lock().unwrap().push((range, tx));
+        rx
+    }
+
+    /// whether any watcher is interested in the `key`
+    pub(crate) fn is_watched(&self, key: &R::Key) -> bool {
+        self.watchers
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|(range, _)| (range.0.as_ref(), range.1.as_ref()).contains(key))
+    }
+
+    /// the change of writing `record`, `None` if no watcher is interested in it
+    pub(crate) async fn put(
+        &self,
+        record: &R,
+        ts: Timestamp,
+    ) -> Result<Option<Change<R::Key>>, DbError<R>> {
+        let key = record.key().to_key();
+        if !self.is_watched(&key) {
+            return Ok(None);
+        }
+        self.put_ref(key, record.as_record_ref(), ts)
+            .await
+            .map(Some)
+    }
+
+    pub(crate) async fn put_ref(
+        &self,
+        key: R::Key,
+        record: R::Ref<'_>,
+        ts: Timestamp,
+    ) -> Result<Change<R::Key>, DbError<R>> {
+        let mut value = Vec::new();
+        record
+            .encode(&mut Cursor::new(&mut value))
+            .await
+            .map_err(|e| DbError::WalWrite(Box::new(e)))?;
+
+        Ok(Change::Put {
+            key,
+            value: Arc::new(value),
+            ts,
+        })
+    }
+
+    /// the changes of writing the rows of a `batch` holding the internal columns
+    pub(crate) async fn put_record_batch(
+        &self,
+        batch: &RecordBatch,
+    ) -> Result<Vec<Change<R::Key>>, DbError<R>> {
+        let schema = batch.schema();
+        let projection_mask = ProjectionMask::all();
+        let mut changes = Vec::new();
+
+        for offset in 0..batch.num_rows() {
+            let record = R::Ref::from_record_batch(batch, offset, &projection_mask, &schema);
+            let key = record.value();
+            let owned_key = key.value().clone().to_key();
+
+            if let (true, Some(value)) = (self.is_watched(&owned_key), record.get()) {
+                changes.push(self.put_ref(owned_key, value, key.ts()).await?);
+            }
+        }
+        Ok(changes)
+    }
+
+    /// send the `change` to the watchers interested in it, dropping the closed ones
+    pub(crate) fn send(&self, change: Change<R::Key>) {
+        self.watchers
+            .lock()
+            .unwrap()
+            .retain(|(range, tx)| !change.is_in(range) || tx.send(change.clone()).is_ok());
+    }
+}
+
+impl<R> DB<R>
+where
+    R: Record + Send + Sync,
+    R::Columns: Send + Sync,
+{
+    /// subscribe to the mutations committed from now on to keys in the `range`: puts, deletes and
+    /// overlapping range deletions, including the writes of transactions. Data installed by
+    /// [`DB::ingest`] and [`DB::bulk_load`] is not reported. The mutations are buffered until the
+    /// stream is polled, and the subscription ends when the stream is dropped.
+    pub async fn watch(
+        &self,
+        range: (Bound<R::Key>, Bound<R::Key>),
+    ) -> impl Stream<Item = Result<Mutation<R>, DbError<R>>> {
+        let changes = self.schema.read().await.watchers.register(range);
+
+        stream! {
+            while let Ok(change) = changes.recv_async().await {
+                yield change.into_mutation().await;
+            }
+        }
+    }
+
+    /// subscribe to the puts and deletes committed from now on to keys in the `range` as by
+    /// [`DB::watch`], delivered as arrow `RecordBatch`es of up to `batch_size` rows holding the
+    /// columns of `R` after an `_op` column, [`OP_INSERT`] or [`OP_DELETE`], and the
+    /// `_ts` of the commit. The columns other than the primary key hold the defaults of the
+    /// deleted rows. The mutations already committed are batched together, so a slow writer is
+    /// not waited on to fill a batch. Range deletions are not reported, see [`DB::watch`].
+    pub async fn watch_batches(
+        &self,
+        range: (Bound<R::Key>, Bound<R::Key>),
+        batch_size: usize,
+    ) -> impl Stream<Item = Result<RecordBatch, DbError<R>>> {
+        let (changes, arrow_schema) = {
+            let schema = self.schema.read().await;
+            (
+                schema.watchers.register(range),
+                schema.record_instance.arrow_schema::<R>(),
+            )
+        };
+        let schema = change_schema(&arrow_schema);
+        let batch_size = batch_size.max(1);
+
+        stream! {
+            while let Ok(change) = changes.recv_async().await {
+                let mut ready = vec![change];
+                while ready.len() < batch_size {
+                    match changes.try_recv() {
+                        Ok(change) => ready.push(change),
+                        Err(_) => break,
+                    }
+                }
+                let mut mutations = Vec::with_capacity(ready.len());
+                for change in ready {
+                    match change.into_mutation().await {
+                        Ok(mutation) => mutations.push(mutation),
+                        Err(err) => {
+                            yield Err(err);
+                            return;
+                        }
+                    }
+                }
+                match change_batch(&mutations, &arrow_schema, &schema) {
+                    Ok(batch) if batch.num_rows() == 0 => {}
+                    result => yield result.map_err(DbError::from),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod tests {
+    use std::ops::Bound;
+
+    use arrow::{
+        array::{AsArray, StringArray},
+        datatypes::UInt64Type,
+    };
+    use fusio::path::Path;
+    use futures_util::StreamExt;
+    use tempfile::TempDir;
+
+    use super::{Mutation, OP_DELETE, OP_INSERT};
+    use crate::{executor::tokio::TokioExecutor, tests::Test, DbOption, DB};
+
+    fn test(key: &str, vu32: u32) -> Test {
+        Test {
+            vstring: key.to_string(),
+            vu32,
+            vbool: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn watch() {
+        let temp_dir = TempDir::new().unwrap();
+        let option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap());
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+
+        let mut watch = Box::pin(
+            db.watch((
+                Bound::Included("b".to_string()),
+                Bound::Excluded("d".to_string()),
+            ))
+            .await,
+        );
+        db.insert(test("a", 0)).await.unwrap();
+        db.insert(test("b", 1)).await.unwrap();
+        db.remove("c".to_string()).await.unwrap();
+        db.insert(test("d", 3)).await.unwrap();
+        let (b, d, e) = ("b".to_string(), "d".to_string(), "e".to_string());
+        db.delete_range((Bound::Included(&d), Bound::Included(&e)))
+            .await
+            .unwrap();
+        db.delete_range((Bound::Unbounded, Bound::Included(&b)))
+            .await
+            .unwrap();
+
+        let mut txn = db.transaction().await;
+        txn.insert(test("c", 2));
+        txn.commit().await.unwrap();
+
+        let Some(Ok(Mutation::Put { key, value, .. })) = watch.next().await else {
+            panic!("expected put")
+        };
+        assert_eq!(key, "b");
+        assert_eq!(value, test("b", 1));
+        assert!(matches!(
+            watch.next().await,
+            Some(Ok(Mutation::Delete { key, .. })) if key == "c"
+        ));
+        assert!(matches!(
+            watch.next().await,
+            Some(Ok(Mutation::DeleteRange {
+                lower: Bound::Unbounded,
+                ..
+            }))
+        ));
+        let Some(Ok(Mutation::Put { key, value, .. })) = watch.next().await else {
+            panic!("expected put")
+        };
+        assert_eq!(key, "c");
+        assert_eq!(value, test("c", 2));
+    }
+
+    #[tokio::test]
+    async fn watch_batches() {
+        let temp_dir = TempDir::new().unwrap();
+        let option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap());
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+
+        let mut batches = Box::pin(
+            db.watch_batches(
+                (
+                    Bound::Included("b".to_string()),
+                    Bound::Excluded("e".to_string()),
+                ),
+                2,
+            )
+            .await,
+        );
+        db.insert(test("a", 0)).await.unwrap();
+        db.insert(test("b", 1)).await.unwrap();
+        let (b, c) = ("b".to_string(), "c".to_string());
+        db.delete_range((Bound::Included(&b), Bound::Included(&c)))
+            .await
+            .unwrap();
+        db.remove("c".to_string()).await.unwrap();
+        db.insert(test("d", 3)).await.unwrap();
+
+        let batch = batches.next().await.unwrap().unwrap();
+        let fields = batch
+            .schema()
+            .fields()
+            .iter()
+            .map(|field| field.name().clone())
+            .collect::<Vec<_>>();
+        assert_eq!(fields, ["_op", "_ts", "vstring", "vu32", "vbool"]);
+        // the range deletion is left out of the batch
+        assert_eq!(
+            batch.column(0).as_string::<i32>(),
+            &StringArray::from(vec![OP_INSERT])
+        );
+        assert_eq!(
+            batch.column(2).as_string::<i32>(),
+            &StringArray::from(vec!["b"])
+        );
+
+        let batch = batches.next().await.unwrap().unwrap();
+        assert_eq!(
+            batch.column(0).as_string::<i32>(),
+            &StringArray::from(vec![OP_DELETE, OP_INSERT])
+        );
+        assert_eq!(
+            batch.column(2).as_string::<i32>(),
+            &StringArray::from(vec!["c", "d"])
+        );
+        let ts = batch.column(1).as_primitive::<UInt64Type>();
+        assert!(ts.value(0) < ts.value(1));
+    }
+}
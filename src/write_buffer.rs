@@ -0,0 +1,249 @@
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use flume::Sender;
+
+use crate::compaction::CompactTask;
+
+/// A budget of memtable memory shared by the [`DB`](crate::DB)s of a process, each registered
+/// with [`DbOption::write_buffer_manager`](crate::DbOption::write_buffer_manager). Once their
+/// memtables hold more than the budget together, the database holding the most has its mutable
+/// memtable frozen and its immutables flushed, whatever their own thresholds.
+#[derive(Debug)]
+pub struct WriteBufferManager {
+    budget: usize,
+    /// the memory of the members, possibly off for a moment while they update it
+    total: AtomicUsize,
+    next_id: AtomicU64,
+    members: Mutex<BTreeMap<u64, Member>>,
+}
+
+#[derive(Debug)]
+struct Member {
+    usage: Arc<Usage>,
+    compaction_tx: Sender<CompactTask>,
+}
+
+#[derive(Debug, Default)]
+struct Usage {
+    mutable: AtomicUsize,
+    immutables: AtomicUsize,
+    /// a flush was requested and has not completed yet
+    releasing: AtomicBool,
+}
+
+impl Usage {
+    fn memory(&self) -> usize {
+        self.mutable.load(Ordering::Relaxed) + self.immutables.load(Ordering::Relaxed)
+    }
+}
+
+impl WriteBufferManager {
+    /// a `budget` of memtable memory in bytes
+    pub fn new(budget: usize) -> Self {
+        WriteBufferManager {
+            budget,
+            total: AtomicUsize::new(0),
+            next_id: AtomicU64::new(0),
+            members: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn budget(&self) -> usize {
+        self.budget
+    }
+
+    /// the memory held by the memtables of the databases sharing the budget
+    pub fn memory_usage(&self) -> usize {
+        self.members
+            .lock()
+            .unwrap()
+            .values()
+            .map(|member| member.usage.memory())
+            .sum()
+    }
+
+    pub(crate) fn register(self: &Arc<Self>, compaction_tx: Sender<CompactTask>) -> WriteBuffer {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let usage = Arc::new(Usage::default());
+        self.members.lock().unwrap().insert(
+            id,
+            Member {
+                usage: usage.clone(),
+                compaction_tx,
+            },
+        );
+
+        WriteBuffer {
+            id,
+            usage,
+            manager: self.clone(),
+        }
+    }
+
+    fn adjust(&self, old: usize, new: usize) {
+        let total = if new >= old {
+            self.total.fetch_add(new - old, Ordering::Relaxed) + (new - old)
+        } else {
+            self.total
+                .fetch_sub(old - new, Ordering::Relaxed)
+                .wrapping_sub(old - new)
+        };
+        if total > self.budget {
+            self.release();
+        }
+    }
+
+    /// flush the largest member once the exact memory is over the budget, unless it is being
+    /// flushed already
+    fn release(&self) {
+        let members = self.members.lock().unwrap();
+        let memory = members
+            .values()
+            .map(|member| member.usage.memory())
+            .sum::<usize>();
+        if memory <= self.budget {
+            return;
+        }
+        let Some(largest) = members.values().max_by_key(|member| member.usage.memory()) else {
+            return;
+        };
+        if !largest.usage.releasing.load(Ordering::Relaxed)
+            && largest.compaction_tx.try_send(CompactTask::Release).is_ok()
+        {
+            largest.usage.releasing.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+/// The memtable memory of a [`DB`](crate::DB) accounted in a [`WriteBufferManager`], until
+/// dropped.
+#[derive(Debug)]
+pub(crate) struct WriteBuffer {
+    id: u64,
+    usage: Arc<Usage>,
+    manager: Arc<WriteBufferManager>,
+}
+
+impl WriteBuffer {
+    pub(crate) fn set_mutable(&self, bytes: usize) {
+        let old = self.usage.mutable.swap(bytes, Ordering::Relaxed);
+        self.manager.adjust(old, bytes);
+    }
+
+    /// the memory of the immutables, set once a flush or freeze completed
+    pub(crate) fn set_immutables(&self, bytes: usize) {
+        self.usage.releasing.store(false, Ordering::Relaxed);
+        let old = self.usage.immutables.swap(bytes, Ordering::Relaxed);
+        self.manager.adjust(old, bytes);
+    }
+}
+
+impl Drop for WriteBuffer {
+    fn drop(&mut self) {
+        self.manager.members.lock().unwrap().remove(&self.id);
+        self.manager.total.fetch_sub(
+            self.usage.mutable.load(Ordering::Relaxed)
+                + self.usage.immutables.load(Ordering::Relaxed),
+            Ordering::Relaxed,
+        );
+    }
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use flume::bounded;
+    use fusio::path::Path;
+    use tempfile::TempDir;
+
+    use super::WriteBufferManager;
+    use crate::{
+        compaction::CompactTask,
+        executor::tokio::TokioExecutor,
+        tests::{test_items, Test},
+        trigger::TriggerType,
+        DbOption, DB,
+    };
+
+    #[test]
+    fn release_the_largest() {
+        let manager = Arc::new(WriteBufferManager::new(100));
+        let (small_tx, small_rx) = bounded(1);
+        let (large_tx, large_rx) = bounded(1);
+        let small = manager.register(small_tx);
+        let large = manager.register(large_tx);
+
+        small.set_mutable(30);
+        large.set_mutable(60);
+        large.set_immutables(10);
+        assert_eq!(manager.memory_usage(), 100);
+        assert!(large_rx.is_empty());
+
+        small.set_mutable(40);
+        assert!(matches!(large_rx.try_recv(), Ok(CompactTask::Release)));
+        assert!(small_rx.is_empty());
+        // not requested again until the flush completes
+        small.set_mutable(45);
+        assert!(large_rx.is_empty());
+
+        large.set_mutable(0);
+        large.set_immutables(0);
+        assert_eq!(manager.memory_usage(), 45);
+        drop(small);
+        assert_eq!(manager.memory_usage(), 0);
+        assert!(small_rx.is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn write_buffer_manager() {
+        let manager = Arc::new(WriteBufferManager::new(2048));
+        let small_dir = TempDir::new().unwrap();
+        let large_dir = TempDir::new().unwrap();
+        let open = |dir: &TempDir| {
+            let mut option = DbOption::from(Path::from_filesystem_path(dir.path()).unwrap())
+                .write_buffer_manager(manager.clone());
+            // neither flushes by its own thresholds
+            option.trigger_type = TriggerType::Length(usize::MAX);
+            DB::<Test>::new(option, TokioExecutor::new())
+        };
+        let small = open(&small_dir).await.unwrap();
+        let large = open(&large_dir).await.unwrap();
+
+        small.insert(test_items()[0].clone()).await.unwrap();
+        for i in 0..200 {
+            large
+                .insert(Test {
+                    vstring: i.to_string(),
+                    vu32: i,
+                    vbool: Some(true),
+                })
+                .await
+                .unwrap();
+        }
+        let mut flushed = false;
+        for _ in 0..500 {
+            if !large.version_set.current().await.level_slice[0].is_empty() {
+                flushed = true;
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(flushed);
+        assert!(small.version_set.current().await.level_slice[0].is_empty());
+        assert!(small.memory_usage().await.mutable > 0);
+        assert_eq!(
+            large
+                .get(&"199".to_string(), |entry| Some(entry.get().vu32))
+                .await
+                .unwrap(),
+            Some(199)
+        );
+    }
+}
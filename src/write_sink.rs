@@ -0,0 +1,135 @@
+use std::{mem, pin::pin};
+
+use futures_core::Stream;
+use futures_util::StreamExt;
+
+use crate::{record::Record, transaction::CommitError, DB};
+
+/// A handle writing the records sent to it as batches of [`DB::insert_batch`], returned by
+/// [`DB::write_sink`]. Each batch is appended to the WAL under a single lock, and the sender is
+/// held back while a batch is written, or while the writes are stalled.
+///
+/// The records buffered by [`WriteSink::send`] are written by [`WriteSink::flush`], and are lost
+/// if the sink is dropped before.
+pub struct WriteSink<'db, R>
+where
+    R: Record,
+{
+    db: &'db DB<R>,
+    batch_size: usize,
+    buffer: Vec<R>,
+}
+
+impl<'db, R> WriteSink<'db, R>
+where
+    R: Record + Send + Sync,
+    R::Columns: Send + Sync,
+{
+    pub(crate) fn new(db: &'db DB<R>, batch_size: usize) -> Self {
+        let batch_size = batch_size.max(1);
+        WriteSink {
+            db,
+            batch_size,
+            buffer: Vec::with_capacity(batch_size),
+        }
+    }
+
+    /// buffer `record`, writing the buffered records once there are `batch_size` of them
+    pub async fn send(&mut self, record: R) -> Result<(), CommitError<R>> {
+        self.buffer.push(record);
+        if self.buffer.len() >= self.batch_size {
+            self.flush().await?;
+        }
+        Ok(())
+    }
+
+    /// write every record of `records` and the ones buffered before, returning how many records
+    /// the stream yielded. The records ready are written together, up to `batch_size` of them,
+    /// so a slow stream is not waited on to fill a batch.
+    pub async fn send_all(
+        &mut self,
+        records: impl Stream<Item = R>,
+    ) -> Result<usize, CommitError<R>> {
+        self.flush().await?;
+
+        let mut chunks = pin!(records.ready_chunks(self.batch_size));
+        let mut sent = 0;
+        while let Some(chunk) = chunks.next().await {
+            sent += chunk.len();
+            self.db.insert_batch(chunk.into_iter()).await?;
+        }
+        Ok(sent)
+    }
+
+    /// write the records buffered
+    pub async fn flush(&mut self) -> Result<(), CommitError<R>> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        let records = mem::replace(&mut self.buffer, Vec::with_capacity(self.batch_size));
+        self.db.insert_batch(records.into_iter()).await
+    }
+
+    /// records sent and not written yet
+    pub fn buffered(&self) -> usize {
+        self.buffer.len()
+    }
+}
+
+impl<R> DB<R>
+where
+    R: Record + Send + Sync,
+    R::Columns: Send + Sync,
+{
+    /// a [`WriteSink`] writing the records sent to it in batches of `batch_size`, for ingesting
+    /// records from a stream
+    pub fn write_sink(&self, batch_size: usize) -> WriteSink<'_, R> {
+        WriteSink::new(self, batch_size)
+    }
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod tests {
+    use fusio::path::Path;
+    use tempfile::TempDir;
+
+    use crate::{
+        executor::tokio::TokioExecutor,
+        tests::{test_items, Test},
+        DbOption, DB,
+    };
+
+    #[tokio::test]
+    async fn write_sink() {
+        let temp_dir = TempDir::new().unwrap();
+        let option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap());
+        let db: DB<Test> = DB::new(option.clone(), TokioExecutor::new()).await.unwrap();
+
+        let items = test_items();
+        let (sent, streamed) = items.split_at(items.len() / 2);
+        let mut sink = db.write_sink(8);
+        for item in sent.iter().cloned() {
+            sink.send(item).await.unwrap();
+        }
+        assert_eq!(sink.buffered(), sent.len() % 8);
+        assert_eq!(
+            sink.send_all(futures::stream::iter(streamed.to_vec()))
+                .await
+                .unwrap(),
+            streamed.len()
+        );
+        assert_eq!(sink.buffered(), 0);
+        drop(sink);
+        drop(db);
+
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+        for item in &items {
+            assert_eq!(
+                db.get(&item.vstring, |entry| entry.get().vu32)
+                    .await
+                    .unwrap(),
+                Some(item.vu32)
+            );
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use std::error::Error;
+
+use crate::record::{Key, Record};
+
+/// Callback checking every record and deletion before it is written to the WAL and memtable,
+/// see [`DbOption::write_validator`](crate::DbOption::write_validator).
+///
+/// This enforces the invariants of a schema in one place, e.g. non-empty keys or the ranges of
+/// fields, whichever code path writes the records.
+pub trait WriteValidator<R>: Send + Sync
+where
+    R: Record,
+{
+    /// `value` is `None` for deletions. An error rejects the write with
+    /// [`DbError::Rejected`](crate::DbError::Rejected), along with the rest of its batch or
+    /// transaction.
+    fn validate(
+        &self,
+        key: <R::Key as Key>::Ref<'_>,
+        value: Option<&R>,
+    ) -> Result<(), Box<dyn Error + Send + Sync>>;
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod tests {
+    use fusio::path::Path;
+    use tempfile::TempDir;
+
+    use super::WriteValidator;
+    use crate::{
+        executor::tokio::TokioExecutor, tests::Test, transaction::CommitError, DbError, DbOption,
+        DB,
+    };
+
+    #[tokio::test]
+    async fn write_validator() {
+        struct Validator;
+
+        impl WriteValidator<Test> for Validator {
+            fn validate(
+                &self,
+                key: &str,
+                value: Option<&Test>,
+            ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+                if key.is_empty() {
+                    return Err("empty key".into());
+                }
+                if value.is_some_and(|value| value.vu32 > 100) {
+                    return Err(format!("vu32 of {} out of range", key).into());
+                }
+                Ok(())
+            }
+        }
+
+        let temp_dir = TempDir::new().unwrap();
+        let option = DbOption::from(Path::from_filesystem_path(temp_dir.path()).unwrap())
+            .write_validator(Validator);
+        let db: DB<Test> = DB::new(option, TokioExecutor::new()).await.unwrap();
+
+        db.insert(Test {
+            vstring: "a".to_string(),
+            vu32: 1,
+            vbool: None,
+        })
+        .await
+        .unwrap();
+        assert!(matches!(
+            db.insert(Test {
+                vstring: "b".to_string(),
+                vu32: 101,
+                vbool: None,
+            })
+            .await,
+            Err(CommitError::Database(DbError::Rejected(err)))
+                if err.to_string() == "vu32 of b out of range"
+        ));
+        assert!(matches!(
+            db.remove(String::new()).await,
+            Err(CommitError::Database(DbError::Rejected(_)))
+        ));
+
+        let mut txn = db.transaction().await;
+        txn.insert(Test {
+            vstring: "c".to_string(),
+            vu32: 3,
+            vbool: None,
+        });
+        txn.insert(Test {
+            vstring: String::new(),
+            vu32: 0,
+            vbool: None,
+        });
+        assert!(matches!(
+            txn.commit().await,
+            Err(CommitError::Database(DbError::Rejected(_)))
+        ));
+        for (key, vu32) in [("a", Some(1)), ("b", None), ("c", None)] {
+            assert_eq!(
+                db.get(&key.to_string(), |entry| entry.get().vu32)
+                    .await
+                    .unwrap(),
+                vu32
+            );
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use tonbo::serdes::{Decode, Encode};
 use tonbo_macros::Record;
 
 #[derive(Record, Debug, PartialEq)]
@@ -8,11 +9,20 @@ pub struct User {
     name: String,
 }
 
+#[derive(Encode, Decode, Debug, PartialEq)]
+pub struct Point {
+    x: u32,
+    label: Option<String>,
+}
+
+#[derive(Encode, Decode, Debug, PartialEq)]
+pub struct Pair<T>(T, bool);
+
 #[cfg(test)]
 mod tests {
     use std::{io::Cursor, sync::Arc};
 
-    use arrow::array::{BooleanArray, RecordBatch, StringArray, UInt32Array, UInt8Array};
+    use arrow::array::{BooleanArray, RecordBatch, StringArray, UInt64Array, UInt8Array};
     use parquet::{
         arrow::{arrow_to_parquet_schema, ProjectionMask},
         format::SortingColumn,
@@ -21,12 +31,12 @@ mod tests {
     use tokio::io::AsyncSeekExt;
     use tonbo::{
         inmem::immutable::{ArrowArrays, Builder},
-        record::{Record, RecordRef},
+        record::{Record, RecordDecodeError, RecordRef},
         serdes::{Decode, Encode},
         timestamp::timestamped::Timestamped,
     };
 
-    use crate::{User, UserImmutableArrays, UserRef};
+    use crate::{Pair, Point, User, UserImmutableArrays, UserRef};
 
     #[tokio::test]
     async fn test_record_info() {
@@ -115,7 +125,7 @@ mod tests {
                 Arc::new(User::arrow_schema().project(&[0, 1, 2, 3, 4]).unwrap()),
                 vec![
                     Arc::new(BooleanArray::from(vec![false])),
-                    Arc::new(UInt32Array::from(vec![9])),
+                    Arc::new(UInt64Array::from(vec![9])),
                     Arc::new(StringArray::from(vec!["test@example.com"])),
                     Arc::new(UInt8Array::from(vec![9])),
                     Arc::new(StringArray::from(vec!["cat"])),
@@ -149,7 +159,7 @@ mod tests {
                 Arc::new(User::arrow_schema().project(&[0, 1, 3, 4]).unwrap()),
                 vec![
                     Arc::new(BooleanArray::from(vec![false])),
-                    Arc::new(UInt32Array::from(vec![9])),
+                    Arc::new(UInt64Array::from(vec![9])),
                     Arc::new(UInt8Array::from(vec![9])),
                     Arc::new(StringArray::from(vec!["cat"])),
                 ],
@@ -198,6 +208,33 @@ mod tests {
         assert_eq!(original, decoded);
     }
 
+    #[tokio::test]
+    async fn test_derive_encode_and_decode() {
+        let original = Pair(
+            Point {
+                x: 7,
+                label: Some("seven".to_string()),
+            },
+            true,
+        );
+        let mut bytes = Vec::new();
+        let mut cursor = Cursor::new(&mut bytes);
+
+        assert_eq!(original.size(), 4 + original.0.label.size() + 1);
+        original.encode(&mut cursor).await.unwrap();
+
+        cursor.seek(std::io::SeekFrom::Start(0)).await.unwrap();
+        let decoded = Pair::<Point>::decode(&mut cursor).await.unwrap();
+        assert_eq!(original, decoded);
+
+        // the field failing to decode is named by the error
+        let mut cursor = Cursor::new(&bytes[..6]);
+        match Point::decode(&mut cursor).await {
+            Err(RecordDecodeError::Decode { field_name, .. }) => assert_eq!(field_name, "label"),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
     #[tokio::test]
     async fn test_record_arrays() {
         let mut builder = UserImmutableArrays::builder(User::arrow_schema(), 10);
@@ -245,7 +282,7 @@ mod tests {
                 Arc::new(User::arrow_schema().project(&[0, 1, 2, 3, 4]).unwrap(),),
                 vec![
                     Arc::new(BooleanArray::from(vec![false, false, true])),
-                    Arc::new(UInt32Array::from(vec![0, 1, 2])),
+                    Arc::new(UInt64Array::from(vec![0, 1, 2])),
                     Arc::new(StringArray::from(vec![
                         Some("cat@example.com"),
                         Some("dog@example.com"),
@@ -306,7 +343,7 @@ mod tests {
                 Arc::new(User::arrow_schema().project(&[0, 1, 3, 4]).unwrap(),),
                 vec![
                     Arc::new(BooleanArray::from(vec![false, false, true])),
-                    Arc::new(UInt32Array::from(vec![0, 1, 2])),
+                    Arc::new(UInt64Array::from(vec![0, 1, 2])),
                     Arc::new(UInt8Array::from(vec![0, 1, 0])),
                     Arc::new(StringArray::from(vec!["cat", "dog", "human"])),
                 ],
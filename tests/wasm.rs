@@ -80,7 +80,7 @@ mod tests {
             primary_key_index,
         );
 
-        let db: DB<DynRecord, OpfsExecutor> =
+        let db: DB<DynRecord> =
             DB::with_schema(option, OpfsExecutor::new(), cols_desc, primary_key_index)
                 .await
                 .unwrap();
@@ -166,7 +166,7 @@ mod tests {
             primary_key_index,
         );
 
-        let db: DB<DynRecord, OpfsExecutor> =
+        let db: DB<DynRecord> =
             DB::with_schema(option, OpfsExecutor::new(), cols_desc, primary_key_index)
                 .await
                 .unwrap();
@@ -251,7 +251,7 @@ mod tests {
         );
 
         {
-            let db: DB<DynRecord, OpfsExecutor> =
+            let db: DB<DynRecord> =
                 DB::with_schema(option, OpfsExecutor::new(), cols_desc, primary_key_index)
                     .await
                     .unwrap();
@@ -269,7 +269,7 @@ mod tests {
             "id".to_string(),
             primary_key_index,
         );
-        let db: DB<DynRecord, OpfsExecutor> =
+        let db: DB<DynRecord> =
             DB::with_schema(option, OpfsExecutor::new(), cols_desc, primary_key_index)
                 .await
                 .unwrap();
@@ -2,6 +2,7 @@ mod keys;
 mod schema_model;
 
 mod record;
+mod serdes;
 
 pub(crate) mod data_type;
 
@@ -40,6 +41,43 @@ pub fn tonbo_record(input: TokenStream) -> TokenStream {
     }
 }
 
+/// implements `tonbo::serdes::Encode` for a struct by encoding its fields one after another,
+/// the error of a field is wrapped along with its name in `RecordEncodeError::Encode`
+///
+/// # Example
+///
+/// ```no_rust
+/// use tonbo::serdes::{Decode, Encode};
+///
+/// #[derive(Encode, Decode)]
+/// pub struct Point {
+///     pub x: u32,
+///     pub label: Option<String>,
+/// }
+/// ```
+#[proc_macro_derive(Encode)]
+pub fn encode(input: TokenStream) -> TokenStream {
+    let ast = parse_macro_input!(input as DeriveInput);
+
+    match serdes::handle_encode(ast) {
+        Ok(codegen) => codegen.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+/// implements `tonbo::serdes::Decode` for a struct by decoding its fields in the order
+/// `Encode` writes them, the error of a field is wrapped along with its name in
+/// `RecordDecodeError::Decode`
+#[proc_macro_derive(Decode)]
+pub fn decode(input: TokenStream) -> TokenStream {
+    let ast = parse_macro_input!(input as DeriveInput);
+
+    match serdes::handle_decode(ast) {
+        Ok(codegen) => codegen.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
 #[proc_macro_derive(KeyAttributes, attributes(primary_key))]
 pub fn key_attributes(_input: TokenStream) -> TokenStream {
     let gen = quote::quote! {};
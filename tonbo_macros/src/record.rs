@@ -1,7 +1,7 @@
 #![allow(clippy::too_many_arguments)]
 use darling::{ast::Data, util::Ignored, FromDeriveInput, FromField};
 use proc_macro2::{Ident, TokenStream};
-use quote::quote;
+use quote::{format_ident, quote};
 use syn::{DeriveInput, Error, GenericArgument, Type};
 
 use crate::{keys::PrimaryKey, utils::ident_generator::IdentGenerator, DataType};
@@ -251,7 +251,7 @@ fn trait_record_codegen(
                 static SCHEMA: ::tonbo::once_cell::sync::Lazy<::std::sync::Arc<::tonbo::arrow::datatypes::Schema>> = ::tonbo::once_cell::sync::Lazy::new(|| {
                     ::std::sync::Arc::new(::tonbo::arrow::datatypes::Schema::new(vec![
                         ::tonbo::arrow::datatypes::Field::new("_null", ::tonbo::arrow::datatypes::DataType::Boolean, false),
-                        ::tonbo::arrow::datatypes::Field::new("_ts", ::tonbo::arrow::datatypes::DataType::UInt32, false),
+                        ::tonbo::arrow::datatypes::Field::new("_ts", ::tonbo::arrow::datatypes::DataType::UInt64, false),
                         #(#schema_fields)*
                     ]))
                 });
@@ -268,41 +268,53 @@ fn trait_record_codegen(
 }
 
 fn trait_decode_codegen(struct_name: &Ident, fields: &[RecordStructFieldOpt]) -> TokenStream {
-    let mut decode_method_fields: Vec<TokenStream> = Vec::new();
     let mut field_names: Vec<TokenStream> = Vec::new();
 
     for field in fields.iter() {
         let field_name = field.ident.as_ref().unwrap();
 
-        let (data_type, is_nullable) = field.to_data_type().expect("unreachable code");
+        field_names.push(quote!(#field_name,));
+    }
+    // `call` of the decoding method on every field
+    let decode_method_fields = |call: TokenStream| -> Vec<TokenStream> {
+        let mut decode_method_fields = Vec::new();
 
-        let field_ty = data_type.to_field_ty();
+        for field in fields.iter() {
+            let field_name = field.ident.as_ref().unwrap();
 
-        field_names.push(quote!(#field_name,));
+            let (data_type, is_nullable) = field.to_data_type().expect("unreachable code");
 
-        if field.primary_key.unwrap_or_default() {
-            decode_method_fields.push(quote! {
-                            let #field_name = #field_ty::decode(reader).await.map_err(|err| ::tonbo::record::RecordDecodeError::Decode {
-                                field_name: stringify!(#field_name).to_string(),
-                                error: Box::new(err),
-                            })?;
-                        });
-        } else if is_nullable {
-            decode_method_fields.push(quote! {
-                                let #field_name = Option::<#field_ty>::decode(reader).await.map_err(|err| ::tonbo::record::RecordDecodeError::Decode {
+            let field_ty = data_type.to_field_ty();
+
+            if field.primary_key.unwrap_or_default() {
+                decode_method_fields.push(quote! {
+                                let #field_name = #field_ty::#call.await.map_err(|err| ::tonbo::record::RecordDecodeError::Decode {
                                     field_name: stringify!(#field_name).to_string(),
                                     error: Box::new(err),
                                 })?;
                             });
-        } else {
-            decode_method_fields.push(quote! {
-                                let #field_name = Option::<#field_ty>::decode(reader).await.map_err(|err| ::tonbo::record::RecordDecodeError::Decode {
-                                    field_name: stringify!(#field_name).to_string(),
-                                    error: Box::new(err),
-                                })?.unwrap();
-                            });
+            } else if is_nullable {
+                decode_method_fields.push(quote! {
+                                    let #field_name = Option::<#field_ty>::#call.await.map_err(|err| ::tonbo::record::RecordDecodeError::Decode {
+                                        field_name: stringify!(#field_name).to_string(),
+                                        error: Box::new(err),
+                                    })?;
+                                });
+            } else {
+                decode_method_fields.push(quote! {
+                                    let #field_name = Option::<#field_ty>::#call.await.map_err(|err| ::tonbo::record::RecordDecodeError::Decode {
+                                        field_name: stringify!(#field_name).to_string(),
+                                        error: Box::new(err),
+                                    })?.unwrap();
+                                });
+            }
         }
-    }
+        decode_method_fields
+    };
+    let decode_fields = decode_method_fields(quote!(decode(reader)));
+    let decode_compact_fields = decode_method_fields(quote!(decode_compact(reader)));
+    let decode_buffered_fields = decode_method_fields(quote!(decode_buffered(reader, compact)));
+
     quote! {
 
         impl ::tonbo::serdes::Decode for #struct_name {
@@ -312,7 +324,29 @@ fn trait_decode_codegen(struct_name: &Ident, fields: &[RecordStructFieldOpt]) ->
             where
                 R: ::fusio::SeqRead,
             {
-                #(#decode_method_fields)*
+                #(#decode_fields)*
+
+                Ok(Self {
+                    #(#field_names)*
+                })
+            }
+
+            async fn decode_compact<R>(reader: &mut R) -> Result<Self, Self::Error>
+            where
+                R: ::fusio::SeqRead,
+            {
+                #(#decode_compact_fields)*
+
+                Ok(Self {
+                    #(#field_names)*
+                })
+            }
+
+            async fn decode_buffered(
+                reader: &mut ::tonbo::serdes::BytesReader,
+                compact: bool,
+            ) -> Result<Self, Self::Error> {
+                #(#decode_buffered_fields)*
 
                 Ok(Self {
                     #(#field_names)*
@@ -458,7 +492,7 @@ fn trait_decode_ref_codegen(
 
                 let ts = record_batch
                     .column(1)
-                    .as_primitive::<::tonbo::arrow::datatypes::UInt32Type>()
+                    .as_primitive::<::tonbo::arrow::datatypes::UInt64Type>()
                     .value(offset)
                     .into();
 
@@ -475,17 +509,23 @@ fn trait_decode_ref_codegen(
 
 fn trait_encode_codegen(struct_name: &Ident, fields: &[RecordStructFieldOpt]) -> TokenStream {
     let mut encode_method_fields: Vec<TokenStream> = Vec::new();
+    let mut encode_compact_fields: Vec<TokenStream> = Vec::new();
     let mut encode_size_fields: Vec<TokenStream> = Vec::new();
 
     for field in fields.iter() {
         let field_name = field.ident.as_ref().unwrap();
 
-        encode_method_fields.push(quote! {
-                    ::tonbo::serdes::Encode::encode(&self.#field_name, writer).await.map_err(|err| ::tonbo::record::RecordEncodeError::Encode {
-                        field_name: stringify!(#field_name).to_string(),
-                        error: Box::new(err),
-                    })?;
-                });
+        for (method, method_fields) in [
+            (format_ident!("encode"), &mut encode_method_fields),
+            (format_ident!("encode_compact"), &mut encode_compact_fields),
+        ] {
+            method_fields.push(quote! {
+                        ::tonbo::serdes::Encode::#method(&self.#field_name, writer).await.map_err(|err| ::tonbo::record::RecordEncodeError::Encode {
+                            field_name: stringify!(#field_name).to_string(),
+                            error: Box::new(err),
+                        })?;
+                    });
+        }
         encode_size_fields.push(quote! {
             + self.#field_name.size()
         });
@@ -506,6 +546,15 @@ fn trait_encode_codegen(struct_name: &Ident, fields: &[RecordStructFieldOpt]) ->
                 Ok(())
             }
 
+            async fn encode_compact<W>(&self, writer: &mut W) -> Result<(), Self::Error>
+            where
+                W: ::fusio::Write,
+            {
+                #(#encode_compact_fields)*
+
+                Ok(())
+            }
+
             fn size(&self) -> usize {
                 0 #(#encode_size_fields)*
             }
@@ -532,7 +581,7 @@ fn struct_array_codegen(struct_name: &Ident, fields: &[RecordStructFieldOpt]) ->
         #[derive(Debug)]
         pub struct #struct_arrays_name {
             _null: ::std::sync::Arc<::tonbo::arrow::array::BooleanArray>,
-            _ts: ::std::sync::Arc<::tonbo::arrow::array::UInt32Array>,
+            _ts: ::std::sync::Arc<::tonbo::arrow::array::UInt64Array>,
 
             #(#arrays_init_fields)*
 
@@ -604,7 +653,7 @@ fn trait_arrow_array_codegen(
                     #(#builder_init_fields)*
 
                     _null: ::tonbo::arrow::array::BooleanBufferBuilder::new(capacity),
-                    _ts: ::tonbo::arrow::array::UInt32Builder::with_capacity(capacity),
+                    _ts: ::tonbo::arrow::array::UInt64Builder::with_capacity(capacity),
                 }
             }
 
@@ -719,7 +768,7 @@ fn struct_builder_codegen(
             #(#builder_fields)*
 
             _null: ::tonbo::arrow::array::BooleanBufferBuilder,
-            _ts: ::tonbo::arrow::array::UInt32Builder,
+            _ts: ::tonbo::arrow::array::UInt64Builder,
         }
 
         impl ::tonbo::inmem::immutable::Builder<#struct_arrays_name> for #struct_builder_name {
@@ -0,0 +1,177 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{parse_quote, Data, DeriveInput, Error, Fields, Generics, Ident, Index, Member, Type};
+
+/// the members of the fields of the struct, along with their names in the errors and their types
+fn struct_fields(ast: &DeriveInput, derive: &str) -> Result<Vec<(Member, String, Type)>, Error> {
+    let Data::Struct(data_struct) = &ast.data else {
+        return Err(Error::new_spanned(
+            ast,
+            format!("{derive} can only be derived for structs"),
+        ));
+    };
+    Ok(match &data_struct.fields {
+        Fields::Named(fields) => fields
+            .named
+            .iter()
+            .map(|field| {
+                let ident = field.ident.clone().unwrap();
+                let name = ident.to_string();
+                (Member::Named(ident), name, field.ty.clone())
+            })
+            .collect(),
+        Fields::Unnamed(fields) => fields
+            .unnamed
+            .iter()
+            .enumerate()
+            .map(|(index, field)| {
+                (
+                    Member::Unnamed(Index::from(index)),
+                    index.to_string(),
+                    field.ty.clone(),
+                )
+            })
+            .collect(),
+        Fields::Unit => Vec::new(),
+    })
+}
+
+/// `generics` with every field type bound by `bound`
+fn bounded_generics(
+    generics: &Generics,
+    fields: &[(Member, String, Type)],
+    bound: TokenStream,
+) -> Generics {
+    let mut generics = generics.clone();
+    if !generics.params.is_empty() {
+        let where_clause = generics.make_where_clause();
+        for (_, _, ty) in fields {
+            where_clause.predicates.push(parse_quote!(#ty: #bound));
+        }
+    }
+    generics
+}
+
+pub(crate) fn handle_encode(ast: DeriveInput) -> Result<TokenStream, Error> {
+    let fields = struct_fields(&ast, "Encode")?;
+    let struct_name = &ast.ident;
+    let generics = bounded_generics(
+        &ast.generics,
+        &fields,
+        quote!(::tonbo::serdes::Encode + Sync),
+    );
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let encode_fields = |method: Ident| {
+        fields.iter().map(move |(member, name, _)| {
+            quote! {
+                ::tonbo::serdes::Encode::#method(&self.#member, writer).await.map_err(|err| ::tonbo::record::RecordEncodeError::Encode {
+                    field_name: #name.to_string(),
+                    error: Box::new(err),
+                })?;
+            }
+        })
+    };
+    let encode_compact_fields = encode_fields(format_ident!("encode_compact"));
+    let encode_fields = encode_fields(format_ident!("encode"));
+    let size_fields = fields.iter().map(|(member, _, _)| {
+        quote! {
+            + ::tonbo::serdes::Encode::size(&self.#member)
+        }
+    });
+
+    Ok(quote! {
+        impl #impl_generics ::tonbo::serdes::Encode for #struct_name #ty_generics #where_clause {
+            type Error = ::tonbo::record::RecordEncodeError;
+
+            async fn encode<W>(&self, writer: &mut W) -> Result<(), Self::Error>
+            where
+                W: ::fusio::Write,
+            {
+                #(#encode_fields)*
+
+                Ok(())
+            }
+
+            async fn encode_compact<W>(&self, writer: &mut W) -> Result<(), Self::Error>
+            where
+                W: ::fusio::Write,
+            {
+                #(#encode_compact_fields)*
+
+                Ok(())
+            }
+
+            fn size(&self) -> usize {
+                0 #(#size_fields)*
+            }
+        }
+    })
+}
+
+pub(crate) fn handle_decode(ast: DeriveInput) -> Result<TokenStream, Error> {
+    let fields = struct_fields(&ast, "Decode")?;
+    let struct_name = &ast.ident;
+    let generics = bounded_generics(&ast.generics, &fields, quote!(::tonbo::serdes::Decode));
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let values: Vec<_> = (0..fields.len())
+        .map(|index| format_ident!("field_{}", index))
+        .collect();
+    let decode_fields = |call: TokenStream| {
+        fields
+            .iter()
+            .zip(values.iter())
+            .map(move |((_, name, ty), value)| {
+                quote! {
+                    let #value = <#ty as ::tonbo::serdes::Decode>::#call.await.map_err(|err| ::tonbo::record::RecordDecodeError::Decode {
+                        field_name: #name.to_string(),
+                        error: Box::new(err),
+                    })?;
+                }
+            })
+    };
+    let decode_compact_fields = decode_fields(quote!(decode_compact(reader)));
+    let decode_buffered_fields = decode_fields(quote!(decode_buffered(reader, compact)));
+    let decode_fields = decode_fields(quote!(decode(reader)));
+    let members: Vec<_> = fields.iter().map(|(member, _, _)| member).collect();
+
+    Ok(quote! {
+        impl #impl_generics ::tonbo::serdes::Decode for #struct_name #ty_generics #where_clause {
+            type Error = ::tonbo::record::RecordDecodeError;
+
+            async fn decode<R>(reader: &mut R) -> Result<Self, Self::Error>
+            where
+                R: ::fusio::SeqRead,
+            {
+                #(#decode_fields)*
+
+                Ok(Self {
+                    #(#members: #values,)*
+                })
+            }
+
+            async fn decode_compact<R>(reader: &mut R) -> Result<Self, Self::Error>
+            where
+                R: ::fusio::SeqRead,
+            {
+                #(#decode_compact_fields)*
+
+                Ok(Self {
+                    #(#members: #values,)*
+                })
+            }
+
+            async fn decode_buffered(
+                reader: &mut ::tonbo::serdes::BytesReader,
+                compact: bool,
+            ) -> Result<Self, Self::Error> {
+                #(#decode_buffered_fields)*
+
+                Ok(Self {
+                    #(#members: #values,)*
+                })
+            }
+        }
+    })
+}